@@ -0,0 +1,92 @@
+//! The compiler with the LLVM back end
+
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use back_end::{link, link_wasm, CodeGen, Platform};
+use compiler::{Backend, Command, Emit, Options};
+use front_end::source_manager::SourceManager;
+use inkwell::context::Context;
+use inkwell::OptimizationLevel;
+use middle_end::mir::MirProgram;
+
+/// Code generation with LLVM
+struct Llvm;
+
+impl Backend for Llvm {
+    fn check_target(&self, target: &str) -> Result<(), String> {
+        target.parse::<Platform>().map(|_| ())
+    }
+
+    /// Emit, build or run the program with LLVM. The executable is written
+    /// next to the first source file unless -o says where; for wasm32 it is
+    /// a .wasm module.
+    fn generate(&self, options: &Options, source_manager: &SourceManager, mir_program: &MirProgram) {
+        let filename = &options.inputs[0];
+        let source = Path::new(filename);
+        // Parsing the arguments checked the target
+        let platform = options.target.as_deref().map_or(Ok(Platform::Native), str::parse)
+            .unwrap_or_else(|e| options.fail(e));
+
+        let context = Context::create();
+        let module_name = source.file_stem().and_then(|stem| stem.to_str()).unwrap_or("main");
+        let mut codegen = CodeGen::new(&context, module_name).with_platform(platform);
+        if options.debug_info {
+            let file_names: Vec<String> = source_manager.file_ids()
+                .map(|id| source_manager.file_name(id).unwrap_or_default().to_string())
+                .collect();
+            codegen = codegen.with_debug_info(&file_names);
+        }
+        // Natively, behaviors run on the runtime's threads
+        if platform == Platform::Native && mir_program.functions.values().any(|func| func.is_behavior) {
+            codegen = codegen.with_threads();
+        }
+        let opt_level = match options.backend_opt_level {
+            0 => OptimizationLevel::None,
+            1 => OptimizationLevel::Less,
+            2 => OptimizationLevel::Default,
+            _ => OptimizationLevel::Aggressive,
+        };
+        codegen.compile(mir_program)
+            .and_then(|_| codegen.verify())
+            .and_then(|_| codegen.optimize(opt_level))
+            .unwrap_or_else(|e| options.fail(e));
+
+        if options.emits(Emit::LlvmIr) {
+            options.write(Emit::LlvmIr, filename, codegen.ir());
+        }
+        if options.emits(Emit::LlvmBc) {
+            // Parsing the arguments made sure bitcode has a directory to go to
+            if let Some(path) = options.out_path(Emit::LlvmBc, filename) {
+                codegen.write_bitcode(&path).unwrap_or_else(|e| options.fail(e));
+            }
+        }
+
+        match options.command {
+            Command::Build => {
+                let executable = options.output.clone().unwrap_or_else(|| match (source.extension(), platform) {
+                    (Some(_), _) | (None, Platform::Wasm32) => source.with_extension(platform.executable_extension()),
+                    (None, Platform::Native) => source.with_extension("out"),
+                });
+                let object = executable.with_extension("o");
+                codegen.emit_object(&object).unwrap_or_else(|e| options.fail(e));
+                let linked = match platform {
+                    Platform::Native => link(&[&object], &executable),
+                    Platform::Wasm32 => link_wasm(&[&object], &executable),
+                };
+                let _ = fs::remove_file(&object);
+                linked.unwrap_or_else(|e| options.fail(e));
+            },
+            Command::Run => {
+                let status = codegen.run_jit().unwrap_or_else(|e| options.fail(e));
+                process::exit(status);
+            },
+            Command::Check | Command::Emit => {},
+        }
+    }
+}
+
+fn main() {
+    compiler::main(Some(&Llvm));
+}
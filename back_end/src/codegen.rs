@@ -0,0 +1,1283 @@
+//! LLVM code generation from MIR
+//!
+//! `CodeGen` lowers a `MirProgram` to an LLVM module, which it can
+//! optimize, write as IR, bitcode or an object file, or run with LLVM's
+//! JIT. Values have the LLVM types of their MIR types, and arithmetic
+//! wraps and traps where the interpreter does.
+
+use crate::debug_info::DebugInfo;
+use crate::mangle;
+use crate::platform::{Platform, WASM_IMPORT_MODULE};
+use crate::memory_order;
+use crate::runtime;
+use c_back_end::{ownership, symbol};
+use front_end::types::{NumericKind, Type};
+use inkwell::attributes::AttributeLoc;
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::{Builder, BuilderError};
+use inkwell::context::Context;
+use inkwell::intrinsics::Intrinsic as LlvmIntrinsic;
+use inkwell::module::{Linkage, Module};
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, IntType, StructType};
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FloatValue, FunctionValue, InstructionValue, IntValue, PhiValue, PointerValue,
+};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel};
+use middle_end::mir::types::{
+    BinaryOperation, BlockId, Constant, Instruction, Intrinsic, MirFunction, MirProgram, Operand, Terminator, VarId,
+    INIT_FUNCTION,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+pub use c_back_end::CodegenError;
+
+/// Turns what an LLVM builder call failed with into a `CodegenError`
+trait Built<T> {
+    fn built(self) -> Result<T, CodegenError>;
+}
+
+impl<T> Built<T> for Result<T, BuilderError> {
+    fn built(self) -> Result<T, CodegenError> {
+        self.map_err(|e| CodegenError::Builder(e.to_string()))
+    }
+}
+
+/// Where a variable is stored, and the type of what is stored there
+#[derive(Debug, Clone, Copy)]
+struct Slot<'ctx> {
+    pointer: PointerValue<'ctx>,
+    typ: BasicTypeEnum<'ctx>,
+}
+
+/// Lowers MIR into an LLVM module
+///
+/// Every variable of a function gets a stack slot, an `alloca` in its
+/// entry block; reading a variable loads from its slot and assigning it
+/// stores there. LLVM's `mem2reg` turns the slots back into registers, so
+/// lowering does not need SSA form. Globals become module globals starting
+/// out as zero.
+pub struct CodeGen<'ctx> {
+    context: &'ctx Context,
+    module: Module<'ctx>,
+    builder: Builder<'ctx>,
+
+    /// The module global of each MIR global
+    globals: HashMap<VarId, Slot<'ctx>>,
+
+    /// The slot of each variable of the function being compiled
+    variables: HashMap<VarId, Slot<'ctx>>,
+
+    /// The LLVM block of each block of the function being compiled
+    blocks: HashMap<BlockId, BasicBlock<'ctx>>,
+
+    /// The LLVM phi of each MIR phi, by its block and target
+    phis: HashMap<(BlockId, VarId), PhiValue<'ctx>>,
+
+    /// The function being compiled
+    function: Option<FunctionValue<'ctx>>,
+
+    /// How hard LLVM optimizes, set by `optimize`
+    optimization: OptimizationLevel,
+
+    /// The debug information being generated, if it is wanted
+    debug: Option<DebugInfo<'ctx>>,
+
+    /// What the code is generated for
+    platform: Platform,
+
+    /// Whether behaviors may run on several threads at once
+    threads: bool,
+
+    /// The variable each `peak` target of the function being compiled
+    /// may share storage with, following peaks of peaks to the end
+    aliases: HashMap<VarId, VarId>,
+
+    /// The view of each `peak` target of the function being compiled: a
+    /// slot holding a pointer to the storage it is accessed through
+    views: HashMap<VarId, Slot<'ctx>>,
+
+    /// The variables of the function being compiled holding string copies
+    /// it frees, as `ownership` decides
+    owned: Vec<VarId>,
+
+    /// How many atomic blocks the instruction being compiled is inside
+    atomic_depth: usize,
+
+    /// The global holding the mailbox of each behavior, by name, when
+    /// behaviors run on threads
+    mailboxes: BTreeMap<String, Slot<'ctx>>,
+}
+
+impl<'ctx> CodeGen<'ctx> {
+    pub fn new(context: &'ctx Context, module_name: &str) -> Self {
+        CodeGen {
+            context,
+            module: context.create_module(module_name),
+            builder: context.create_builder(),
+            globals: HashMap::new(),
+            variables: HashMap::new(),
+            blocks: HashMap::new(),
+            phis: HashMap::new(),
+            function: None,
+            optimization: OptimizationLevel::None,
+            debug: None,
+            platform: Platform::Native,
+            threads: false,
+            aliases: HashMap::new(),
+            views: HashMap::new(),
+            owned: Vec::new(),
+            atomic_depth: 0,
+            mailboxes: BTreeMap::new(),
+        }
+    }
+
+    /// Generate code for behaviors running on several threads at once,
+    /// sent to the runtime's scheduler, with atomic accesses to the
+    /// variables they share, as `memory_order` explains, and atomic blocks
+    /// holding the runtime's lock. Without threads a behavior is called
+    /// like any function.
+    pub fn with_threads(mut self) -> Self {
+        self.threads = true;
+        self
+    }
+
+    /// Generate code for `platform` instead of the host, as `platform`
+    /// explains
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Describe the program for debuggers with DWARF, as `debug_info`
+    /// explains, its source files named by file id in `files`
+    pub fn with_debug_info(mut self, files: &[String]) -> Self {
+        self.debug = Some(DebugInfo::new(self.context, &self.module, files));
+        self
+    }
+
+    /// The module generated so far
+    pub fn module(&self) -> &Module<'ctx> {
+        &self.module
+    }
+
+    /// The name of the LLVM function for the MIR function `name`: the
+    /// symbol `mangle` gives it in this module, or `custod_main` and
+    /// `custod_init` for the program's `main` and top-level statements
+    pub fn symbol(&self, name: &str) -> String {
+        match name {
+            "main" | INIT_FUNCTION => symbol(name).to_string(),
+            name => mangle::mangle(&self.module.get_name().to_string_lossy(), name),
+        }
+    }
+
+    /// The name of the function running messages to the behavior `name`
+    fn message_handler(&self, name: &str) -> String {
+        format!("{}.message", self.symbol(name))
+    }
+
+    /// Check the module with LLVM's verifier
+    pub fn verify(&self) -> Result<(), CodegenError> {
+        self.module.verify().map_err(|message| CodegenError::Verification(message.to_string()))
+    }
+
+    /// Lower every function of `program` into the module. Every function
+    /// is declared before any is lowered, so calls may refer to functions
+    /// defined later or to themselves.
+    pub fn compile(&mut self, program: &MirProgram) -> Result<(), CodegenError> {
+        self.declare_globals(program)?;
+        let mut functions: Vec<&MirFunction> = program.functions.values().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        for func in &functions {
+            self.declare_function(func)?;
+        }
+        if self.threads {
+            for func in functions.iter().filter(|func| func.is_behavior) {
+                self.compile_message_handler(func)?;
+            }
+        }
+        for func in functions {
+            self.compile_function(program, func)?;
+        }
+        if program.functions.contains_key("main") || program.functions.contains_key(INIT_FUNCTION) {
+            self.compile_entry_point(program)?;
+        }
+        if let Some(debug) = &self.debug {
+            debug.finalize();
+        }
+        Ok(())
+    }
+
+    /// Run LLVM's optimization pipeline for `level` over the module, which
+    /// promotes the slots to registers and combines and numbers values
+    /// from `-O1` up, and make object files use the same level
+    pub fn optimize(&mut self, level: OptimizationLevel) -> Result<(), CodegenError> {
+        self.optimization = level;
+        let pipeline = match level {
+            OptimizationLevel::None => return Ok(()),
+            OptimizationLevel::Less => "default<O1>",
+            OptimizationLevel::Default => "default<O2>",
+            OptimizationLevel::Aggressive => "default<O3>",
+        };
+        let machine = self.target_machine()?;
+        self.module.run_passes(pipeline, &machine, PassBuilderOptions::create())
+            .map_err(|message| CodegenError::Optimization(message.to_string()))
+    }
+
+    /// The module as LLVM's textual IR
+    pub fn ir(&self) -> String {
+        self.module.print_to_string().to_string()
+    }
+
+    /// Write the module as textual IR, for reading or for `llc` and `opt`
+    pub fn write_ir(&self, path: &Path) -> Result<(), CodegenError> {
+        self.module.print_to_file(path).map_err(|message| CodegenError::Emit(message.to_string()))
+    }
+
+    /// Write the module as LLVM bitcode
+    pub fn write_bitcode(&self, path: &Path) -> Result<(), CodegenError> {
+        match self.module.write_bitcode_to_path(path) {
+            true => Ok(()),
+            false => Err(CodegenError::Emit(format!("cannot write bitcode to {}", path.display()))),
+        }
+    }
+
+    /// Compile the module with LLVM's JIT at the level `optimize` set and
+    /// run the program's `main` in this process, returning its exit status
+    pub fn run_jit(&self) -> Result<i32, CodegenError> {
+        if self.platform != Platform::Native {
+            return Err(CodegenError::Jit(format!("code for {} cannot run in this process", self.platform)));
+        }
+        Target::initialize_native(&InitializationConfig::default()).map_err(CodegenError::Jit)?;
+        let engine = self.module.create_jit_execution_engine(self.optimization)
+            .map_err(|message| CodegenError::Jit(message.to_string()))?;
+        for (name, address) in runtime::functions() {
+            if let Some(function) = self.module.get_function(name) {
+                engine.add_global_mapping(&function, address);
+            }
+        }
+        // The C `main` compile_entry_point defines takes nothing and
+        // returns an `i32`
+        unsafe {
+            let main = engine.get_function::<unsafe extern "C" fn() -> i32>("main")
+                .map_err(|error| CodegenError::Jit(error.to_string()))?;
+            Ok(main.call())
+        }
+    }
+
+    /// Write the module as an object file for its platform
+    pub fn emit_object(&self, path: &Path) -> Result<(), CodegenError> {
+        let machine = self.target_machine()?;
+        machine.write_to_file(&self.module, FileType::Object, path)
+            .map_err(|message| CodegenError::Emit(message.to_string()))
+    }
+
+    /// A machine for the platform, with the module set up for its target
+    fn target_machine(&self) -> Result<TargetMachine, CodegenError> {
+        self.platform.initialize().map_err(CodegenError::Emit)?;
+        let triple = self.platform.triple();
+        let target = Target::from_triple(&triple).map_err(|message| CodegenError::Emit(message.to_string()))?;
+        let (cpu, features) = self.platform.cpu();
+        let machine = target
+            .create_target_machine(
+                &triple,
+                &cpu,
+                &features,
+                self.optimization,
+                RelocMode::PIC,
+                CodeModel::Default,
+            )
+            .ok_or_else(|| CodegenError::Emit(format!("no target machine for {}", triple)))?;
+        self.module.set_triple(&triple);
+        self.module.set_data_layout(&machine.get_target_data().get_data_layout());
+        Ok(machine)
+    }
+
+    /// The C `main`, which runs the program's top-level statements, then
+    /// calls its `main` and returns its result as the exit status, or zero
+    /// if it has none. Behaviors on threads get their mailboxes and
+    /// workers first, and it waits for them to finish. Natively it flushes
+    /// C's output buffers last, so what the program printed comes before
+    /// anything its host prints after a JIT run.
+    fn compile_entry_point(&mut self, program: &MirProgram) -> Result<(), CodegenError> {
+        let main = program.functions.get("main");
+        if let Some(main) = main.filter(|main| !main.parameters.is_empty()) {
+            return Err(unsupported(main, "a main that takes parameters"));
+        }
+        let i32_type = self.context.i32_type();
+        let function = self.module.add_function("main", i32_type.fn_type(&[], false), None);
+        self.builder.position_at_end(self.context.append_basic_block(function, "entry"));
+        self.builder.unset_current_debug_location();
+        if !self.mailboxes.is_empty() {
+            let size_type = self.size_type();
+            let start = self.runtime_function("custod_rt_scheduler_start", self.context.void_type().fn_type(&[size_type.into()], false));
+            self.builder.build_call(start, &[size_type.const_zero().into()], "").built()?;
+            let text = self.context.i8_type().ptr_type(AddressSpace::default());
+            let new = self.runtime_function("custod_rt_mailbox_new", text.fn_type(&[], false));
+            for mailbox in self.mailboxes.values() {
+                let created = self.builder.build_call(new, &[], "").built()?.try_as_basic_value().left()
+                    .ok_or_else(|| CodegenError::Builder("custod_rt_mailbox_new returns nothing".to_string()))?;
+                self.builder.build_store(mailbox.pointer, created).built()?;
+            }
+        }
+        let mut result = None;
+        for name in [INIT_FUNCTION, "main"].into_iter().filter(|name| program.functions.contains_key(*name)) {
+            let callee = self.module.get_function(&self.symbol(name))
+                .ok_or_else(|| CodegenError::UnknownFunction(name.to_string()))?;
+            result = self.builder.build_call(callee, &[], "").built()?.try_as_basic_value().left();
+        }
+        if !self.mailboxes.is_empty() {
+            self.call_runtime("custod_rt_scheduler_wait")?;
+        }
+        let status = match result {
+            Some(BasicValueEnum::IntValue(value)) => self.builder.build_int_cast(value, i32_type, "").built()?,
+            _ => i32_type.const_zero(),
+        };
+        if self.platform == Platform::Native {
+            let text = self.context.i8_type().ptr_type(AddressSpace::default());
+            let fflush = self.module.get_function("fflush").unwrap_or_else(|| {
+                self.module.add_function("fflush", i32_type.fn_type(&[text.into()], false), Some(Linkage::External))
+            });
+            self.builder.build_call(fflush, &[text.const_null().into()], "").built()?;
+        }
+        self.builder.build_return(Some(&status)).built()?;
+        Ok(())
+    }
+
+    /// Add `func` to the module with the LLVM types of its parameters and
+    /// result, to be defined by `compile_function`. A function without a
+    /// result returns `void`.
+    fn declare_function(&mut self, func: &MirFunction) -> Result<FunctionValue<'ctx>, CodegenError> {
+        let parameters = func.parameters.iter()
+            .map(|(_, typ)| self.llvm_type(typ).map(Into::into)
+                .ok_or_else(|| unsupported(func, &format!("parameters of type {:?}", typ))))
+            .collect::<Result<Vec<BasicMetadataTypeEnum>, _>>()?;
+        let fn_type = match func.return_type.as_ref().and_then(|typ| self.llvm_type(typ)) {
+            Some(typ) => typ.fn_type(&parameters, false),
+            None => self.context.void_type().fn_type(&parameters, false),
+        };
+        Ok(self.module.add_function(&self.symbol(&func.name), fn_type, None))
+    }
+
+    /// Give the behavior `func` a mailbox, a global the C `main` fills in
+    /// with `custod_rt_mailbox_new`, as the interpreter gives each behavior
+    /// its own, and define `<behavior>.message`, which a worker calls with
+    /// the actor and the arguments block of a message to run it
+    fn compile_message_handler(&mut self, func: &MirFunction) -> Result<(), CodegenError> {
+        let text = self.context.i8_type().ptr_type(AddressSpace::default());
+        let mailbox = self.module.add_global(text, None, &format!("{}.mailbox", self.symbol(&func.name)));
+        mailbox.set_initializer(&text.const_null());
+        mailbox.set_linkage(Linkage::Internal);
+        self.mailboxes.insert(func.name.clone(), Slot { pointer: mailbox.as_pointer_value(), typ: text.into() });
+
+        let fn_type = self.context.void_type().fn_type(&[text.into(), text.into()], false);
+        let handler = self.module.add_function(&self.message_handler(&func.name), fn_type, Some(Linkage::Internal));
+        self.builder.position_at_end(self.context.append_basic_block(handler, "entry"));
+        self.builder.unset_current_debug_location();
+        let block = handler.get_last_param()
+            .ok_or_else(|| CodegenError::Builder(format!("{} takes no arguments block", self.message_handler(&func.name))))?;
+        let arguments = self.argument_slots(func, block.into_pointer_value())?.into_iter()
+            .map(|argument| Ok(self.builder.build_load(argument.typ, argument.pointer, "").built()?.into()))
+            .collect::<Result<Vec<BasicMetadataValueEnum>, CodegenError>>()?;
+        let behavior = self.module.get_function(&self.symbol(&func.name))
+            .ok_or_else(|| CodegenError::UnknownFunction(func.name.clone()))?;
+        self.builder.build_call(behavior, &arguments, "").built()?;
+        self.builder.build_return(None).built()?;
+        Ok(())
+    }
+
+    /// Lower the body of `func`, which has been declared. Each MIR block
+    /// becomes an LLVM block, reached from an `entry` block that holds the
+    /// slots. The phis of a block become LLVM phis at its start, which
+    /// store what they pick into their slots, so they are all assigned at
+    /// once on entry as in the interpreter.
+    fn compile_function(&mut self, program: &MirProgram, func: &MirFunction) -> Result<(), CodegenError> {
+        let symbol = self.symbol(&func.name);
+        let function = self.module.get_function(&symbol)
+            .ok_or_else(|| CodegenError::UnknownFunction(func.name.clone()))?;
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+        self.function = Some(function);
+        if let Some(debug) = &mut self.debug {
+            debug.enter_function(func, function, &symbol);
+        }
+        self.locate(func, None);
+        self.allocate_variables(func)?;
+        self.allocate_views(func)?;
+        for ((var, _), value) in func.parameters.iter().zip(function.get_param_iter()) {
+            self.builder.build_store(self.slot(func, *var)?.pointer, value).built()?;
+        }
+        self.owned = ownership::owned_copies(func);
+        for &var in &self.owned {
+            let slot = self.slot(func, var)?;
+            self.builder.build_store(slot.pointer, slot.typ.const_zero()).built()?;
+        }
+
+
+        self.blocks.clear();
+        self.phis.clear();
+        for block in &func.blocks {
+            let llvm_block = self.context.append_basic_block(function, &format!("block{}", block.id.0));
+            self.blocks.insert(block.id, llvm_block);
+        }
+        self.builder.build_unconditional_branch(self.block(func, func.entry_block)?).built()?;
+
+        // Every phi exists before any jump to its block is lowered
+        for block in &func.blocks {
+            self.builder.position_at_end(self.blocks[&block.id]);
+            for instruction in &block.instructions {
+                if let Instruction::Phi { target, .. } = instruction {
+                    let phi = self.builder.build_phi(self.slot(func, *target)?.typ, "").built()?;
+                    self.phis.insert((block.id, *target), phi);
+                }
+            }
+        }
+        let atomic_depths = memory_order::atomic_depths(func);
+        for block in &func.blocks {
+            self.builder.position_at_end(self.blocks[&block.id]);
+            self.atomic_depth = atomic_depths.get(&block.id).copied().unwrap_or(0);
+            for instruction in &block.instructions {
+                self.compile_instruction(program, func, block.id, instruction)?;
+            }
+            self.compile_terminator(program, func, block.id, &block.terminator)?;
+        }
+        Ok(())
+    }
+
+    /// Add a zeroed module global for every global of `program` not
+    /// declared yet, in id order so the module does not depend on hashing
+    fn declare_globals(&mut self, program: &MirProgram) -> Result<(), CodegenError> {
+        let mut globals: Vec<_> = program.globals.iter().collect();
+        globals.sort_by_key(|(_, var)| var.id.0);
+        for (name, var) in globals {
+            if self.globals.contains_key(&var.id) {
+                continue;
+            }
+            let typ = self.llvm_type(&var.typ).ok_or_else(|| CodegenError::Unsupported {
+                function: name.clone(),
+                construct: format!("globals of type {:?}", var.typ),
+            })?;
+            let global = self.module.add_global(typ, None, name);
+            global.set_initializer(&typ.const_zero());
+            self.globals.insert(var.id, Slot { pointer: global.as_pointer_value(), typ });
+        }
+        Ok(())
+    }
+
+    /// Give every `peak` target of `func` a view, pointing at its own slot
+    /// to start with, and find the variables each may share storage with.
+    /// A `peak` stores the address of its source's storage in the view, so
+    /// from then on a write through either is seen by both, in whatever
+    /// order the blocks are laid out. No access carries `noalias` or
+    /// type-based alias metadata, so LLVM never assumes a view and its
+    /// source are apart; the optimizer promotes views whose target it can
+    /// tell back to registers.
+    fn allocate_views(&mut self, func: &MirFunction) -> Result<(), CodegenError> {
+        self.views.clear();
+        self.aliases.clear();
+        let mut peaks: Vec<(VarId, VarId)> = func.blocks.iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| match instruction {
+                Instruction::Peak { target, source } => Some((*target, *source)),
+                _ => None,
+            })
+            .collect();
+        peaks.sort_by_key(|(target, _)| target.0);
+        for &(target, source) in &peaks {
+            self.aliases.entry(target).or_insert(source);
+        }
+        // A peak of a peak target shares the storage of what that peaks,
+        // and no chain is longer than the peaks there are
+        for _ in 0..peaks.len() {
+            let owners: HashMap<VarId, VarId> = self.aliases.iter()
+                .map(|(&target, &source)| (target, self.aliases.get(&source).copied().unwrap_or(source)))
+                .collect();
+            self.aliases = owners;
+        }
+        for (target, _) in peaks {
+            if self.views.contains_key(&target) {
+                continue;
+            }
+            let slot = self.slot(func, target)?;
+            let typ = slot.typ.ptr_type(AddressSpace::default());
+            let name = func.variables.get(&target).map_or_else(String::new, |var| format!("{}.view", var.name));
+            let pointer = self.builder.build_alloca(typ, &name).built()?;
+            self.builder.build_store(pointer, slot.pointer).built()?;
+            self.views.insert(target, Slot { pointer, typ: typ.into() });
+        }
+        Ok(())
+    }
+
+    /// Give every variable of `func` a slot at the start of its entry
+    /// block, in id order
+    fn allocate_variables(&mut self, func: &MirFunction) -> Result<(), CodegenError> {
+        self.variables.clear();
+        let mut variables: Vec<_> = func.variables.values().collect();
+        variables.sort_by_key(|var| var.id.0);
+        for var in variables {
+            let typ = self.llvm_type(&var.typ)
+                .ok_or_else(|| unsupported(func, &format!("variables of type {:?}", var.typ)))?;
+            let pointer = self.builder.build_alloca(typ, var.name.as_str()).built()?;
+            self.variables.insert(var.id, Slot { pointer, typ });
+            if let (Some(debug), Some(block)) = (&self.debug, self.builder.get_insert_block()) {
+                let argument = func.parameters.iter().position(|(param, _)| *param == var.id);
+                debug.declare(self.context, func, var, argument.map(|index| index as u32 + 1), pointer, block);
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_instruction(
+        &mut self,
+        program: &MirProgram,
+        func: &MirFunction,
+        block: BlockId,
+        instruction: &Instruction,
+    ) -> Result<(), CodegenError> {
+        let var = instruction.target().or_else(|| instruction.operands().into_iter().find_map(variable));
+        if let Some(var) = var {
+            self.locate(func, Some(var));
+        }
+        match instruction {
+            // Both store their source converted to the type of the target
+            Instruction::Assign { target, source } |
+            Instruction::Cast { target, source } => {
+                let typ = self.operand_type(program, func, source)?;
+                let value = self.operand(program, func, source)?;
+                self.store(program, func, *target, value, &typ)?;
+            },
+            Instruction::BinaryOp { target, left, op, right } => {
+                let typ = self.operand_type(program, func, left)?;
+                let left = self.operand(program, func, left)?;
+                let right = self.operand(program, func, right)?;
+                let value = self.binary_op(*op, &typ, left, right)?;
+                let result_type = if op.is_comparison() { Type::Bool } else { typ };
+                self.store(program, func, *target, value, &result_type)?;
+            },
+            Instruction::Intrinsic { intrinsic: Intrinsic::Print, arguments, .. } => {
+                let [argument] = arguments.as_slice() else {
+                    return Err(unsupported(func, "print without exactly one argument"));
+                };
+                let typ = self.operand_type(program, func, argument)?;
+                let value = self.operand(program, func, argument)?;
+                self.print(func, &typ, value)?;
+            },
+            Instruction::Intrinsic { target, intrinsic, arguments } => {
+                let Some(first) = arguments.first() else {
+                    return Err(unsupported(func, &format!("{} without arguments", intrinsic.name())));
+                };
+                let typ = self.operand_type(program, func, first)?;
+                let arguments = arguments.iter()
+                    .map(|argument| self.operand(program, func, argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let value = self.intrinsic(func, *intrinsic, &typ, &arguments)?;
+                if let Some(target) = target.filter(|target| self.owned.contains(target)) {
+                    self.free_copy(func, target)?;
+                }
+                if let (Some(target), Some(result_type)) = (target, intrinsic.result_type(&typ)) {
+                    self.store(program, func, *target, value, &result_type)?;
+                }
+            },
+            Instruction::Peak { target, source } => {
+                let source = self.storage(func, *source)?;
+                let view = self.views.get(target)
+                    .ok_or_else(|| CodegenError::UnknownVariable { function: func.name.clone(), var: *target })?;
+                self.builder.build_store(view.pointer, source.pointer).built()?;
+            },
+            Instruction::Call { target, function, arguments } => {
+                let callee = program.functions.get(function)
+                    .ok_or_else(|| CodegenError::UnknownFunction(function.clone()))?;
+                let declaration = self.module.get_function(&self.symbol(function))
+                    .ok_or_else(|| CodegenError::UnknownFunction(function.clone()))?;
+                if callee.parameters.len() != arguments.len() {
+                    return Err(CodegenError::ArityMismatch {
+                        function: function.clone(),
+                        expected: callee.parameters.len(),
+                        found: arguments.len(),
+                    });
+                }
+                let arguments = callee.parameters.iter().zip(arguments)
+                    .map(|((_, typ), argument)| {
+                        let from = self.operand_type(program, func, argument)?;
+                        let value = self.operand(program, func, argument)?;
+                        self.convert(value, &from, typ)
+                    })
+                    .collect::<Result<Vec<_>, CodegenError>>()?;
+                if self.threads && callee.is_behavior {
+                    return self.send(callee, &arguments);
+                }
+                let arguments: Vec<BasicMetadataValueEnum> = arguments.into_iter().map(Into::into).collect();
+                let call = self.builder.build_call(declaration, &arguments, "").built()?;
+                if let (Some(target), Some(value), Some(typ)) = (target, call.try_as_basic_value().left(), &callee.return_type) {
+                    self.store(program, func, *target, value, typ)?;
+                }
+            },
+            // Its operands are added by the jumps and branches to the block
+            Instruction::Phi { target, .. } => {
+                let phi = self.phis[&(block, *target)];
+                self.builder.build_store(self.storage(func, *target)?.pointer, phi.as_basic_value()).built()?;
+            },
+            // On one thread nothing else runs in between anyway
+            Instruction::AtomicBegin => {
+                self.atomic_depth += 1;
+                if self.threads {
+                    self.call_runtime("custod_rt_atomic_begin")?;
+                }
+            },
+            Instruction::AtomicEnd => {
+                self.atomic_depth = self.atomic_depth.saturating_sub(1);
+                if self.threads {
+                    self.call_runtime("custod_rt_atomic_end")?;
+                }
+            },
+            Instruction::WriteBarrier { .. } => {
+                let (ordering, single_thread) = memory_order::barrier(self.threads);
+                self.builder.build_fence(ordering, i32::from(single_thread), "").built()?;
+            },
+            Instruction::Nop => {},
+        }
+        Ok(())
+    }
+
+    fn compile_terminator(
+        &mut self,
+        program: &MirProgram,
+        func: &MirFunction,
+        block: BlockId,
+        terminator: &Terminator,
+    ) -> Result<(), CodegenError> {
+        if let Some(var) = terminator.operand().and_then(variable) {
+            self.locate(func, Some(var));
+        }
+        match terminator {
+            Terminator::Return(operand) => {
+                let result_type = func.return_type.as_ref().filter(|typ| self.llvm_type(typ).is_some());
+                match (operand, result_type) {
+                    (Some(operand), Some(result_type)) => {
+                        let typ = self.operand_type(program, func, operand)?;
+                        let value = self.operand(program, func, operand)?;
+                        let value = self.convert(value, &typ, result_type)?;
+                        self.free_copies(func)?;
+                        self.builder.build_return(Some(&value)).built()?;
+                    },
+                    _ => {
+                        self.free_copies(func)?;
+                        self.builder.build_return(None).built()?;
+                    },
+                }
+                Ok(())
+            },
+            Terminator::Jump(target) => {
+                self.phi_operands(program, func, block, *target)?;
+                self.builder.build_unconditional_branch(self.block(func, *target)?).built()?;
+                Ok(())
+            },
+            Terminator::Branch { condition, true_block, false_block } => {
+                let BasicValueEnum::IntValue(condition) = self.operand(program, func, condition)? else {
+                    return Err(unsupported(func, "branches on a value that is not a boolean"));
+                };
+                self.phi_operands(program, func, block, *true_block)?;
+                self.phi_operands(program, func, block, *false_block)?;
+                let (true_block, false_block) = (self.block(func, *true_block)?, self.block(func, *false_block)?);
+                self.builder.build_conditional_branch(condition, true_block, false_block).built()?;
+                Ok(())
+            },
+        }
+    }
+
+    /// Give the instructions that follow the source location of `var`, or
+    /// of `func` for `None`, if it has one and debug information is wanted
+    fn locate(&self, func: &MirFunction, var: Option<VarId>) {
+        let Some(debug) = &self.debug else {
+            return;
+        };
+        let location = match var {
+            Some(var) => match func.variables.get(&var).and_then(|var| var.location.as_ref()) {
+                Some(location) => Some(location),
+                None => return,
+            },
+            None => None,
+        };
+        if let Some(location) = debug.location(self.context, func, location) {
+            self.builder.set_current_debug_location(location);
+        }
+    }
+
+    /// Give the phis of `successor` their operands for control coming
+    /// from `block`, read where the builder is
+    fn phi_operands(&self, program: &MirProgram, func: &MirFunction, block: BlockId, successor: BlockId) -> Result<(), CodegenError> {
+        let Some(successor_block) = func.block(successor) else {
+            return Err(CodegenError::UnknownBlock { function: func.name.clone(), block: successor });
+        };
+        let current = self.builder.get_insert_block().expect("builder outside a block");
+        for instruction in &successor_block.instructions {
+            let Instruction::Phi { target, sources } = instruction else {
+                continue;
+            };
+            let (_, source) = sources.iter().find(|(predecessor, _)| *predecessor == block)
+                .ok_or_else(|| CodegenError::MissingPhiSource {
+                    function: func.name.clone(),
+                    block: successor,
+                    predecessor: block,
+                })?;
+            let typ = self.operand_type(program, func, source)?;
+            let value = self.operand(program, func, source)?;
+            let value = self.convert(value, &typ, &self.variable_type(program, func, *target)?)?;
+            self.phis[&(successor, *target)].add_incoming(&[(&value, current)]);
+        }
+        Ok(())
+    }
+
+    /// The LLVM block of a block of `func`
+    fn block(&self, func: &MirFunction, block: BlockId) -> Result<BasicBlock<'ctx>, CodegenError> {
+        self.blocks.get(&block)
+            .copied()
+            .ok_or_else(|| CodegenError::UnknownBlock { function: func.name.clone(), block })
+    }
+
+    /// The LLVM type values of `typ` are stored as, if it has one.
+    /// Integers are as wide as their type, booleans are `i1`, floats
+    /// `float` or `double`, and strings pointers to NUL-terminated text.
+    fn llvm_type(&self, typ: &Type) -> Option<BasicTypeEnum<'ctx>> {
+        let context = self.context;
+        let typ = match typ {
+            Type::Int8 | Type::UInt8 => context.i8_type().into(),
+            Type::Int16 | Type::UInt16 => context.i16_type().into(),
+            Type::Int32 | Type::UInt32 => context.i32_type().into(),
+            Type::Int | Type::Int64 | Type::UInt | Type::UInt64 => context.i64_type().into(),
+            Type::Float32 => context.f32_type().into(),
+            Type::Float | Type::Float64 => context.f64_type().into(),
+            Type::Bool => context.bool_type().into(),
+            Type::String => context.i8_type().ptr_type(AddressSpace::default()).into(),
+            Type::Unit | Type::Param(_) => return None,
+        };
+        Some(typ)
+    }
+
+    /// The slot of a local of `func` or of a global
+    fn slot(&self, func: &MirFunction, var: VarId) -> Result<Slot<'ctx>, CodegenError> {
+        self.variables.get(&var)
+            .or_else(|| self.globals.get(&var))
+            .copied()
+            .ok_or_else(|| CodegenError::UnknownVariable { function: func.name.clone(), var })
+    }
+
+    /// Where `var` is read and written: its slot, or for a `peak` target
+    /// the storage its view points at
+    fn storage(&self, func: &MirFunction, var: VarId) -> Result<Slot<'ctx>, CodegenError> {
+        let slot = self.slot(func, var)?;
+        let Some(view) = self.views.get(&var) else {
+            return Ok(slot);
+        };
+        let pointer = self.builder.build_load(view.typ, view.pointer, "").built()?.into_pointer_value();
+        Ok(Slot { pointer, typ: slot.typ })
+    }
+
+    /// The type of a local of `func` or of a global
+    fn variable_type(&self, program: &MirProgram, func: &MirFunction, var: VarId) -> Result<Type, CodegenError> {
+        func.variables.get(&var)
+            .or_else(|| program.globals.values().find(|global| global.id == var))
+            .map(|var| var.typ.clone())
+            .ok_or_else(|| CodegenError::UnknownVariable { function: func.name.clone(), var })
+    }
+
+    fn operand_type(&self, program: &MirProgram, func: &MirFunction, operand: &Operand) -> Result<Type, CodegenError> {
+        match operand {
+            Operand::Variable(var) => self.variable_type(program, func, *var),
+            Operand::Constant(constant) => Ok(constant.typ()),
+        }
+    }
+
+    /// The value of an operand: a constant, or a load from a variable's slot
+    fn operand(&self, program: &MirProgram, func: &MirFunction, operand: &Operand) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let value = match operand {
+            Operand::Variable(var) => {
+                let slot = self.storage(func, *var)?;
+                let value = self.builder.build_load(slot.typ, slot.pointer, "").built()?;
+                if let Some(load) = value.as_instruction_value() {
+                    self.make_atomic(program, func, *var, load)?;
+                }
+                value
+            },
+            Operand::Constant(Constant::Integer(value, typ)) => match (typ.numeric_kind(), self.llvm_type(typ)) {
+                (Some(NumericKind::Float), Some(BasicTypeEnum::FloatType(float))) => float.const_float(*value as f64).into(),
+                (_, Some(BasicTypeEnum::IntType(int))) => int.const_int(*value as u64, true).into(),
+                _ => return Err(unsupported(func, &format!("constants of type {:?}", typ))),
+            },
+            Operand::Constant(Constant::Boolean(value)) => self.context.bool_type().const_int(*value as u64, false).into(),
+            Operand::Constant(Constant::String(id)) => {
+                let text = self.builder.build_global_string_ptr(program.strings.get(*id), "str").built()?;
+                text.as_pointer_value().into()
+            },
+        };
+        Ok(value)
+    }
+
+    /// Store `value`, of type `from`, into `target`, converted to its type
+    fn store(&self, program: &MirProgram, func: &MirFunction, target: VarId, value: BasicValueEnum<'ctx>, from: &Type) -> Result<(), CodegenError> {
+        let slot = self.storage(func, target)?;
+        let to = self.variable_type(program, func, target)?;
+        let value = self.convert(value, from, &to)?;
+        let store = self.builder.build_store(slot.pointer, value).built()?;
+        self.make_atomic(program, func, target, store)
+    }
+
+    /// Make `access`, a load or store of `var`, atomic with the ordering
+    /// `memory_order` gives it, if the code is threaded and it needs one
+    fn make_atomic(&self, program: &MirProgram, func: &MirFunction, var: VarId, access: InstructionValue<'ctx>) -> Result<(), CodegenError> {
+        if !self.threads {
+            return Ok(());
+        }
+        let owner = self.aliases.get(&var).copied().unwrap_or(var);
+        let in_atomic_block = self.atomic_depth > 0;
+        let ordering = [var, owner].into_iter()
+            .filter_map(|var| func.variables.get(&var).or_else(|| program.globals.values().find(|global| global.id == var)))
+            .find_map(|var| memory_order::ordering(&var.permissions, in_atomic_block));
+        let Some(ordering) = ordering else {
+            return Ok(());
+        };
+        let typ = self.slot(func, var)?.typ;
+        let bytes = match typ {
+            BasicTypeEnum::IntType(int) if int.get_bit_width() >= 8 => int.get_bit_width() / 8,
+            BasicTypeEnum::FloatType(float) if float == self.context.f32_type() => 4,
+            BasicTypeEnum::FloatType(_) => 8,
+            BasicTypeEnum::PointerType(_) if self.platform == Platform::Wasm32 => 4,
+            BasicTypeEnum::PointerType(_) => 8,
+            _ => {
+                let typ = self.variable_type(program, func, var)?;
+                return Err(unsupported(func, &format!("sharing a writes {:?} between threads", typ)));
+            },
+        };
+        // Atomic accesses must be aligned to their size
+        access.set_alignment(bytes).map_err(|message| CodegenError::Builder(message.to_string()))?;
+        access.set_atomic_ordering(ordering).map_err(|message| CodegenError::Builder(message.to_string()))
+    }
+
+    /// Call the runtime function `name`, which takes nothing and returns
+    /// nothing
+    fn call_runtime(&self, name: &str) -> Result<(), CodegenError> {
+        let function = self.runtime_function(name, self.context.void_type().fn_type(&[], false));
+        self.builder.build_call(function, &[], "").built()?;
+        Ok(())
+    }
+
+    /// The runtime function `name` of type `fn_type`, declared on first use
+    fn runtime_function(&self, name: &str, fn_type: FunctionType<'ctx>) -> FunctionValue<'ctx> {
+        self.module.get_function(name)
+            .unwrap_or_else(|| self.module.add_function(name, fn_type, Some(Linkage::External)))
+    }
+
+    /// C's `size_t` on the platform
+    fn size_type(&self) -> IntType<'ctx> {
+        match self.platform {
+            Platform::Native => self.context.i64_type(),
+            Platform::Wasm32 => self.context.i32_type(),
+        }
+    }
+
+    /// Queue a call of the behavior `callee` with `arguments`, already of
+    /// its parameter types, on its mailbox: the arguments are copied into a
+    /// block from `custod_rt_alloc` and sent with `custod_rt_send`, leaving
+    /// the call's target unassigned
+    fn send(&self, callee: &MirFunction, arguments: &[BasicValueEnum<'ctx>]) -> Result<(), CodegenError> {
+        let text = self.context.i8_type().ptr_type(AddressSpace::default());
+        let size_type = self.size_type();
+        let size = self.arguments_type(callee)?.size_of()
+            .ok_or_else(|| unsupported(callee, "behavior parameters without a size"))?;
+        let size = self.builder.build_int_cast(size, size_type, "").built()?;
+        let alloc = self.runtime_function("custod_rt_alloc", text.fn_type(&[size_type.into()], false));
+        let block = self.builder.build_call(alloc, &[size.into()], "").built()?.try_as_basic_value().left()
+            .ok_or_else(|| CodegenError::Builder("custod_rt_alloc returns nothing".to_string()))?
+            .into_pointer_value();
+        for (argument, value) in self.argument_slots(callee, block)?.into_iter().zip(arguments) {
+            self.builder.build_store(argument.pointer, *value).built()?;
+        }
+
+        let mailbox = self.mailboxes.get(&callee.name)
+            .ok_or_else(|| CodegenError::UnknownFunction(callee.name.clone()))?;
+        let mailbox = self.builder.build_load(mailbox.typ, mailbox.pointer, "").built()?;
+        let handler = self.module.get_function(&self.message_handler(&callee.name))
+            .ok_or_else(|| CodegenError::UnknownFunction(callee.name.clone()))?;
+        let handler = self.builder.build_pointer_cast(handler.as_global_value().as_pointer_value(), text, "").built()?;
+        let fn_type = self.context.void_type().fn_type(&[text.into(), text.into(), text.into(), text.into()], false);
+        let send = self.runtime_function("custod_rt_send", fn_type);
+        self.builder.build_call(send, &[mailbox.into(), handler.into(), text.const_null().into(), block.into()], "").built()?;
+        Ok(())
+    }
+
+    /// The struct a message to the behavior `func` carries its arguments
+    /// in, a field for each parameter
+    fn arguments_type(&self, func: &MirFunction) -> Result<StructType<'ctx>, CodegenError> {
+        let fields = func.parameters.iter()
+            .map(|(_, typ)| self.llvm_type(typ)
+                .ok_or_else(|| unsupported(func, &format!("parameters of type {:?}", typ))))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.context.struct_type(&fields, false))
+    }
+
+    /// Where each argument of the behavior `func` is in the arguments
+    /// block `block`
+    fn argument_slots(&self, func: &MirFunction, block: PointerValue<'ctx>) -> Result<Vec<Slot<'ctx>>, CodegenError> {
+        let typ = self.arguments_type(func)?;
+        let pointer = self.builder.build_pointer_cast(block, typ.ptr_type(AddressSpace::default()), "").built()?;
+        let arguments = Slot { pointer, typ: typ.into() };
+        typ.get_field_types().into_iter().enumerate()
+            .map(|(index, field)| {
+                let pointer = self.builder.build_struct_gep(arguments.typ, arguments.pointer, index as u32, "").built()?;
+                Ok(Slot { pointer, typ: field })
+            })
+            .collect()
+    }
+
+    /// `value` of type `from` as a value of type `to`. Integers wrap to a
+    /// narrower width and extend by the signedness of `from`; floats
+    /// convert to integers by truncating towards zero.
+    fn convert(&self, value: BasicValueEnum<'ctx>, from: &Type, to: &Type) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let invalid = || CodegenError::InvalidConversion { from: from.clone(), to: to.clone() };
+        let target = self.llvm_type(to).ok_or_else(invalid)?;
+        if value.get_type() == target && from.numeric_kind() == to.numeric_kind() {
+            return Ok(value);
+        }
+        let from_signed = from.numeric_kind() == Some(NumericKind::Signed);
+        let to_signed = to.numeric_kind() == Some(NumericKind::Signed);
+        let converted: BasicValueEnum = match (value, target) {
+            (BasicValueEnum::IntValue(value), BasicTypeEnum::IntType(target)) => {
+                let (width, target_width) = (value.get_type().get_bit_width(), target.get_bit_width());
+                if target_width < width {
+                    self.builder.build_int_truncate(value, target, "").built()?.into()
+                } else if target_width > width && from_signed {
+                    self.builder.build_int_s_extend(value, target, "").built()?.into()
+                } else if target_width > width {
+                    self.builder.build_int_z_extend(value, target, "").built()?.into()
+                } else {
+                    value.into()
+                }
+            },
+            (BasicValueEnum::IntValue(value), BasicTypeEnum::FloatType(target)) if from_signed => {
+                self.builder.build_signed_int_to_float(value, target, "").built()?.into()
+            },
+            (BasicValueEnum::IntValue(value), BasicTypeEnum::FloatType(target)) => {
+                self.builder.build_unsigned_int_to_float(value, target, "").built()?.into()
+            },
+            (BasicValueEnum::FloatValue(value), BasicTypeEnum::IntType(target)) if to_signed => {
+                self.builder.build_float_to_signed_int(value, target, "").built()?.into()
+            },
+            (BasicValueEnum::FloatValue(value), BasicTypeEnum::IntType(target)) => {
+                self.builder.build_float_to_unsigned_int(value, target, "").built()?.into()
+            },
+            (BasicValueEnum::FloatValue(value), BasicTypeEnum::FloatType(target)) => {
+                self.builder.build_float_cast(value, target, "").built()?.into()
+            },
+            (value, target) if value.get_type() == target => value,
+            _ => return Err(invalid()),
+        };
+        Ok(converted)
+    }
+
+    /// `left op right` for operands of type `typ`. Integer arithmetic
+    /// wraps at the width of the type, as in the interpreter.
+    fn binary_op(
+        &self,
+        op: BinaryOperation,
+        typ: &Type,
+        left: BasicValueEnum<'ctx>,
+        right: BasicValueEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        use BinaryOperation::*;
+
+        let invalid = || CodegenError::InvalidOperands { op, typ: typ.clone() };
+        let builder = &self.builder;
+        let value: BasicValueEnum = match (left, right) {
+            (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => match op {
+                Add => builder.build_float_add(l, r, "").built()?.into(),
+                Subtract => builder.build_float_sub(l, r, "").built()?.into(),
+                Multiply => builder.build_float_mul(l, r, "").built()?.into(),
+                Divide => builder.build_float_div(l, r, "").built()?.into(),
+                Remainder => builder.build_float_rem(l, r, "").built()?.into(),
+                Equal => builder.build_float_compare(FloatPredicate::OEQ, l, r, "").built()?.into(),
+                NotEqual => builder.build_float_compare(FloatPredicate::UNE, l, r, "").built()?.into(),
+                LessThan => builder.build_float_compare(FloatPredicate::OLT, l, r, "").built()?.into(),
+                LessThanEqual => builder.build_float_compare(FloatPredicate::OLE, l, r, "").built()?.into(),
+                GreaterThan => builder.build_float_compare(FloatPredicate::OGT, l, r, "").built()?.into(),
+                GreaterThanEqual => builder.build_float_compare(FloatPredicate::OGE, l, r, "").built()?.into(),
+                And | Or => return Err(invalid()),
+            },
+            (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) if *typ == Type::Bool => match op {
+                And => builder.build_and(l, r, "").built()?.into(),
+                Or => builder.build_or(l, r, "").built()?.into(),
+                Equal => builder.build_int_compare(IntPredicate::EQ, l, r, "").built()?.into(),
+                NotEqual => builder.build_int_compare(IntPredicate::NE, l, r, "").built()?.into(),
+                _ => return Err(invalid()),
+            },
+            (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
+                let signed = match typ.numeric_kind() {
+                    Some(NumericKind::Signed) => true,
+                    Some(NumericKind::Unsigned) => false,
+                    _ => return Err(invalid()),
+                };
+                let predicate = |signed_predicate, unsigned_predicate| if signed { signed_predicate } else { unsigned_predicate };
+                match op {
+                    Add => builder.build_int_add(l, r, "").built()?.into(),
+                    Subtract => builder.build_int_sub(l, r, "").built()?.into(),
+                    Multiply => builder.build_int_mul(l, r, "").built()?.into(),
+                    Divide => self.divide(l, r, signed, false)?.into(),
+                    Remainder => self.divide(l, r, signed, true)?.into(),
+                    Equal => builder.build_int_compare(IntPredicate::EQ, l, r, "").built()?.into(),
+                    NotEqual => builder.build_int_compare(IntPredicate::NE, l, r, "").built()?.into(),
+                    LessThan => builder.build_int_compare(predicate(IntPredicate::SLT, IntPredicate::ULT), l, r, "").built()?.into(),
+                    LessThanEqual => builder.build_int_compare(predicate(IntPredicate::SLE, IntPredicate::ULE), l, r, "").built()?.into(),
+                    GreaterThan => builder.build_int_compare(predicate(IntPredicate::SGT, IntPredicate::UGT), l, r, "").built()?.into(),
+                    GreaterThanEqual => builder.build_int_compare(predicate(IntPredicate::SGE, IntPredicate::UGE), l, r, "").built()?.into(),
+                    And | Or => return Err(invalid()),
+                }
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(value)
+    }
+
+    /// `left / right`, or `left % right` for `remainder`, trapping on a
+    /// zero divisor. Signed division of the smallest value by -1 wraps as
+    /// in the interpreter instead of being undefined: a divisor of -1 is
+    /// replaced by 1 and the quotient negated.
+    fn divide(&self, left: IntValue<'ctx>, right: IntValue<'ctx>, signed: bool, remainder: bool) -> Result<IntValue<'ctx>, CodegenError> {
+        self.trap_if_zero(right)?;
+        let builder = &self.builder;
+        if !signed {
+            return Ok(match remainder {
+                true => builder.build_int_unsigned_rem(left, right, "").built()?,
+                false => builder.build_int_unsigned_div(left, right, "").built()?,
+            });
+        }
+
+        let typ = right.get_type();
+        let is_minus_one = builder.build_int_compare(IntPredicate::EQ, right, typ.const_all_ones(), "").built()?;
+        let divisor = builder.build_select(is_minus_one, typ.const_int(1, false), right, "").built()?.into_int_value();
+        let (result, by_minus_one) = match remainder {
+            true => (builder.build_int_signed_rem(left, divisor, "").built()?, typ.const_zero()),
+            false => (builder.build_int_signed_div(left, divisor, "").built()?, builder.build_int_neg(left, "").built()?),
+        };
+        Ok(builder.build_select(is_minus_one, by_minus_one, result, "").built()?.into_int_value())
+    }
+
+    /// Stop the program with `llvm.trap` if `value` is zero, and go on in
+    /// a new block otherwise
+    fn trap_if_zero(&self, value: IntValue<'ctx>) -> Result<(), CodegenError> {
+        let function = self.function.expect("division outside a function");
+        let is_zero = self.builder.build_int_compare(IntPredicate::EQ, value, value.get_type().const_zero(), "").built()?;
+        let trap = self.context.append_basic_block(function, "division_by_zero");
+        let divide = self.context.append_basic_block(function, "divide");
+        self.builder.build_conditional_branch(is_zero, trap, divide).built()?;
+
+        self.builder.position_at_end(trap);
+        self.call_intrinsic("llvm.trap", &[], &[])?;
+        self.builder.build_unreachable().built()?;
+        self.builder.position_at_end(divide);
+        Ok(())
+    }
+
+    /// The result of a built-in operation on `arguments`, the first of
+    /// which is of type `typ`. String operations other than `clone` are
+    /// reported as unsupported.
+    fn intrinsic(
+        &self,
+        func: &MirFunction,
+        intrinsic: Intrinsic,
+        typ: &Type,
+        arguments: &[BasicValueEnum<'ctx>],
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let builder = &self.builder;
+        let signed = typ.numeric_kind() == Some(NumericKind::Signed);
+        let value = match (intrinsic, arguments) {
+            (Intrinsic::Abs, [BasicValueEnum::IntValue(value)]) if signed => {
+                let negative = builder.build_int_compare(IntPredicate::SLT, *value, value.get_type().const_zero(), "").built()?;
+                let negated = builder.build_int_neg(*value, "").built()?;
+                builder.build_select(negative, negated, *value, "").built()?
+            },
+            (Intrinsic::Abs, [BasicValueEnum::IntValue(value)]) => (*value).into(),
+            (Intrinsic::Abs, [BasicValueEnum::FloatValue(value)]) => self.float_intrinsic("llvm.fabs", &[*value])?,
+            (Intrinsic::Min | Intrinsic::Max, [BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)]) => {
+                let predicate = match (intrinsic, signed) {
+                    (Intrinsic::Min, true) => IntPredicate::SLT,
+                    (Intrinsic::Min, false) => IntPredicate::ULT,
+                    (_, true) => IntPredicate::SGT,
+                    (_, false) => IntPredicate::UGT,
+                };
+                let pick_left = builder.build_int_compare(predicate, *l, *r, "").built()?;
+                builder.build_select(pick_left, *l, *r, "").built()?
+            },
+            (Intrinsic::Min, [BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)]) => self.float_intrinsic("llvm.minnum", &[*l, *r])?,
+            (Intrinsic::Max, [BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)]) => self.float_intrinsic("llvm.maxnum", &[*l, *r])?,
+            (Intrinsic::Clone, [BasicValueEnum::PointerValue(text)]) if *typ == Type::String => {
+                let routine = self.clone_routine(typ)?;
+                builder.build_call(routine, &[(*text).into()], "").built()?.try_as_basic_value().left()
+                    .ok_or_else(|| CodegenError::Builder("custod_rt_clone_string returns nothing".to_string()))?
+            },
+            // Numbers and booleans have no storage but their value
+            (Intrinsic::Clone, [BasicValueEnum::IntValue(_) | BasicValueEnum::FloatValue(_)]) => arguments[0],
+            _ => return Err(unsupported(func, &format!("{} of {:?}", intrinsic.name(), typ))),
+        };
+        Ok(value)
+    }
+
+    /// The function `clone` copies values of `typ` with, defined on first
+    /// use, so a module has one for each type it clones. A string's copy
+    /// is its text in a block of its own, from `custod_rt_clone_string`.
+    fn clone_routine(&self, typ: &Type) -> Result<FunctionValue<'ctx>, CodegenError> {
+        let name = format!("custod.clone.{:?}", typ);
+        if let Some(routine) = self.module.get_function(&name) {
+            return Ok(routine);
+        }
+        let text = self.context.i8_type().ptr_type(AddressSpace::default());
+        let fn_type = text.fn_type(&[text.into()], false);
+        let clone_string = self.runtime_function("custod_rt_clone_string", fn_type);
+        let routine = self.module.add_function(&name, fn_type, Some(Linkage::Internal));
+
+        // A builder of its own leaves the function being lowered where it is
+        let builder = self.context.create_builder();
+        builder.position_at_end(self.context.append_basic_block(routine, "entry"));
+        let original = routine.get_nth_param(0)
+            .ok_or_else(|| CodegenError::Builder(format!("{} has no parameter", name)))?;
+        let copy = builder.build_call(clone_string, &[original.into()], "").built()?.try_as_basic_value().left()
+            .ok_or_else(|| CodegenError::Builder("custod_rt_clone_string returns nothing".to_string()))?;
+        builder.build_return(Some(&copy)).built()?;
+        Ok(routine)
+    }
+
+    /// Free the string copy `var` holds, if it holds one
+    fn free_copy(&self, func: &MirFunction, var: VarId) -> Result<(), CodegenError> {
+        let text = self.context.i8_type().ptr_type(AddressSpace::default());
+        let free = self.runtime_function("custod_rt_free", self.context.void_type().fn_type(&[text.into()], false));
+        let slot = self.slot(func, var)?;
+        let copy = self.builder.build_load(slot.typ, slot.pointer, "").built()?;
+        self.builder.build_call(free, &[copy.into()], "").built()?;
+        Ok(())
+    }
+
+    /// Free every string copy the function owns, before it returns. Copies
+    /// that leave the function are not owned, as `ownership` explains.
+    fn free_copies(&self, func: &MirFunction) -> Result<(), CodegenError> {
+        for &var in &self.owned {
+            self.free_copy(func, var)?;
+        }
+        Ok(())
+    }
+
+    /// Write `value`, of type `typ`, and a newline to standard output with
+    /// C's `printf`, as the interpreter does: integers in decimal, booleans
+    /// as `true` or `false` and strings as they are. Floats are written
+    /// with 15 significant digits, which matches the interpreter for most
+    /// values but not the shortest form it finds for every one.
+    fn print(&self, func: &MirFunction, typ: &Type, value: BasicValueEnum<'ctx>) -> Result<(), CodegenError> {
+        if self.platform == Platform::Wasm32 {
+            return self.print_to_host(func, typ, value);
+        }
+        let builder = &self.builder;
+        let i64_type = self.context.i64_type();
+        let (format, value): (&str, BasicValueEnum) = match (typ, value) {
+            (Type::Bool, BasicValueEnum::IntValue(value)) => {
+                let yes = builder.build_global_string_ptr("true", "true").built()?.as_pointer_value();
+                let no = builder.build_global_string_ptr("false", "false").built()?.as_pointer_value();
+                ("%s\n", builder.build_select(value, yes, no, "").built()?)
+            },
+            (Type::String, value) => ("%s\n", value),
+            (_, BasicValueEnum::IntValue(value)) if typ.numeric_kind() == Some(NumericKind::Signed) => {
+                ("%lld\n", builder.build_int_s_extend_or_bit_cast(value, i64_type, "").built()?.into())
+            },
+            (_, BasicValueEnum::IntValue(value)) => {
+                ("%llu\n", builder.build_int_z_extend_or_bit_cast(value, i64_type, "").built()?.into())
+            },
+            // Variadic arguments are passed as doubles
+            (_, BasicValueEnum::FloatValue(value)) => {
+                ("%.15g\n", builder.build_float_cast(value, self.context.f64_type(), "").built()?.into())
+            },
+            _ => return Err(unsupported(func, &format!("print of {:?}", typ))),
+        };
+        let format = builder.build_global_string_ptr(format, "format").built()?.as_pointer_value();
+        builder.build_call(self.printf(), &[format.into(), value.into()], "").built()?;
+        Ok(())
+    }
+
+    /// Pass `value`, of type `typ`, to the WebAssembly host's function
+    /// for printing values of its type
+    fn print_to_host(&self, func: &MirFunction, typ: &Type, value: BasicValueEnum<'ctx>) -> Result<(), CodegenError> {
+        let builder = &self.builder;
+        let (name, value): (&str, BasicValueEnum) = match (typ, value) {
+            (Type::Bool, BasicValueEnum::IntValue(value)) => {
+                ("print_bool", builder.build_int_z_extend(value, self.context.i32_type(), "").built()?.into())
+            },
+            (Type::String, value) => ("print_string", value),
+            (_, BasicValueEnum::IntValue(value)) if typ.numeric_kind() == Some(NumericKind::Signed) => {
+                ("print_int", builder.build_int_s_extend_or_bit_cast(value, self.context.i64_type(), "").built()?.into())
+            },
+            (_, BasicValueEnum::IntValue(value)) => {
+                ("print_uint", builder.build_int_z_extend_or_bit_cast(value, self.context.i64_type(), "").built()?.into())
+            },
+            (_, BasicValueEnum::FloatValue(value)) => {
+                ("print_float", builder.build_float_cast(value, self.context.f64_type(), "").built()?.into())
+            },
+            _ => return Err(unsupported(func, &format!("print of {:?}", typ))),
+        };
+        let import = self.module.get_function(name).unwrap_or_else(|| {
+            let fn_type = self.context.void_type().fn_type(&[value.get_type().into()], false);
+            let import = self.module.add_function(name, fn_type, Some(Linkage::External));
+            for (key, value) in [("wasm-import-module", WASM_IMPORT_MODULE), ("wasm-import-name", name)] {
+                import.add_attribute(AttributeLoc::Function, self.context.create_string_attribute(key, value));
+            }
+            import
+        });
+        builder.build_call(import, &[value.into()], "").built()?;
+        Ok(())
+    }
+
+    /// C's `printf`, declared on first use
+    fn printf(&self) -> FunctionValue<'ctx> {
+        self.module.get_function("printf").unwrap_or_else(|| {
+            let text = self.context.i8_type().ptr_type(AddressSpace::default());
+            let fn_type = self.context.i32_type().fn_type(&[text.into()], true);
+            self.module.add_function("printf", fn_type, Some(Linkage::External))
+        })
+    }
+
+    /// A call to an LLVM intrinsic overloaded on the type of its float
+    /// arguments
+    fn float_intrinsic(&self, name: &str, arguments: &[FloatValue<'ctx>]) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let typ = arguments[0].get_type().as_basic_type_enum();
+        let arguments: Vec<BasicValueEnum> = arguments.iter().map(|argument| (*argument).into()).collect();
+        self.call_intrinsic(name, &[typ], &arguments)?
+            .ok_or_else(|| CodegenError::Builder(format!("{} returned nothing", name)))
+    }
+
+    /// Call the LLVM intrinsic `name`, declaring it for the types it is
+    /// overloaded on
+    fn call_intrinsic(
+        &self,
+        name: &str,
+        types: &[BasicTypeEnum<'ctx>],
+        arguments: &[BasicValueEnum<'ctx>],
+    ) -> Result<Option<BasicValueEnum<'ctx>>, CodegenError> {
+        let declaration = LlvmIntrinsic::find(name)
+            .and_then(|intrinsic| intrinsic.get_declaration(&self.module, types))
+            .ok_or_else(|| CodegenError::Builder(format!("LLVM has no intrinsic {}", name)))?;
+        let arguments: Vec<_> = arguments.iter().map(|argument| (*argument).into()).collect();
+        let call = self.builder.build_call(declaration, &arguments, "").built()?;
+        Ok(call.try_as_basic_value().left())
+    }
+}
+
+/// The variable an operand reads, if it reads one
+fn variable(operand: &Operand) -> Option<VarId> {
+    match operand {
+        Operand::Variable(var) => Some(*var),
+        Operand::Constant(_) => None,
+    }
+}
+
+fn unsupported(func: &MirFunction, construct: &str) -> CodegenError {
+    CodegenError::Unsupported {
+        function: func.name.clone(),
+        construct: construct.to_string(),
+    }
+}
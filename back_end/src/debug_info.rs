@@ -0,0 +1,201 @@
+//! DWARF debug information
+//!
+//! With debug information on, `CodeGen` describes every function and every
+//! variable declared in the source, and gives each instruction a source
+//! line, so a debugger can step through a compiled program by the lines of
+//! its source and show its variables. MIR keeps locations for functions
+//! and variables only: an instruction is placed at the variable it
+//! assigns, or else at the first variable it reads, and otherwise stays
+//! on the line of the instruction before it. Temporaries are not
+//! described, and a file id the `CodeGen` was not given a name for is
+//! described as the first file.
+
+use front_end::types::Type;
+use inkwell::basic_block::BasicBlock;
+use inkwell::context::Context;
+use inkwell::debug_info::{
+    AsDIScope, DICompileUnit, DIFile, DIFlags, DIFlagsConstants, DILocation, DISubprogram, DIType,
+    DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+};
+use inkwell::module::{FlagBehavior, Module};
+use inkwell::values::{FunctionValue, PointerValue};
+use inkwell::AddressSpace;
+use middle_end::hir::types::SourceLocation;
+use middle_end::mir::types::{MirFunction, MirVariable};
+use std::path::Path;
+
+/// DWARF type encodings, from the DWARF standard
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_FLOAT: u32 = 0x04;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_UNSIGNED: u32 = 0x08;
+const DW_ATE_UNSIGNED_CHAR: u32 = 0x08;
+
+/// The version of LLVM's debug metadata the module is written in
+const DEBUG_METADATA_VERSION: u64 = 3;
+
+/// The debug information of a module being generated
+pub(crate) struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    unit: DICompileUnit<'ctx>,
+
+    /// The file of each source file id
+    files: Vec<DIFile<'ctx>>,
+
+    /// The function being compiled
+    subprogram: Option<DISubprogram<'ctx>>,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+    /// Debug information for `module`, whose source files are named by
+    /// file id in `files`
+    pub(crate) fn new(context: &'ctx Context, module: &Module<'ctx>, files: &[String]) -> Self {
+        let (directory, name) = split(files.first().map(String::as_str).unwrap_or("<unknown>"));
+        let (builder, unit) = module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &name,
+            &directory,
+            "custod",
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        let version = context.i32_type().const_int(DEBUG_METADATA_VERSION, false);
+        module.add_basic_value_flag("Debug Info Version", FlagBehavior::Warning, version);
+        let files = files.iter()
+            .map(|file| {
+                let (directory, name) = split(file);
+                builder.create_file(&name, &directory)
+            })
+            .collect();
+        DebugInfo { builder, unit, files, subprogram: None }
+    }
+
+    /// Describe `func`, compiled to `function` under the name `symbol`,
+    /// and make it the scope of the locations that follow
+    pub(crate) fn enter_function(&mut self, func: &MirFunction, function: FunctionValue<'ctx>, symbol: &str) {
+        let file = self.file(func.location.as_ref());
+        let line = func.location.map_or(0, |location| location.start.line as u32);
+        let parameters: Vec<DIType> = func.parameters.iter()
+            .filter_map(|(_, typ)| self.typ(typ))
+            .collect();
+        let result = func.return_type.as_ref().and_then(|typ| self.typ(typ));
+        let signature = self.builder.create_subroutine_type(file, result, &parameters, DIFlags::ZERO);
+        let subprogram = self.builder.create_function(
+            file.as_debug_info_scope(),
+            &func.name,
+            Some(symbol),
+            file,
+            line,
+            signature,
+            false,
+            true,
+            line,
+            DIFlags::ZERO,
+            false,
+        );
+        function.set_subprogram(subprogram);
+        self.subprogram = Some(subprogram);
+    }
+
+    /// Where in the function being compiled `location` is, or where the
+    /// function is for `None`
+    pub(crate) fn location(&self, context: &'ctx Context, func: &MirFunction, location: Option<&SourceLocation>) -> Option<DILocation<'ctx>> {
+        let subprogram = self.subprogram?;
+        let (line, column) = match location.or(func.location.as_ref()) {
+            Some(location) => (location.start.line as u32, location.start.column as u32),
+            None => (0, 0),
+        };
+        Some(self.builder.create_debug_location(context, line, column, subprogram.as_debug_info_scope(), None))
+    }
+
+    /// Describe `var`, stored at `pointer`, unless it is a temporary or has
+    /// no location. `argument` is its position among the parameters,
+    /// counting from one; parameters have no location of their own and
+    /// are placed at their function.
+    pub(crate) fn declare(
+        &self,
+        context: &'ctx Context,
+        func: &MirFunction,
+        var: &MirVariable,
+        argument: Option<u32>,
+        pointer: PointerValue<'ctx>,
+        block: BasicBlock<'ctx>,
+    ) {
+        let location = var.location.as_ref().or(func.location.as_ref().filter(|_| argument.is_some()));
+        let (Some(subprogram), Some(location)) = (self.subprogram, location) else {
+            return;
+        };
+        let Some(typ) = self.typ(&var.typ).filter(|_| !var.permissions.is_empty()) else {
+            return;
+        };
+        let scope = subprogram.as_debug_info_scope();
+        let (file, line) = (self.file(Some(location)), location.start.line as u32);
+        let variable = match argument {
+            Some(argument) => self.builder.create_parameter_variable(
+                scope, var.name.as_str(), argument, file, line, typ, true, DIFlags::ZERO,
+            ),
+            None => self.builder.create_auto_variable(
+                scope, var.name.as_str(), file, line, typ, true, DIFlags::ZERO, 0,
+            ),
+        };
+        if let Some(location) = self.location(context, func, Some(location)) {
+            self.builder.insert_declare_at_end(pointer, Some(variable), None, location, block);
+        }
+    }
+
+    /// Resolve what has been described, before the module is verified or
+    /// written
+    pub(crate) fn finalize(&self) {
+        self.builder.finalize();
+    }
+
+    fn file(&self, location: Option<&SourceLocation>) -> DIFile<'ctx> {
+        location.and_then(|location| self.files.get(location.file_id))
+            .copied()
+            .unwrap_or_else(|| self.unit.get_file())
+    }
+
+    /// The debug type of values of `typ`, if they have one
+    fn typ(&self, typ: &Type) -> Option<DIType<'ctx>> {
+        let (name, bits, encoding) = match typ {
+            Type::Int8 => ("Int8", 8, DW_ATE_SIGNED),
+            Type::Int16 => ("Int16", 16, DW_ATE_SIGNED),
+            Type::Int32 => ("Int32", 32, DW_ATE_SIGNED),
+            Type::Int => ("Int", 64, DW_ATE_SIGNED),
+            Type::Int64 => ("Int64", 64, DW_ATE_SIGNED),
+            Type::UInt8 => ("UInt8", 8, DW_ATE_UNSIGNED),
+            Type::UInt16 => ("UInt16", 16, DW_ATE_UNSIGNED),
+            Type::UInt32 => ("UInt32", 32, DW_ATE_UNSIGNED),
+            Type::UInt => ("UInt", 64, DW_ATE_UNSIGNED),
+            Type::UInt64 => ("UInt64", 64, DW_ATE_UNSIGNED),
+            Type::Float32 => ("Float32", 32, DW_ATE_FLOAT),
+            Type::Float => ("Float", 64, DW_ATE_FLOAT),
+            Type::Float64 => ("Float64", 64, DW_ATE_FLOAT),
+            Type::Bool => ("Bool", 8, DW_ATE_BOOLEAN),
+            Type::String => {
+                let character = self.builder.create_basic_type("Char", 8, DW_ATE_UNSIGNED_CHAR, DIFlags::ZERO).ok()?;
+                let text = self.builder.create_pointer_type("String", character.as_type(), 64, 0, AddressSpace::default());
+                return Some(text.as_type());
+            },
+            Type::Unit | Type::Param(_) => return None,
+        };
+        self.builder.create_basic_type(name, bits, encoding, DIFlags::ZERO).ok().map(|typ| typ.as_type())
+    }
+}
+
+/// The directory and file name of `path`
+fn split(path: &str) -> (String, String) {
+    let path = Path::new(path);
+    let directory = path.parent().map(|parent| parent.display().to_string()).unwrap_or_default();
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    (directory, name)
+}
@@ -1,63 +1,25 @@
-use inkwell::context::Context;
-use inkwell::builder::Builder;
-use inkwell::module::Module;
-use inkwell::values::{IntValue, PointerValue};
-use std::collections::HashMap;
-use middle_end::mir::{MirFunction, MirInstruction, MirValue};
-
-pub struct CodeGen<'ctx> {
-    context: &'ctx Context,
-    module: Module<'ctx>,
-    builder: Builder<'ctx>,
-    variables: HashMap<String, PointerValue<'ctx>>,
-}
-
-impl<'ctx> CodeGen<'ctx> {
-    pub fn new(context: &'ctx Context, module_name: &str) -> Self {
-        let module = context.create_module(module_name);
-        let builder = context.create_builder();
-        
-        CodeGen {
-            context,
-            module,
-            builder,
-            variables: HashMap::new(),
-        }
-    }
-
-    pub fn compile(&mut self, mir: &MirFunction) {
-        let i64_type = self.context.i64_type();
-        let fn_type = i64_type.fn_type(&[], false);
-        let function = self.module.add_function("main", fn_type, None);
-        let basic_block = self.context.append_basic_block(function, "entry");
-        
-        self.builder.position_at_end(basic_block);
-
-        for instruction in &mir.instructions {
-            self.compile_instruction(instruction);
-        }
-
-        // Return 0
-        let ret_val = i64_type.const_int(0, false);
-        self.builder.build_return(Some(&ret_val));
-    }
-
-    fn compile_instruction(&self, instruction: &MirInstruction) {
-        // We'll implement this next
-    }
-}
-
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Back end of the compiler
+//!
+//! Lowers the MIR the middle end produces to LLVM IR, through inkwell, and
+//! links the object files it writes into executables, natively or for
+//! WebAssembly. `c_back_end`, which needs no LLVM, writes MIR as C source
+//! instead and holds the error type both share. Compiled programs call the
+//! runtime library, `custod_rt`, which executables are linked with.
+//! `mangle` names their functions in object files.
+
+pub mod codegen;
+mod debug_info;
+pub mod link;
+pub mod mangle;
+mod memory_order;
+pub mod platform;
+mod runtime;
+
+pub use c_back_end::to_c;
+pub use codegen::{CodeGen, CodegenError};
+pub use link::{link, link_wasm, LinkError};
+pub use mangle::{demangle, mangle};
+pub use platform::Platform;
 
 #[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+mod tests;
@@ -0,0 +1,135 @@
+//! Linking object files into an executable
+//!
+//! The system C compiler does the linking, so the C library `print` calls
+//! into and the startup code that calls `main` come with it. `CC` picks
+//! the compiler, and `cc` is used without it. The runtime library,
+//! `custod_rt`, is linked in too when `runtime_library` finds it.
+//!
+//! Errors show the linker's messages with the functions it names
+//! demangled.
+//!
+//! WebAssembly objects are linked by LLVM's `wasm-ld`, or the linker
+//! `WASM_LD` names, into a module that exports `main` and imports what
+//! the host provides.
+
+use crate::mangle::demangle_symbols;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Why linking failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    /// The linker could not be started
+    Spawn {
+        linker: String,
+        message: String,
+    },
+
+    /// The linker ran and reported an error
+    Failed {
+        linker: String,
+        status: Option<i32>,
+        stderr: String,
+    },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Spawn { linker, message } => write!(f, "Cannot run linker '{}': {}", linker, message),
+            LinkError::Failed { linker, status: Some(status), stderr } => {
+                write!(f, "Linker '{}' failed with exit status {}:\n{}", linker, status, demangle_symbols(stderr))
+            },
+            LinkError::Failed { linker, status: None, stderr } => {
+                write!(f, "Linker '{}' was stopped by a signal:\n{}", linker, demangle_symbols(stderr))
+            },
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// What the Rust standard library in the runtime needs from the system
+const RUNTIME_SYSTEM_LIBRARIES: [&str; 3] = ["-lpthread", "-ldl", "-lm"];
+
+/// The C compiler used to link
+pub fn linker() -> String {
+    from_env("CC", "cc")
+}
+
+/// The linker used for WebAssembly
+pub fn wasm_linker() -> String {
+    from_env("WASM_LD", "wasm-ld")
+}
+
+/// The static runtime library: the file `CUSTOD_RT` names, or else
+/// `libcustod_rt.a` next to the running compiler. Built as a dependency,
+/// cargo puts it in `deps` with a hash in its name, so the newest such
+/// library there, or next to a test binary in `deps`, comes last.
+pub fn runtime_library() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("CUSTOD_RT").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    let directory = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    let library = directory.join("libcustod_rt.a");
+    if library.is_file() {
+        return Some(library);
+    }
+    [directory.join("deps"), directory]
+        .iter()
+        .filter_map(|directory| std::fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("libcustod_rt-") && name.ends_with(".a")
+        })
+        .max_by_key(|entry| entry.metadata().and_then(|metadata| metadata.modified()).ok())
+        .map(|entry| entry.path())
+}
+
+/// Link `objects` into the executable `output`, with the runtime library
+/// and the system libraries it needs if there is one
+pub fn link(objects: &[&Path], output: &Path) -> Result<(), LinkError> {
+    let Some(runtime) = runtime_library() else {
+        return run(linker(), &[], objects, &[], output);
+    };
+    let mut objects = objects.to_vec();
+    objects.push(&runtime);
+    run(linker(), &[], &objects, &RUNTIME_SYSTEM_LIBRARIES, output)
+}
+
+/// Link WebAssembly `objects` into the module `output`. The host's
+/// functions stay undefined, to be imported when the module is
+/// instantiated.
+pub fn link_wasm(objects: &[&Path], output: &Path) -> Result<(), LinkError> {
+    run(wasm_linker(), &["--no-entry", "--export=main", "--allow-undefined"], objects, &[], output)
+}
+
+/// The program the variable `name` names, or `default` without it
+fn from_env(name: &str, default: &str) -> String {
+    std::env::var(name).ok().filter(|program| !program.is_empty()).unwrap_or_else(|| default.to_string())
+}
+
+/// Run `linker` with `flags`, then `objects`, then `libraries`, which
+/// must come after the objects that use them
+fn run(linker: String, flags: &[&str], objects: &[&Path], libraries: &[&str], output: &Path) -> Result<(), LinkError> {
+    let result = Command::new(&linker)
+        .args(flags)
+        .args(objects)
+        .args(libraries)
+        .arg("-o")
+        .arg(output)
+        .output()
+        .map_err(|error| LinkError::Spawn { linker: linker.clone(), message: error.to_string() })?;
+    if !result.status.success() {
+        return Err(LinkError::Failed {
+            linker,
+            status: result.status.code(),
+            stderr: String::from_utf8_lossy(&result.stderr).into_owned(),
+        });
+    }
+    Ok(())
+}
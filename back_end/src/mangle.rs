@@ -0,0 +1,187 @@
+//! Symbol names of compiled functions
+//!
+//! Functions of different modules, functions nested in different ones and
+//! the instances of one generic function may all share a name in the
+//! source, so the symbol of each in an object file spells out all three,
+//! and `demangle` turns it back into the name diagnostics show.
+
+use front_end::types::Type;
+use std::fmt;
+
+/// The types a type argument can be, as MIR names write them
+const TYPE_ARGUMENTS: [Type; 16] = [
+    Type::Int,
+    Type::Int8,
+    Type::Int16,
+    Type::Int32,
+    Type::Int64,
+    Type::UInt,
+    Type::UInt8,
+    Type::UInt16,
+    Type::UInt32,
+    Type::UInt64,
+    Type::Float,
+    Type::Float32,
+    Type::Float64,
+    Type::Bool,
+    Type::String,
+    Type::Unit,
+];
+
+/// What a function's symbol encodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// The module the function is compiled in
+    pub module: String,
+
+    /// The names of the functions it is nested in, outermost first, then
+    /// its own
+    pub path: Vec<String>,
+
+    /// The type arguments of an instance of a generic function, or none
+    pub type_arguments: Vec<String>,
+}
+
+impl Symbol {
+    /// The symbol of the MIR function `name` in `module`. MIR already
+    /// names lifted closures by their path (`outer$inner`) and instances by
+    /// their type arguments (`id$Int`); type names are keywords, so a
+    /// trailing part that is one is a type argument.
+    pub fn from_mir(module: &str, name: &str) -> Self {
+        let mut path: Vec<String> = name.split('$').map(str::to_string).collect();
+        let functions = path.iter()
+            .rposition(|part| !is_type_argument(part))
+            .map_or(1, |last| last + 1);
+        let type_arguments = path.split_off(functions);
+        Symbol { module: module.to_string(), path, type_arguments }
+    }
+
+    /// The symbol as it is written in an object file:
+    ///
+    /// ```text
+    /// _CN <module> <function>... [I <type argument>... E] E
+    /// ```
+    ///
+    /// where the functions are the path from the outermost one in to the
+    /// function itself. The instance of `id` for `Int` in the module `main`
+    /// is `_CN4main2idI3IntEE`, and `inner`, nested in `outer`, is
+    /// `_CN4main5outer5innerE`.
+    pub fn mangle(&self) -> String {
+        let mut symbol = String::from("_CN");
+        for name in std::iter::once(&self.module).chain(&self.path) {
+            push_name(&mut symbol, name);
+        }
+        if !self.type_arguments.is_empty() {
+            symbol.push('I');
+            for typ in &self.type_arguments {
+                push_name(&mut symbol, typ);
+            }
+            symbol.push('E');
+        }
+        symbol.push('E');
+        symbol
+    }
+}
+
+impl fmt::Display for Symbol {
+    /// The function as diagnostics name it, e.g. `main::outer::inner` or
+    /// `main::pair<Int, Bool>`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.module, self.path.join("::"))?;
+        if !self.type_arguments.is_empty() {
+            write!(f, "<{}>", self.type_arguments.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The symbol of the MIR function `name` compiled in `module`
+pub fn mangle(module: &str, name: &str) -> String {
+    Symbol::from_mir(module, name).mangle()
+}
+
+/// What `symbol` encodes, or `None` if it is not a mangled symbol
+pub fn demangle(symbol: &str) -> Option<Symbol> {
+    match parse(symbol) {
+        Some((demangled, length)) if length == symbol.len() => Some(demangled),
+        _ => None,
+    }
+}
+
+/// `text` with every mangled symbol in it replaced by its demangled name,
+/// for a linker's messages
+pub fn demangle_symbols(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("_CN") {
+        result.push_str(&rest[..start]);
+        match parse(&rest[start..]) {
+            Some((symbol, length)) => {
+                result.push_str(&symbol.to_string());
+                rest = &rest[start + length..];
+            },
+            None => {
+                result.push_str("_CN");
+                rest = &rest[start + 3..];
+            },
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Append `name` as its length in bytes followed by the name, as in the
+/// Itanium C++ ABI, with a `_` in between when the name is empty or starts
+/// with a digit or `_` itself, as in Rust's, so a module named after a file
+/// like `2d.cd` stays unambiguous
+fn push_name(symbol: &mut String, name: &str) {
+    symbol.push_str(&name.len().to_string());
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit() || c == '_') {
+        symbol.push('_');
+    }
+    symbol.push_str(name);
+}
+
+fn is_type_argument(name: &str) -> bool {
+    TYPE_ARGUMENTS.iter().any(|typ| format!("{:?}", typ) == name)
+}
+
+/// The symbol at the start of `text` and how many bytes it takes up
+fn parse(text: &str) -> Option<(Symbol, usize)> {
+    let mut rest = text.strip_prefix("_CN")?;
+    let mut names = Vec::new();
+    while let Some((name, after)) = name(rest) {
+        names.push(name);
+        rest = after;
+    }
+    let mut type_arguments = Vec::new();
+    if let Some(after) = rest.strip_prefix('I') {
+        rest = after;
+        while let Some((name, after)) = name(rest) {
+            type_arguments.push(name);
+            rest = after;
+        }
+        rest = rest.strip_prefix('E')?;
+        if type_arguments.is_empty() {
+            return None;
+        }
+    }
+    rest = rest.strip_prefix('E')?;
+    if names.len() < 2 {
+        return None;
+    }
+    let module = names.remove(0);
+    Some((Symbol { module, path: names, type_arguments }, text.len() - rest.len()))
+}
+
+/// The length-prefixed name at the start of `text`, and what follows it
+fn name(text: &str) -> Option<(String, &str)> {
+    let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let length: usize = text[..digits].parse().ok()?;
+    let start = match text[digits..].starts_with('_') {
+        true => digits + 1,
+        false => digits,
+    };
+    let name = text.get(start..start + length)?;
+    Some((name.to_string(), &text[start + length..]))
+}
@@ -0,0 +1,73 @@
+//! Memory orderings for variables behaviors share
+//!
+//! When `CodeGen::with_threads` lets behaviors run in parallel, every
+//! access to a `writes` variable, which other behaviors may change, is
+//! atomic, so no access is a data race. Write barriers become fences.
+
+use front_end::types::Permission;
+use inkwell::AtomicOrdering;
+use middle_end::mir::types::{BlockId, Instruction, MirFunction};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// The ordering of accesses to a variable with `permissions`, or `None`
+/// for plain loads and stores. Outside an atomic block it is sequentially
+/// consistent, so all threads see the writes in one order, as when
+/// behaviors run one at a time; inside one it is monotonic, since the lock
+/// the block holds already orders it against every other atomic block.
+///
+/// No other permission needs atomics: a `reads` value never changes while
+/// it is shared, and a `read` or `write` one has a single owner. A `peak`
+/// of a `writes` variable is accessed as the variable itself. LLVM has no
+/// atomic booleans, so sharing a `writes` Bool between threads is reported
+/// as unsupported.
+pub(crate) fn ordering(permissions: &[Permission], in_atomic_block: bool) -> Option<AtomicOrdering> {
+    if !permissions.contains(&Permission::Writes) {
+        return None;
+    }
+    match in_atomic_block {
+        true => Some(AtomicOrdering::Monotonic),
+        false => Some(AtomicOrdering::SequentiallyConsistent),
+    }
+}
+
+/// The ordering of the fence a write barrier becomes, and whether it only
+/// orders accesses within one thread
+///
+/// MIR puts a `WriteBarrier` before a store to a variable while a `peak`
+/// alias of it is live. As a release fence, every write before it is
+/// visible before the store after it, so a thread that sees the new value
+/// through the alias sees those writes too. On one thread only the
+/// compiler could move writes past the store, so the fence is limited to
+/// the thread and costs no instruction.
+pub(crate) fn barrier(threads: bool) -> (AtomicOrdering, bool) {
+    (AtomicOrdering::Release, !threads)
+}
+
+/// How many atomic blocks each block of `func` reachable from its entry
+/// starts inside. MIR closes every atomic block on every path out of it,
+/// so all the ways into a block agree.
+pub(crate) fn atomic_depths(func: &MirFunction) -> HashMap<BlockId, usize> {
+    let mut depths = HashMap::from([(func.entry_block, 0usize)]);
+    let mut pending = vec![func.entry_block];
+    while let Some(id) = pending.pop() {
+        let Some(block) = func.block(id) else {
+            continue;
+        };
+        let mut depth = depths[&id];
+        for instruction in &block.instructions {
+            match instruction {
+                Instruction::AtomicBegin => depth += 1,
+                Instruction::AtomicEnd => depth = depth.saturating_sub(1),
+                _ => {},
+            }
+        }
+        for successor in block.terminator.successors() {
+            if let Entry::Vacant(entry) = depths.entry(successor) {
+                entry.insert(depth);
+                pending.push(successor);
+            }
+        }
+    }
+    depths
+}
@@ -0,0 +1,95 @@
+//! Platforms code is generated for
+//!
+//! `Native` is the machine the compiler runs on, where `print` calls C's
+//! `printf`. `Wasm32` is 32-bit WebAssembly for a browser or another host
+//! without a C library. There `print` calls functions the host provides,
+//! imported from the module `custod`:
+//!
+//! - `print_int(i64)` and `print_uint(i64)` for signed and unsigned integers
+//! - `print_float(f64)` for floats
+//! - `print_bool(i32)`, given 1 for true and 0 for false
+//! - `print_string(i32)`, given the address of NUL-terminated UTF-8 text in
+//!   the module's memory
+//!
+//! The host calls the exported `main` to run the program.
+
+use inkwell::targets::{InitializationConfig, Target, TargetMachine, TargetTriple};
+use std::fmt;
+use std::str::FromStr;
+
+/// What generated code runs on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Platform {
+    /// The machine the compiler runs on
+    #[default]
+    Native,
+
+    /// 32-bit WebAssembly
+    Wasm32,
+}
+
+/// The module the WebAssembly host's functions are imported from
+pub const WASM_IMPORT_MODULE: &str = "custod";
+
+impl Platform {
+    /// The target triple LLVM generates code for
+    pub fn triple(self) -> TargetTriple {
+        match self {
+            Platform::Native => TargetMachine::get_default_triple(),
+            Platform::Wasm32 => TargetTriple::create("wasm32-unknown-unknown"),
+        }
+    }
+
+    /// The CPU and its features to generate code for: the host's natively,
+    /// and the baseline WebAssembly every engine runs
+    pub(crate) fn cpu(self) -> (String, String) {
+        match self {
+            Platform::Native => (
+                TargetMachine::get_host_cpu_name().to_string(),
+                TargetMachine::get_host_cpu_features().to_string(),
+            ),
+            Platform::Wasm32 => ("generic".to_string(), String::new()),
+        }
+    }
+
+    /// Make LLVM's code generator for the platform available
+    pub(crate) fn initialize(self) -> Result<(), String> {
+        match self {
+            Platform::Native => Target::initialize_native(&InitializationConfig::default()),
+            Platform::Wasm32 => {
+                Target::initialize_webassembly(&InitializationConfig::default());
+                Ok(())
+            },
+        }
+    }
+
+    /// The extension of the executable the linker writes
+    pub fn executable_extension(self) -> &'static str {
+        match self {
+            Platform::Native => "",
+            Platform::Wasm32 => "wasm",
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Native => write!(f, "native"),
+            Platform::Wasm32 => write!(f, "wasm32"),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    /// Parse `native` or `wasm32`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" => Ok(Platform::Native),
+            "wasm32" => Ok(Platform::Wasm32),
+            other => Err(format!("unknown target '{}', expected native or wasm32", other)),
+        }
+    }
+}
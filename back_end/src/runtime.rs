@@ -0,0 +1,32 @@
+//! The runtime library generated code calls
+//!
+//! An executable gets `custod_rt` from the static library `link` adds. The
+//! compiler links the same crate, so a program run by the JIT calls the
+//! functions in this process instead, mapped by name here.
+
+use custod_rt::{alloc, atomic, clone, mailbox, print, scheduler};
+
+/// Every runtime function, by name, with its address in this process
+pub(crate) fn functions() -> Vec<(&'static str, usize)> {
+    vec![
+        ("custod_rt_print_int", print::custod_rt_print_int as *const () as usize),
+        ("custod_rt_print_uint", print::custod_rt_print_uint as *const () as usize),
+        ("custod_rt_print_float", print::custod_rt_print_float as *const () as usize),
+        ("custod_rt_print_bool", print::custod_rt_print_bool as *const () as usize),
+        ("custod_rt_print_string", print::custod_rt_print_string as *const () as usize),
+        ("custod_rt_alloc", alloc::custod_rt_alloc as *const () as usize),
+        ("custod_rt_free", alloc::custod_rt_free as *const () as usize),
+        ("custod_rt_size", alloc::custod_rt_size as *const () as usize),
+        ("custod_rt_atomic_begin", atomic::custod_rt_atomic_begin as *const () as usize),
+        ("custod_rt_atomic_end", atomic::custod_rt_atomic_end as *const () as usize),
+        ("custod_rt_clone", clone::custod_rt_clone as *const () as usize),
+        ("custod_rt_clone_string", clone::custod_rt_clone_string as *const () as usize),
+        ("custod_rt_mailbox_new", mailbox::custod_rt_mailbox_new as *const () as usize),
+        ("custod_rt_mailbox_send", mailbox::custod_rt_mailbox_send as *const () as usize),
+        ("custod_rt_mailbox_run", mailbox::custod_rt_mailbox_run as *const () as usize),
+        ("custod_rt_mailbox_free", mailbox::custod_rt_mailbox_free as *const () as usize),
+        ("custod_rt_scheduler_start", scheduler::custod_rt_scheduler_start as *const () as usize),
+        ("custod_rt_send", scheduler::custod_rt_send as *const () as usize),
+        ("custod_rt_scheduler_wait", scheduler::custod_rt_scheduler_wait as *const () as usize),
+    ]
+}
@@ -0,0 +1,804 @@
+//! Tests for lowering MIR instructions to LLVM IR
+
+use crate::codegen::{CodeGen, CodegenError};
+use crate::link::link;
+use crate::platform::Platform;
+use front_end::types::Type;
+use inkwell::context::Context;
+use inkwell::execution_engine::ExecutionEngine;
+use inkwell::module::Module;
+use inkwell::OptimizationLevel;
+use front_end::parser::Parser;
+use middle_end::hir::convert_statements_to_hir;
+use middle_end::mir::convert_hir_to_mir;
+use middle_end::mir::types::BlockId;
+use middle_end::mir::{parse_mir, Interpreter, MirProgram, Value};
+
+fn parse(source: &str) -> MirProgram {
+    parse_mir(source).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Lower `program` and check the module with LLVM's verifier
+fn compile<'ctx>(context: &'ctx Context, program: &MirProgram) -> Result<CodeGen<'ctx>, CodegenError> {
+    let mut codegen = CodeGen::new(context, "test");
+    codegen.compile(program)?;
+    println!("{}", codegen.ir());
+    codegen.verify()?;
+    Ok(codegen)
+}
+
+/// Run the generated `function`, which takes nothing, and return its result
+fn run(codegen: &CodeGen, program: &MirProgram, function: &str) -> Value {
+    let engine = codegen.module()
+        .create_jit_execution_engine(OptimizationLevel::None)
+        .unwrap_or_else(|e| panic!("{}", e));
+    unsafe fn call<T>(engine: &ExecutionEngine, function: &str) -> T {
+        engine.get_function::<unsafe extern "C" fn() -> T>(function).unwrap().call()
+    }
+    let symbol = codegen.symbol(function);
+    unsafe {
+        match program.functions[function].return_type {
+            Some(Type::Int8) => Value::Integer(call::<i8>(&engine, &symbol) as i64),
+            Some(Type::UInt8) => Value::Integer(call::<u8>(&engine, &symbol) as i64),
+            Some(Type::Int | Type::Int64) => Value::Integer(call::<i64>(&engine, &symbol)),
+            Some(Type::Bool) => Value::Boolean(call::<bool>(&engine, &symbol)),
+            Some(Type::Float | Type::Float64) => Value::Float(call::<f64>(&engine, &symbol)),
+            ref typ => panic!("cannot run a function returning {:?}", typ),
+        }
+    }
+}
+
+/// The result of `function` run by the compiled code and by the interpreter
+fn both(program: &MirProgram, function: &str) -> (Value, Value) {
+    let context = Context::create();
+    let codegen = compile(&context, program).unwrap_or_else(|e| panic!("{}", e));
+    let compiled = run(&codegen, program, function);
+    let interpreted = Interpreter::new(program).call(function, vec![])
+        .unwrap_or_else(|e| panic!("{}", e))
+        .unwrap_or_else(|| panic!("{} returned nothing", function));
+    (compiled, interpreted)
+}
+
+#[test]
+fn test_integer_arithmetic_matches_interpreter() {
+    let source = r#"
+        fn wrap() -> Int8 {
+            var a: Int8 [0]
+            var b: Int8 [1]
+
+            block0:
+                a[0] = 100i8
+                b[1] = a[0] + a[0]
+                return b[1]
+        }
+
+        fn divide() -> Int {
+            var q: Int [2]
+            var r: Int [3]
+            var s: Int [4]
+
+            block0:
+                q[2] = -17 / 5
+                r[3] = -17 % 5
+                s[4] = q[2] * 100
+                s[4] = s[4] + r[3]
+                return s[4]
+        }
+
+        fn smallest() -> Int {
+            var m: Int [5]
+            var q: Int [6]
+
+            block0:
+                m[5] = -9223372036854775807 - 1
+                q[6] = m[5] / -1
+                return q[6]
+        }
+
+        fn unsigned() -> UInt8 {
+            var a: UInt8 [7]
+            var b: UInt8 [8]
+            var c: UInt8 [9]
+
+            block0:
+                a[7] = 250u8
+                b[8] = a[7] + 10u8
+                c[9] = a[7] / b[8]
+                return c[9]
+        }
+
+        fn compare() -> Bool {
+            var a: Bool [10]
+            var b: Bool [11]
+            var c: Bool [12]
+
+            block0:
+                a[10] = 3 < 4
+                b[11] = 200u8 > 100u8
+                c[12] = a[10] && b[11]
+                return c[12]
+        }
+    "#;
+    let program = parse(source);
+    for function in ["wrap", "divide", "smallest", "unsigned", "compare"] {
+        let (compiled, interpreted) = both(&program, function);
+        assert_eq!(compiled, interpreted, "{}", function);
+    }
+    assert_eq!(both(&program, "wrap").0, Value::Integer(-56));
+    assert_eq!(both(&program, "divide").0, Value::Integer(-302));
+}
+
+#[test]
+fn test_casts_floats_and_intrinsics() {
+    let source = r#"
+        fn floats() -> Int {
+            var f: Float [0]
+            var g: Float [1]
+            var i: Int [2]
+
+            block0:
+                f[0] = 7 as Float
+                g[1] = f[0] / 2f
+                i[2] = g[1] as Int
+                return i[2]
+        }
+
+        fn narrow() -> Int {
+            var a: Int [3]
+            var b: Int8 [4]
+            var c: Int [5]
+
+            block0:
+                a[3] = 300
+                b[4] = a[3] as Int8
+                c[5] = b[4] as Int
+                return c[5]
+        }
+
+        fn intrinsics() -> Int {
+            var a: Int [6]
+            var b: Int [7]
+            var c: Int [8]
+            var f: Float [9]
+            var g: Float [10]
+            var d: Int [11]
+
+            block0:
+                a[6] = intrinsic abs(-5)
+                b[7] = intrinsic min(a[6], 3)
+                c[8] = intrinsic max(a[6], b[7])
+                f[9] = intrinsic abs(-2f)
+                g[10] = intrinsic max(f[9], 1f)
+                d[11] = g[10] as Int
+                c[8] = c[8] * 10
+                c[8] = c[8] + d[11]
+                return c[8]
+        }
+    "#;
+    let program = parse(source);
+    for function in ["floats", "narrow", "intrinsics"] {
+        let (compiled, interpreted) = both(&program, function);
+        assert_eq!(compiled, interpreted, "{}", function);
+    }
+    assert_eq!(both(&program, "narrow").0, Value::Integer(44));
+    assert_eq!(both(&program, "intrinsics").0, Value::Integer(52));
+}
+
+#[test]
+fn test_division_checks_for_zero() {
+    let source = r#"
+        fn inverse() -> Int {
+            var d: Int [0]
+            var q: Int [1]
+
+            block0:
+                d[0] = 0
+                q[1] = 100 / d[0]
+                return q[1]
+        }
+    "#;
+    let context = Context::create();
+    let codegen = compile(&context, &parse(source)).unwrap_or_else(|e| panic!("{}", e));
+    let ir = codegen.ir();
+    assert!(ir.contains("call void @llvm.trap()"), "{}", ir);
+    assert!(ir.contains("division_by_zero:"), "{}", ir);
+}
+
+#[test]
+fn test_peak_and_globals_share_storage() {
+    let source = r#"
+        var total: Int [0] heap writes
+
+        fn share() -> Int {
+            var a: Int [1]
+            var b: Int [2]
+            var c: Int [3]
+
+            block0:
+                a[1] = 1
+                b[2] = peak a[1]
+                b[2] = 5
+                total[0] = a[1] + 1
+                c[3] = total[0] * 2
+                return c[3]
+        }
+    "#;
+    let program = parse(source);
+    assert_eq!(both(&program, "share"), (Value::Integer(12), Value::Integer(12)));
+
+    let context = Context::create();
+    let codegen = compile(&context, &program).unwrap();
+    assert!(codegen.module().get_global("total").is_some());
+}
+
+#[test]
+fn test_peak_views_see_later_updates() {
+    // The peak runs before the block laid out first, and the view reads
+    // what is written to its source after it
+    let source = r#"
+        fn view() -> Int {
+            var a: Int [0]
+            var v: Int [1] read
+            var w: Int [2] read
+            var r: Int [3]
+
+            block0:
+                a[0] = 1
+                v[1] = 100
+                r[3] = v[1]
+                jump block2
+            block1:
+                a[0] = a[0] + 10
+                w[2] = 7
+                r[3] = r[3] + v[1]
+                r[3] = r[3] + w[2]
+                return r[3]
+            block2:
+                v[1] = peak a[0]
+                w[2] = peak v[1]
+                a[0] = 5
+                r[3] = r[3] + v[1]
+                jump block1
+        }
+    "#;
+    let program = parse(source);
+    assert_eq!(both(&program, "view"), (Value::Integer(119), Value::Integer(119)));
+
+    let context = Context::create();
+    let codegen = compile(&context, &program).unwrap();
+    assert!(codegen.ir().contains("%v.view = alloca"), "{}", codegen.ir());
+    assert!(codegen.ir().contains("%w.view = alloca"), "{}", codegen.ir());
+}
+
+#[test]
+fn test_functions_and_calls() {
+    let source = r#"
+        fn square(n: Int [0] read) -> Int {
+            var r: Int [1]
+
+            block0:
+                r[1] = n[0] * n[0]
+                return r[1]
+        }
+
+        fn narrow(n: Int8 [2] read, m: Int8 [3] read) -> Int8 {
+            var r: Int8 [4]
+
+            block0:
+                r[4] = n[2] + m[3]
+                return r[4]
+        }
+
+        fn record(n: Int [5] read) {
+            block0:
+                total[6] = n[5]
+                return
+        }
+
+        var total: Int [6] heap writes
+
+        fn caller() -> Int {
+            var a: Int [7]
+            var b: Int8 [8]
+            var c: Int [9]
+
+            block0:
+                a[7] = call square(7)
+                b[8] = call narrow(100i8, 100i8)
+                c[9] = b[8] as Int
+                c[9] = a[7] + c[9]
+                call record(c[9])
+                c[9] = total[6] + 1
+                return c[9]
+        }
+    "#;
+    let program = parse(source);
+    assert_eq!(both(&program, "caller"), (Value::Integer(-6), Value::Integer(-6)));
+
+    let context = Context::create();
+    let codegen = compile(&context, &program).unwrap();
+    let square = codegen.module().get_function(&codegen.symbol("square")).unwrap();
+    assert_eq!(square.count_params(), 1);
+    assert!(codegen.module().get_function(&codegen.symbol("record")).unwrap().get_type().get_return_type().is_none());
+}
+
+#[test]
+fn test_branches_and_loops() {
+    let source = r#"
+        fn sum() -> Int {
+            var i: Int [0]
+            var total: Int [1]
+            var more: Bool [2]
+
+            block0:
+                i[0] = 0
+                total[1] = 0
+                jump block1
+            block1:
+                more[2] = i[0] < 10
+                branch more[2] ? block2 : block3
+            block2:
+                total[1] = total[1] + i[0]
+                i[0] = i[0] + 1
+                jump block1
+            block3:
+                return total[1]
+        }
+
+        fn fib(n: Int [3] read) -> Int {
+            var small: Bool [4]
+            var a: Int [5]
+            var b: Int [6]
+            var m: Int [7]
+
+            block0:
+                small[4] = n[3] < 2
+                branch small[4] ? block1 : block2
+            block1:
+                return n[3]
+            block2:
+                m[7] = n[3] - 1
+                a[5] = call fib(m[7])
+                m[7] = n[3] - 2
+                b[6] = call fib(m[7])
+                a[5] = a[5] + b[6]
+                return a[5]
+        }
+
+        fn fibs() -> Int {
+            var r: Int [8]
+
+            block0:
+                r[8] = call fib(20)
+                return r[8]
+        }
+
+        fn swap() -> Int {
+            var a: Int [9]
+            var b: Int [10]
+            var n: Int [11]
+            var done: Bool [12]
+            var r: Int [13]
+
+            block0:
+                jump block1
+            block1:
+                a[9] = phi(block0: 1, block2: b[10])
+                b[10] = phi(block0: 2, block2: a[9])
+                n[11] = phi(block0: 0, block2: n[11])
+                done[12] = n[11] == 3
+                branch done[12] ? block3 : block2
+            block2:
+                n[11] = n[11] + 1
+                jump block1
+            block3:
+                r[13] = a[9] * 10
+                r[13] = r[13] + b[10]
+                return r[13]
+        }
+    "#;
+    let program = parse(source);
+    assert_eq!(both(&program, "sum"), (Value::Integer(45), Value::Integer(45)));
+    assert_eq!(both(&program, "fibs"), (Value::Integer(6765), Value::Integer(6765)));
+    let (compiled, interpreted) = both(&program, "swap");
+    assert_eq!(compiled, interpreted);
+    assert_eq!(compiled, Value::Integer(21));
+}
+
+#[test]
+fn test_unsupported_mir_is_reported() {
+    let error = |source: &str| match compile(&Context::create(), &parse(source)) {
+        Err(error) => error,
+        result => panic!("compiled to {:?}", result.map(|_| ())),
+    };
+
+    let concat = r#"
+        fn joins() -> Int {
+            var s: String [0]
+
+            block0:
+                s[0] = intrinsic concat("a", "b")
+                return 1
+        }
+    "#;
+    assert_eq!(error(concat), CodegenError::Unsupported {
+        function: "joins".to_string(),
+        construct: "concat of String".to_string(),
+    });
+
+    let missing = r#"
+        fn picks() -> Int {
+            var x: Int [0]
+
+            block0:
+                jump block1
+            block1:
+                x[0] = phi(block2: 1)
+                return x[0]
+        }
+    "#;
+    assert_eq!(error(missing), CodegenError::MissingPhiSource {
+        function: "picks".to_string(),
+        block: BlockId(1),
+        predecessor: BlockId(0),
+    });
+
+    let arity = r#"
+        fn one(n: Int [0] read) -> Int {
+            block0:
+                return n[0]
+        }
+
+        fn caller() -> Int {
+            var x: Int [1]
+
+            block0:
+                x[1] = call one()
+                return x[1]
+        }
+    "#;
+    assert_eq!(error(arity), CodegenError::ArityMismatch {
+        function: "one".to_string(),
+        expected: 1,
+        found: 0,
+    });
+}
+
+#[test]
+fn test_print_calls_printf() {
+    let source = r#"
+        fn show() -> Int {
+            var a: Int [0]
+            var b: UInt8 [1]
+            var f: Float32 [2]
+
+            block0:
+                a[0] = -42
+                b[1] = 200u8
+                f[2] = 2f32
+                intrinsic print(a[0])
+                intrinsic print(b[1])
+                intrinsic print(true)
+                intrinsic print(f[2])
+                intrinsic print("done")
+                return a[0]
+        }
+    "#;
+    let program = parse(source);
+    assert_eq!(both(&program, "show"), (Value::Integer(-42), Value::Integer(-42)));
+
+    let context = Context::create();
+    let codegen = compile(&context, &program).unwrap();
+    let ir = codegen.ir();
+    assert!(codegen.module().get_function("printf").is_some());
+    for format in [r#"c"%lld\0A\00""#, r#"c"%llu\0A\00""#, r#"c"%s\0A\00""#, r#"c"%.15g\0A\00""#] {
+        assert!(ir.contains(format), "{} missing from {}", format, ir);
+    }
+}
+
+#[test]
+fn test_executable_runs_main() {
+    let source = r#"
+        fn twice(n: Int [0] read) -> Int {
+            var r: Int [1]
+
+            block0:
+                r[1] = n[0] * 2
+                return r[1]
+        }
+
+        fn main() -> Int {
+            var x: Int [2]
+            var s: String [3]
+
+            block0:
+                x[2] = call twice(21)
+                intrinsic print(x[2])
+                s[3] = intrinsic clone("linked")
+                intrinsic print(s[3])
+                return 3
+        }
+    "#;
+    let context = Context::create();
+    let codegen = compile(&context, &parse(source)).unwrap();
+    assert!(codegen.module().get_function("custod_main").is_some());
+
+    let directory = std::env::temp_dir().join(format!("custod-link-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let (object, executable) = (directory.join("main.o"), directory.join("main"));
+    codegen.emit_object(&object).unwrap_or_else(|e| panic!("{}", e));
+    link(&[&object], &executable).unwrap_or_else(|e| panic!("{}", e));
+
+    let output = std::process::Command::new(&executable).output().unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\nlinked\n");
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn test_ir_and_bitcode_files() {
+    let source = r#"
+        fn seven() -> Int {
+            block0:
+                return 7
+        }
+    "#;
+    let context = Context::create();
+    let codegen = compile(&context, &parse(source)).unwrap();
+    let directory = std::env::temp_dir().join(format!("custod-emit-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let (ir, bitcode) = (directory.join("seven.ll"), directory.join("seven.bc"));
+    codegen.write_ir(&ir).unwrap();
+    codegen.write_bitcode(&bitcode).unwrap();
+
+    let text = std::fs::read_to_string(&ir).unwrap();
+    assert_eq!(text, codegen.ir());
+    assert!(text.contains(&format!("define i64 @{}()", codegen.symbol("seven"))), "{}", text);
+    let read_back = Module::parse_bitcode_from_path(&bitcode, &context).unwrap();
+    assert!(read_back.get_function(&codegen.symbol("seven")).is_some());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_optimization_levels() {
+    let source = r#"
+        fn sum() -> Int {
+            var i: Int [0]
+            var total: Int [1]
+            var more: Bool [2]
+
+            block0:
+                i[0] = 0
+                total[1] = 0
+                jump block1
+            block1:
+                more[2] = i[0] < 100
+                branch more[2] ? block2 : block3
+            block2:
+                total[1] = total[1] + i[0]
+                i[0] = i[0] + 1
+                jump block1
+            block3:
+                return total[1]
+        }
+    "#;
+    let program = parse(source);
+    for level in [OptimizationLevel::None, OptimizationLevel::Less, OptimizationLevel::Default, OptimizationLevel::Aggressive] {
+        let context = Context::create();
+        let mut codegen = compile(&context, &program).unwrap();
+        codegen.optimize(level).unwrap_or_else(|e| panic!("{}", e));
+        codegen.verify().unwrap();
+        let ir = codegen.ir();
+        assert_eq!(ir.contains("alloca"), level == OptimizationLevel::None, "{:?}: {}", level, ir);
+        assert_eq!(run(&codegen, &program, "sum"), Value::Integer(4950), "{:?}", level);
+    }
+}
+
+#[test]
+fn test_debug_info_describes_source() {
+    let source = "fn twice(reads n: Int) -> Int {\n    reads doubled = n * 2\n    return doubled\n}";
+    let mut parser = Parser::from_source(source);
+    let program = convert_hir_to_mir(&convert_statements_to_hir(parser.parse_statements()));
+    assert!(program.functions["twice"].location.is_some());
+
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context, "twice").with_debug_info(&["src/twice.cu".to_string()]);
+    codegen.compile(&program).unwrap_or_else(|e| panic!("{}", e));
+    codegen.verify().unwrap_or_else(|e| panic!("{}", e));
+    let ir = codegen.ir();
+    println!("{}", ir);
+    assert!(ir.contains(r#"!DIFile(filename: "twice.cu", directory: "src")"#), "{}", ir);
+    assert!(ir.contains(r#"!DISubprogram(name: "twice""#), "{}", ir);
+    assert!(ir.contains(r#"!DILocalVariable(name: "n", arg: 1"#), "{}", ir);
+    assert!(ir.contains(r#"!DILocalVariable(name: "doubled""#), "{}", ir);
+    assert!(ir.contains("!DILocation(line: 2"), "{}", ir);
+    assert!(!ir.contains(r#"!DILocalVariable(name: "temp_"#), "{}", ir);
+
+    // The object file carries the DWARF sections
+    let object = std::env::temp_dir().join(format!("custod-debug-{}.o", std::process::id()));
+    codegen.emit_object(&object).unwrap_or_else(|e| panic!("{}", e));
+    let bytes = std::fs::read(&object).unwrap();
+    std::fs::remove_file(&object).unwrap();
+    assert!(bytes.windows(11).any(|window| window == b".debug_info"));
+}
+
+#[test]
+fn test_run_jit() {
+    let source = r#"
+        fn main() -> Int {
+            var x: Int [0]
+
+            block0:
+                x[0] = 6 * 7
+                intrinsic print(x[0])
+                x[0] = x[0] - 35
+                return x[0]
+        }
+    "#;
+    let context = Context::create();
+    let mut codegen = compile(&context, &parse(source)).unwrap();
+    codegen.optimize(OptimizationLevel::Default).unwrap();
+    assert_eq!(codegen.run_jit(), Ok(7));
+
+    let context = Context::create();
+    let codegen = compile(&context, &parse("fn helper() -> Int {\n block0:\n return 1\n }")).unwrap();
+    assert!(matches!(codegen.run_jit(), Err(CodegenError::Jit(_))));
+}
+
+#[test]
+fn test_wasm_object_imports_print() {
+    let source = r#"
+        fn main() -> Int {
+            var x: Int [0]
+            var ok: Bool [1]
+
+            block0:
+                x[0] = 6 * 7
+                ok[1] = x[0] > 40
+                intrinsic print(x[0])
+                intrinsic print(ok[1])
+                intrinsic print("wasm")
+                return 0
+        }
+    "#;
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context, "main").with_platform(Platform::Wasm32);
+    codegen.compile(&parse(source)).unwrap_or_else(|e| panic!("{}", e));
+    codegen.verify().unwrap_or_else(|e| panic!("{}", e));
+    let ir = codegen.ir();
+    assert!(codegen.module().get_function("printf").is_none(), "{}", ir);
+    assert!(ir.contains(r#""wasm-import-module"="custod""#), "{}", ir);
+    assert!(ir.contains(r#""wasm-import-name"="print_bool""#), "{}", ir);
+    assert!(matches!(codegen.run_jit(), Err(CodegenError::Jit(_))));
+
+    let object = std::env::temp_dir().join(format!("custod-wasm-{}.o", std::process::id()));
+    codegen.emit_object(&object).unwrap_or_else(|e| panic!("{}", e));
+    let bytes = std::fs::read(&object).unwrap();
+    std::fs::remove_file(&object).unwrap();
+    assert!(bytes.starts_with(b"\0asm"));
+    for name in [&b"custod"[..], b"print_int", b"print_string"] {
+        assert!(bytes.windows(name.len()).any(|window| window == name));
+    }
+}
+
+#[test]
+fn test_threads_make_shared_variables_atomic() {
+    let source = r#"
+        var total: Int [0] heap writes
+        var limit: Int [1] heap reads
+
+        fn main() -> Int {
+            var view: Int [2] read
+            var local: Int [3] read write
+
+            block0:
+                total[0] = 40
+                limit[1] = 2
+                view[2] = peak total[0]
+                atomic begin
+                local[3] = view[2] + limit[1]
+                total[0] = local[3]
+                atomic end
+                local[3] = total[0] - 35
+                return local[3]
+        }
+    "#;
+    let program = parse(source);
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context, "main").with_threads();
+    codegen.compile(&program).unwrap_or_else(|e| panic!("{}", e));
+    codegen.verify().unwrap_or_else(|e| panic!("{}", e));
+    let ir = codegen.ir();
+    assert_eq!(ir.matches("store atomic i64 40").count(), 1, "{}", ir);
+    assert_eq!(ir.matches("seq_cst, align 8").count(), 2, "{}", ir);
+    assert_eq!(ir.matches("monotonic, align 8").count(), 2, "{}", ir);
+    assert_eq!(ir.matches("load atomic").count() + ir.matches("store atomic").count(), 4, "{}", ir);
+    assert!(ir.contains("call void @custod_rt_atomic_begin()"), "{}", ir);
+    assert!(ir.contains("call void @custod_rt_atomic_end()"), "{}", ir);
+    assert_eq!(codegen.run_jit(), Ok(7));
+
+    // Without threads nothing is atomic
+    let context = Context::create();
+    let codegen = compile(&context, &program).unwrap();
+    assert!(!codegen.ir().contains("atomic"), "{}", codegen.ir());
+
+    let flag = "var done: Bool [0] heap writes\nfn finish() {\n block0:\n done[0] = true\n return\n }";
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context, "flag").with_threads();
+    assert!(matches!(codegen.compile(&parse(flag)), Err(CodegenError::Unsupported { .. })));
+}
+
+#[test]
+fn test_behaviors_run_on_the_scheduler() {
+    let source = r#"
+        var total: Int [0] heap writes
+
+        fn add(n: Int [1] read, label: String [2] read) behavior {
+            var sum: Int [3] read write
+            var finished: Bool [4] read write
+
+            block0:
+                atomic begin
+                sum[3] = total[0] + n[1]
+                total[0] = sum[3]
+                atomic end
+                finished[4] = sum[3] == 5050
+                branch finished[4] ? block1 : block2
+            block1:
+                intrinsic print(label[2])
+                return
+            block2:
+                return
+        }
+
+        fn main() -> Int {
+            var i: Int [5] read write
+            var more: Bool [6] read write
+
+            block0:
+                total[0] = 0
+                i[5] = 1
+                jump block1
+            block1:
+                call add(i[5], "done")
+                i[5] = i[5] + 1
+                more[6] = i[5] <= 100
+                branch more[6] ? block1 : block2
+            block2:
+                return 3
+        }
+    "#;
+    let program = parse(source);
+    let context = Context::create();
+    let mut codegen = CodeGen::new(&context, "actors").with_threads();
+    codegen.compile(&program).unwrap_or_else(|e| panic!("{}", e));
+    codegen.verify().unwrap_or_else(|e| panic!("{}", e));
+    let ir = codegen.ir();
+    let add = codegen.symbol("add");
+    assert_eq!(add, "_CN6actors3addE");
+    assert!(ir.contains(&format!("@{}.mailbox = internal global", add)), "{}", ir);
+    assert!(ir.contains(&format!("define internal void @{}.message(", add)), "{}", ir);
+    assert!(ir.contains("call void @custod_rt_scheduler_start(i64 0)"), "{}", ir);
+    assert!(ir.contains("call void @custod_rt_send("), "{}", ir);
+    assert!(ir.contains("call void @custod_rt_scheduler_wait()"), "{}", ir);
+    let main = &ir[ir.find("define i64 @custod_main()").unwrap()..];
+    assert!(!main[..main.find("\n}").unwrap()].contains(&format!("call void @{}(", add)), "{}", ir);
+    assert_eq!(codegen.run_jit(), Ok(3));
+
+    // The sum is complete, and printed, only once every message has run
+    let directory = std::env::temp_dir().join(format!("custod-actors-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let (object, executable) = (directory.join("actors.o"), directory.join("actors"));
+    codegen.emit_object(&object).unwrap_or_else(|e| panic!("{}", e));
+    link(&[&object], &executable).unwrap_or_else(|e| panic!("{}", e));
+    let output = std::process::Command::new(&executable).output().unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "done\n");
+    assert_eq!(output.status.code(), Some(3));
+
+    // Without threads a behavior is an ordinary call
+    let context = Context::create();
+    let codegen = compile(&context, &program).unwrap();
+    assert!(codegen.ir().contains(&format!("call void @{}(", codegen.symbol("add"))), "{}", codegen.ir());
+    assert!(!codegen.ir().contains("custod_rt_send"), "{}", codegen.ir());
+}
@@ -0,0 +1,208 @@
+//! Checking generated IR against expectations written in the source
+//!
+//! As with LLVM's FileCheck, a MIR snippet says what its IR must contain
+//! in `//` comments, each a directive and a pattern:
+//!
+//! - `CHECK: p` matches `p` on a line after the last match
+//! - `CHECK-NEXT: p` matches on the line right after it
+//! - `CHECK-SAME: p` matches later on the same line
+//! - `CHECK-NOT: p` fails if `p` is between the last match and the next
+//! - `CHECK-LABEL: p` matches like `CHECK`, and the directives before it
+//!   only look at the lines before its match, so one function's checks
+//!   cannot match in the next
+//!
+//! Patterns match literally, with runs of spaces matching any run of
+//! spaces or tabs, except that `{{.*}}` matches anything on the line. Register
+//! numbers change with every lowering change, so patterns should name
+//! instructions and values, not registers.
+
+use crate::codegen::CodeGen;
+use inkwell::context::Context;
+use middle_end::mir::parse_mir;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Directive {
+    Check,
+    Next,
+    Same,
+    Not,
+    Label,
+}
+
+/// Compile the MIR `source` and check its IR against the directives in it
+pub(crate) fn check_ir(source: &str) {
+    check_ir_with(source, |codegen| codegen)
+}
+
+/// As `check_ir`, with the code generator set up by `configure`
+pub(crate) fn check_ir_with(source: &str, configure: impl FnOnce(CodeGen) -> CodeGen) {
+    let program = parse_mir(source).unwrap_or_else(|e| panic!("{}", e));
+    let context = Context::create();
+    let mut codegen = configure(CodeGen::new(&context, "check"));
+    codegen.compile(&program).unwrap_or_else(|e| panic!("{}", e));
+    codegen.verify().unwrap_or_else(|e| panic!("{}", e));
+    if let Err(message) = filecheck(&codegen.ir(), source) {
+        panic!("{}\n\nin the IR:\n{}", message, codegen.ir());
+    }
+}
+
+/// Match the directives in `checks` against `text`, returning what did
+/// not match
+pub(crate) fn filecheck(text: &str, checks: &str) -> Result<(), String> {
+    let directives = directives(checks)?;
+    if directives.is_empty() {
+        return Err("no CHECK directives".to_string());
+    }
+    let lines: Vec<&str> = text.lines().collect();
+
+    // Labels split the text first; other directives stay between them
+    let mut bounds = Vec::new();
+    let mut from = 0;
+    for (directive, pattern, _) in &directives {
+        if *directive == Directive::Label {
+            let line = (from..lines.len())
+                .find(|&line| find(lines[line], pattern).is_some())
+                .ok_or_else(|| failure(Directive::Label, pattern, from, &lines))?;
+            bounds.push(line);
+            from = line + 1;
+        }
+    }
+    bounds.push(lines.len());
+
+    // Where the last match ended: a line and a column in it
+    let mut position: Option<(usize, usize)> = None;
+    let mut not: Vec<&str> = Vec::new();
+    let mut labels = bounds.into_iter();
+    let mut end = labels.next().unwrap_or(lines.len());
+    for (directive, pattern, number) in &directives {
+        let start = position.map_or(0, |(line, _)| line + 1);
+        let found = match directive {
+            Directive::Not => {
+                not.push(pattern);
+                continue;
+            },
+            Directive::Label => {
+                let line = end;
+                end = labels.next().unwrap_or(lines.len());
+                find(lines[line], pattern).map(|column| (line, column))
+            },
+            Directive::Check => (start..end)
+                .find_map(|line| find(lines[line], pattern).map(|column| (line, column))),
+            Directive::Next => lines.get(start)
+                .filter(|_| start < end)
+                .and_then(|line| find(line, pattern))
+                .map(|column| (start, column)),
+            Directive::Same => position.and_then(|(line, column)| {
+                find(&lines[line][column..], pattern).map(|after| (line, column + after))
+            }),
+        };
+        let Some(found) = found else {
+            return Err(format!("line {}: {}", number, failure(*directive, pattern, start, &lines)));
+        };
+        // What CHECK-NOT rules out lies between the last match and this one
+        for pattern in not.drain(..) {
+            let between = (start..found.0).find(|&line| find(lines[line], pattern).is_some());
+            if let Some(line) = between {
+                return Err(format!("line {}: CHECK-NOT: {} found on line {}: {}", number, pattern, line + 1, lines[line]));
+            }
+        }
+        position = Some(found);
+    }
+    let start = position.map_or(0, |(line, _)| line + 1);
+    for pattern in not {
+        if let Some(line) = (start..lines.len()).find(|&line| find(lines[line], pattern).is_some()) {
+            return Err(format!("CHECK-NOT: {} found on line {}: {}", pattern, line + 1, lines[line]));
+        }
+    }
+    Ok(())
+}
+
+/// Every directive in `checks`, with its pattern and line number
+fn directives(checks: &str) -> Result<Vec<(Directive, &str, usize)>, String> {
+    let mut directives = Vec::new();
+    for (index, line) in checks.lines().enumerate() {
+        let Some((_, comment)) = line.split_once("//") else {
+            continue;
+        };
+        let comment = comment.trim();
+        let directive = [
+            ("CHECK:", Directive::Check),
+            ("CHECK-NEXT:", Directive::Next),
+            ("CHECK-SAME:", Directive::Same),
+            ("CHECK-NOT:", Directive::Not),
+            ("CHECK-LABEL:", Directive::Label),
+        ]
+        .into_iter()
+        .find_map(|(prefix, directive)| comment.strip_prefix(prefix).map(|pattern| (directive, pattern.trim())));
+        match directive {
+            Some((directive, "")) => return Err(format!("line {}: {:?} without a pattern", index + 1, directive)),
+            Some((directive, pattern)) => directives.push((directive, pattern, index + 1)),
+            None if comment.starts_with("CHECK") => return Err(format!("line {}: unknown directive: {}", index + 1, comment)),
+            None => {},
+        }
+    }
+    if directives.first().is_some_and(|(directive, _, _)| *directive == Directive::Same) {
+        return Err("CHECK-SAME before any match".to_string());
+    }
+    Ok(directives)
+}
+
+/// The column just after where `pattern` first matches in `line`
+fn find(line: &str, pattern: &str) -> Option<usize> {
+    let pieces: Vec<&str> = pattern.split("{{.*}}").collect();
+    (0..=line.len()).filter(|&start| line.is_char_boundary(start)).find_map(|start| {
+        let mut column = start;
+        for (index, piece) in pieces.iter().enumerate() {
+            column = match index {
+                0 => column + literal(&line[column..], piece)?,
+                _ => {
+                    let rest = &line[column..];
+                    let offset = (0..=rest.len())
+                        .filter(|&offset| rest.is_char_boundary(offset))
+                        .find(|&offset| literal(&rest[offset..], piece).is_some())?;
+                    column + offset + literal(&rest[offset..], piece)?
+                },
+            };
+        }
+        Some(column)
+    })
+}
+
+/// How much of the start of `text` the literal `piece` matches, with a
+/// run of spaces in it matching any run of spaces or tabs
+fn literal(text: &str, piece: &str) -> Option<usize> {
+    let mut matched = 0;
+    let mut rest = piece;
+    while !rest.is_empty() {
+        let remaining = &text[matched..];
+        if rest.starts_with(' ') {
+            let spaces = remaining.len() - remaining.trim_start_matches([' ', '\t']).len();
+            if spaces == 0 {
+                return None;
+            }
+            matched += spaces;
+            rest = rest.trim_start_matches(' ');
+        } else {
+            let word = rest.split(' ').next().unwrap_or(rest);
+            if !remaining.starts_with(word) {
+                return None;
+            }
+            matched += word.len();
+            rest = &rest[word.len()..];
+        }
+    }
+    Some(matched)
+}
+
+/// Why `directive` did not match `pattern` from line `start` on
+fn failure(directive: Directive, pattern: &str, start: usize, lines: &[&str]) -> String {
+    let name = match directive {
+        Directive::Check => "CHECK",
+        Directive::Next => "CHECK-NEXT",
+        Directive::Same => "CHECK-SAME",
+        Directive::Not => "CHECK-NOT",
+        Directive::Label => "CHECK-LABEL",
+    };
+    let near = lines.get(start).copied().unwrap_or("the end of the IR");
+    format!("{}: {} not found, looking from line {}: {}", name, pattern, start + 1, near)
+}
@@ -0,0 +1,293 @@
+//! Golden IR tests: MIR snippets with the IR they lower to in `CHECK`
+//! comments, matched by `filecheck`
+//!
+//! Pointer types differ between LLVM versions, `i64*` or `ptr`, so the
+//! patterns leave them to `{{.*}}`.
+
+use super::filecheck::{check_ir, check_ir_with, filecheck};
+
+#[test]
+fn test_filecheck_directives() {
+    let text = "define i64 @f() {\n  %1 = add  i64 %0, 1\n  ret i64 %1\n}\ndefine void @g() {\n  ret void\n}";
+    let passing = [
+        "// CHECK: add i64 %0, 1",
+        "// CHECK: define i64 @f()\n// CHECK-NEXT: %1 = add i64\n// CHECK-SAME: , 1\n// CHECK-NEXT: ret i64 %1",
+        "// CHECK: %1 = {{.*}} %0, 1",
+        "// CHECK-LABEL: @f()\n// CHECK-NOT: ret void\n// CHECK-LABEL: @g()\n// CHECK: ret void",
+    ];
+    for checks in passing {
+        assert_eq!(filecheck(text, checks), Ok(()), "{}", checks);
+    }
+    let failing = [
+        "",
+        "// CHECK: sub i64",
+        "// CHECK: define i64 @f()\n// CHECK-NEXT: ret i64",
+        "// CHECK: ret i64\n// CHECK: %1 = add",
+        "// CHECK: add\n// CHECK-SAME: @f",
+        "// CHECK-NOT: ret void",
+        "// CHECK-LABEL: @f()\n// CHECK: ret void\n// CHECK-LABEL: @g()",
+        "// CHECK-NEXT:",
+        "// CHECK-NXT: add",
+    ];
+    for checks in failing {
+        assert!(filecheck(text, checks).is_err(), "{}", checks);
+    }
+}
+
+#[test]
+fn test_ir_of_arithmetic() {
+    check_ir(r#"
+        // CHECK-LABEL: define i64 @_CN5check7averageE(i64 %0, i64 %1)
+        // CHECK-NEXT: entry:
+        // CHECK-NEXT: %x = alloca i64
+        // CHECK-NEXT: %y = alloca i64
+        // CHECK-NEXT: %r = alloca i64
+        // CHECK-NEXT: store i64 %0, {{.*}} %x
+        // CHECK-NEXT: store i64 %1, {{.*}} %y
+        // CHECK: add i64
+        // CHECK: icmp eq i64 %{{.*}}, 0
+        // CHECK-NEXT: br i1 %{{.*}}, label %division_by_zero, label %divide
+        // CHECK: division_by_zero:
+        // CHECK-NEXT: call void @llvm.trap()
+        // CHECK-NEXT: unreachable
+        // CHECK: sdiv i64
+        // CHECK: ret i64
+        fn average(x: Int [0] read, y: Int [1] read) -> Int {
+            var r: Int [2]
+
+            block0:
+                r[2] = x[0] + y[1]
+                r[2] = r[2] / y[1]
+                return r[2]
+        }
+    "#);
+}
+
+#[test]
+fn test_ir_of_calls_and_casts() {
+    check_ir(r#"
+        fn narrow(n: Int [0] read) -> Int8 {
+            var r: Int8 [1]
+
+            block0:
+                r[1] = n[0] as Int8
+                return r[1]
+        }
+
+        // CHECK-LABEL: define i64 @custod_main()
+        // CHECK: call i8 @_CN5check6narrowE(i64 300)
+        // CHECK: sext i8 {{.*}} to i64
+        // CHECK-LABEL: define i8 @_CN5check6narrowE(i64 %0)
+        // CHECK: trunc i64 {{.*}} to i8
+        // CHECK-LABEL: define i32 @main()
+        // CHECK-NEXT: entry:
+        // CHECK-NEXT: call i64 @custod_main()
+        // CHECK-NEXT: trunc i64 {{.*}} to i32
+        // CHECK-NEXT: call i32 @fflush({{.*}} null)
+        fn main() -> Int {
+            var small: Int8 [2]
+            var r: Int [3]
+
+            block0:
+                small[2] = call narrow(300)
+                r[3] = small[2] as Int
+                return r[3]
+        }
+    "#);
+}
+
+#[test]
+fn test_ir_of_phis() {
+    check_ir(r#"
+        // CHECK: block1:
+        // CHECK-NEXT: %{{.*}} = phi i64 [ 1, %block0 ], [ {{.*}}, %block2 ]
+        // CHECK-NEXT: store i64 {{.*}}, {{.*}} %x
+        // CHECK: block2:
+        // CHECK: br label %block1
+        fn count() -> Int {
+            var x: Int [0]
+            var more: Bool [1]
+            var next: Int [2]
+
+            block0:
+                jump block1
+            block1:
+                x[0] = phi(block0: 1, block2: next[2])
+                more[1] = x[0] < 10
+                branch more[1] ? block2 : block3
+            block2:
+                next[2] = x[0] + 1
+                jump block1
+            block3:
+                return x[0]
+        }
+    "#);
+}
+
+#[test]
+fn test_ir_of_print() {
+    check_ir(r#"
+        // CHECK: @format = private unnamed_addr constant [6 x i8] c"%lld\0A\00"
+        // CHECK-LABEL: define void @_CN5check4showE(i64 %0)
+        // CHECK: call i32 ({{.*}}, ...) @printf({{.*}}@format{{.*}}, i64
+        fn show(n: Int [0] read) {
+            block0:
+                intrinsic print(n[0])
+                return
+        }
+    "#);
+}
+
+#[test]
+fn test_ir_of_top_level_statements() {
+    check_ir(r#"
+        // CHECK-LABEL: define void @custod_init()
+        // CHECK: store i64 5, {{.*}} @total
+        // CHECK-LABEL: define i32 @main()
+        // CHECK-NEXT: entry:
+        // CHECK-NEXT: call void @custod_init()
+        // CHECK-NEXT: %{{.*}} = call i64 @custod_main()
+        var total: Int [0] heap reads writes
+
+        fn $init() {
+            block0:
+                total[0] = 5
+                return
+        }
+
+        fn main() -> Int {
+            block0:
+                return total[0]
+        }
+    "#);
+}
+
+#[test]
+fn test_ir_of_peak_views() {
+    check_ir(r#"
+        // CHECK: %a = alloca i64
+        // CHECK: %v = alloca i64
+        // CHECK-NEXT: %v.view = alloca
+        // CHECK-NEXT: store {{.*}} %v, {{.*}} %v.view
+        // CHECK: block0:
+        // CHECK-NEXT: store i64 1, {{.*}} %a
+        // CHECK-NEXT: store {{.*}} %a, {{.*}} %v.view
+        // CHECK-NEXT: %{{.*}} = load {{.*}} %v.view
+        // CHECK-NEXT: store i64 5, {{.*}} %
+        fn share() -> Int {
+            var a: Int [0]
+            var v: Int [1] read
+
+            block0:
+                a[0] = 1
+                v[1] = peak a[0]
+                v[1] = 5
+                return a[0]
+        }
+    "#);
+}
+
+#[test]
+fn test_ir_of_write_barriers() {
+    let source = |fence: &str| format!(r#"
+        // CHECK-LABEL: define i64 @_CN5check5shareE()
+        // CHECK: store i64 1,
+        // CHECK-NOT: fence
+        // CHECK: {}
+        // CHECK-NEXT: store i64 2,
+        // CHECK-NOT: fence
+        // CHECK: ret i64
+        fn share() -> Int {{
+            var a: Int [0] read write
+            var v: Int [1] reads
+
+            block0:
+                a[0] = 1
+                v[1] = peak a[0]
+                barrier a[0]
+                a[0] = 2
+                return v[1]
+        }}
+    "#, fence);
+    check_ir(&source(r#"fence syncscope("singlethread") release"#));
+    check_ir_with(&source("fence release"), |codegen| codegen.with_threads());
+}
+
+#[test]
+fn test_ir_of_clone() {
+    // Every clone of a string calls the one copy routine of the module.
+    // The copy in `s` never leaves the function, so it is freed before the
+    // return; the one in `u` is returned, so it is the caller's.
+    check_ir(r#"
+        // CHECK-LABEL: define {{.*}} @_CN5check4copyE(
+        // CHECK: store {{.*}} null, {{.*}} %s
+        // CHECK: call {{.*}} @custod.clone.String(
+        // CHECK: call {{.*}} @custod.clone.String(
+        // CHECK: load {{.*}} %s
+        // CHECK-NEXT: call void @custod_rt_free(
+        // CHECK-NEXT: ret
+        // CHECK-LABEL: define internal {{.*}} @custod.clone.String(
+        // CHECK-NEXT: entry:
+        // CHECK-NEXT: call {{.*}} @custod_rt_clone_string(
+        // CHECK-NOT: define
+        fn copy(t: String [0] reads) -> String {
+            var s: String [1] reads
+            var u: String [2] reads
+
+            block0:
+                s[1] = intrinsic clone(t[0])
+                u[2] = intrinsic clone(s[1])
+                return u[2]
+        }
+    "#);
+}
+
+#[test]
+fn test_ir_of_threads() {
+    let source = r#"
+        // CHECK: @total = global i64 0
+        // CHECK: @_CN5check4bumpE.mailbox = internal global {{.*}} null
+        // CHECK-LABEL: define void @_CN5check4bumpE(i64 %0)
+        // CHECK: call void @custod_rt_atomic_begin()
+        // CHECK-NEXT: load atomic i64, {{.*}} @total monotonic, align 8
+        // CHECK-NOT: load atomic
+        // CHECK: store atomic i64 {{.*}} @total monotonic, align 8
+        // CHECK-NEXT: call void @custod_rt_atomic_end()
+        // CHECK-LABEL: define i64 @custod_main()
+        // CHECK: store atomic i64 0, {{.*}} @total seq_cst, align 8
+        // CHECK: call {{.*}} @custod_rt_alloc(
+        // CHECK: load {{.*}} @_CN5check4bumpE.mailbox
+        // CHECK-NEXT: call void @custod_rt_send(
+        // CHECK-NOT: call void @_CN5check4bumpE(
+        // CHECK-LABEL: define internal void @_CN5check4bumpE.message(
+        // CHECK: call void @_CN5check4bumpE(i64
+        // CHECK-NEXT: ret void
+        // CHECK-LABEL: define i32 @main()
+        // CHECK-NEXT: entry:
+        // CHECK-NEXT: call void @custod_rt_scheduler_start(i64 0)
+        // CHECK-NEXT: %{{.*}} = call {{.*}} @custod_rt_mailbox_new()
+        // CHECK-NEXT: store {{.*}} @_CN5check4bumpE.mailbox
+        // CHECK-NEXT: call i64 @custod_main()
+        // CHECK-NEXT: call void @custod_rt_scheduler_wait()
+        var total: Int [0] heap writes
+
+        fn bump(n: Int [1] read) behavior {
+            var sum: Int [2] read write
+
+            block0:
+                atomic begin
+                sum[2] = total[0] + n[1]
+                total[0] = sum[2]
+                atomic end
+                return
+        }
+
+        fn main() -> Int {
+            block0:
+                total[0] = 0
+                call bump(2)
+                return 0
+        }
+    "#;
+    check_ir_with(source, |codegen| codegen.with_threads());
+}
@@ -0,0 +1,63 @@
+//! Tests for mangling symbol names
+
+use crate::codegen::CodeGen;
+use crate::link::link;
+use crate::mangle::{demangle, demangle_symbols, mangle, Symbol};
+use inkwell::context::Context;
+use middle_end::mir::parse_mir;
+
+#[test]
+fn test_mangle_round_trips() {
+    let names = [
+        ("main", "helper", "_CN4main6helperE", "main::helper"),
+        ("main", "outer$inner", "_CN4main5outer5innerE", "main::outer::inner"),
+        ("main", "pair$Int$Bool", "_CN4main4pairI3Int4BoolEE", "main::pair<Int, Bool>"),
+        ("main", "outer$inner$String", "_CN4main5outer5innerI6StringEE", "main::outer::inner<String>"),
+        ("2d", "_area", "_CN2_2d5__areaE", "2d::_area"),
+    ];
+    for (module, name, symbol, shown) in names {
+        assert_eq!(mangle(module, name), symbol);
+        let demangled = demangle(symbol).unwrap_or_else(|| panic!("{} does not demangle", symbol));
+        assert_eq!(demangled, Symbol::from_mir(module, name));
+        assert_eq!(demangled.to_string(), shown);
+        assert_eq!(demangled.mangle(), symbol);
+    }
+
+    for symbol in ["helper", "_CN4mainE", "_CN4main6helper", "_CN4main6helperIEE", "_CN4main9helperE", "_CN4main6helperEE"] {
+        assert_eq!(demangle(symbol), None, "{}", symbol);
+    }
+}
+
+#[test]
+fn test_demangle_symbols_in_linker_messages() {
+    let message = "undefined reference to `_CN4main5outer5innerE'\nin _CN4main4pairI3IntEE+0x1c, _CN4oops";
+    assert_eq!(
+        demangle_symbols(message),
+        "undefined reference to `main::outer::inner'\nin main::pair<Int>+0x1c, _CN4oops",
+    );
+}
+
+#[test]
+fn test_functions_of_one_name_link_from_two_modules() {
+    let library = "fn helper() -> Int {\n block0:\n return 1\n }";
+    let program = "fn helper() -> Int {\n block0:\n return 2\n }\n\
+                   fn main() -> Int {\n var r: Int [0]\n block0:\n r[0] = call helper()\n return r[0]\n }";
+    let directory = std::env::temp_dir().join(format!("custod-mangle-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let context = Context::create();
+    let mut objects = Vec::new();
+    for (module, source) in [("library", library), ("program", program)] {
+        let mut codegen = CodeGen::new(&context, module);
+        codegen.compile(&parse_mir(source).unwrap()).unwrap_or_else(|e| panic!("{}", e));
+        assert!(codegen.module().get_function(&format!("_CN{}{}6helperE", module.len(), module)).is_some());
+        let object = directory.join(format!("{}.o", module));
+        codegen.emit_object(&object).unwrap_or_else(|e| panic!("{}", e));
+        objects.push(object);
+    }
+    let executable = directory.join("program");
+    let objects: Vec<_> = objects.iter().map(|object| object.as_path()).collect();
+    link(&objects, &executable).unwrap_or_else(|e| panic!("{}", e));
+    let status = std::process::Command::new(&executable).status().unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert_eq!(status.code(), Some(2));
+}
@@ -1,22 +1,11 @@
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use middle_end::mir::{MirFunction, MirInstruction, MirValue};
+//! Tests for the back end
+//!
+//! Each test lowers MIR, usually written in the textual format, and checks
+//! the module LLVM builds from it, except for those of the symbols
+//! functions are named by. `filecheck` matches the IR against
+//! expectations written in the MIR, for the golden tests in `ir_tests`.
 
-    #[test]
-    fn test_basic_codegen() {
-        let context = Context::create();
-        let mut codegen = CodeGen::new(&context, "test");
-
-        let mir = MirFunction {
-            instructions: vec![
-                MirInstruction::WriteBarrier { reference: "counter".to_string() },
-                MirInstruction::Load { target: 0, value: MirValue::Number(2) },
-                MirInstruction::Store { target: "counter".to_string(), value: MirValue::Temporary(0) },
-            ]
-        };
-
-        codegen.compile(&mir);
-        assert!(codegen.module.verify().is_ok());
-    }
-}
\ No newline at end of file
+mod codegen_tests;
+mod filecheck;
+mod ir_tests;
+mod mangle_tests;
@@ -0,0 +1,594 @@
+//! C source generation from MIR
+//!
+//! `to_c` writes a program as one C99 file that needs only the C library,
+//! for platforms LLVM does not target and for reading what a checked
+//! program does. It computes and prints what `codegen` does.
+
+use crate::{symbol, CodegenError};
+use crate::ownership;
+use front_end::types::{NumericKind, Permission, Type};
+use middle_end::mir::types::{
+    BinaryOperation, BlockId, Constant, Instruction, Intrinsic, MirFunction, MirProgram, Operand, Terminator, VarId,
+    INIT_FUNCTION,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Words C gives a meaning that globals and functions may not be named
+const C_KEYWORDS: [&str; 37] = [
+    "auto", "bool", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "false", "float", "for", "goto", "if", "inline", "int", "long", "main", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "true", "typedef",
+    "union", "unsigned", "void", "while",
+];
+
+/// The program as C source. Each MIR function becomes a C function of the
+/// same name, and a program's `main` is named `custod_main` with a C `main`
+/// calling it after `custod_init`, which runs the global initializers and
+/// other top-level statements. The interpreter's integers wrap, which
+/// signed C integers only do when compiled with `-fwrapv`, and floats use
+/// `<math.h>`, so programs need `-lm` on some systems.
+pub fn to_c(program: &MirProgram) -> Result<String, CodegenError> {
+    let mut output = String::new();
+    // For `strdup`, which C99 leaves to POSIX
+    writeln!(output, "#define _POSIX_C_SOURCE 200809L").unwrap();
+    writeln!(output, "#include <math.h>").unwrap();
+    writeln!(output, "#include <stdbool.h>").unwrap();
+    writeln!(output, "#include <stdint.h>").unwrap();
+    writeln!(output, "#include <stdio.h>").unwrap();
+    writeln!(output, "#include <stdlib.h>").unwrap();
+    writeln!(output, "#include <string.h>").unwrap();
+
+    let mut globals: Vec<_> = program.globals.iter().collect();
+    globals.sort_by_key(|(_, var)| var.id.0);
+    if !globals.is_empty() {
+        writeln!(output).unwrap();
+    }
+    for (name, var) in &globals {
+        let typ = c_type(&var.typ).ok_or_else(|| CodegenError::Unsupported {
+            function: name.to_string(),
+            construct: format!("globals of type {:?}", var.typ),
+        })?;
+        writeln!(output, "{} {};{}", typ, global_name(name), permissions_comment(&var.permissions)).unwrap();
+    }
+
+    let mut functions: Vec<&MirFunction> = program.functions.values().collect();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    writeln!(output).unwrap();
+    for func in &functions {
+        writeln!(output, "{};", signature(func)?).unwrap();
+    }
+    for func in &functions {
+        writeln!(output).unwrap();
+        FunctionWriter::new(program, func).write(&mut output)?;
+    }
+
+    let main = program.functions.get("main");
+    if let Some(main) = main.filter(|main| !main.parameters.is_empty()) {
+        return Err(unsupported(main, "a main that takes parameters"));
+    }
+    let init = program.functions.contains_key(INIT_FUNCTION);
+    if main.is_some() || init {
+        writeln!(output).unwrap();
+        writeln!(output, "int main(void) {{").unwrap();
+        if init {
+            writeln!(output, "    {}();", symbol(INIT_FUNCTION)).unwrap();
+        }
+        match main.map(|main| main.return_type.as_ref().and_then(c_type)) {
+            Some(Some(_)) => writeln!(output, "    return (int){}();", symbol("main")).unwrap(),
+            Some(None) => writeln!(output, "    {}();\n    return 0;", symbol("main")).unwrap(),
+            None => writeln!(output, "    return 0;").unwrap(),
+        }
+        writeln!(output, "}}").unwrap();
+    }
+    Ok(output)
+}
+
+/// Writes the definition of one function
+///
+/// Every variable is declared at the top of the function as `name_id`, with
+/// its permissions in a comment, and each block becomes a label that jumps
+/// and branches `goto`. The target of a `peak` is a variable declared by
+/// it, so it is written as the variable it aliases throughout; atomic
+/// blocks are marked by comments.
+struct FunctionWriter<'a> {
+    program: &'a MirProgram,
+    func: &'a MirFunction,
+
+    /// The variable each `peak` target is another name for
+    aliases: HashMap<VarId, VarId>,
+
+    /// The C name of each global, by id
+    globals: HashMap<VarId, String>,
+
+    /// The variables holding string copies the function frees, as
+    /// `ownership` decides
+    owned: Vec<VarId>,
+}
+
+impl<'a> FunctionWriter<'a> {
+    fn new(program: &'a MirProgram, func: &'a MirFunction) -> Self {
+        let mut aliases = HashMap::new();
+        for instruction in func.blocks.iter().flat_map(|block| &block.instructions) {
+            if let Instruction::Peak { target, source } = instruction {
+                aliases.insert(*target, *source);
+            }
+        }
+        let globals = program.globals.iter()
+            .map(|(name, var)| (var.id, global_name(name)))
+            .collect();
+        let owned = ownership::owned_copies(func);
+        FunctionWriter { program, func, aliases, globals, owned }
+    }
+
+    fn write(&self, output: &mut String) -> Result<(), CodegenError> {
+        let func = self.func;
+        writeln!(output, "{} {{", signature(func)?).unwrap();
+
+        let parameters: HashSet<VarId> = func.parameters.iter().map(|(var, _)| *var).collect();
+        let mut variables: Vec<_> = func.variables.values()
+            .filter(|var| !parameters.contains(&var.id) && !self.aliases.contains_key(&var.id))
+            .collect();
+        variables.sort_by_key(|var| var.id.0);
+        for var in &variables {
+            let typ = c_type(&var.typ).ok_or_else(|| unsupported(func, &format!("variables of type {:?}", var.typ)))?;
+            let initializer = if self.owned.contains(&var.id) { " = NULL" } else { "" };
+            writeln!(output, "    {} {}{};{}", typ, self.name(var.id)?, initializer, permissions_comment(&var.permissions)).unwrap();
+        }
+
+        // Only blocks something jumps to need a label
+        let mut targets: HashSet<BlockId> = func.blocks.iter()
+            .flat_map(|block| block.terminator.successors())
+            .collect();
+        if func.blocks.first().is_some_and(|block| block.id != func.entry_block) {
+            targets.insert(func.entry_block);
+            writeln!(output, "    goto block{};", func.entry_block.0).unwrap();
+        }
+        for block in &func.blocks {
+            if targets.contains(&block.id) {
+                writeln!(output, "block{}:", block.id.0).unwrap();
+            }
+            for instruction in &block.instructions {
+                self.write_instruction(instruction, output)?;
+            }
+            self.write_terminator(block.id, &block.terminator, output)?;
+        }
+        writeln!(output, "}}").unwrap();
+        Ok(())
+    }
+
+    fn write_instruction(&self, instruction: &Instruction, output: &mut String) -> Result<(), CodegenError> {
+        let func = self.func;
+        match instruction {
+            Instruction::Assign { target, source } |
+            Instruction::Cast { target, source } => {
+                let value = self.converted(source, &self.variable_type(*target)?)?;
+                writeln!(output, "    {} = {};", self.name(*target)?, value).unwrap();
+            },
+            Instruction::BinaryOp { target, left, op, right } => {
+                let typ = self.operand_type(left)?;
+                let (l, r) = (self.operand(left)?, self.operand(right)?);
+                let value = self.binary_op(*op, &typ, &l, &r, output)?;
+                let result_type = if op.is_comparison() { Type::Bool } else { typ };
+                let value = self.convert(value, &result_type, &self.variable_type(*target)?)?;
+                writeln!(output, "    {} = {};", self.name(*target)?, value).unwrap();
+            },
+            Instruction::Intrinsic { intrinsic: Intrinsic::Print, arguments, .. } => {
+                let [argument] = arguments.as_slice() else {
+                    return Err(unsupported(func, "print without exactly one argument"));
+                };
+                let typ = self.operand_type(argument)?;
+                let value = self.operand(argument)?;
+                // The formats `codegen` gives `printf`
+                let statement = match typ {
+                    Type::Bool => format!("puts({} ? \"true\" : \"false\")", value),
+                    Type::String => format!("puts({})", value),
+                    _ => match typ.numeric_kind() {
+                        Some(NumericKind::Signed) => format!("printf(\"%lld\\n\", (long long){})", value),
+                        Some(NumericKind::Unsigned) => format!("printf(\"%llu\\n\", (unsigned long long){})", value),
+                        Some(NumericKind::Float) => format!("printf(\"%.15g\\n\", (double){})", value),
+                        None => return Err(unsupported(func, &format!("print of {:?}", typ))),
+                    },
+                };
+                writeln!(output, "    {};", statement).unwrap();
+            },
+            Instruction::Intrinsic { target, intrinsic, arguments } => {
+                let Some(first) = arguments.first() else {
+                    return Err(unsupported(func, &format!("{} without arguments", intrinsic.name())));
+                };
+                let typ = self.operand_type(first)?;
+                let arguments = arguments.iter()
+                    .map(|argument| self.operand(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let value = self.intrinsic(*intrinsic, &typ, &arguments)?;
+                if let Some(target) = target.filter(|target| self.owned.contains(target)) {
+                    self.write_free(target, "    ", output)?;
+                }
+                match (target, intrinsic.result_type(&typ)) {
+                    (Some(target), Some(result_type)) => {
+                        let value = self.convert(value, &result_type, &self.variable_type(*target)?)?;
+                        writeln!(output, "    {} = {};", self.name(*target)?, value).unwrap();
+                    },
+                    _ => writeln!(output, "    {};", value).unwrap(),
+                }
+            },
+            Instruction::Peak { target, source } => {
+                let var = func.variables.get(target)
+                    .ok_or_else(|| CodegenError::UnknownVariable { function: func.name.clone(), var: *target })?;
+                writeln!(output, "    /* {} peaks at {} */", var.name.as_str(), self.name(*source)?).unwrap();
+            },
+            Instruction::Call { target, function, arguments } => {
+                let callee = self.program.functions.get(function)
+                    .ok_or_else(|| CodegenError::UnknownFunction(function.clone()))?;
+                if callee.parameters.len() != arguments.len() {
+                    return Err(CodegenError::ArityMismatch {
+                        function: function.clone(),
+                        expected: callee.parameters.len(),
+                        found: arguments.len(),
+                    });
+                }
+                let arguments = callee.parameters.iter().zip(arguments)
+                    .map(|((_, typ), argument)| self.converted(argument, typ))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let call = format!("{}({})", symbol(function), arguments.join(", "));
+                match (target, callee.return_type.as_ref().filter(|typ| c_type(typ).is_some())) {
+                    (Some(target), Some(typ)) => {
+                        let value = self.convert(call, typ, &self.variable_type(*target)?)?;
+                        writeln!(output, "    {} = {};", self.name(*target)?, value).unwrap();
+                    },
+                    _ => writeln!(output, "    {};", call).unwrap(),
+                }
+            },
+            // Assigned by the jumps and branches to the block
+            Instruction::Phi { .. } => {},
+            Instruction::AtomicBegin => writeln!(output, "    /* atomic {{ */").unwrap(),
+            Instruction::AtomicEnd => writeln!(output, "    /* }} atomic */").unwrap(),
+            // A peak target is written as the variable it aliases, on one
+            // thread, so the new value is seen through it at once
+            Instruction::WriteBarrier { variable } => {
+                writeln!(output, "    /* write barrier for {} */", self.name(*variable)?).unwrap();
+            },
+            Instruction::Nop => {},
+        }
+        Ok(())
+    }
+
+    fn write_terminator(&self, block: BlockId, terminator: &Terminator, output: &mut String) -> Result<(), CodegenError> {
+        let func = self.func;
+        match terminator {
+            Terminator::Return(operand) => {
+                let result_type = func.return_type.as_ref().filter(|typ| c_type(typ).is_some());
+                match (operand, result_type) {
+                    (Some(operand), Some(result_type)) => {
+                        let value = self.converted(operand, result_type)?;
+                        for &var in &self.owned {
+                            self.write_free(var, "    ", output)?;
+                        }
+                        writeln!(output, "    return {};", value).unwrap();
+                    },
+                    _ => {
+                        for &var in &self.owned {
+                            self.write_free(var, "    ", output)?;
+                        }
+                        writeln!(output, "    return;").unwrap();
+                    },
+                }
+            },
+            Terminator::Jump(target) => self.write_goto(block, *target, "    ", output)?,
+            Terminator::Branch { condition, true_block, false_block } => {
+                writeln!(output, "    if ({}) {{", self.operand(condition)?).unwrap();
+                self.write_goto(block, *true_block, "        ", output)?;
+                writeln!(output, "    }} else {{").unwrap();
+                self.write_goto(block, *false_block, "        ", output)?;
+                writeln!(output, "    }}").unwrap();
+            },
+        }
+        Ok(())
+    }
+
+    /// Jump from `block` to `successor`, assigning the phis of `successor`
+    /// their operands for `block` first, all at once through temporaries,
+    /// so they read the values of `block` as in the interpreter
+    fn write_goto(&self, block: BlockId, successor: BlockId, indent: &str, output: &mut String) -> Result<(), CodegenError> {
+        let func = self.func;
+        let Some(successor_block) = func.block(successor) else {
+            return Err(CodegenError::UnknownBlock { function: func.name.clone(), block: successor });
+        };
+        let mut assignments = Vec::new();
+        for instruction in &successor_block.instructions {
+            let Instruction::Phi { target, sources } = instruction else {
+                continue;
+            };
+            let (_, source) = sources.iter().find(|(predecessor, _)| *predecessor == block)
+                .ok_or_else(|| CodegenError::MissingPhiSource {
+                    function: func.name.clone(),
+                    block: successor,
+                    predecessor: block,
+                })?;
+            let typ = self.variable_type(*target)?;
+            assignments.push((*target, typ.clone(), self.converted(source, &typ)?));
+        }
+
+        // One phi needs no temporary; several are read before any is
+        // assigned, since one may read another
+        match assignments.as_slice() {
+            [] => {},
+            [(target, _, value)] => writeln!(output, "{}{} = {};", indent, self.name(*target)?, value).unwrap(),
+            _ => {
+                writeln!(output, "{}{{", indent).unwrap();
+                for (index, (_, typ, value)) in assignments.iter().enumerate() {
+                    let typ = c_type(typ).ok_or_else(|| unsupported(func, &format!("phis of type {:?}", typ)))?;
+                    writeln!(output, "{}    {} phi{} = {};", indent, typ, index, value).unwrap();
+                }
+                for (index, (target, _, _)) in assignments.iter().enumerate() {
+                    writeln!(output, "{}    {} = phi{};", indent, self.name(*target)?, index).unwrap();
+                }
+                writeln!(output, "{}}}", indent).unwrap();
+            },
+        }
+        writeln!(output, "{}goto block{};", indent, successor.0).unwrap();
+        Ok(())
+    }
+
+    /// Free the string copy `var` holds, if it holds one
+    fn write_free(&self, var: VarId, indent: &str, output: &mut String) -> Result<(), CodegenError> {
+        writeln!(output, "{}free((void *){});", indent, self.name(var)?).unwrap();
+        Ok(())
+    }
+
+    /// The C name of a local of the function or of a global, following
+    /// peaks to the variable they alias
+    fn name(&self, var: VarId) -> Result<String, CodegenError> {
+        let mut var = var;
+        while let Some(source) = self.aliases.get(&var) {
+            var = *source;
+        }
+        if let Some(local) = self.func.variables.get(&var) {
+            return Ok(format!("{}_{}", identifier(local.name.as_str()), var.0));
+        }
+        self.globals.get(&var)
+            .cloned()
+            .ok_or_else(|| CodegenError::UnknownVariable { function: self.func.name.clone(), var })
+    }
+
+    /// The type of a local of the function or of a global
+    fn variable_type(&self, var: VarId) -> Result<Type, CodegenError> {
+        self.func.variables.get(&var)
+            .or_else(|| self.program.globals.values().find(|global| global.id == var))
+            .map(|var| var.typ.clone())
+            .ok_or_else(|| CodegenError::UnknownVariable { function: self.func.name.clone(), var })
+    }
+
+    fn operand_type(&self, operand: &Operand) -> Result<Type, CodegenError> {
+        match operand {
+            Operand::Variable(var) => self.variable_type(*var),
+            Operand::Constant(constant) => Ok(constant.typ()),
+        }
+    }
+
+    /// An operand as a C expression
+    fn operand(&self, operand: &Operand) -> Result<String, CodegenError> {
+        let expression = match operand {
+            Operand::Variable(var) => self.name(*var)?,
+            Operand::Constant(Constant::Integer(value, typ)) => integer(*value, typ),
+            Operand::Constant(Constant::Boolean(value)) => value.to_string(),
+            Operand::Constant(Constant::String(id)) => string_literal(self.program.strings.get(*id)),
+        };
+        Ok(expression)
+    }
+
+    /// An operand as a C expression of type `to`
+    fn converted(&self, operand: &Operand, to: &Type) -> Result<String, CodegenError> {
+        let from = self.operand_type(operand)?;
+        self.convert(self.operand(operand)?, &from, to)
+    }
+
+    /// `expression` of type `from` cast to `to` when they differ; C's
+    /// conversions wrap integers and truncate floats towards zero as
+    /// `codegen` does
+    fn convert(&self, expression: String, from: &Type, to: &Type) -> Result<String, CodegenError> {
+        let invalid = || CodegenError::InvalidConversion { from: from.clone(), to: to.clone() };
+        let target = c_type(to).ok_or_else(invalid)?;
+        if from == to {
+            return Ok(expression);
+        }
+        if from.numeric_kind().is_none() || to.numeric_kind().is_none() {
+            return Err(invalid());
+        }
+        match expression.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+            true => Ok(format!("({}){}", target, expression)),
+            false => Ok(format!("({})({})", target, expression)),
+        }
+    }
+
+    /// `left op right` for operands of type `typ`. Division writes its
+    /// check for a zero divisor, which calls `abort`, to `output` first.
+    fn binary_op(&self, op: BinaryOperation, typ: &Type, left: &str, right: &str, output: &mut String) -> Result<String, CodegenError> {
+        use BinaryOperation::*;
+
+        let invalid = || CodegenError::InvalidOperands { op, typ: typ.clone() };
+        let kind = typ.numeric_kind();
+        let operator = match op {
+            Add => "+",
+            Subtract => "-",
+            Multiply => "*",
+            Equal => "==",
+            NotEqual => "!=",
+            LessThan => "<",
+            LessThanEqual => "<=",
+            GreaterThan => ">",
+            GreaterThanEqual => ">=",
+            And => "&&",
+            Or => "||",
+            Divide | Remainder => match kind {
+                Some(NumericKind::Float) if op == Divide => "/",
+                Some(NumericKind::Float) => {
+                    let fmod = if *typ == Type::Float32 { "fmodf" } else { "fmod" };
+                    return Ok(format!("{}({}, {})", fmod, left, right));
+                },
+                // Dividing the smallest value by -1 wraps as in the
+                // interpreter instead of trapping
+                Some(NumericKind::Signed) => {
+                    writeln!(output, "    if ({} == 0) abort();", right).unwrap();
+                    return Ok(match op {
+                        Divide => format!("{} == -1 ? -{} : {} / {}", right, left, left, right),
+                        _ => format!("{} == -1 ? 0 : {} % {}", right, left, right),
+                    });
+                },
+                Some(NumericKind::Unsigned) => {
+                    writeln!(output, "    if ({} == 0) abort();", right).unwrap();
+                    if op == Divide { "/" } else { "%" }
+                },
+                None => return Err(invalid()),
+            },
+        };
+        let valid = match kind {
+            _ if *typ == Type::Bool => matches!(op, And | Or | Equal | NotEqual),
+            Some(_) => !matches!(op, And | Or),
+            None => false,
+        };
+        if !valid {
+            return Err(invalid());
+        }
+        Ok(format!("{} {} {}", left, operator, right))
+    }
+
+    /// A built-in operation on `arguments`, the first of which is of type
+    /// `typ`, as a C expression. A `clone` of a string is a `strdup`, freed
+    /// when the function returns as in `codegen`; other string operations
+    /// are reported as unsupported.
+    fn intrinsic(&self, intrinsic: Intrinsic, typ: &Type, arguments: &[String]) -> Result<String, CodegenError> {
+        let suffix = if *typ == Type::Float32 { "f" } else { "" };
+        let expression = match (intrinsic, typ.numeric_kind(), arguments) {
+            (Intrinsic::Abs, Some(NumericKind::Signed), [value]) => format!("{} < 0 ? -{} : {}", value, value, value),
+            (Intrinsic::Abs, Some(NumericKind::Unsigned), [value]) => value.clone(),
+            (Intrinsic::Abs, Some(NumericKind::Float), [value]) => format!("fabs{}({})", suffix, value),
+            (Intrinsic::Min, Some(NumericKind::Float), [l, r]) => format!("fmin{}({}, {})", suffix, l, r),
+            (Intrinsic::Max, Some(NumericKind::Float), [l, r]) => format!("fmax{}({}, {})", suffix, l, r),
+            (Intrinsic::Min, Some(_), [l, r]) => format!("{} < {} ? {} : {}", l, r, l, r),
+            (Intrinsic::Max, Some(_), [l, r]) => format!("{} > {} ? {} : {}", l, r, l, r),
+            (Intrinsic::Clone, _, [value]) if *typ == Type::String => format!("strdup({})", value),
+            (Intrinsic::Clone, _, [value]) => value.clone(),
+            _ => return Err(unsupported(self.func, &format!("{} of {:?}", intrinsic.name(), typ))),
+        };
+        Ok(expression)
+    }
+}
+
+/// The C declaration of `func`, without a body
+fn signature(func: &MirFunction) -> Result<String, CodegenError> {
+    let result = func.return_type.as_ref().and_then(c_type).unwrap_or("void");
+    let parameters = func.parameters.iter()
+        .map(|(var, typ)| {
+            let typ = c_type(typ).ok_or_else(|| unsupported(func, &format!("parameters of type {:?}", typ)))?;
+            let name = func.variables.get(var).map_or("param", |var| var.name.as_str());
+            Ok(format!("{} {}_{}", typ, identifier(name), var.0))
+        })
+        .collect::<Result<Vec<_>, CodegenError>>()?;
+    let parameters = if parameters.is_empty() { "void".to_string() } else { parameters.join(", ") };
+    Ok(format!("{} {}({})", result, symbol(&func.name), parameters))
+}
+
+/// The C type values of `typ` are stored as, if it has one
+fn c_type(typ: &Type) -> Option<&'static str> {
+    let typ = match typ {
+        Type::Int8 => "int8_t",
+        Type::Int16 => "int16_t",
+        Type::Int32 => "int32_t",
+        Type::Int | Type::Int64 => "int64_t",
+        Type::UInt8 => "uint8_t",
+        Type::UInt16 => "uint16_t",
+        Type::UInt32 => "uint32_t",
+        Type::UInt | Type::UInt64 => "uint64_t",
+        Type::Float32 => "float",
+        Type::Float | Type::Float64 => "double",
+        Type::Bool => "bool",
+        Type::String => "const char *",
+        Type::Unit | Type::Param(_) => return None,
+    };
+    Some(typ)
+}
+
+/// An integer constant of type `typ` as a C literal
+fn integer(value: i64, typ: &Type) -> String {
+    match typ.numeric_kind() {
+        Some(NumericKind::Float) => {
+            let suffix = if *typ == Type::Float32 { "f" } else { "" };
+            format!("{:?}{}", value as f64, suffix)
+        },
+        Some(NumericKind::Unsigned) if value as u64 > u32::MAX as u64 => format!("UINT64_C({})", value as u64),
+        Some(NumericKind::Unsigned) => format!("{}u", value as u64),
+        _ if value == i64::MIN => "INT64_MIN".to_string(),
+        _ if i32::try_from(value).is_err() => format!("INT64_C({})", value),
+        // A negative literal is negated where it is used, so it keeps its
+        // sign in any expression
+        _ if value < 0 => format!("({})", value),
+        _ => value.to_string(),
+    }
+}
+
+/// `text` as a C string literal
+fn string_literal(text: &str) -> String {
+    let mut literal = String::from("\"");
+    let mut previous = '\0';
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            // Two question marks could start a trigraph
+            '?' if previous == '?' => literal.push_str("\\?"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => write!(literal, "\\{:03o}", c as u32).unwrap(),
+            c => literal.push(c),
+        }
+        previous = c;
+    }
+    literal.push('"');
+    literal
+}
+
+/// `name` with every character C does not allow in identifiers replaced
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// The C name of the global `name`, which has no id appended to keep it
+/// recognizable, so one named like a C keyword gets an underscore
+fn global_name(name: &str) -> String {
+    let name = identifier(name);
+    match C_KEYWORDS.contains(&name.as_str()) {
+        true => format!("{}_", name),
+        false => name,
+    }
+}
+
+/// A comment listing `permissions`, or nothing for a temporary
+fn permissions_comment(permissions: &[Permission]) -> String {
+    if permissions.is_empty() {
+        return String::new();
+    }
+    let names: Vec<&str> = permissions.iter()
+        .map(|permission| match permission {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Reads => "reads",
+            Permission::Writes => "writes",
+        })
+        .collect();
+    format!(" /* {} */", names.join(" "))
+}
+
+fn unsupported(func: &MirFunction, construct: &str) -> CodegenError {
+    CodegenError::Unsupported {
+        function: func.name.clone(),
+        construct: construct.to_string(),
+    }
+}
@@ -0,0 +1,99 @@
+//! Errors from generating code
+
+use front_end::types::Type;
+use middle_end::mir::types::{BinaryOperation, BlockId, VarId};
+use std::fmt;
+
+/// Why MIR could not be lowered to LLVM IR or C
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodegenError {
+    /// MIR the back end cannot lower yet
+    Unsupported {
+        function: String,
+        construct: String,
+    },
+
+    /// Call to a function the program does not define
+    UnknownFunction(String),
+
+    /// Call with the wrong number of arguments
+    ArityMismatch {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// Phi with no operand for one of the blocks that continue in its block
+    MissingPhiSource {
+        function: String,
+        block: BlockId,
+        predecessor: BlockId,
+    },
+
+    /// Use of a variable that is neither a local of the function nor a global
+    UnknownVariable {
+        function: String,
+        var: VarId,
+    },
+
+    /// Reference to a block the function does not have
+    UnknownBlock {
+        function: String,
+        block: BlockId,
+    },
+
+    /// Operation on operands of a type it is not defined for
+    InvalidOperands {
+        op: BinaryOperation,
+        typ: Type,
+    },
+
+    /// Conversion between types that have none
+    InvalidConversion {
+        from: Type,
+        to: Type,
+    },
+
+    /// LLVM's optimization pipeline failed
+    Optimization(String),
+
+    /// The program could not be run by LLVM's JIT
+    Jit(String),
+
+    /// An object, IR or bitcode file could not be written
+    Emit(String),
+
+    /// An instruction LLVM's builder refused
+    Builder(String),
+
+    /// The module failed LLVM's verifier
+    Verification(String),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodegenError::Unsupported { function, construct } => {
+                write!(f, "Cannot generate code for {} in '{}' yet", construct, function)
+            },
+            CodegenError::UnknownFunction(name) => write!(f, "Unknown function '{}'", name),
+            CodegenError::ArityMismatch { function, expected, found } => {
+                write!(f, "Function '{}' takes {} argument(s) but {} were passed", function, expected, found)
+            },
+            CodegenError::MissingPhiSource { function, block, predecessor } => {
+                write!(f, "Phi in block{} of '{}' has no operand for block{}", block.0, function, predecessor.0)
+            },
+            CodegenError::UnknownVariable { function, var } => write!(f, "Unknown variable {} in '{}'", var.0, function),
+            CodegenError::UnknownBlock { function, block } => write!(f, "Unknown block{} in '{}'", block.0, function),
+            CodegenError::InvalidOperands { op, typ } => write!(f, "Cannot apply {:?} to {:?}", op, typ),
+            CodegenError::InvalidConversion { from, to } => write!(f, "Cannot convert {:?} to {:?}", from, to),
+            CodegenError::Optimization(message) => write!(f, "Cannot optimize module: {}", message),
+            CodegenError::Jit(message) => write!(f, "Cannot run program: {}", message),
+            CodegenError::Emit(message) => write!(f, "Cannot write output: {}", message),
+            CodegenError::Builder(message) => write!(f, "LLVM builder error: {}", message),
+            CodegenError::Verification(message) => write!(f, "Invalid LLVM module: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
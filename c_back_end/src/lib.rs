@@ -0,0 +1,28 @@
+//! The back end that needs no LLVM
+//!
+//! `c` writes MIR as C source, for platforms LLVM does not target.
+//! `back_end`, the LLVM back end, shares the error type, the symbols
+//! functions are named by and which string copies a function frees.
+
+pub mod c;
+pub mod error;
+pub mod ownership;
+
+pub use c::to_c;
+pub use error::CodegenError;
+
+use middle_end::mir::types::INIT_FUNCTION;
+
+/// The name of the MIR function `name` where it needs no mangling: the
+/// program's `main` is `custod_main` and its top-level statements are
+/// `custod_init`, which the C `main` calls
+pub fn symbol(name: &str) -> &str {
+    match name {
+        "main" => "custod_main",
+        INIT_FUNCTION => "custod_init",
+        name => name,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,96 @@
+//! Which string copies a function frees
+//!
+//! A `clone` of a string is text of its own on the heap. The function that
+//! makes a copy frees it when it returns, as long as the copy never leaves
+//! the function; a copy that does is owned by the program until it exits.
+
+use middle_end::mir::types::{Instruction, Intrinsic, MirFunction, Operand, Storage, VarId};
+use middle_end::mir::Liveness;
+use front_end::types::Type;
+use std::collections::HashSet;
+
+/// The variables strings are cloned into that `func` owns the copies of.
+/// Each starts out null, its old copy is freed before every clone into it,
+/// and its last copy is freed at every return.
+///
+/// A copy is owned when it is only read by operations, and copied by plain
+/// assignment into stack locals that are only read by operations too: it
+/// is never returned, passed to a call, stored to a global, peaked or
+/// merged by a phi, and nothing still reads a copy when it is replaced.
+pub fn owned_copies(func: &MirFunction) -> Vec<VarId> {
+    let mut clones: Vec<VarId> = func.blocks.iter()
+        .flat_map(|block| &block.instructions)
+        .filter_map(|instruction| match instruction {
+            Instruction::Intrinsic { target: Some(target), intrinsic: Intrinsic::Clone, .. } => Some(*target),
+            _ => None,
+        })
+        .filter(|target| func.variables.get(target).is_some_and(|var| var.typ == Type::String))
+        .collect();
+    clones.sort_by_key(|var| var.0);
+    clones.dedup();
+    if clones.is_empty() {
+        return clones;
+    }
+
+    let liveness = Liveness::compute(func);
+    clones.retain(|&copy| owns(func, &liveness, copy));
+    clones
+}
+
+/// Whether the copies cloned into `copy` can be freed by `func`
+fn owns(func: &MirFunction, liveness: &Liveness, copy: VarId) -> bool {
+    let instructions = || func.blocks.iter().flat_map(|block| &block.instructions);
+
+    // The variables a copy reaches by plain assignment
+    let mut holders = HashSet::from([copy]);
+    loop {
+        let reached: Vec<VarId> = instructions()
+            .filter_map(|instruction| match instruction {
+                Instruction::Assign { target, source: Operand::Variable(source) } if holders.contains(source) => Some(*target),
+                _ => None,
+            })
+            .filter(|target| !holders.contains(target))
+            .collect();
+        if reached.is_empty() {
+            break;
+        }
+        holders.extend(reached);
+    }
+
+    let peaked = instructions().any(|instruction| match instruction {
+        Instruction::Peak { target, source } => holders.contains(target) || holders.contains(source),
+        _ => false,
+    });
+    let local = |var: &VarId| func.variables.get(var).is_some_and(|var| var.storage == Storage::Stack)
+        && !func.parameters.iter().any(|(param, _)| param == var);
+    if peaked || !holders.iter().all(local) {
+        return false;
+    }
+
+    let holds = |operand: &Operand| operand.variable().is_some_and(|var| holders.contains(&var));
+    for block in &func.blocks {
+        let live = liveness.live_before(block);
+        for (index, instruction) in block.instructions.iter().enumerate() {
+            let reads_holder = instruction.operands().into_iter().any(holds);
+            let sets_holder = instruction.target().is_some_and(|target| holders.contains(&target));
+            let allowed = match instruction {
+                // Replacing the copy drops the old one, so nothing may
+                // still read it
+                Instruction::Intrinsic { target: Some(target), intrinsic: Intrinsic::Clone, .. } if *target == copy => {
+                    !reads_holder && live[index].is_disjoint(&holders)
+                },
+                Instruction::BinaryOp { .. } | Instruction::Intrinsic { .. } => !sets_holder,
+                Instruction::Assign { source, .. } => !sets_holder || holds(source),
+                Instruction::Phi { sources, .. } => !sets_holder && !sources.iter().any(|(_, source)| holds(source)),
+                _ => !sets_holder && !reads_holder,
+            };
+            if !allowed {
+                return false;
+            }
+        }
+        if block.terminator.operand().is_some_and(holds) {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,282 @@
+//! Tests for writing MIR as C source
+
+use crate::c::to_c;
+use crate::CodegenError;
+use middle_end::mir::interpreter::io::ScriptedIo;
+use middle_end::mir::{parse_mir, Interpreter, MirProgram, Value};
+use std::process::Command;
+
+fn parse(source: &str) -> MirProgram {
+    parse_mir(source).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// The C compiler: the one `CC` names, or `cc`
+fn c_compiler() -> String {
+    std::env::var("CC").ok().filter(|program| !program.is_empty()).unwrap_or_else(|| "cc".to_string())
+}
+
+/// Compile the C for `program` with the system C compiler and run it,
+/// returning what it printed and its exit status
+fn compile_and_run(program: &MirProgram, name: &str) -> (String, Option<i32>) {
+    let source = to_c(program).unwrap_or_else(|e| panic!("{}", e));
+    println!("{}", source);
+    let directory = std::env::temp_dir().join(format!("custod-c-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let (file, executable) = (directory.join("main.c"), directory.join("main"));
+    std::fs::write(&file, &source).unwrap();
+    let compiled = Command::new(c_compiler())
+        .args(["-std=c99", "-Wall", "-Werror", "-fwrapv", "-o"])
+        .arg(&executable)
+        .arg(&file)
+        .arg("-lm")
+        .output()
+        .unwrap();
+    assert!(compiled.status.success(), "{}", String::from_utf8_lossy(&compiled.stderr));
+    let output = Command::new(&executable).output().unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    (String::from_utf8_lossy(&output.stdout).into_owned(), output.status.code())
+}
+
+/// What the interpreter prints running the program, and what `main` returns
+fn interpret(program: &MirProgram) -> (String, Option<Value>) {
+    let mut io = ScriptedIo::default();
+    let result = Interpreter::new(program).with_io(&mut io).run().unwrap_or_else(|e| panic!("{}", e));
+    let printed = io.output().iter().map(|line| format!("{}\n", line)).collect();
+    (printed, result)
+}
+
+#[test]
+fn test_c_matches_interpreter() {
+    let source = r#"
+        var total: Int [0] heap writes
+
+        fn fib(n: Int [1] read) -> Int {
+            var small: Bool [2]
+            var a: Int [3]
+            var b: Int [4]
+
+            block0:
+                small[2] = n[1] < 2
+                branch small[2] ? block1 : block2
+            block1:
+                return n[1]
+            block2:
+                a[3] = n[1] - 1
+                a[3] = call fib(a[3])
+                b[4] = n[1] - 2
+                b[4] = call fib(b[4])
+                a[3] = a[3] + b[4]
+                return a[3]
+        }
+
+        fn main() -> Int {
+            var x: Int [5]
+            var y: Int [6]
+            var z: Int [7]
+            var i: Int [8]
+            var more: Bool [9]
+            var small: Int8 [10]
+            var u: UInt8 [11]
+            var f: Float [12]
+            var g: Float32 [13]
+            var w: Int [14]
+
+            block0:
+                x[5] = call fib(20)
+                intrinsic print(x[5])
+                total[0] = 0
+                i[8] = 0
+                jump block1
+            block1:
+                more[9] = i[8] < 5
+                branch more[9] ? block2 : block3
+            block2:
+                total[0] = total[0] + i[8]
+                i[8] = i[8] + 1
+                jump block1
+            block3:
+                intrinsic print(total[0])
+                small[10] = 100i8
+                small[10] = small[10] + 100i8
+                intrinsic print(small[10])
+                u[11] = 200u8
+                u[11] = u[11] * 2u8
+                intrinsic print(u[11])
+                y[6] = -9223372036854775808
+                z[7] = y[6] / -1
+                intrinsic print(z[7])
+                z[7] = -7 % 2
+                intrinsic print(z[7])
+                z[7] = intrinsic abs(-5)
+                intrinsic print(z[7])
+                z[7] = intrinsic max(z[7], 9)
+                intrinsic print(z[7])
+                f[12] = x[5] as Float
+                f[12] = f[12] / 4f
+                intrinsic print(f[12])
+                g[13] = 2f32
+                intrinsic print(g[13])
+                w[14] = peak x[5]
+                w[14] = 3
+                intrinsic print(x[5])
+                intrinsic print(more[9])
+                intrinsic print("done \"here\"")
+                return x[5]
+        }
+    "#;
+    let program = parse(source);
+    let (printed, result) = interpret(&program);
+    assert_eq!(result, Some(Value::Integer(3)));
+    assert_eq!(compile_and_run(&program, "matches"), (printed, Some(3)));
+}
+
+#[test]
+fn test_c_assigns_phis_at_once() {
+    let source = r#"
+        fn main() -> Int {
+            var a: Int [0]
+            var b: Int [1]
+            var i: Int [2]
+            var more: Bool [3]
+
+            block0:
+                jump block1
+            block1:
+                a[0] = phi(block0: 1, block2: b[1])
+                b[1] = phi(block0: 2, block2: a[0])
+                i[2] = phi(block0: 0, block2: i[2])
+                more[3] = i[2] < 3
+                branch more[3] ? block2 : block3
+            block2:
+                i[2] = i[2] + 1
+                jump block1
+            block3:
+                intrinsic print(a[0])
+                return b[1]
+        }
+    "#;
+    let program = parse(source);
+    let (printed, result) = interpret(&program);
+    assert_eq!(printed, "2\n");
+    assert_eq!(result, Some(Value::Integer(1)));
+
+    let c = to_c(&program).unwrap();
+    assert!(c.contains("int64_t phi0 = b_1;"), "{}", c);
+    assert!(c.contains("a_0 = phi0;"), "{}", c);
+    assert_eq!(compile_and_run(&program, "phis"), (printed, Some(1)));
+}
+
+#[test]
+fn test_c_is_readable() {
+    let source = r#"
+        var count: Int [0] heap writes
+
+        fn bump(by: Int [1] read) {
+            var step: Int [2] reads write
+
+            block0:
+                atomic begin
+                step[2] = by[1]
+                count[0] = count[0] + step[2]
+                atomic end
+                return
+        }
+    "#;
+    let c = to_c(&parse(source)).unwrap();
+    for expected in [
+        "int64_t count; /* writes */",
+        "void bump(int64_t by_1) {",
+        "    int64_t step_2; /* reads write */",
+        "    count = count + step_2;",
+        "    /* atomic { */",
+    ] {
+        assert!(c.contains(expected), "{} missing from {}", expected, c);
+    }
+    assert!(!c.contains("int main"), "{}", c);
+
+    let concat = "fn join() -> String {\n var s: String [0]\n block0:\n s[0] = intrinsic concat(\"a\", \"b\")\n return s[0]\n }";
+    assert!(matches!(to_c(&parse(concat)), Err(CodegenError::Unsupported { .. })));
+}
+
+#[test]
+fn test_c_runs_top_level_statements_first() {
+    let source = r#"
+        var total: Int [0] heap reads writes
+
+        fn $init() {
+            block0:
+                total[0] = 5
+                intrinsic print("start")
+                return
+        }
+
+        fn main() -> Int {
+            block0:
+                return total[0]
+        }
+    "#;
+    let program = parse(source);
+    let c = to_c(&program).unwrap();
+    assert!(c.contains("int main(void) {\n    custod_init();\n    return (int)custod_main();"), "{}", c);
+    assert_eq!(interpret(&program), ("start\n".to_string(), Some(Value::Integer(5))));
+    assert_eq!(compile_and_run(&program, "init"), ("start\n".to_string(), Some(5)));
+
+    // A script without main only runs its top-level statements
+    let script = parse(&source[..source.find("fn main").unwrap()]);
+    assert_eq!(interpret(&script), ("start\n".to_string(), None));
+    assert_eq!(compile_and_run(&script, "script"), ("start\n".to_string(), Some(0)));
+}
+
+#[test]
+fn test_c_clones_strings_and_frees_the_copies_it_owns() {
+    let source = r#"
+        fn copy(t: String [0] reads) -> String {
+            var s: String [1] reads
+
+            block0:
+                s[1] = intrinsic clone(t[0])
+                return s[1]
+        }
+
+        fn main() -> Int {
+            var i: Int [2]
+            var more: Bool [3]
+            var s: String [4]
+            var u: String [5] reads
+            var r: String [6] reads
+
+            block0:
+                i[2] = 0
+                jump block1
+            block1:
+                more[3] = i[2] < 2
+                branch more[3] ? block2 : block3
+            block2:
+                s[4] = intrinsic clone("again")
+                u[5] = s[4]
+                intrinsic print(u[5])
+                i[2] = i[2] + 1
+                jump block1
+            block3:
+                r[6] = call copy("kept")
+                intrinsic print(r[6])
+                return 0
+        }
+    "#;
+    let program = parse(source);
+    let (printed, _) = interpret(&program);
+    assert_eq!(printed, "again\nagain\nkept\n");
+
+    // The copy in the loop is freed before the next and at the return;
+    // the one `copy` returns is its caller's
+    let c = to_c(&program).unwrap();
+    for expected in [
+        "    const char * s_4 = NULL;",
+        "    free((void *)s_4);\n    s_4 = strdup(\"again\");",
+        "    free((void *)s_4);\n    return 0;",
+        "    s_1 = strdup(t_0);\n    return s_1;",
+    ] {
+        assert!(c.contains(expected), "{} missing from {}", expected, c);
+    }
+    assert_eq!(compile_and_run(&program, "clones"), (printed, Some(0)));
+}
@@ -0,0 +1,6 @@
+//! Tests for the C back end
+//!
+//! Each test writes MIR, usually in the textual format, as C source, and
+//! compiles and runs it to compare with the interpreter.
+
+mod c_tests;
@@ -0,0 +1,79 @@
+/*
+ * The C ABI between generated code and the custod runtime.
+ *
+ * Generated code calls only what is declared here. Memory the runtime
+ * returns is released with custod_rt_free, and a failed allocation aborts
+ * the program instead of returning NULL.
+ */
+
+#ifndef CUSTOD_RT_H
+#define CUSTOD_RT_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Writing a value and a newline, formatted as the interpreter does */
+void custod_rt_print_int(int64_t value);
+void custod_rt_print_uint(uint64_t value);
+void custod_rt_print_float(double value);
+void custod_rt_print_bool(bool value);
+void custod_rt_print_string(const char *text);
+
+/* Heap blocks: zeroed, 16-byte aligned, with their size kept by the runtime */
+void *custod_rt_alloc(size_t size);
+void custod_rt_free(void *block);
+size_t custod_rt_size(const void *block);
+
+/* Where a block holds a pointer to another block, and that block's type;
+ * a NULL type is a block without pointers */
+typedef struct CustodTypeInfo CustodTypeInfo;
+typedef struct CustodField {
+    size_t offset;
+    const CustodTypeInfo *type;
+} CustodField;
+struct CustodTypeInfo {
+    const CustodField *fields;
+    size_t field_count;
+};
+
+/* A deep copy of a block and every block it leads to; NULL for NULL */
+void *custod_rt_clone(const void *block, const CustodTypeInfo *type);
+char *custod_rt_clone_string(const char *text);
+
+/* Atomic blocks: one lock for the whole program, taken again by nested
+ * blocks of the thread holding it */
+void custod_rt_atomic_begin(void);
+void custod_rt_atomic_end(void);
+
+/* Actor mailboxes: behaviors queued in order, run one at a time. The
+ * mailbox frees a message's arguments block after its behavior returns. */
+typedef struct CustodMailbox CustodMailbox;
+typedef struct CustodMessage {
+    void (*behavior)(void *actor, void *arguments);
+    void *actor;
+    void *arguments;
+} CustodMessage;
+
+CustodMailbox *custod_rt_mailbox_new(void);
+void custod_rt_mailbox_send(CustodMailbox *mailbox, CustodMessage message);
+size_t custod_rt_mailbox_run(CustodMailbox *mailbox);
+void custod_rt_mailbox_free(CustodMailbox *mailbox);
+
+/* Running mailboxes on worker threads: 0 threads starts one per core.
+ * custod_rt_send queues a message and puts its mailbox in line, and
+ * custod_rt_scheduler_wait returns once every message sent has run. */
+void custod_rt_scheduler_start(size_t threads);
+void custod_rt_send(CustodMailbox *mailbox, void (*behavior)(void *actor, void *arguments),
+                    void *actor, void *arguments);
+void custod_rt_scheduler_wait(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
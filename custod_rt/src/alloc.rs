@@ -0,0 +1,57 @@
+//! Heap allocation
+//!
+//! Every block starts with a header holding its size, so `custod_rt_free`
+//! and `custod_rt_clone` need only the pointer. Blocks are zeroed and
+//! aligned for any value generated code stores, and a failed allocation
+//! aborts the program.
+
+use std::alloc::{alloc_zeroed, dealloc, handle_alloc_error, Layout};
+
+/// Alignment of every block, and the size of its header
+pub const ALIGNMENT: usize = 16;
+
+/// A zeroed block of `size` bytes
+#[no_mangle]
+pub extern "C" fn custod_rt_alloc(size: usize) -> *mut u8 {
+    let layout = layout(size);
+    // SAFETY: the layout is never zero-sized, as it includes the header
+    unsafe {
+        let block = alloc_zeroed(layout);
+        if block.is_null() {
+            handle_alloc_error(layout);
+        }
+        (block as *mut usize).write(size);
+        block.add(ALIGNMENT)
+    }
+}
+
+/// Release a block `custod_rt_alloc` returned. Null is ignored.
+///
+/// # Safety
+///
+/// `pointer` must be null or a block from `custod_rt_alloc` not released
+/// yet.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_free(pointer: *mut u8) {
+    if pointer.is_null() {
+        return;
+    }
+    let block = pointer.sub(ALIGNMENT);
+    dealloc(block, layout((block as *const usize).read()));
+}
+
+/// The size `pointer` was allocated with
+///
+/// # Safety
+///
+/// `pointer` must be a block from `custod_rt_alloc` not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_size(pointer: *const u8) -> usize {
+    (pointer.sub(ALIGNMENT) as *const usize).read()
+}
+
+fn layout(size: usize) -> Layout {
+    size.checked_add(ALIGNMENT)
+        .and_then(|total| Layout::from_size_align(total, ALIGNMENT).ok())
+        .unwrap_or_else(|| std::process::abort())
+}
@@ -0,0 +1,44 @@
+//! Atomic blocks
+//!
+//! An atomic block runs as one unit: no other behavior sees the state it
+//! changes halfway. Compiled code holds one lock, shared by every atomic
+//! block of the program, from `custod_rt_atomic_begin` to the matching
+//! `custod_rt_atomic_end`. Blocks nest, on the thread that holds the
+//! lock, without taking it again.
+
+use std::cell::{Cell, RefCell};
+use std::sync::{Mutex, MutexGuard};
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// How deep in atomic blocks this thread is
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+
+    /// The lock, while this thread is in an atomic block
+    static GUARD: RefCell<Option<MutexGuard<'static, ()>>> = const { RefCell::new(None) };
+}
+
+/// Enter an atomic block, waiting until no other thread is in one
+#[no_mangle]
+pub extern "C" fn custod_rt_atomic_begin() {
+    if DEPTH.get() == 0 {
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        GUARD.set(Some(guard));
+    }
+    DEPTH.set(DEPTH.get() + 1);
+}
+
+/// Leave an atomic block, letting other threads in when it is the
+/// outermost. Leaving one the thread is not in is ignored.
+#[no_mangle]
+pub extern "C" fn custod_rt_atomic_end() {
+    match DEPTH.get() {
+        0 => {},
+        1 => {
+            DEPTH.set(0);
+            GUARD.set(None);
+        },
+        depth => DEPTH.set(depth - 1),
+    }
+}
@@ -0,0 +1,97 @@
+//! Copying heap values for `clone`
+//!
+//! `clone` gives its result storage of its own, so a value that points to
+//! other heap blocks is copied with all of them. Generated code describes
+//! where a block's pointers are with a `CustodTypeInfo`, emitted once per
+//! type as a constant. A block reached twice is copied once, so the copy
+//! shares what the original shares, and cycles end.
+
+use crate::alloc::{custod_rt_alloc, custod_rt_size};
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+/// Where a block holds a pointer to another block, and what that block is
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CustodField {
+    /// Offset of the pointer from the start of the block, in bytes
+    pub offset: usize,
+
+    /// The type of the block it points to, or null for one that holds no
+    /// pointers
+    pub typ: *const CustodTypeInfo,
+}
+
+/// The pointers in blocks of a type. Its size is in the block's header.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CustodTypeInfo {
+    pub fields: *const CustodField,
+    pub field_count: usize,
+}
+
+// SAFETY: type information is immutable, emitted as constants
+unsafe impl Sync for CustodTypeInfo {}
+unsafe impl Sync for CustodField {}
+
+impl CustodTypeInfo {
+    /// The fields of the type
+    ///
+    /// # Safety
+    ///
+    /// `fields` must point to `field_count` fields, or be null if there
+    /// are none.
+    pub unsafe fn fields(&self) -> &[CustodField] {
+        match self.fields.is_null() {
+            true => &[],
+            false => std::slice::from_raw_parts(self.fields, self.field_count),
+        }
+    }
+}
+
+/// A copy of the block `pointer` and of every block it leads to, described
+/// by `typ`, or null for null. A null `typ` copies only the block.
+///
+/// # Safety
+///
+/// `pointer` must be null or a block from `custod_rt_alloc` whose pointers
+/// `typ` describes, each null or such a block itself.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_clone(pointer: *const u8, typ: *const CustodTypeInfo) -> *mut u8 {
+    deep_copy(pointer, typ, &mut HashMap::new())
+}
+
+/// A heap copy of NUL-terminated `text`, released with `custod_rt_free`
+///
+/// # Safety
+///
+/// `text` must point to NUL-terminated text.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_clone_string(text: *const c_char) -> *mut c_char {
+    let bytes = CStr::from_ptr(text).to_bytes_with_nul();
+    let copy = custod_rt_alloc(bytes.len());
+    ptr::copy_nonoverlapping(bytes.as_ptr(), copy, bytes.len());
+    copy as *mut c_char
+}
+
+/// Copy `pointer` unless `copies` has a copy of it already
+unsafe fn deep_copy(pointer: *const u8, typ: *const CustodTypeInfo, copies: &mut HashMap<*const u8, *mut u8>) -> *mut u8 {
+    if pointer.is_null() {
+        return ptr::null_mut();
+    }
+    if let Some(copy) = copies.get(&pointer) {
+        return *copy;
+    }
+    let size = custod_rt_size(pointer);
+    let copy = custod_rt_alloc(size);
+    ptr::copy_nonoverlapping(pointer, copy, size);
+    copies.insert(pointer, copy);
+    if let Some(typ) = typ.as_ref() {
+        for field in typ.fields() {
+            let slot = copy.add(field.offset) as *mut *mut u8;
+            slot.write_unaligned(deep_copy(slot.read_unaligned(), field.typ, copies));
+        }
+    }
+    copy
+}
@@ -0,0 +1,30 @@
+//! Runtime support for compiled programs
+//!
+//! Natively compiled programs call into this library for what is easier
+//! written once in Rust than generated as LLVM IR: writing values, heap
+//! allocation, copying heap values for `clone`, the lock atomic blocks
+//! hold, the mailboxes actor behaviors are queued on, and the threads
+//! that run them. It is built as a static library the linker adds to
+//! every executable, and as an rlib the compiler links so its JIT finds
+//! the same functions.
+//!
+//! Generated code sees only a C ABI: functions named `custod_rt_*` and the
+//! `#[repr(C)]` types they take, all declared in `include/custod_rt.h`.
+//! Integers are passed as `int64_t` or `uint64_t`, booleans as `bool`,
+//! strings as pointers to NUL-terminated UTF-8, and sizes as `size_t`.
+//! Nothing the runtime returns is owned by Rust: memory it hands out is
+//! released through `custod_rt_free`, and a failed allocation aborts the
+//! program rather than returning null.
+
+pub mod alloc;
+pub mod atomic;
+pub mod clone;
+pub mod mailbox;
+pub mod print;
+pub mod scheduler;
+
+pub use clone::{CustodField, CustodTypeInfo};
+pub use mailbox::{CustodMailbox, CustodMessage};
+
+#[cfg(test)]
+mod tests;
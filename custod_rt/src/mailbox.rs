@@ -0,0 +1,110 @@
+//! Actor mailboxes
+//!
+//! Calling a behavior queues a message on its actor's mailbox instead of
+//! running it. Whoever runs the mailbox, a scheduler thread or the end of
+//! `main`, runs the queued behaviors in the order they were sent, one at a
+//! time, so the behaviors of one actor never overlap. A mailbox can be
+//! sent to from any thread.
+
+use crate::alloc::custod_rt_free;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// A queued behavior call
+#[repr(C)]
+#[derive(Debug)]
+pub struct CustodMessage {
+    /// The behavior, given the actor's state and its arguments
+    pub behavior: unsafe extern "C" fn(actor: *mut u8, arguments: *mut u8),
+
+    /// The state of the actor the behavior belongs to
+    pub actor: *mut u8,
+
+    /// The arguments, in a block from `custod_rt_alloc` the mailbox
+    /// releases once the behavior returns, or null
+    pub arguments: *mut u8,
+}
+
+// SAFETY: sending checks that arguments are sendable, so the message may
+// run on another thread than the one that sent it
+unsafe impl Send for CustodMessage {}
+
+/// The queue of one actor, opaque to generated code
+#[derive(Debug, Default)]
+pub struct CustodMailbox {
+    queue: Mutex<VecDeque<CustodMessage>>,
+
+    /// Held while a thread runs the queue
+    running: Mutex<()>,
+}
+
+/// A new, empty mailbox
+#[no_mangle]
+pub extern "C" fn custod_rt_mailbox_new() -> *mut CustodMailbox {
+    Box::into_raw(Box::default())
+}
+
+/// Queue `message` on `mailbox`
+///
+/// # Safety
+///
+/// `mailbox` must come from `custod_rt_mailbox_new` and not be freed.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_mailbox_send(mailbox: *mut CustodMailbox, message: CustodMessage) {
+    (*mailbox).push(message);
+}
+
+/// Run the queued messages of `mailbox` until it is empty, including those
+/// sent while it runs, and return how many ran. Returns 0 at once if
+/// another thread is running the mailbox.
+///
+/// # Safety
+///
+/// `mailbox` must come from `custod_rt_mailbox_new` and not be freed, and
+/// the messages queued on it must be valid calls.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_mailbox_run(mailbox: *mut CustodMailbox) -> usize {
+    let mailbox = &*mailbox;
+    let Ok(_running) = mailbox.running.try_lock() else {
+        return 0;
+    };
+    let mut count = 0;
+    // The queue is not locked while a behavior runs, so it can send more
+    while let Some(message) = pop(mailbox) {
+        (message.behavior)(message.actor, message.arguments);
+        custod_rt_free(message.arguments);
+        count += 1;
+    }
+    count
+}
+
+/// Free `mailbox`, dropping the messages still queued and their arguments
+///
+/// # Safety
+///
+/// `mailbox` must come from `custod_rt_mailbox_new`, not be freed yet and
+/// not be in use by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_mailbox_free(mailbox: *mut CustodMailbox) {
+    if mailbox.is_null() {
+        return;
+    }
+    let mailbox = Box::from_raw(mailbox);
+    for message in mailbox.queue.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        custod_rt_free(message.arguments);
+    }
+}
+
+impl CustodMailbox {
+    pub(crate) fn push(&self, message: CustodMessage) {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).push_back(message);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+}
+
+fn pop(mailbox: &CustodMailbox) -> Option<CustodMessage> {
+    mailbox.queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front()
+}
@@ -0,0 +1,44 @@
+//! Writing values to standard output
+//!
+//! Each function writes one value and a newline, formatted as the
+//! interpreter formats it, so a compiled program prints what the
+//! interpreted one does. Floats of either width are passed as doubles.
+
+use std::ffi::{c_char, CStr};
+use std::fmt::Display;
+use std::io::Write;
+
+#[no_mangle]
+pub extern "C" fn custod_rt_print_int(value: i64) {
+    write_line(value);
+}
+
+#[no_mangle]
+pub extern "C" fn custod_rt_print_uint(value: u64) {
+    write_line(value);
+}
+
+#[no_mangle]
+pub extern "C" fn custod_rt_print_float(value: f64) {
+    write_line(value);
+}
+
+#[no_mangle]
+pub extern "C" fn custod_rt_print_bool(value: bool) {
+    write_line(value);
+}
+
+/// # Safety
+///
+/// `text` must point to NUL-terminated text that stays valid during the
+/// call. Text that is not UTF-8 is written with replacement characters.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_print_string(text: *const c_char) {
+    write_line(CStr::from_ptr(text).to_string_lossy());
+}
+
+/// Write `value` and a newline. A program whose output is closed keeps
+/// running, as with C's `printf`.
+fn write_line(value: impl Display) {
+    let _ = writeln!(std::io::stdout().lock(), "{}", value);
+}
@@ -0,0 +1,131 @@
+//! Running behaviors on a pool of threads
+//!
+//! `custod_rt_send` queues a behavior call on a mailbox and puts the
+//! mailbox in line to be run. Worker threads, started by
+//! `custod_rt_scheduler_start`, take mailboxes from the line and run them
+//! until they are empty, so the behaviors of different mailboxes run in
+//! parallel and those of one mailbox in order, one at a time.
+//! `custod_rt_scheduler_wait` returns once every message sent has run,
+//! including those sent by behaviors; without workers it runs them on the
+//! calling thread. The workers stay for the life of the process, waiting
+//! for more mailboxes.
+
+use crate::mailbox::{custod_rt_mailbox_run, CustodMailbox, CustodMessage};
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// A mailbox in line, sent between threads
+struct Ready(*mut CustodMailbox);
+
+// SAFETY: a mailbox may be used from any thread
+unsafe impl Send for Ready {}
+
+struct State {
+    /// Mailboxes with messages to run, oldest first. A mailbox may be in
+    /// line more than once; running it again finds it empty.
+    ready: VecDeque<Ready>,
+
+    /// Messages sent that have not finished running
+    outstanding: usize,
+
+    workers: usize,
+}
+
+static STATE: Mutex<State> = Mutex::new(State { ready: VecDeque::new(), outstanding: 0, workers: 0 });
+
+/// Signalled when a mailbox gets in line
+static WORK: Condvar = Condvar::new();
+
+/// Signalled when the last outstanding message has run
+static IDLE: Condvar = Condvar::new();
+
+/// Start worker threads until there are `threads`, or as many as the
+/// machine runs at once for 0
+#[no_mangle]
+pub extern "C" fn custod_rt_scheduler_start(threads: usize) {
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, |count| count.get()),
+        threads => threads,
+    };
+    let mut state = lock();
+    while state.workers < threads {
+        std::thread::spawn(work);
+        state.workers += 1;
+    }
+}
+
+/// Queue a call of `behavior` on `mailbox`, as `custod_rt_mailbox_send`
+/// does, and put the mailbox in line to run
+///
+/// # Safety
+///
+/// `mailbox` must come from `custod_rt_mailbox_new` and stay valid until
+/// `custod_rt_scheduler_wait` returns, and the call must be valid from
+/// any thread.
+#[no_mangle]
+pub unsafe extern "C" fn custod_rt_send(
+    mailbox: *mut CustodMailbox,
+    behavior: unsafe extern "C" fn(actor: *mut u8, arguments: *mut u8),
+    actor: *mut u8,
+    arguments: *mut u8,
+) {
+    // Counted before it is queued, so a worker never finishes it first
+    let mut state = lock();
+    state.outstanding += 1;
+    (*mailbox).push(CustodMessage { behavior, actor, arguments });
+    state.ready.push_back(Ready(mailbox));
+    WORK.notify_one();
+}
+
+/// Wait until every message sent has run
+#[no_mangle]
+pub extern "C" fn custod_rt_scheduler_wait() {
+    let mut state = lock();
+    while state.outstanding > 0 {
+        if state.workers > 0 {
+            state = IDLE.wait(state).unwrap_or_else(|e| e.into_inner());
+            continue;
+        }
+        let Some(Ready(mailbox)) = state.ready.pop_front() else {
+            break;
+        };
+        drop(state);
+        // SAFETY: senders keep mailboxes valid until the wait is over
+        state = unsafe { run(mailbox) };
+    }
+}
+
+/// What a worker thread does: run mailboxes as they get in line
+fn work() {
+    let mut state = lock();
+    loop {
+        let Some(Ready(mailbox)) = state.ready.pop_front() else {
+            state = WORK.wait(state).unwrap_or_else(|e| e.into_inner());
+            continue;
+        };
+        drop(state);
+        // SAFETY: senders keep mailboxes valid until the wait is over
+        state = unsafe { run(mailbox) };
+    }
+}
+
+/// Run `mailbox` until it is empty, and count what ran as done. It goes
+/// back in line if another thread was running it, or a message came in
+/// just as it emptied.
+unsafe fn run(mailbox: *mut CustodMailbox) -> MutexGuard<'static, State> {
+    let ran = custod_rt_mailbox_run(mailbox);
+    let mut state = lock();
+    if !(*mailbox).is_empty() {
+        state.ready.push_back(Ready(mailbox));
+        WORK.notify_one();
+    }
+    state.outstanding -= ran;
+    if state.outstanding == 0 {
+        IDLE.notify_all();
+    }
+    state
+}
+
+fn lock() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,5 @@
+//! Tests for the runtime
+//!
+//! Each test calls the runtime through its C ABI, as generated code does.
+
+mod runtime_tests;
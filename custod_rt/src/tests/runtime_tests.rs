@@ -0,0 +1,180 @@
+//! Tests for allocation, cloning, atomic blocks and mailboxes
+
+use crate::alloc::{custod_rt_alloc, custod_rt_free, custod_rt_size, ALIGNMENT};
+use crate::clone::{custod_rt_clone, custod_rt_clone_string, CustodField, CustodTypeInfo};
+use crate::mailbox::{custod_rt_mailbox_free, custod_rt_mailbox_new, custod_rt_mailbox_run, custod_rt_mailbox_send, CustodMessage};
+use std::ffi::CStr;
+use std::ptr;
+
+/// A list node: a value, then the next node
+static NODE: CustodTypeInfo = CustodTypeInfo { fields: NODE_FIELDS.as_ptr(), field_count: 1 };
+static NODE_FIELDS: [CustodField; 1] = [CustodField { offset: 8, typ: &NODE }];
+
+unsafe fn node(value: i64, next: *mut u8) -> *mut u8 {
+    let node = custod_rt_alloc(16);
+    (node as *mut i64).write(value);
+    (node.add(8) as *mut *mut u8).write(next);
+    node
+}
+
+unsafe fn values(mut node: *const u8) -> Vec<i64> {
+    let mut values = Vec::new();
+    while !node.is_null() && values.len() < 10 {
+        values.push((node as *const i64).read());
+        node = (node.add(8) as *const *const u8).read();
+    }
+    values
+}
+
+#[test]
+fn test_alloc_is_zeroed_and_aligned() {
+    unsafe {
+        let block = custod_rt_alloc(24);
+        assert_eq!(block as usize % ALIGNMENT, 0);
+        assert_eq!(custod_rt_size(block), 24);
+        assert!(std::slice::from_raw_parts(block, 24).iter().all(|byte| *byte == 0));
+        custod_rt_free(block);
+        custod_rt_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn test_clone_copies_what_a_block_leads_to() {
+    unsafe {
+        let list = node(1, node(2, node(3, ptr::null_mut())));
+        let copy = custod_rt_clone(list, &NODE);
+        assert_ne!(copy, list);
+        assert_eq!(values(copy), vec![1, 2, 3]);
+
+        // Changing the original leaves the copy alone, all the way down
+        let second = (list.add(8) as *const *mut u8).read();
+        (second as *mut i64).write(20);
+        assert_eq!(values(list), vec![1, 20, 3]);
+        assert_eq!(values(copy), vec![1, 2, 3]);
+
+        // A cycle is copied as a cycle
+        let ring = node(7, ptr::null_mut());
+        (ring.add(8) as *mut *mut u8).write(ring);
+        let ring_copy = custod_rt_clone(ring, &NODE);
+        assert_ne!(ring_copy, ring);
+        assert_eq!((ring_copy.add(8) as *const *mut u8).read(), ring_copy);
+
+        assert!(custod_rt_clone(ptr::null(), &NODE).is_null());
+    }
+}
+
+#[test]
+fn test_clone_string() {
+    unsafe {
+        let copy = custod_rt_clone_string(c"héllo".as_ptr());
+        assert_eq!(CStr::from_ptr(copy).to_str(), Ok("héllo"));
+        assert_eq!(custod_rt_size(copy as *const u8), "héllo".len() + 1);
+        custod_rt_free(copy as *mut u8);
+    }
+}
+
+/// Appends its argument to the vector the actor is, and sends itself
+/// again for arguments above zero
+unsafe extern "C" fn record(actor: *mut u8, arguments: *mut u8) {
+    let log = &mut *(actor as *mut Vec<i64>);
+    let value = (arguments as *const i64).read();
+    log.push(value);
+    if value > 0 {
+        let mailbox = (arguments.add(8) as *const *mut crate::CustodMailbox).read();
+        send(mailbox, actor, value - 1);
+    }
+}
+
+unsafe fn send(mailbox: *mut crate::CustodMailbox, actor: *mut u8, value: i64) {
+    let arguments = custod_rt_alloc(16);
+    (arguments as *mut i64).write(value);
+    (arguments.add(8) as *mut *mut crate::CustodMailbox).write(mailbox);
+    custod_rt_mailbox_send(mailbox, CustodMessage { behavior: record, actor, arguments });
+}
+
+#[test]
+fn test_mailbox_runs_messages_in_order() {
+    unsafe {
+        let mut log: Vec<i64> = Vec::new();
+        let actor = &mut log as *mut Vec<i64> as *mut u8;
+        let mailbox = custod_rt_mailbox_new();
+        send(mailbox, actor, 10);
+        send(mailbox, actor, 2);
+        assert_eq!(custod_rt_mailbox_run(mailbox), 14);
+        assert_eq!(custod_rt_mailbox_run(mailbox), 0);
+        assert_eq!(log, vec![10, 2, 9, 1, 8, 0, 7, 6, 5, 4, 3, 2, 1, 0]);
+
+        // Messages never run are released with the mailbox
+        send(mailbox, actor, 5);
+        custod_rt_mailbox_free(mailbox);
+    }
+}
+
+#[test]
+fn test_atomic_blocks_exclude_each_other() {
+    use crate::atomic::{custod_rt_atomic_begin, custod_rt_atomic_end};
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    // Read, then write, inside a block: without the lock, increments of
+    // other threads in between would be lost
+    static COUNT: AtomicI64 = AtomicI64::new(0);
+    let threads: Vec<_> = (0..4)
+        .map(|_| std::thread::spawn(|| {
+            for _ in 0..1000 {
+                custod_rt_atomic_begin();
+                custod_rt_atomic_begin();
+                let count = COUNT.load(Ordering::Relaxed);
+                std::hint::spin_loop();
+                COUNT.store(count + 1, Ordering::Relaxed);
+                custod_rt_atomic_end();
+                custod_rt_atomic_end();
+            }
+        }))
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(COUNT.load(Ordering::Relaxed), 4000);
+    custod_rt_atomic_end();
+}
+
+/// Adds one to the count the actor is, and sends itself again for
+/// arguments above zero
+unsafe extern "C" fn count(actor: *mut u8, arguments: *mut u8) {
+    use crate::scheduler::custod_rt_send;
+
+    // Not atomic: the behaviors of one mailbox never overlap
+    *(actor as *mut i64) += 1;
+    let value = (arguments as *const i64).read();
+    if value > 0 {
+        let mailbox = (arguments.add(8) as *const *mut crate::CustodMailbox).read();
+        let next = custod_rt_alloc(16);
+        (next as *mut i64).write(value - 1);
+        (next.add(8) as *mut *mut crate::CustodMailbox).write(mailbox);
+        custod_rt_send(mailbox, count, actor, next);
+    }
+}
+
+#[test]
+fn test_scheduler_runs_every_message() {
+    use crate::scheduler::{custod_rt_scheduler_start, custod_rt_scheduler_wait, custod_rt_send};
+
+    unsafe {
+        custod_rt_scheduler_start(4);
+        let mut counts = [0i64; 8];
+        let mailboxes: Vec<_> = counts.iter().map(|_| custod_rt_mailbox_new()).collect();
+        for (actor, &mailbox) in counts.iter_mut().zip(&mailboxes) {
+            for _ in 0..10 {
+                let arguments = custod_rt_alloc(16);
+                (arguments as *mut i64).write(9);
+                (arguments.add(8) as *mut *mut crate::CustodMailbox).write(mailbox);
+                custod_rt_send(mailbox, count, actor as *mut i64 as *mut u8, arguments);
+            }
+        }
+        custod_rt_scheduler_wait();
+        assert_eq!(counts, [100; 8]);
+        for mailbox in mailboxes {
+            custod_rt_mailbox_free(mailbox);
+        }
+    }
+}
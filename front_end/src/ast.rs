@@ -1,56 +1,79 @@
+use crate::symbol_table::Span;
 use crate::token::TokenType;
 use crate::types::PermissionedType;
 
+/// An expression, with the span of source it was parsed from
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
-    Number(i64),
-    Variable(String),
+    Number(i64, Span),
+    Variable(String, Span),
     Binary {
         left: Box<Expression>,
         operator: TokenType,
         right: Box<Expression>,
+        span: Span,
     },
-    Clone(Box<Expression>),
-    Peak(Box<Expression>),
+    Clone(Box<Expression>, Span),
+    Peak(Box<Expression>, Span),
+    /// Moves a value out of a variable, which cannot be used afterwards
+    Consume(Box<Expression>, Span),
     Call {
         function: String,
         arguments: Vec<Expression>,
+        span: Span,
     },
 }
 
 impl Expression {
     pub fn new_binary(left: Expression, operator: TokenType, right: Expression) -> Self {
-        // Debug print to check the binary expression creation
-        println!("Creating binary expression with operator: {:?}", operator);
-        
+        let span = left.span().combine(right.span());
         Expression::Binary {
             left: Box::new(left),
             operator,
             right: Box::new(right),
+            span,
         }
     }
     
-    pub fn new_variable(name: String) -> Self {
-        Expression::Variable(name)
+    pub fn new_variable(name: String, span: Span) -> Self {
+        Expression::Variable(name, span)
     }
     
-    pub fn new_number(value: i64) -> Self {
-        Expression::Number(value)
+    pub fn new_number(value: i64, span: Span) -> Self {
+        Expression::Number(value, span)
     }
     
-    pub fn new_call(function: String, arguments: Vec<Expression>) -> Self {
+    pub fn new_call(function: String, arguments: Vec<Expression>, span: Span) -> Self {
         Expression::Call {
             function,
             arguments,
+            span,
         }
     }
     
-    pub fn new_peak(expr: Expression) -> Self {
-        Expression::Peak(Box::new(expr))
+    pub fn new_peak(expr: Expression, span: Span) -> Self {
+        Expression::Peak(Box::new(expr), span)
     }
     
-    pub fn new_clone(expr: Expression) -> Self {
-        Expression::Clone(Box::new(expr))
+    pub fn new_clone(expr: Expression, span: Span) -> Self {
+        Expression::Clone(Box::new(expr), span)
+    }
+    
+    pub fn new_consume(expr: Expression, span: Span) -> Self {
+        Expression::Consume(Box::new(expr), span)
+    }
+    
+    /// The source this expression was parsed from
+    pub fn span(&self) -> &Span {
+        match self {
+            Expression::Number(_, span) |
+            Expression::Variable(_, span) |
+            Expression::Clone(_, span) |
+            Expression::Peak(_, span) |
+            Expression::Consume(_, span) |
+            Expression::Binary { span, .. } |
+            Expression::Call { span, .. } => span,
+        }
     }
     
     pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
@@ -71,36 +94,54 @@ pub struct Variable {
     pub typ: PermissionedType,
 }
 
+/// A statement, with the span of source it was parsed from. An expression
+/// statement spans exactly its expression.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     Declaration {
         name: String,
         typ: PermissionedType,
         initializer: Option<Expression>,
+        span: Span,
     },
     Assignment {
         target: String,
         value: Expression,
         target_type: PermissionedType,
+        /// Binary operator of a compound assignment such as `+=`
+        operator: Option<TokenType>,
+        span: Span,
     },
     Expression(Expression),
-    Print(Expression),
-    Block(Vec<Statement>),
-    Return(Expression),
+    Print(Expression, Span),
+    Block(Vec<Statement>, Span),
+    Return(Expression, Span),
     Actor {
         name: String,
         state: Vec<Statement>,
         methods: Vec<Statement>,
         behaviors: Vec<Statement>,
+        span: Span,
     },
     Function {
         name: String,
+        type_params: Vec<String>, // Generic parameters, e.g. `T` in `fn id<T>`
         params: Vec<(String, PermissionedType)>,
         body: Vec<Statement>,
         return_type: Option<PermissionedType>,
         is_behavior: bool,
+        span: Span,
     },
-    AtomicBlock(Vec<Statement>),
+    AtomicBlock(Vec<Statement>, Span),
+    While {
+        condition: Expression,
+        body: Vec<Statement>,
+        span: Span,
+    },
+    /// Leave the innermost loop
+    Break(Span),
+    /// Go on with the next iteration of the innermost loop
+    Continue(Span),
 }
 
 impl Statement {
@@ -126,32 +167,55 @@ impl Statement {
         }
     }
     
-    pub fn new_declaration(name: String, typ: PermissionedType, initializer: Option<Expression>) -> Self {
-        Statement::Declaration { name, typ, initializer }
+    pub fn new_declaration(name: String, typ: PermissionedType, initializer: Option<Expression>, span: Span) -> Self {
+        Statement::Declaration { name, typ, initializer, span }
     }
     
-    pub fn new_assignment(target: String, value: Expression, target_type: PermissionedType) -> Self {
-        Statement::Assignment { target, value, target_type }
+    pub fn new_assignment(target: String, value: Expression, target_type: PermissionedType, span: Span) -> Self {
+        Statement::Assignment { target, value, target_type, operator: None, span }
     }
 
+
     pub fn new_expression(expr: Expression) -> Self {
         Statement::Expression(expr)
     }
     
-    pub fn new_print(expr: Expression) -> Self {
-        Statement::Print(expr)
+    pub fn new_print(expr: Expression, span: Span) -> Self {
+        Statement::Print(expr, span)
+    }
+    
+    pub fn new_block(statements: Vec<Statement>, span: Span) -> Self {
+        Statement::Block(statements, span)
     }
     
-    pub fn new_block(statements: Vec<Statement>) -> Self {
-        Statement::Block(statements)
+    pub fn new_return(expr: Expression, span: Span) -> Self {
+        Statement::Return(expr, span)
     }
     
-    pub fn new_return(expr: Expression) -> Self {
-        Statement::Return(expr)
+    pub fn new_atomic_block(statements: Vec<Statement>, span: Span) -> Self {
+        Statement::AtomicBlock(statements, span)
     }
     
-    pub fn new_atomic_block(statements: Vec<Statement>) -> Self {
-        Statement::AtomicBlock(statements)
+    pub fn new_while(condition: Expression, body: Vec<Statement>, span: Span) -> Self {
+        Statement::While { condition, body, span }
+    }
+    
+    /// The source this statement was parsed from
+    pub fn span(&self) -> &Span {
+        match self {
+            Statement::Expression(expr) => expr.span(),
+            Statement::Print(_, span) |
+            Statement::Block(_, span) |
+            Statement::Return(_, span) |
+            Statement::AtomicBlock(_, span) |
+            Statement::Break(span) |
+            Statement::Continue(span) |
+            Statement::While { span, .. } |
+            Statement::Declaration { span, .. } |
+            Statement::Assignment { span, .. } |
+            Statement::Actor { span, .. } |
+            Statement::Function { span, .. } => span,
+        }
     }
     
     pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
@@ -161,23 +225,32 @@ impl Statement {
 
 pub struct FunctionBuilder {
     name: String,
+    type_params: Vec<String>,
     parameters: Vec<(String, PermissionedType)>,
     body: Vec<Statement>,
     return_type: Option<PermissionedType>,
     is_behavior: bool,
+    span: Span,
 }
 
 impl FunctionBuilder {
     pub fn new(name: String) -> Self {
         FunctionBuilder {
             name,
+            type_params: Vec::new(),
             parameters: Vec::new(),
             body: Vec::new(),
             return_type: None,
             is_behavior: false,
+            span: Span::point(0, 0),
         }
     }
     
+    pub fn with_type_params(mut self, type_params: Vec<String>) -> Self {
+        self.type_params = type_params;
+        self
+    }
+    
     pub fn with_parameter(mut self, name: String, typ: PermissionedType) -> Self {
         self.parameters.push((name, typ));
         self
@@ -198,13 +271,20 @@ impl FunctionBuilder {
         self
     }
     
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+    
     pub fn build(self) -> Statement {
         Statement::Function {
             name: self.name,
+            type_params: self.type_params,
             params: self.parameters,
             body: self.body,
             return_type: self.return_type,
             is_behavior: self.is_behavior,
+            span: self.span,
         }
     }
 }
@@ -0,0 +1,95 @@
+//! The diagnostic code registry
+//!
+//! Every stable code any phase puts on a `Diagnostic` is defined here,
+//! so no two kinds of error can share one. The letter names the phase
+//! that reports it: `E` for the front end, `P` for the middle end's
+//! permission checks, `S` for its other semantic checks and `R` for the
+//! interpreter. Within `E`, resolution errors take `E00xx`, parse errors
+//! `E01xx` and errors reading the source `E02xx`.
+//!
+//! Refer to a code through its constant, e.g. `codes::parse::UNEXPECTED_TOKEN`,
+//! never by spelling out the string.
+
+macro_rules! registry {
+    ($($phase:ident { $($name:ident = $code:literal, $summary:literal;)* })*) => {
+        $(
+            pub mod $phase {
+                $(
+                    #[doc = $summary]
+                    pub const $name: &str = $code;
+                )*
+            }
+        )*
+
+        /// Every registered code with a one-line summary, in table order
+        pub const ALL: &[(&str, &str)] = &[$($(($code, $summary),)*)*];
+    };
+}
+
+registry! {
+    resolve {
+        DUPLICATE_SYMBOL = "E0001", "A name is defined twice in one scope";
+        UNDEFINED_SYMBOL = "E0002", "A name is used that is not in scope";
+        IMMUTABLE_ASSIGNMENT = "E0003", "A variable without write permission is assigned";
+        PERMISSION_VIOLATION = "E0004", "A variable is shared with permissions it cannot give";
+        READ_ACCESS_VIOLATION = "E0005", "A reads variable is copied without clone or peak";
+        TYPE_MISMATCH = "E0006", "An expression has a different type from the one expected";
+        INVALID_OPERANDS = "E0007", "An operator is applied to types it is not defined for";
+    }
+    parse {
+        UNEXPECTED_TOKEN = "E0101", "The parser found a token it did not expect";
+        INVALID_EXPRESSION = "E0102", "An expression is missing or malformed";
+        SYNTAX_ERROR = "E0103", "A statement or declaration is malformed";
+    }
+    source {
+        UNREADABLE_FILE = "E0201", "A source file cannot be read";
+    }
+    permission {
+        MISSING_READ = "P0001", "A variable is read without read permission";
+        MISSING_WRITE = "P0002", "A variable is written without write permission";
+        PEAK_WITHOUT_READ = "P0003", "A variable is peaked without read permission";
+        ILLEGAL_ALIAS = "P0004", "A variable whose permissions are not shareable is aliased";
+        WRITE_ALIAS_CONFLICT = "P0005", "A second writable alias is made while another writer is in use";
+        EXCLUSIVE_VIOLATION = "P0006", "A variable that is not exclusive is passed where exclusive access is required";
+        ARGUMENT_PERMISSION = "P0007", "An argument lacks a permission its parameter needs";
+        USE_AFTER_CONSUME = "P0008", "A variable is used after it was consumed";
+        UNDEFINED_VARIABLE = "P0009", "A variable is used that is not declared";
+        UNKNOWN_FUNCTION = "P0010", "A call names a function that is not defined";
+        ARITY_MISMATCH = "P0011", "A call passes the wrong number of arguments";
+        STORED_ALIAS = "P0012", "A variable another function stored an alias of is passed for exclusive writing";
+        UNSENDABLE_PARAMETER = "P0013", "A behavior parameter's permissions cannot cross to another actor";
+        UNSENDABLE_ARGUMENT = "P0014", "A behavior is sent a value that is neither exclusive nor immutable";
+        DATA_RACE = "P0015", "Two behaviors access a writes global outside atomic blocks, one of them writing";
+        ATOMIC_NON_SHAREABLE = "P0016", "An atomic block touches a variable that cannot be shared";
+        ATOMIC_UNSAFE_CALL = "P0017", "An atomic block calls a behavior, or a function that calls one";
+        ACTOR_STATE_ACCESS = "P0018", "An actor's state is used from outside that actor";
+    }
+    semantic {
+        TYPE_MISMATCH = "S0001", "A value has a different type from the one its use needs";
+        INVALID_OPERANDS = "S0002", "An operator is applied to types it is not defined for";
+        MISPLACED_LOOP_CONTROL = "S0003", "A break or continue is outside any loop of its function";
+        UNREACHABLE_CODE = "S0004", "A statement can never run";
+        UNRESOLVED_NAME = "S0005", "A name is used that no declaration in scope has";
+        DUPLICATE_DEFINITION = "S0006", "A name is defined twice in one scope";
+        SHADOWED_VARIABLE = "S0007", "A variable hides one of the same name from an enclosing scope";
+        WRONG_NAMESPACE = "S0008", "A function is used as a value, or a value is called";
+    }
+    runtime {
+        UNKNOWN_FUNCTION = "R0001", "A call names a function the program does not have";
+        ARITY_MISMATCH = "R0002", "A function is called with the wrong number of arguments";
+        UNINITIALIZED_VARIABLE = "R0003", "A variable is read before it is assigned";
+        UNKNOWN_BLOCK = "R0004", "A jump names a block the function does not have";
+        MISSING_PHI_SOURCE = "R0005", "A phi has no value for the block control came from";
+        INVALID_OPERANDS = "R0006", "An operation is applied to values of the wrong kind";
+        NON_BOOLEAN_CONDITION = "R0007", "A branch condition is not a boolean";
+        INVALID_CAST = "R0008", "A value cannot be converted to the type asked for";
+        INVALID_INTRINSIC_ARGUMENTS = "R0009", "A built-in is called with arguments it does not take";
+        DIVISION_BY_ZERO = "R0010", "An integer is divided by zero";
+        LIMIT_EXCEEDED = "R0011", "The program used more of a resource than the run's limits allow";
+    }
+}
+
+/// The summary of a registered code
+pub fn summary(code: &str) -> Option<&'static str> {
+    ALL.iter().find(|(registered, _)| *registered == code).map(|(_, summary)| *summary)
+}
@@ -0,0 +1,462 @@
+//! Diagnostics shared by every compiler phase
+//!
+//! Parse, resolution, type and permission errors all describe themselves
+//! as a `Diagnostic`, and `DiagnosticRenderer` is the single place that
+//! turns one into text or JSON, so every phase reports in the same format.
+
+use crate::source_manager::SourceManager;
+use crate::symbol_table::Span;
+use colored::{Color, Colorize};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Prevents compilation from succeeding
+    Error,
+    /// Allows compilation but points at a likely mistake
+    Warning,
+    /// Additional information
+    Note,
+    /// A suggestion for improvement
+    Help,
+}
+
+impl Severity {
+    fn color(self) -> Color {
+        match self {
+            Severity::Error => Color::Red,
+            Severity::Warning => Color::Yellow,
+            Severity::Note => Color::Green,
+            Severity::Help => Color::Cyan,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        })
+    }
+}
+
+/// A span with a message explaining its part in a diagnostic
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self { span, message: message.into() }
+    }
+}
+
+/// A change that would fix a diagnostic
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    /// What to do, e.g. "add read permission"
+    pub message: String,
+
+    /// The declaration or expression to write instead, if there is one
+    pub replacement: Option<String>,
+}
+
+impl Suggestion {
+    pub fn new(message: impl Into<String>, replacement: Option<String>) -> Self {
+        Self { message: message.into(), replacement }
+    }
+}
+
+/// A message about the program, with everything needed to render it: a
+/// severity, an optional stable code, a message, a primary label at the
+/// offending span, secondary labels at related spans, free-form notes and
+/// suggested fixes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// Stable identifier of the diagnostic kind, e.g. `E0002` or `P0001`
+    pub code: Option<String>,
+
+    pub message: String,
+
+    /// Where the problem is
+    pub primary: Option<Label>,
+
+    /// Related locations, such as an earlier definition
+    pub secondary: Vec<Label>,
+
+    pub notes: Vec<String>,
+
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn note(message: impl Into<String>) -> Self {
+        Self::new(Severity::Note, message)
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Point at the offending span
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.primary = Some(Label::new(span, message));
+        self
+    }
+
+    /// Point at a related span
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    /// Suggest a fix that has no replacement text
+    pub fn with_help(self, message: impl Into<String>) -> Self {
+        self.with_suggestion(Suggestion::new(message, None))
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Whether `other` is the same problem found again: the same code at
+    /// the same primary span. Without a primary span nothing says so.
+    pub fn duplicates(&self, other: &Diagnostic) -> bool {
+        match (&self.primary, &other.primary) {
+            (Some(label), Some(other_label)) => self.code == other.code && label.span == other_label.span,
+            _ => false,
+        }
+    }
+}
+
+/// Keep only the first of the diagnostics that report the same problem
+pub fn dedup(diagnostics: &mut Vec<Diagnostic>) {
+    let mut kept: Vec<Diagnostic> = Vec::with_capacity(diagnostics.len());
+    for diagnostic in diagnostics.drain(..) {
+        if !kept.iter().any(|earlier| earlier.duplicates(&diagnostic)) {
+            kept.push(diagnostic);
+        }
+    }
+    *diagnostics = kept;
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&DiagnosticRenderer::new().render(self))
+    }
+}
+
+/// Errors that can describe themselves as a diagnostic. Each error type
+/// implements `From<&Error> for Diagnostic`, with its code and its span;
+/// this is that conversion as a method.
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+impl<T> ToDiagnostic for T
+where
+    for<'a> &'a T: Into<Diagnostic>,
+{
+    fn to_diagnostic(&self) -> Diagnostic {
+        self.into()
+    }
+}
+
+/// Formats diagnostics as text, quoting source lines when the source is
+/// available
+pub struct DiagnosticRenderer<'a> {
+    sources: Cow<'a, SourceManager>,
+    color: bool,
+}
+
+impl Default for DiagnosticRenderer<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> DiagnosticRenderer<'a> {
+    /// A renderer without source text
+    pub fn new() -> Self {
+        Self { sources: Cow::Owned(SourceManager::new()), color: false }
+    }
+
+    /// A renderer that quotes lines of a single source text
+    pub fn with_source(source: &str) -> Self {
+        let mut sources = SourceManager::new();
+        sources.set_default_source(source);
+        Self { sources: Cow::Owned(sources), color: false }
+    }
+
+    /// A renderer that quotes lines of every file `sources` knows
+    pub fn with_source_manager(sources: &'a SourceManager) -> Self {
+        Self { sources: Cow::Borrowed(sources), color: false }
+    }
+
+    /// Color the severity, the gutter and the underlines with ANSI
+    /// escapes, for a terminal. `colored` only colors what goes to a
+    /// terminal stdout by itself, and diagnostics go to stderr, so this
+    /// overrides it for the process.
+    pub fn with_color(mut self, color: bool) -> Self {
+        if color {
+            colored::control::set_override(true);
+        }
+        self.color = color;
+        self
+    }
+
+    /// Render one diagnostic, quoting the lines its labels are on:
+    ///
+    /// ```text
+    /// error[E0002]: undefined variable `z`
+    ///  --> 3:15
+    ///   |
+    /// 3 | reads y = z
+    ///   |           ^ variable not found in this scope
+    /// help: did you mean `x`?
+    /// ```
+    ///
+    /// The primary label's span is underlined with a caret at its start and
+    /// tildes for the rest, `^~~~`, and secondary labels' with tildes alone.
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut output = String::new();
+        let severity = diagnostic.severity.color();
+        let heading = match &diagnostic.code {
+            Some(code) => format!("{}[{}]", diagnostic.severity, code),
+            None => diagnostic.severity.to_string(),
+        };
+        writeln!(output, "{}{}", self.paint(&heading, severity), self.bold(&format!(": {}", diagnostic.message))).unwrap();
+
+        // Labels at the same place share one snippet, the primary's if it
+        // is one of them
+        let mut snippets: Vec<(bool, Vec<&Label>)> = Vec::new();
+        let primary = diagnostic.primary.iter().map(|label| (label, true));
+        let secondary = diagnostic.secondary.iter().map(|label| (label, false));
+        for (label, is_primary) in primary.chain(secondary) {
+            match snippets.iter_mut().find(|(_, labels)| self.same_place(&labels[0].span, &label.span)) {
+                Some((_, labels)) => labels.push(label),
+                None => snippets.push((is_primary, vec![label])),
+            }
+        }
+        for (is_primary, labels) in snippets {
+            let span = &labels[0].span;
+            let location = self.sources.format_location(span);
+            if self.line(span).is_some() {
+                let color = match is_primary {
+                    true => severity,
+                    false => Color::Blue,
+                };
+                let messages: Vec<&str> = labels.iter()
+                    .map(|label| label.message.as_str())
+                    .filter(|message| !message.is_empty())
+                    .collect();
+                writeln!(output, "{} {}", self.paint(" -->", Color::Blue), location).unwrap();
+                self.quote(&mut output, span, &messages, is_primary, color);
+                continue;
+            }
+            for (index, label) in labels.iter().enumerate() {
+                if is_primary && index == 0 {
+                    writeln!(output, "{} {}", self.paint(" -->", Color::Blue), location).unwrap();
+                    if !label.message.is_empty() {
+                        writeln!(output, "  {} {}", self.paint("=", Color::Blue), label.message).unwrap();
+                    }
+                } else {
+                    writeln!(output, "  {} {} at {}", self.paint("=", Color::Blue), label.message, location).unwrap();
+                }
+            }
+        }
+
+        for note in &diagnostic.notes {
+            writeln!(output, "{} {}", self.paint("note:", Severity::Note.color()), note).unwrap();
+        }
+        for suggestion in &diagnostic.suggestions {
+            writeln!(output, "{} {}", self.paint("help:", Severity::Help.color()), suggestion.message).unwrap();
+            if let Some(replacement) = &suggestion.replacement {
+                writeln!(output, "    {}", self.paint(replacement, Color::Green)).unwrap();
+            }
+        }
+
+        output.trim_end().to_string()
+    }
+
+    /// Render one diagnostic as a line of JSON for editors and CI, with
+    /// every label a span that says whether it is the primary one:
+    ///
+    /// ```text
+    /// {"severity":"error","code":"E0002","message":"undefined variable `z`",
+    ///  "spans":[{"file":"main.cd","line_start":3,"column_start":15,"line_end":3,
+    ///  "column_end":15,"is_primary":true,"label":"variable not found in this scope"}],
+    ///  "notes":[],"suggestions":[{"message":"did you mean `x`?","replacement":null}]}
+    /// ```
+    pub fn render_json(&self, diagnostic: &Diagnostic) -> String {
+        let primary = diagnostic.primary.iter().map(|label| (label, true));
+        let secondary = diagnostic.secondary.iter().map(|label| (label, false));
+        let spans = primary.chain(secondary)
+            .map(|(label, is_primary)| JsonSpan {
+                file: self.file_name(&label.span),
+                line_start: label.span.start_line,
+                column_start: label.span.start_column,
+                line_end: label.span.end_line,
+                column_end: label.span.end_column,
+                is_primary,
+                label: &label.message,
+            })
+            .collect();
+        let json = JsonDiagnostic {
+            severity: diagnostic.severity,
+            code: diagnostic.code.as_deref(),
+            message: &diagnostic.message,
+            spans,
+            notes: &diagnostic.notes,
+            suggestions: &diagnostic.suggestions,
+        };
+        serde_json::to_string(&json).expect("diagnostics serialize to JSON")
+    }
+
+    /// Render every diagnostic, separated by blank lines
+    pub fn render_all(&self, diagnostics: &[Diagnostic]) -> String {
+        diagnostics.iter()
+            .map(|diagnostic| self.render(diagnostic))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// The file a span is in, if it is known
+    fn file_name<'s>(&'s self, span: &'s Span) -> Option<&'s str> {
+        span.source_file.as_deref().or_else(|| span.file_id.and_then(|id| self.sources.file_name(id)))
+    }
+
+    /// The source line a span starts on, if the source is known. A span
+    /// in a file the renderer was not given is looked up in its single
+    /// source text, if it has one.
+    fn line(&self, span: &Span) -> Option<&str> {
+        match span.file_id.filter(|&id| self.sources.file_name(id).is_some()) {
+            Some(id) => self.sources.get_file_line(id, span.start_line),
+            None => self.sources.get_line(span.start_line),
+        }
+    }
+
+    /// Whether two spans cover the same text of the same file
+    fn same_place(&self, a: &Span, b: &Span) -> bool {
+        (a.start_line, a.start_column, a.end_line, a.end_column) == (b.start_line, b.start_column, b.end_line, b.end_column)
+            && self.file_name(a) == self.file_name(b)
+    }
+
+    /// Quote the line `span` starts on with the span underlined in
+    /// `color`, followed by the first message, and each further message
+    /// on a line of its own below it. The line must be available.
+    fn quote(&self, output: &mut String, span: &Span, messages: &[&str], primary: bool, color: Color) {
+        let line = self.line(span).expect("quoted spans have a source line");
+        let gutter = span.start_line.to_string();
+        let padding = " ".repeat(gutter.len());
+        let start = span.start_column.saturating_sub(1);
+        let width = if span.end_line == span.start_line && span.end_column > span.start_column {
+            span.end_column - span.start_column + 1
+        } else {
+            token_length(line, start)
+        };
+
+        let underline = match primary {
+            true => format!("^{}", "~".repeat(width - 1)),
+            false => "~".repeat(width),
+        };
+        let marker = format!("{} {}", underline, messages.first().copied().unwrap_or_default());
+        writeln!(output, "{}", self.paint(&format!("{} |", padding), Color::Blue)).unwrap();
+        writeln!(output, "{} {}", self.paint(&format!("{} |", gutter), Color::Blue), line.trim_end()).unwrap();
+        writeln!(output, "{} {}{}", self.paint(&format!("{} |", padding), Color::Blue), " ".repeat(start), self.paint(marker.trim_end(), color)).unwrap();
+        for message in messages.iter().skip(1) {
+            let indent = " ".repeat(start + width + 1);
+            writeln!(output, "{} {}{}", self.paint(&format!("{} |", padding), Color::Blue), indent, self.paint(message, color)).unwrap();
+        }
+    }
+
+    /// `text` bold and in `color`, if the output is colored
+    fn paint(&self, text: &str, color: Color) -> String {
+        match self.color {
+            true => text.color(color).bold().to_string(),
+            false => text.to_string(),
+        }
+    }
+
+    /// `text` bold, if the output is colored
+    fn bold(&self, text: &str) -> String {
+        match self.color {
+            true => text.bold().to_string(),
+            false => text.to_string(),
+        }
+    }
+}
+
+/// A diagnostic as `render_json` writes it
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    severity: Severity,
+    code: Option<&'a str>,
+    message: &'a str,
+    spans: Vec<JsonSpan<'a>>,
+    notes: &'a [String],
+    suggestions: &'a [Suggestion],
+}
+
+/// A label with where it is spelled out, lines and columns counting from 1
+#[derive(Serialize)]
+struct JsonSpan<'a> {
+    file: Option<&'a str>,
+    line_start: usize,
+    column_start: usize,
+    line_end: usize,
+    column_end: usize,
+    is_primary: bool,
+    label: &'a str,
+}
+
+/// Length of the identifier or number starting at `start`, at least one
+fn token_length(line: &str, start: usize) -> usize {
+    line.chars()
+        .skip(start)
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .count()
+        .max(1)
+}
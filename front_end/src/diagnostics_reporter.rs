@@ -1,3 +1,5 @@
+use crate::codes;
+use crate::diagnostic::{Diagnostic, DiagnosticRenderer, Suggestion, ToDiagnostic};
 use crate::symbol_table::{ResolutionError};
 use crate::source_manager::SourceManager;
 
@@ -9,149 +11,104 @@ impl DiagnosticReporter {
     pub fn new(source_manager: SourceManager) -> Self {
         Self { source_manager }
     }
-    
+
     pub fn report_error(&self, error: &ResolutionError) -> String {
+        self.report(&error.to_diagnostic())
+    }
+
+    /// Render any diagnostic against the registered sources
+    pub fn report(&self, diagnostic: &Diagnostic) -> String {
+        DiagnosticRenderer::with_source_manager(&self.source_manager).render(diagnostic)
+    }
+}
+
+impl From<&ResolutionError> for Diagnostic {
+    fn from(error: &ResolutionError) -> Self {
         match error {
             ResolutionError::DuplicateSymbol { name, first, second } => {
-                let mut output = format!("error[E0001]: duplicate definition of `{}`\n", name);
-                
-                // First definition - use accurate line/column from token
-                let first_loc = format!("{}:{}", first.start_line, first.start_column);
-                output.push_str(&format!("--> {}\n", first_loc));
-                
-                // Get the snippet from source manager with proper position
-                let first_snippet = self.source_manager.get_snippet(first);
-                output.push_str(&format!("{}\n", first_snippet));
-                output.push_str(" | first definition here\n\n");
-                
-                // Second definition
-                let second_loc = format!("{}:{}", second.start_line, second.start_column);
-                output.push_str(&format!("--> {}\n", second_loc));
-                
-                let second_snippet = self.source_manager.get_snippet(second);
-                output.push_str(&format!("{}\n", second_snippet));
-                output.push_str(" | redefinition here\n\n");
-                
-                output.push_str("note: each variable must be defined only once per scope");
-                
-                output
+                Diagnostic::error(format!("duplicate definition of `{}`", name))
+                    .with_code(codes::resolve::DUPLICATE_SYMBOL)
+                    .with_primary(second.clone(), "redefinition here")
+                    .with_secondary(first.clone(), "first definition here")
+                    .with_note("each name must be defined only once per scope")
             },
-            ResolutionError::UndefinedSymbol { name, span } => {
-                let mut output = format!("error[E0002]: undefined variable `{}`\n", name);
-                
-                let loc = format!("{}:{}", span.start_line, span.start_column);
-                output.push_str(&format!("--> {}\n", loc));
-                
-                let snippet = self.source_manager.get_snippet(span);
-                output.push_str(&format!("{}\n", snippet));
-                output.push_str(" | variable not found in this scope\n\n");
-                
-                output.push_str("help: consider declaring the variable before using it");
-                
-                output
+            ResolutionError::UndefinedSymbol { name, span, suggestion } => {
+                let diagnostic = Diagnostic::error(format!("undefined variable `{}`", name))
+                    .with_code(codes::resolve::UNDEFINED_SYMBOL)
+                    .with_primary(span.clone(), "variable not found in this scope");
+                match suggestion {
+                    Some(candidate) => diagnostic.with_suggestion(Suggestion::new(
+                        format!("did you mean `{}`?", candidate),
+                        Some(candidate.clone()),
+                    )),
+                    None => diagnostic.with_help("consider declaring the variable before using it"),
+                }
             },
             ResolutionError::ImmutableAssignment { name, span, declaration_span } => {
-                let mut output = format!("error[E0003]: cannot assign to immutable variable `{}`\n", name);
-                
-                // Show where the immutable assignment happened
-                let loc = format!("{}:{}", span.start_line, span.start_column);
-                output.push_str(&format!("--> {}\n", loc));
-                
-                let snippet = self.source_manager.get_snippet(span);
-                output.push_str(&format!("{}\n", snippet));
-                output.push_str(" | cannot assign to immutable variable\n\n");
-                
-                // If we have the declaration span, show it too
+                let mut diagnostic = Diagnostic::error(format!("cannot assign to immutable variable `{}`", name))
+                    .with_code(codes::resolve::IMMUTABLE_ASSIGNMENT)
+                    .with_primary(span.clone(), "cannot assign to immutable variable");
                 if let Some(decl_span) = declaration_span {
-                    let decl_loc = format!("{}:{}", decl_span.start_line, decl_span.start_column);
-                    output.push_str(&format!("--> {}\n", decl_loc));
-                    
-                    let decl_snippet = self.source_manager.get_snippet(decl_span);
-                    output.push_str(&format!("{}\n", decl_snippet));
-                    output.push_str(" | variable declared here without write permission\n\n");
+                    diagnostic = diagnostic.with_secondary(decl_span.clone(), "variable declared here without write permission");
                 }
-                
-                output.push_str("help: add 'write' or 'writes' permission to make the variable mutable");
-                
-                output
+                diagnostic.with_help("add 'write' or 'writes' permission to make the variable mutable")
             },
             ResolutionError::PermissionViolation { name, required, provided, span, declaration_span } => {
-                let mut output = format!("error[E0004]: permission violation for variable `{}`\n", name);
-                
-                // Show where the violation happened
-                let loc = format!("{}:{}", span.start_line, span.start_column);
-                output.push_str(&format!("--> {}\n", loc));
-                
-                let snippet = self.source_manager.get_snippet(span);
-                output.push_str(&format!("{}\n", snippet));
-                output.push_str(&format!(" | requires permission '{}' but found '{}'\n\n", required, provided));
-                
-                // If we have the declaration span, show it too
+                let mut diagnostic = Diagnostic::error(format!("permission violation for variable `{}`", name))
+                    .with_code(codes::resolve::PERMISSION_VIOLATION)
+                    .with_primary(span.clone(), format!("requires permission '{}' but found '{}'", required, provided));
                 if let Some(decl_span) = declaration_span {
-                    let decl_loc = format!("{}:{}", decl_span.start_line, decl_span.start_column);
-                    output.push_str(&format!("--> {}\n", decl_loc));
-                    
-                    let decl_snippet = self.source_manager.get_snippet(decl_span);
-                    output.push_str(&format!("{}\n", decl_snippet));
-                    output.push_str(&format!(" | variable declared with '{}' permission\n\n", provided));
+                    diagnostic = diagnostic.with_secondary(decl_span.clone(), format!("variable declared with '{}' permission", provided));
                 }
-                
-                output.push_str(&format!("help: update the variable declaration to include '{}' permission", required));
-                
-                output
+                diagnostic.with_help(format!("update the variable declaration to include '{}' permission", required))
             },
             ResolutionError::ReadAccessViolation { name, span, declaration_span, target_permission } => {
-                let mut output = format!("error[E0005]: cannot directly assign reads variable `{}` to {} variable\n", name, target_permission);
-                
-                // Show where the violation happened
-                let loc = format!("{}:{}", span.start_line, span.start_column);
-                output.push_str(&format!("--> {}\n", loc));
-                
-                let snippet = self.source_manager.get_snippet(span);
-                output.push_str(&format!("{}\n", snippet));
-                output.push_str(" | cannot directly assign reads variable without clone or peak\n\n");
-                
-                // If we have the declaration span, show it too
+                let mut diagnostic = Diagnostic::error(format!(
+                        "cannot directly assign reads variable `{}` to {} variable", name, target_permission
+                    ))
+                    .with_code(codes::resolve::READ_ACCESS_VIOLATION)
+                    .with_primary(span.clone(), "cannot directly assign reads variable without clone or peak");
                 if let Some(decl_span) = declaration_span {
-                    let decl_loc = format!("{}:{}", decl_span.start_line, decl_span.start_column);
-                    output.push_str(&format!("--> {}\n", decl_loc));
-                    
-                    let decl_snippet = self.source_manager.get_snippet(decl_span);
-                    output.push_str(&format!("{}\n", decl_snippet));
-                    output.push_str(" | variable declared with 'reads' permission\n\n");
+                    diagnostic = diagnostic.with_secondary(decl_span.clone(), "variable declared with 'reads' permission");
                 }
-                
-                // Always provide both solutions regardless of target permission
-                output.push_str("help: you have two options to fix this issue:\n\n");
-                output.push_str("Option 1: use 'clone' to create a deep copy of the variable\n");
-                output.push_str(&format!("  reads {} = clone {}\n\n", "c", name)); // Using "c" as example variable name
-                
-                output.push_str("Option 2: use 'peak' with 'read' permission to create a read-only reference\n");
-                output.push_str(&format!("  read {} = peak {}\n", "c", name)); // Using "c" as example
-                
-                output
+
+                // Both fixes apply regardless of the target permission
+                diagnostic
+                    .with_suggestion(Suggestion::new(
+                        "use 'clone' to create a deep copy of the variable",
+                        Some(format!("reads c = clone {}", name)),
+                    ))
+                    .with_suggestion(Suggestion::new(
+                        "use 'peak' with 'read' permission to create a read-only reference",
+                        Some(format!("read c = peak {}", name)),
+                    ))
             },
             ResolutionError::TypeMismatch { expected, found, span, context } => {
-                let mut output = format!("error[E0006]: type mismatch {}\n", context);
-                
-                let loc = format!("{}:{}", span.start_line, span.start_column);
-                output.push_str(&format!("--> {}\n", loc));
-                
-                let snippet = self.source_manager.get_snippet(span);
-                output.push_str(&format!("{}\n", snippet));
-                
-                // Show the expected vs. found types
-                output.push_str(&format!("   | expected type `{}`, found `{}`\n\n", expected, found));
-                
-                // Add helpful suggestion
+                let diagnostic = Diagnostic::error(format!("type mismatch {}", context))
+                    .with_code(codes::resolve::TYPE_MISMATCH)
+                    .with_primary(span.clone(), format!("expected type `{}`, found `{}`", expected, found));
                 if context.contains("return") {
-                    output.push_str("help: ensure the expression's type matches the function's return type");
+                    diagnostic.with_help("ensure the expression's type matches the function's return type")
+                } else {
+                    diagnostic.with_help("ensure the types match with what is expected")
+                }
+            },
+            ResolutionError::InvalidOperands { operator, left, right, span, context } => {
+                let diagnostic = Diagnostic::error(format!(
+                        "cannot {} `{}` and `{}` {}", operator.operation_verb(), left, right, context
+                    ))
+                    .with_code(codes::resolve::INVALID_OPERANDS)
+                    .with_primary(span.clone(), format!(
+                        "no implementation for `{} {} {}`", left, operator.operator_symbol(), right
+                    ));
+                if left != right {
+                    diagnostic.with_help("both operands must have the same type; convert one of them explicitly")
+                } else if operator.is_arithmetic() {
+                    diagnostic.with_help("arithmetic is only defined for numeric types")
                 } else {
-                    output.push_str("help: ensure the types match with what is expected");
+                    diagnostic.with_help("ordering comparisons are only defined for numeric types")
                 }
-                
-                output
             },
         }
     }
-}
\ No newline at end of file
+}
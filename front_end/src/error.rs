@@ -1,3 +1,5 @@
+use crate::codes;
+use crate::diagnostic::Diagnostic;
 use crate::symbol_table::{ResolutionError, Span};
 use std::fmt;
 
@@ -24,15 +26,15 @@ impl ParseError {
     }
     
     pub fn unexpected_token(span: Span, message: String) -> Self {
-        Self::new(span, message).with_code("E0001")
+        Self::new(span, message).with_code(codes::parse::UNEXPECTED_TOKEN)
     }
     
     pub fn invalid_expression(span: Span, message: String) -> Self {
-        Self::new(span, message).with_code("E0002")
+        Self::new(span, message).with_code(codes::parse::INVALID_EXPRESSION)
     }
     
     pub fn syntax_error(span: Span, message: String) -> Self {
-        Self::new(span, message).with_code("E0003")
+        Self::new(span, message).with_code(codes::parse::SYNTAX_ERROR)
     }
 }
 
@@ -52,6 +54,15 @@ impl fmt::Display for ParseError {
     }
 }
 
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let code = error.error_code.as_deref().unwrap_or(codes::parse::SYNTAX_ERROR);
+        Diagnostic::error(error.message.clone())
+            .with_code(code)
+            .with_primary(error.span.clone(), "")
+    }
+}
+
 /// Common error type for compiler errors
 #[derive(Debug, Clone)]
 pub enum CompileError {
@@ -78,3 +89,17 @@ impl fmt::Display for CompileError {
         }
     }
 }
+
+impl From<&CompileError> for Diagnostic {
+    fn from(error: &CompileError) -> Self {
+        match error {
+            CompileError::Parse(err) => err.into(),
+            CompileError::Resolution(err) => err.into(),
+            CompileError::TypeError(msg, span) => Diagnostic::error(format!("type error: {}", msg))
+                .with_code(codes::resolve::TYPE_MISMATCH)
+                .with_primary(span.clone(), ""),
+            CompileError::IoError(msg) => Diagnostic::error(format!("io error: {}", msg))
+                .with_code(codes::source::UNREADABLE_FILE),
+        }
+    }
+}
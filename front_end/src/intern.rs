@@ -0,0 +1,114 @@
+//! Interned identifiers
+//!
+//! Variable and function names are looked up and copied far more often
+//! than they are created. `Name` is a `u32` handle to a string stored once
+//! in a process-wide interner, so copying a name is free and hashing or
+//! comparing one never touches the string. Both the front end and the
+//! middle end intern through this module, so a `Name` means the same
+//! identifier everywhere.
+//!
+//! Interned strings live for the rest of the process; identifiers are few
+//! and small, so this is cheaper than reference counting them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned identifier
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Name(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Name>,
+    strings: Vec<&'static str>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Name {
+    /// Intern a string, returning the handle it already has if it was
+    /// interned before
+    pub fn new(name: &str) -> Self {
+        let mut interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&id) = interner.ids.get(name) {
+            return id;
+        }
+        let id = Name(interner.strings.len() as u32);
+        let name: &'static str = Box::leak(name.into());
+        interner.strings.push(name);
+        interner.ids.insert(name, id);
+        id
+    }
+
+    /// The handle of a string if it was interned, without interning it.
+    /// Lookups of names nobody declared use this so they leave the
+    /// interner alone.
+    pub fn existing(name: &str) -> Option<Self> {
+        let interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+        interner.ids.get(name).copied()
+    }
+
+    /// The interned string
+    pub fn as_str(self) -> &'static str {
+        let interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+        interner.strings[self.0 as usize]
+    }
+
+    /// The raw handle, unique per distinct string
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Name::new(name)
+    }
+}
+
+impl From<&String> for Name {
+    fn from(name: &String) -> Self {
+        Name::new(name)
+    }
+}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl serde::Serialize for Name {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Name {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Name::new(&name))
+    }
+}
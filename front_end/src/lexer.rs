@@ -87,8 +87,13 @@ impl Lexer {
             // Existing keywords
             "fn" => TokenType::Fn,
             "on" => TokenType::On,
+            "atomic" => TokenType::Atomic,
+            "actor" => TokenType::Actor,
             "if" => TokenType::If,
             "else" => TokenType::Else,
+            "while" => TokenType::While,
+            "break" => TokenType::Break,
+            "continue" => TokenType::Continue,
             "print" => TokenType::Print,
             
             // Permission modifiers
@@ -103,13 +108,24 @@ impl Lexer {
             // Permission operations
             "peak" => TokenType::Peak,    // Add peak keyword
             "clone" => TokenType::Clone,  // Add clone keyword
+            "consume" => TokenType::Consume,
             
             // Types
             "Int" => TokenType::TypeInt,
             "Int8" => TokenType::TypeInt8,
+            "Int16" => TokenType::TypeInt16,
+            "Int32" => TokenType::TypeInt32,
+            "Int64" => TokenType::TypeInt64,
+            "UInt" => TokenType::TypeUInt,
+            "UInt8" => TokenType::TypeUInt8,
+            "UInt16" => TokenType::TypeUInt16,
+            "UInt32" => TokenType::TypeUInt32,
+            "UInt64" => TokenType::TypeUInt64,
+            "Float" => TokenType::TypeFloat,
+            "Float32" => TokenType::TypeFloat32,
             "Float64" => TokenType::TypeFloat64,
             "Bool" => TokenType::TypeBool,
-            // ... other types
+            "String" => TokenType::TypeString,
             
             // Default case - it's an identifier
             _ => TokenType::Identifier(text.to_string()),
@@ -154,7 +170,7 @@ impl Lexer {
         
         // Only add EOF token if we don't already have one
         // This prevents duplicate EOF tokens
-        if tokens.last().map_or(true, |t| t.token_type != TokenType::Eof) {
+        if !matches!(tokens.last(), Some(t) if t.token_type == TokenType::Eof) {
             tokens.push(Token::new(TokenType::Eof, "", self.line, self.start_column));
         }
         
@@ -266,14 +282,10 @@ impl Lexer {
                     self.advance();
                 },
                 // Skip comments
-                '/' => {
-                    if self.peek_next() == '/' {
-                        // Line comment - advance until EOL or EOF
-                        while self.peek() != '\n' && !self.is_at_end() {
-                            self.advance();
-                        }
-                    } else {
-                        return; // Not whitespace, so return
+                '/' if self.peek_next() == '/' => {
+                    // Line comment - advance until EOL or EOF
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
                     }
                 },
                 _ => return, // Not whitespace, so return
@@ -4,7 +4,10 @@ pub mod types;
 pub mod parser;
 pub mod lexer;
 pub mod symbol_table;
+pub mod intern; // Interned identifiers shared with the middle end
 pub mod source_manager;
+pub mod diagnostic; // Diagnostics shared by every phase
+pub mod codes; // The one table of diagnostic codes
 pub mod diagnostics_reporter;
 pub mod error; // Add new error module
 pub mod source_location; // Add new source location module
@@ -3,8 +3,8 @@ use crate::ast::{Expression, FunctionBuilder, Statement};
 use crate::types::{Type, Permission, PermissionedType};
 use crate::symbol_table::{ResolutionError, Span, Symbol, SymbolKind, SymbolTable};
 use crate::error::{ParseError, CompileError};
-use crate::type_inference::{TypeInferer, TypeInferenceExt};
-use crate::type_checker::{TypeChecker}; // Add this import
+use crate::type_inference::{TypeEnvironment, TypeInferer, TypeInferenceExt};
+use crate::source_manager::{FileId, SourceManager};
 use std::collections::HashMap;
 
 // Define a new Result type alias for parser operations
@@ -16,6 +16,9 @@ pub struct Parser {
     symbol_table: SymbolTable,
     token_locations: HashMap<usize, Span>,
     errors: Vec<CompileError>, // Track errors separately from symbol table
+    type_env: TypeEnvironment, // Function signatures inferred so far
+    function_spans: HashMap<String, Span>, // Where each function was first defined
+    type_params: Vec<String>, // Generic parameters of the functions being parsed
 }
 
 impl Parser {
@@ -34,6 +37,9 @@ impl Parser {
             symbol_table: SymbolTable::new(),
             token_locations,
             errors: Vec::new(),
+            type_env: TypeEnvironment::new(),
+            function_spans: HashMap::new(),
+            type_params: Vec::new(),
         }
     }
     
@@ -61,16 +67,7 @@ impl Parser {
         let tokens = lexer.scan_tokens();
         
         // Create token locations with accurate positions from token data
-        let mut token_locations = HashMap::new();
-        
-        for (i, token) in tokens.iter().enumerate() {
-            token_locations.insert(i, Span::new(
-                token.line,
-                token.column,
-                token.line,
-                token.column + token.length - 1
-            ));
-        }
+        let token_locations = token_spans(&tokens, None);
         
         Parser {
             tokens,
@@ -78,7 +75,46 @@ impl Parser {
             symbol_table: SymbolTable::new(),
             token_locations,
             errors: Vec::new(),
+            type_env: TypeEnvironment::new(),
+            function_spans: HashMap::new(),
+            type_params: Vec::new(),
+        }
+    }
+    
+    /// Create a parser for one file registered with the source manager;
+    /// spans produced while parsing carry the file's id
+    pub fn from_file(source_manager: &SourceManager, file_id: FileId) -> Self {
+        let mut parser = Self::new(Vec::new());
+        parser.load_file(source_manager, file_id);
+        parser
+    }
+    
+    /// Switch the parser over to another file while keeping the symbol
+    /// table, so declarations from previously parsed files stay visible
+    pub fn load_file(&mut self, source_manager: &SourceManager, file_id: FileId) {
+        use crate::lexer::Lexer;
+        
+        let source = source_manager.file_source(file_id).unwrap_or_default();
+        let name = source_manager.file_name(file_id).unwrap_or_default();
+        
+        let mut lexer = Lexer::new(source.to_string());
+        self.tokens = lexer.scan_tokens();
+        self.token_locations = token_spans(&self.tokens, Some((file_id, name)));
+        self.current = 0;
+    }
+    
+    /// Parse every file in the source manager, in registration order, into
+    /// a single program. Later files can refer to symbols declared in
+    /// earlier ones.
+    pub fn parse_files(&mut self, source_manager: &SourceManager) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        
+        for file_id in source_manager.file_ids() {
+            self.load_file(source_manager, file_id);
+            statements.extend(self.parse_statements());
         }
+        
+        statements
     }
     
     // Move these position tracking methods to a new SourcePosition trait or struct
@@ -109,6 +145,12 @@ impl Parser {
         }
     }
     
+    /// Check the token after the current one without consuming anything
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        self.tokens.get(self.current + 1)
+            .is_some_and(|token| &token.token_type == token_type)
+    }
+    
     fn match_token(&mut self, token_type: &TokenType) -> bool {
         if self.check(token_type) {
             self.advance();
@@ -161,7 +203,12 @@ impl Parser {
     
     // Helper method to get the current span
     fn current_span(&self) -> Span {
-        if let Some(span) = self.token_locations.get(&self.current) {
+        self.span_at(self.current)
+    }
+    
+    // Span of the token at `index`, including its file when known
+    fn span_at(&self, index: usize) -> Span {
+        if let Some(span) = self.token_locations.get(&index) {
             span.clone()
         } else {
             // Default span if not found
@@ -171,9 +218,6 @@ impl Parser {
     
     // Update parse_expression to return ParseResult
     pub fn parse_expression(&mut self) -> ParseResult<Expression> {
-        // First, log what we're trying to parse
-        println!("Parsing expression, current token: {:?}", self.peek().token_type);
-        
         // Delegate to comparison which handles operators via parse_addition, etc.
         self.parse_comparison()
     }
@@ -197,20 +241,15 @@ impl Parser {
 
         // Handle * and / operators (higher precedence)
         while self.match_token(&TokenType::Star) || self.match_token(&TokenType::Slash) {
-            println!("Found multiplication/division operator: {:?}", self.previous().token_type);
             let operator = self.previous().token_type.clone();
-            
-            // Print token for debugging
-            println!("Parsing right side of operation");
-            
             let right = self.parse_primary()?;
             
-            println!("Creating binary expression: {:?} {:?} {:?}", left, operator, right);
-            
+            let span = left.span().combine(right.span());
             left = Expression::Binary {
                 left: Box::new(left),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
 
@@ -227,10 +266,12 @@ impl Parser {
         ]) {
             let operator = self.previous().token_type.clone();
             let right = self.parse_addition()?;
+            let span = expr.span().combine(right.span());
             expr = Expression::Binary {
                 left: Box::new(expr),
                 operator,
                 right: Box::new(right),
+                span,
             };
         }
         
@@ -244,12 +285,11 @@ impl Parser {
                 TokenType::Number(val) => val,
                 _ => unreachable!(),
             };
-            return Ok(Expression::Number(value));
+            return Ok(Expression::Number(value, self.span_at(self.current - 1)));
         }
         
         // Handle grouping with parentheses
         if self.match_token(&TokenType::LeftParen) {
-            println!("Parsing grouped expression");
             let expr = self.parse_expression()?;
             self.consume(&TokenType::RightParen, "Expected ')' after expression")?;
             return Ok(expr); // Return the inner expression directly
@@ -257,14 +297,26 @@ impl Parser {
         
         // Handle peak operator
         if self.match_token(&TokenType::Peak) {
+            let start = self.span_at(self.current - 1);
             let expr = self.parse_primary()?;
-            return Ok(Expression::Peak(Box::new(expr)));
+            let span = start.combine(expr.span());
+            return Ok(Expression::Peak(Box::new(expr), span));
         }
         
         // Handle clone operator
         if self.match_token(&TokenType::Clone) {
+            let start = self.span_at(self.current - 1);
             let expr = self.parse_primary()?;
-            return Ok(Expression::Clone(Box::new(expr)));
+            let span = start.combine(expr.span());
+            return Ok(Expression::Clone(Box::new(expr), span));
+        }
+        
+        // Handle consume operator
+        if self.match_token(&TokenType::Consume) {
+            let start = self.span_at(self.current - 1);
+            let expr = self.parse_primary()?;
+            let span = start.combine(expr.span());
+            return Ok(Expression::Consume(Box::new(expr), span));
         }
         
         // Handle variable references
@@ -275,20 +327,18 @@ impl Parser {
             };
             
             // Create a span for this variable reference
-            let token = self.previous();
-            let span = Span::new(
-                token.line, 
-                token.column,
-                token.line,
-                token.column + token.length - 1
-            );
+            let span = self.span_at(self.current - 1);
+            
+            if self.match_token(&TokenType::LeftParen) {
+                return self.parse_call(name, span);
+            }
             
             // Allow identifiers even if they're not in the symbol table yet
             // (particularly for function parameters which might be referenced before they're added)
-            let _ = self.symbol_table.resolve(&name, span);
+            let _ = self.symbol_table.resolve(&name, span.clone());
             
             // Return the variable reference expression
-            return Ok(Expression::Variable(name));
+            return Ok(Expression::Variable(name, span));
         }
         
         // Other primary expression types...
@@ -299,6 +349,28 @@ impl Parser {
         ))
     }
 
+    /// Parse the arguments of a call to `function`, whose name ends at
+    /// `start`, up to the closing parenthesis
+    fn parse_call(&mut self, function: String, start: Span) -> ParseResult<Expression> {
+        let mut arguments = Vec::new();
+        
+        // Parse arguments list if not empty
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                arguments.push(self.parse_expression()?);
+                
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        
+        self.consume(&TokenType::RightParen, "Expected ')' after function arguments")?;
+        
+        let span = start.combine(&self.span_at(self.current - 1));
+        Ok(Expression::Call { function, arguments, span })
+    }
+
     // Improve error handling in parse_statement
     pub fn parse_statement(&mut self) -> ParseResult<Statement> {
         match self.peek().token_type {
@@ -311,35 +383,41 @@ impl Parser {
             TokenType::On => {
                 self.parse_function_declaration(true) // behavior
             },
+            TokenType::Actor => {
+                self.parse_actor_declaration()
+            },
             TokenType::Return => {
+                let start = self.current_span();
                 self.advance(); // consume 'return'
                 let value = self.parse_expression()?;
-                Ok(Statement::new_return(value))
+                let span = start.combine(value.span());
+                Ok(Statement::new_return(value, span))
             },
             TokenType::Print => {
+                let start = self.current_span();
                 self.advance(); // consume 'print'
                 let expr = self.parse_expression()?;
-                Ok(Statement::new_print(expr))
+                let span = start.combine(expr.span());
+                Ok(Statement::new_print(expr, span))
+            },
+            TokenType::Identifier(_) if self.check_next(&TokenType::Colon) => {
+                // A declaration without permission keywords, e.g. `x: Int = 5`
+                self.parse_variable_declaration()
             },
             TokenType::Identifier(_) => {
                 // This could be an assignment, function call, or a standalone expression
                 let start_pos = self.current;
                 let name = self.get_identifier_name()?;
                 
-                if self.match_token(&TokenType::Equal) {
-                    // Check symbol table first for permission
-                    let token = self.previous();
-                    let span = Span::new(
-                        token.line,
-                        token.column,
-                        token.line,
-                        token.column + token.lexeme.len()
-                    );
+                let operator = self.match_compound_assignment();
+                if operator.is_some() || self.match_token(&TokenType::Equal) {
+                    // Check symbol table first for permission, pointing at the target
+                    let span = self.span_at(start_pos);
                     
                     // Instead of returning the error directly, record it and continue
                     if let Err(err) = self.symbol_table.check_assignment(&name, span.clone()) {
                         // Add the error to the symbol table's error list
-                        match err {
+                        match *err {
                             ResolutionError::ImmutableAssignment { name, span, declaration_span } => {
                                 self.symbol_table.add_error(ResolutionError::ImmutableAssignment {
                                     name,
@@ -347,19 +425,21 @@ impl Parser {
                                     declaration_span
                                 });
                             },
-                            _ => {
+                            err => {
                                 // Handle other error types
                                 self.symbol_table.add_error(err);
                             }
                         }
                         
-                        // Continue parsing to handle error recovery
+                        // Continue parsing to handle error recovery; the
+                        // error is reported, so look the target up quietly
                         let right = self.parse_expression()?;
-                        let target_type = match self.symbol_table.resolve(&name, span.clone()) {
+                        let target_type = match self.symbol_table.lookup(&name) {
                             Some(symbol) => symbol.typ.clone(),
                             None => PermissionedType::new(Type::Int, vec![])
                         };
-                        return Ok(Statement::new_assignment(name, right, target_type));
+                        let span = self.span_at(start_pos).combine(right.span());
+                        return Ok(Statement::Assignment { target: name, value: right, target_type, operator, span });
                     }
                     
                     let right = self.parse_expression()?;
@@ -367,30 +447,10 @@ impl Parser {
                         Some(symbol) => symbol.typ.clone(),
                         None => PermissionedType::new(Type::Int, vec![])
                     };
-                    return Ok(Statement::new_assignment(name, right, target_type));
-                } else if self.match_token(&TokenType::LeftParen) {
-                    // Function call handling
-                    let mut arguments = Vec::new();
-                    
-                    // Parse arguments list if not empty
-                    if !self.check(&TokenType::RightParen) {
-                        loop {
-                            arguments.push(self.parse_expression()?);
-                            
-                            if !self.match_token(&TokenType::Comma) {
-                                break;
-                            }
-                        }
-                    }
-                    
-                    self.consume(&TokenType::RightParen, "Expected ')' after function arguments")?;
-                    
-                    Ok(Statement::Expression(Expression::Call {
-                        function: name,
-                        arguments,
-                    }))
+                    let span = self.span_at(start_pos).combine(right.span());
+                    Ok(Statement::Assignment { target: name, value: right, target_type, operator, span })
                 } else {
-                    // This is a standalone identifier, which could be part of an expression
+                    // A call or another expression starting with the identifier
                     // Reset position and try parsing as an expression
                     self.current = start_pos;
                     let expr = self.parse_expression()?;
@@ -400,15 +460,53 @@ impl Parser {
             TokenType::LeftBrace => {
                 self.parse_block()
             },
+            TokenType::Atomic => {
+                let start = self.current_span();
+                self.advance(); // consume 'atomic'
+                let statements = self.parse_braced_statements()?;
+                let span = start.combine(&self.span_at(self.current - 1));
+                Ok(Statement::new_atomic_block(statements, span))
+            },
+            TokenType::While => {
+                let start = self.current_span();
+                self.advance(); // consume 'while'
+                let condition = self.parse_expression()?;
+                let body = self.parse_braced_statements()?;
+                let span = start.combine(&self.span_at(self.current - 1));
+                Ok(Statement::new_while(condition, body, span))
+            },
+            TokenType::Break => {
+                let span = self.current_span();
+                self.advance();
+                Ok(Statement::Break(span))
+            },
+            TokenType::Continue => {
+                let span = self.current_span();
+                self.advance();
+                Ok(Statement::Continue(span))
+            },
             _ => {
                 // Try to parse as an expression statement
-                println!("Attempting to parse expression statement with token: {:?}", self.peek().token_type);
                 let expr = self.parse_expression()?;
                 Ok(Statement::Expression(expr))
             },
         }
     }
 
+    /// Consume a compound assignment token such as `+=`, returning the
+    /// binary operator it applies
+    fn match_compound_assignment(&mut self) -> Option<TokenType> {
+        let operator = match self.peek().token_type {
+            TokenType::PlusEqual => TokenType::Plus,
+            TokenType::MinusEqual => TokenType::Minus,
+            TokenType::StarEqual => TokenType::Star,
+            TokenType::SlashEqual => TokenType::Slash,
+            _ => return None,
+        };
+        self.advance();
+        Some(operator)
+    }
+
     // Helper method to get identifier name
     fn get_identifier_name(&mut self) -> ParseResult<String> {
         if let TokenType::Identifier(ref name) = self.peek().token_type.clone() {
@@ -450,43 +548,13 @@ impl Parser {
         let name = self.get_identifier_name()?;
         
         // Create span using the token's position data
-        let token = &self.tokens[name_token_pos];
-        let name_span = Span::new(
-            token.line,
-            token.column,
-            token.line,
-            token.column + token.length - 1
-        );
+        let name_span = self.span_at(name_token_pos);
         
         // Check for type annotation (optional)
-        let typ = if self.match_token(&TokenType::Colon) {
-            // Use the new parse_type function
-            let base_type = self.parse_type()?;
-            PermissionedType::new(base_type, permissions)
+        let annotation = if self.match_token(&TokenType::Colon) {
+            Some(self.parse_type()?)
         } else {
-            // If no type annotation, infer from the initializer if possible
-            self.consume(&TokenType::Equal, "Expected '=' after variable name")?;
-            let initializer_expr = self.parse_expression()?;
-            
-            // Create a type inferer to infer the type
-            let mut inferer = TypeInferer::new(&mut self.symbol_table);
-            let inferred_type = initializer_expr.infer_type(&mut inferer);
-            
-            // Return to the parser with the inferred type and prepare to continue
-            let typ = PermissionedType::new(inferred_type, permissions);
-            
-            // Create the declaration statement with the inferred type
-            let declaration = Statement::new_declaration(name.clone(), typ.clone(), Some(initializer_expr));
-            
-            // Define the symbol with the accurate span and inferred type
-            self.symbol_table.define(Symbol {
-                name,
-                typ: typ.clone(),
-                kind: SymbolKind::Variable,
-                span: name_span,
-            });
-            
-            return Ok(declaration);
+            None
         };
         
         // Expect assignment with initializer
@@ -494,30 +562,36 @@ impl Parser {
         
         let initializer_expr = self.parse_expression()?;
         
+        // If no type annotation, infer from the initializer
+        let base_type = match annotation {
+            Some(base_type) => base_type,
+            None => {
+                let mut inferer = TypeInferer::new(&mut self.symbol_table);
+                initializer_expr.infer_type(&mut inferer)
+            }
+        };
+        let typ = PermissionedType::new(base_type, permissions);
+        
         // Check permission compatibility if initializer is a variable
-        if let Expression::Variable(ref source_name) = initializer_expr {
+        if let Expression::Variable(ref source_name, _) = initializer_expr {
             // Create span for the expression
-            let expr_span = Span::new(
-                self.previous().line,
-                self.previous().column,
-                self.previous().line,
-                self.previous().column + self.previous().length - 1
-            );
+            let expr_span = self.span_at(self.current - 1);
             
             // Check permission compatibility
             if let Err(err) = self.symbol_table.check_permission_compatibility(source_name, &typ.permissions, expr_span) {
-                self.symbol_table.add_error(err);
+                self.symbol_table.add_error(*err);
             }
         }
         
         // Don't check permission errors when using peak operator
         // This allows read c = peak counter to work
-        if let Expression::Peak(_) = initializer_expr {
+        if let Expression::Peak(..) = initializer_expr {
             // Peak expressions bypass normal permission checking
         }
         
         // Create declaration statement
-        let declaration = Statement::new_declaration(name.clone(), typ.clone(), Some(initializer_expr));
+        let span = self.span_at(start_token_pos).combine(initializer_expr.span());
+        let declaration = Statement::new_declaration(name.clone(), typ.clone(), Some(initializer_expr), span);
         
         // Define the symbol with the accurate span
         self.symbol_table.define(Symbol {
@@ -531,38 +605,129 @@ impl Parser {
     }
 
     fn parse_block(&mut self) -> ParseResult<Statement> {
+        let start = self.current_span();
+        let statements = self.parse_braced_statements()?;
+        let span = start.combine(&self.span_at(self.current - 1));
+        Ok(Statement::Block(statements, span))
+    }
+    
+    /// Parse `{ ... }`, the body of a block or an atomic block
+    fn parse_braced_statements(&mut self) -> ParseResult<Vec<Statement>> {
         self.consume(&TokenType::LeftBrace, "Expected '{'")?;
         
+        // Declarations inside the braces are only visible within them
+        self.symbol_table.begin_scope();
+        let statements = self.parse_block_statements();
+        self.symbol_table.end_scope();
+        let statements = statements?;
+        
+        self.consume(&TokenType::RightBrace, "Expected '}' after block")?;
+        
+        Ok(statements)
+    }
+    
+    fn parse_block_statements(&mut self) -> ParseResult<Vec<Statement>> {
         let mut statements = Vec::new();
         
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
             statements.push(self.parse_statement()?);
         }
         
-        self.consume(&TokenType::RightBrace, "Expected '}' after block")?;
-        
-        Ok(Statement::Block(statements))
+        Ok(statements)
     }
 
+    /// Parse `actor Name { ... }`, whose body holds state declarations,
+    /// `fn` methods and `on` behaviors
+    fn parse_actor_declaration(&mut self) -> ParseResult<Statement> {
+        let start = self.current_span();
+        self.advance(); // consume 'actor'
+        let name = self.get_identifier_name()?;
+        self.consume(&TokenType::LeftBrace, "Expected '{' after actor name")?;
+        
+        let mut state = Vec::new();
+        let mut methods = Vec::new();
+        let mut behaviors = Vec::new();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            match self.peek().token_type {
+                TokenType::Fn => methods.push(self.parse_function_declaration(false)?),
+                TokenType::On => behaviors.push(self.parse_function_declaration(true)?),
+                TokenType::Reads | TokenType::Read | TokenType::Write | TokenType::Writes => {
+                    state.push(self.parse_variable_declaration()?);
+                },
+                TokenType::Identifier(_) if self.check_next(&TokenType::Colon) => {
+                    state.push(self.parse_variable_declaration()?);
+                },
+                _ => return Err(ParseError::unexpected_token(
+                    self.current_span(),
+                    format!("Expected state declaration, 'fn' or 'on' in actor '{}', found {:?}",
+                           name, self.peek().token_type)
+                )),
+            }
+        }
+        
+        self.consume(&TokenType::RightBrace, "Expected '}' after actor body")?;
+        let span = start.combine(&self.span_at(self.current - 1));
+        
+        Ok(Statement::Actor { name, state, methods, behaviors, span })
+    }
+    
     fn parse_function_declaration(&mut self, is_behavior: bool) -> ParseResult<Statement> {
-        println!("Starting to parse a function declaration, is_behavior={}", is_behavior);
+        let outer_type_params = self.type_params.len();
+        let function = self.parse_function(is_behavior);
         
+        // Generic parameters are only in scope inside their function
+        self.type_params.truncate(outer_type_params);
+        function
+    }
+    
+    /// Parse the generic parameters of a function, e.g. `<T, U>`
+    fn parse_type_parameters(&mut self) -> ParseResult<Vec<String>> {
+        let mut type_params = Vec::new();
+        if !self.match_token(&TokenType::Less) {
+            return Ok(type_params);
+        }
+        
+        loop {
+            type_params.push(self.get_identifier_name()?);
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+        }
+        self.consume(&TokenType::Greater, "Expected '>' after type parameters")?;
+        Ok(type_params)
+    }
+    
+    fn parse_function(&mut self, is_behavior: bool) -> ParseResult<Statement> {
         // Store the function start position for error reporting
         let function_start_pos = self.current;
         
         self.advance(); // Consume 'fn' or 'on'
         
         let name = self.get_identifier_name()?;
-        println!("Parsing function with name: {}", name);
+        
+        // Report redefinitions against the first definition
+        let name_span = self.span_at(self.current - 1);
+        match self.function_spans.get(&name) {
+            Some(first) => self.symbol_table.add_error(ResolutionError::DuplicateSymbol {
+                name: name.clone(),
+                first: first.clone(),
+                second: name_span,
+            }),
+            None => {
+                self.function_spans.insert(name.clone(), name_span);
+            }
+        }
+        
+        let type_params = self.parse_type_parameters()?;
+        self.type_params.extend(type_params.iter().cloned());
         
         self.consume(&TokenType::LeftParen, "Expected '(' after function name")?;
-        println!("Found opening parenthesis");
         
         // Parse parameters
         let mut parameters = Vec::new();
+        let mut param_spans = Vec::new();
         
         if !self.check(&TokenType::RightParen) {
-            println!("Parsing parameters");
             loop {
                 // Parse parameter permissions
                 let mut permissions = Vec::new();
@@ -572,26 +737,20 @@ impl Parser {
                     TokenType::Reads => {
                         self.advance();
                         permissions.push(Permission::Reads);
-                        println!("Found Reads permission");
                     },
                     TokenType::Writes => {
                         self.advance();
                         permissions.push(Permission::Writes);
-                        println!("Found Writes permission");
                     },
                     TokenType::Read => {
                         self.advance();
                         permissions.push(Permission::Read);
-                        println!("Found Read permission");
                     },
                     TokenType::Write => {
                         self.advance();
                         permissions.push(Permission::Write);
-                        println!("Found Write permission");
                     },
-                    _ => {
-                        println!("No permission specified for parameter");
-                    }
+                    _ => {}
                 }
                 
                 // Check for additional permission
@@ -599,31 +758,26 @@ impl Parser {
                     TokenType::Write => {
                         self.advance();
                         permissions.push(Permission::Write);
-                        println!("Found additional Write permission");
                     },
                     TokenType::Writes => {
                         self.advance();
                         permissions.push(Permission::Writes);
-                        println!("Found additional Writes permission");
                     },
                     _ => {}
                 }
                 
                 // Get parameter name
+                let param_span = self.current_span();
                 let param_name = self.get_identifier_name()?;
-                println!("Parameter name: {}", param_name);
                 
                 // Parse parameter type
                 let param_type = if self.match_token(&TokenType::Colon) {
-                    println!("Found colon, parsing parameter type");
                     // Use the parse_type function instead of checking for specific types
                     match self.parse_type() {
                         Ok(base_type) => {
-                            println!("Parameter type: {:?}", base_type);
                             PermissionedType::new(base_type, permissions.clone())
                         },
-                        Err(err) => {
-                            println!("Error parsing parameter type: {:?}", err);
+                        Err(_) => {
                             return Err(ParseError::unexpected_token(
                                 self.current_span(),
                                 "Expected type after ':'".to_string()
@@ -631,40 +785,30 @@ impl Parser {
                         }
                     }
                 } else {
-                    println!("No type specified, using default Int");
                     // Default to Int if no type specified
                     PermissionedType::new(Type::Int, permissions.clone())
                 };
                 
                 // Add the parameter to our list
                 parameters.push((param_name.clone(), param_type));
-                println!("Added parameter {} to function", param_name);
+                param_spans.push(param_span);
                 
                 if !self.match_token(&TokenType::Comma) {
-                    println!("No more parameters");
                     break;
                 }
-                println!("Found comma, parsing next parameter");
             }
-        } else {
-            println!("No parameters to parse");
         }
         
-        println!("Expecting right parenthesis");
         self.consume(&TokenType::RightParen, "Expected ')' after parameters")?;
-        println!("Found closing parenthesis");
         
         // Update return type parsing in parse_function_declaration
         let return_type = if self.match_token(&TokenType::Arrow) {
-            println!("Found return type arrow ->");
             // Use parse_type instead of checking for specific types
             match self.parse_type() {
                 Ok(base_type) => {
-                    println!("Return type: {:?}", base_type);
                     Some(PermissionedType::new(base_type, vec![]))
                 },
-                Err(err) => {
-                    println!("Error parsing return type: {:?}", err);
+                Err(_) => {
                     return Err(ParseError::unexpected_token(
                         self.current_span(),
                         "Expected return type after '->'".to_string()
@@ -672,22 +816,31 @@ impl Parser {
                 }
             }
         } else {
-            println!("No return type specified");
             None
         };
         
-        println!("Parsing function body");
+        // Parameters live in the function's own scope, visible to the body
+        // but not to anything declared after the function
+        self.symbol_table.begin_scope();
+        for ((param_name, param_type), span) in parameters.iter().zip(param_spans) {
+            self.symbol_table.define(Symbol {
+                name: param_name.clone(),
+                typ: param_type.clone(),
+                kind: SymbolKind::Parameter,
+                span,
+            });
+        }
+        
         // Parse function body
-        let body_stmt = self.parse_block()?;
-        println!("Parsed function body block");
+        let body_stmt = self.parse_block();
+        self.symbol_table.end_scope();
+        let body_stmt = body_stmt?;
         
         // Extract statements from body block
         let body = match body_stmt {
-            Statement::Block(statements) => {
+            Statement::Block(statements, _) => {
                 // If there's no explicit return statement and the body isn't empty,
                 // add an implicit return for the last expression
-                println!("Function body has {} statements", statements.len());
-                
                 if !statements.is_empty() {
                     let mut modified_statements = statements.clone();
                     
@@ -695,27 +848,21 @@ impl Parser {
                     if let Some(last) = modified_statements.last() {
                         match last {
                             // If the last statement is already a return, don't modify
-                            Statement::Return(_) => {
-                                println!("Last statement is already a return");
-                            },
+                            Statement::Return(..) => {},
                             
                             // If it's an expression, convert it to a return statement
                             Statement::Expression(expr) => {
-                                println!("Converting expression to return: {:?}", expr);
                                 let last_idx = modified_statements.len() - 1;
-                                modified_statements[last_idx] = Statement::Return(expr.clone());
+                                modified_statements[last_idx] = Statement::Return(expr.clone(), expr.span().clone());
                             },
                             
                             // For other types, we don't create an implicit return
-                            _ => {
-                                println!("Last statement is not an expression, not creating return");
-                            }
+                            _ => {}
                         }
                     }
                     
                     modified_statements
                 } else {
-                    println!("Function body is empty");
                     statements
                 }
             },
@@ -725,25 +872,46 @@ impl Parser {
             ))
         };
         
-        println!("Creating function {} with {} params and {} body statements", 
-                 name, parameters.len(), body.len());
+        // Type check the function
+        let function_span = if let Some(span) = self.token_locations.get(&function_start_pos) {
+            span.clone()
+        } else {
+            Span::point(0, 0)
+        };
+        
+        // Solve the function's type constraints. Without an explicit `-> T`
+        // the return type is inferred; functions returning Unit keep `None`.
+        let mut inferer = TypeInferer::with_environment(
+            &mut self.symbol_table,
+            std::mem::take(&mut self.type_env),
+        );
+        let inferred_return = inferer.infer_function(
+            &name, &parameters, &body, return_type.as_ref(), function_span.clone(),
+        );
+        let type_errors = inferer.get_type_errors().to_vec();
+        self.type_env = inferer.into_environment();
         
-        // Register parameters in symbol table - add this for future validations
-        for (param_name, param_type) in &parameters {
-            let span = Span::point(0, 0); // Default span for now
-            self.symbol_table.define(Symbol {
-                name: param_name.clone(),
-                typ: param_type.clone(),
-                kind: SymbolKind::Parameter,
-                span,
-            });
+        // Add any type errors to our errors list
+        for error in type_errors {
+            self.symbol_table.add_error(error);
         }
         
+        let return_type = match (return_type, inferred_return) {
+            (Some(annotated), _) => Some(annotated),
+            (None, Type::Unit) => None,
+            (None, inferred) => {
+                Some(PermissionedType::new(inferred, vec![]))
+            }
+        };
+
         // Create function using builder - pass parameters correctly
+        let span = function_span.combine(&self.span_at(self.current - 1));
         let mut builder = FunctionBuilder::new(name)
+            .with_type_params(type_params)
+            .with_span(span)
             .as_behavior(is_behavior)
-            .with_return_type(return_type.clone())
-            .with_body(body.clone());
+            .with_return_type(return_type)
+            .with_body(body);
         
         // Explicitly add each parameter to the builder
         for (name, typ) in parameters {
@@ -752,23 +920,6 @@ impl Parser {
         
         let function = builder.build();
         
-        // Type check the function
-        let function_span = if let Some(span) = self.token_locations.get(&function_start_pos) {
-            span.clone()
-        } else {
-            Span::point(0, 0)
-        };
-        
-        // Check return type compatibility - now with immutable reference
-        let type_checker = TypeChecker::new(&self.symbol_table);
-        let type_errors = type_checker.check_function(&function, function_span);
-        
-        // Add any type errors to our errors list
-        for error in type_errors {
-            self.symbol_table.add_error(error);
-        }
-        
-        println!("Successfully built function statement");
         Ok(function)
     }
 
@@ -777,14 +928,11 @@ impl Parser {
         let mut statements = Vec::new();
         
         while !self.is_at_end() {
-            println!("Parsing statement, current token: {:?}", self.peek().token_type);
             match self.parse_statement() {
                 Ok(stmt) => {
-                    println!("Successfully parsed statement: {:?}", stmt);
                     statements.push(stmt);
                 },
                 Err(err) => {
-                    println!("Error parsing statement: {:?}", err);
                     // Record the error instead of printing it
                     self.errors.push(CompileError::Parse(err));
                     self.synchronize();
@@ -792,7 +940,6 @@ impl Parser {
             }
         }
         
-        println!("Finished parsing statements, found {}", statements.len());
         statements
     }
 
@@ -814,6 +961,9 @@ impl Parser {
                 TokenType::Writes |
                 TokenType::Fn |
                 TokenType::On |
+                TokenType::Atomic |
+                TokenType::While |
+                TokenType::Actor |
                 TokenType::Return |
                 TokenType::Print => return,
                 _ => {}
@@ -889,6 +1039,11 @@ impl Parser {
                 self.advance();
                 Ok(Type::Unit)
             },
+            TokenType::Identifier(ref name) if self.type_params.contains(name) => {
+                let param = Type::Param(name.clone());
+                self.advance();
+                Ok(param)
+            },
             _ => Err(ParseError::unexpected_token(
                 self.current_span(),
                 format!("Expected type name, got {:?}", self.peek().token_type)
@@ -905,3 +1060,20 @@ impl Parser {
         temp_parser.parse_expression()
     }
 }
+
+/// Build the span of every token, tagged with its file when it has one
+fn token_spans(tokens: &[Token], file: Option<(FileId, &str)>) -> HashMap<usize, Span> {
+    tokens.iter().enumerate().map(|(i, token)| {
+        let span = Span::new(
+            token.line,
+            token.column,
+            token.line,
+            token.column + token.length - 1
+        );
+        let span = match file {
+            Some((id, name)) => span.in_file(id, name),
+            None => span,
+        };
+        (i, span)
+    }).collect()
+}
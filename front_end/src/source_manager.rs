@@ -1,62 +1,141 @@
 use std::collections::HashMap;
 use crate::symbol_table::Span;
 
+/// Identifier of a file registered with the `SourceManager`
+pub type FileId = usize;
+
+/// A single source file with precomputed line offsets
+#[derive(Debug, Clone)]
+struct SourceFile {
+    name: String,
+    content: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(name: &str, content: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            content: content.to_string(),
+            line_starts: compute_line_starts(content),
+        }
+    }
+    
+    fn line(&self, line_number: usize) -> Option<&str> {
+        line_in(&self.content, &self.line_starts, line_number)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SourceManager {
-    sources: HashMap<String, String>,
+    files: Vec<SourceFile>,
+    file_ids: HashMap<String, FileId>,
     default_source: String,
     line_starts: Vec<usize>,
 }
 
+impl Default for SourceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SourceManager {
     pub fn new() -> Self {
         Self {
-            sources: HashMap::new(),
+            files: Vec::new(),
+            file_ids: HashMap::new(),
             default_source: String::new(),
             line_starts: vec![0],
         }
     }
     
+    /// Register a file and return its id. Adding a file with a name that is
+    /// already known replaces its contents and keeps the id.
+    pub fn add_file(&mut self, name: String, content: String) -> FileId {
+        if let Some(&id) = self.file_ids.get(&name) {
+            self.files[id] = SourceFile::new(&name, &content);
+            return id;
+        }
+        
+        let id = self.files.len();
+        self.files.push(SourceFile::new(&name, &content));
+        self.file_ids.insert(name, id);
+        id
+    }
+    
     pub fn add_source(&mut self, name: &str, content: &str) {
-        self.sources.insert(name.to_string(), content.to_string());
+        self.add_file(name.to_string(), content.to_string());
+    }
+    
+    /// Ids of all registered files, in the order they were added
+    pub fn file_ids(&self) -> impl Iterator<Item = FileId> {
+        0..self.files.len()
+    }
+    
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+    
+    pub fn file_id(&self, name: &str) -> Option<FileId> {
+        self.file_ids.get(name).copied()
+    }
+    
+    pub fn file_name(&self, id: FileId) -> Option<&str> {
+        self.files.get(id).map(|file| file.name.as_str())
+    }
+    
+    pub fn file_source(&self, id: FileId) -> Option<&str> {
+        self.files.get(id).map(|file| file.content.as_str())
     }
     
     pub fn set_default_source(&mut self, content: &str) {
         self.default_source = content.to_string();
-        self.line_starts = vec![0]; // Reset line starts, first line starts at position 0
-        
-        // Find all line start positions (after newlines)
-        for (i, c) in content.char_indices() {
-            if c == '\n' {
-                self.line_starts.push(i + 1);
-            }
-        }
+        self.line_starts = compute_line_starts(content);
     }
     
     // Get a specific line from the source
     pub fn get_line(&self, line_number: usize) -> Option<&str> {
-        if line_number == 0 || line_number > self.line_starts.len() {
-            return None;
-        }
-        
-        let start = self.line_starts[line_number - 1]; // Line numbers are 1-based
-        let end = if line_number < self.line_starts.len() {
-            self.line_starts[line_number]
-        } else {
-            self.default_source.len()
-        };
+        line_in(&self.default_source, &self.line_starts, line_number)
+    }
+    
+    /// Get a specific line from a registered file
+    pub fn get_file_line(&self, id: FileId, line_number: usize) -> Option<&str> {
+        self.files.get(id).and_then(|file| file.line(line_number))
+    }
+    
+    /// Format the location of a span as `file:line:column`, or `line:column`
+    /// when the span does not belong to a registered file
+    pub fn format_location(&self, span: &Span) -> String {
+        let file = span.file_id
+            .and_then(|id| self.file_name(id))
+            .or(span.source_file.as_deref());
         
-        Some(&self.default_source[start..end])
+        match file {
+            Some(name) => format!("{}:{}:{}", name, span.start_line, span.start_column),
+            None => format!("{}:{}", span.start_line, span.start_column),
+        }
     }
     
     // Get a snippet for a specific span with context
     pub fn get_snippet(&self, span: &Span) -> String {
+        // Spans from a registered file read from that file, everything else
+        // from the default source
+        let line_count = match span.file_id.and_then(|id| self.files.get(id)) {
+            Some(file) => file.line_starts.len(),
+            None => self.line_starts.len(),
+        };
+        
         // Handle out of bounds
-        if span.start_line == 0 || span.start_line > self.line_starts.len() {
+        if span.start_line == 0 || span.start_line > line_count {
             return String::from("<invalid line number>");
         }
         
         // Get the source line
-        let line = self.get_line(span.start_line).unwrap_or("<line not found>");
+        let line = match span.file_id {
+            Some(id) if id < self.files.len() => self.get_file_line(id, span.start_line),
+            _ => self.get_line(span.start_line),
+        }.unwrap_or("<line not found>");
         let trimmed_line = line.trim_end();
         
         // Calculate the indentation level
@@ -78,4 +157,30 @@ impl SourceManager {
         // Return the line and caret indicator
         format!("    {}\n{}", trimmed_line.trim_start(), carets)
     }
-}
\ No newline at end of file
+}
+
+// Find all line start positions (after newlines); the first line starts at 0
+fn compute_line_starts(content: &str) -> Vec<usize> {
+    let mut line_starts = vec![0];
+    for (i, c) in content.char_indices() {
+        if c == '\n' {
+            line_starts.push(i + 1);
+        }
+    }
+    line_starts
+}
+
+fn line_in<'a>(content: &'a str, line_starts: &[usize], line_number: usize) -> Option<&'a str> {
+    if line_number == 0 || line_number > line_starts.len() {
+        return None;
+    }
+    
+    let start = line_starts[line_number - 1]; // Line numbers are 1-based
+    let end = if line_number < line_starts.len() {
+        line_starts[line_number]
+    } else {
+        content.len()
+    };
+    
+    Some(&content[start..end])
+}
@@ -1,15 +1,20 @@
 use std::collections::HashMap;
 use crate::types::{ Permission, PermissionedType};
 use crate::ast::{Statement, Expression};
+use crate::token::TokenType;
+use crate::source_manager::FileId;
+use crate::intern::Name;
+use crate::codes;
 
 /// Represents a region of source code with start and end positions
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Span {
     pub start_line: usize,
     pub start_column: usize,
     pub end_line: usize,
     pub end_column: usize,
     pub source_file: Option<String>, // Optional source file path
+    pub file_id: Option<FileId>, // Id of the file in the SourceManager, if registered
 }
 
 impl Span {
@@ -20,6 +25,7 @@ impl Span {
             end_line,
             end_column,
             source_file: None,
+            file_id: None,
         }
     }
     
@@ -28,6 +34,12 @@ impl Span {
         self
     }
     
+    /// Attach a registered source file to this span
+    pub fn in_file(mut self, id: FileId, name: &str) -> Self {
+        self.file_id = Some(id);
+        self.with_file(name)
+    }
+    
     /// Create a single-point span (for when we only have a position, not a range)
     pub fn point(line: usize, column: usize) -> Self {
         Self::new(line, column, line, column)
@@ -59,6 +71,7 @@ impl Span {
             end_line,
             end_column,
             source_file: self.source_file.clone(),
+            file_id: self.file_id,
         }
     }
 }
@@ -119,7 +132,7 @@ pub enum SymbolKind {
 /// A scope represents a lexical block with its own variable declarations
 #[derive(Debug)]
 struct Scope {
-    symbols: HashMap<String, Symbol>,
+    symbols: HashMap<Name, Symbol>,
     parent: Option<usize>, // Index of parent scope in SymbolTable's scopes vec
 }
 
@@ -127,7 +140,7 @@ struct Scope {
 #[derive(Debug, Clone)]
 pub enum ResolutionError {
     DuplicateSymbol{name: String, first: Span, second: Span},
-    UndefinedSymbol{name: String, span: Span},
+    UndefinedSymbol{name: String, span: Span, suggestion: Option<String>},
     ImmutableAssignment{name: String, span: Span, declaration_span: Option<Span>},
     PermissionViolation{name: String, required: String, provided: String, span: Span, declaration_span: Option<Span>},
     ReadAccessViolation{name: String, span: Span, declaration_span: Option<Span>, target_permission: String},
@@ -137,6 +150,13 @@ pub enum ResolutionError {
         span: Span,
         context: String 
     },
+    InvalidOperands {
+        operator: TokenType,
+        left: String,
+        right: String,
+        span: Span,
+        context: String
+    },
 }
 
 impl std::fmt::Display for ResolutionError {
@@ -156,13 +176,17 @@ impl std::fmt::Display for ResolutionError {
                     write!(f, " at line {}:{}", second.start_line, second.start_column)
                 }
             },
-            ResolutionError::UndefinedSymbol{name, span} => {
+            ResolutionError::UndefinedSymbol{name, span, suggestion} => {
                 write!(f, "Error: Variable '{}' not defined in this scope", name)?;
                 if let Some(file) = &span.source_file {
-                    write!(f, " ({}:{}:{})", file, span.start_line, span.start_column)
+                    write!(f, " ({}:{}:{})", file, span.start_line, span.start_column)?;
                 } else {
-                    write!(f, " (line {}:{})", span.start_line, span.start_column)
+                    write!(f, " (line {}:{})", span.start_line, span.start_column)?;
                 }
+                if let Some(candidate) = suggestion {
+                    write!(f, "; did you mean `{}`?", candidate)?;
+                }
+                Ok(())
             },
             ResolutionError::ImmutableAssignment{name, span, declaration_span} => {
                 write!(f, "Error: Cannot assign to immutable variable '{}'", name)?;
@@ -204,7 +228,7 @@ impl std::fmt::Display for ResolutionError {
                 }
             },
             ResolutionError::ReadAccessViolation{name, span, declaration_span, target_permission} => {
-                write!(f, "error[E0005]: cannot directly assign reads variable '{}' to {} variable", name, target_permission)?;
+                write!(f, "error[{}]: cannot directly assign reads variable '{}' to {} variable", codes::resolve::READ_ACCESS_VIOLATION, name, target_permission)?;
                 if let Some(file) = &span.source_file {
                     write!(f, " at {}:{}:{}", file, span.start_line, span.start_column)?;
                 } else {
@@ -223,23 +247,63 @@ impl std::fmt::Display for ResolutionError {
                 Ok(())
             },
             ResolutionError::TypeMismatch { expected, found, span, context } => {
-                write!(f, "error[E0006]: type mismatch {}\n", context)?;
+                writeln!(f, "error[{}]: type mismatch {}", codes::resolve::TYPE_MISMATCH, context)?;
                 
                 // Show where the type mismatch happened
                 let loc = format!("{}:{}", span.start_line, span.start_column);
-                write!(f, "--> {}\n", loc)?;
+                writeln!(f, "--> {}", loc)?;
                 
                 // Add more details about the mismatch
-                write!(f, "   |\n")?;
-                write!(f, "   | expected `{}`, found `{}`\n", expected, found)?;
-                write!(f, "   |\n")?;
+                writeln!(f, "   |")?;
+                writeln!(f, "   | expected `{}`, found `{}`", expected, found)?;
+                writeln!(f, "   |")?;
                 
                 write!(f, "help: ensure that all return values match the function's return type")
             },
+            ResolutionError::InvalidOperands { operator, left, right, span, context } => {
+                writeln!(f, "error[{}]: cannot {} `{}` and `{}` {}", codes::resolve::INVALID_OPERANDS, operator.operation_verb(), left, right, context)?;
+                writeln!(f, "--> {}:{}", span.start_line, span.start_column)?;
+                writeln!(f, "   |")?;
+                writeln!(f, "   | no implementation for `{} {} {}`", left, operator.operator_symbol(), right)?;
+                write!(f, "   |")
+            },
         }
     }
 }
 
+/// Levenshtein distance between two identifiers, counted in characters
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+    let mut current = vec![0; b_chars.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b_chars.len()]
+}
+
+/// Pick the candidate closest to `name`, provided it is close enough to be a
+/// plausible typo (at most one edit per three characters). Ties are broken
+/// alphabetically so suggestions are stable.
+pub fn closest_name<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.to_string())
+}
+
 /// The Symbol Table manages variable scopes and provides methods for resolving variables
 pub struct SymbolTable {
     scopes: Vec<Scope>,
@@ -247,15 +311,19 @@ pub struct SymbolTable {
     errors: Vec<ResolutionError>,
 }
 
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SymbolTable {
     pub fn new() -> Self {
-        let mut scopes = Vec::new();
-        
         // Start with global scope
-        scopes.push(Scope {
+        let scopes = vec![Scope {
             symbols: HashMap::new(),
             parent: None,
-        });
+        }];
         
         Self {
             scopes,
@@ -283,9 +351,21 @@ impl SymbolTable {
         }
     }
     
+    /// Number of scopes enclosing the current one (0 at global scope)
+    pub fn scope_depth(&self) -> usize {
+        let mut depth = 0;
+        let mut scope_idx = self.current_scope;
+        while let Some(parent) = self.scopes[scope_idx].parent {
+            depth += 1;
+            scope_idx = parent;
+        }
+        depth
+    }
+    
     pub fn define(&mut self, symbol: Symbol) {
         // Check for duplicate in current scope
-        if let Some(existing) = self.scopes[self.current_scope].symbols.get(&symbol.name) {
+        let name = Name::new(&symbol.name);
+        if let Some(existing) = self.scopes[self.current_scope].symbols.get(&name) {
             self.errors.push(ResolutionError::DuplicateSymbol{
                 name: symbol.name.clone(),
                 first: existing.span.clone(),
@@ -295,58 +375,102 @@ impl SymbolTable {
         }
         
         // Add to current scope
-        self.scopes[self.current_scope].symbols.insert(
-            symbol.name.clone(), symbol
-        );
+        self.scopes[self.current_scope].symbols.insert(name, symbol);
     }
     
     pub fn resolve(&mut self, name: &str, span: Span) -> Option<&Symbol> {
-        let mut scope_idx = self.current_scope;
-        
-        loop {
-            if let Some(symbol) = self.scopes[scope_idx].symbols.get(name) {
-                return Some(symbol);
-            }
+        if let Some(key) = Name::existing(name) {
+            let mut scope_idx = self.current_scope;
             
-            // Move to parent scope if it exists
-            match self.scopes[scope_idx].parent {
-                Some(parent_idx) => scope_idx = parent_idx,
-                None => break, // We've reached the global scope
+            loop {
+                if let Some(symbol) = self.scopes[scope_idx].symbols.get(&key) {
+                    return Some(symbol);
+                }
+                
+                // Move to parent scope if it exists
+                match self.scopes[scope_idx].parent {
+                    Some(parent_idx) => scope_idx = parent_idx,
+                    None => break, // We've reached the global scope
+                }
             }
         }
         
         // Symbol not found - add an error
+        let suggestion = self.suggest_similar(name);
         self.errors.push(ResolutionError::UndefinedSymbol {
             name: name.to_string(),
             span,
+            suggestion,
         });
         None
     }
+
+    /// Find the visible symbol whose name is closest to `name`, for
+    /// "did you mean" hints on undefined identifiers
+    pub fn suggest_similar(&self, name: &str) -> Option<String> {
+        let mut candidates = Vec::new();
+        let mut scope_idx = self.current_scope;
+
+        loop {
+            candidates.extend(self.scopes[scope_idx].symbols.keys().map(|name| name.as_str()));
+
+            match self.scopes[scope_idx].parent {
+                Some(parent_idx) => scope_idx = parent_idx,
+                None => break,
+            }
+        }
+
+        closest_name(name, candidates)
+    }
+
+    /// Look up a symbol through the scope chain without reporting an error
+    /// when it is missing
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        let name = Name::existing(name)?;
+        let mut scope_idx = self.current_scope;
+
+        loop {
+            if let Some(symbol) = self.scopes[scope_idx].symbols.get(&name) {
+                return Some(symbol);
+            }
+
+            match self.scopes[scope_idx].parent {
+                Some(parent_idx) => scope_idx = parent_idx,
+                None => return None,
+            }
+        }
+    }
     
-    pub fn check_assignment(&mut self, name: &str, span: Span) -> Result<(), ResolutionError> {
-        match self.resolve(name, span.clone()) {
+    /// Check that `name` names a variable that may be written. The error
+    /// is returned, not recorded, so the caller reports it once.
+    pub fn check_assignment(&mut self, name: &str, span: Span) -> Result<(), Box<ResolutionError>> {
+        match self.lookup(name) {
             Some(symbol) => {
                 // Check if variable has write permission
                 if symbol.typ.permissions.contains(&Permission::Write) ||
                    symbol.typ.permissions.contains(&Permission::Writes) {
                     Ok(())
                 } else {
-                    Err(ResolutionError::ImmutableAssignment{
+                    Err(Box::new(ResolutionError::ImmutableAssignment{
                         name: name.to_string(),
                         span,
                         declaration_span: Some(symbol.span.clone()),
-                    })
+                    }))
                 }
             },
-            None => Err(ResolutionError::UndefinedSymbol{
+            None => Err(Box::new(ResolutionError::UndefinedSymbol{
                 name: name.to_string(),
                 span,
-            }),
+                suggestion: self.suggest_similar(name),
+            })),
         }
     }
     
-    pub fn check_permission_compatibility(&mut self, source_name: &str, target_permissions: &[Permission], span: Span) -> Result<(), ResolutionError> {
-        match self.resolve(source_name, span.clone()) {
+    /// Check that a variable declared with `target_permissions` may be
+    /// initialized from `source_name`. The error is returned, not
+    /// recorded, so the caller reports it once.
+    pub fn check_permission_compatibility(&mut self, source_name: &str, target_permissions: &[Permission], span: Span) -> Result<(), Box<ResolutionError>> {
+        match self.lookup(source_name) {
             Some(symbol) => {
                 // Check if permissions are compatible
                 let source_has_write = symbol.typ.permissions.contains(&Permission::Write);
@@ -354,13 +478,13 @@ impl SymbolTable {
                 
                 // If both have write permission, that's a violation (write is exclusive)
                 if source_has_write && target_has_write {
-                    return Err(ResolutionError::PermissionViolation {
+                    return Err(Box::new(ResolutionError::PermissionViolation {
                         name: source_name.to_string(),
                         required: "writes".to_string(), // Should have writes permission for sharing
                         provided: "write".to_string(),  // But has exclusive write permission
                         span,
                         declaration_span: Some(symbol.span.clone()),
-                    });
+                    }));
                 }
                 
                 // Check for reads assignment without clone
@@ -370,34 +494,31 @@ impl SymbolTable {
                 
                 if (target_has_reads || target_has_read) && source_has_reads {
                     // We need either peak or clone keyword for copying from reads variable
-                    return Err(ResolutionError::ReadAccessViolation {
+                    return Err(Box::new(ResolutionError::ReadAccessViolation {
                         name: source_name.to_string(),
                         span,
                         declaration_span: Some(symbol.span.clone()),
                         target_permission: if target_has_reads { "reads".to_string() } else { "read".to_string() },
-                    });
+                    }));
                 }
                 
                 Ok(())
             },
-            None => Err(ResolutionError::UndefinedSymbol {
+            None => Err(Box::new(ResolutionError::UndefinedSymbol {
                 name: source_name.to_string(),
                 span,
-            }),
+                suggestion: self.suggest_similar(source_name),
+            })),
         }
     }
     
-    pub fn process_statement(&mut self, stmt: &Statement, token_locations: &HashMap<usize, Location>) {
+    pub fn process_statement(&mut self, stmt: &Statement) {
         match stmt {
-            Statement::Declaration{name, typ, initializer} => {
-                let location = token_locations.get(&self.current_scope)
-                    .cloned().unwrap_or(Location{line: 0, column: 0, span: None});
-                
+            Statement::Declaration{name, typ, initializer, span} => {
                 // Check initializer for permission compatibility if it's a variable reference
-                if let Some(Expression::Variable(source_name)) = initializer {
-                    let span = location.span.clone().unwrap_or_else(|| Span::point(0, 0));
-                    if let Err(err) = self.check_permission_compatibility(source_name, &typ.permissions, span) {
-                        self.add_error(err);
+                if let Some(Expression::Variable(source_name, source_span)) = initializer {
+                    if let Err(err) = self.check_permission_compatibility(source_name, &typ.permissions, source_span.clone()) {
+                        self.add_error(*err);
                     }
                 }
                 
@@ -405,43 +526,70 @@ impl SymbolTable {
                     name: name.clone(),
                     typ: typ.clone(),
                     kind: SymbolKind::Variable,
-                    span: location.span.unwrap_or_else(|| Span::point(0, 0)),
+                    span: span.clone(),
                 });
                 
                 // Process initializer if present
                 if let Some(expr) = initializer {
-                    self.process_expression(expr, token_locations);
+                    self.process_expression(expr);
                 }
             },
-            Statement::Assignment{target, value, ..} => {
+            Statement::Assignment{target, value, span, ..} => {
                 // Check if variable exists and is writable
-                let location = token_locations.get(&self.current_scope)
-                    .cloned().unwrap_or(Location{line: 0, column: 0, span: None});
-                
-                let _ = self.check_assignment(target, location.span.unwrap_or_else(|| Span::point(0, 0)));
-                self.process_expression(value, token_locations);
+                if let Err(err) = self.check_assignment(target, span.clone()) {
+                    self.add_error(*err);
+                }
+                self.process_expression(value);
+            },
+            Statement::Block(statements, _) | Statement::AtomicBlock(statements, _) => {
+                self.begin_scope();
+                for stmt in statements {
+                    self.process_statement(stmt);
+                }
+                self.end_scope();
+            },
+            Statement::While{condition, body, ..} => {
+                self.process_expression(condition);
+                self.begin_scope();
+                for stmt in body {
+                    self.process_statement(stmt);
+                }
+                self.end_scope();
+            },
+            Statement::Function{params, body, ..} => {
+                // Parameters are scoped to the function body
+                self.begin_scope();
+                for (name, typ) in params {
+                    self.define(Symbol {
+                        name: name.clone(),
+                        typ: typ.clone(),
+                        kind: SymbolKind::Parameter,
+                        span: Span::point(0, 0),
+                    });
+                }
+                for stmt in body {
+                    self.process_statement(stmt);
+                }
+                self.end_scope();
             },
             // Handle other statement types...
             _ => {}
         }
     }
     
-    pub fn process_expression(&mut self, expr: &Expression, token_locations: &HashMap<usize, Location>) {
+    pub fn process_expression(&mut self, expr: &Expression) {
         match expr {
-            Expression::Variable(name) => {
-                let location = token_locations.get(&self.current_scope)
-                    .cloned().unwrap_or(Location{line: 0, column: 0, span: None});
-                
-                let _ = self.resolve(name, location.span.unwrap_or_else(|| Span::point(0, 0)));
+            Expression::Variable(name, span) => {
+                let _ = self.resolve(name, span.clone());
             },
             Expression::Binary{left, right, ..} => {
-                self.process_expression(left, token_locations);
-                self.process_expression(right, token_locations);
+                self.process_expression(left);
+                self.process_expression(right);
             },
-            Expression::Call { function: _, arguments } => {
+            Expression::Call { arguments, .. } => {
                 // Process all arguments
                 for arg in arguments {
-                    self.process_expression(arg, token_locations);
+                    self.process_expression(arg);
                 }
             },
             // Handle other expression types...
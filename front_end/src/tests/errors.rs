@@ -28,6 +28,35 @@ fn test_duplicate_variable_error() {
     }
 }
 
+#[test]
+fn test_duplicate_function_error() {
+    let source = "
+    fn add(reads a: Int) -> Int {
+        return a
+    }
+    fn add(reads b: Int) -> Int {
+        return b
+    }
+    ";
+    
+    let mut source_manager = SourceManager::new();
+    source_manager.set_default_source(source);
+    
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let errors = parser.get_symbol_table().get_errors();
+    let reporter = DiagnosticReporter::new(source_manager);
+    let formatted: Vec<String> = errors.iter().map(|error| reporter.report_error(error)).collect();
+    
+    let duplicate = formatted.iter().find(|msg| msg.contains("duplicate definition of `add`"))
+        .expect("Should have caught the duplicate function");
+    assert!(duplicate.contains("--> 2:8") && duplicate.contains("first definition here"),
+           "Error should point at the first definition, got {}", duplicate);
+    assert!(duplicate.contains("--> 5:8") && duplicate.contains("redefinition here"),
+           "Error should point at the redefinition, got {}", duplicate);
+}
+
 #[test]
 fn test_undefined_variable_error() {
     let source = "
@@ -53,6 +82,50 @@ fn test_undefined_variable_error() {
     }
 }
 
+#[test]
+fn test_undefined_variable_suggests_similar_name() {
+    let source = "
+    reads counter: Int = 1
+    reads total: Int = countr
+    ";
+    
+    let mut source_manager = SourceManager::new();
+    source_manager.set_default_source(source);
+    
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let errors = parser.get_symbol_table().get_errors();
+    assert!(!errors.is_empty(), "Should have caught an undefined symbol error");
+    
+    let reporter = DiagnosticReporter::new(source_manager);
+    let formatted: Vec<String> = errors.iter().map(|error| reporter.report_error(error)).collect();
+    assert!(formatted.iter().any(|msg| msg.contains("did you mean `counter`?")),
+           "Error should suggest the similarly named variable, got {:?}", formatted);
+}
+
+#[test]
+fn test_undefined_variable_without_close_match() {
+    let source = "
+    reads counter: Int = 1
+    reads total: Int = xyz
+    ";
+    
+    let mut source_manager = SourceManager::new();
+    source_manager.set_default_source(source);
+    
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let errors = parser.get_symbol_table().get_errors();
+    let reporter = DiagnosticReporter::new(source_manager);
+    for error in errors {
+        let formatted = reporter.report_error(error);
+        assert!(!formatted.contains("did you mean"),
+               "Unrelated names should not be suggested, got {}", formatted);
+    }
+}
+
 #[test]
 fn test_immutable_assignment_error() {
     let source = "
@@ -194,16 +267,16 @@ fn test_read_assignment_with_peak() {
     
     // Verify the second statement is a declaration with the right name
     match &statements[1] {
-        crate::ast::Statement::Declaration { name, typ, initializer } => {
+        crate::ast::Statement::Declaration { name, typ, initializer, .. } => {
             // Check the variable name
             assert_eq!(name, "c", "Variable should be named 'c'");
             
             // Check that initializer is a peak expression
             match initializer {
-                Some(crate::ast::Expression::Peak(expr)) => {
+                Some(crate::ast::Expression::Peak(expr, _)) => {
                     // Check that the peak expression contains the counter variable
                     match &**expr {
-                        crate::ast::Expression::Variable(var_name) => {
+                        crate::ast::Expression::Variable(var_name, _) => {
                             assert_eq!(var_name, "counter", "Should peak the 'counter' variable");
                         },
                         _ => panic!("Expected variable reference inside peak"),
@@ -222,3 +295,179 @@ fn test_read_assignment_with_peak() {
 }
 
 
+
+#[test]
+fn test_parse_and_resolution_errors_share_diagnostic_format() {
+    use crate::diagnostic::{DiagnosticRenderer, Severity, ToDiagnostic};
+    use crate::error::ParseError;
+    use crate::symbol_table::{ResolutionError, Span};
+    
+    let source = "reads total: Int = 1\nreads x: Int = totl +\n";
+    let renderer = DiagnosticRenderer::with_source(source);
+    
+    let parse_error = ParseError::invalid_expression(Span::new(2, 21, 2, 21), "expected expression after '+'".to_string());
+    let resolution_error = ResolutionError::UndefinedSymbol {
+        name: "totl".to_string(),
+        span: Span::new(2, 16, 2, 19),
+        suggestion: Some("total".to_string()),
+    };
+    
+    let parse = parse_error.to_diagnostic();
+    let resolution = resolution_error.to_diagnostic();
+    assert_eq!(parse.severity, Severity::Error);
+    assert_eq!(resolution.suggestions[0].replacement.as_deref(), Some("total"));
+    
+    // Both render with a code, a location and the quoted line
+    let parse_report = renderer.render(&parse);
+    let resolution_report = renderer.render(&resolution);
+    println!("{}\n\n{}", parse_report, resolution_report);
+    assert!(parse_report.starts_with("error[E0102]: expected expression after '+'\n --> 2:21"));
+    assert!(resolution_report.starts_with("error[E0002]: undefined variable `totl`\n --> 2:16"));
+    assert!(resolution_report.contains("2 | reads x: Int = totl +"));
+    assert!(resolution_report.contains("  |                ^~~~ variable not found in this scope"));
+    assert!(resolution_report.contains("help: did you mean `total`?\n    total"));
+}
+
+#[test]
+fn test_diagnostics_render_as_json() {
+    use crate::diagnostic::{DiagnosticRenderer, ToDiagnostic};
+    use crate::symbol_table::{ResolutionError, Span};
+    
+    let mut source_manager = SourceManager::new();
+    let file_id = source_manager.add_file("main.cd".to_string(), "reads a = 1\nreads a = 2\n".to_string());
+    let in_file = |span: Span| Span { file_id: Some(file_id), ..span };
+    let error = ResolutionError::DuplicateSymbol {
+        name: "a".to_string(),
+        first: in_file(Span::new(1, 7, 1, 7)),
+        second: in_file(Span::new(2, 7, 2, 7)),
+    };
+    
+    let json = DiagnosticRenderer::with_source_manager(&source_manager).render_json(&error.to_diagnostic());
+    assert!(!json.contains('\n'), "one diagnostic per line: {}", json);
+    assert!(json.starts_with(r#"{"severity":"error","code":"E0001","message":"duplicate definition of `a`","#));
+    assert!(json.contains(r#"{"file":"main.cd","line_start":2,"column_start":7,"line_end":2,"column_end":7,"is_primary":true,"label":"redefinition here"}"#));
+    assert!(json.contains(r#""is_primary":false,"label":"first definition here""#));
+    assert!(json.contains(r#""notes":["each name must be defined only once per scope"],"suggestions":[]"#));
+}
+
+#[test]
+fn test_diagnostics_underline_primary_with_carets_and_color_for_terminals() {
+    use crate::diagnostic::{DiagnosticRenderer, ToDiagnostic};
+    use crate::symbol_table::{ResolutionError, Span};
+    
+    let source = "reads total = 1\nreads total = 2\n";
+    let error = ResolutionError::DuplicateSymbol {
+        name: "total".to_string(),
+        first: Span::new(1, 7, 1, 11),
+        second: Span::new(2, 7, 2, 11),
+    };
+    let diagnostic = error.to_diagnostic();
+    
+    let plain = DiagnosticRenderer::with_source(source).render(&diagnostic);
+    assert!(plain.contains("2 | reads total = 2\n  |       ^~~~~ redefinition here"), "{}", plain);
+    assert!(plain.contains("1 | reads total = 1\n  |       ~~~~~ first definition here"), "{}", plain);
+    assert!(!plain.contains('\x1b'), "plain output has no escapes: {:?}", plain);
+    
+    let colored = DiagnosticRenderer::with_source(source).with_color(true).render(&diagnostic);
+    assert!(colored.starts_with("\x1b[1;31merror[E0001]\x1b[0m"), "{:?}", colored);
+    assert!(colored.contains("\x1b[1;31m^~~~~ redefinition here\x1b[0m"), "{:?}", colored);
+    assert!(colored.contains("\x1b[1;34m~~~~~ first definition here\x1b[0m"), "{:?}", colored);
+}
+
+#[test]
+fn test_labels_at_the_same_span_share_one_snippet() {
+    use crate::diagnostic::{Diagnostic, DiagnosticRenderer};
+    use crate::symbol_table::Span;
+
+    let source = "reads writes total = 0\ntotal = total + 1\nprint total\n";
+    let diagnostic = Diagnostic::error("Potential data race on 'total'")
+        .with_primary(Span::new(2, 1, 2, 17), "")
+        .with_secondary(Span::new(2, 1, 2, 17), "writes in 'deposit'")
+        .with_secondary(Span::new(3, 7, 3, 11), "reads in 'report'")
+        .with_secondary(Span::new(3, 7, 3, 11), "reads again");
+
+    let rendered = DiagnosticRenderer::with_source(source).render(&diagnostic);
+    assert_eq!(rendered, [
+        "error: Potential data race on 'total'",
+        " --> 2:1",
+        "  |",
+        "2 | total = total + 1",
+        "  | ^~~~~~~~~~~~~~~~~ writes in 'deposit'",
+        " --> 3:7",
+        "  |",
+        "3 | print total",
+        "  |       ~~~~~ reads in 'report'",
+        "  |             reads again",
+    ].join("\n"));
+}
+
+#[test]
+fn test_every_diagnostic_code_is_registered_once() {
+    use crate::codes;
+    use std::collections::HashSet;
+    
+    let mut seen = HashSet::new();
+    for (code, summary) in codes::ALL {
+        assert!(seen.insert(*code), "code {} is registered twice", code);
+        assert!(!summary.is_empty(), "code {} has no summary", code);
+    }
+    
+    // Parse and resolution errors used to share E0001-E0003
+    assert_ne!(codes::parse::UNEXPECTED_TOKEN, codes::resolve::DUPLICATE_SYMBOL);
+    assert_eq!(codes::summary(codes::parse::INVALID_EXPRESSION), Some("An expression is missing or malformed"));
+    assert_eq!(codes::summary("E9999"), None);
+}
+
+#[test]
+fn test_undefined_assignment_target_is_reported_once() {
+    use crate::diagnostic::ToDiagnostic;
+    
+    let source = "reads a: Int = 1\ny = 3\n";
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let diagnostics: Vec<_> = parser.get_errors().iter().map(|error| error.to_diagnostic()).collect();
+    let undefined: Vec<_> = diagnostics.iter()
+        .filter(|diagnostic| diagnostic.message == "undefined variable `y`")
+        .collect();
+    assert_eq!(undefined.len(), 1, "{:?}", diagnostics);
+    // At the target, not at the `=`
+    let span = &undefined[0].primary.as_ref().unwrap().span;
+    assert_eq!((span.start_line, span.start_column), (2, 1));
+}
+
+#[test]
+fn test_type_errors_keep_their_code_and_span() {
+    use crate::codes;
+    use crate::diagnostic::ToDiagnostic;
+    use crate::error::CompileError;
+    use crate::symbol_table::Span;
+    
+    let error = CompileError::TypeError("expected Int, found Bool".to_string(), Span::new(3, 5, 3, 8));
+    let diagnostic = error.to_diagnostic();
+    assert_eq!(diagnostic.code.as_deref(), Some(codes::resolve::TYPE_MISMATCH));
+    assert_eq!(diagnostic.primary.unwrap().span, Span::new(3, 5, 3, 8));
+}
+
+#[test]
+fn test_dedup_keeps_the_first_report_of_a_problem() {
+    use crate::diagnostic::{dedup, Diagnostic};
+    use crate::symbol_table::Span;
+    
+    let at = |code: &str, line: usize| Diagnostic::error(format!("{} on line {}", code, line))
+        .with_code(code)
+        .with_primary(Span::new(line, 1, line, 1), "");
+    let mut diagnostics = vec![
+        at("E0002", 1),
+        at("E0002", 1),
+        // Another problem at the same place, or the same one elsewhere
+        at("E0005", 1),
+        at("E0002", 2),
+        // Without a span there is nothing to compare
+        Diagnostic::error("no span"),
+        Diagnostic::error("no span"),
+    ];
+    dedup(&mut diagnostics);
+    let messages: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+    assert_eq!(messages, ["E0002 on line 1", "E0005 on line 1", "E0002 on line 2", "no span", "no span"]);
+}
@@ -3,4 +3,6 @@ mod parser_tests;
 mod ast_tests;
 mod pipeline_tests;
 mod errors;
-mod type_inference_tests; // Add the new test module
\ No newline at end of file
+mod type_inference_tests; // Add the new test module
+mod scope_tests;
+mod multi_file_tests;
@@ -0,0 +1,57 @@
+use crate::diagnostics_reporter::DiagnosticReporter;
+use crate::error::CompileError;
+use crate::parser::Parser;
+use crate::source_manager::SourceManager;
+
+#[test]
+fn test_source_manager_assigns_file_ids() {
+    let mut source_manager = SourceManager::new();
+    let lib = source_manager.add_file("lib.cd".to_string(), "reads x: Int = 1\n".to_string());
+    let main = source_manager.add_file("main.cd".to_string(), "reads y: Int = 2\n".to_string());
+    
+    assert_ne!(lib, main);
+    assert_eq!(source_manager.file_count(), 2);
+    assert_eq!(source_manager.file_id("main.cd"), Some(main));
+    assert_eq!(source_manager.file_name(lib), Some("lib.cd"));
+    assert_eq!(source_manager.get_file_line(main, 1), Some("reads y: Int = 2\n"));
+    
+    // Re-adding a file keeps its id
+    let again = source_manager.add_file("lib.cd".to_string(), "reads z: Int = 3\n".to_string());
+    assert_eq!(again, lib);
+    assert_eq!(source_manager.file_count(), 2);
+}
+
+#[test]
+fn test_symbols_resolve_across_files() {
+    let mut source_manager = SourceManager::new();
+    source_manager.add_file("lib.cd".to_string(), "reads write counter: Int = 1\n".to_string());
+    source_manager.add_file("main.cd".to_string(), "reads write total: Int = counter + 1\n".to_string());
+    
+    let mut parser = Parser::new(Vec::new());
+    let statements = parser.parse_files(&source_manager);
+    
+    assert_eq!(statements.len(), 2);
+    assert!(parser.get_symbol_table().get_errors().is_empty(),
+           "'counter' from lib.cd should be visible in main.cd, got {:?}", parser.get_symbol_table().get_errors());
+}
+
+#[test]
+fn test_diagnostics_name_the_file() {
+    let mut source_manager = SourceManager::new();
+    source_manager.add_file("lib.cd".to_string(), "reads write counter: Int = 1\n".to_string());
+    source_manager.add_file("main.cd".to_string(), "\nreads write total: Int = missing\n".to_string());
+    
+    let mut parser = Parser::new(Vec::new());
+    parser.parse_files(&source_manager);
+    
+    let reporter = DiagnosticReporter::new(source_manager);
+    let messages: Vec<String> = parser.get_errors().iter().filter_map(|error| match error {
+        CompileError::Resolution(res_error) => Some(reporter.report_error(res_error)),
+        _ => None,
+    }).collect();
+    
+    assert!(messages.iter().any(|msg| msg.contains("undefined variable `missing`") && msg.contains("--> main.cd:2:")),
+           "Error should point into main.cd, got {:?}", messages);
+    assert!(messages.iter().any(|msg| msg.contains("reads write total: Int = missing")),
+           "Snippet should come from main.cd, got {:?}", messages);
+}
@@ -1,6 +1,6 @@
 use crate::parser::Parser;
 use crate::ast::{Statement, Expression};
-use crate::token::{self, TokenType};
+use crate::token::TokenType;
 use crate::types::{Type, Permission};
 
 #[test]
@@ -11,7 +11,7 @@ fn test_parse_variable_declaration_with_type() {
     // Add debug prints to understand what's happening
     println!("Test source: {}", source);
     
-    let mut parser = Parser::from_source(&source.to_string());
+    let mut parser = Parser::from_source(source);
 
     
     let statements = parser.parse_statements();
@@ -21,7 +21,7 @@ fn test_parse_variable_declaration_with_type() {
     
     // Check that it's a variable declaration
     match &statements[0] {
-        Statement::Declaration { name, initializer, typ } => {
+        Statement::Declaration { name, initializer, typ, .. } => {
             // Check the variable name
             assert_eq!(name, "x");
             
@@ -32,7 +32,7 @@ fn test_parse_variable_declaration_with_type() {
             
             // Check the initializer value
             match initializer {
-                Some(Expression::Number(val)) => assert_eq!(*val, 42),
+                Some(Expression::Number(val, _)) => assert_eq!(*val, 42),
                 _ => panic!("Expected number initializer"),
             }
         },
@@ -44,7 +44,7 @@ fn test_parse_variable_declaration_with_type() {
 #[test]
 fn test_multiple_permissions() {
     let source = "reads write x: Int = 42";
-    let mut parser = Parser::from_source(&source.to_string());
+    let mut parser = Parser::from_source(source);
     
     let statements = parser.parse_statements();
     assert_eq!(statements.len(), 1, "Should have parsed one statement");
@@ -65,7 +65,7 @@ fn test_parse_simple_function_declaration() {
     
     println!("Test source: {}", source);
     
-    let mut parser = Parser::from_source(&source.to_string());
+    let mut parser = Parser::from_source(source);
     let statements = parser.parse_statements();
     
     println!("Parsed statements: {}", statements.len());
@@ -88,7 +88,7 @@ fn test_read_write_and_anon_function() {
     
     println!("Test source: {}", source);
     
-    let mut parser = Parser::from_source(&source.to_string());
+    let mut parser = Parser::from_source(source);
     let statements = parser.parse_statements();
     
     println!("Parsed statements: {}", statements.len());
@@ -98,7 +98,7 @@ fn test_read_write_and_anon_function() {
     
     // First statement: Variable declaration with read and write permissions
     match &statements[0] {
-        Statement::Declaration { name, typ, initializer } => {
+        Statement::Declaration { name, typ, initializer, .. } => {
             // Check name
             assert_eq!(name, "a", "Variable should be named 'a'");
             
@@ -114,7 +114,7 @@ fn test_read_write_and_anon_function() {
             
             // Check initializer
             match initializer {
-                Some(Expression::Number(val)) => assert_eq!(*val, 32),
+                Some(Expression::Number(val, _)) => assert_eq!(*val, 32),
                 _ => panic!("Expected number initializer with value 32"),
             }
             
@@ -125,12 +125,12 @@ fn test_read_write_and_anon_function() {
     
     // Second statement: Named function declaration
     match &statements[1] {
-        Statement::Function { name, params, body, return_type, is_behavior } => {
+        Statement::Function { name, params, body, return_type, is_behavior, .. } => {
             // Check function name
             assert_eq!(name, "add", "Function should be named 'add'");
             
             // Check it's not a behavior
-            assert_eq!(*is_behavior, false, "Should not be a behavior method");
+            assert!(!*is_behavior, "Should not be a behavior method");
             
             // Check that it has no parameters
             assert_eq!(params.len(), 0, "Function should have no parameters");
@@ -147,10 +147,10 @@ fn test_read_write_and_anon_function() {
             
             // Check the return statement
             match &body[0] {
-                Statement::Return(expr) => {
+                Statement::Return(expr, _) => {
                     // Should return the number 1
                     match expr {
-                        Expression::Number(val) => assert_eq!(*val, 1),
+                        Expression::Number(val, _) => assert_eq!(*val, 1),
                         _ => panic!("Expected number 1 in return statement"),
                     }
                 },
@@ -163,3 +163,61 @@ fn test_read_write_and_anon_function() {
     }
 }
 
+
+#[test]
+fn test_parse_actor_declaration() {
+    let source = "actor Counter {\n  reads write count: Int = 0\n  fn current() -> Int {\n    return count\n  }\n  on increment() {\n    count = count + 1\n  }\n}";
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    
+    assert_eq!(statements.len(), 1, "Should have parsed one actor declaration");
+    
+    match &statements[0] {
+        Statement::Actor { name, state, methods, behaviors, .. } => {
+            assert_eq!(name, "Counter");
+            assert_eq!(state.len(), 1, "Actor should have one state field");
+            assert_eq!(methods.len(), 1, "Actor should have one method");
+            assert_eq!(behaviors.len(), 1, "Actor should have one behavior");
+            assert!(matches!(&behaviors[0], Statement::Function { is_behavior: true, .. }),
+                    "Behavior should be marked as a behavior");
+        },
+        _ => panic!("Expected actor declaration"),
+    }
+}
+
+#[test]
+fn test_parse_generic_function() {
+    let source = "fn pair<A, B>(reads first: A, reads second: B) -> A {\n  return first\n}";
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    
+    match &statements[0] {
+        Statement::Function { type_params, params, return_type, .. } => {
+            assert_eq!(type_params, &["A", "B"]);
+            assert_eq!(params[0].1.base_type, Type::Param("A".to_string()));
+            assert_eq!(params[1].1.base_type, Type::Param("B".to_string()));
+            assert_eq!(return_type.as_ref().unwrap().base_type, Type::Param("A".to_string()));
+        },
+        _ => panic!("Expected function declaration"),
+    }
+}
+
+#[test]
+fn test_parse_while_with_break_and_continue() {
+    let source = "while i < 3 {\n  i += 1\n  continue\n  break\n}";
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    
+    match &statements[0] {
+        Statement::While { condition, body, .. } => {
+            assert!(matches!(condition, Expression::Binary { operator: TokenType::Less, .. }));
+            assert_eq!(body.len(), 3);
+            assert!(matches!(body[1], Statement::Continue(_)));
+            assert!(matches!(body[2], Statement::Break(_)));
+        },
+        other => panic!("Expected while loop, got {:?}", other),
+    }
+}
@@ -46,7 +46,7 @@ fn test_front_end_pipeline() {
     // Validate the first statement (variable declaration)
     println!("\n--- Validating Variable Declaration ---");
     match &statements[0] {
-        Statement::Declaration { name, typ, initializer } => {
+        Statement::Declaration { name, typ, initializer, .. } => {
             println!("✓ First statement is a variable declaration");
             println!("  Name: {}", name);
             println!("  Type: {:?}", typ);
@@ -61,7 +61,7 @@ fn test_front_end_pipeline() {
             
             // Verify initializer
             match initializer {
-                Some(Expression::Number(n)) => {
+                Some(Expression::Number(n, _)) => {
                     assert_eq!(*n, 10, "Initializer should be 10");
                     println!("  ✓ Variable initializer correct");
                 },
@@ -116,7 +116,7 @@ fn test_front_end_pipeline() {
             
             // Verify it's a function call
             match initializer {
-                Some(Expression::Call { function, arguments }) => {
+                Some(Expression::Call { function, arguments, .. }) => {
                     println!("  ✓ Initializer is a function call");
                     
                     // Verify function name (function is a String in your AST)
@@ -126,7 +126,7 @@ fn test_front_end_pipeline() {
                     // Verify argument
                     assert_eq!(arguments.len(), 1, "Function call should have 1 argument");
                     match &arguments[0] {
-                        Expression::Number(n) => {
+                        Expression::Number(n, _) => {
                             assert_eq!(*n, 5, "Argument should be 5");
                             println!("  ✓ Function argument correct");
                         },
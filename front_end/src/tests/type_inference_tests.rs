@@ -14,7 +14,7 @@ fn test_basic_type_inference() {
     
     // Check that it's a variable declaration with the correct inferred type
     match &statements[0] {
-        Statement::Declaration { name, typ, initializer } => {
+        Statement::Declaration { name, typ, initializer, .. } => {
             // Check the variable name
             assert_eq!(name, "x");
             
@@ -23,7 +23,7 @@ fn test_basic_type_inference() {
             
             // Check the initializer value
             match initializer {
-                Some(Expression::Number(val)) => assert_eq!(*val, 42),
+                Some(Expression::Number(val, _)) => assert_eq!(*val, 42),
                 _ => panic!("Expected number initializer"),
             }
         },
@@ -174,7 +174,7 @@ fn calculate() -> Int {
             
             // Check that the statement is now a return
             match &body[0] {
-                Statement::Return(expr) => {
+                Statement::Return(expr, _) => {
                     match expr {
                         Expression::Binary { .. } => {
                             // Success - the expression was converted to a return
@@ -219,14 +219,15 @@ fn test_function_return_type_inference() {
     // Check if we got any statements at all
     assert!(!statements.is_empty(), "Should have parsed at least one statement");
     
-    // In a more complete implementation, we would verify that the return type
-    // was inferred as Int. For now, we just verify the basic parsing works.
+    // The return type should be inferred from the implicit return expression
     match &statements[0] {
         Statement::Function { name, return_type, body, .. } => {
             assert_eq!(name, "multiply", "Function should be named 'multiply'");
             
-            // Currently return type is None when not specified
-            assert!(return_type.is_none(), "Return type should be None when not specified");
+            // Return type is inferred as Int from `a * b`
+            assert!(return_type.is_some(), "Return type should be inferred when not specified");
+            assert_eq!(return_type.as_ref().unwrap().base_type, Type::Int,
+                      "Inferred return type should be Int");
             
             // Check that body exists and has statements
             assert!(!body.is_empty(), "Function body should have at least one statement");
@@ -234,13 +235,13 @@ fn test_function_return_type_inference() {
             // The last expression should be converted to a return
             let last_stmt = body.last().unwrap();
             match last_stmt {
-                Statement::Return(expr) => {
+                Statement::Return(expr, _) => {
                     // It should be a binary operation
                     match expr {
-                        Expression::Binary { left, operator, right } => {
+                        Expression::Binary { left, operator, right, .. } => {
                             println!("Found binary expression with operator: {:?}", operator);
                             match (&**left, operator, &**right) {
-                                (Expression::Variable(name_left), op, Expression::Variable(name_right)) => {
+                                (Expression::Variable(name_left, _), op, Expression::Variable(name_right, _)) => {
                                     assert_eq!(name_left, "a", "Left operand should be 'a'");
                                     assert_eq!(name_right, "b", "Right operand should be 'b'");
                                     
@@ -263,6 +264,26 @@ fn test_function_return_type_inference() {
     }
 }
 
+#[test]
+fn test_function_without_return_keeps_unit() {
+    // A body with no return expression should not get an inferred return type
+    let source = "
+    fn log_value(reads x: Int) {
+        print x
+    }
+    ";
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    
+    match &statements[0] {
+        Statement::Function { return_type, .. } => {
+            assert!(return_type.is_none(), "Unit-returning functions should keep no return type");
+        },
+        _ => panic!("Expected function declaration"),
+    }
+}
+
 #[test]
 fn test_function_with_explicit_return() {
     // Function with explicit return statement
@@ -287,7 +308,7 @@ fn test_function_with_explicit_return() {
             
             // Check that the body contains a return statement
             match &body[0] {
-                Statement::Return(_) => {
+                Statement::Return(_, _) => {
                     println!("Successfully parsed explicit return");
                 },
                 _ => panic!("Expected explicit return statement"),
@@ -353,7 +374,7 @@ fn test_complex_function_body() {
             // Check that the last statement is a return
             if body.len() >= 3 {
                 match &body[2] {
-                    Statement::Return(expr) => {
+                    Statement::Return(expr, _) => {
                         println!("Last statement is a return: {:?}", expr);
                     },
                     _ => println!("Last statement is not a return: {:?}", &body[body.len()-1]),
@@ -401,9 +422,9 @@ fn test_binary_expressions() {
         Statement::Function { body, .. } => {
             assert!(!body.is_empty(), "Function body should have at least one statement");
             match &body[0] {
-                Statement::Return(expr) => {
+                Statement::Return(expr, _) => {
                     match expr {
-                        Expression::Binary { left, operator, right } => {
+                        Expression::Binary { .. } => {
                             println!("Successfully parsed binary expression in function body");
                         },
                         _ => panic!("Expected binary expression in return"),
@@ -457,10 +478,10 @@ fn test_type_mismatch_in_return() {
             
             // Check the last statement is a return with a Bool expression
             match &body[2] {
-                Statement::Return(expr) => {
+                Statement::Return(expr, _) => {
                     // This should be a comparison operation (which would be a Bool in a proper type system)
                     match expr {
-                        Expression::Binary { left, operator, right } => {
+                        Expression::Binary { operator, .. } => {
                             // Verify it's a comparison operation
                             assert!(matches!(operator, 
                                             crate::token::TokenType::Greater | 
@@ -525,15 +546,10 @@ fn test_explicit_type_mismatch_detection() {
     
     // Find a type mismatch error
     let has_type_error = errors.iter().any(|e| {
-        match e {
-            crate::error::CompileError::Resolution(res_err) => {
-                match res_err {
-                    crate::symbol_table::ResolutionError::TypeMismatch { .. } => true,
-                    _ => false
-                }
-            },
-            _ => false
-        }
+        matches!(
+            e,
+            crate::error::CompileError::Resolution(crate::symbol_table::ResolutionError::TypeMismatch { .. })
+        )
     });
     
     assert!(has_type_error, "Should have detected a type mismatch error");
@@ -541,18 +557,8 @@ fn test_explicit_type_mismatch_detection() {
 
 #[test]
 fn test_multiple_return_type_mismatches() {
-    // Function with explicit Int return type but multiple different return types
-    let source = "
-    fn multiple_mismatches(reads x: Int) -> Int {
-        if x > 10 {  // This is just pseudocode since we don't have if statements yet
-            return x > 100  // Bool return - ERROR
-        } else {
-            return x        // Int return - OK
-        }
-    }
-    ";
-    
-    // We don't have conditionals yet, so we'll simulate this with two functions
+    // Functions with explicit Int return types but different return types;
+    // we don't have conditionals yet, so we simulate one with several functions
     let actual_source = "
     fn bool_return(reads x: Int) -> Int {
         x > 100  // Returns Bool when Int expected - ERROR
@@ -588,15 +594,10 @@ fn test_multiple_return_type_mismatches() {
     
     // Count type mismatch errors
     let type_mismatch_count = errors.iter().filter(|e| {
-        match e {
-            crate::error::CompileError::Resolution(res_err) => {
-                match res_err {
-                    crate::symbol_table::ResolutionError::TypeMismatch { .. } => true,
-                    _ => false
-                }
-            },
-            _ => false
-        }
+        matches!(
+            e,
+            crate::error::CompileError::Resolution(crate::symbol_table::ResolutionError::TypeMismatch { .. })
+        )
     }).count();
     
     assert_eq!(type_mismatch_count, 1, "Should have detected exactly one type mismatch error");
@@ -654,9 +655,45 @@ fn test_complex_expressions_with_mismatched_types() {
         }
     });
     
-    // While we don't yet check binary expression type compatibility,
-    // we should at least be detecting return type mismatches
     assert!(has_type_error, "Should have detected a return type mismatch in bad_return");
+    
+    // The operands of `a + b` are checked as well
+    let invalid_operands = errors.iter().find(|e| matches!(e,
+        crate::error::CompileError::Resolution(crate::symbol_table::ResolutionError::InvalidOperands {
+            context, ..
+        }) if context.contains("bad_math")));
+    
+    match invalid_operands {
+        Some(crate::error::CompileError::Resolution(err)) => {
+            let message = err.to_string();
+            println!("{}", message);
+            assert!(message.contains("cannot add `Int` and `Bool`"), "Error should name the operation and both types");
+        },
+        _ => panic!("Should have detected the Int + Bool operands in bad_math"),
+    }
+}
+
+#[test]
+fn test_arithmetic_on_non_numeric_operands() {
+    // Matching operand types are still rejected when arithmetic isn't defined for them
+    let source = "
+    fn both_bool(reads a: Bool, reads b: Bool) {
+        reads sum = a * b
+        a == b
+    }
+    ";
+    
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let operand_errors: Vec<_> = parser.get_errors().into_iter().filter_map(|e| match e {
+        crate::error::CompileError::Resolution(err @ crate::symbol_table::ResolutionError::InvalidOperands { .. }) => Some(err),
+        _ => None,
+    }).collect();
+    
+    // Only the multiplication is invalid; comparing two Bools for equality is fine
+    assert_eq!(operand_errors.len(), 1, "Should have reported exactly the Bool * Bool expression");
+    assert!(operand_errors[0].to_string().contains("cannot multiply `Bool` and `Bool`"));
 }
 
 #[test]
@@ -696,17 +733,17 @@ fn test_parenthesized_expressions() {
         Statement::Function { body, .. } => {
             assert_eq!(body.len(), 1, "Function body should have 1 statement");
             match &body[0] {
-                Statement::Return(expr) => {
+                Statement::Return(expr, _) => {
                     // Verify it's a binary expression with the correct structure
                     match expr {
-                        Expression::Binary { left, operator, right } => {
+                        Expression::Binary { left, operator, right, .. } => {
                             // Multiplication operator
                             assert!(matches!(operator, crate::token::TokenType::Star), 
                                     "Expected multiplication operator");
                             
                             // Left operand should be a parenthesized expression
                             match &**left {
-                                Expression::Binary { left: inner_left, operator: inner_op, right: inner_right } => {
+                                Expression::Binary { operator: inner_op, .. } => {
                                     assert!(matches!(inner_op, crate::token::TokenType::Plus),
                                             "Expected addition operator inside parentheses");
                                 },
@@ -715,7 +752,7 @@ fn test_parenthesized_expressions() {
                             
                             // Right operand should be a number
                             match &**right {
-                                Expression::Number(val) => assert_eq!(*val, 2),
+                                Expression::Number(val, _) => assert_eq!(*val, 2),
                                 _ => panic!("Expected number as right operand"),
                             }
                         },
@@ -728,3 +765,190 @@ fn test_parenthesized_expressions() {
         _ => panic!("Expected function declaration"),
     }
 }
+
+#[test]
+fn test_return_type_inferred_through_calls() {
+    // The caller's return type comes from the callee's inferred signature
+    let source = "
+    fn is_big(reads x: Int) {
+        x > 100
+    }
+    
+    fn check(reads y: Int) {
+        is_big(y)
+    }
+    ";
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    
+    assert_eq!(statements.len(), 2, "Should have parsed two function declarations");
+    match &statements[1] {
+        Statement::Function { name, return_type, .. } => {
+            assert_eq!(name, "check");
+            assert_eq!(return_type.as_ref().map(|t| t.base_type.clone()), Some(Type::Bool),
+                      "Return type of 'check' should be inferred from 'is_big'");
+        },
+        _ => panic!("Expected function declaration"),
+    }
+}
+
+#[test]
+fn test_conflicting_returns_without_annotation() {
+    // Without an annotation, the first return fixes the type and later ones must agree
+    let source = "
+    fn conflicting(reads x: Int) {
+        return x > 1
+        return x
+    }
+    ";
+    
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let mismatch = parser.get_errors().into_iter().find_map(|e| match e {
+        crate::error::CompileError::Resolution(crate::symbol_table::ResolutionError::TypeMismatch {
+            expected, found, context, ..
+        }) => Some((expected, found, context)),
+        _ => None,
+    });
+    
+    let (expected, found, context) = mismatch.expect("Should have detected a return type conflict");
+    assert_eq!(expected, "Bool");
+    assert_eq!(found, "Int");
+    assert!(context.contains("conflicting"), "Error should mention the function name");
+}
+
+#[test]
+fn test_call_argument_type_mismatch() {
+    // Arguments are unified against the callee's parameter types
+    let source = "
+    fn increment(reads x: Int) -> Int {
+        x + 1
+    }
+    
+    fn caller(reads y: Int) {
+        increment(y > 0)
+    }
+    ";
+    
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let has_argument_error = parser.get_errors().iter().any(|e| matches!(e,
+        crate::error::CompileError::Resolution(crate::symbol_table::ResolutionError::TypeMismatch {
+            context, ..
+        }) if context.contains("argument 1 of call to 'increment'")));
+    
+    assert!(has_argument_error, "Should have detected a Bool argument passed to an Int parameter");
+}
+
+#[test]
+fn test_generic_function_called_at_two_types() {
+    // Each call instantiates `id`'s signature afresh, so using it at Int
+    // does not fix T for the Bool call
+    let source = "
+    fn id<T>(reads x: T) -> T {
+        return x
+    }
+    
+    fn main() {
+        reads a: Int = id(1)
+        reads b: Bool = id(1 > 0)
+        reads c: Int = id(1 > 0)
+    }
+    ";
+    
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    
+    let mismatches: Vec<_> = parser.get_errors().into_iter().filter_map(|e| match e {
+        crate::error::CompileError::Resolution(crate::symbol_table::ResolutionError::TypeMismatch {
+            expected, found, span, context,
+        }) => Some((expected, found, span, context)),
+        _ => None,
+    }).collect();
+    
+    // Only the third call disagrees with its declaration
+    assert_eq!(mismatches.len(), 1, "{:?}", mismatches);
+    let (expected, found, span, context) = &mismatches[0];
+    assert_eq!(expected, "Int");
+    assert_eq!(found, "Bool");
+    assert_eq!(context, "in declaration of 'c'");
+    
+    // Reported at the call, not at the enclosing `fn`
+    assert_eq!((span.start_line, span.start_column), (9, 24));
+}
+
+/// The type mismatches and invalid operands reported for a program
+fn type_errors(source: &str) -> Vec<crate::symbol_table::ResolutionError> {
+    let mut parser = Parser::from_source(source);
+    let _ = parser.parse_statements();
+    parser.get_errors().into_iter().filter_map(|e| match e {
+        crate::error::CompileError::Resolution(error @ (
+            crate::symbol_table::ResolutionError::TypeMismatch { .. } |
+            crate::symbol_table::ResolutionError::InvalidOperands { .. }
+        )) => Some(error),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn test_sized_integers_take_literals_and_widen() {
+    // Literals take the type they are used at, and narrower integers
+    // widen in initializers, operands and arguments
+    let source = "
+    fn narrow(reads x: Int8) -> Int8 {
+        return x
+    }
+    
+    fn main() -> Int32 {
+        reads a: Int8 = 1
+        reads b: Int32 = a
+        reads c: Int32 = a + b
+        reads d: Int8 = narrow(5) + 1
+        return c + d
+    }
+    ";
+    
+    let errors = type_errors(source);
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn test_literals_default_to_int() {
+    // Nothing constrains the literal, so the return type is Int and not
+    // generic
+    let source = "
+    fn one() {
+        return 1
+    }
+    
+    fn main() {
+        reads b: Bool = one()
+    }
+    ";
+    
+    let errors = type_errors(source);
+    assert!(matches!(&errors[..], [crate::symbol_table::ResolutionError::TypeMismatch { expected, found, .. }]
+        if expected == "Bool" && found == "Int"), "{:?}", errors);
+}
+
+#[test]
+fn test_narrowing_and_overflowing_literals_are_rejected() {
+    let source = "
+    fn main() {
+        reads a: Int8 = 300
+        reads b: UInt = 1
+        reads c: Int8 = b
+        reads d: Int32 = 1
+        reads e: Int8 = d + 1
+    }
+    ";
+    
+    let contexts: Vec<String> = type_errors(source).into_iter().filter_map(|e| match e {
+        crate::symbol_table::ResolutionError::TypeMismatch { context, .. } => Some(context),
+        _ => None,
+    }).collect();
+    assert_eq!(contexts, ["in declaration of 'a'", "in declaration of 'c'", "in declaration of 'e'"]);
+}
@@ -1,4 +1,6 @@
-#[derive(Debug, PartialEq, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum TokenType {
     // Single-character tokens
     LeftParen, RightParen,
@@ -21,6 +23,7 @@ pub enum TokenType {
     
     // Permission operations
     Peak, Clone,  // Add these new token types
+    Consume,
     
     // Literals
     Identifier(String),
@@ -28,8 +31,8 @@ pub enum TokenType {
     Number(i64),
     
     // Keywords
-    If, Else, While, For,
-    Fn, On, Actor, Return, Print,
+    If, Else, While, For, Break, Continue,
+    Fn, On, Actor, Return, Print, Atomic,
     
     // Types
     TypeInt, TypeInt8, TypeInt16, TypeInt32, TypeInt64,
@@ -42,6 +45,52 @@ pub enum TokenType {
     Eof,
 }
 
+impl TokenType {
+    /// Arithmetic operators: `+ - * /`
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(self, TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash)
+    }
+
+    /// Ordering comparisons: `< <= > >=`
+    pub fn is_ordering(&self) -> bool {
+        matches!(self, TokenType::Less | TokenType::LessEqual | TokenType::Greater | TokenType::GreaterEqual)
+    }
+
+    /// Equality comparisons: `== !=`
+    pub fn is_equality(&self) -> bool {
+        matches!(self, TokenType::EqualEqual | TokenType::BangEqual)
+    }
+
+    /// Source spelling of a binary operator
+    pub fn operator_symbol(&self) -> &'static str {
+        match self {
+            TokenType::Plus => "+",
+            TokenType::Minus => "-",
+            TokenType::Star => "*",
+            TokenType::Slash => "/",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::EqualEqual => "==",
+            TokenType::BangEqual => "!=",
+            _ => "?",
+        }
+    }
+
+    /// The verb used in diagnostics about a binary operator, e.g. "add"
+    pub fn operation_verb(&self) -> &'static str {
+        match self {
+            TokenType::Plus => "add",
+            TokenType::Minus => "subtract",
+            TokenType::Star => "multiply",
+            TokenType::Slash => "divide",
+            _ if self.is_ordering() || self.is_equality() => "compare",
+            _ => "combine",
+        }
+    }
+}
+
 use crate::types::Permission;
 
 #[derive(Debug, Clone, PartialEq)]
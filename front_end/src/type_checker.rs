@@ -1,6 +1,6 @@
 use crate::ast::{Expression, Statement};
 use crate::symbol_table::{Span, ResolutionError, SymbolTable};
-use crate::types::{Type, PermissionedType};
+use crate::types::Type;
 use crate::token::TokenType;
 
 // Change the TypeChecker to use an immutable reference to SymbolTable
@@ -18,24 +18,22 @@ impl<'a> TypeChecker<'a> {
     pub fn check_function(&self, function: &Statement, span: Span) -> Vec<ResolutionError> {
         let mut errors = Vec::new();
         
-        if let Statement::Function { name, return_type, body, .. } = function {
-            // If there's an explicit return type, check all returns match it
-            if let Some(return_type) = return_type {
-                let expected_type = &return_type.base_type;
-                
-                // Check all return statements in the body
-                for stmt in body {
-                    if let Statement::Return(expr) = stmt {
-                        let expr_type = self.infer_expression_type(expr);
-                        
-                        if &expr_type != expected_type {
-                            errors.push(ResolutionError::TypeMismatch {
-                                expected: format!("{:?}", expected_type),
-                                found: format!("{:?}", expr_type),
-                                span: span.clone(),
-                                context: format!("in return value of function '{}'", name)
-                            });
-                        }
+        // If there's an explicit return type, check all returns match it
+        if let Statement::Function { name, return_type: Some(return_type), body, .. } = function {
+            let expected_type = &return_type.base_type;
+            
+            // Check all return statements in the body
+            for stmt in body {
+                if let Statement::Return(expr, _) = stmt {
+                    let expr_type = self.infer_expression_type(expr);
+                    
+                    if &expr_type != expected_type {
+                        errors.push(ResolutionError::TypeMismatch {
+                            expected: format!("{:?}", expected_type),
+                            found: format!("{:?}", expr_type),
+                            span: span.clone(),
+                            context: format!("in return value of function '{}'", name)
+                        });
                     }
                 }
             }
@@ -46,15 +44,14 @@ impl<'a> TypeChecker<'a> {
     
     pub fn infer_expression_type(&self, expr: &Expression) -> Type {
         match expr {
-            Expression::Number(_) => Type::Int,
-            Expression::Variable(name) => {
-                // Since we're using an immutable reference, we need to handle this differently
-                // We can't use resolve since it modifies the symbol table
-                // Instead, let's use a simple type inference based on the expression
-                
-                // For variables, default to Int for now
-                // In a more advanced implementation, we would track variable types separately
-                Type::Int
+            Expression::Number(_, _) => Type::Int,
+            Expression::Variable(name, _) => {
+                // Look the variable up without resolving it, which would
+                // record a use; default to Int for names not declared yet
+                self.symbol_table
+                    .lookup(name)
+                    .map(|symbol| symbol.typ.base_type.clone())
+                    .unwrap_or(Type::Int)
             },
             
             Expression::Binary { operator, .. } => {
@@ -74,8 +71,9 @@ impl<'a> TypeChecker<'a> {
             Expression::Call { .. } => Type::Int, // For now, all function calls default to Int
             
             // Operators that maintain the type of their operand
-            Expression::Clone(expr) => self.infer_expression_type(expr),
-            Expression::Peak(expr) => self.infer_expression_type(expr),
+            Expression::Clone(expr, _) => self.infer_expression_type(expr),
+            Expression::Peak(expr, _) => self.infer_expression_type(expr),
+            Expression::Consume(expr, _) => self.infer_expression_type(expr),
         }
     }
 }
@@ -1,25 +1,74 @@
 use std::collections::HashMap;
-use crate::types::{Type, Permission, PermissionedType};
+use crate::types::{Type, PermissionedType};
 use crate::ast::{Expression, Statement};
-use crate::symbol_table::{SymbolTable, Span, Symbol, SymbolKind};
+use crate::symbol_table::{SymbolTable, Span, ResolutionError};
+use crate::token::TokenType;
 
 /// Represents a type variable used during type inference
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TypeVar(usize);
 
-/// Represents either a concrete type or a type variable
+/// Represents either a concrete type, a type variable or a function signature
 #[derive(Debug, Clone, PartialEq)]
 pub enum InferenceType {
     Concrete(Type),
     Variable(TypeVar),
+    Function(Vec<InferenceType>, Box<InferenceType>),
+}
+
+impl std::fmt::Display for InferenceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InferenceType::Concrete(t) => write!(f, "{:?}", t),
+            InferenceType::Variable(TypeVar(id)) => write!(f, "?T{}", id),
+            InferenceType::Function(params, ret) => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") -> {}", ret)
+            }
+        }
+    }
+}
+
+/// A generalized type `forall quantified. body`, used for function signatures
+/// so that each call site gets its own instantiation (let-polymorphism)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeScheme {
+    pub quantified: Vec<TypeVar>,
+    pub body: InferenceType,
+}
+
+/// A constraint collected while walking a function body: `found` must
+/// convert to `expected`, or for operands both must promote to one type
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    pub expected: InferenceType,
+    pub found: InferenceType,
+    pub span: Span,
+    pub context: String,
+    /// Set when the constraint relates the operands of a binary operator
+    pub operator: Option<TokenType>,
+    /// Type of an arithmetic expression, the promoted type of its operands
+    pub result: Option<InferenceType>,
 }
 
 /// The type environment tracks type variables and constraints
+#[derive(Default)]
 pub struct TypeEnvironment {
     /// Mapping from type variables to their resolved types (if known)
     substitutions: HashMap<TypeVar, InferenceType>,
     /// Counter to generate unique type variables
     next_var_id: usize,
+    /// Generalized signatures of the functions inferred so far
+    schemes: HashMap<String, TypeScheme>,
+    /// Type variables of integer literals, with the range of values each
+    /// must hold
+    literals: HashMap<TypeVar, (i64, i64)>,
 }
 
 /// Provides methods for type unification and inference
@@ -30,6 +79,17 @@ pub struct TypeInferer<'a> {
     symbol_table: &'a mut SymbolTable,
     /// Type errors found during inference
     errors: Vec<String>,
+    /// Constraints collected for the function currently being inferred
+    constraints: Vec<Constraint>,
+    /// Lexical scopes of local variable types inside function bodies
+    locals: Vec<HashMap<String, InferenceType>>,
+    /// Name and return type variable of each function being inferred
+    function_stack: Vec<(String, InferenceType)>,
+    /// Unification failures reported while solving constraints
+    type_errors: Vec<ResolutionError>,
+    /// Type variables standing for the generic parameters of each function
+    /// being inferred
+    type_params: Vec<HashMap<String, InferenceType>>,
 }
 
 impl TypeEnvironment {
@@ -37,6 +97,8 @@ impl TypeEnvironment {
         Self {
             substitutions: HashMap::new(),
             next_var_id: 0,
+            schemes: HashMap::new(),
+            literals: HashMap::new(),
         }
     }
     
@@ -47,6 +109,35 @@ impl TypeEnvironment {
         var
     }
     
+    /// Create a type variable for an integer literal, which takes any
+    /// numeric type that holds `value`
+    pub fn fresh_literal(&mut self, value: i64) -> TypeVar {
+        let var = self.fresh_var();
+        self.literals.insert(var.clone(), (value, value));
+        var
+    }
+    
+    /// Bind the literals created since `first` that nothing constrained to
+    /// the default integer type
+    pub fn default_literals(&mut self, first: &TypeVar) {
+        let unresolved: Vec<TypeVar> = self.literals.keys()
+            .filter(|var| var.0 >= first.0 && !self.substitutions.contains_key(var))
+            .cloned()
+            .collect();
+        for var in unresolved {
+            self.substitutions.insert(var, InferenceType::Concrete(Type::Int));
+        }
+    }
+    
+    /// `t` resolved, with an unconstrained literal shown as the default
+    /// integer type
+    pub fn resolve_for_display(&self, t: &InferenceType) -> InferenceType {
+        match self.resolve(t) {
+            InferenceType::Variable(var) if self.literals.contains_key(&var) => InferenceType::Concrete(Type::Int),
+            resolved => resolved,
+        }
+    }
+    
     /// Apply substitutions to resolve a type to its most concrete form
     pub fn resolve(&self, t: &InferenceType) -> InferenceType {
         match t {
@@ -59,10 +150,90 @@ impl TypeEnvironment {
                     t.clone()
                 }
             },
+            InferenceType::Function(params, ret) => InferenceType::Function(
+                params.iter().map(|p| self.resolve(p)).collect(),
+                Box::new(self.resolve(ret)),
+            ),
             _ => t.clone(),
         }
     }
     
+    /// Check whether a type variable appears inside a type (the occurs check)
+    pub fn occurs(&self, var: &TypeVar, t: &InferenceType) -> bool {
+        match self.resolve(t) {
+            InferenceType::Variable(other) => &other == var,
+            InferenceType::Function(params, ret) => {
+                params.iter().any(|p| self.occurs(var, p)) || self.occurs(var, &ret)
+            },
+            InferenceType::Concrete(_) => false,
+        }
+    }
+    
+    /// Collect the unresolved type variables of a type
+    pub fn free_vars(&self, t: &InferenceType) -> Vec<TypeVar> {
+        let mut vars = Vec::new();
+        self.collect_free_vars(&self.resolve(t), &mut vars);
+        vars
+    }
+    
+    fn collect_free_vars(&self, t: &InferenceType, vars: &mut Vec<TypeVar>) {
+        match t {
+            InferenceType::Variable(var) => {
+                if !vars.contains(var) {
+                    vars.push(var.clone());
+                }
+            },
+            InferenceType::Function(params, ret) => {
+                for param in params {
+                    self.collect_free_vars(param, vars);
+                }
+                self.collect_free_vars(ret, vars);
+            },
+            InferenceType::Concrete(_) => {}
+        }
+    }
+    
+    /// Quantify over every free variable of `t` that is not fixed by the
+    /// enclosing environment
+    pub fn generalize(&self, t: &InferenceType, env_vars: &[TypeVar]) -> TypeScheme {
+        let body = self.resolve(t);
+        let quantified = self.free_vars(&body)
+            .into_iter()
+            .filter(|var| !env_vars.contains(var))
+            .collect();
+        TypeScheme { quantified, body }
+    }
+    
+    /// Replace the quantified variables of a scheme with fresh ones
+    pub fn instantiate(&mut self, scheme: &TypeScheme) -> InferenceType {
+        let mapping: HashMap<TypeVar, InferenceType> = scheme.quantified
+            .iter()
+            .map(|var| (var.clone(), InferenceType::Variable(self.fresh_var())))
+            .collect();
+        Self::substitute(&scheme.body, &mapping)
+    }
+    
+    fn substitute(t: &InferenceType, mapping: &HashMap<TypeVar, InferenceType>) -> InferenceType {
+        match t {
+            InferenceType::Variable(var) => mapping.get(var).cloned().unwrap_or_else(|| t.clone()),
+            InferenceType::Function(params, ret) => InferenceType::Function(
+                params.iter().map(|p| Self::substitute(p, mapping)).collect(),
+                Box::new(Self::substitute(ret, mapping)),
+            ),
+            InferenceType::Concrete(_) => t.clone(),
+        }
+    }
+    
+    /// Record the generalized signature of a function
+    pub fn define_scheme(&mut self, name: &str, scheme: TypeScheme) {
+        self.schemes.insert(name.to_string(), scheme);
+    }
+    
+    /// Look up the signature of a previously inferred function
+    pub fn get_scheme(&self, name: &str) -> Option<&TypeScheme> {
+        self.schemes.get(name)
+    }
+    
     /// Add a substitution from a type variable to another type
     pub fn add_substitution(&mut self, var: TypeVar, target: InferenceType) {
         // Ensure we don't create a cycle by resolving the target first
@@ -73,18 +244,39 @@ impl TypeEnvironment {
 
 impl<'a> TypeInferer<'a> {
     pub fn new(symbol_table: &'a mut SymbolTable) -> Self {
+        Self::with_environment(symbol_table, TypeEnvironment::new())
+    }
+    
+    /// Create an inferer that continues from a previously built environment,
+    /// so function signatures inferred earlier are visible at call sites
+    pub fn with_environment(symbol_table: &'a mut SymbolTable, env: TypeEnvironment) -> Self {
         Self {
-            env: TypeEnvironment::new(),
+            env,
             symbol_table,
             errors: Vec::new(),
+            constraints: Vec::new(),
+            locals: Vec::new(),
+            function_stack: Vec::new(),
+            type_errors: Vec::new(),
+            type_params: Vec::new(),
         }
     }
     
+    /// Give back the environment so a later inferer can reuse it
+    pub fn into_environment(self) -> TypeEnvironment {
+        self.env
+    }
+    
     /// Get any errors found during type inference
     pub fn get_errors(&self) -> &[String] {
         &self.errors
     }
     
+    /// Get the unification failures found while solving function constraints
+    pub fn get_type_errors(&self) -> &[ResolutionError] {
+        &self.type_errors
+    }
+    
     /// Unify two types, updating the type environment
     pub fn unify(&mut self, t1: InferenceType, t2: InferenceType, span: Span) -> Result<(), String> {
         let t1 = self.env.resolve(&t1);
@@ -100,25 +292,67 @@ impl<'a> TypeInferer<'a> {
                 }
             },
             
+            (InferenceType::Variable(v1), InferenceType::Variable(v2)) if v1 == v2 => Ok(()),
+            
             // If one is a type variable, bind it to the other type
-            (InferenceType::Variable(var), other) => {
+            (InferenceType::Variable(var), other) | (other, InferenceType::Variable(var)) => {
+                if self.env.occurs(&var, &other) {
+                    return Err(format!("Infinite type: {} occurs in {}", InferenceType::Variable(var), other));
+                }
+                // A literal's values must fit the type it is bound to
+                if let Some((min, max)) = self.env.literals.get(&var).copied() {
+                    match &other {
+                        InferenceType::Concrete(t) if t.literal_fits(min) && t.literal_fits(max) => {},
+                        InferenceType::Variable(other_var) => {
+                            let range = match self.env.literals.get(other_var) {
+                                Some(&(other_min, other_max)) => (min.min(other_min), max.max(other_max)),
+                                None => (min, max),
+                            };
+                            self.env.literals.insert(other_var.clone(), range);
+                        },
+                        _ => return Err(format!("Type mismatch: {:?} is incompatible with {}", Type::Int, other)),
+                    }
+                }
                 self.env.add_substitution(var, other);
                 Ok(())
             },
             
-            (other, InferenceType::Variable(var)) => {
-                self.env.add_substitution(var, other);
-                Ok(())
+            // Functions unify parameter-wise and on their return types
+            (InferenceType::Function(p1, r1), InferenceType::Function(p2, r2)) => {
+                if p1.len() != p2.len() {
+                    return Err(format!("Type mismatch: functions take {} and {} arguments", p1.len(), p2.len()));
+                }
+                for (a, b) in p1.into_iter().zip(p2) {
+                    self.unify(a, b, span.clone())?;
+                }
+                self.unify(*r1, *r2, span)
             },
+            
+            (t1, t2) => Err(format!("Type mismatch: {} is incompatible with {}", t1, t2)),
+        }
+    }
+    
+    /// Check that a value of type `found` may be used where `expected` is:
+    /// numbers widen implicitly, everything else unifies
+    pub fn convert(&mut self, expected: InferenceType, found: InferenceType, span: Span) -> Result<(), String> {
+        match (self.env.resolve(&expected), self.env.resolve(&found)) {
+            (InferenceType::Concrete(to), InferenceType::Concrete(from)) => {
+                if from.can_widen_to(&to) {
+                    Ok(())
+                } else {
+                    Err(format!("Type mismatch: {:?} is incompatible with {:?}", to, from))
+                }
+            },
+            (expected, found) => self.unify(expected, found, span),
         }
     }
     
     /// Infer the type of an expression
-    pub fn infer_expression(&mut self, expr: &Expression, span: Span) -> InferenceType {
+    pub fn infer_expression(&mut self, expr: &Expression) -> InferenceType {
         match expr {
-            Expression::Number(_) => InferenceType::Concrete(Type::Int),
+            Expression::Number(_, _) => InferenceType::Concrete(Type::Int),
             
-            Expression::Variable(name) => {
+            Expression::Variable(name, span) => {
                 // Look up the variable in the symbol table
                 if let Some(symbol) = self.symbol_table.resolve(name, span.clone()) {
                     InferenceType::Concrete(symbol.typ.base_type.clone())
@@ -129,15 +363,25 @@ impl<'a> TypeInferer<'a> {
                 }
             },
             
-            Expression::Binary { left, operator, right } => {
+            Expression::Binary { left, operator, right, span } => {
                 // Infer types of both operands
-                let left_type = self.infer_expression(left, span.clone());
-                let right_type = self.infer_expression(right, span.clone());
+                let left_type = self.infer_expression(left);
+                let right_type = self.infer_expression(right);
                 
-                // Unify the operand types
-                if let Err(err) = self.unify(left_type.clone(), right_type.clone(), span.clone()) {
-                    self.errors.push(format!("In binary expression: {}", err));
-                }
+                // Numeric operands promote to a common type; others unify
+                let promoted = match (self.env.resolve(&left_type), self.env.resolve(&right_type)) {
+                    (InferenceType::Concrete(left), InferenceType::Concrete(right)) => left.promote(&right),
+                    _ => None,
+                };
+                let left_type = match promoted {
+                    Some(common) => InferenceType::Concrete(common),
+                    None => {
+                        if let Err(err) = self.unify(left_type.clone(), right_type, span.clone()) {
+                            self.errors.push(format!("In binary expression: {}", err));
+                        }
+                        left_type
+                    },
+                };
                 
                 // Handle specific operator types
                 match operator {
@@ -154,51 +398,58 @@ impl<'a> TypeInferer<'a> {
                 }
             },
             
-            Expression::Call { function, arguments } => {
-                // Function calls are complex - we'd need to look up the function signature
-                // For now, just use a placeholder type
-                // In a full implementation, we would:
-                // 1. Look up the function in a function table
-                // 2. Check argument types against parameter types
-                // 3. Return the function's return type
+            Expression::Call { function, arguments, span } => {
+                let argument_types: Vec<InferenceType> = arguments.iter()
+                    .map(|arg| self.infer_expression(arg))
+                    .collect();
                 
-                // Placeholder implementation
-                for arg in arguments {
-                    let _ = self.infer_expression(arg, span.clone());
+                // Use the inferred signature when the function is already known
+                if let Some(scheme) = self.env.get_scheme(function).cloned() {
+                    let result = InferenceType::Variable(self.env.fresh_var());
+                    let call_type = InferenceType::Function(argument_types, Box::new(result.clone()));
+                    let callee = self.env.instantiate(&scheme);
+                    if let Err(err) = self.unify(callee, call_type, span.clone()) {
+                        self.errors.push(format!("In call to '{}': {}", function, err));
+                    }
+                    return self.env.resolve(&result);
                 }
                 
-                // For demo purposes, assume all functions return Int
-                // In a real implementation, we would look up the function signature
+                // Unknown functions are assumed to return Int
                 InferenceType::Concrete(Type::Int)
             },
             
-            Expression::Peak(expr) => {
+            Expression::Peak(expr, _) => {
                 // Peak returns the same type as its operand but with read permission
-                self.infer_expression(expr, span)
+                self.infer_expression(expr)
             },
             
-            Expression::Clone(expr) => {
+            Expression::Clone(expr, _) => {
                 // Clone returns the same type as its operand
-                self.infer_expression(expr, span)
+                self.infer_expression(expr)
+            },
+            
+            Expression::Consume(expr, _) => {
+                // Consume moves the value out, keeping its type
+                self.infer_expression(expr)
             },
         }
     }
     
     /// Infer the return type of a function based on its body
-    pub fn infer_function_return_type(&mut self, body: &[Statement], span: Span) -> Option<Type> {
+    pub fn infer_function_return_type(&mut self, body: &[Statement]) -> Option<Type> {
         // Look for return statements
         for stmt in body {
             match stmt {
-                Statement::Return(expr) => {
-                    let expr_type = self.infer_expression(expr, span.clone());
-                    match self.env.resolve(&expr_type) {
-                        InferenceType::Concrete(t) => return Some(t),
-                        _ => {} // Continue looking for more concrete returns
+                Statement::Return(expr, _) => {
+                    let expr_type = self.infer_expression(expr);
+                    // Otherwise keep looking for a more concrete return
+                    if let InferenceType::Concrete(t) = self.env.resolve(&expr_type) {
+                        return Some(t);
                     }
                 },
-                Statement::Block(inner_statements) => {
+                Statement::Block(inner_statements, _) | Statement::AtomicBlock(inner_statements, _) => {
                     // Recursively check blocks
-                    if let Some(ret_type) = self.infer_function_return_type(inner_statements, span.clone()) {
+                    if let Some(ret_type) = self.infer_function_return_type(inner_statements) {
                         return Some(ret_type);
                     }
                 },
@@ -208,16 +459,10 @@ impl<'a> TypeInferer<'a> {
         
         // If we reach here without finding a return, check the last statement
         // for an implicit return
-        if let Some(last) = body.last() {
-            match last {
-                Statement::Expression(expr) => {
-                    let expr_type = self.infer_expression(expr, span);
-                    match self.env.resolve(&expr_type) {
-                        InferenceType::Concrete(t) => return Some(t),
-                        _ => {}
-                    }
-                },
-                _ => {}
+        if let Some(Statement::Expression(expr)) = body.last() {
+            let expr_type = self.infer_expression(expr);
+            if let InferenceType::Concrete(t) = self.env.resolve(&expr_type) {
+                return Some(t);
             }
         }
         
@@ -226,110 +471,116 @@ impl<'a> TypeInferer<'a> {
     }
     
     /// Process a statement for type inference
-    pub fn infer_statement(&mut self, stmt: &Statement, span: Span) -> Result<(), String> {
+    pub fn infer_statement(&mut self, stmt: &Statement) -> Result<(), String> {
         match stmt {
-            Statement::Declaration { name, typ, initializer } => {
+            Statement::Declaration { name, typ, initializer, .. } => {
                 if let Some(expr) = initializer {
-                    let expr_type = self.infer_expression(expr, span.clone());
+                    let expr_type = self.infer_expression(expr);
                     
                     // If the declaration has an explicit type, unify with the expression type
                     let decl_type = InferenceType::Concrete(typ.base_type.clone());
-                    if let Err(err) = self.unify(decl_type, expr_type, span) {
+                    if let Err(err) = self.convert(decl_type, expr_type, expr.span().clone()) {
                         self.errors.push(format!("In declaration of '{}': {}", name, err));
                     }
                 }
                 Ok(())
             },
             
-            Statement::Assignment { target, value, target_type } => {
-                let expr_type = self.infer_expression(value, span.clone());
+            Statement::Assignment { target, value, target_type, .. } => {
+                let expr_type = self.infer_expression(value);
                 let target_concrete_type = InferenceType::Concrete(target_type.base_type.clone());
                 
-                if let Err(err) = self.unify(target_concrete_type, expr_type, span) {
+                if let Err(err) = self.convert(target_concrete_type, expr_type, value.span().clone()) {
                     self.errors.push(format!("In assignment to '{}': {}", target, err));
                 }
                 Ok(())
             },
             
             Statement::Expression(expr) => {
-                let _ = self.infer_expression(expr, span);
+                let _ = self.infer_expression(expr);
                 Ok(())
             },
             
-            Statement::Print(expr) => {
-                let _ = self.infer_expression(expr, span);
+            Statement::Print(expr, _) => {
+                let _ = self.infer_expression(expr);
                 Ok(())
             },
             
-            Statement::Return(expr) => {
+            Statement::Return(expr, _) => {
                 // For returns, we would ideally check against the function's declared return type
                 // This would require more context than we currently have
-                let _ = self.infer_expression(expr, span);
+                let _ = self.infer_expression(expr);
                 Ok(())
             },
             
-            Statement::Block(statements) => {
+            Statement::Block(statements, _) => {
                 for stmt in statements {
-                    self.infer_statement(stmt, span.clone())?;
+                    self.infer_statement(stmt)?;
                 }
                 Ok(())
             },
             
-            Statement::Function { params, body, return_type, .. } => {
-                // First check parameters
-                for (name, param_type) in params {
-                    // Record parameter types in environment
-                    // (This would be more complex in a real implementation)
-                }
+            Statement::Function { name, params, body, return_type, span, .. } => {
+                let reported = self.type_errors.len();
+                self.infer_function(name, params, body, return_type.as_ref(), span.clone());
                 
-                // Process function body
-                for stmt in body {
-                    self.infer_statement(stmt, span.clone())?;
-                }
-                
-                // If return type isn't explicitly specified, try to infer it
-                if return_type.is_none() {
-                    if let Some(inferred_return) = self.infer_function_return_type(body, span) {
-                        println!("Inferred return type: {:?}", inferred_return);
-                        // We would use this inferred return type for type checking
+                for error in &self.type_errors[reported..] {
+                    match error {
+                        ResolutionError::TypeMismatch { expected, found, context, .. } => {
+                            self.errors.push(format!("Type mismatch {}: expected {}, found {}", context, expected, found));
+                        },
+                        ResolutionError::InvalidOperands { operator, left, right, context, .. } => {
+                            self.errors.push(format!("Cannot {} {} and {} {}", operator.operation_verb(), left, right, context));
+                        },
+                        _ => {}
                     }
                 }
-                
                 Ok(())
             },
             
             Statement::Actor { state, methods, behaviors, .. } => {
                 // Process actor components
                 for stmt in state {
-                    self.infer_statement(stmt, span.clone())?;
+                    self.infer_statement(stmt)?;
                 }
                 
                 for method in methods {
-                    self.infer_statement(method, span.clone())?;
+                    self.infer_statement(method)?;
                 }
                 
                 for behavior in behaviors {
-                    self.infer_statement(behavior, span.clone())?;
+                    self.infer_statement(behavior)?;
                 }
                 
                 Ok(())
             },
             
-            Statement::AtomicBlock(statements) => {
+            Statement::AtomicBlock(statements, _) => {
                 for stmt in statements {
-                    self.infer_statement(stmt, span.clone())?;
+                    self.infer_statement(stmt)?;
+                }
+                Ok(())
+            },
+            
+            Statement::While { condition, body, .. } => {
+                let condition_type = self.infer_expression(condition);
+                if let Err(err) = self.unify(InferenceType::Concrete(Type::Bool), condition_type, condition.span().clone()) {
+                    self.errors.push(format!("In loop condition: {}", err));
+                }
+                for stmt in body {
+                    self.infer_statement(stmt)?;
                 }
                 Ok(())
             },
+            
+            Statement::Break(_) | Statement::Continue(_) => Ok(()),
         }
     }
     
     /// Infer types for a whole program (list of statements)
     pub fn infer_program(&mut self, statements: &[Statement]) -> Result<(), Vec<String>> {
         for stmt in statements {
-            // Use a default span if we don't have a better one
-            let span = Span::point(0, 0);
-            if let Err(err) = self.infer_statement(stmt, span) {
+            if let Err(err) = self.infer_statement(stmt) {
                 self.errors.push(err);
             }
         }
@@ -342,17 +593,313 @@ impl<'a> TypeInferer<'a> {
     }
     
     /// Try to infer the type of a variable declaration without an explicit type
-    pub fn infer_variable_declaration_type(&mut self, initializer: &Expression, span: Span) -> Type {
-        let inferred = self.infer_expression(initializer, span);
+    pub fn infer_variable_declaration_type(&mut self, initializer: &Expression) -> Type {
+        let inferred = self.infer_expression(initializer);
         match self.env.resolve(&inferred) {
             InferenceType::Concrete(t) => t,
-            InferenceType::Variable(_) => {
+            InferenceType::Variable(_) | InferenceType::Function(..) => {
                 // If we couldn't infer a concrete type, default to Int
                 // In a real system, we might want to report an error here
                 Type::Int
             }
         }
     }
+    
+    /// Infer a whole function by collecting equality constraints over its
+    /// body and solving them together. Returns the (possibly inferred)
+    /// return type; unification failures are available via `get_type_errors`.
+    pub fn infer_function(
+        &mut self,
+        name: &str,
+        params: &[(String, PermissionedType)],
+        body: &[Statement],
+        return_type: Option<&PermissionedType>,
+        span: Span,
+    ) -> Type {
+        let outer_constraints = std::mem::take(&mut self.constraints);
+        // Literals from here on belong to this function
+        let first_var = self.env.fresh_var();
+        
+        // Generic parameters become type variables, so the signature is
+        // generalized over them below
+        self.type_params.push(HashMap::new());
+        let param_types: Vec<InferenceType> = params.iter()
+            .map(|(_, typ)| self.annotation(&typ.base_type))
+            .collect();
+        let ret = InferenceType::Variable(self.env.fresh_var());
+        if let Some(annotated) = return_type {
+            let annotated = self.annotation(&annotated.base_type);
+            let _ = self.unify(ret.clone(), annotated, span.clone());
+        }
+        
+        // A monomorphic signature lets recursive calls constrain the function itself
+        let signature = InferenceType::Function(param_types.clone(), Box::new(ret.clone()));
+        self.env.define_scheme(name, TypeScheme { quantified: Vec::new(), body: signature.clone() });
+        
+        self.locals.push(params.iter()
+            .map(|(param, _)| param.clone())
+            .zip(param_types)
+            .collect());
+        self.function_stack.push((name.to_string(), ret.clone()));
+        
+        for stmt in body {
+            self.collect_statement(stmt);
+        }
+        
+        self.function_stack.pop();
+        self.locals.pop();
+        self.type_params.pop();
+        self.solve();
+        self.env.default_literals(&first_var);
+        self.constraints = outer_constraints;
+        
+        // Generalize over whatever the enclosing scopes don't pin down
+        let env_vars: Vec<TypeVar> = self.locals.iter()
+            .flat_map(|scope| scope.values())
+            .flat_map(|t| self.env.free_vars(t))
+            .collect();
+        let scheme = self.env.generalize(&signature, &env_vars);
+        self.env.define_scheme(name, scheme);
+        
+        match self.env.resolve(&ret) {
+            InferenceType::Concrete(t) => t,
+            // No return constrained the type, so the function returns nothing
+            _ => Type::Unit,
+        }
+    }
+    
+    /// Unify every collected constraint in order, reporting each failure at
+    /// the span of the constraint that introduced it
+    fn solve(&mut self) {
+        let mut solved_operands = Vec::new();
+        
+        for constraint in std::mem::take(&mut self.constraints) {
+            let expected = self.env.resolve(&constraint.expected);
+            let found = self.env.resolve(&constraint.found);
+            
+            let unified = match &constraint.operator {
+                Some(_) => self.promote_operands(&expected, &found, constraint.result.as_ref(), &constraint.span),
+                None => self.convert(expected.clone(), found.clone(), constraint.span.clone()).is_ok(),
+            };
+            let expected = self.env.resolve_for_display(&expected);
+            let found = self.env.resolve_for_display(&found);
+            match (unified, constraint.operator) {
+                (true, Some(operator)) => solved_operands.push((operator, constraint.expected, constraint.span, constraint.context)),
+                (true, None) => {},
+                (false, Some(operator)) => self.type_errors.push(ResolutionError::InvalidOperands {
+                    operator,
+                    left: expected.to_string(),
+                    right: found.to_string(),
+                    span: constraint.span,
+                    context: constraint.context,
+                }),
+                (false, None) => self.type_errors.push(ResolutionError::TypeMismatch {
+                    expected: expected.to_string(),
+                    found: found.to_string(),
+                    span: constraint.span,
+                    context: constraint.context,
+                }),
+            }
+        }
+        
+        // Operands agree; arithmetic and ordering still need a numeric type
+        for (operator, operand, span, context) in solved_operands {
+            if let InferenceType::Concrete(t) = self.env.resolve(&operand) {
+                let needs_numeric = operator.is_arithmetic() || operator.is_ordering();
+                if needs_numeric && !t.is_numeric() {
+                    self.type_errors.push(ResolutionError::InvalidOperands {
+                        operator,
+                        left: format!("{:?}", t),
+                        right: format!("{:?}", t),
+                        span,
+                        context,
+                    });
+                }
+            }
+        }
+    }
+    
+    /// Bring the operands of a binary expression to a common type: numbers
+    /// promote, anything else must unify. An arithmetic expression takes
+    /// the common type, or the left operand's when there is none.
+    fn promote_operands(&mut self, left: &InferenceType, right: &InferenceType, result: Option<&InferenceType>, span: &Span) -> bool {
+        let (common, promoted) = match (left, right) {
+            (InferenceType::Concrete(left_type), InferenceType::Concrete(right_type)) => match left_type.promote(right_type) {
+                Some(common) => (InferenceType::Concrete(common), true),
+                None => (left.clone(), false),
+            },
+            _ => (left.clone(), self.unify(left.clone(), right.clone(), span.clone()).is_ok()),
+        };
+        if let Some(result) = result {
+            let _ = self.unify(result.clone(), common, span.clone());
+        }
+        promoted
+    }
+    
+    fn constrain(&mut self, expected: InferenceType, found: InferenceType, span: Span, context: String) {
+        self.constraints.push(Constraint { expected, found, span, context, operator: None, result: None });
+    }
+    
+    fn current_function_name(&self) -> String {
+        self.function_stack.last()
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default()
+    }
+    
+    fn bind_local(&mut self, name: &str, t: InferenceType) {
+        if let Some(scope) = self.locals.last_mut() {
+            scope.insert(name.to_string(), t);
+        }
+    }
+    
+    /// The inference type of an annotation, with generic parameters of the
+    /// enclosing functions replaced by their type variables
+    fn annotation(&mut self, typ: &Type) -> InferenceType {
+        let Type::Param(name) = typ else {
+            return InferenceType::Concrete(typ.clone());
+        };
+        if let Some(var) = self.type_params.iter().rev().find_map(|params| params.get(name)) {
+            return var.clone();
+        }
+        let var = InferenceType::Variable(self.env.fresh_var());
+        if let Some(params) = self.type_params.last_mut() {
+            params.insert(name.clone(), var.clone());
+        }
+        var
+    }
+    
+    fn lookup_local(&self, name: &str) -> Option<InferenceType> {
+        self.locals.iter().rev().find_map(|scope| scope.get(name).cloned())
+    }
+    
+    /// Collect constraints for a statement inside a function body. Each
+    /// constraint carries the span of the expression whose type it checks,
+    /// so a failure points at that expression rather than the function.
+    fn collect_statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Declaration { name, typ, initializer, .. } => {
+                let declared = self.annotation(&typ.base_type);
+                if let Some(expr) = initializer {
+                    let found = self.collect_expression(expr);
+                    self.constrain(declared.clone(), found, expr.span().clone(), format!("in declaration of '{}'", name));
+                }
+                self.bind_local(name, declared);
+            },
+            
+            Statement::Assignment { target, value, target_type, .. } => {
+                let found = self.collect_expression(value);
+                let expected = self.annotation(&target_type.base_type);
+                self.constrain(expected, found, value.span().clone(), format!("in assignment to '{}'", target));
+            },
+            
+            Statement::Expression(expr) | Statement::Print(expr, _) => {
+                let _ = self.collect_expression(expr);
+            },
+            
+            Statement::Return(expr, _) => {
+                let found = self.collect_expression(expr);
+                if let Some((name, ret)) = self.function_stack.last().cloned() {
+                    self.constrain(ret, found, expr.span().clone(), format!("in return value of function '{}'", name));
+                }
+            },
+            
+            Statement::Block(statements, _) | Statement::AtomicBlock(statements, _) => {
+                self.locals.push(HashMap::new());
+                for stmt in statements {
+                    self.collect_statement(stmt);
+                }
+                self.locals.pop();
+            },
+            
+            Statement::While { condition, body, .. } => {
+                let found = self.collect_expression(condition);
+                self.constrain(InferenceType::Concrete(Type::Bool), found, condition.span().clone(), "in loop condition".to_string());
+                self.locals.push(HashMap::new());
+                for stmt in body {
+                    self.collect_statement(stmt);
+                }
+                self.locals.pop();
+            },
+            
+            Statement::Break(_) | Statement::Continue(_) => {},
+            
+            Statement::Function { name, params, body, return_type, span, .. } => {
+                let _ = self.infer_function(name, params, body, return_type.as_ref(), span.clone());
+            },
+            
+            Statement::Actor { state, methods, behaviors, .. } => {
+                for stmt in state.iter().chain(methods).chain(behaviors) {
+                    self.collect_statement(stmt);
+                }
+            },
+        }
+    }
+    
+    /// Collect constraints for an expression and return its (possibly
+    /// unresolved) type
+    fn collect_expression(&mut self, expr: &Expression) -> InferenceType {
+        match expr {
+            Expression::Number(value, _) => InferenceType::Variable(self.env.fresh_literal(*value)),
+            
+            Expression::Variable(name, _) => {
+                if let Some(t) = self.lookup_local(name) {
+                    return t;
+                }
+                match self.symbol_table.lookup(name) {
+                    Some(symbol) => InferenceType::Concrete(symbol.typ.base_type.clone()),
+                    // Undefined names are reported by the parser; keep solving
+                    None => InferenceType::Variable(self.env.fresh_var()),
+                }
+            },
+            
+            Expression::Binary { left, operator, right, span } => {
+                let left_type = self.collect_expression(left);
+                let right_type = self.collect_expression(right);
+                let comparison = operator.is_ordering() || operator.is_equality();
+                let result = if comparison {
+                    InferenceType::Concrete(Type::Bool)
+                } else {
+                    InferenceType::Variable(self.env.fresh_var())
+                };
+                self.constraints.push(Constraint {
+                    expected: left_type,
+                    found: right_type,
+                    span: span.clone(),
+                    context: format!("in function '{}'", self.current_function_name()),
+                    operator: Some(operator.clone()),
+                    result: Some(result.clone()).filter(|_| !comparison),
+                });
+                result
+            },
+            
+            Expression::Call { function, arguments, .. } => {
+                let argument_types: Vec<InferenceType> = arguments.iter()
+                    .map(|arg| self.collect_expression(arg))
+                    .collect();
+                
+                let scheme = match self.env.get_scheme(function) {
+                    Some(scheme) => scheme.clone(),
+                    None => return InferenceType::Variable(self.env.fresh_var()),
+                };
+                
+                match self.env.instantiate(&scheme) {
+                    InferenceType::Function(params, ret) => {
+                        if params.len() == argument_types.len() {
+                            let argument_spans = arguments.iter().map(|arg| arg.span().clone());
+                            for (i, ((param, arg), arg_span)) in params.into_iter().zip(argument_types).zip(argument_spans).enumerate() {
+                                self.constrain(param, arg, arg_span,
+                                    format!("in argument {} of call to '{}'", i + 1, function));
+                            }
+                        }
+                        *ret
+                    },
+                    other => other,
+                }
+            },
+            
+            Expression::Peak(expr, _) | Expression::Clone(expr, _) | Expression::Consume(expr, _) => self.collect_expression(expr),
+        }
+    }
 }
 
 // Type utilities for working with the AST
@@ -362,11 +909,10 @@ pub trait TypeInferenceExt {
 
 impl TypeInferenceExt for Expression {
     fn infer_type(&self, inferer: &mut TypeInferer) -> Type {
-        let span = Span::point(0, 0); // Default span
-        let inferred = inferer.infer_expression(self, span);
+        let inferred = inferer.infer_expression(self);
         match inferer.env.resolve(&inferred) {
             InferenceType::Concrete(t) => t,
-            InferenceType::Variable(_) => Type::Int, // Default
+            InferenceType::Variable(_) | InferenceType::Function(..) => Type::Int, // Default
         }
     }
 }
@@ -1,4 +1,8 @@
-#[derive(Debug, Clone, PartialEq)]
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Type {
     Int,    // Platform default integer (replaces I64)
     Int8,   // 8-bit signed integer
@@ -16,10 +20,13 @@ pub enum Type {
     Bool,   // Boolean type
     String, // String type
     Unit,   // Unit type (for functions that return nothing)
+    Param(String), // Generic type parameter, replaced by a concrete type before MIR
 }
 
-impl Type {
-    pub fn from_str(s: &str) -> Result<Self, String> {
+impl FromStr for Type {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "int" => Ok(Type::Int),
             "int8" => Ok(Type::Int8),
@@ -42,7 +49,108 @@ impl Type {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]  // Added Clone
+impl Type {
+    /// The numeric family of this type, if it is numeric
+    pub fn numeric_kind(&self) -> Option<NumericKind> {
+        match self {
+            Type::Int | Type::Int8 | Type::Int16 | Type::Int32 | Type::Int64 => Some(NumericKind::Signed),
+            Type::UInt | Type::UInt8 | Type::UInt16 | Type::UInt32 | Type::UInt64 => Some(NumericKind::Unsigned),
+            Type::Float | Type::Float32 | Type::Float64 => Some(NumericKind::Float),
+            _ => None,
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        self.numeric_kind().is_some()
+    }
+
+    /// Whether this is a generic type parameter
+    pub fn is_param(&self) -> bool {
+        matches!(self, Type::Param(_))
+    }
+
+    /// Width in bits of a numeric type. The platform defaults `Int`, `UInt`
+    /// and `Float` are 64 bits wide.
+    pub fn bit_width(&self) -> Option<u32> {
+        match self {
+            Type::Int8 | Type::UInt8 => Some(8),
+            Type::Int16 | Type::UInt16 => Some(16),
+            Type::Int32 | Type::UInt32 | Type::Float32 => Some(32),
+            Type::Int | Type::Int64 | Type::UInt | Type::UInt64 |
+            Type::Float | Type::Float64 => Some(64),
+            _ => None,
+        }
+    }
+
+    /// Whether a value of this type converts implicitly to `target`.
+    ///
+    /// Integers widen within the same signedness, floats widen to wider
+    /// floats, and integers convert to a float only when the float's
+    /// mantissa holds every value (up to 16 bits into `Float32`, up to 32
+    /// bits into `Float64`). Signed and unsigned never mix implicitly.
+    pub fn can_widen_to(&self, target: &Type) -> bool {
+        if self == target {
+            return true;
+        }
+
+        let (from_kind, to_kind) = match (self.numeric_kind(), target.numeric_kind()) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return false,
+        };
+        let (from_width, to_width) = match (self.bit_width(), target.bit_width()) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return false,
+        };
+
+        match (from_kind, to_kind) {
+            (NumericKind::Signed, NumericKind::Signed) |
+            (NumericKind::Unsigned, NumericKind::Unsigned) |
+            (NumericKind::Float, NumericKind::Float) => from_width <= to_width,
+            (NumericKind::Signed, NumericKind::Float) |
+            (NumericKind::Unsigned, NumericKind::Float) => from_width * 2 <= to_width,
+            _ => false,
+        }
+    }
+
+    /// Whether an integer literal is representable in this type. Literals
+    /// take any numeric type whose range holds them.
+    pub fn literal_fits(&self, value: i64) -> bool {
+        match self {
+            Type::Int8 => i8::try_from(value).is_ok(),
+            Type::Int16 => i16::try_from(value).is_ok(),
+            Type::Int32 => i32::try_from(value).is_ok(),
+            Type::Int | Type::Int64 => true,
+            Type::UInt8 => u8::try_from(value).is_ok(),
+            Type::UInt16 => u16::try_from(value).is_ok(),
+            Type::UInt32 => u32::try_from(value).is_ok(),
+            Type::UInt | Type::UInt64 => value >= 0,
+            Type::Float | Type::Float32 | Type::Float64 => true,
+            _ => false,
+        }
+    }
+
+    /// The common type both operands of a binary expression are promoted to,
+    /// or `None` when one of them would need an explicit cast
+    pub fn promote(&self, other: &Type) -> Option<Type> {
+        if other.can_widen_to(self) {
+            Some(self.clone())
+        } else if self.can_widen_to(other) {
+            Some(other.clone())
+        } else {
+            None
+        }
+    }
+}
+
+/// Families of numeric types used by the promotion rules
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumericKind {
+    Signed,
+    Unsigned,
+    Float,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]  // Added Clone
 pub enum Permission {
     Read,
     Write,
@@ -0,0 +1,193 @@
+//! Validation of atomic blocks
+//!
+//! An `atomic { }` block coordinates access to state that other behaviors
+//! share, so inside it only variables with shareable permissions (`reads`
+//! or `writes`) may be touched; anything else is exclusive to its owner and
+//! needs no atomicity. Variables declared inside the block are its own and
+//! always allowed. Sending a message cannot be part of an atomic unit, so
+//! calls to behaviors, and to functions that end up calling one, are
+//! rejected as well.
+
+use crate::hir::permissions::PermissionError;
+use crate::hir::types::*;
+use crate::hir::visitor::{self, HirVisitor};
+use front_end::types::Permission;
+use std::collections::{HashMap, HashSet};
+
+/// Check the variables and calls used inside every atomic block
+pub fn check_atomic_blocks(program: &HirProgram) -> Vec<PermissionError> {
+    let mut checker = AtomicChecker {
+        unsafe_functions: unsafe_functions(program),
+        scopes: vec![HashMap::new()],
+        atomic_start: None,
+        reported: HashSet::new(),
+        errors: Vec::new(),
+    };
+    for stmt in &program.statements {
+        checker.visit_statement(stmt);
+    }
+    checker.errors
+}
+
+/// Functions that cannot be called inside an atomic block, with the
+/// behavior each of them sends to. A behavior sends to itself.
+fn unsafe_functions(program: &HirProgram) -> HashMap<String, String> {
+    #[derive(Default)]
+    struct CallCollector {
+        calls: Vec<String>,
+    }
+
+    impl HirVisitor for CallCollector {
+        fn visit_expression(&mut self, expr: &HirExpression) {
+            if let HirExpression::Call { function, .. } = expr {
+                self.calls.push(function.clone());
+            }
+            visitor::walk_expression(self, expr);
+        }
+    }
+
+    let mut calls = Vec::new();
+    let mut unsafe_functions = HashMap::new();
+    for stmt in &program.statements {
+        if let HirStatement::Function(func) = stmt {
+            if func.is_behavior {
+                unsafe_functions.insert(func.name.clone(), func.name.clone());
+            } else {
+                let mut collector = CallCollector::default();
+                for stmt in &func.body {
+                    collector.visit_statement(stmt);
+                }
+                calls.push((func.name.clone(), collector.calls));
+            }
+        }
+    }
+
+    // A function calling an unsafe function is unsafe too
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (name, callees) in &calls {
+            if unsafe_functions.contains_key(name) {
+                continue;
+            }
+            let target = callees.iter().find_map(|callee| unsafe_functions.get(callee)).cloned();
+            if let Some(target) = target {
+                unsafe_functions.insert(name.clone(), target);
+                changed = true;
+            }
+        }
+    }
+    unsafe_functions
+}
+
+/// Whether a variable may be touched inside an atomic block
+fn is_shareable(permissions: &[Permission]) -> bool {
+    permissions.is_empty()
+        || permissions.iter().any(|p| matches!(p, Permission::Reads | Permission::Writes))
+}
+
+struct AtomicChecker {
+    unsafe_functions: HashMap<String, String>,
+
+    /// Permissions of the variables in scope, innermost scope last
+    scopes: Vec<HashMap<String, Vec<Permission>>>,
+
+    /// Index of the first scope belonging to the outermost enclosing atomic
+    /// block, if there is one
+    atomic_start: Option<usize>,
+
+    /// Variables already reported for the current atomic block
+    reported: HashSet<String>,
+
+    errors: Vec<PermissionError>,
+}
+
+impl AtomicChecker {
+    fn declare(&mut self, name: &str, permissions: &[Permission]) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), permissions.to_vec());
+        }
+    }
+
+    /// Check a use of a variable against the enclosing atomic block
+    fn touch(&mut self, name: &str, location: Option<SourceLocation>) {
+        let Some(start) = self.atomic_start else {
+            return;
+        };
+        let found = self.scopes.iter().enumerate().rev()
+            .find_map(|(depth, scope)| scope.get(name).map(|perms| (depth, perms)));
+        let Some((depth, declared)) = found else {
+            return;
+        };
+
+        if depth < start && !is_shareable(declared) && self.reported.insert(name.to_string()) {
+            self.errors.push(PermissionError::AtomicNonShareable {
+                variable: name.to_string(),
+                declared: declared.clone(),
+                span: location,
+            });
+        }
+    }
+
+    /// Visit statements in a scope of their own
+    fn visit_scoped(&mut self, statements: &[HirStatement], scope: HashMap<String, Vec<Permission>>) {
+        self.scopes.push(scope);
+        for stmt in statements {
+            self.visit_statement(stmt);
+        }
+        self.scopes.pop();
+    }
+}
+
+impl HirVisitor for AtomicChecker {
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                visitor::walk_statement(self, stmt);
+                self.declare(&var.name, &var.permissions);
+            },
+            HirStatement::Assignment(assign) => {
+                visitor::walk_statement(self, stmt);
+                self.touch(&assign.target, stmt.location().copied());
+            },
+            HirStatement::Block(statements, _) => self.visit_scoped(statements, HashMap::new()),
+            HirStatement::Atomic(statements, _) => {
+                // Nested atomic blocks are part of the outermost one
+                let outer = self.atomic_start;
+                if outer.is_none() {
+                    self.atomic_start = Some(self.scopes.len());
+                    self.reported.clear();
+                }
+                self.visit_scoped(statements, HashMap::new());
+                self.atomic_start = outer;
+            },
+            HirStatement::Function(func) => {
+                // The body runs when the function is called, not here
+                let outer = self.atomic_start.take();
+                let params = func.parameters.iter()
+                    .map(|param| (param.name.clone(), param.permissions.clone()))
+                    .collect();
+                self.visit_scoped(&func.body, params);
+                self.atomic_start = outer;
+            },
+            _ => visitor::walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        match expr {
+            HirExpression::Variable(name, _, location, _) => self.touch(name, location.known().copied()),
+            HirExpression::Call { function, arguments, .. } if self.atomic_start.is_some() => {
+                if let Some(behavior) = self.unsafe_functions.get(function) {
+                    self.errors.push(PermissionError::AtomicUnsafeCall {
+                        function: function.clone(),
+                        behavior: behavior.clone(),
+                        span: arguments.iter().find_map(|arg| arg.location()).copied(),
+                    });
+                }
+            },
+            _ => {},
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
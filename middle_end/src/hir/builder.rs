@@ -0,0 +1,318 @@
+//! Fluent construction of HIR
+//!
+//! Builds programs directly, without source text, so analyses can be tested
+//! on exactly the HIR they need. Nodes get the default, unknown location.
+//!
+//! ```
+//! # use front_end::token::TokenType;
+//! # use front_end::types::{Permission, Type};
+//! use middle_end::hir::builder::*;
+//!
+//! let program = HirBuilder::new()
+//!     .function(FunctionBuilder::new("double")
+//!         .param(&[Permission::Reads], "x", Type::Int)
+//!         .returns(Type::Int)
+//!         .return_value(binary(var("x", Type::Int), TokenType::Star, int(2))))
+//!     .declare(&[Permission::Reads], "result", Type::Int, call("double", vec![int(21)], Type::Int))
+//!     .build();
+//! assert_eq!(program.statements.len(), 2);
+//! ```
+
+use crate::hir::types::*;
+use crate::hir::visitor::{self, HirVisitor};
+use front_end::token::TokenType;
+use front_end::types::{Permission, Type};
+
+/// Statements shared by programs, function bodies and blocks
+pub trait StatementBuilder: Sized {
+    /// Append a statement
+    fn statement(self, stmt: HirStatement) -> Self;
+
+    /// Declare a variable with an initial value
+    fn declare(self, permissions: &[Permission], name: &str, typ: Type, initializer: HirExpression) -> Self {
+        self.statement(HirStatement::Declaration(HirVariable {
+            name: name.to_string(),
+            typ,
+            permissions: permissions.to_vec(),
+            initializer: Some(initializer),
+            span: SourceLocation::default(),
+            actor: None,
+            symbol: None,
+        }))
+    }
+
+    fn assign(self, target: &str, value: HirExpression) -> Self {
+        self.statement(HirStatement::Assignment(HirAssignment {
+            target: target.to_string(),
+            value,
+            operator: None,
+            symbol: None,
+            span: SourceLocation::default(),
+        }))
+    }
+
+    fn print(self, expr: HirExpression) -> Self {
+        self.statement(HirStatement::Print(expr, SourceLocation::default()))
+    }
+
+    /// Call a function for its effects, discarding the result
+    fn call(self, function: &str, arguments: Vec<HirExpression>) -> Self {
+        self.statement(HirStatement::Expression(call(function, arguments, Type::Unit)))
+    }
+
+    fn return_value(self, expr: HirExpression) -> Self {
+        self.statement(HirStatement::Return(Some(expr), SourceLocation::default()))
+    }
+
+    fn return_nothing(self) -> Self {
+        self.statement(HirStatement::Return(None, SourceLocation::default()))
+    }
+
+    fn block(self, body: impl FnOnce(BlockBuilder) -> BlockBuilder) -> Self {
+        self.statement(HirStatement::Block(body(BlockBuilder::new()).statements, SourceLocation::default()))
+    }
+
+    fn atomic(self, body: impl FnOnce(BlockBuilder) -> BlockBuilder) -> Self {
+        self.statement(HirStatement::Atomic(body(BlockBuilder::new()).statements, SourceLocation::default()))
+    }
+
+    fn if_then(self, condition: HirExpression, then_branch: impl FnOnce(BlockBuilder) -> BlockBuilder) -> Self {
+        self.statement(HirStatement::If {
+            condition,
+            then_branch: Box::new(BlockBuilder::new().build_with(then_branch)),
+            else_branch: None,
+            span: SourceLocation::default(),
+        })
+    }
+
+    fn if_else(
+        self,
+        condition: HirExpression,
+        then_branch: impl FnOnce(BlockBuilder) -> BlockBuilder,
+        else_branch: impl FnOnce(BlockBuilder) -> BlockBuilder,
+    ) -> Self {
+        self.statement(HirStatement::If {
+            condition,
+            then_branch: Box::new(BlockBuilder::new().build_with(then_branch)),
+            else_branch: Some(Box::new(BlockBuilder::new().build_with(else_branch))),
+            span: SourceLocation::default(),
+        })
+    }
+
+    fn while_loop(self, condition: HirExpression, body: impl FnOnce(BlockBuilder) -> BlockBuilder) -> Self {
+        self.statement(HirStatement::While {
+            condition,
+            body: Box::new(BlockBuilder::new().build_with(body)),
+            span: SourceLocation::default(),
+        })
+    }
+
+    fn function(self, function: FunctionBuilder) -> Self {
+        self.statement(HirStatement::Function(function.build()))
+    }
+}
+
+/// Builds a whole program; `build` fills in the type information the
+/// converter would record
+pub struct HirBuilder {
+    program: HirProgram,
+}
+
+impl Default for HirBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HirBuilder {
+    pub fn new() -> Self {
+        Self { program: HirProgram::new() }
+    }
+
+    pub fn build(mut self) -> HirProgram {
+        let mut collector = TypeCollector { type_info: TypeInfo::default() };
+        collector.visit_program(&self.program);
+        self.program.type_info = collector.type_info;
+        self.program
+    }
+}
+
+impl StatementBuilder for HirBuilder {
+    fn statement(mut self, stmt: HirStatement) -> Self {
+        self.program.add_statement(stmt);
+        self
+    }
+}
+
+/// Builds a function or behavior, whose body is given with the
+/// `StatementBuilder` methods
+pub struct FunctionBuilder {
+    function: HirFunction,
+}
+
+impl FunctionBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            function: HirFunction {
+                name: name.to_string(),
+                type_params: Vec::new(),
+                parameters: Vec::new(),
+                body: Vec::new(),
+                return_type: None,
+                is_behavior: false,
+                actor: None,
+                span: SourceLocation::default(),
+            },
+        }
+    }
+
+    /// A behavior, declared with `on`
+    pub fn behavior(name: &str) -> Self {
+        let mut builder = Self::new(name);
+        builder.function.is_behavior = true;
+        builder
+    }
+
+    pub fn param(mut self, permissions: &[Permission], name: &str, typ: Type) -> Self {
+        self.function.parameters.push(HirParameter {
+            name: name.to_string(),
+            typ,
+            permissions: permissions.to_vec(),
+            symbol: None,
+        });
+        self
+    }
+
+    pub fn type_param(mut self, name: &str) -> Self {
+        self.function.type_params.push(name.to_string());
+        self
+    }
+
+    pub fn returns(mut self, typ: Type) -> Self {
+        self.function.return_type = Some(typ);
+        self
+    }
+
+    /// Make this a method or behavior of `actor`
+    pub fn in_actor(mut self, actor: &str) -> Self {
+        self.function.actor = Some(actor.to_string());
+        self
+    }
+
+    pub fn build(self) -> HirFunction {
+        self.function
+    }
+}
+
+impl StatementBuilder for FunctionBuilder {
+    fn statement(mut self, stmt: HirStatement) -> Self {
+        self.function.body.push(stmt);
+        self
+    }
+}
+
+/// Builds the statements of a block, a branch or a loop body
+#[derive(Default)]
+pub struct BlockBuilder {
+    statements: Vec<HirStatement>,
+}
+
+impl BlockBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The block built by `body`
+    fn build_with(self, body: impl FnOnce(BlockBuilder) -> BlockBuilder) -> HirStatement {
+        HirStatement::Block(body(self).statements, SourceLocation::default())
+    }
+
+    pub fn build(self) -> Vec<HirStatement> {
+        self.statements
+    }
+}
+
+impl StatementBuilder for BlockBuilder {
+    fn statement(mut self, stmt: HirStatement) -> Self {
+        self.statements.push(stmt);
+        self
+    }
+}
+
+pub fn int(value: i64) -> HirExpression {
+    HirExpression::Integer(value, SourceLocation::default())
+}
+
+pub fn boolean(value: bool) -> HirExpression {
+    HirExpression::Boolean(value, SourceLocation::default())
+}
+
+pub fn string(value: &str) -> HirExpression {
+    HirExpression::String(value.to_string(), SourceLocation::default())
+}
+
+pub fn var(name: &str, typ: Type) -> HirExpression {
+    HirExpression::Variable(name.to_string(), typ, SourceLocation::default(), None)
+}
+
+/// A binary operation; comparisons are `Bool`, anything else has the type
+/// of its left operand
+pub fn binary(left: HirExpression, operator: TokenType, right: HirExpression) -> HirExpression {
+    let result_type = if operator.is_ordering() || operator.is_equality() {
+        Type::Bool
+    } else {
+        left.get_type()
+    };
+    HirExpression::Binary {
+        left: Box::new(left),
+        operator,
+        right: Box::new(right),
+        result_type,
+        span: SourceLocation::default(),
+    }
+}
+
+pub fn call(function: &str, arguments: Vec<HirExpression>, result_type: Type) -> HirExpression {
+    HirExpression::Call {
+        function: function.to_string(),
+        arguments,
+        result_type,
+        span: SourceLocation::default(),
+    }
+}
+
+pub fn peak(expr: HirExpression) -> HirExpression {
+    HirExpression::Peak(Box::new(expr), SourceLocation::default())
+}
+
+pub fn clone_of(expr: HirExpression) -> HirExpression {
+    HirExpression::Clone(Box::new(expr), SourceLocation::default())
+}
+
+pub fn consume(expr: HirExpression) -> HirExpression {
+    HirExpression::Consume(Box::new(expr), SourceLocation::default())
+}
+
+/// Records variable types and function signatures, as the converter does
+struct TypeCollector {
+    type_info: TypeInfo,
+}
+
+impl HirVisitor for TypeCollector {
+    fn visit_function(&mut self, func: &HirFunction) {
+        self.type_info.functions.entry(func.name.clone()).or_insert_with(|| FunctionSignature {
+            parameters: func.parameters.clone(),
+            return_type: func.return_type.clone(),
+        });
+        visitor::walk_function(self, func);
+    }
+
+    fn visit_parameter(&mut self, param: &HirParameter) {
+        self.type_info.variables.insert(param.name.clone(), param.typ.clone());
+    }
+
+    fn visit_variable(&mut self, var: &HirVariable) {
+        self.type_info.variables.insert(var.name.clone(), var.typ.clone());
+        visitor::walk_variable(self, var);
+    }
+}
@@ -0,0 +1,338 @@
+//! Closure conversion
+//!
+//! Functions may be declared inside other functions and use the enclosing
+//! function's parameters and locals. MIR and the backends only know
+//! top-level functions, so this pass lifts every nested function to the top
+//! level under its path (`outer$inner`). The variables it captures become
+//! its environment: extra parameters after the declared ones, with the
+//! permissions the variables were declared with, which every call passes
+//! along. Each lifted function is recorded in `TypeInfo::closures`.
+//!
+//! A nested function can be called after its declaration, from the rest of
+//! the enclosing block, from functions nested there and from itself.
+
+use crate::hir::types::*;
+use crate::hir::visitor::{self, HirVisitor};
+use std::collections::{HashMap, HashSet};
+
+/// Lift every nested function to the top level
+pub fn lift_closures(program: &mut HirProgram) {
+    let mut lifter = Lifter {
+        type_info: &mut program.type_info,
+        locals: Vec::new(),
+        functions: Vec::new(),
+        lifted: Vec::new(),
+    };
+    for stmt in program.statements.iter_mut() {
+        if let HirStatement::Function(func) = stmt {
+            let path = func.name.clone();
+            lifter.lift_nested(func, &path);
+        }
+    }
+
+    let lifted = lifter.lifted.into_iter().map(HirStatement::Function);
+    program.statements.extend(lifted);
+}
+
+struct Lifter<'a> {
+    type_info: &'a mut TypeInfo,
+
+    /// Parameters and locals of the enclosing functions, innermost scope
+    /// last. Globals are not captured, so the top level has no scope here.
+    locals: Vec<HashMap<String, Capture>>,
+
+    /// Lifted names of the nested functions declared so far, by their
+    /// declared names, innermost scope last
+    functions: Vec<HashMap<String, String>>,
+
+    /// Lifted functions, innermost first
+    lifted: Vec<HirFunction>,
+}
+
+impl Lifter<'_> {
+    /// Lift the functions nested in `func`, whose path is `path`
+    fn lift_nested(&mut self, func: &mut HirFunction, path: &str) {
+        let params = func.parameters.iter()
+            .map(|param| (param.name.clone(), Capture {
+                name: param.name.clone(),
+                typ: param.typ.clone(),
+                permissions: param.permissions.clone(),
+                symbol: param.symbol,
+            }))
+            .collect();
+        self.locals.push(params);
+        self.functions.push(HashMap::new());
+        self.lift_block(&mut func.body, path);
+        self.functions.pop();
+        self.locals.pop();
+    }
+
+    /// Lift a nested function declared in the function at `path`
+    fn lift(&mut self, mut func: HirFunction, path: &str) {
+        let name = format!("{}${}", path, func.name);
+        self.lift_nested(&mut func, &name);
+
+        let captures = self.captures(&func);
+        for capture in &captures {
+            func.parameters.push(HirParameter {
+                name: capture.name.clone(),
+                typ: capture.typ.clone(),
+                permissions: capture.permissions.clone(),
+                symbol: capture.symbol,
+            });
+        }
+
+        // Recursive calls pass the environment on unchanged
+        let declared = std::mem::replace(&mut func.name, name.clone());
+        for stmt in func.body.iter_mut() {
+            for_each_call(stmt, &mut |function, arguments| {
+                if *function == declared {
+                    function.clone_from(&name);
+                    arguments.extend(captures.iter().map(environment_argument));
+                }
+            });
+        }
+
+        self.type_info.functions.insert(name.clone(), FunctionSignature {
+            parameters: func.parameters.clone(),
+            return_type: func.return_type.clone(),
+        });
+        self.type_info.closures.insert(name.clone(), Closure {
+            name: declared.clone(),
+            enclosing: path.to_string(),
+            captures,
+        });
+        if let Some(scope) = self.functions.last_mut() {
+            scope.insert(declared, name);
+        }
+        self.lifted.push(func);
+    }
+
+    /// Variables of the enclosing functions that `func` uses, in order of
+    /// first use
+    fn captures(&self, func: &HirFunction) -> Vec<Capture> {
+        let mut free = FreeVariables {
+            scopes: vec![func.parameters.iter().map(|param| param.name.clone()).collect()],
+            seen: HashSet::new(),
+            names: Vec::new(),
+        };
+        for stmt in &func.body {
+            free.visit_statement(stmt);
+        }
+
+        free.names.iter()
+            .filter_map(|name| self.locals.iter().rev().find_map(|scope| scope.get(name)))
+            .cloned()
+            .collect()
+    }
+
+    /// Lift the functions declared in a list of statements, in a scope of
+    /// its own
+    fn lift_block(&mut self, statements: &mut Vec<HirStatement>, path: &str) {
+        self.locals.push(HashMap::new());
+        self.functions.push(HashMap::new());
+        let mut kept = Vec::with_capacity(statements.len());
+        for stmt in std::mem::take(statements) {
+            match stmt {
+                HirStatement::Function(func) => self.lift(func, path),
+                mut stmt => {
+                    self.rewrite_statement(&mut stmt, path);
+                    kept.push(stmt);
+                },
+            }
+        }
+        *statements = kept;
+        self.functions.pop();
+        self.locals.pop();
+    }
+
+    /// Redirect calls to lifted functions and record declared locals
+    fn rewrite_statement(&mut self, stmt: &mut HirStatement, path: &str) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                if let Some(init) = &mut var.initializer {
+                    self.rewrite_calls(init);
+                }
+                if let Some(scope) = self.locals.last_mut() {
+                    scope.insert(var.name.clone(), Capture {
+                        name: var.name.clone(),
+                        typ: var.typ.clone(),
+                        permissions: var.permissions.clone(),
+                        symbol: var.symbol,
+                    });
+                }
+            },
+            HirStatement::Assignment(assign) => self.rewrite_calls(&mut assign.value),
+            HirStatement::Function(_) => {
+                // A lone function as the branch of an if or the body of a
+                // loop; lift it like one in a block
+                let span = *stmt.span();
+                let mut block = vec![std::mem::replace(stmt, HirStatement::Block(Vec::new(), span))];
+                self.lift_block(&mut block, path);
+            },
+            HirStatement::Return(expr, _) => {
+                if let Some(expr) = expr {
+                    self.rewrite_calls(expr);
+                }
+            },
+            HirStatement::Print(expr, _) |
+            HirStatement::Expression(expr) => self.rewrite_calls(expr),
+            HirStatement::Block(statements, _) |
+            HirStatement::Atomic(statements, _) => self.lift_block(statements, path),
+            HirStatement::If { condition, then_branch, else_branch, .. } => {
+                self.rewrite_calls(condition);
+                self.rewrite_statement(then_branch, path);
+                if let Some(else_branch) = else_branch {
+                    self.rewrite_statement(else_branch, path);
+                }
+            },
+            HirStatement::While { condition, body, .. } => {
+                self.rewrite_calls(condition);
+                self.rewrite_statement(body, path);
+            },
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => {},
+        }
+    }
+
+    /// Point calls to nested functions at their lifted versions, passing
+    /// the environment
+    fn rewrite_calls(&self, expr: &mut HirExpression) {
+        for_each_call_in_expression(expr, &mut |function, arguments| {
+            let lifted = self.functions.iter().rev().find_map(|scope| scope.get(function.as_str()));
+            if let Some(lifted) = lifted {
+                let closure = &self.type_info.closures[lifted];
+                arguments.extend(closure.captures.iter().map(environment_argument));
+                function.clone_from(lifted);
+            }
+        });
+    }
+}
+
+/// The argument passing a captured variable on to a lifted function
+fn environment_argument(capture: &Capture) -> HirExpression {
+    HirExpression::Variable(capture.name.clone(), capture.typ.clone(), SourceLocation::default(), capture.symbol)
+}
+
+/// Apply `f` to the name and arguments of every call in a statement, after
+/// the calls nested in its arguments
+fn for_each_call(stmt: &mut HirStatement, f: &mut impl FnMut(&mut String, &mut Vec<HirExpression>)) {
+    match stmt {
+        HirStatement::Declaration(var) => {
+            if let Some(init) = &mut var.initializer {
+                for_each_call_in_expression(init, f);
+            }
+        },
+        HirStatement::Assignment(assign) => for_each_call_in_expression(&mut assign.value, f),
+        HirStatement::Function(func) => {
+            for stmt in func.body.iter_mut() {
+                for_each_call(stmt, f);
+            }
+        },
+        HirStatement::Return(expr, _) => {
+            if let Some(expr) = expr {
+                for_each_call_in_expression(expr, f);
+            }
+        },
+        HirStatement::Print(expr, _) |
+        HirStatement::Expression(expr) => for_each_call_in_expression(expr, f),
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => {
+            for stmt in statements.iter_mut() {
+                for_each_call(stmt, f);
+            }
+        },
+        HirStatement::If { condition, then_branch, else_branch, .. } => {
+            for_each_call_in_expression(condition, f);
+            for_each_call(then_branch, f);
+            if let Some(else_branch) = else_branch {
+                for_each_call(else_branch, f);
+            }
+        },
+        HirStatement::While { condition, body, .. } => {
+            for_each_call_in_expression(condition, f);
+            for_each_call(body, f);
+        },
+        HirStatement::Break(_) |
+        HirStatement::Continue(_) => {},
+    }
+}
+
+fn for_each_call_in_expression(expr: &mut HirExpression, f: &mut impl FnMut(&mut String, &mut Vec<HirExpression>)) {
+    match expr {
+        HirExpression::Call { function, arguments, .. } => {
+            for arg in arguments.iter_mut() {
+                for_each_call_in_expression(arg, f);
+            }
+            f(function, arguments);
+        },
+        HirExpression::Binary { left, right, .. } => {
+            for_each_call_in_expression(left, f);
+            for_each_call_in_expression(right, f);
+        },
+        HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
+            for_each_call_in_expression(condition, f);
+            for_each_call_in_expression(then_expr, f);
+            for_each_call_in_expression(else_expr, f);
+        },
+        HirExpression::Cast { expr: inner, .. } |
+        HirExpression::Peak(inner, _) |
+        HirExpression::Clone(inner, _) |
+        HirExpression::Consume(inner, _) => for_each_call_in_expression(inner, f),
+        HirExpression::Integer(_, _) |
+        HirExpression::Variable(_, _, _, _) |
+        HirExpression::Boolean(_, _) |
+        HirExpression::String(_, _) => {},
+    }
+}
+
+/// Collects the variables a function uses without declaring them
+struct FreeVariables {
+    /// Names declared in the function, innermost scope last
+    scopes: Vec<HashSet<String>>,
+
+    seen: HashSet<String>,
+
+    /// Free names in order of first use
+    names: Vec<String>,
+}
+
+impl FreeVariables {
+    fn use_name(&mut self, name: &str) {
+        let declared = self.scopes.iter().any(|scope| scope.contains(name));
+        if !declared && self.seen.insert(name.to_string()) {
+            self.names.push(name.to_string());
+        }
+    }
+}
+
+impl HirVisitor for FreeVariables {
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                visitor::walk_statement(self, stmt);
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(var.name.clone());
+                }
+            },
+            HirStatement::Assignment(assign) => {
+                visitor::walk_statement(self, stmt);
+                self.use_name(&assign.target);
+            },
+            HirStatement::Block(_, _) |
+            HirStatement::Atomic(_, _) => {
+                self.scopes.push(HashSet::new());
+                visitor::walk_statement(self, stmt);
+                self.scopes.pop();
+            },
+            _ => visitor::walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        if let HirExpression::Variable(name, _, _, _) = expr {
+            self.use_name(name);
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
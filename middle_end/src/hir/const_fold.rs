@@ -1,13 +1,21 @@
 //! Constant folding for HIR
 //!
 //! This module implements compile-time evaluation of constant expressions.
+//! Variables bound to a constant are replaced by their value for the rest of
+//! their scope, as long as they are declared without `write` or `writes`:
+//! nothing can change such a variable after its declaration. Calls to pure
+//! functions with constant arguments are evaluated when the callee's body
+//! is straight-line code that folds to a constant result.
 
+use crate::hir::effects::pure_functions;
 use crate::hir::types::*;
 use front_end::token::TokenType;
+use front_end::types::Permission;
+use std::collections::HashMap;
 
 /// Fold constants in a HIR program
 pub fn fold_constants(program: &mut HirProgram) {
-    let mut folder = ConstantFolder::new();
+    let mut folder = ConstantFolder::new(program);
     
     // Process each statement in the program
     for i in 0..program.statements.len() {
@@ -19,12 +27,105 @@ pub fn fold_constants(program: &mut HirProgram) {
 }
 
 /// Visitor for constant folding
-struct ConstantFolder;
+struct ConstantFolder {
+    /// Known values of variables, innermost scope last. `None` marks a
+    /// variable whose value is not constant and shadows any outer binding.
+    scopes: Vec<HashMap<String, Option<HirExpression>>>,
+    
+    /// Pure functions of the program, which may be evaluated when called
+    /// with constant arguments
+    pure_functions: HashMap<String, HirFunction>,
+    
+    /// Number of calls currently being evaluated
+    call_depth: usize,
+}
+
+/// How deeply calls are evaluated before giving up, so recursion ends
+const MAX_CALL_DEPTH: usize = 16;
 
 impl ConstantFolder {
-    /// Create a new constant folder
-    fn new() -> Self {
-        Self
+    /// Create a new constant folder for a program
+    fn new(program: &HirProgram) -> Self {
+        let pure = pure_functions(program);
+        let pure_functions = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                HirStatement::Function(func) if pure.contains(&func.name) => {
+                    Some((func.name.clone(), func.clone()))
+                },
+                _ => None,
+            })
+            .collect();
+        
+        Self {
+            scopes: vec![HashMap::new()],
+            pure_functions,
+            call_depth: 0,
+        }
+    }
+    
+    /// The constant result of calling a pure function with constant
+    /// arguments, if its body folds to one
+    fn evaluate_call(&mut self, function: &str, arguments: &[HirExpression]) -> Option<HirExpression> {
+        let func = self.pure_functions.get(function)?.clone();
+        if func.parameters.len() != arguments.len() || self.call_depth >= MAX_CALL_DEPTH {
+            return None;
+        }
+        
+        // The body sees only its parameters, bound to the arguments
+        let bindings = func.parameters.iter()
+            .zip(arguments)
+            .map(|(param, arg)| (param.name.clone(), Some(arg.clone())))
+            .collect();
+        let outer = std::mem::replace(&mut self.scopes, vec![bindings]);
+        self.call_depth += 1;
+        
+        let mut result = None;
+        for stmt in &func.body {
+            match self.fold_statement(stmt) {
+                HirStatement::Return(Some(value), _) => {
+                    result = Some(value).filter(is_literal);
+                    break;
+                },
+                HirStatement::Declaration(_) |
+                HirStatement::Assignment(_) |
+                HirStatement::Expression(_) => {},
+                // Anything else may branch or return nothing
+                _ => break,
+            }
+        }
+        
+        self.call_depth -= 1;
+        self.scopes = outer;
+        result
+    }
+    
+    /// Fold a statement in its own scope
+    fn fold_scoped(&mut self, stmt: &HirStatement) -> HirStatement {
+        self.scopes.push(HashMap::new());
+        let folded = self.fold_statement(stmt);
+        self.scopes.pop();
+        folded
+    }
+    
+    /// Record what is known about a variable declared in the current scope
+    fn bind(&mut self, name: &str, value: Option<HirExpression>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), value);
+        }
+    }
+    
+    /// Stop treating a variable as constant
+    fn forget(&mut self, name: &str) {
+        if let Some(value) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            *value = None;
+        }
+    }
+    
+    /// The constant value of a variable, if it has one
+    fn lookup(&self, name: &str) -> Option<&HirExpression> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.get(name))
+            .and_then(|value| value.as_ref())
     }
     
     /// Fold constants in a statement
@@ -33,19 +134,35 @@ impl ConstantFolder {
             HirStatement::Declaration(var) => {
                 let initializer = var.initializer.as_ref().map(|expr| self.fold_expression(expr));
                 
+                let writable = var.permissions.iter()
+                    .any(|p| matches!(p, Permission::Write | Permission::Writes));
+                let value = initializer.as_ref()
+                    .filter(|init| !writable && is_literal(init))
+                    .cloned();
+                self.bind(&var.name, value);
+                
                 HirStatement::Declaration(HirVariable {
                     name: var.name.clone(),
                     typ: var.typ.clone(),
                     permissions: var.permissions.clone(),
                     initializer,
-                    location: var.location.clone(),
+                    span: var.span,
+                    actor: var.actor.clone(),
+                    symbol: var.symbol,
                 })
             },
             
             HirStatement::Assignment(assign) => {
+                // Permission checking rejects writes to constants, but never
+                // propagate a value that has been overwritten
+                self.forget(&assign.target);
+                
                 HirStatement::Assignment(HirAssignment {
                     target: assign.target.clone(),
                     value: self.fold_expression(&assign.value),
+                    operator: assign.operator.clone(),
+                    symbol: assign.symbol,
+                    span: assign.span,
                 })
             },
             
@@ -53,131 +170,230 @@ impl ConstantFolder {
                 HirStatement::Expression(self.fold_expression(expr))
             },
             
-            HirStatement::Return(expr_opt) => {
-                HirStatement::Return(expr_opt.as_ref().map(|expr| self.fold_expression(expr)))
+            HirStatement::Return(expr_opt, span) => {
+                HirStatement::Return(expr_opt.as_ref().map(|expr| self.fold_expression(expr)), *span)
             },
             
-            HirStatement::Print(expr) => {
-                HirStatement::Print(self.fold_expression(expr))
+            HirStatement::Print(expr, span) => {
+                HirStatement::Print(self.fold_expression(expr), *span)
             },
             
             HirStatement::Function(func) => {
+                // Parameters shadow any outer constants with the same name
+                self.scopes.push(HashMap::new());
+                for param in &func.parameters {
+                    self.bind(&param.name, None);
+                }
+                
                 // Fold expressions in the function body
                 let body = func.body.iter()
                     .map(|stmt| self.fold_statement(stmt))
                     .collect();
+                self.scopes.pop();
                 
                 HirStatement::Function(HirFunction {
                     name: func.name.clone(),
+                    type_params: func.type_params.clone(),
                     parameters: func.parameters.clone(),
                     body,
                     return_type: func.return_type.clone(),
+                    is_behavior: func.is_behavior,
+                    actor: func.actor.clone(),
+                    span: func.span,
                 })
             },
             
-            HirStatement::Block(statements) => {
+            HirStatement::Block(statements, span) => {
+                self.scopes.push(HashMap::new());
+                let folded = statements.iter()
+                    .map(|stmt| self.fold_statement(stmt))
+                    .collect();
+                self.scopes.pop();
+                
+                HirStatement::Block(folded, *span)
+            },
+            
+            HirStatement::Atomic(statements, span) => {
+                self.scopes.push(HashMap::new());
                 let folded = statements.iter()
                     .map(|stmt| self.fold_statement(stmt))
                     .collect();
+                self.scopes.pop();
                 
-                HirStatement::Block(folded)
+                HirStatement::Atomic(folded, *span)
             },
             
             // Fold expressions in control flow statements
-            HirStatement::If { condition, then_branch, else_branch } => {
+            HirStatement::If { condition, then_branch, else_branch, span } => {
                 HirStatement::If {
                     condition: self.fold_expression(condition),
-                    then_branch: Box::new(self.fold_statement(then_branch)),
-                    else_branch: else_branch.as_ref().map(|stmt| Box::new(self.fold_statement(stmt))),
+                    then_branch: Box::new(self.fold_scoped(then_branch)),
+                    else_branch: else_branch.as_ref().map(|stmt| Box::new(self.fold_scoped(stmt))),
+                    span: *span,
                 }
             },
             
-            HirStatement::While { condition, body } => {
+            HirStatement::While { condition, body, span } => {
                 HirStatement::While {
                     condition: self.fold_expression(condition),
-                    body: Box::new(self.fold_statement(body)),
+                    body: Box::new(self.fold_scoped(body)),
+                    span: *span,
                 }
             },
+            
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => stmt.clone(),
         }
     }
     
     /// Fold constants in an expression
     fn fold_expression(&mut self, expr: &HirExpression) -> HirExpression {
         match expr {
-            HirExpression::Binary { left, operator, right, result_type } => {
+            HirExpression::Binary { left, operator, right, result_type, span } => {
                 let folded_left = self.fold_expression(left);
                 let folded_right = self.fold_expression(right);
                 
                 // Try to evaluate constant binary expressions
-                match (&folded_left, operator, &folded_right) {
-                    (HirExpression::Integer(lhs, _), TokenType::Plus, HirExpression::Integer(rhs, _)) => {
-                        HirExpression::Integer(lhs + rhs, None)
-                    },
-                    (HirExpression::Integer(lhs, _), TokenType::Minus, HirExpression::Integer(rhs, _)) => {
-                        HirExpression::Integer(lhs - rhs, None)
-                    },
-                    (HirExpression::Integer(lhs, _), TokenType::Star, HirExpression::Integer(rhs, _)) => {
-                        HirExpression::Integer(lhs * rhs, None)
-                    },
-                    (HirExpression::Integer(lhs, _), TokenType::Slash, HirExpression::Integer(rhs, _)) if *rhs != 0 => {
-                        HirExpression::Integer(lhs / rhs, None)
+                let folded = match (&folded_left, &folded_right) {
+                    (HirExpression::Integer(lhs, _), HirExpression::Integer(rhs, _)) if operator.is_arithmetic() => fold_arithmetic(*lhs, operator, *rhs)
+                        .map(|value| HirExpression::Integer(value, *span)),
+                    _ if operator.is_ordering() || operator.is_equality() => {
+                        fold_comparison(&folded_left, operator, &folded_right, *span)
                     },
-                    // Can't fold, return a new binary expression with folded operands
-                    _ => HirExpression::Binary {
-                        left: Box::new(folded_left),
-                        operator: operator.clone(),
-                        right: Box::new(folded_right),
-                        result_type: result_type.clone(),
-                    }
-                }
+                    _ => None,
+                };
+                
+                // Can't fold, return a new binary expression with folded operands
+                folded.unwrap_or_else(|| HirExpression::Binary {
+                    left: Box::new(folded_left),
+                    operator: operator.clone(),
+                    right: Box::new(folded_right),
+                    result_type: result_type.clone(),
+                    span: *span,
+                })
             },
             
-            HirExpression::Conditional { condition, then_expr, else_expr, result_type } => {
+            HirExpression::Conditional { condition, then_expr, else_expr, result_type, span } => {
                 let folded_condition = self.fold_expression(condition);
                 
                 // If condition is a constant boolean, select the appropriate branch
                 match folded_condition {
-                    HirExpression::Boolean(true) => self.fold_expression(then_expr),
-                    HirExpression::Boolean(false) => self.fold_expression(else_expr),
+                    HirExpression::Boolean(true, _) => self.fold_expression(then_expr),
+                    HirExpression::Boolean(false, _) => self.fold_expression(else_expr),
                     _ => HirExpression::Conditional {
                         condition: Box::new(folded_condition),
                         then_expr: Box::new(self.fold_expression(then_expr)),
                         else_expr: Box::new(self.fold_expression(else_expr)),
                         result_type: result_type.clone(),
+                        span: *span,
                     }
                 }
             },
             
             // Other expression types just need their subexpressions folded
-            HirExpression::Call { function, arguments, result_type } => {
-                let folded_args = arguments.iter()
+            HirExpression::Call { function, arguments, result_type, span } => {
+                let folded_args: Vec<HirExpression> = arguments.iter()
                     .map(|arg| self.fold_expression(arg))
                     .collect();
                 
+                if folded_args.iter().all(is_literal) {
+                    if let Some(value) = self.evaluate_call(function, &folded_args) {
+                        return located(value, *span);
+                    }
+                }
+                
                 HirExpression::Call {
                     function: function.clone(),
                     arguments: folded_args,
                     result_type: result_type.clone(),
+                    span: *span,
                 }
             },
             
-            HirExpression::Cast { expr, target_type } => {
+            HirExpression::Cast { expr, target_type, span } => {
                 HirExpression::Cast {
                     expr: Box::new(self.fold_expression(expr)),
                     target_type: target_type.clone(),
+                    span: *span,
                 }
             },
             
-            HirExpression::Peak(expr) => {
-                HirExpression::Peak(Box::new(self.fold_expression(expr)))
+            HirExpression::Peak(expr, span) => {
+                HirExpression::Peak(Box::new(self.fold_expression(expr)), *span)
+            },
+            
+            HirExpression::Clone(expr, span) => {
+                HirExpression::Clone(Box::new(self.fold_expression(expr)), *span)
             },
             
-            HirExpression::Clone(expr) => {
-                HirExpression::Clone(Box::new(self.fold_expression(expr)))
+            HirExpression::Consume(expr, span) => {
+                HirExpression::Consume(Box::new(self.fold_expression(expr)), *span)
             },
             
-            // Leaf nodes (literals and variables) remain the same
+            // Constant variables are replaced by their value at the use site
+            HirExpression::Variable(name, _, location, _) => match self.lookup(name) {
+                Some(value) => located(value.clone(), *location),
+                None => expr.clone(),
+            },
+            
+            // Literals remain the same
             _ => expr.clone(),
         }
     }
 }
+
+/// Evaluate arithmetic on two integer literals as the interpreter does,
+/// wrapping on overflow. Division by zero and `i64::MIN / -1` are left to
+/// run time.
+fn fold_arithmetic(lhs: i64, operator: &TokenType, rhs: i64) -> Option<i64> {
+    match operator {
+        TokenType::Plus => Some(lhs.wrapping_add(rhs)),
+        TokenType::Minus => Some(lhs.wrapping_sub(rhs)),
+        TokenType::Star => Some(lhs.wrapping_mul(rhs)),
+        TokenType::Slash => lhs.checked_div(rhs),
+        _ => None,
+    }
+}
+
+/// Evaluate a comparison between two literals
+fn fold_comparison(
+    left: &HirExpression,
+    operator: &TokenType,
+    right: &HirExpression,
+    span: SourceLocation,
+) -> Option<HirExpression> {
+    let ordering = match (left, right) {
+        (HirExpression::Integer(lhs, _), HirExpression::Integer(rhs, _)) => lhs.cmp(rhs),
+        // Booleans and strings only support equality
+        (HirExpression::Boolean(lhs, _), HirExpression::Boolean(rhs, _)) if operator.is_equality() => lhs.cmp(rhs),
+        (HirExpression::String(lhs, _), HirExpression::String(rhs, _)) if operator.is_equality() => lhs.cmp(rhs),
+        _ => return None,
+    };
+    
+    let result = match operator {
+        TokenType::EqualEqual => ordering.is_eq(),
+        TokenType::BangEqual => ordering.is_ne(),
+        TokenType::Less => ordering.is_lt(),
+        TokenType::LessEqual => ordering.is_le(),
+        TokenType::Greater => ordering.is_gt(),
+        TokenType::GreaterEqual => ordering.is_ge(),
+        _ => return None,
+    };
+    
+    Some(HirExpression::Boolean(result, span))
+}
+
+/// A folded literal, located where the expression it replaces was
+fn located(value: HirExpression, span: SourceLocation) -> HirExpression {
+    match value {
+        HirExpression::Integer(value, _) => HirExpression::Integer(value, span),
+        HirExpression::Boolean(value, _) => HirExpression::Boolean(value, span),
+        HirExpression::String(value, _) => HirExpression::String(value, span),
+        other => other,
+    }
+}
+
+/// Whether an expression is a literal value that can be propagated
+fn is_literal(expr: &HirExpression) -> bool {
+    matches!(expr, HirExpression::Integer(_, _) | HirExpression::Boolean(_, _) | HirExpression::String(_, _))
+}
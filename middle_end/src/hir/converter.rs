@@ -1,6 +1,10 @@
 //! AST to HIR conversion
 //!
 //! This module handles the conversion from the AST representation to HIR.
+//!
+//! Actors have no node of their own in HIR: their state declarations,
+//! methods and behaviors become top-level statements tagged with the actor
+//! they belong to, so every pass handles them like any other declaration.
 
 use crate::hir::types::*;
 use front_end::ast::{Statement, Expression};
@@ -21,6 +25,13 @@ pub fn convert_statements_to_hir(statements: Vec<Statement>) -> HirProgram {
     
     // Process each statement
     for stmt in statements {
+        if let Statement::Actor { name, state, methods, behaviors, .. } = stmt {
+            for member in converter.convert_actor(&name, state, methods, behaviors) {
+                program.add_statement(member);
+            }
+            continue;
+        }
+        
         let hir_stmt = converter.convert_statement(stmt);
         program.add_statement(hir_stmt);
     }
@@ -30,8 +41,8 @@ pub fn convert_statements_to_hir(statements: Vec<Statement>) -> HirProgram {
         program.type_info.variables.insert(name, typ);
     }
     
-    for (name, ret_type) in converter.type_info.functions {
-        program.type_info.functions.insert(name, ret_type);
+    for (name, signature) in converter.type_info.functions {
+        program.type_info.functions.insert(name, signature);
     }
     
     program
@@ -58,62 +69,72 @@ impl HirConverter {
         program
     }
     
-    // Overloaded version without arguments (for multiple statements case)
-    pub fn finalize_multi(self) -> HirProgram {
-        let mut program = HirProgram::new();
-        program.type_info = self.type_info;
-        program
+    /// Convert the members of an actor, tagging each with the actor's name
+    fn convert_actor(
+        &mut self,
+        name: &str,
+        state: Vec<Statement>,
+        methods: Vec<Statement>,
+        behaviors: Vec<Statement>,
+    ) -> Vec<HirStatement> {
+        state.into_iter()
+            .chain(methods)
+            .chain(behaviors)
+            .map(|member| {
+                let mut hir_member = self.convert_statement(member);
+                match &mut hir_member {
+                    HirStatement::Declaration(var) => var.actor = Some(name.to_string()),
+                    HirStatement::Function(func) => func.actor = Some(name.to_string()),
+                    _ => {},
+                }
+                hir_member
+            })
+            .collect()
     }
     
     /// Convert an AST statement to an HIR statement
     pub fn convert_statement(&mut self, stmt: Statement) -> HirStatement {
         match stmt {
-            Statement::Declaration { ref name, ref typ, ref initializer } => {
-                // Use ref pattern for all fields to avoid moving anything out of stmt
-                
-                // Extract source location from the AST if available
-                let location = extract_location(&stmt);
-                
+            Statement::Declaration { name, typ, initializer, span } => {
                 // Convert permissions from front-end to HIR format
-                let permissions: Vec<Permission> = typ.permissions
-                    .iter()
-                    .map(|p| Permission::from(p.clone()))
-                    .collect();
+                let permissions: Vec<Permission> = typ.permissions.to_vec();
                 
                 // Convert initializer if present
-                let init_expr = initializer.as_ref().map(|expr| self.convert_expression(expr.clone()));
+                let init_expr = initializer.map(|expr| self.convert_expression(expr));
                 
                 // Record type information
                 let base_type = typ.base_type.clone();
                 self.type_info.variables.insert(name.clone(), base_type.clone());
                 
                 HirStatement::Declaration(HirVariable {
-                    name: name.clone(),
+                    name,
                     typ: base_type,
                     permissions,
                     initializer: init_expr,
-                    location, // Use the extracted location
+                    span: SourceLocation::from(&span),
+                    actor: None,
+                    symbol: None,
                 })
             },
             
-            Statement::Assignment { target, value, target_type: _ } => {
+            Statement::Assignment { target, value, target_type: _, operator, span } => {
                 let hir_value = self.convert_expression(value);
                 
                 HirStatement::Assignment(HirAssignment {
                     target,
                     value: hir_value,
+                    operator,
+                    symbol: None,
+                    span: SourceLocation::from(&span),
                 })
             },
             
-            Statement::Function { name, params, body, return_type, is_behavior: _ } => {
+            Statement::Function { name, type_params, params, body, return_type, is_behavior, span } => {
                 // Convert parameters
                 let parameters: Vec<HirParameter> = params
                     .into_iter()
                     .map(|(name, typ)| {
-                        let permissions: Vec<Permission> = typ.permissions
-                            .iter()
-                            .map(|p| Permission::from(p.clone()))
-                            .collect();
+                        let permissions: Vec<Permission> = typ.permissions.to_vec();
                             
                         // Record parameter type
                         self.type_info.variables.insert(name.clone(), typ.base_type.clone());
@@ -122,6 +143,7 @@ impl HirConverter {
                             name,
                             typ: typ.base_type,
                             permissions,
+                            symbol: None,
                         }
                     })
                     .collect();
@@ -132,43 +154,78 @@ impl HirConverter {
                     .map(|stmt| self.convert_statement(stmt))
                     .collect();
                 
-                // Record function return type
+                // Record function signature; a redefinition is reported by name
+                // resolution and must not replace the first signature
                 let return_typ = return_type.map(|t| t.base_type.clone());
-                self.type_info.functions.insert(name.clone(), return_typ.clone());
+                self.type_info.functions.entry(name.clone()).or_insert_with(|| FunctionSignature {
+                    parameters: parameters.clone(),
+                    return_type: return_typ.clone(),
+                });
                 
                 HirStatement::Function(HirFunction {
                     name,
+                    type_params,
                     parameters,
                     body: hir_body,
                     return_type: return_typ,
+                    is_behavior,
+                    actor: None,
+                    span: SourceLocation::from(&span),
                 })
             },
             
-            Statement::Return(expr) => {
-                HirStatement::Return(Some(self.convert_expression(expr)))
+            Statement::Return(expr, span) => {
+                HirStatement::Return(Some(self.convert_expression(expr)), SourceLocation::from(&span))
             },
             
-            Statement::Print(expr) => {
-                HirStatement::Print(self.convert_expression(expr))
+            Statement::Print(expr, span) => {
+                HirStatement::Print(self.convert_expression(expr), SourceLocation::from(&span))
             },
             
             Statement::Expression(expr) => {
                 HirStatement::Expression(self.convert_expression(expr))
             },
             
-            Statement::Block(statements) => {
+            Statement::Block(statements, span) => {
+                let hir_statements: Vec<HirStatement> = statements
+                    .into_iter()
+                    .map(|stmt| self.convert_statement(stmt))
+                    .collect();
+                
+                HirStatement::Block(hir_statements, SourceLocation::from(&span))
+            },
+            
+            Statement::AtomicBlock(statements, span) => {
                 let hir_statements: Vec<HirStatement> = statements
                     .into_iter()
                     .map(|stmt| self.convert_statement(stmt))
                     .collect();
                 
-                HirStatement::Block(hir_statements)
+                HirStatement::Atomic(hir_statements, SourceLocation::from(&span))
             },
             
+            Statement::While { condition, body, span } => {
+                let span = SourceLocation::from(&span);
+                let hir_body: Vec<HirStatement> = body
+                    .into_iter()
+                    .map(|stmt| self.convert_statement(stmt))
+                    .collect();
+                
+                HirStatement::While {
+                    condition: self.convert_expression(condition),
+                    body: Box::new(HirStatement::Block(hir_body, span)),
+                    span,
+                }
+            },
+            
+            Statement::Break(span) => HirStatement::Break(SourceLocation::from(&span)),
+            
+            Statement::Continue(span) => HirStatement::Continue(SourceLocation::from(&span)),
+            
             // Any other types of statements we need to handle
-            _ => {
+            other => {
                 // For now, convert unhandled statement types to an empty block
-                HirStatement::Block(vec![])
+                HirStatement::Block(vec![], SourceLocation::from(other.span()))
             }
         }
     }
@@ -176,34 +233,38 @@ impl HirConverter {
     /// Convert an AST expression to an HIR expression
     pub fn convert_expression(&mut self, expr: Expression) -> HirExpression {
         match expr {
-            Expression::Number(value) => {
-                // Extract source location from the AST if available
-                let location = extract_expr_location(&expr);
-                
-                HirExpression::Integer(value, location)
+            Expression::Number(value, span) => {
+                HirExpression::Integer(value, SourceLocation::from(&span))
             },
             
-            Expression::Variable(ref name) => {
-                // Use ref to borrow the name without moving it
-                // Extract source location first
-                let location = extract_expr_location(&expr);
-                
+            Expression::Variable(name, span) => {
                 // Look up the type if known, otherwise default to Int
                 let typ = self.type_info.variables
-                    .get(name)
+                    .get(&name)
                     .cloned()
                     .unwrap_or(Type::Int);
                 
-                HirExpression::Variable(name.clone(), typ, location)
+                HirExpression::Variable(name, typ, SourceLocation::from(&span), None)
             },
             
-            Expression::Binary { left, operator, right } => {
+            Expression::Binary { left, operator, right, span } => {
                 let left_expr = self.convert_expression(*left);
                 let right_expr = self.convert_expression(*right);
                 
-                // Simplistic type determination - in a real compiler we'd do proper type checking
+                // Arithmetic yields the promoted operand type. Integer literals take
+                // the type of the other operand; mismatched operands fall back to
+                // the left type and are reported by validation.
                 let result_type = match operator {
-                    TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => Type::Int,
+                    TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash => {
+                        match (&left_expr, &right_expr) {
+                            (_, HirExpression::Integer(_, _)) => left_expr.get_type(),
+                            (HirExpression::Integer(_, _), _) => right_expr.get_type(),
+                            _ => {
+                                let left_type = left_expr.get_type();
+                                left_type.promote(&right_expr.get_type()).unwrap_or(left_type)
+                            }
+                        }
+                    },
                     TokenType::Greater | TokenType::GreaterEqual | 
                     TokenType::Less | TokenType::LessEqual | 
                     TokenType::EqualEqual | TokenType::BangEqual => Type::Bool,
@@ -215,10 +276,11 @@ impl HirConverter {
                     operator,
                     right: Box::new(right_expr),
                     result_type,
+                    span: SourceLocation::from(&span),
                 }
             },
             
-            Expression::Call { function, arguments } => {
+            Expression::Call { function, arguments, span } => {
                 let hir_arguments: Vec<HirExpression> = arguments
                     .into_iter()
                     .map(|arg| self.convert_expression(arg))
@@ -227,7 +289,7 @@ impl HirConverter {
                 // Try to look up the return type, default to Int if unknown
                 let result_type = self.type_info.functions
                     .get(&function)
-                    .and_then(|t| t.clone())
+                    .and_then(|signature| signature.return_type.clone())
                     .unwrap_or(Type::Int);
                 
                 // KNOWN LIMITATION: The parser currently doesn't properly parse function calls with arguments.
@@ -237,30 +299,21 @@ impl HirConverter {
                     function,
                     arguments: hir_arguments,
                     result_type,
+                    span: SourceLocation::from(&span),
                 }
             },
             
-            Expression::Peak(expr) => {
-                HirExpression::Peak(Box::new(self.convert_expression(*expr)))
+            Expression::Peak(expr, span) => {
+                HirExpression::Peak(Box::new(self.convert_expression(*expr)), SourceLocation::from(&span))
+            },
+            
+            Expression::Consume(expr, span) => {
+                HirExpression::Consume(Box::new(self.convert_expression(*expr)), SourceLocation::from(&span))
             },
             
-            Expression::Clone(expr) => {
-                HirExpression::Clone(Box::new(self.convert_expression(*expr)))
+            Expression::Clone(expr, span) => {
+                HirExpression::Clone(Box::new(self.convert_expression(*expr)), SourceLocation::from(&span))
             },
         }
     }
 }
-
-/// Extract source location from an AST statement
-fn extract_location(stmt: &Statement) -> Option<SourceLocation> {
-    // This would depend on how your AST stores locations
-    // For now, we'll return None as a placeholder
-    None
-}
-
-/// Extract source location from an AST expression
-fn extract_expr_location(expr: &Expression) -> Option<SourceLocation> {
-    // This would depend on how your AST stores locations
-    // For now, we'll return None as a placeholder
-    None
-}
@@ -0,0 +1,193 @@
+//! Static data-race detection across behaviors
+//!
+//! Behaviors run concurrently with each other, so two of them touching the
+//! same `writes` global can interleave. This analysis collects the accesses
+//! every behavior makes to such globals, directly or through the functions
+//! it calls, and reports each pair of behaviors where one writes a global
+//! that the other reads or writes. Accesses inside `atomic { }` run as one
+//! unit and cannot race, and behaviors of the same actor never run at the
+//! same time.
+
+use crate::hir::permissions::PermissionError;
+use crate::hir::types::*;
+use crate::hir::visitor::{self, HirVisitor};
+use front_end::types::Permission;
+use std::collections::{HashMap, HashSet};
+
+/// One read or write of a shared global
+#[derive(Debug, Clone)]
+struct Access {
+    global: String,
+    write: bool,
+    location: Option<SourceLocation>,
+}
+
+/// Find globals that two behaviors may access at the same time, with at
+/// least one of them writing
+pub fn find_data_races(program: &HirProgram) -> Vec<PermissionError> {
+    let mut globals = Vec::new();
+    let mut functions = HashMap::new();
+    for stmt in &program.statements {
+        match stmt {
+            // Actor state is only reachable from its own actor's behaviors
+            HirStatement::Declaration(var) if var.actor.is_none() && var.permissions.contains(&Permission::Writes) => {
+                globals.push(var.name.clone());
+            },
+            HirStatement::Function(func) => {
+                functions.entry(func.name.clone()).or_insert(func);
+            },
+            _ => {},
+        }
+    }
+    if globals.is_empty() {
+        return Vec::new();
+    }
+
+    let shared: HashSet<String> = globals.iter().cloned().collect();
+    let behaviors: Vec<(&HirFunction, Vec<Access>)> = program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            HirStatement::Function(func) if func.is_behavior => {
+                Some((func, collect_accesses(func, &shared, &functions)))
+            },
+            _ => None,
+        })
+        .collect();
+
+    let mut errors = Vec::new();
+    for (i, (first, first_accesses)) in behaviors.iter().enumerate() {
+        for (second, second_accesses) in &behaviors[i + 1..] {
+            // An actor handles one message at a time, so its own behaviors
+            // never run concurrently
+            if first.actor.is_some() && first.actor == second.actor {
+                continue;
+            }
+            for global in &globals {
+                if let Some((a, b)) = conflict(global, first_accesses, second_accesses) {
+                    errors.push(PermissionError::DataRace {
+                        variable: global.clone(),
+                        first_behavior: first.name.clone(),
+                        first_writes: a.write,
+                        first_at: a.location,
+                        second_behavior: second.name.clone(),
+                        second_writes: b.write,
+                        second_at: b.location,
+                    });
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// The first pair of accesses to `global` that conflict, preferring pairs
+/// where the first behavior writes
+fn conflict<'a>(global: &str, first: &'a [Access], second: &'a [Access]) -> Option<(&'a Access, &'a Access)> {
+    let first: Vec<&Access> = first.iter().filter(|access| access.global == global).collect();
+    let second: Vec<&Access> = second.iter().filter(|access| access.global == global).collect();
+
+    let writer_first = first.iter()
+        .find(|access| access.write)
+        .zip(second.first());
+    writer_first.or_else(|| first.first().zip(second.iter().find(|access| access.write)))
+        .map(|(a, b)| (*a, *b))
+}
+
+/// Accesses a behavior makes to shared globals outside atomic blocks
+fn collect_accesses(
+    behavior: &HirFunction,
+    globals: &HashSet<String>,
+    functions: &HashMap<String, &HirFunction>,
+) -> Vec<Access> {
+    let mut collector = AccessCollector {
+        globals,
+        functions,
+        scopes: Vec::new(),
+        active: vec![behavior.name.clone()],
+        accesses: Vec::new(),
+    };
+    collector.collect_function(behavior);
+    collector.accesses
+}
+
+/// Walks a behavior and the functions it calls, recording global accesses
+struct AccessCollector<'a> {
+    globals: &'a HashSet<String>,
+    functions: &'a HashMap<String, &'a HirFunction>,
+
+    /// Local names shadowing globals, innermost scope last
+    scopes: Vec<HashSet<String>>,
+
+    /// Functions being walked, so recursive calls are followed only once
+    active: Vec<String>,
+
+    accesses: Vec<Access>,
+}
+
+impl AccessCollector<'_> {
+    /// Walk a function body with only its parameters in scope
+    fn collect_function(&mut self, func: &HirFunction) {
+        let params = func.parameters.iter().map(|param| param.name.clone()).collect();
+        let outer = std::mem::replace(&mut self.scopes, vec![params]);
+        for stmt in &func.body {
+            self.visit_statement(stmt);
+        }
+        self.scopes = outer;
+    }
+
+    fn record(&mut self, name: &str, write: bool, location: Option<SourceLocation>) {
+        let shadowed = self.scopes.iter().any(|scope| scope.contains(name));
+        if self.globals.contains(name) && !shadowed {
+            self.accesses.push(Access { global: name.to_string(), write, location });
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+}
+
+impl HirVisitor for AccessCollector<'_> {
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        match stmt {
+            // Nothing in an atomic block can interleave with another behavior
+            HirStatement::Atomic(_, _) => {},
+            // Nested functions run only when called
+            HirStatement::Function(_) => {},
+            HirStatement::Declaration(var) => {
+                visitor::walk_statement(self, stmt);
+                self.declare(&var.name);
+            },
+            HirStatement::Assignment(assign) => {
+                visitor::walk_statement(self, stmt);
+                self.record(&assign.target, true, stmt.location().copied());
+            },
+            HirStatement::Block(_, _) => {
+                self.scopes.push(HashSet::new());
+                visitor::walk_statement(self, stmt);
+                self.scopes.pop();
+            },
+            _ => visitor::walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        match expr {
+            HirExpression::Variable(name, _, location, _) => self.record(name, false, location.known().copied()),
+            HirExpression::Call { function, .. } => {
+                // Calls to behaviors are asynchronous sends; their accesses
+                // belong to the callee
+                let callee = self.functions.get(function)
+                    .filter(|callee| !callee.is_behavior && !self.active.contains(function));
+                if let Some(callee) = callee {
+                    self.active.push(function.clone());
+                    self.collect_function(callee);
+                    self.active.pop();
+                }
+            },
+            _ => {},
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
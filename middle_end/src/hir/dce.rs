@@ -1,32 +1,106 @@
 //! Dead code elimination for HIR
 //!
-//! This module implements basic dead code elimination optimizations.
+//! This module removes functions that can never be called and variables
+//! that are never read. Functions are kept when they are reachable in the
+//! call graph from `main` or from a top-level statement; declarations and
+//! assignments are kept when their value has a side effect (a call to a
+//! function that is not pure). Expression statements are dropped unless
+//! they have such an effect.
 
+use crate::hir::effects::{calls_impure, pure_functions};
 use crate::hir::types::*;
-use std::collections::HashSet;
+use crate::hir::visitor::{self, HirVisitor};
+use std::collections::{HashMap, HashSet};
 
 /// Eliminate dead code in a HIR program
 pub fn eliminate_dead_code(program: &mut HirProgram) {
-    // First, identify used variables
+    // Drop functions first, so variables only they read become unused
+    remove_unreachable_functions(program);
+    
+    // Then identify used variables
     let used_variables = find_used_variables(program);
+    let pure = pure_functions(program);
+    
+    // Remove unused declarations, at the top level and in every nested body
+    retain_live_statements(&mut program.statements, &used_variables, &pure);
+    for stmt in program.statements.iter_mut() {
+        eliminate_dead_code_in_statement(stmt, &used_variables, &pure);
+    }
+}
+
+/// Remove functions that are not reachable from `main` or the top level.
+/// A program with neither is left alone, since nothing anchors the call graph.
+fn remove_unreachable_functions(program: &mut HirProgram) {
+    let mut call_graph: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut roots = CallCollector::default();
     
-    // Then remove unused variable declarations
-    program.statements.retain(|stmt| {
+    for stmt in &program.statements {
         match stmt {
-            HirStatement::Declaration(var) => {
-                used_variables.contains(&var.name)
+            HirStatement::Function(func) => {
+                let mut callees = CallCollector::default();
+                callees.visit_function(func);
+                call_graph.insert(func.name.clone(), callees.calls);
             },
-            // Keep all other statements
-            _ => true,
+            other => roots.visit_statement(other),
         }
-    });
+    }
     
-    // Process nested blocks and function bodies
-    for i in 0..program.statements.len() {
-        if let Some(stmt) = program.statements.get_mut(i) {
-            eliminate_dead_code_in_statement(stmt, &used_variables);
+    let has_top_level_code = program.statements.iter()
+        .any(|stmt| !matches!(stmt, HirStatement::Function(_)));
+    if call_graph.contains_key("main") {
+        roots.calls.insert("main".to_string());
+    } else if !has_top_level_code {
+        return;
+    }
+    
+    // Walk the call graph from the roots
+    let mut reachable = HashSet::new();
+    let mut pending: Vec<String> = roots.calls.into_iter().collect();
+    while let Some(name) = pending.pop() {
+        if !reachable.insert(name.clone()) {
+            continue;
+        }
+        if let Some(callees) = call_graph.get(&name) {
+            pending.extend(callees.iter().filter(|callee| !reachable.contains(*callee)).cloned());
         }
     }
+    
+    program.statements.retain(|stmt| match stmt {
+        HirStatement::Function(func) => reachable.contains(&func.name),
+        _ => true,
+    });
+    program.type_info.functions.retain(|name, _| reachable.contains(name));
+}
+
+/// Collects the names of all functions called in the nodes it visits
+#[derive(Default)]
+struct CallCollector {
+    calls: HashSet<String>,
+}
+
+impl HirVisitor for CallCollector {
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        if let HirExpression::Call { function, .. } = expr {
+            self.calls.insert(function.clone());
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
+
+/// Remove declarations of, and assignments to, variables that are never read
+fn retain_live_statements(statements: &mut Vec<HirStatement>, used_variables: &HashSet<String>, pure: &HashSet<String>) {
+    statements.retain(|stmt| match stmt {
+        HirStatement::Declaration(var) => {
+            used_variables.contains(&var.name)
+                || var.initializer.as_ref().is_some_and(|init| calls_impure(init, pure))
+        },
+        HirStatement::Assignment(assign) => {
+            used_variables.contains(&assign.target) || calls_impure(&assign.value, pure)
+        },
+        HirStatement::Expression(expr) => calls_impure(expr, pure),
+        // Keep all other statements
+        _ => true,
+    });
 }
 
 /// Find all variables that are actually used in the program
@@ -52,8 +126,7 @@ fn collect_used_variables(stmt: &HirStatement, used: &mut HashSet<String>) {
         },
         
         HirStatement::Assignment(assign) => {
-            // Assignment target is considered used
-            used.insert(assign.target.clone());
+            // Writing a variable does not make it used, only reading does
             collect_used_variables_expr(&assign.value, used);
         },
         
@@ -61,17 +134,18 @@ fn collect_used_variables(stmt: &HirStatement, used: &mut HashSet<String>) {
             collect_used_variables_expr(expr, used);
         },
         
-        HirStatement::Return(expr_opt) => {
+        HirStatement::Return(expr_opt, _) => {
             if let Some(expr) = expr_opt {
                 collect_used_variables_expr(expr, used);
             }
         },
         
-        HirStatement::Print(expr) => {
+        HirStatement::Print(expr, _) => {
             collect_used_variables_expr(expr, used);
         },
         
-        HirStatement::Block(statements) => {
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => {
             for stmt in statements {
                 collect_used_variables(stmt, used);
             }
@@ -89,7 +163,7 @@ fn collect_used_variables(stmt: &HirStatement, used: &mut HashSet<String>) {
             }
         },
         
-        HirStatement::If { condition, then_branch, else_branch } => {
+        HirStatement::If { condition, then_branch, else_branch, .. } => {
             collect_used_variables_expr(condition, used);
             collect_used_variables(then_branch, used);
             if let Some(else_stmt) = else_branch {
@@ -97,17 +171,20 @@ fn collect_used_variables(stmt: &HirStatement, used: &mut HashSet<String>) {
             }
         },
         
-        HirStatement::While { condition, body } => {
+        HirStatement::While { condition, body, .. } => {
             collect_used_variables_expr(condition, used);
             collect_used_variables(body, used);
         },
+        
+        HirStatement::Break(_) |
+        HirStatement::Continue(_) => {},
     }
 }
 
 /// Collect variable uses from an expression
 fn collect_used_variables_expr(expr: &HirExpression, used: &mut HashSet<String>) {
     match expr {
-        HirExpression::Variable(name, _, _) => {
+        HirExpression::Variable(name, _, _, _) => {
             used.insert(name.clone());
         },
         
@@ -133,11 +210,12 @@ fn collect_used_variables_expr(expr: &HirExpression, used: &mut HashSet<String>)
             collect_used_variables_expr(expr, used);
         },
         
-        HirExpression::Peak(expr) => {
+        HirExpression::Peak(expr, _) => {
             collect_used_variables_expr(expr, used);
         },
         
-        HirExpression::Clone(expr) => {
+        HirExpression::Clone(expr, _) |
+        HirExpression::Consume(expr, _) => {
             collect_used_variables_expr(expr, used);
         },
         
@@ -147,39 +225,56 @@ fn collect_used_variables_expr(expr: &HirExpression, used: &mut HashSet<String>)
 }
 
 /// Recursively eliminate dead code in statement blocks
-fn eliminate_dead_code_in_statement(stmt: &mut HirStatement, used_variables: &HashSet<String>) {
+fn eliminate_dead_code_in_statement(stmt: &mut HirStatement, used_variables: &HashSet<String>, pure: &HashSet<String>) {
     match stmt {
-        HirStatement::Block(statements) => {
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => {
             // Remove unused variable declarations
-            statements.retain(|stmt| {
-                match stmt {
-                    HirStatement::Declaration(var) => used_variables.contains(&var.name),
-                    _ => true,
-                }
-            });
+            retain_live_statements(statements, used_variables, pure);
             
             // Recursively process the remaining statements
             for sub_stmt in statements.iter_mut() {
-                eliminate_dead_code_in_statement(sub_stmt, used_variables);
+                eliminate_dead_code_in_statement(sub_stmt, used_variables, pure);
             }
         },
         
         HirStatement::Function(func) => {
             // Process function body
+            retain_live_statements(&mut func.body, used_variables, pure);
             for sub_stmt in func.body.iter_mut() {
-                eliminate_dead_code_in_statement(sub_stmt, used_variables);
+                eliminate_dead_code_in_statement(sub_stmt, used_variables, pure);
+            }
+        },
+        
+        // A constant condition (usually left by constant folding) selects one branch
+        HirStatement::If { condition: HirExpression::Boolean(taken, _), then_branch, else_branch, span } => {
+            let span = *span;
+            let mut branch = if *taken {
+                Some(std::mem::replace(then_branch.as_mut(), HirStatement::Block(Vec::new(), span)))
+            } else {
+                else_branch.take().map(|stmt| *stmt)
+            };
+            
+            if let Some(ref mut branch) = branch {
+                eliminate_dead_code_in_statement(branch, used_variables, pure);
             }
+            *stmt = branch.unwrap_or(HirStatement::Block(Vec::new(), span));
         },
         
         HirStatement::If { then_branch, else_branch, .. } => {
-            eliminate_dead_code_in_statement(then_branch, used_variables);
+            eliminate_dead_code_in_statement(then_branch, used_variables, pure);
             if let Some(else_stmt) = else_branch {
-                eliminate_dead_code_in_statement(else_stmt, used_variables);
+                eliminate_dead_code_in_statement(else_stmt, used_variables, pure);
             }
         },
         
+        // A loop whose condition is false never runs
+        HirStatement::While { condition: HirExpression::Boolean(false, _), span, .. } => {
+            *stmt = HirStatement::Block(Vec::new(), *span);
+        },
+        
         HirStatement::While { body, .. } => {
-            eliminate_dead_code_in_statement(body, used_variables);
+            eliminate_dead_code_in_statement(body, used_variables, pure);
         },
         
         _ => {},
@@ -2,49 +2,110 @@
 //!
 //! This module implements transformations that simplify complex HIR constructs
 //! into simpler, more primitive operations.
+//!
+//! Compound assignments such as `x += n` become `x = x + n`. Since the
+//! rewritten assignment reads its own target, the target must be readable;
+//! a write-only variable is reported here rather than by every later pass.
 
+use crate::hir::diagnostics::{Diagnostic, DiagnosticReporter};
 use crate::hir::types::*;
+use front_end::codes::permission;
+use front_end::token::TokenType;
+use front_end::types::{Permission, Type};
+use std::collections::HashMap;
 
-/// Desugar a HIR program
-pub fn desugar_program(program: &mut HirProgram) {
+/// Desugar a HIR program, reporting compound assignments to variables
+/// that cannot be read
+pub fn desugar_program(program: &mut HirProgram) -> DiagnosticReporter {
     let mut desugarer = Desugarer::new();
     
     // Process each statement in the program
-    for i in 0..program.statements.len() {
-        if let Some(statement) = program.statements.get(i).cloned() {
-            let desugared = desugarer.desugar_statement(&statement);
-            
-            // Replace with desugared version if it changed
-            if !desugarer.is_same_statement(&statement, &desugared) {
-                program.statements[i] = desugared;
-            }
-        }
+    for statement in program.statements.iter_mut() {
+        *statement = desugarer.desugar_statement(statement);
     }
+    
+    desugarer.reporter
 }
 
 /// Helper struct for desugaring operations
-struct Desugarer;
+struct Desugarer {
+    /// Type and permissions of the variables in scope, innermost scope last
+    scopes: Vec<HashMap<String, (Type, Vec<Permission>)>>,
+    
+    /// Problems found while desugaring
+    reporter: DiagnosticReporter,
+}
 
 impl Desugarer {
     /// Create a new desugarer
     pub fn new() -> Self {
-        Self
+        Self {
+            scopes: vec![HashMap::new()],
+            reporter: DiagnosticReporter::new(),
+        }
+    }
+    
+    /// Record a variable declared in the current scope
+    fn declare(&mut self, name: &str, typ: &Type, permissions: &[Permission]) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), (typ.clone(), permissions.to_vec()));
+        }
     }
     
-    /// Check if two statements are the same (to avoid unnecessary replacements)
-    fn is_same_statement(&self, original: &HirStatement, desugared: &HirStatement) -> bool {
-        // This is a simplistic check - in a real compiler we'd have a more
-        // comprehensive equality check or add a PartialEq implementation
-        match (original, desugared) {
-            (HirStatement::Declaration(_), HirStatement::Declaration(_)) => true,
-            (HirStatement::Assignment(_), HirStatement::Assignment(_)) => true,
-            (HirStatement::Expression(_), HirStatement::Expression(_)) => true,
-            (HirStatement::Return(_), HirStatement::Return(_)) => true,
-            (HirStatement::Print(_), HirStatement::Print(_)) => true,
-            (HirStatement::Function(_), HirStatement::Function(_)) => true,
-            (HirStatement::Block(a), HirStatement::Block(b)) => a.len() == b.len(),
-            _ => false,
+    /// Type and permissions of the innermost variable with the given name
+    fn lookup(&self, name: &str) -> Option<&(Type, Vec<Permission>)> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+    
+    /// Desugar a statement in a scope of its own
+    fn desugar_scoped(&mut self, stmt: &HirStatement) -> HirStatement {
+        self.scopes.push(HashMap::new());
+        let desugared = self.desugar_statement(stmt);
+        self.scopes.pop();
+        desugared
+    }
+    
+    /// Rewrite `target op= value` to `target = target op value`
+    fn desugar_compound_assignment(&mut self, assign: &HirAssignment, operator: &TokenType) -> HirStatement {
+        let location = assign.span;
+        let (typ, permissions) = match self.lookup(&assign.target) {
+            Some((typ, permissions)) => (typ.clone(), Some(permissions.clone())),
+            None => (Type::Int, None),
+        };
+        
+        // Unknown names are left to name resolution
+        if let Some(permissions) = permissions {
+            let readable = permissions.contains(&Permission::Read) || permissions.contains(&Permission::Reads);
+            if !readable {
+                let mut diagnostic = Diagnostic::error(format!(
+                        "Cannot use '{}=' on '{}' - write-only reference cannot read its own value",
+                        operator.operator_symbol(), assign.target
+                    ))
+                    .with_code(permission::MISSING_READ)
+                    .with_help(format!(
+                        "declare '{}' with read permission, or assign it without reading: {} = ...",
+                        assign.target, assign.target
+                    ));
+                if let Some(location) = location.known() {
+                    diagnostic = diagnostic.with_primary(location.to_span(), "");
+                }
+                self.reporter.add(diagnostic);
+            }
         }
+        
+        HirStatement::Assignment(HirAssignment {
+            target: assign.target.clone(),
+            value: HirExpression::Binary {
+                left: Box::new(HirExpression::Variable(assign.target.clone(), typ.clone(), location, assign.symbol)),
+                operator: operator.clone(),
+                right: Box::new(self.desugar_expression(&assign.value)),
+                result_type: typ,
+                span: location,
+            },
+            operator: None,
+            symbol: assign.symbol,
+            span: location,
+        })
     }
     
     /// Desugar a statement
@@ -54,63 +115,109 @@ impl Desugarer {
                 // For declarations with initializers, we could split them into
                 // declaration and assignment for simplicity
                 let initializer = var.initializer.as_ref().map(|expr| self.desugar_expression(expr));
+                self.declare(&var.name, &var.typ, &var.permissions);
                 
                 HirStatement::Declaration(HirVariable {
                     name: var.name.clone(),
                     typ: var.typ.clone(),
                     permissions: var.permissions.clone(),
                     initializer,
-                    location: var.location.clone(),
+                    span: var.span,
+                    actor: var.actor.clone(),
+                    symbol: var.symbol,
                 })
             },
             
-            HirStatement::Assignment(assign) => {
-                HirStatement::Assignment(HirAssignment {
+            HirStatement::Assignment(assign) => match &assign.operator {
+                Some(operator) => self.desugar_compound_assignment(assign, operator),
+                None => HirStatement::Assignment(HirAssignment {
                     target: assign.target.clone(),
                     value: self.desugar_expression(&assign.value),
-                })
+                    operator: None,
+                    symbol: assign.symbol,
+                    span: assign.span,
+                }),
             },
             
             HirStatement::Expression(expr) => {
                 HirStatement::Expression(self.desugar_expression(expr))
             },
 
-            HirStatement::Print(expr) => {
-                HirStatement::Print(self.desugar_expression(expr))
+            HirStatement::Print(expr, span) => {
+                HirStatement::Print(self.desugar_expression(expr), *span)
             },
             
-            HirStatement::Block(statements) => {
+            HirStatement::Block(statements, span) => {
+                self.scopes.push(HashMap::new());
                 let desugared_stmts: Vec<HirStatement> = statements
                     .iter()
                     .map(|s| self.desugar_statement(s))
                     .collect();
+                self.scopes.pop();
                 
-                HirStatement::Block(desugared_stmts)
+                HirStatement::Block(desugared_stmts, *span)
+            },
+            
+            HirStatement::Atomic(statements, span) => {
+                self.scopes.push(HashMap::new());
+                let desugared_stmts: Vec<HirStatement> = statements
+                    .iter()
+                    .map(|s| self.desugar_statement(s))
+                    .collect();
+                self.scopes.pop();
+                
+                HirStatement::Atomic(desugared_stmts, *span)
             },
             
             HirStatement::Function(func) => {
-                // Desugar function body statements
+                // Desugar function body statements, with the parameters in scope
+                self.scopes.push(HashMap::new());
+                for param in &func.parameters {
+                    self.declare(&param.name, &param.typ, &param.permissions);
+                }
                 let desugared_body: Vec<HirStatement> = func.body
                     .iter()
                     .map(|s| self.desugar_statement(s))
                     .collect();
+                self.scopes.pop();
                 
                 HirStatement::Function(HirFunction {
                     name: func.name.clone(),
+                    type_params: func.type_params.clone(),
                     parameters: func.parameters.clone(),
                     body: desugared_body,
                     return_type: func.return_type.clone(),
+                    is_behavior: func.is_behavior,
+                    actor: func.actor.clone(),
+                    span: func.span,
                 })
             },
             
-            HirStatement::Return(expr_opt) => {
+            HirStatement::Return(expr_opt, span) => {
                 // Process Option<HirExpression> correctly
                 let desugared = expr_opt.as_ref().map(|expr| self.desugar_expression(expr));
-                HirStatement::Return(desugared)
+                HirStatement::Return(desugared, *span)
             },
             
-            // Handle any other statement types
-            _ => stmt.clone(),
+            HirStatement::If { condition, then_branch, else_branch, span } => {
+                HirStatement::If {
+                    condition: self.desugar_expression(condition),
+                    then_branch: Box::new(self.desugar_scoped(then_branch)),
+                    else_branch: else_branch.as_ref().map(|branch| Box::new(self.desugar_scoped(branch))),
+                    span: *span,
+                }
+            },
+            
+            HirStatement::While { condition, body, span } => {
+                HirStatement::While {
+                    condition: self.desugar_expression(condition),
+                    body: Box::new(self.desugar_scoped(body)),
+                    span: *span,
+                }
+            },
+            
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => stmt.clone(),
         }
     }
     
@@ -118,22 +225,22 @@ impl Desugarer {
     pub fn desugar_expression(&mut self, expr: &HirExpression) -> HirExpression {
         match expr {
             HirExpression::Integer(val, loc) => {
-                HirExpression::Integer(*val, loc.clone())
+                HirExpression::Integer(*val, *loc)
             },
             
-            HirExpression::Boolean(val) => {
-                HirExpression::Boolean(*val)
+            HirExpression::Boolean(val, loc) => {
+                HirExpression::Boolean(*val, *loc)
             },
             
-            HirExpression::String(val) => {
-                HirExpression::String(val.clone())
+            HirExpression::String(val, loc) => {
+                HirExpression::String(val.clone(), *loc)
             },
             
-            HirExpression::Variable(name, typ, loc) => {
-                HirExpression::Variable(name.clone(), typ.clone(), loc.clone())
+            HirExpression::Variable(name, typ, loc, symbol) => {
+                HirExpression::Variable(name.clone(), typ.clone(), *loc, *symbol)
             },
             
-            HirExpression::Binary { left, operator, right, result_type } => {
+            HirExpression::Binary { left, operator, right, result_type, span } => {
                 // Desugar nested binary expressions
                 let desugared_left = Box::new(self.desugar_expression(left));
                 let desugared_right = Box::new(self.desugar_expression(right));
@@ -143,10 +250,11 @@ impl Desugarer {
                     operator: operator.clone(),
                     right: desugared_right,
                     result_type: result_type.clone(),
+                    span: *span,
                 }
             },
             
-            HirExpression::Call { function, arguments, result_type } => {
+            HirExpression::Call { function, arguments, result_type, span } => {
                 // Desugar function call arguments
                 let desugared_args: Vec<HirExpression> = arguments
                     .iter()
@@ -157,31 +265,38 @@ impl Desugarer {
                     function: function.clone(),
                     arguments: desugared_args,
                     result_type: result_type.clone(),
+                    span: *span,
                 }
             },
             
-            HirExpression::Conditional { condition, then_expr, else_expr, result_type } => {
+            HirExpression::Conditional { condition, then_expr, else_expr, result_type, span } => {
                 HirExpression::Conditional {
                     condition: Box::new(self.desugar_expression(condition)),
                     then_expr: Box::new(self.desugar_expression(then_expr)),
                     else_expr: Box::new(self.desugar_expression(else_expr)),
                     result_type: result_type.clone(),
+                    span: *span,
                 }
             },
             
-            HirExpression::Cast { expr, target_type } => {
+            HirExpression::Cast { expr, target_type, span } => {
                 HirExpression::Cast {
                     expr: Box::new(self.desugar_expression(expr)),
                     target_type: target_type.clone(),
+                    span: *span,
                 }
             },
             
-            HirExpression::Peak(expr) => {
-                HirExpression::Peak(Box::new(self.desugar_expression(expr)))
+            HirExpression::Peak(expr, span) => {
+                HirExpression::Peak(Box::new(self.desugar_expression(expr)), *span)
+            },
+            
+            HirExpression::Clone(expr, span) => {
+                HirExpression::Clone(Box::new(self.desugar_expression(expr)), *span)
             },
             
-            HirExpression::Clone(expr) => {
-                HirExpression::Clone(Box::new(self.desugar_expression(expr)))
+            HirExpression::Consume(expr, span) => {
+                HirExpression::Consume(Box::new(self.desugar_expression(expr)), *span)
             },
         }
     }
@@ -0,0 +1,137 @@
+//! Effect inference for functions
+//!
+//! A function is pure when calling it has no effect beyond computing its
+//! result: it never assigns or consumes a variable it did not declare
+//! itself (parameters included), never prints, never sends a message to a
+//! behavior and only calls other pure functions. The optimizer may fold,
+//! reorder or drop calls to pure functions. Mutually recursive functions
+//! are pure unless one of them has an effect.
+
+use crate::hir::types::*;
+use crate::hir::visitor::{self, HirVisitor};
+use std::collections::{HashMap, HashSet};
+
+/// The names of every pure top-level function in a program
+pub fn pure_functions(program: &HirProgram) -> HashSet<String> {
+    let mut calls = HashMap::new();
+    for stmt in &program.statements {
+        if let HirStatement::Function(func) = stmt {
+            let mut finder = EffectFinder::new();
+            for stmt in &func.body {
+                finder.visit_statement(stmt);
+            }
+            if !func.is_behavior && !finder.effectful {
+                calls.insert(func.name.clone(), finder.calls);
+            }
+        }
+    }
+
+    // Start from every function without direct effects and drop those
+    // calling something impure until nothing changes
+    let mut pure: HashSet<String> = calls.keys().cloned().collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (name, callees) in &calls {
+            if pure.contains(name) && !callees.iter().all(|callee| pure.contains(callee)) {
+                pure.remove(name);
+                changed = true;
+            }
+        }
+    }
+    pure
+}
+
+/// Whether an expression calls a function outside the given pure set
+pub fn calls_impure(expr: &HirExpression, pure: &HashSet<String>) -> bool {
+    struct ImpureCall<'a> {
+        pure: &'a HashSet<String>,
+        found: bool,
+    }
+
+    impl HirVisitor for ImpureCall<'_> {
+        fn visit_expression(&mut self, expr: &HirExpression) {
+            if let HirExpression::Call { function, .. } = expr {
+                self.found |= !self.pure.contains(function);
+            }
+            visitor::walk_expression(self, expr);
+        }
+    }
+
+    let mut finder = ImpureCall { pure, found: false };
+    finder.visit_expression(expr);
+    finder.found
+}
+
+/// Looks for direct effects in a function body and collects its callees
+struct EffectFinder {
+    /// Names declared by the function, innermost scope last
+    scopes: Vec<HashSet<String>>,
+
+    /// Functions called from the body
+    calls: HashSet<String>,
+
+    /// Whether the body has an effect of its own
+    effectful: bool,
+}
+
+impl EffectFinder {
+    /// Parameters belong to the caller, so they are never in scope
+    fn new() -> Self {
+        Self {
+            scopes: vec![HashSet::new()],
+            calls: HashSet::new(),
+            effectful: false,
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn visit_scoped(&mut self, stmt: &HirStatement) {
+        self.scopes.push(HashSet::new());
+        visitor::walk_statement(self, stmt);
+        self.scopes.pop();
+    }
+}
+
+impl HirVisitor for EffectFinder {
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                visitor::walk_statement(self, stmt);
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(var.name.clone());
+                }
+            },
+            HirStatement::Assignment(assign) => {
+                if !self.is_local(&assign.target) {
+                    self.effectful = true;
+                }
+                visitor::walk_statement(self, stmt);
+            },
+            HirStatement::Print(_, _) => self.effectful = true,
+            // Nested functions run only when called
+            HirStatement::Function(_) => {},
+            HirStatement::Block(_, _) |
+            HirStatement::Atomic(_, _) => self.visit_scoped(stmt),
+            _ => visitor::walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        match expr {
+            HirExpression::Call { function, .. } => {
+                self.calls.insert(function.clone());
+            },
+            // Moving out of a caller's variable changes the caller
+            HirExpression::Consume(inner, _)
+                if !matches!(inner.as_ref(), HirExpression::Variable(name, _, _, _) if self.is_local(name)) => {
+                self.effectful = true;
+            },
+            _ => {},
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
@@ -0,0 +1,206 @@
+//! Escape analysis for function locals and parameters
+//!
+//! A variable escapes when a reference to it can outlive the function that
+//! declares it: it is returned, assigned to a variable declared outside the
+//! function, sent to a behavior, captured by a nested function, or passed
+//! to a parameter its callee lets escape in turn. A local initialized or
+//! assigned from another variable without a copy (`peak x` or plain `x`)
+//! aliases it, so when the alias escapes, so does the original. `clone`
+//! makes an independent copy and stops the chain.
+//!
+//! Variables are tracked by name, so shadowed declarations share a result;
+//! this only errs towards escaping. Lowering keeps values that do not
+//! escape on the stack.
+
+use crate::hir::types::*;
+use crate::hir::visitor::{self, HirVisitor};
+use std::collections::{HashMap, HashSet};
+
+/// Which variables of each function escape it
+#[derive(Debug, Clone, Default)]
+pub struct EscapeAnalysis {
+    /// Escaping locals and parameters by function
+    variables: HashMap<String, HashSet<String>>,
+
+    /// Indices of the escaping parameters by function
+    parameters: HashMap<String, HashSet<usize>>,
+}
+
+impl EscapeAnalysis {
+    /// Analyze every top-level function. A function forwarding a parameter
+    /// to another function depends on that function's result, so this
+    /// repeats until nothing changes.
+    pub fn analyze(program: &HirProgram) -> Self {
+        let functions: Vec<&HirFunction> = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                HirStatement::Function(func) => Some(func),
+                _ => None,
+            })
+            .collect();
+        let behaviors: HashSet<&str> = functions.iter()
+            .filter(|func| func.is_behavior)
+            .map(|func| func.name.as_str())
+            .collect();
+
+        let mut analysis = Self::default();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for func in &functions {
+                let escaping = escaping_variables(func, &analysis.parameters, &behaviors);
+                let parameters: HashSet<usize> = func.parameters.iter()
+                    .enumerate()
+                    .filter(|(_, param)| escaping.contains(&param.name))
+                    .map(|(i, _)| i)
+                    .collect();
+                if analysis.parameters.get(&func.name) != Some(&parameters) {
+                    analysis.parameters.insert(func.name.clone(), parameters);
+                    changed = true;
+                }
+                analysis.variables.insert(func.name.clone(), escaping);
+            }
+        }
+        analysis
+    }
+
+    /// Whether a local or parameter of `function` escapes it
+    pub fn escapes(&self, function: &str, name: &str) -> bool {
+        self.variables.get(function).is_some_and(|names| names.contains(name))
+    }
+
+    /// Whether `function` lets the parameter at `index` escape, so the
+    /// caller's argument outlives the call
+    pub fn stores_alias(&self, function: &str, index: usize) -> bool {
+        self.parameters.get(function).is_some_and(|params| params.contains(&index))
+    }
+}
+
+/// The variable an argument or initializer refers to without copying it
+pub fn aliased_variable(expr: &HirExpression) -> Option<&str> {
+    match expr {
+        HirExpression::Variable(name, _, _, _) => Some(name),
+        HirExpression::Peak(inner, _) => match inner.as_ref() {
+            HirExpression::Variable(name, _, _, _) => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The locals and parameters of `func` that escape it, given what is known
+/// about the functions it calls
+fn escaping_variables(
+    func: &HirFunction,
+    known: &HashMap<String, HashSet<usize>>,
+    behaviors: &HashSet<&str>,
+) -> HashSet<String> {
+    let mut finder = EscapeFinder {
+        known,
+        behaviors,
+        origins: HashMap::new(),
+        escaping: HashSet::new(),
+    };
+    for param in &func.parameters {
+        finder.declare(&param.name, None);
+    }
+    for stmt in &func.body {
+        finder.visit_statement(stmt);
+    }
+    finder.escaping
+}
+
+struct EscapeFinder<'a> {
+    known: &'a HashMap<String, HashSet<usize>>,
+    behaviors: &'a HashSet<&'a str>,
+
+    /// The variables each name declared in the function may refer to,
+    /// itself included
+    origins: HashMap<String, HashSet<String>>,
+
+    escaping: HashSet<String>,
+}
+
+impl EscapeFinder<'_> {
+    fn declare(&mut self, name: &str, initializer: Option<&HirExpression>) {
+        let mut origins = HashSet::from([name.to_string()]);
+        origins.extend(self.origins_of(initializer));
+        self.origins.insert(name.to_string(), origins);
+    }
+
+    /// The variables an expression refers to without copying them
+    fn origins_of(&self, expr: Option<&HirExpression>) -> HashSet<String> {
+        expr.and_then(aliased_variable)
+            .and_then(|name| self.origins.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn escape(&mut self, expr: &HirExpression) {
+        let origins = self.origins_of(Some(expr));
+        self.escaping.extend(origins);
+    }
+
+    /// Mark every variable of this function a nested function refers to
+    fn capture(&mut self, nested: &HirFunction) {
+        #[derive(Default)]
+        struct References(HashSet<String>);
+
+        impl HirVisitor for References {
+            fn visit_expression(&mut self, expr: &HirExpression) {
+                if let HirExpression::Variable(name, _, _, _) = expr {
+                    self.0.insert(name.clone());
+                }
+                visitor::walk_expression(self, expr);
+            }
+        }
+
+        let mut references = References::default();
+        for stmt in &nested.body {
+            references.visit_statement(stmt);
+        }
+        for name in references.0 {
+            let shadowed = nested.parameters.iter().any(|param| param.name == name);
+            if let Some(origins) = self.origins.get(&name).filter(|_| !shadowed) {
+                self.escaping.extend(origins.iter().cloned());
+            }
+        }
+    }
+}
+
+impl HirVisitor for EscapeFinder<'_> {
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                visitor::walk_statement(self, stmt);
+                self.declare(&var.name, var.initializer.as_ref());
+            },
+            HirStatement::Assignment(assign) => {
+                visitor::walk_statement(self, stmt);
+                let aliased = self.origins_of(Some(&assign.value));
+                match self.origins.get_mut(&assign.target) {
+                    Some(origins) => origins.extend(aliased),
+                    // The target lives outside the function
+                    None => self.escaping.extend(aliased),
+                }
+            },
+            HirStatement::Return(Some(expr), _) => {
+                visitor::walk_statement(self, stmt);
+                self.escape(expr);
+            },
+            HirStatement::Function(nested) => self.capture(nested),
+            _ => visitor::walk_statement(self, stmt),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        if let HirExpression::Call { function, arguments, .. } = expr {
+            let sends = self.behaviors.contains(function.as_str());
+            for (i, arg) in arguments.iter().enumerate() {
+                if sends || self.known.get(function).is_some_and(|params| params.contains(&i)) {
+                    self.escape(arg);
+                }
+            }
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
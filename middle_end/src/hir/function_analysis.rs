@@ -1,261 +1,233 @@
 //! Function Analysis for HIR
 //!
-//! This module handles function-specific analysis including:
-//! - Permission tracking across function calls
-//! - Permissions propagation for function parameters and return values
-//! - Detection of capability violations in function calls
+//! This module checks function calls against the callee's signature:
+//! - The number and types of the arguments
+//! - Permissions an argument needs for the parameter it is passed to
+//! - Parameters that a function stores an alias of, followed through calls
+//!
+//! A parameter escapes when the function returns it, assigns it to a
+//! variable declared outside the function, sends it to a behavior, or passes
+//! it on to a parameter that escapes in turn (see `escape`). Escapes are
+//! computed for every function up front, so a caller that hands a variable
+//! to such a parameter knows the variable is aliased from then on, even
+//! though the alias lives in another function.
+//!
+//! Behaviors (`on` functions) run concurrently with their caller, so their
+//! parameters and the arguments sent to them must be sendable: exclusive,
+//! immutable, or a fresh value such as a `clone`.
 
+use crate::hir::diagnostics::ToDiagnostic;
+use crate::hir::escape::{aliased_variable, EscapeAnalysis};
+use crate::hir::passes::{AnalysisContext, AnalysisPass, VariableInfo};
+use crate::hir::permissions::{is_sendable, PermissionError};
 use crate::hir::types::*;
-use crate::hir::permissions::{PermissionChecker, PermissionError};
-use front_end::types::{Permission, Type};
+use crate::hir::validation::call_site_errors;
+use front_end::intern::Name;
+use front_end::types::Permission;
 use std::collections::{HashMap, HashSet};
 
-/// Function permissions context
-pub struct FunctionPermissionsContext {
-    /// Maps function names to their signature permissions
-    function_signatures: HashMap<String, FunctionSignature>,
-    
-    /// Permission errors found during analysis
-    errors: Vec<PermissionError>,
-}
+/// Checks call arity, argument types and the permissions of arguments
+#[derive(Default)]
+pub struct CallSignaturePass {
+    /// Names of the `on` behaviors
+    behaviors: HashSet<String>,
+
+    /// Which parameters each function stores an alias of
+    escapes: EscapeAnalysis,
+
+    /// Variables of the function being analyzed that a call has stored an
+    /// alias of, with the function that stored it
+    captured: HashMap<Name, String>,
 
-/// A function signature with permission information
-#[derive(Clone, Debug)]
-pub struct FunctionSignature {
-    /// Function name
-    name: String,
-    
-    /// Parameter types and permissions
-    parameters: Vec<(String, Type, Vec<Permission>)>,
-    
-    /// Return type and permissions
-    return_type: Option<(Type, Vec<Permission>)>,
+    /// Captured variables of the enclosing functions, innermost last
+    enclosing: Vec<HashMap<Name, String>>,
 }
 
-impl FunctionPermissionsContext {
-    /// Create a new function permissions context
-    pub fn new() -> Self {
-        Self {
-            function_signatures: HashMap::new(),
-            errors: Vec::new(),
-        }
+impl AnalysisPass for CallSignaturePass {
+    fn name(&self) -> &'static str {
+        "calls"
     }
-    
-    /// Register a function signature
-    pub fn register_function(&mut self, func: &HirFunction) {
-        let mut params = Vec::new();
-        
-        for param in &func.parameters {
-            params.push((
-                param.name.clone(),
-                param.typ.clone(),
-                param.permissions.clone()
-            ));
-        }
-        
-        // For now, assumed permissions on return values
-        // In a full implementation, we'd extract these from the function signature
-        let return_info = func.return_type.clone().map(|typ| {
-            // Default to read+write (isolated) permissions for return values
-            // This is similar to Pony's approach where returned values default to iso
-            let permissions = vec![Permission::Read, Permission::Write];
-            (typ, permissions)
-        });
-        
-        let signature = FunctionSignature {
-            name: func.name.clone(),
-            parameters: params,
-            return_type: return_info,
-        };
-        
-        self.function_signatures.insert(func.name.clone(), signature);
+
+    fn start(&mut self, ctx: &mut AnalysisContext) {
+        let program = ctx.program();
+        self.behaviors = program.statements.iter()
+            .filter_map(|stmt| match stmt {
+                HirStatement::Function(func) if func.is_behavior => Some(func.name.clone()),
+                _ => None,
+            })
+            .collect();
+        self.escapes = EscapeAnalysis::analyze(program);
     }
-    
-    /// Analyze function calls in a program
-    pub fn analyze_program(&mut self, program: &HirProgram) -> Vec<PermissionError> {
-        // First register all function signatures
-        for stmt in &program.statements {
-            if let HirStatement::Function(ref func) = stmt {
-                self.register_function(func);
-            }
-        }
-        
-        // Then analyze function bodies
-        for stmt in &program.statements {
-            if let HirStatement::Function(ref func) = stmt {
-                self.analyze_function_body(func);
-            }
-        }
-        
-        // Run a second phase of analysis for call sites
-        for stmt in &program.statements {
-            self.analyze_statement_for_calls(stmt);
+
+    fn enter_function(&mut self, func: &HirFunction, ctx: &mut AnalysisContext) {
+        self.enclosing.push(std::mem::take(&mut self.captured));
+        if func.is_behavior {
+            check_behavior_parameters(func, ctx);
         }
-        
-        self.errors.clone()
     }
-    
-    /// Analyze function body for permission issues
-    fn analyze_function_body(&mut self, func: &HirFunction) {
-        // Create a permission checker for this function context
-        let mut checker = PermissionChecker::new();
-        
-        // Register parameters with their permissions
-        for param in &func.parameters {
-            checker.register_parameter(&param.name, &param.permissions);
-        }
-        
-        // Check the body statements
-        for stmt in &func.body {
-            checker.check_statement(stmt);
-        }
-        
-        // Collect any errors
-        self.errors.extend(checker.get_errors());
+
+    fn exit_function(&mut self, _func: &HirFunction, _ctx: &mut AnalysisContext) {
+        self.captured = self.enclosing.pop().unwrap_or_default();
     }
-    
-    /// Analyze a statement for function calls
-    fn analyze_statement_for_calls(&mut self, stmt: &HirStatement) {
+
+    fn exit_statement(&mut self, stmt: &HirStatement, _ctx: &mut AnalysisContext) {
+        // The variable holds a new value the stored alias does not see
         match stmt {
-            HirStatement::Expression(expr) => {
-                self.analyze_expression_for_calls(expr);
-            },
             HirStatement::Assignment(assign) => {
-                self.analyze_expression_for_calls(&assign.value);
-            },
-            HirStatement::Print(expr) => {
-                self.analyze_expression_for_calls(expr);
-            },
-            HirStatement::Block(statements) => {
-                for stmt in statements {
-                    self.analyze_statement_for_calls(stmt);
-                }
-            },
-            HirStatement::Function(func) => {
-                for stmt in &func.body {
-                    self.analyze_statement_for_calls(stmt);
-                }
-            },
-            HirStatement::Return(expr_opt) => {
-                if let Some(expr) = expr_opt {
-                    self.analyze_expression_for_calls(expr);
-                }
+                self.captured.remove(&Name::new(&assign.target));
             },
             HirStatement::Declaration(var) => {
-                if let Some(init) = &var.initializer {
-                    self.analyze_expression_for_calls(init);
-                }
+                self.captured.remove(&Name::new(&var.name));
             },
-            // Other statement types don't contain function calls
             _ => {},
         }
     }
-    
-    /// Analyze an expression for function calls
-    fn analyze_expression_for_calls(&mut self, expr: &HirExpression) {
-        match expr {
-            HirExpression::Call { function, arguments, .. } => {
-                // Check if the function signature is registered
-                if let Some(signature) = self.function_signatures.get(function) {
-                    // Clone the signature to avoid borrowing conflicts
-                    let signature_clone = signature.clone();
-                    // Check argument permissions match parameter requirements
-                    self.check_argument_permissions(function, arguments, &signature_clone);
-                } else {
-                    // Unknown function
-                    self.errors.push(PermissionError {
-                        message: format!("Call to unknown function '{}'", function),
-                        location: None,
-                    });
-                }
-            },
-            HirExpression::Binary { left, right, .. } => {
-                self.analyze_expression_for_calls(left);
-                self.analyze_expression_for_calls(right);
-            },
-            HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
-                self.analyze_expression_for_calls(condition);
-                self.analyze_expression_for_calls(then_expr);
-                self.analyze_expression_for_calls(else_expr);
-            },
-            HirExpression::Cast { expr, .. } => {
-                self.analyze_expression_for_calls(expr);
-            },
-            HirExpression::Peak(expr) => {
-                self.analyze_expression_for_calls(expr);
-            },
-            HirExpression::Clone(expr) => {
-                self.analyze_expression_for_calls(expr);
-            },
-            // Literals and variables don't contain function calls
-            _ => {},
+
+    fn check_expression(&mut self, expr: &HirExpression, ctx: &mut AnalysisContext) {
+        if let HirExpression::Call { function, arguments, .. } = expr {
+            self.check_call(expr, function, arguments, ctx);
         }
     }
-    
-    /// Check argument permissions against parameter requirements
-    fn check_argument_permissions(
-        &mut self, 
-        function_name: &str,
-        arguments: &[HirExpression],
-        signature: &FunctionSignature
-    ) {
-        // This is where we'd implement Pony-like permission compatibility rules
-        
-        // Check if we have the right number of arguments
+}
+
+impl CallSignaturePass {
+    /// Check one call against the callee's signature
+    fn check_call(&mut self, call: &HirExpression, function: &str, arguments: &[HirExpression], ctx: &mut AnalysisContext) {
+        let Some(signature) = ctx.signature(function) else {
+            ctx.report_permission(PermissionError::UnknownFunction {
+                name: function.to_string(),
+                span: call.location().copied(),
+            });
+            return;
+        };
+
         if arguments.len() != signature.parameters.len() {
-            self.errors.push(PermissionError {
-                message: format!(
-                    "Function '{}' expects {} arguments, but {} were provided",
-                    function_name,
-                    signature.parameters.len(),
-                    arguments.len()
-                ),
-                location: None,
+            ctx.report_permission(PermissionError::ArityMismatch {
+                function: function.to_string(),
+                expected: signature.parameters.len(),
+                found: arguments.len(),
+                span: arguments.iter().find_map(|arg| arg.location()).copied(),
             });
             return;
         }
-        
-        // For each argument, check permission compatibility
+
+        let can_write = |name: &str| ctx.lookup(name).is_some_and(VariableInfo::can_write);
+        let errors = call_site_errors(function, arguments, ctx.program(), &can_write);
+        for error in errors {
+            ctx.report(error.to_diagnostic());
+        }
+
+        for (arg, param) in arguments.iter().zip(&signature.parameters) {
+            if let HirExpression::Variable(name, _, location, _) = arg {
+                self.check_variable_argument(name, location, param, function, ctx);
+            }
+        }
+
+        if self.behaviors.contains(function) {
+            for arg in arguments {
+                check_sendable_argument(function, arg, ctx);
+            }
+        }
+
+        // Arguments the callee keeps an alias of stay aliased after the call
         for (i, arg) in arguments.iter().enumerate() {
-            if let HirExpression::Variable(name, _, _) = arg {
-                // Example check for an arg that's a variable reference
-                self.check_variable_permission_for_arg(
-                    name, 
-                    &signature.parameters[i].2,
-                    function_name,
-                    i
-                );
+            if let Some(name) = aliased_variable(arg) {
+                if self.escapes.stores_alias(function, i) {
+                    self.captured.insert(Name::new(name), function.to_string());
+                }
             }
         }
     }
-    
-    /// Check if a variable has appropriate permissions to be passed as an argument
-    fn check_variable_permission_for_arg(
-        &mut self,
-        var_name: &str,
-        param_permissions: &[Permission],
-        function_name: &str,
-        param_index: usize
+
+    /// Check that a variable can be passed to a parameter needing exclusive access
+    fn check_variable_argument(
+        &self,
+        name: &str,
+        location: &SourceLocation,
+        param: &HirParameter,
+        function: &str,
+        ctx: &mut AnalysisContext,
     ) {
-        // Check for exclusive permissions required by parameter
-        let has_exclusive_param = param_permissions.contains(&Permission::Read) && 
-                                param_permissions.contains(&Permission::Write) &&
-                                !param_permissions.contains(&Permission::Reads) &&
-                                !param_permissions.contains(&Permission::Writes);
-                                
-        if has_exclusive_param {
-            // For parameters requiring exclusive access, we need to verify the variable
-            // actually has exclusive permissions compatible with Pony's iso capability
-            
-            // Create a temporary full permission checker to find the variable's actual permissions
-            let mut temp_checker = PermissionChecker::new();
-            let err = temp_checker.check_variable_aliasing_for_function_arg(
-                var_name, param_permissions, function_name, param_index
-            );
-            
-            if let Some(error) = err {
-                self.errors.push(error);
+        let declared = ctx.lookup(name).map(|info| info.permissions.clone()).unwrap_or_default();
+
+        if is_exclusive(&param.permissions) && !declared.is_empty() && !is_exclusive(&declared) {
+            ctx.report_permission(PermissionError::ExclusiveViolation {
+                variable: name.to_string(),
+                parameter: param.name.clone(),
+                function: Some(function.to_string()),
+                aliased: false,
+                span: location.known().copied(),
+            });
+        } else if param.permissions.contains(&Permission::Write) && !param.permissions.contains(&Permission::Writes) {
+            // Exclusive write access is impossible once another function
+            // holds an alias of the variable
+            if let Some(stored_by) = self.captured.get(&Name::new(name)) {
+                ctx.report_permission(PermissionError::StoredAlias {
+                    variable: name.to_string(),
+                    parameter: param.name.clone(),
+                    function: function.to_string(),
+                    stored_by: stored_by.clone(),
+                    span: location.known().copied(),
+                });
             }
         }
     }
 }
+
+/// Whether permissions give exclusive access, like Pony's iso
+fn is_exclusive(permissions: &[Permission]) -> bool {
+    permissions.contains(&Permission::Read)
+        && permissions.contains(&Permission::Write)
+        && !permissions.contains(&Permission::Reads)
+        && !permissions.contains(&Permission::Writes)
+}
+
+/// Report behavior parameters that cannot be sent between actors
+fn check_behavior_parameters(func: &HirFunction, ctx: &mut AnalysisContext) {
+    for param in &func.parameters {
+        if !is_sendable(&param.permissions) {
+            ctx.report_permission(PermissionError::UnsendableParameter {
+                behavior: func.name.clone(),
+                parameter: param.name.clone(),
+                declared: param.permissions.clone(),
+                span: func.span.known().copied(),
+            });
+        }
+    }
+}
+
+/// Check that an argument can be sent to a behavior. Clones and computed
+/// values are fresh and always sendable; a variable must be exclusive or
+/// immutable, and a `peak` of one must be immutable.
+fn check_sendable_argument(behavior: &str, arg: &HirExpression, ctx: &mut AnalysisContext) {
+    let (name, location, peak) = match arg {
+        HirExpression::Variable(name, _, location, _) => (name, location, false),
+        HirExpression::Consume(inner, _) => match inner.as_ref() {
+            HirExpression::Variable(name, _, location, _) => (name, location, false),
+            _ => return,
+        },
+        HirExpression::Peak(inner, _) => match inner.as_ref() {
+            HirExpression::Variable(name, _, location, _) => (name, location, true),
+            _ => return,
+        },
+        _ => return,
+    };
+    let Some(declared) = ctx.lookup(name).map(|info| info.permissions.clone()) else {
+        return;
+    };
+
+    let sendable = if peak {
+        declared.is_empty() || (declared.contains(&Permission::Reads) && is_sendable(&declared))
+    } else {
+        is_sendable(&declared)
+    };
+    if !sendable {
+        ctx.report_permission(PermissionError::UnsendableArgument {
+            variable: name.clone(),
+            behavior: behavior.to_string(),
+            declared,
+            peak,
+            span: location.known().copied(),
+        });
+    }
+}
@@ -0,0 +1,274 @@
+//! Function inlining for HIR
+//!
+//! Calls to small helpers whose body is a single `return` expression are
+//! replaced by that expression, with parameters substituted by the call's
+//! arguments. Only bodies that refer to nothing but their parameters are
+//! inlined, so no name in the body can be captured by a local at the call
+//! site. Running `fold_constants` afterwards sees through helpers like
+//! `double(x)`.
+
+use crate::hir::types::*;
+use crate::hir::visitor::{self, HirVisitor};
+use std::collections::HashMap;
+
+/// Largest body, in expression nodes, that is inlined by default
+pub const DEFAULT_INLINE_LIMIT: usize = 8;
+
+/// Inline small functions at their call sites
+pub fn inline_functions(program: &mut HirProgram) {
+    inline_functions_with_limit(program, DEFAULT_INLINE_LIMIT);
+}
+
+/// Inline functions whose body has at most `limit` expression nodes
+pub fn inline_functions_with_limit(program: &mut HirProgram, limit: usize) {
+    let mut inliner = Inliner {
+        candidates: find_candidates(program, limit),
+        active: Vec::new(),
+    };
+    if inliner.candidates.is_empty() {
+        return;
+    }
+
+    for stmt in program.statements.iter_mut() {
+        inliner.inline_statement(stmt);
+    }
+}
+
+/// A function body that can replace a call
+struct Candidate {
+    parameters: Vec<String>,
+    body: HirExpression,
+}
+
+/// Collect the top-level functions small enough to inline
+fn find_candidates(program: &HirProgram, limit: usize) -> HashMap<String, Candidate> {
+    program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            // Behaviors run asynchronously, so a call to one is never inlined
+            HirStatement::Function(func) if !func.is_behavior => match func.body.as_slice() {
+                [HirStatement::Return(Some(body), _)]
+                    if expression_size(body) <= limit && only_uses_parameters(body, &func.parameters) => Some((
+                    func.name.clone(),
+                    Candidate {
+                        parameters: func.parameters.iter().map(|param| param.name.clone()).collect(),
+                        body: body.clone(),
+                    },
+                )),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rewrites calls to candidates, tracking which bodies are being expanded
+struct Inliner {
+    candidates: HashMap<String, Candidate>,
+
+    /// Functions currently being expanded, so recursion stops
+    active: Vec<String>,
+}
+
+impl Inliner {
+    /// Inline calls in every expression of a statement
+    fn inline_statement(&mut self, stmt: &mut HirStatement) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                if let Some(init) = &mut var.initializer {
+                    self.inline_expression(init);
+                }
+            },
+            HirStatement::Assignment(assign) => self.inline_expression(&mut assign.value),
+            HirStatement::Function(func) => {
+                for stmt in func.body.iter_mut() {
+                    self.inline_statement(stmt);
+                }
+            },
+            HirStatement::Return(expr, _) => {
+                if let Some(expr) = expr {
+                    self.inline_expression(expr);
+                }
+            },
+            HirStatement::Print(expr, _) |
+            HirStatement::Expression(expr) => self.inline_expression(expr),
+            HirStatement::Block(statements, _) |
+            HirStatement::Atomic(statements, _) => {
+                for stmt in statements.iter_mut() {
+                    self.inline_statement(stmt);
+                }
+            },
+            HirStatement::If { condition, then_branch, else_branch, .. } => {
+                self.inline_expression(condition);
+                self.inline_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.inline_statement(else_branch);
+                }
+            },
+            HirStatement::While { condition, body, .. } => {
+                self.inline_expression(condition);
+                self.inline_statement(body);
+            },
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => {},
+        }
+    }
+
+    /// Inline calls in an expression, innermost first
+    fn inline_expression(&mut self, expr: &mut HirExpression) {
+        match expr {
+            HirExpression::Binary { left, right, .. } => {
+                self.inline_expression(left);
+                self.inline_expression(right);
+            },
+            HirExpression::Call { arguments, .. } => {
+                for arg in arguments.iter_mut() {
+                    self.inline_expression(arg);
+                }
+            },
+            HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
+                self.inline_expression(condition);
+                self.inline_expression(then_expr);
+                self.inline_expression(else_expr);
+            },
+            HirExpression::Cast { expr: inner, .. } |
+            HirExpression::Peak(inner, _) |
+            HirExpression::Clone(inner, _) |
+            HirExpression::Consume(inner, _) => self.inline_expression(inner),
+            HirExpression::Integer(_, _) |
+            HirExpression::Variable(_, _, _, _) |
+            HirExpression::Boolean(_, _) |
+            HirExpression::String(_, _) => {},
+        }
+
+        if let Some((name, mut expanded)) = self.expand_call(expr) {
+            // The expansion may itself call other small helpers
+            self.active.push(name);
+            self.inline_expression(&mut expanded);
+            self.active.pop();
+            *expr = expanded;
+        }
+    }
+
+    /// The inlined body for a call, if the call can be inlined
+    fn expand_call(&self, expr: &HirExpression) -> Option<(String, HirExpression)> {
+        let HirExpression::Call { function, arguments, .. } = expr else {
+            return None;
+        };
+        if self.active.contains(function) {
+            return None;
+        }
+        let candidate = self.candidates.get(function)?;
+        if candidate.parameters.len() != arguments.len() {
+            return None;
+        }
+
+        // An argument with side effects must be evaluated exactly once
+        for (param, arg) in candidate.parameters.iter().zip(arguments) {
+            if contains_call(arg) && count_uses(&candidate.body, param) != 1 {
+                return None;
+            }
+        }
+
+        let bindings: HashMap<&str, &HirExpression> = candidate.parameters.iter()
+            .map(String::as_str)
+            .zip(arguments)
+            .collect();
+        Some((function.clone(), substitute(&candidate.body, &bindings)))
+    }
+}
+
+/// Replace parameter references with the bound argument expressions
+fn substitute(expr: &HirExpression, bindings: &HashMap<&str, &HirExpression>) -> HirExpression {
+    match expr {
+        HirExpression::Variable(name, _, _, _) => match bindings.get(name.as_str()) {
+            Some(arg) => (*arg).clone(),
+            None => expr.clone(),
+        },
+        HirExpression::Binary { left, operator, right, result_type, span } => HirExpression::Binary {
+            left: Box::new(substitute(left, bindings)),
+            operator: operator.clone(),
+            right: Box::new(substitute(right, bindings)),
+            result_type: result_type.clone(),
+            span: *span,
+        },
+        HirExpression::Call { function, arguments, result_type, span } => HirExpression::Call {
+            function: function.clone(),
+            arguments: arguments.iter().map(|arg| substitute(arg, bindings)).collect(),
+            result_type: result_type.clone(),
+            span: *span,
+        },
+        HirExpression::Conditional { condition, then_expr, else_expr, result_type, span } => HirExpression::Conditional {
+            condition: Box::new(substitute(condition, bindings)),
+            then_expr: Box::new(substitute(then_expr, bindings)),
+            else_expr: Box::new(substitute(else_expr, bindings)),
+            result_type: result_type.clone(),
+            span: *span,
+        },
+        HirExpression::Cast { expr, target_type, span } => HirExpression::Cast {
+            expr: Box::new(substitute(expr, bindings)),
+            target_type: target_type.clone(),
+            span: *span,
+        },
+        HirExpression::Peak(inner, span) => HirExpression::Peak(Box::new(substitute(inner, bindings)), *span),
+        HirExpression::Clone(inner, span) => HirExpression::Clone(Box::new(substitute(inner, bindings)), *span),
+        HirExpression::Consume(inner, span) => HirExpression::Consume(Box::new(substitute(inner, bindings)), *span),
+        HirExpression::Integer(_, _) |
+        HirExpression::Boolean(_, _) |
+        HirExpression::String(_, _) => expr.clone(),
+    }
+}
+
+/// Counts expression nodes, variable references and calls
+#[derive(Default)]
+struct ExpressionStats<'a> {
+    size: usize,
+    calls: usize,
+    variables: Vec<String>,
+    uses_of: Option<&'a str>,
+    uses: usize,
+}
+
+impl HirVisitor for ExpressionStats<'_> {
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        self.size += 1;
+        match expr {
+            HirExpression::Call { .. } => self.calls += 1,
+            HirExpression::Variable(name, _, _, _) => {
+                if Some(name.as_str()) == self.uses_of {
+                    self.uses += 1;
+                }
+                self.variables.push(name.clone());
+            },
+            _ => {},
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
+
+/// Number of expression nodes in an expression
+fn expression_size(expr: &HirExpression) -> usize {
+    let mut stats = ExpressionStats::default();
+    stats.visit_expression(expr);
+    stats.size
+}
+
+/// Whether every variable an expression refers to is one of the parameters
+fn only_uses_parameters(expr: &HirExpression, parameters: &[HirParameter]) -> bool {
+    let mut stats = ExpressionStats::default();
+    stats.visit_expression(expr);
+    stats.variables.iter().all(|name| parameters.iter().any(|param| &param.name == name))
+}
+
+/// Whether an expression calls a function
+fn contains_call(expr: &HirExpression) -> bool {
+    let mut stats = ExpressionStats::default();
+    stats.visit_expression(expr);
+    stats.calls > 0
+}
+
+/// How often a variable is referenced in an expression
+fn count_uses(expr: &HirExpression, name: &str) -> usize {
+    let mut stats = ExpressionStats { uses_of: Some(name), ..Default::default() };
+    stats.visit_expression(expr);
+    stats.uses
+}
@@ -10,37 +10,57 @@ pub mod name_resolver;
 pub mod desugar;
 pub mod diagnostics;
 pub mod permissions;  // Make sure this is public
+pub mod permission_errors; // Structured permission errors and rendering
+pub mod scoped_map;      // Maps with undoable scopes for the checkers
 pub mod const_fold;      // New module for constant folding
 pub mod dce;             // New module for dead code elimination
+pub mod inline;          // Inlining of small functions
 pub mod pretty_print;    // New module for pretty printing
-pub mod function_analysis; // Add the new module
+pub mod serialize;       // JSON output for external tools
+pub mod function_analysis; // Call checks against function signatures
+pub mod visitor;         // Shared traversal over the HIR
+pub mod passes;          // Semantic analysis pass manager
+pub mod unreachable;     // Unreachable code warnings
+pub mod permission_inference; // Suggested permissions for unannotated declarations
+pub mod data_race;       // Races between behaviors on shared globals
+pub mod atomic;          // Access rules inside atomic blocks
+pub mod effects;         // Pure function inference for the optimizer
+pub mod escape;          // Variables that outlive their function
+pub mod monomorphize;    // Instances of generic functions for lowering
+pub mod closures;        // Lifting nested functions to the top level
+pub mod builder;         // Fluent HIR construction for tests
 
 // Re-export key functions and types
 pub use types::{HirProgram, HirStatement, HirExpression};
 pub use converter::{convert_ast_to_hir, convert_statements_to_hir};
-pub use name_resolver::{resolve_names, resolve_names_with_source}; // Add the new function
+pub use name_resolver::{resolve_names, resolve_names_with_source, bind_symbols};
 pub use validation::ValidationError;
 pub use desugar::desugar_program;
 pub use const_fold::fold_constants;
 pub use dce::eliminate_dead_code;
+pub use inline::inline_functions;
 pub use pretty_print::pretty_print;
-pub use permissions::PermissionChecker;
-pub use function_analysis::FunctionPermissionsContext;
+pub use permissions::PermissionPass;
+pub use function_analysis::CallSignaturePass;
+pub use visitor::HirVisitor;
+pub use passes::{Analysis, AnalysisPass, AnalysisContext, PassManager};
+pub use permission_inference::{suggest_permissions, PermissionSuggestion};
+pub use data_race::find_data_races;
+pub use atomic::check_atomic_blocks;
+pub use effects::pure_functions;
+pub use escape::EscapeAnalysis;
+pub use monomorphize::monomorphize;
+pub use closures::lift_closures;
+pub use builder::HirBuilder;
 
 /// Analyze a program for permission violations
 pub fn check_permissions(program: &HirProgram) -> Vec<permissions::PermissionError> {
-    // First perform basic permission checking
-    let mut checker = permissions::PermissionChecker::new();
-    let basic_errors = checker.check_program(program);
-    
-    // Then perform function-specific permission analysis
-    let mut func_checker = function_analysis::FunctionPermissionsContext::new();
-    let func_errors = func_checker.analyze_program(program);
-    
-    // Combine errors
-    let mut all_errors = basic_errors;
-    all_errors.extend(func_errors);
-    all_errors
+    PassManager::with_default_passes().analyze(program).permission_errors
+}
+
+/// Run the built-in semantic analyses over a program in a single pass
+pub fn analyze_program(program: &HirProgram) -> diagnostics::DiagnosticReporter {
+    PassManager::with_default_passes().run(program)
 }
 
 #[cfg(test)]
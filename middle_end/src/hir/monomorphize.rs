@@ -0,0 +1,222 @@
+//! Monomorphization of generic functions
+//!
+//! MIR has no type parameters, so before lowering every call to a generic
+//! function is redirected to an instance of it specialized for the types
+//! of the call's arguments. Calls with the same type arguments share one
+//! instance. Each instance is recorded in `TypeInfo::instances` under its
+//! symbol name (`id$Int`), so diagnostics can show it as `id<Int>` and
+//! code generation names it consistently. Instances may call generic
+//! functions in turn, which are instantiated the same way; the generic
+//! definitions themselves are dropped.
+//!
+//! Only top-level functions are generic. A call whose type arguments
+//! cannot all be inferred from its arguments is left for validation to
+//! report.
+
+use crate::hir::types::*;
+use front_end::types::Type;
+use std::collections::HashMap;
+
+/// Type arguments bound to the generic parameters of one instance
+type Bindings = HashMap<String, Type>;
+
+/// Replace every generic function by the instances the program calls
+pub fn monomorphize(program: &mut HirProgram) {
+    let mut generics = HashMap::new();
+    program.statements.retain(|stmt| match stmt {
+        HirStatement::Function(func) if !func.type_params.is_empty() => {
+            generics.entry(func.name.clone()).or_insert_with(|| func.clone());
+            false
+        },
+        _ => true,
+    });
+    if generics.is_empty() {
+        return;
+    }
+
+    let mut monomorphizer = Monomorphizer {
+        generics,
+        type_info: &mut program.type_info,
+        pending: Vec::new(),
+    };
+    for stmt in program.statements.iter_mut() {
+        monomorphizer.rewrite_statement(stmt, &Bindings::new());
+    }
+
+    // Instances are rewritten after they are created, which may create more
+    let mut next = 0;
+    while next < monomorphizer.pending.len() {
+        let (mut instance, bindings) = monomorphizer.pending[next].clone();
+        for stmt in instance.body.iter_mut() {
+            monomorphizer.rewrite_statement(stmt, &bindings);
+        }
+        monomorphizer.pending[next].0 = instance;
+        next += 1;
+    }
+
+    let instances = monomorphizer.pending.into_iter()
+        .map(|(instance, _)| HirStatement::Function(instance));
+    program.statements.extend(instances);
+}
+
+/// A type with generic parameters replaced by their bound types
+fn substitute(typ: &Type, bindings: &Bindings) -> Type {
+    match typ {
+        Type::Param(name) => bindings.get(name).cloned().unwrap_or_else(|| typ.clone()),
+        _ => typ.clone(),
+    }
+}
+
+struct Monomorphizer<'a> {
+    /// Generic functions by name
+    generics: HashMap<String, HirFunction>,
+
+    type_info: &'a mut TypeInfo,
+
+    /// Instances in the order they were created, with the bindings their
+    /// bodies are rewritten under
+    pending: Vec<(HirFunction, Bindings)>,
+}
+
+impl Monomorphizer<'_> {
+    /// Substitute bound types in a statement and redirect its generic calls
+    fn rewrite_statement(&mut self, stmt: &mut HirStatement, bindings: &Bindings) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                var.typ = substitute(&var.typ, bindings);
+                if let Some(init) = &mut var.initializer {
+                    self.rewrite_expression(init, bindings);
+                }
+            },
+            HirStatement::Assignment(assign) => self.rewrite_expression(&mut assign.value, bindings),
+            HirStatement::Function(func) => {
+                for param in func.parameters.iter_mut() {
+                    param.typ = substitute(&param.typ, bindings);
+                }
+                func.return_type = func.return_type.as_ref().map(|typ| substitute(typ, bindings));
+                for stmt in func.body.iter_mut() {
+                    self.rewrite_statement(stmt, bindings);
+                }
+            },
+            HirStatement::Return(expr, _) => {
+                if let Some(expr) = expr {
+                    self.rewrite_expression(expr, bindings);
+                }
+            },
+            HirStatement::Print(expr, _) |
+            HirStatement::Expression(expr) => self.rewrite_expression(expr, bindings),
+            HirStatement::Block(statements, _) |
+            HirStatement::Atomic(statements, _) => {
+                for stmt in statements.iter_mut() {
+                    self.rewrite_statement(stmt, bindings);
+                }
+            },
+            HirStatement::If { condition, then_branch, else_branch, .. } => {
+                self.rewrite_expression(condition, bindings);
+                self.rewrite_statement(then_branch, bindings);
+                if let Some(else_branch) = else_branch {
+                    self.rewrite_statement(else_branch, bindings);
+                }
+            },
+            HirStatement::While { condition, body, .. } => {
+                self.rewrite_expression(condition, bindings);
+                self.rewrite_statement(body, bindings);
+            },
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => {},
+        }
+    }
+
+    /// Substitute bound types in an expression, innermost first, so the
+    /// arguments of a generic call have their concrete types
+    fn rewrite_expression(&mut self, expr: &mut HirExpression, bindings: &Bindings) {
+        match expr {
+            HirExpression::Variable(_, typ, _, _) => *typ = substitute(typ, bindings),
+            HirExpression::Binary { left, right, result_type, .. } => {
+                self.rewrite_expression(left, bindings);
+                self.rewrite_expression(right, bindings);
+                *result_type = substitute(result_type, bindings);
+            },
+            HirExpression::Call { function, arguments, result_type, .. } => {
+                for arg in arguments.iter_mut() {
+                    self.rewrite_expression(arg, bindings);
+                }
+                *result_type = substitute(result_type, bindings);
+                if let Some((symbol, return_type)) = self.instantiate(function, arguments) {
+                    *function = symbol;
+                    if let Some(return_type) = return_type {
+                        *result_type = return_type;
+                    }
+                }
+            },
+            HirExpression::Conditional { condition, then_expr, else_expr, result_type, .. } => {
+                self.rewrite_expression(condition, bindings);
+                self.rewrite_expression(then_expr, bindings);
+                self.rewrite_expression(else_expr, bindings);
+                *result_type = substitute(result_type, bindings);
+            },
+            HirExpression::Cast { expr: inner, target_type, .. } => {
+                self.rewrite_expression(inner, bindings);
+                *target_type = substitute(target_type, bindings);
+            },
+            HirExpression::Peak(inner, _) |
+            HirExpression::Clone(inner, _) |
+            HirExpression::Consume(inner, _) => self.rewrite_expression(inner, bindings),
+            HirExpression::Integer(_, _) |
+            HirExpression::Boolean(_, _) |
+            HirExpression::String(_, _) => {},
+        }
+    }
+
+    /// The instance a call to `function` with these arguments goes to, and
+    /// its return type, creating the instance on first use. `None` when
+    /// `function` is not generic or its type arguments can't be inferred.
+    fn instantiate(&mut self, function: &str, arguments: &[HirExpression]) -> Option<(String, Option<Type>)> {
+        let generic = self.generics.get(function)?;
+        if generic.parameters.len() != arguments.len() {
+            return None;
+        }
+
+        let mut bindings = Bindings::new();
+        for (param, arg) in generic.parameters.iter().zip(arguments) {
+            let Type::Param(name) = &param.typ else {
+                continue;
+            };
+            let typ = arg.get_type();
+            if typ.is_param() {
+                return None;
+            }
+            match bindings.get(name) {
+                Some(bound) if *bound != typ => return None,
+                _ => {
+                    bindings.insert(name.clone(), typ);
+                },
+            }
+        }
+        let type_arguments = generic.type_params.iter()
+            .map(|param| bindings.get(param).cloned())
+            .collect::<Option<Vec<Type>>>()?;
+
+        let instance = Instance { generic: function.to_string(), type_arguments };
+        let symbol = instance.symbol();
+        if !self.type_info.instances.contains_key(&symbol) {
+            let mut func = generic.clone();
+            func.name = symbol.clone();
+            func.type_params.clear();
+            for param in func.parameters.iter_mut() {
+                param.typ = substitute(&param.typ, &bindings);
+            }
+            func.return_type = func.return_type.as_ref().map(|typ| substitute(typ, &bindings));
+
+            self.type_info.functions.insert(symbol.clone(), FunctionSignature {
+                parameters: func.parameters.clone(),
+                return_type: func.return_type.clone(),
+            });
+            self.type_info.instances.insert(symbol.clone(), instance);
+            self.pending.push((func, bindings));
+        }
+
+        let return_type = self.type_info.functions[&symbol].return_type.clone();
+        Some((symbol, return_type))
+    }
+}
@@ -1,8 +1,12 @@
 //! Name resolution for HIR
 //!
-//! This module handles symbol resolution and validation in HIR.
+//! This module handles symbol resolution and validation in HIR. Every
+//! declaration gets a fresh `SymbolId`; `bind_symbols` stamps it on the
+//! declaration and on every use, so later passes can tell shadowed
+//! variables apart without comparing names.
 
-use crate::hir::scope::{SymbolTable, Symbol, ScopeError, SourceLocation};
+use crate::hir::scope::{SymbolTable, Symbol, ScopeError, SourceLocation, Namespace};
+use front_end::intern::Name;
 use crate::hir::types::*;
 use std::collections::HashMap;
 use crate::hir::diagnostics::DiagnosticReporter;
@@ -25,6 +29,13 @@ pub struct ResolvedNames {
 
 /// Resolve names in a HIR program
 pub fn resolve_names(program: &HirProgram) -> ResolvedNames {
+    bind_symbols(&mut program.clone())
+}
+
+/// Resolve names in a HIR program and record the result in it: the symbol
+/// of every declaration, parameter, variable use and assignment target,
+/// and the type of every symbol in `type_info.symbols`
+pub fn bind_symbols(program: &mut HirProgram) -> ResolvedNames {
     let mut resolver = NameResolver::new();
     resolver.resolve_program(program);
     resolver.finalize()
@@ -40,7 +51,7 @@ pub fn resolve_names_with_source(program: &HirProgram, source: &str) -> Resolved
         .map(|(i, line)| (i + 1, line.to_string()))
         .collect();
     
-    resolver.resolve_program_with_source(program, source_lines);
+    resolver.resolve_program_with_source(&mut program.clone(), source_lines);
     
     let mut result = resolver.finalize();
     
@@ -53,23 +64,6 @@ pub fn resolve_names_with_source(program: &HirProgram, source: &str) -> Resolved
     result
 }
 
-// Helper function to create source location from HIR node
-fn source_location_from_hir(expr: &HirExpression) -> Option<SourceLocation> {
-    match expr {
-        HirExpression::Variable(_, _, loc) => {
-            loc.as_ref().map(|l| {
-                SourceLocation {
-                    line: l.start.line,
-                    column: l.start.column,
-                    file: format!("file_{}", l.file_id),
-                }
-            })
-        },
-        // Add more cases for other expression types as needed
-        _ => None,
-    }
-}
-
 /// Name resolver that builds up resolution information
 pub(crate) struct NameResolver {
     /// Symbol table to track scopes and symbols
@@ -81,9 +75,12 @@ pub(crate) struct NameResolver {
     /// Maps canonical names to their symbols
     symbols: HashMap<String, Symbol>,
     
-    /// Unique counter for generating canonical names
+    /// Unique counter for symbol IDs and canonical names
     unique_counter: usize,
     
+    /// Types of the variable symbols declared so far
+    symbol_types: HashMap<SymbolId, front_end::types::Type>,
+    
     /// Errors encountered during resolution
     errors: Vec<ScopeError>,
     
@@ -99,6 +96,7 @@ impl NameResolver {
             name_mapping: HashMap::new(),
             symbols: HashMap::new(),
             unique_counter: 0,
+            symbol_types: HashMap::new(),
             errors: Vec::new(),
             source_lines: None,
         }
@@ -106,7 +104,7 @@ impl NameResolver {
     
     /// Finalize name resolution and return the results
     pub fn finalize(self) -> ResolvedNames {
-        let mut diagnostics = DiagnosticReporter::from_scope_errors(self.errors.clone());
+        let diagnostics = DiagnosticReporter::from_scope_errors(self.errors.clone());
         
         ResolvedNames {
             name_mapping: self.name_mapping,
@@ -116,36 +114,66 @@ impl NameResolver {
         }
     }
     
-    /// Generate a unique canonical name
-    fn generate_canonical_name(&mut self, base_name: &str) -> String {
-        let canonical = format!("{}_{}", base_name, self.unique_counter);
+    /// Allocate a symbol ID and its canonical name
+    fn new_symbol(&mut self, base_name: &str) -> (SymbolId, String) {
+        let id = SymbolId(self.unique_counter);
         self.unique_counter += 1;
-        canonical
+        (id, format!("{}_{}", base_name, id.0))
     }
     
     /// Resolve names in a program
-    pub fn resolve_program(&mut self, program: &HirProgram) {
-        // First pass: register all top-level declarations
-        for statement in &program.statements {
+    pub fn resolve_program(&mut self, program: &mut HirProgram) {
+        // First pass: declare all top-level names exactly once, so they can be
+        // used before their definition and redefinitions are reported
+        let mut occurrences: HashMap<(Namespace, String), usize> = HashMap::new();
+        for statement in program.statements.iter_mut() {
             match statement {
                 HirStatement::Declaration(var) => {
-                    self.register_variable(var, None);
+                    let occurrence = occurrences.entry((Namespace::Value, var.name.clone())).or_insert(0);
+                    let location = self.find_definition(&var.name, Namespace::Value, *occurrence);
+                    *occurrence += 1;
+                    self.declare_variable(var, location, true);
                 },
                 HirStatement::Function(func) => {
-                    self.register_function(func, None);
+                    let occurrence = occurrences.entry((Namespace::Function, func.name.clone())).or_insert(0);
+                    let location = self.find_definition(&func.name, Namespace::Function, *occurrence);
+                    *occurrence += 1;
+                    self.declare_function(func, location);
                 },
                 _ => {}
             }
         }
         
-        // Second pass: resolve variable references in bodies
-        for statement in &program.statements {
-            self.resolve_statement(statement);
+        // Second pass: resolve initializers, function bodies and other statements
+        for statement in program.statements.iter_mut() {
+            match statement {
+                HirStatement::Declaration(var) => {
+                    if let Some(init) = &mut var.initializer {
+                        self.resolve_expression(init);
+                    }
+                },
+                _ => self.resolve_statement(statement),
+            }
         }
+        
+        program.type_info.symbols.extend(self.symbol_types.drain());
+    }
+    
+    /// Find the source location of the `occurrence`-th definition of a name
+    fn find_definition(&self, name: &str, namespace: Namespace, occurrence: usize) -> Option<SourceLocation> {
+        let source_lines = self.source_lines.as_ref()?;
+        
+        source_lines.iter()
+            .filter_map(|(line_num, line)| {
+                defined_name_column(line, namespace)
+                    .filter(|(defined, _)| *defined == name)
+                    .map(|(_, column)| SourceLocation::with_position(*line_num, column, "input".to_string()))
+            })
+            .nth(occurrence)
     }
     
     /// Resolve program with source information for better error messages
-    pub fn resolve_program_with_source(&mut self, program: &HirProgram, source_lines: Vec<(usize, String)>) {
+    pub fn resolve_program_with_source(&mut self, program: &mut HirProgram, source_lines: Vec<(usize, String)>) {
         // Store source lines for location lookups
         self.source_lines = Some(source_lines);
         
@@ -154,18 +182,30 @@ impl NameResolver {
     }
     
     /// Register a variable declaration in the symbol table
-    fn register_variable(&mut self, var: &HirVariable, location: Option<SourceLocation>) {
-        let canonical_name = self.generate_canonical_name(&var.name);
+    fn register_variable(&mut self, var: &mut HirVariable, location: Option<SourceLocation>) {
+        // Don't report duplicate errors when trying to register variables with bad initializers
+        let skip_add = var.initializer.as_ref()
+            .is_some_and(|init| self.has_undefined_variables(init));
+        
+        self.declare_variable(var, location, !skip_add);
+    }
+    
+    /// Record a variable symbol, adding it to the current scope if requested
+    fn declare_variable(&mut self, var: &mut HirVariable, location: Option<SourceLocation>, add_to_scope: bool) {
+        let (id, canonical_name) = self.new_symbol(&var.name);
+        var.symbol = Some(id);
+        self.symbol_types.insert(id, var.typ.clone());
         
         // Create a symbol for the variable
         let symbol = Symbol {
-            name: var.name.clone(),
+            id,
+            name: Name::new(&var.name),
             typ: var.typ.clone(),
             permissions: var.permissions.clone(),
             is_function: false,
             location: location.or_else(|| {
                 // Convert from HirVariable's location if available
-                var.location.as_ref().map(|loc| {
+                var.span.known().map(|loc| {
                     SourceLocation {
                         line: loc.start.line,
                         column: loc.start.column,
@@ -176,15 +216,7 @@ impl NameResolver {
         };
         
         // Add to symbol table only if we're not in an error recovery context
-        // Don't report duplicate errors when trying to register variables with bad initializers
-        let mut skip_add = false;
-        
-        if let Some(init) = &var.initializer {
-            // Check if initializer contains undefined variables
-            skip_add = self.has_undefined_variables(init);
-        }
-        
-        if !skip_add {
+        if add_to_scope {
             if let Err(error) = self.symbol_table.add_symbol(symbol.clone()) {
                 self.errors.push(error);
             }
@@ -198,9 +230,9 @@ impl NameResolver {
     /// Check if an expression contains references to undefined variables
     fn has_undefined_variables(&self, expr: &HirExpression) -> bool {
         match expr {
-            HirExpression::Variable(name, _, _) => {
+            HirExpression::Variable(name, _, _, _) => {
                 // Check if this variable is defined
-                self.symbol_table.lookup(name).is_none()
+                self.symbol_table.lookup_value(name).is_none()
             },
             HirExpression::Binary { left, right, .. } => {
                 // Check both sides recursively
@@ -216,20 +248,22 @@ impl NameResolver {
                 self.has_undefined_variables(else_expr)
             },
             HirExpression::Cast { expr, .. } => self.has_undefined_variables(expr),
-            HirExpression::Peak(expr) => self.has_undefined_variables(expr),
-            HirExpression::Clone(expr) => self.has_undefined_variables(expr),
+            HirExpression::Peak(expr, _) => self.has_undefined_variables(expr),
+            HirExpression::Clone(expr, _) => self.has_undefined_variables(expr),
+            HirExpression::Consume(expr, _) => self.has_undefined_variables(expr),
             // Literals don't contain variable references
             _ => false,
         }
     }
     
-    /// Register a function declaration in the symbol table
-    fn register_function(&mut self, func: &HirFunction, location: Option<SourceLocation>) {
-        let canonical_name = self.generate_canonical_name(&func.name);
+    /// Declare a function in the symbol table; its body is resolved later
+    fn declare_function(&mut self, func: &HirFunction, location: Option<SourceLocation>) {
+        let (id, canonical_name) = self.new_symbol(&func.name);
         
         // Create a symbol for the function
         let symbol = Symbol {
-            name: func.name.clone(),
+            id,
+            name: Name::new(&func.name),
             typ: func.return_type.clone().unwrap_or(front_end::types::Type::Unit),
             permissions: Vec::new(), // Functions don't have permissions
             is_function: true,
@@ -241,38 +275,20 @@ impl NameResolver {
             self.errors.push(error);
         }
         
-        // Record canonical name and store symbol
-        self.name_mapping.insert(func.name.clone(), canonical_name.clone());
-        self.symbols.insert(canonical_name, symbol);
-        
-        // Process function body with a new scope
-        self.symbol_table.enter_scope();
-        
-        // Register parameters
-        for param in &func.parameters {
-            self.register_variable(&HirVariable {
-                name: param.name.clone(),
-                typ: param.typ.clone(),
-                permissions: param.permissions.clone(),
-                initializer: None,
-                location: None,
-            }, None);
+        // Record canonical name and store symbol, keeping the first definition
+        // when the function is defined twice
+        if !self.name_mapping.contains_key(&func.name) {
+            self.name_mapping.insert(func.name.clone(), canonical_name.clone());
         }
-        
-        // Resolve body statements
-        for stmt in &func.body {
-            self.resolve_statement(stmt);
-        }
-        
-        self.symbol_table.exit_scope();
+        self.symbols.insert(canonical_name, symbol);
     }
     
     /// Resolve names in a statement
-    fn resolve_statement(&mut self, stmt: &HirStatement) {
+    fn resolve_statement(&mut self, stmt: &mut HirStatement) {
         match stmt {
             HirStatement::Declaration(var) => {
                 // First handle the initializer if present
-                if let Some(init) = &var.initializer {
+                if let Some(init) = &mut var.initializer {
                     self.resolve_expression(init);
                 }
                 
@@ -282,12 +298,14 @@ impl NameResolver {
             
             HirStatement::Assignment(assign) => {
                 // Resolve the right-hand side expression
-                self.resolve_expression(&assign.value);
+                self.resolve_expression(&mut assign.value);
                 
                 // Resolve the target variable
-                if let Some(symbol) = self.symbol_table.lookup(&assign.target) {
+                if let Some(symbol) = self.symbol_table.lookup_value(&assign.target) {
+                    assign.symbol = Some(symbol.id);
+                    
                     // Found the variable - map to canonical name
-                    if let Some(canonical) = self.name_mapping.get(&symbol.name) {
+                    if let Some(canonical) = self.name_mapping.get(symbol.name.as_str()) {
                         self.name_mapping.insert(assign.target.clone(), canonical.clone());
                     }
                 } else {
@@ -309,11 +327,21 @@ impl NameResolver {
                         None
                     };
                     
-                    // Variable not found
-                    self.errors.push(ScopeError::NotFound { 
-                        name: assign.target.clone(),
-                        location // Add the location field
-                    });
+                    // Variable not found, or the name refers to a function
+                    let error = match self.symbol_table.lookup_function(&assign.target) {
+                        Some(function) => ScopeError::WrongNamespace {
+                            name: assign.target.clone(),
+                            expected: Namespace::Value,
+                            location,
+                            definition: function.location.clone(),
+                        },
+                        None => ScopeError::NotFound { 
+                            name: assign.target.clone(),
+                            location, // Add the location field
+                            suggestion: self.symbol_table.suggest_similar(&assign.target, Namespace::Value),
+                        },
+                    };
+                    self.errors.push(error);
                 }
             },
             
@@ -321,15 +349,20 @@ impl NameResolver {
                 self.resolve_expression(expr);
             },
             
-            HirStatement::Print(expr) => {
+            HirStatement::Print(expr, _) => {
                 self.resolve_expression(expr);
             },
             
-            HirStatement::Block(statements) => {
+            HirStatement::Return(Some(expr), _) => {
+                self.resolve_expression(expr);
+            },
+            
+            HirStatement::Block(statements, _) |
+            HirStatement::Atomic(statements, _) => {
                 // Create a new scope for the block
                 self.symbol_table.enter_scope();
                 
-                for stmt in statements {
+                for stmt in statements.iter_mut() {
                     self.resolve_statement(stmt);
                 }
                 
@@ -337,51 +370,68 @@ impl NameResolver {
             },
             
             HirStatement::Function(func) => {
-                // Already handled in the first pass
-                // But we might need to resolve names within the function body
+                // The name was declared in the first pass; resolve the body
+                // in its own scope
                 self.symbol_table.enter_scope();
                 
-                // Register parameters again to ensure proper scoping
-                for param in &func.parameters {
-                    self.register_variable(&HirVariable {
+                // Register parameters
+                for param in func.parameters.iter_mut() {
+                    let mut var = HirVariable {
                         name: param.name.clone(),
                         typ: param.typ.clone(),
                         permissions: param.permissions.clone(),
                         initializer: None,
-                        location: None,
-                    }, None);
+                        span: Default::default(),
+                        actor: None,
+                        symbol: None,
+                    };
+                    self.register_variable(&mut var, None);
+                    param.symbol = var.symbol;
                 }
                 
                 // Resolve body statements
-                for stmt in &func.body {
+                for stmt in func.body.iter_mut() {
                     self.resolve_statement(stmt);
                 }
                 
                 self.symbol_table.exit_scope();
             },
             
+            HirStatement::If { condition, then_branch, else_branch, .. } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            },
+            
+            HirStatement::While { condition, body, .. } => {
+                self.resolve_expression(condition);
+                self.resolve_statement(body);
+            },
+            
             _ => {}  // Handle other statements as needed
         }
     }
     
     /// Resolve names in an expression, with better location tracking
-    fn resolve_expression(&mut self, expr: &HirExpression) {
+    fn resolve_expression(&mut self, expr: &mut HirExpression) {
         match expr {
             HirExpression::Integer(_, _) => {
                 // Integers don't contain names to resolve
             },
             
-            HirExpression::Boolean(_) => {
+            HirExpression::Boolean(_, _) => {
                 // Booleans don't contain names to resolve
             },
             
-            HirExpression::String(_) => {
+            HirExpression::String(_, _) => {
                 // Strings don't contain names to resolve
             },
             
-            HirExpression::Variable(name, _typ, loc) => {
+            HirExpression::Variable(name, _typ, loc, resolved) => {
                 // Extract location from expression if available
-                let location = loc.as_ref().map(|l| {
+                let location = loc.known().map(|l| {
                     SourceLocation {
                         line: l.start.line,
                         column: l.start.column,
@@ -390,9 +440,11 @@ impl NameResolver {
                 });
                 
                 // Look up variable in all visible scopes
-                if let Some(symbol) = self.symbol_table.lookup(name) {
+                if let Some(symbol) = self.symbol_table.lookup_value(name) {
+                    *resolved = Some(symbol.id);
+                    
                     // Found the variable - map to canonical name
-                    if let Some(canonical) = self.name_mapping.get(&symbol.name) {
+                    if let Some(canonical) = self.name_mapping.get(symbol.name.as_str()) {
                         self.name_mapping.insert(name.clone(), canonical.clone());
                     }
                 } else {
@@ -401,8 +453,8 @@ impl NameResolver {
                         // Try to find the line containing this variable reference
                         let mut found_location = None;
                         for (line_num, line) in source_lines {
-                            if line.contains(name) {
-                                let col = line.find(name).unwrap_or(1) + 1;
+                            if line.contains(name.as_str()) {
+                                let col = line.find(name.as_str()).unwrap_or(1) + 1;
                                 found_location = Some(SourceLocation::with_position(
                                     *line_num,
                                     col,
@@ -424,9 +476,18 @@ impl NameResolver {
                     };
                     
                     // Create error with the better location
-                    let error = ScopeError::NotFound {
-                        name: name.clone(),
-                        location: Some(source_location), // Add location to NotFound errors
+                    let error = match self.symbol_table.lookup_function(name) {
+                        Some(function) => ScopeError::WrongNamespace {
+                            name: name.clone(),
+                            expected: Namespace::Value,
+                            location: Some(source_location),
+                            definition: function.location.clone(),
+                        },
+                        None => ScopeError::NotFound {
+                            name: name.clone(),
+                            location: Some(source_location), // Add location to NotFound errors
+                            suggestion: self.symbol_table.suggest_similar(name, Namespace::Value),
+                        },
                     };
                     self.errors.push(error);
                 }
@@ -439,29 +500,30 @@ impl NameResolver {
             
             HirExpression::Call { function, arguments, .. } => {
                 // Resolve function name
-                if let Some(symbol) = self.symbol_table.lookup(function) {
-                    if symbol.is_function {
-                        // Found the function - map to canonical name
-                        if let Some(canonical) = self.name_mapping.get(&symbol.name) {
-                            self.name_mapping.insert(function.clone(), canonical.clone());
-                        }
-                    } else {
-                        // Symbol exists but is not a function
-                        self.errors.push(ScopeError::NotFound { 
-                            name: function.clone(),
-                            location: None // Add the missing location field
-                        });
+                if let Some(symbol) = self.symbol_table.lookup_function(function) {
+                    // Found the function - map to canonical name
+                    if let Some(canonical) = self.name_mapping.get(symbol.name.as_str()) {
+                        self.name_mapping.insert(function.clone(), canonical.clone());
                     }
+                } else if let Some(variable) = self.symbol_table.lookup_value(function) {
+                    // Symbol exists but is a variable, not a function
+                    self.errors.push(ScopeError::WrongNamespace {
+                        name: function.clone(),
+                        expected: Namespace::Function,
+                        location: None,
+                        definition: variable.location.clone(),
+                    });
                 } else {
                     // Function not found
                     self.errors.push(ScopeError::NotFound { 
                         name: function.clone(),
-                        location: None // Add the missing location field
+                        location: None, // Add the missing location field
+                        suggestion: self.symbol_table.suggest_similar(function, Namespace::Function),
                     });
                 }
                 
                 // Resolve arguments
-                for arg in arguments {
+                for arg in arguments.iter_mut() {
                     self.resolve_expression(arg);
                 }
             },
@@ -476,13 +538,48 @@ impl NameResolver {
                 self.resolve_expression(expr);
             },
             
-            HirExpression::Peak(expr) => {
+            HirExpression::Peak(expr, _) => {
                 self.resolve_expression(expr);
             },
             
-            HirExpression::Clone(expr) => {
+            HirExpression::Clone(expr, _) |
+            HirExpression::Consume(expr, _) => {
                 self.resolve_expression(expr);
             },
         }
     }
 }
+
+/// If a source line defines a name in the given namespace, return the name
+/// and its 1-based column
+fn defined_name_column(line: &str, namespace: Namespace) -> Option<(&str, usize)> {
+    let mut rest = line.trim_start();
+    
+    match namespace {
+        Namespace::Function => {
+            rest = rest.strip_prefix("fn ")?.trim_start();
+        },
+        Namespace::Value => {
+            // A declaration starts with at least one permission keyword
+            let mut has_permission = false;
+            while let Some((word, tail)) = rest.split_once(char::is_whitespace) {
+                if !matches!(word, "read" | "reads" | "write" | "writes") {
+                    break;
+                }
+                has_permission = true;
+                rest = tail.trim_start();
+            }
+            if !has_permission {
+                return None;
+            }
+        },
+    }
+    
+    let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+    if end == 0 {
+        return None;
+    }
+    
+    let column = line.len() - rest.len() + 1;
+    Some((&rest[..end], column))
+}
\ No newline at end of file
@@ -0,0 +1,370 @@
+//! Semantic analysis pass manager
+//!
+//! Analyses implement `AnalysisPass` and register with a `PassManager`,
+//! which walks the HIR once and hands every node to each pass in turn.
+//! Passes share an `AnalysisContext` that tracks the variables in scope
+//! with their types and permissions, the function being analyzed, whether
+//! a loop of that function encloses the current node and the signatures of
+//! all declared functions, and that collects the diagnostics every pass
+//! reports. Permission errors are kept in structured form as well, for
+//! callers that inspect them rather than print them.
+
+use crate::hir::atomic::check_atomic_blocks;
+use crate::hir::data_race::find_data_races;
+use crate::hir::diagnostics::{Diagnostic, DiagnosticReporter, ToDiagnostic};
+use crate::hir::function_analysis::CallSignaturePass;
+use crate::hir::permission_errors::PermissionError;
+use crate::hir::permissions::PermissionPass;
+use crate::hir::types::*;
+use crate::hir::unreachable::UnreachableCodePass;
+use crate::hir::validation::{binary_operand_error, conversion_error, loop_control_error};
+use crate::hir::visitor::{self, HirVisitor};
+use front_end::types::{Permission, Type};
+use std::collections::HashMap;
+
+/// What the analysis knows about a variable in scope
+#[derive(Debug, Clone)]
+pub struct VariableInfo {
+    /// Declared type
+    pub typ: Type,
+
+    /// Declared permissions
+    pub permissions: Vec<Permission>,
+}
+
+impl VariableInfo {
+    /// Whether the variable may be read
+    pub fn can_read(&self) -> bool {
+        self.permissions.iter().any(|p| matches!(p, Permission::Read | Permission::Reads))
+    }
+
+    /// Whether the variable may be written
+    pub fn can_write(&self) -> bool {
+        self.permissions.iter().any(|p| matches!(p, Permission::Write | Permission::Writes))
+    }
+}
+
+/// State shared by all passes during a single traversal
+pub struct AnalysisContext<'a> {
+    program: &'a HirProgram,
+    scopes: Vec<HashMap<String, VariableInfo>>,
+    current_function: Option<String>,
+    loop_depth: usize,
+    diagnostics: DiagnosticReporter,
+    permission_errors: Vec<PermissionError>,
+}
+
+impl<'a> AnalysisContext<'a> {
+    fn new(program: &'a HirProgram) -> Self {
+        Self {
+            program,
+            scopes: vec![HashMap::new()],
+            current_function: None,
+            loop_depth: 0,
+            diagnostics: DiagnosticReporter::new(),
+            permission_errors: Vec::new(),
+        }
+    }
+
+    /// Whether the current node is inside a loop of the current function
+    pub fn in_loop(&self) -> bool {
+        self.loop_depth > 0
+    }
+
+    /// The program being analyzed
+    pub fn program(&self) -> &'a HirProgram {
+        self.program
+    }
+
+    /// Look up a variable, innermost scope first
+    pub fn lookup(&self, name: &str) -> Option<&VariableInfo> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Name of the function whose body is being analyzed
+    pub fn current_function(&self) -> Option<&str> {
+        self.current_function.as_deref()
+    }
+
+    /// Signature of a declared function
+    pub fn signature(&self, name: &str) -> Option<&'a FunctionSignature> {
+        self.program.type_info.functions.get(name)
+    }
+
+    /// Record a diagnostic
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.add(diagnostic);
+    }
+
+    /// Record a permission error, which is reported as a diagnostic too
+    pub fn report_permission(&mut self, error: PermissionError) {
+        self.diagnostics.add(error.to_diagnostic());
+        self.permission_errors.push(error);
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, typ: &Type, permissions: &[Permission]) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), VariableInfo {
+                typ: typ.clone(),
+                permissions: permissions.to_vec(),
+            });
+        }
+    }
+}
+
+/// A semantic check run by the `PassManager`.
+///
+/// `check_statement` runs before the statement's own declaration enters
+/// scope, so a declaration's initializer cannot see the variable itself;
+/// `exit_statement` runs once it has.
+pub trait AnalysisPass {
+    /// Short name used when listing passes
+    fn name(&self) -> &'static str;
+
+    /// Called once before the program is visited
+    fn start(&mut self, _ctx: &mut AnalysisContext) {}
+
+    /// Called before a function body is analyzed, with its parameters in scope
+    fn enter_function(&mut self, _func: &HirFunction, _ctx: &mut AnalysisContext) {}
+
+    /// Called after a function body has been analyzed
+    fn exit_function(&mut self, _func: &HirFunction, _ctx: &mut AnalysisContext) {}
+
+    /// Called for every statement, before its children
+    fn check_statement(&mut self, _stmt: &HirStatement, _ctx: &mut AnalysisContext) {}
+
+    /// Called for every statement, after its children
+    fn exit_statement(&mut self, _stmt: &HirStatement, _ctx: &mut AnalysisContext) {}
+
+    /// Called for every expression, outermost first
+    fn check_expression(&mut self, _expr: &HirExpression, _ctx: &mut AnalysisContext) {}
+
+    /// Called once the whole program has been visited
+    fn finish(&mut self, _ctx: &mut AnalysisContext) {}
+}
+
+/// What a run of the pass manager found
+#[derive(Debug)]
+pub struct Analysis {
+    /// Diagnostics of every pass, permission errors included
+    pub diagnostics: DiagnosticReporter,
+
+    /// The permission errors, in the order they were reported
+    pub permission_errors: Vec<PermissionError>,
+}
+
+/// Runs registered analysis passes over a program in one traversal
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn AnalysisPass>>,
+}
+
+impl PassManager {
+    /// Create a pass manager with no passes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a pass manager with the built-in permission, call, operand,
+    /// loop control, reachability and concurrency checks
+    pub fn with_default_passes() -> Self {
+        let mut manager = Self::new();
+        manager.register(Box::new(PermissionPass::default()));
+        manager.register(Box::new(CallSignaturePass::default()));
+        manager.register(Box::new(OperandTypePass));
+        manager.register(Box::new(ConversionPass));
+        manager.register(Box::new(LoopControlPass));
+        manager.register(Box::new(UnreachableCodePass));
+        manager.register(Box::new(ConcurrencyPass));
+        manager
+    }
+
+    /// Add a pass; passes see each node in registration order
+    pub fn register(&mut self, pass: Box<dyn AnalysisPass>) {
+        self.passes.push(pass);
+    }
+
+    /// Names of the registered passes, in order
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Analyze a program and return the diagnostics of all passes
+    pub fn run(&mut self, program: &HirProgram) -> DiagnosticReporter {
+        self.analyze(program).diagnostics
+    }
+
+    /// Analyze a program, keeping the permission errors apart as well
+    pub fn analyze(&mut self, program: &HirProgram) -> Analysis {
+        let mut driver = Driver {
+            passes: &mut self.passes,
+            ctx: AnalysisContext::new(program),
+        };
+
+        for pass in driver.passes.iter_mut() {
+            pass.start(&mut driver.ctx);
+        }
+        driver.visit_program(program);
+        for pass in driver.passes.iter_mut() {
+            pass.finish(&mut driver.ctx);
+        }
+
+        Analysis {
+            diagnostics: driver.ctx.diagnostics,
+            permission_errors: driver.ctx.permission_errors,
+        }
+    }
+}
+
+/// Walks the HIR, keeping the context's scopes in step and dispatching to the passes
+struct Driver<'p, 'a> {
+    passes: &'p mut [Box<dyn AnalysisPass>],
+    ctx: AnalysisContext<'a>,
+}
+
+impl HirVisitor for Driver<'_, '_> {
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        for pass in self.passes.iter_mut() {
+            pass.check_statement(stmt, &mut self.ctx);
+        }
+
+        match stmt {
+            HirStatement::Block(_, _) | HirStatement::Atomic(_, _) => {
+                self.ctx.enter_scope();
+                visitor::walk_statement(self, stmt);
+                self.ctx.exit_scope();
+            },
+            HirStatement::While { .. } => {
+                self.ctx.loop_depth += 1;
+                visitor::walk_statement(self, stmt);
+                self.ctx.loop_depth -= 1;
+            },
+            HirStatement::Declaration(var) => {
+                visitor::walk_statement(self, stmt);
+                self.ctx.declare(&var.name, &var.typ, &var.permissions);
+            },
+            _ => visitor::walk_statement(self, stmt),
+        }
+
+        for pass in self.passes.iter_mut() {
+            pass.exit_statement(stmt, &mut self.ctx);
+        }
+    }
+
+    fn visit_function(&mut self, func: &HirFunction) {
+        self.ctx.enter_scope();
+        for param in &func.parameters {
+            self.ctx.declare(&param.name, &param.typ, &param.permissions);
+        }
+        let enclosing = self.ctx.current_function.replace(func.name.clone());
+        let loop_depth = std::mem::replace(&mut self.ctx.loop_depth, 0);
+
+        for pass in self.passes.iter_mut() {
+            pass.enter_function(func, &mut self.ctx);
+        }
+        visitor::walk_function(self, func);
+        for pass in self.passes.iter_mut() {
+            pass.exit_function(func, &mut self.ctx);
+        }
+
+        self.ctx.loop_depth = loop_depth;
+        self.ctx.current_function = enclosing;
+        self.ctx.exit_scope();
+    }
+
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        for pass in self.passes.iter_mut() {
+            pass.check_expression(expr, &mut self.ctx);
+        }
+        visitor::walk_expression(self, expr);
+    }
+}
+
+/// Checks that binary operators are applied to compatible operand types
+pub struct OperandTypePass;
+
+impl AnalysisPass for OperandTypePass {
+    fn name(&self) -> &'static str {
+        "operands"
+    }
+
+    fn check_expression(&mut self, expr: &HirExpression, ctx: &mut AnalysisContext) {
+        if let HirExpression::Binary { left, operator, right, span, .. } = expr {
+            if let Some(error) = binary_operand_error(left, operator, right, span, ctx.program()) {
+                ctx.report(error.to_diagnostic());
+            }
+        }
+    }
+}
+
+/// Checks that initializers, assigned values and returned values convert
+/// implicitly to the type they are stored as
+pub struct ConversionPass;
+
+impl AnalysisPass for ConversionPass {
+    fn name(&self) -> &'static str {
+        "conversions"
+    }
+
+    fn check_statement(&mut self, stmt: &HirStatement, ctx: &mut AnalysisContext) {
+        let program = ctx.program();
+        let error = match stmt {
+            HirStatement::Declaration(var) => var.initializer.as_ref().and_then(|init| {
+                conversion_error(init, &var.typ, format!("initialization of variable '{}'", var.name), program)
+            }),
+            HirStatement::Assignment(assign) => ctx.lookup(&assign.target).and_then(|target| {
+                conversion_error(&assign.value, &target.typ, format!("assignment to variable '{}'", assign.target), program)
+            }),
+            HirStatement::Return(Some(expr), _) => ctx.current_function()
+                .and_then(|function| Some((function, ctx.signature(function)?.return_type.as_ref()?)))
+                .and_then(|(function, return_type)| {
+                    conversion_error(expr, return_type, format!("return value in function '{}'", function), program)
+                }),
+            _ => None,
+        };
+        if let Some(error) = error {
+            ctx.report(error.to_diagnostic());
+        }
+    }
+}
+
+/// Checks that `break` and `continue` only appear inside loops
+pub struct LoopControlPass;
+
+impl AnalysisPass for LoopControlPass {
+    fn name(&self) -> &'static str {
+        "loops"
+    }
+
+    fn check_statement(&mut self, stmt: &HirStatement, ctx: &mut AnalysisContext) {
+        if let Some(error) = loop_control_error(stmt, ctx.in_loop()) {
+            ctx.report(error.to_diagnostic());
+        }
+    }
+}
+
+/// Reports data races between behaviors and misuse of atomic blocks. Both
+/// follow calls across the whole program, so they run once the traversal
+/// is done.
+pub struct ConcurrencyPass;
+
+impl AnalysisPass for ConcurrencyPass {
+    fn name(&self) -> &'static str {
+        "concurrency"
+    }
+
+    fn finish(&mut self, ctx: &mut AnalysisContext) {
+        let program = ctx.program();
+        for error in find_data_races(program).into_iter().chain(check_atomic_blocks(program)) {
+            ctx.report_permission(error);
+        }
+    }
+}
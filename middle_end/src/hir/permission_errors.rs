@@ -0,0 +1,491 @@
+//! Permission errors and their rendering
+//!
+//! The permission checker reports `PermissionError` values that carry the
+//! names, permissions and source spans involved rather than preformatted
+//! text. Each kind has a stable code (`P0001`, ...) for tools and tests to
+//! match on, and suggestions are computed from the same data. Each error
+//! converts to the shared `Diagnostic`, which `PermissionErrorRenderer`
+//! turns into text.
+
+use crate::hir::permissions::unsendable_reason;
+use crate::hir::types::SourceLocation;
+use front_end::codes::permission;
+use front_end::diagnostic::{Diagnostic, DiagnosticRenderer, ToDiagnostic};
+pub use front_end::diagnostic::Suggestion;
+use front_end::types::Permission;
+use std::fmt;
+
+/// What a variable was being used for when it turned out not to exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Write,
+    Peak,
+    Alias,
+}
+
+impl Operation {
+    /// The phrase used in messages, e.g. "read from"
+    fn phrase(&self) -> &'static str {
+        match self {
+            Operation::Read => "read from",
+            Operation::Write => "write to",
+            Operation::Peak => "peak",
+            Operation::Alias => "alias",
+        }
+    }
+}
+
+/// A permission violation found by the checker
+#[derive(Debug, Clone)]
+pub enum PermissionError {
+    /// Reading a variable declared without `read`/`reads`
+    MissingRead {
+        name: String,
+        declared: Vec<Permission>,
+        span: Option<SourceLocation>,
+    },
+
+    /// Assigning a variable declared without `write`/`writes`
+    MissingWrite {
+        name: String,
+        declared: Vec<Permission>,
+        span: Option<SourceLocation>,
+    },
+
+    /// `peak` of a variable that cannot be read
+    PeakWithoutRead {
+        name: String,
+        declared: Vec<Permission>,
+        span: Option<SourceLocation>,
+    },
+
+    /// Aliasing a variable whose permissions are not shareable
+    IllegalAlias {
+        source: String,
+        declared: Vec<Permission>,
+        span: Option<SourceLocation>,
+    },
+
+    /// A second writable alias while another writer is still in use
+    WriteAliasConflict {
+        source: String,
+        existing: String,
+        span: Option<SourceLocation>,
+    },
+
+    /// Passing a variable that is not exclusive where exclusive access is required
+    ExclusiveViolation {
+        variable: String,
+        parameter: String,
+        function: Option<String>,
+        aliased: bool,
+        span: Option<SourceLocation>,
+    },
+
+    /// Passing a variable to a parameter it lacks the permission for
+    ArgumentPermission {
+        variable: String,
+        parameter: String,
+        required: Permission,
+        span: Option<SourceLocation>,
+    },
+
+    /// Using a variable after its value was moved out by `consume`
+    UseAfterConsume {
+        name: String,
+        consumed_at: Option<SourceLocation>,
+        used_at: Option<SourceLocation>,
+    },
+
+    /// Referring to a variable that is not declared
+    UndefinedVariable {
+        name: String,
+        operation: Operation,
+        span: Option<SourceLocation>,
+    },
+
+    /// Calling a function that is not declared
+    UnknownFunction {
+        name: String,
+        span: Option<SourceLocation>,
+    },
+
+    /// Calling a function with the wrong number of arguments
+    ArityMismatch {
+        function: String,
+        expected: usize,
+        found: usize,
+        span: Option<SourceLocation>,
+    },
+
+    /// Passing a variable that another function stored an alias of where
+    /// exclusive write access is required
+    StoredAlias {
+        variable: String,
+        parameter: String,
+        function: String,
+        stored_by: String,
+        span: Option<SourceLocation>,
+    },
+
+    /// A behavior parameter whose permissions cannot cross to another actor
+    UnsendableParameter {
+        behavior: String,
+        parameter: String,
+        declared: Vec<Permission>,
+        span: Option<SourceLocation>,
+    },
+
+    /// Sending a behavior a value that is neither exclusive nor immutable,
+    /// or a `peak` of a value that is not immutable
+    UnsendableArgument {
+        variable: String,
+        behavior: String,
+        declared: Vec<Permission>,
+        peak: bool,
+        span: Option<SourceLocation>,
+    },
+
+    /// Two behaviors accessing the same `writes` global outside atomic
+    /// blocks, at least one of them writing
+    DataRace {
+        variable: String,
+        first_behavior: String,
+        first_writes: bool,
+        first_at: Option<SourceLocation>,
+        second_behavior: String,
+        second_writes: bool,
+        second_at: Option<SourceLocation>,
+    },
+
+    /// Touching a variable without shareable permissions inside an atomic block
+    AtomicNonShareable {
+        variable: String,
+        declared: Vec<Permission>,
+        span: Option<SourceLocation>,
+    },
+
+    /// Calling a behavior, or a function that calls one, inside an atomic block
+    AtomicUnsafeCall {
+        function: String,
+        behavior: String,
+        span: Option<SourceLocation>,
+    },
+
+    /// Using an actor's state from outside that actor; `accessor` is the
+    /// actor the use is in, if any
+    ActorStateAccess {
+        variable: String,
+        actor: String,
+        accessor: Option<String>,
+        span: Option<SourceLocation>,
+    },
+}
+
+impl PermissionError {
+    /// Stable identifier of the error kind
+    pub fn code(&self) -> &'static str {
+        match self {
+            PermissionError::MissingRead { .. } => permission::MISSING_READ,
+            PermissionError::MissingWrite { .. } => permission::MISSING_WRITE,
+            PermissionError::PeakWithoutRead { .. } => permission::PEAK_WITHOUT_READ,
+            PermissionError::IllegalAlias { .. } => permission::ILLEGAL_ALIAS,
+            PermissionError::WriteAliasConflict { .. } => permission::WRITE_ALIAS_CONFLICT,
+            PermissionError::ExclusiveViolation { .. } => permission::EXCLUSIVE_VIOLATION,
+            PermissionError::ArgumentPermission { .. } => permission::ARGUMENT_PERMISSION,
+            PermissionError::UseAfterConsume { .. } => permission::USE_AFTER_CONSUME,
+            PermissionError::UndefinedVariable { .. } => permission::UNDEFINED_VARIABLE,
+            PermissionError::UnknownFunction { .. } => permission::UNKNOWN_FUNCTION,
+            PermissionError::ArityMismatch { .. } => permission::ARITY_MISMATCH,
+            PermissionError::StoredAlias { .. } => permission::STORED_ALIAS,
+            PermissionError::UnsendableParameter { .. } => permission::UNSENDABLE_PARAMETER,
+            PermissionError::UnsendableArgument { .. } => permission::UNSENDABLE_ARGUMENT,
+            PermissionError::DataRace { .. } => permission::DATA_RACE,
+            PermissionError::AtomicNonShareable { .. } => permission::ATOMIC_NON_SHAREABLE,
+            PermissionError::AtomicUnsafeCall { .. } => permission::ATOMIC_UNSAFE_CALL,
+            PermissionError::ActorStateAccess { .. } => permission::ACTOR_STATE_ACCESS,
+        }
+    }
+
+    /// Where the error occurred, if known
+    pub fn span(&self) -> Option<&SourceLocation> {
+        match self {
+            PermissionError::MissingRead { span, .. } |
+            PermissionError::MissingWrite { span, .. } |
+            PermissionError::PeakWithoutRead { span, .. } |
+            PermissionError::IllegalAlias { span, .. } |
+            PermissionError::WriteAliasConflict { span, .. } |
+            PermissionError::ExclusiveViolation { span, .. } |
+            PermissionError::StoredAlias { span, .. } |
+            PermissionError::UnsendableParameter { span, .. } |
+            PermissionError::UnsendableArgument { span, .. } |
+            PermissionError::AtomicNonShareable { span, .. } |
+            PermissionError::AtomicUnsafeCall { span, .. } |
+            PermissionError::ActorStateAccess { span, .. } |
+            PermissionError::ArgumentPermission { span, .. } |
+            PermissionError::UndefinedVariable { span, .. } |
+            PermissionError::UnknownFunction { span, .. } |
+            PermissionError::ArityMismatch { span, .. } => span.as_ref(),
+            PermissionError::UseAfterConsume { used_at, .. } => used_at.as_ref(),
+            PermissionError::DataRace { first_at, .. } => first_at.as_ref(),
+        }
+    }
+
+    /// One-line description of the error
+    pub fn message(&self) -> String {
+        match self {
+            PermissionError::MissingRead { name, .. } => {
+                format!("Cannot read from '{}' - no read permission", name)
+            },
+            PermissionError::MissingWrite { name, .. } => {
+                format!("Cannot write to '{}' - no write permission", name)
+            },
+            PermissionError::PeakWithoutRead { name, .. } => {
+                format!("Cannot peak '{}' - peak requires read permission", name)
+            },
+            PermissionError::IllegalAlias { source, .. } => {
+                format!("Cannot create alias to '{}' - variable has non-shareable permissions", source)
+            },
+            PermissionError::WriteAliasConflict { source, existing, .. } => {
+                format!("Cannot create write alias to '{}' - '{}' already has write permission", source, existing)
+            },
+            PermissionError::ExclusiveViolation { variable, parameter, function, aliased, .. } => {
+                let parameter = match function {
+                    Some(function) => format!("parameter '{}' of function '{}'", parameter, function),
+                    None => format!("parameter '{}'", parameter),
+                };
+                if *aliased {
+                    format!("Cannot pass aliased variable '{}' to {} requiring exclusive write access", variable, parameter)
+                } else {
+                    format!("Cannot pass '{}' to {} - parameter requires exclusive access", variable, parameter)
+                }
+            },
+            PermissionError::ArgumentPermission { variable, parameter, required, .. } => {
+                format!("Cannot pass '{}' to parameter '{}' - parameter requires {} permission",
+                        variable, parameter, keyword(required))
+            },
+            PermissionError::UseAfterConsume { name, .. } => {
+                format!("Use of '{}' after consume", name)
+            },
+            PermissionError::UndefinedVariable { name, operation, .. } => {
+                format!("Cannot {} '{}' - variable not found", operation.phrase(), name)
+            },
+            PermissionError::UnknownFunction { name, .. } => {
+                format!("Call to unknown function '{}'", name)
+            },
+            PermissionError::ArityMismatch { function, expected, found, .. } => {
+                format!("Function '{}' takes {} argument{} but {} {} supplied",
+                        function, expected, if *expected == 1 { "" } else { "s" },
+                        found, if *found == 1 { "was" } else { "were" })
+            },
+            PermissionError::StoredAlias { variable, parameter, function, stored_by, .. } => {
+                format!("Cannot pass '{}' to parameter '{}' of function '{}' requiring exclusive write access - function '{}' stores an alias of it",
+                        variable, parameter, function, stored_by)
+            },
+            PermissionError::UnsendableParameter { behavior, parameter, declared, .. } => {
+                format!("Behavior '{}' cannot take parameter '{}' as {} - {}",
+                        behavior, parameter, keywords(declared), unsendable_reason(declared).unwrap_or_default())
+            },
+            PermissionError::UnsendableArgument { variable, behavior, declared, peak, .. } => {
+                let reason = match unsendable_reason(declared) {
+                    Some(reason) => reason,
+                    None if *peak => "a peak leaves the sender a reference that can still write the value".to_string(),
+                    None => String::new(),
+                };
+                format!("Cannot send '{}' ({}) to behavior '{}' - {}", variable, keywords(declared), behavior, reason)
+            },
+            PermissionError::DataRace { variable, first_behavior, first_writes, second_behavior, second_writes, .. } => {
+                format!("Potential data race on '{}' - behavior '{}' {} it while behavior '{}' {} it",
+                        variable, first_behavior, access_verb(*first_writes), second_behavior, access_verb(*second_writes))
+            },
+            PermissionError::AtomicNonShareable { variable, declared, .. } => {
+                format!("Cannot use '{}' ({}) inside an atomic block - only 'reads' and 'writes' variables can be shared",
+                        variable, keywords(declared))
+            },
+            PermissionError::AtomicUnsafeCall { function, behavior, .. } => {
+                if function == behavior {
+                    format!("Cannot call behavior '{}' inside an atomic block - sending a message cannot be part of an atomic unit", function)
+                } else {
+                    format!("Cannot call '{}' inside an atomic block - it sends a message to behavior '{}'", function, behavior)
+                }
+            },
+            PermissionError::ActorStateAccess { variable, actor, accessor, .. } => match accessor {
+                Some(accessor) => format!("Cannot access '{}', state of actor '{}', from actor '{}'", variable, actor, accessor),
+                None => format!("Cannot access '{}', state of actor '{}', from outside the actor", variable, actor),
+            },
+        }
+    }
+
+    /// Secondary locations worth pointing at, with a label for each
+    pub fn labels(&self) -> Vec<(String, Option<&SourceLocation>)> {
+        match self {
+            PermissionError::UseAfterConsume { consumed_at, used_at, .. } => vec![
+                ("consumed".to_string(), consumed_at.as_ref()),
+                ("used".to_string(), used_at.as_ref()),
+            ],
+            PermissionError::DataRace { first_behavior, first_writes, first_at, second_behavior, second_writes, second_at, .. } => vec![
+                (format!("{} in '{}'", access_verb(*first_writes), first_behavior), first_at.as_ref()),
+                (format!("{} in '{}'", access_verb(*second_writes), second_behavior), second_at.as_ref()),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Ways to fix the error
+    pub fn suggestions(&self) -> Vec<Suggestion> {
+        match self {
+            PermissionError::MissingRead { name, declared, .. } |
+            PermissionError::PeakWithoutRead { name, declared, .. } => {
+                let (keywords, added) = if declared.contains(&Permission::Writes) {
+                    ("reads writes", "reads")
+                } else if declared.contains(&Permission::Write) {
+                    ("read write", "read")
+                } else {
+                    ("read", "read")
+                };
+                vec![Suggestion::new(
+                    format!("add {} permission", added),
+                    Some(format!("{} {}", keywords, name)),
+                )]
+            },
+            PermissionError::MissingWrite { name, declared, .. } => {
+                let keywords = if declared.contains(&Permission::Reads) {
+                    "reads write"
+                } else if declared.contains(&Permission::Read) {
+                    "read write"
+                } else {
+                    "write"
+                };
+                vec![Suggestion::new("add write permission", Some(format!("{} {}", keywords, name)))]
+            },
+            PermissionError::IllegalAlias { source, declared, .. } => {
+                let fix = if declared.contains(&Permission::Read) && declared.contains(&Permission::Write) {
+                    Some(("reads writes", "use shareable permissions instead of read write"))
+                } else if declared.contains(&Permission::Read) {
+                    Some(("reads", "use reads instead of read"))
+                } else if declared.contains(&Permission::Write) {
+                    Some(("writes", "use writes instead of write"))
+                } else {
+                    None
+                };
+                let mut suggestions: Vec<Suggestion> = fix.into_iter()
+                    .map(|(keywords, message)| Suggestion::new(message, Some(format!("{} {}", keywords, source))))
+                    .collect();
+                suggestions.push(Suggestion::new("copy the value instead", Some(format!("clone {}", source))));
+                suggestions
+            },
+            PermissionError::WriteAliasConflict { source, .. } => vec![
+                Suggestion::new("take a read-only view instead", Some(format!("peak {}", source))),
+            ],
+            PermissionError::StoredAlias { variable, stored_by, .. } => vec![
+                Suggestion::new(format!("pass a copy to '{}' instead", stored_by), Some(format!("clone {}", variable))),
+            ],
+            PermissionError::UnsendableParameter { parameter, .. } => vec![
+                Suggestion::new("take an immutable value", Some(format!("reads {}", parameter))),
+                Suggestion::new("take exclusive ownership", Some(format!("read write {}", parameter))),
+            ],
+            PermissionError::UnsendableArgument { variable, .. } => vec![
+                Suggestion::new("send a copy instead", Some(format!("clone {}", variable))),
+            ],
+            PermissionError::DataRace { .. } => vec![
+                Suggestion::new("move the accesses in both behaviors into atomic blocks", Some("atomic { ... }".to_string())),
+            ],
+            PermissionError::AtomicNonShareable { variable, declared, .. } => {
+                let shared: Vec<String> = declared.iter()
+                    .map(|p| match p {
+                        Permission::Read => keyword(&Permission::Reads),
+                        Permission::Write => keyword(&Permission::Writes),
+                        other => keyword(other),
+                    })
+                    .collect();
+                vec![
+                    Suggestion::new("declare it shareable", Some(format!("{} {}", shared.join(" "), variable))),
+                    Suggestion::new("move the access out of the atomic block", None),
+                ]
+            },
+            PermissionError::AtomicUnsafeCall { function, .. } => vec![
+                Suggestion::new(format!("call '{}' after the atomic block", function), None),
+            ],
+            PermissionError::ActorStateAccess { actor, .. } => vec![
+                Suggestion::new(format!("add a behavior to '{}' that does this and call it instead", actor), None),
+            ],
+            PermissionError::UseAfterConsume { name, .. } => vec![
+                Suggestion::new("copy the value before consuming it", Some(format!("clone {}", name))),
+                Suggestion::new(format!("assign '{}' a new value first", name), None),
+            ],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl From<&PermissionError> for Diagnostic {
+    fn from(error: &PermissionError) -> Self {
+        let mut diagnostic = Diagnostic::error(error.message()).with_code(error.code());
+        if let Some(span) = error.span() {
+            diagnostic = diagnostic.with_primary(span.to_span(), "");
+        }
+        for (label, span) in error.labels() {
+            diagnostic = match span {
+                Some(span) => diagnostic.with_secondary(span.to_span(), label),
+                None => diagnostic.with_note(format!("{} at an unknown location", label)),
+            };
+        }
+        error.suggestions().into_iter()
+            .fold(diagnostic, Diagnostic::with_suggestion)
+    }
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", PermissionErrorRenderer::new().render(self))
+    }
+}
+
+/// Permission keyword as written in source
+fn keyword(permission: &Permission) -> String {
+    format!("{:?}", permission).to_lowercase()
+}
+
+/// How a behavior accesses a variable
+fn access_verb(writes: bool) -> &'static str {
+    if writes { "writes" } else { "reads" }
+}
+
+/// Quoted permission keywords of a declaration, e.g. `'reads writes'`
+fn keywords(permissions: &[Permission]) -> String {
+    let keywords: Vec<String> = permissions.iter().map(keyword).collect();
+    format!("'{}'", keywords.join(" "))
+}
+
+/// Formats permission errors for display, quoting source lines when the
+/// source text is available
+#[derive(Default)]
+pub struct PermissionErrorRenderer<'a> {
+    renderer: DiagnosticRenderer<'a>,
+}
+
+impl<'a> PermissionErrorRenderer<'a> {
+    /// A renderer without source text
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A renderer that quotes the offending lines of `source`
+    pub fn with_source(source: &'a str) -> Self {
+        Self { renderer: DiagnosticRenderer::with_source(source) }
+    }
+
+    /// Render one error
+    pub fn render(&self, error: &PermissionError) -> String {
+        self.renderer.render(&error.to_diagnostic())
+    }
+
+    /// Render every error, separated by blank lines
+    pub fn render_all(&self, errors: &[PermissionError]) -> String {
+        errors.iter()
+            .map(|error| self.render(error))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
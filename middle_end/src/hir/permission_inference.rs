@@ -0,0 +1,260 @@
+//! Permission inference for unannotated declarations
+//!
+//! A declaration written without permission keywords (`x: Int = 5`) gets
+//! the smallest permission set that covers how the variable is used:
+//! `read` if it is only read, `reads` once another reference aliases it
+//! (through `peak` or a direct copy of the reference), and `write` added if
+//! it is assigned after its declaration. The initializer alone never
+//! requires `write`. The program is not changed; callers decide whether to
+//! report the suggestions or apply them.
+
+use crate::hir::types::*;
+use front_end::types::Permission;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The permissions inferred for one unannotated declaration
+#[derive(Debug, Clone)]
+pub struct PermissionSuggestion {
+    /// Variable name
+    pub name: String,
+
+    /// Smallest permission set covering every use
+    pub permissions: Vec<Permission>,
+
+    /// Where the variable is declared
+    pub location: Option<SourceLocation>,
+
+    /// Whether the variable is read after its declaration
+    pub read: bool,
+
+    /// Whether the variable is assigned after its declaration
+    pub written: bool,
+
+    /// Whether another reference aliases the variable
+    pub aliased: bool,
+}
+
+impl PermissionSuggestion {
+    /// Why these permissions were chosen
+    pub fn reason(&self) -> &'static str {
+        match (self.aliased, self.written, self.read) {
+            (true, true, _) => "aliased by another reference and assigned after its declaration",
+            (true, false, _) => "aliased by another reference",
+            (false, true, true) => "read and assigned after its declaration",
+            (false, true, false) => "only assigned, never read",
+            (false, false, true) => "only read after its declaration",
+            (false, false, false) => "never used",
+        }
+    }
+}
+
+impl fmt::Display for PermissionSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keywords: Vec<String> = self.permissions.iter()
+            .map(|p| format!("{:?}", p).to_lowercase())
+            .collect();
+        write!(f, "{} {} ({})", keywords.join(" "), self.name, self.reason())
+    }
+}
+
+/// Infer permissions for every declaration written without any, in
+/// declaration order
+pub fn suggest_permissions(program: &HirProgram) -> Vec<PermissionSuggestion> {
+    let mut inference = PermissionInference {
+        program,
+        scopes: vec![HashMap::new()],
+        uses: Vec::new(),
+    };
+    for stmt in &program.statements {
+        inference.visit_statement(stmt);
+    }
+
+    inference.uses.into_iter()
+        .flatten()
+        .map(|mut suggestion| {
+            suggestion.permissions = minimal_permissions(&suggestion);
+            suggestion
+        })
+        .collect()
+}
+
+/// The permissions a usage pattern requires
+fn minimal_permissions(suggestion: &PermissionSuggestion) -> Vec<Permission> {
+    let mut permissions = Vec::new();
+    if suggestion.aliased {
+        permissions.push(Permission::Reads);
+    } else if suggestion.read || !suggestion.written {
+        permissions.push(Permission::Read);
+    }
+    if suggestion.written {
+        permissions.push(Permission::Write);
+    }
+    permissions
+}
+
+/// Walks the program with lexical scopes, recording how each name is used
+struct PermissionInference<'a> {
+    program: &'a HirProgram,
+
+    /// Index into `uses` for each name in scope, innermost scope last
+    scopes: Vec<HashMap<String, usize>>,
+
+    /// Uses recorded for every declared name. Annotated declarations and
+    /// parameters are tracked too, so they shadow outer names, but produce
+    /// no suggestion.
+    uses: Vec<Option<PermissionSuggestion>>,
+}
+
+impl PermissionInference<'_> {
+    fn declare(&mut self, name: &str, suggestion: Option<PermissionSuggestion>) {
+        self.uses.push(suggestion);
+        let index = self.uses.len() - 1;
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), index);
+        }
+    }
+
+    /// The suggestion being built for a name, if it refers to an
+    /// unannotated declaration
+    fn suggestion_mut(&mut self, name: &str) -> Option<&mut PermissionSuggestion> {
+        let index = *self.scopes.iter().rev().find_map(|scope| scope.get(name))?;
+        self.uses[index].as_mut()
+    }
+
+    fn mark_read(&mut self, name: &str) {
+        if let Some(suggestion) = self.suggestion_mut(name) {
+            suggestion.read = true;
+        }
+    }
+
+    fn mark_written(&mut self, name: &str) {
+        if let Some(suggestion) = self.suggestion_mut(name) {
+            suggestion.written = true;
+        }
+    }
+
+    fn mark_aliased(&mut self, name: &str) {
+        if let Some(suggestion) = self.suggestion_mut(name) {
+            suggestion.read = true;
+            suggestion.aliased = true;
+        }
+    }
+
+    fn visit_scoped(&mut self, stmt: &HirStatement) {
+        self.scopes.push(HashMap::new());
+        self.visit_statement(stmt);
+        self.scopes.pop();
+    }
+
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        match stmt {
+            HirStatement::Declaration(var) => {
+                if let Some(init) = &var.initializer {
+                    // `x: Int = y` makes a second reference to y
+                    match init {
+                        HirExpression::Variable(source, _, _, _) => self.mark_aliased(source),
+                        _ => self.visit_expression(init),
+                    }
+                }
+
+                let suggestion = var.permissions.is_empty().then(|| PermissionSuggestion {
+                    name: var.name.clone(),
+                    permissions: Vec::new(),
+                    location: var.span.known().copied(),
+                    read: false,
+                    written: false,
+                    aliased: false,
+                });
+                self.declare(&var.name, suggestion);
+            },
+            HirStatement::Assignment(assign) => {
+                self.visit_expression(&assign.value);
+                if assign.operator.is_some() {
+                    self.mark_read(&assign.target);
+                }
+                self.mark_written(&assign.target);
+            },
+            HirStatement::Function(func) => {
+                self.scopes.push(HashMap::new());
+                for param in &func.parameters {
+                    self.declare(&param.name, None);
+                }
+                for stmt in &func.body {
+                    self.visit_statement(stmt);
+                }
+                self.scopes.pop();
+            },
+            HirStatement::Return(expr, _) => {
+                if let Some(expr) = expr {
+                    self.visit_expression(expr);
+                }
+            },
+            HirStatement::Print(expr, _) |
+            HirStatement::Expression(expr) => self.visit_expression(expr),
+            HirStatement::Block(statements, _) |
+            HirStatement::Atomic(statements, _) => {
+                self.scopes.push(HashMap::new());
+                for stmt in statements {
+                    self.visit_statement(stmt);
+                }
+                self.scopes.pop();
+            },
+            HirStatement::If { condition, then_branch, else_branch, .. } => {
+                self.visit_expression(condition);
+                self.visit_scoped(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.visit_scoped(else_branch);
+                }
+            },
+            HirStatement::While { condition, body, .. } => {
+                self.visit_expression(condition);
+                self.visit_scoped(body);
+            },
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => {},
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        match expr {
+            HirExpression::Variable(name, _, _, _) => self.mark_read(name),
+            HirExpression::Peak(inner, _) => match inner.as_ref() {
+                HirExpression::Variable(name, _, _, _) => self.mark_aliased(name),
+                _ => self.visit_expression(inner),
+            },
+            HirExpression::Call { function, arguments, .. } => {
+                let signature = self.program.type_info.functions.get(function);
+                for (index, arg) in arguments.iter().enumerate() {
+                    // Passing a variable to a writable parameter lets the
+                    // callee assign it
+                    let writable = signature
+                        .and_then(|sig| sig.parameters.get(index))
+                        .is_some_and(|param| param.permissions.iter()
+                            .any(|p| matches!(p, Permission::Write | Permission::Writes)));
+                    if let (true, HirExpression::Variable(name, _, _, _)) = (writable, arg) {
+                        self.mark_read(name);
+                        self.mark_written(name);
+                    } else {
+                        self.visit_expression(arg);
+                    }
+                }
+            },
+            HirExpression::Binary { left, right, .. } => {
+                self.visit_expression(left);
+                self.visit_expression(right);
+            },
+            HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
+                self.visit_expression(condition);
+                self.visit_expression(then_expr);
+                self.visit_expression(else_expr);
+            },
+            HirExpression::Cast { expr: inner, .. } |
+            HirExpression::Clone(inner, _) |
+            HirExpression::Consume(inner, _) => self.visit_expression(inner),
+            HirExpression::Integer(_, _) |
+            HirExpression::Boolean(_, _) |
+            HirExpression::String(_, _) => {},
+        }
+    }
+}
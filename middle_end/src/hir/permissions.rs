@@ -1,591 +1,622 @@
 //! Permission checking for HIR
 //!
-//! This module implements the permission checking system for the HIR representation.
+//! This module implements the permission checking system for the HIR
+//! representation, as a pass run by the `PassManager`.
+//!
+//! Alias checks are flow-sensitive. Statement sequences are checked with the
+//! set of variables still used by later statements, so an alias that is
+//! never used again no longer blocks a new one, and assigning a variable a
+//! new value ends the alias relationships it had.
+//!
+//! Actor state is isolated: the pass tracks the actor whose method or
+//! behavior it is in, and a state field may only be used from its own
+//! actor.
+//!
+//! Scoped state lives in `ScopedMap`s, so entering a block or function only
+//! opens a scope and leaving it undoes the changes made inside, instead of
+//! copying the whole environment. The maps are keyed by interned names, so
+//! recording an alias or a declaration copies a handle, not a string.
+//!
+//! Variables declared without any permission are still to be inferred, and
+//! reading or writing them is not checked.
 
+use front_end::intern::Name;
 use front_end::types::Permission;
 
+use crate::hir::passes::{AnalysisContext, AnalysisPass};
+use crate::hir::scoped_map::ScopedMap;
 use crate::hir::types::*;
-use std::collections::{HashMap, HashSet};
+use crate::hir::visitor::{self, HirVisitor};
+use std::collections::HashSet;
 
-/// Error information for permission checking
-#[derive(Debug, Clone)]
-pub struct PermissionError {
-    /// Error message
-    pub message: String,
-    
-    /// Source location information (line, column)
-    pub location: Option<(usize, usize)>,
-}
+pub use crate::hir::permission_errors::{Operation, PermissionError, PermissionErrorRenderer};
 
-/// Permission checking context
-pub struct PermissionChecker {
+/// Checks that variables are only used as their permissions allow
+#[derive(Default)]
+pub struct PermissionPass {
     /// Maps variable names to their permissions
-    permissions: HashMap<String, Vec<Permission>>,
+    permissions: ScopedMap<Name, Vec<Permission>>,
     
     /// Tracks which variables alias the same memory
-    aliases: HashMap<String, HashSet<String>>,
+    aliases: ScopedMap<Name, HashSet<Name>>,
+    
+    /// Where each variable in scope was declared
+    locations: ScopedMap<Name, SourceLocation>,
+    
+    /// The actor owning each state field in scope
+    owners: ScopedMap<Name, String>,
+    
+    /// The actor whose method or behavior is being checked, if any
+    current_actor: Option<String>,
+    
+    /// Actors of the enclosing functions and state declarations, to
+    /// restore when the current one ends
+    outer_actors: Vec<Option<String>>,
+    
+    /// Variables whose value was moved out by `consume`, with the consume site
+    consumed: ScopedMap<Name, Option<SourceLocation>>,
+    
+    /// Variables used after the statement being checked
+    live: Option<HashSet<Name>>,
+    
+    /// Statement sequences being checked, innermost last
+    sequences: Vec<Sequence>,
     
-    /// Tracks exclusive access variables
-    exclusive_access: HashMap<String, String>,
+    /// Live sets to restore when the loops being checked end
+    loops: Vec<Option<HashSet<Name>>>,
     
-    /// Errors found during permission checking
-    errors: Vec<PermissionError>,
+    /// Nesting depth of the statement being checked
+    depth: usize,
     
-    /// Track variable locations
-    locations: HashMap<String, (usize, usize)>, // (line, column)
+    /// Set while visiting the value of an assignment whose target cannot
+    /// be written; the value is not checked
+    muted: bool,
+    
+    /// Set when the next expression visited is the variable operand of a
+    /// `peak` or `consume`
+    operand_of: Option<Operand>,
 }
 
-impl PermissionChecker {
-    /// Create a new permission checker
-    pub fn new() -> Self {
-        Self {
-            permissions: HashMap::new(),
-            aliases: HashMap::new(),
-            exclusive_access: HashMap::new(),
-            errors: Vec::new(),
-            locations: HashMap::new(), // Add locations tracking
-        }
+/// A statement sequence being checked
+struct Sequence {
+    /// Nesting depth of the sequence's own statements
+    depth: usize,
+    
+    /// For each statement, what the statements after it still use
+    live_after: std::vec::IntoIter<HashSet<Name>>,
+    
+    /// The live set to restore when the sequence ends
+    outer: Option<HashSet<Name>>,
+}
+
+/// An expression that uses its variable operand other than by reading it
+#[derive(Clone, Copy)]
+enum Operand {
+    Peak,
+    Consume,
+}
+
+impl AnalysisPass for PermissionPass {
+    fn name(&self) -> &'static str {
+        "permissions"
     }
     
-    /// Check permissions for a HIR program
-    pub fn check_program(&mut self, program: &HirProgram) -> Vec<PermissionError> {
-        for statement in &program.statements {
-            self.check_statement(statement);
+    /// Functions may be called at any point, so everything they use stays
+    /// live for the whole program
+    fn start(&mut self, ctx: &mut AnalysisContext) {
+        let statements = &ctx.program().statements;
+        let mut used_by_functions = HashSet::new();
+        for stmt in statements {
+            if let HirStatement::Function(func) = stmt {
+                used_by_functions.extend(used_names(&func.body));
+            }
         }
         
-        self.errors.clone()
+        self.live = Some(used_by_functions);
+        self.enter_sequence(statements);
     }
     
-    /// Check program permissions with source code
-    pub fn check_program_with_source(&mut self, program: &HirProgram, source: &str) -> Vec<PermissionError> {
-        // Extract line information from source
-        let lines: Vec<&str> = source.lines().collect();
-        
-        // First collect all variable declarations and their permissions
-        for stmt in &program.statements {
-            match stmt {
-                HirStatement::Declaration(var) => {
-                    self.permissions.insert(var.name.clone(), var.permissions.clone());
-                    
-                    // Try to find the line containing this variable
-                    for (i, line) in lines.iter().enumerate() {
-                        if line.contains(&var.name) {
-                            let column = line.find(&var.name).unwrap_or(0) + 1;
-                            self.locations.insert(var.name.clone(), (i + 1, column));
-                            break;
-                        }
-                    }
-                },
-                _ => {}, // Handle other statement types appropriately
-            }
-        }
+    fn finish(&mut self, _ctx: &mut AnalysisContext) {
+        self.exit_sequence();
+    }
+    
+    fn enter_function(&mut self, func: &HirFunction, _ctx: &mut AnalysisContext) {
+        // The body does not see consumption from the surrounding code
+        self.push_scope();
+        self.consumed.push_scope();
+        self.enter_actor(func.actor.clone());
         
-        // Then check all statements for permission violations
-        for stmt in &program.statements {
-            self.check_statement(stmt);
+        for param in &func.parameters {
+            self.register_variable(&param.name, &param.permissions);
         }
-        
-        self.errors.clone()
+        self.enter_sequence(&func.body);
     }
     
-    /// Register a variable with its permissions
-    fn register_variable(&mut self, name: &str, perms: &[Permission]) {
-        self.permissions.insert(name.to_string(), perms.to_vec());
-        
-        // Track exclusive access
-        if perms.contains(&Permission::Read) && 
-           perms.contains(&Permission::Write) && 
-           !perms.contains(&Permission::Reads) && 
-           !perms.contains(&Permission::Writes) {
-            
-            self.exclusive_access.insert(name.to_string(), name.to_string());
+    fn exit_function(&mut self, _func: &HirFunction, _ctx: &mut AnalysisContext) {
+        self.exit_sequence();
+        self.exit_actor();
+        self.consumed.pop_scope();
+        self.pop_scope();
+    }
+    
+    fn check_statement(&mut self, stmt: &HirStatement, ctx: &mut AnalysisContext) {
+        self.depth += 1;
+        if let Some(sequence) = self.sequences.last_mut() {
+            if sequence.depth == self.depth {
+                self.live = sequence.live_after.next();
+            }
         }
         
-        // Initialize alias set
-        let mut alias_set = HashSet::new();
-        alias_set.insert(name.to_string());
-        self.aliases.insert(name.to_string(), alias_set);
+        match stmt {
+            HirStatement::Declaration(var) => {
+                self.register_variable(&var.name, &var.permissions);
+                if let Some(span) = var.span.known() {
+                    self.locations.insert(Name::new(&var.name), *span);
+                }
+                
+                // A state field's initializer runs inside its actor
+                match &var.actor {
+                    Some(actor) => {
+                        self.owners.insert(Name::new(&var.name), actor.clone());
+                        self.enter_actor(Some(actor.clone()));
+                    },
+                    None => self.enter_actor(self.current_actor.clone()),
+                }
+            },
+            HirStatement::Assignment(assign) => {
+                let target = assign.target.as_str();
+                self.check_actor_access(target, &assign.span, ctx);
+                self.muted = !self.check_write_permission(target, &assign.span, ctx);
+            },
+            HirStatement::Block(statements, _) |
+            HirStatement::Atomic(statements, _) => {
+                self.push_scope();
+                self.enter_sequence(statements);
+            },
+            HirStatement::While { .. } => {
+                // Everything the loop uses is live throughout it, since the
+                // next iteration uses it again
+                self.loops.push(self.live.clone());
+                if let Some(live) = &mut self.live {
+                    live.extend(used_names(std::slice::from_ref(stmt)));
+                }
+            },
+            _ => {},
+        }
     }
     
-    /// Check permissions for a statement
-    pub fn check_statement(&mut self, stmt: &HirStatement) {
+    fn exit_statement(&mut self, stmt: &HirStatement, ctx: &mut AnalysisContext) {
         match stmt {
-            HirStatement::Declaration(var) => self.check_variable_declaration(var),
-            HirStatement::Assignment(assign) => self.check_assignment(&assign.target, &assign.value),
-            HirStatement::Expression(expr) => { self.check_expression_permissions(expr); },
-            HirStatement::Return(expr) => {
-                if let Some(expr) = expr {
-                    self.check_expression_permissions(expr);
+            HirStatement::Declaration(var) => {
+                if let Some(HirExpression::Variable(source_name, _, location, _)) = &var.initializer {
+                    self.check_aliasing(&var.name, source_name, &var.permissions, location, ctx);
                 }
+                self.exit_actor();
             },
-            HirStatement::Print(expr) => {
-                self.check_expression_permissions(expr);
-            },
-            HirStatement::Block(statements) => {
-                // Create a new scope
-                let old_permissions = self.permissions.clone();
-                let old_aliases = self.aliases.clone();
-                let old_exclusive = self.exclusive_access.clone();
-                
-                // Check each statement in the block
-                for stmt in statements {
-                    self.check_statement(stmt);
+            HirStatement::Assignment(assign) => {
+                // A consumed variable is usable again once it holds a new
+                // value, and it no longer shares that value with its former
+                // aliases
+                let checked = !std::mem::take(&mut self.muted);
+                if checked {
+                    let target = Name::new(&assign.target);
+                    self.consumed.remove(&target);
+                    self.drop_aliases(target);
                 }
+            },
+            HirStatement::Block(_, _) |
+            HirStatement::Atomic(_, _) => {
+                self.exit_sequence();
+                self.pop_scope();
                 
-                // Restore old scope
-                self.permissions = old_permissions;
-                self.aliases = old_aliases;
-                self.exclusive_access = old_exclusive;
+                // Outer variables consumed in the block stay consumed
+                let permissions = &self.permissions;
+                self.consumed.retain(|name, _| permissions.contains_key(name));
+            },
+            HirStatement::While { .. } => {
+                self.live = self.loops.pop().flatten();
             },
-            HirStatement::Function(func) => self.check_function(func),
-            _ => {}, // Handle other statement types appropriately
+            _ => {},
         }
+        self.depth -= 1;
     }
     
-    /// Check permissions for a function
-    fn check_function(&mut self, func: &HirFunction) {
-        // Create a new scope for function parameters
-        let old_permissions = self.permissions.clone();
-        let old_aliases = self.aliases.clone();
-        let old_exclusive = self.exclusive_access.clone();
+    fn check_expression(&mut self, expr: &HirExpression, ctx: &mut AnalysisContext) {
+        if self.muted {
+            return;
+        }
         
-        // Add parameters to scope
-        for param in &func.parameters {
-            self.register_variable(&param.name, &param.permissions);
+        match expr {
+            HirExpression::Variable(name, _, location, _) => {
+                let operand_of = self.operand_of.take();
+                self.check_actor_access(name, location, ctx);
+                if !self.check_not_consumed(name, location, ctx) {
+                    return;
+                }
+                
+                match operand_of {
+                    Some(Operand::Peak) => {
+                        self.check_peak_permission(name, location, ctx);
+                    },
+                    Some(Operand::Consume) => {
+                        // Consuming reads the value once, then invalidates the variable
+                        self.check_read_permission(name, location, ctx);
+                        self.consumed.insert(Name::new(name), location.known().copied());
+                    },
+                    None => {
+                        self.check_read_permission(name, location, ctx);
+                    },
+                }
+            },
+            HirExpression::Peak(operand, _) if matches!(**operand, HirExpression::Variable(..)) => {
+                self.operand_of = Some(Operand::Peak);
+            },
+            HirExpression::Consume(operand, _) if matches!(**operand, HirExpression::Variable(..)) => {
+                self.operand_of = Some(Operand::Consume);
+            },
+            _ => {},
         }
+    }
+}
+
+impl PermissionPass {
+    /// Start checking a sequence of statements, working out which variables
+    /// each statement leaves for the ones after it
+    fn enter_sequence(&mut self, statements: &[HirStatement]) {
+        let outer = self.live.clone();
         
-        // Check function body
-        for stmt in &func.body {
-            self.check_statement(stmt);
+        // live_after[i] holds what statements after i (and after the
+        // whole sequence) still use
+        let mut live_after = vec![outer.clone().unwrap_or_default(); statements.len()];
+        for i in (1..statements.len()).rev() {
+            let mut live = live_after[i].clone();
+            live.extend(used_names(std::slice::from_ref(&statements[i])));
+            live_after[i - 1] = live;
         }
         
-        // Restore old scope
-        self.permissions = old_permissions;
-        self.aliases = old_aliases;
-        self.exclusive_access = old_exclusive;
+        self.sequences.push(Sequence {
+            depth: self.depth + 1,
+            live_after: live_after.into_iter(),
+            outer,
+        });
     }
     
-    /// Check permissions for a variable declaration
-    fn check_variable_declaration(&mut self, var: &HirVariable) {
-        // Log permissions for debugging
-        if cfg!(test) {
-            println!("Registering variable '{}' with permissions: {:?}", var.name, var.permissions);
+    /// Finish the innermost sequence, restoring the live set around it
+    fn exit_sequence(&mut self) {
+        if let Some(sequence) = self.sequences.pop() {
+            self.live = sequence.outer;
         }
-        
-        // Register variable with its permissions
-        self.register_variable(&var.name, &var.permissions);
-        
-        // Check initializer permissions
-        if let Some(init) = &var.initializer {
-            self.check_expression_permissions(init);
-            
-            // If it's a variable reference, handle aliasing
-            if let HirExpression::Variable(source_name, _, _) = init {
-                if cfg!(test) {
-                    println!("Checking aliasing from '{}' to '{}'", source_name, var.name);
-                    
-                    // Print permissions for both variables
-                    if let Some(source_perms) = self.permissions.get(source_name) {
-                        println!("  Source '{}' permissions: {:?}", source_name, source_perms);
-                    }
-                    println!("  Target '{}' permissions: {:?}", var.name, var.permissions);
-                }
-                
-                self.check_aliasing(&var.name, source_name, &var.permissions);
+    }
+    
+    /// Check code belonging to `actor` until the matching `exit_actor`
+    fn enter_actor(&mut self, actor: Option<String>) {
+        let outer = std::mem::replace(&mut self.current_actor, actor);
+        self.outer_actors.push(outer);
+    }
+    
+    fn exit_actor(&mut self) {
+        self.current_actor = self.outer_actors.pop().flatten();
+    }
+    
+    /// Where a variable was declared, if that is known
+    fn span_of(&self, name: &str) -> Option<SourceLocation> {
+        self.locations.get(&Name::new(name)).copied()
+    }
+    
+    /// Whether a variable may still be used after the current statement
+    fn is_live(&self, name: Name) -> bool {
+        self.live.as_ref().map_or(true, |live| live.contains(&name))
+    }
+    
+    /// Forget every alias relationship of a variable that now holds a new value
+    fn drop_aliases(&mut self, name: Name) {
+        let others: Vec<Name> = self.aliases.iter()
+            .filter(|(&other, aliases)| other != name && aliases.contains(&name))
+            .map(|(&other, _)| other)
+            .collect();
+        for other in &others {
+            if let Some(aliases) = self.aliases.get_mut(other) {
+                aliases.remove(&name);
             }
         }
+        self.aliases.insert(name, HashSet::from([name]));
+    }
+    
+    /// Open a scope for variables declared in a block or function
+    fn push_scope(&mut self) {
+        self.permissions.push_scope();
+        self.aliases.push_scope();
+        self.owners.push_scope();
+        self.locations.push_scope();
+    }
+    
+    /// Close the innermost scope, undoing every declaration and alias made in it
+    fn pop_scope(&mut self) {
+        self.permissions.pop_scope();
+        self.aliases.pop_scope();
+        self.owners.pop_scope();
+        self.locations.pop_scope();
+    }
+    
+    /// Register a variable with its permissions
+    fn register_variable(&mut self, name: &str, perms: &[Permission]) {
+        let name = Name::new(name);
+        self.permissions.insert(name, perms.to_vec());
+        self.consumed.remove(&name);
+        self.owners.remove(&name);
+        
+        // Initialize alias set
+        self.aliases.insert(name, HashSet::from([name]));
     }
     
-    /// Check permissions for an assignment
-    fn check_assignment(&mut self, target: &str, value: &HirExpression) {
-        // Check if target has write permission
-        if !self.check_write_permission(target) {
+    /// Report a use of another actor's state
+    fn check_actor_access(&mut self, name: &str, location: &SourceLocation, ctx: &mut AnalysisContext) {
+        let Some(owner) = self.owners.get(&Name::new(name)) else {
+            return;
+        };
+        if self.current_actor.as_ref() == Some(owner) {
             return;
         }
         
-        // Check value permissions
-        self.check_expression_permissions(value);
+        ctx.report_permission(PermissionError::ActorStateAccess {
+            variable: name.to_string(),
+            actor: owner.clone(),
+            accessor: self.current_actor.clone(),
+            span: location.known().copied().or_else(|| self.span_of(name)),
+        });
     }
     
-    /// Check permissions for an expression
-    fn check_expression_permissions(&mut self, expr: &HirExpression) {
-        match expr {
-            HirExpression::Integer(_, _) => (), // No permission checking needed for literals
-            HirExpression::Boolean(_) => (), // No permission checking needed for literals
-            HirExpression::String(_) => (),  // No permission checking needed for literals
-            
-            HirExpression::Variable(name, _, _) => {
-                // Check if variable has read permission
-                self.check_read_permission(name);
-            },
-            
-            HirExpression::Binary { left, right, .. } => {
-                self.check_expression_permissions(left);
-                self.check_expression_permissions(right);
-            },
-            
-            HirExpression::Call { arguments, .. } => {
-                for arg in arguments {
-                    self.check_expression_permissions(arg);
-                }
-            },
-            
-            HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
-                self.check_expression_permissions(condition);
-                self.check_expression_permissions(then_expr);
-                self.check_expression_permissions(else_expr);
-            },
-            
-            HirExpression::Cast { expr, .. } => {
-                self.check_expression_permissions(expr);
-            },
-            
-            HirExpression::Peak(expr) => {
-                // For Peak, we need to check special permission rules
-                if let HirExpression::Variable(name, _, _) = &**expr {
-                    self.check_peak_permission(name);
-                } else {
-                    self.check_expression_permissions(expr);
-                }
-            },
-            
-            HirExpression::Clone(expr) => {
-                self.check_expression_permissions(expr);
-            },
-        }
+    /// Report a use of a variable after its value was consumed
+    fn check_not_consumed(&mut self, name: &str, location: &SourceLocation, ctx: &mut AnalysisContext) -> bool {
+        let Some(consumed_at) = self.consumed.get(&Name::new(name)) else {
+            return true;
+        };
+        
+        ctx.report_permission(PermissionError::UseAfterConsume {
+            name: name.to_string(),
+            consumed_at: *consumed_at,
+            used_at: location.known().copied(),
+        });
+        false
     }
     
     /// Check for proper aliasing permissions
-    fn check_aliasing(&mut self, target_name: &str, source_name: &str, target_perms: &[Permission]) {
-        let (has_shareable_perm, source_perms) = self.check_aliasing_permission(source_name);
-        
-        if !has_shareable_perm {
+    fn check_aliasing(
+        &mut self,
+        target_name: &str,
+        source_name: &str,
+        target_perms: &[Permission],
+        location: &SourceLocation,
+        ctx: &mut AnalysisContext,
+    ) {
+        if !self.check_aliasing_permission(source_name, location, ctx) {
             return;
         }
         
-        // Check write permission conflicts
+        let (target, source) = (Name::new(target_name), Name::new(source_name));
+        
+        // Check write permission conflicts. Aliases that are never used
+        // again cannot observe the new writer.
         if target_perms.contains(&Permission::Write) {
-            let conflicting_aliases = self.aliases.get(source_name)
+            let conflicting_aliases = self.aliases.get(&source)
                 .map(|aliases| {
                     aliases.iter()
-                        .filter(|&alias| alias != target_name)
-                        .filter_map(|alias| {
-                            self.permissions.get(alias).map(|perms| 
-                                (alias.clone(), perms.contains(&Permission::Write))
-                            )
-                        })
-                        .filter(|(_, has_write)| *has_write)
-                        .map(|(name, _)| name)
+                        .filter(|&&alias| alias != target && self.is_live(alias))
+                        .filter(|alias| self.permissions.get(alias).is_some_and(|perms| perms.contains(&Permission::Write)))
+                        .copied()
                         .collect::<Vec<_>>()
                 })
                 .unwrap_or_default();
             
             for existing in &conflicting_aliases {
-                self.errors.push(PermissionError {
-                    message: format!("Cannot create write alias to '{}' - '{}' already has write permission", 
-                                   source_name, existing),
-                    location: None,
+                ctx.report_permission(PermissionError::WriteAliasConflict {
+                    source: source_name.to_string(),
+                    existing: existing.to_string(),
+                    span: self.span_of(target_name),
                 });
             }
         }
         
         // Update alias sets safely
-        let source_aliases = self.aliases.get(source_name).cloned().unwrap_or_default();
+        let source_aliases = self.aliases.get(&source).cloned().unwrap_or_default();
         
         let mut updated_set = source_aliases.clone();
-        updated_set.insert(target_name.to_string());
-        self.aliases.insert(target_name.to_string(), updated_set.clone());
+        updated_set.insert(target);
+        self.aliases.insert(target, updated_set);
         
-        if let Some(source_set) = self.aliases.get_mut(source_name) {
-            source_set.insert(target_name.to_string());
+        if let Some(source_set) = self.aliases.get_mut(&source) {
+            source_set.insert(target);
         }
         
         for alias in &source_aliases {
-            if alias != target_name && alias != source_name {
+            if *alias != target && *alias != source {
                 if let Some(other_set) = self.aliases.get_mut(alias) {
-                    other_set.insert(target_name.to_string());
+                    other_set.insert(target);
                 }
             }
         }
     }
     
-    /// Check permissions for a function call expression
-    pub fn check_function_call(&mut self, function_name: &str, arguments: &[HirExpression]) {
-        for arg in arguments {
-            self.check_expression_permissions(arg);
+    /// Permissions of a variable, reporting it if it is not declared
+    fn declared(&self, name: &str, operation: Operation, location: &SourceLocation, ctx: &mut AnalysisContext) -> Option<Vec<Permission>> {
+        let declared = self.permissions.get(&Name::new(name)).cloned();
+        if declared.is_none() {
+            ctx.report_permission(PermissionError::UndefinedVariable {
+                name: name.to_string(),
+                operation,
+                span: location.known().copied(),
+            });
         }
+        declared
     }
     
-    /// Check if a variable can be passed to a parameter with given permissions
-    pub fn check_parameter_compatibility(&mut self, var_name: &str, param_name: &str, param_perms: &[Permission]) {
-        if let Some(var_perms) = self.permissions.get(var_name) {
-            let param_needs_exclusive = param_perms.contains(&Permission::Read) && 
-                                       param_perms.contains(&Permission::Write) && 
-                                       !param_perms.contains(&Permission::Reads) && 
-                                       !param_perms.contains(&Permission::Writes);
-                                       
-            if param_needs_exclusive {
-                let var_has_exclusive = var_perms.contains(&Permission::Read) && 
-                                       var_perms.contains(&Permission::Write) && 
-                                       !var_perms.contains(&Permission::Reads) && 
-                                       !var_perms.contains(&Permission::Writes);
-                                       
-                if !var_has_exclusive {
-                    self.errors.push(PermissionError {
-                        message: format!("Cannot pass '{}' to parameter '{}' - parameter requires exclusive access", 
-                                       var_name, param_name),
-                        location: None,
-                    });
-                }
-            }
-            
-            if (param_perms.contains(&Permission::Read) || param_perms.contains(&Permission::Reads))
-                && !var_perms.contains(&Permission::Read) && !var_perms.contains(&Permission::Reads) {
-                self.errors.push(PermissionError {
-                    message: format!("Cannot pass '{}' to parameter '{}' - parameter requires read permission", 
-                                   var_name, param_name),
-                    location: None,
-                });
-            }
-            
-            if (param_perms.contains(&Permission::Write) || param_perms.contains(&Permission::Writes))
-                && !var_perms.contains(&Permission::Write) && !var_perms.contains(&Permission::Writes) {
-                self.errors.push(PermissionError {
-                    message: format!("Cannot pass '{}' to parameter '{}' - parameter requires write permission", 
-                                   var_name, param_name),
-                    location: None,
-                });
-            }
-            
-            if param_perms.contains(&Permission::Write) && !param_perms.contains(&Permission::Writes) {
-                if let Some(aliases) = self.aliases.get(var_name) {
-                    if aliases.len() > 1 {
-                        self.errors.push(PermissionError {
-                            message: format!("Cannot pass aliased variable '{}' to parameter '{}' requiring exclusive write access", 
-                                           var_name, param_name),
-                            location: None,
-                        });
-                    }
-                }
-            }
+    /// Check write permissions for an assignment
+    fn check_write_permission(&mut self, target: &str, location: &SourceLocation, ctx: &mut AnalysisContext) -> bool {
+        let Some(perms) = self.declared(target, Operation::Write, location, ctx) else {
+            return false;
+        };
+        
+        let has_write = perms.is_empty() || perms.contains(&Permission::Write) || perms.contains(&Permission::Writes);
+        if !has_write {
+            ctx.report_permission(PermissionError::MissingWrite {
+                name: target.to_string(),
+                declared: perms,
+                span: location.known().copied().or_else(|| self.span_of(target)),
+            });
         }
+        has_write
     }
     
-    /// Register a function parameter with its permissions
-    pub fn register_parameter(&mut self, name: &str, permissions: &[Permission]) {
-        self.register_variable(name, permissions);
+    /// Check read permissions for variable access
+    fn check_read_permission(&mut self, target: &str, location: &SourceLocation, ctx: &mut AnalysisContext) {
+        let Some(perms) = self.declared(target, Operation::Read, location, ctx) else {
+            return;
+        };
+        
+        if !can_read(&perms) {
+            ctx.report_permission(PermissionError::MissingRead {
+                name: target.to_string(),
+                declared: perms,
+                span: location.known().copied().or_else(|| self.span_of(target)),
+            });
+        }
     }
     
-    /// Get all accumulated errors
-    pub fn get_errors(&self) -> Vec<PermissionError> {
-        self.errors.clone()
+    /// Check permissions for peak operation
+    fn check_peak_permission(&mut self, target: &str, location: &SourceLocation, ctx: &mut AnalysisContext) {
+        let Some(perms) = self.declared(target, Operation::Peak, location, ctx) else {
+            return;
+        };
+        
+        if !can_read(&perms) {
+            ctx.report_permission(PermissionError::PeakWithoutRead {
+                name: target.to_string(),
+                declared: perms,
+                span: location.known().copied().or_else(|| self.span_of(target)),
+            });
+        }
     }
     
-    /// Check if a variable can be passed to a function argument with given permissions
-    pub fn check_variable_aliasing_for_function_arg(
-        &mut self,
-        var_name: &str, 
-        param_permissions: &[Permission],
-        function_name: &str,
-        param_index: usize
-    ) -> Option<PermissionError> {
-        let param_needs_exclusive = param_permissions.contains(&Permission::Read) && 
-                                  param_permissions.contains(&Permission::Write) && 
-                                  !param_permissions.contains(&Permission::Reads) && 
-                                  !param_permissions.contains(&Permission::Writes);
+    /// Check if aliasing is allowed for a variable, and whether the alias
+    /// is tracked
+    fn check_aliasing_permission(&mut self, source: &str, location: &SourceLocation, ctx: &mut AnalysisContext) -> bool {
+        let Some(perms) = self.declared(source, Operation::Alias, location, ctx) else {
+            return false;
+        };
+        
+        let has_shareable_perm = perms.iter().any(|p| matches!(p, Permission::Reads | Permission::Writes));
+        let has_read = perms.contains(&Permission::Read) || perms.contains(&Permission::Reads);
         
-        if param_needs_exclusive {
-            return Some(PermissionError {
-                message: format!(
-                    "Parameter {} of function '{}' requires exclusive permission (like Pony's iso), but this cannot be guaranteed for '{}'",
-                    param_index + 1,
-                    function_name,
-                    var_name
-                ),
-                location: None,
+        if !has_shareable_perm && has_read {
+            ctx.report_permission(PermissionError::IllegalAlias {
+                source: source.to_string(),
+                declared: perms,
+                span: self.span_of(source),
             });
         }
         
-        None
+        has_shareable_perm
+    }
+}
+
+/// Whether a variable declared with these permissions may be read
+fn can_read(permissions: &[Permission]) -> bool {
+    permissions.is_empty() || permissions.iter().any(|p| matches!(p, Permission::Read | Permission::Reads))
+}
+
+/// Names read or assigned anywhere in a list of statements
+fn used_names(statements: &[HirStatement]) -> HashSet<Name> {
+    #[derive(Default)]
+    struct NameCollector {
+        names: HashSet<Name>,
     }
     
-    /// Check write permissions for an assignment
-    fn check_write_permission(&mut self, target: &str) -> bool {
-        match self.permissions.get(target) {
-            Some(perms) => {
-                let has_write = perms.contains(&Permission::Write) || perms.contains(&Permission::Writes);
-                if !has_write {
-                    let mut message = format!("Cannot write to '{}' - no write permission", target);
-                    
-                    if let Some(location) = self.locations.get(target) {
-                        message = format!("{} | x = y\n    ~ -> Cannot write to '{}' - no write permission", location.0, target);
-                        
-                        if perms.contains(&Permission::Reads) {
-                            message.push_str(&format!("\nsuggestion: reads write {} -> add write permission", target));
-                        } else if perms.contains(&Permission::Read) {
-                            message.push_str(&format!("\nsuggestion: read write {} -> add write permission", target));
-                        } else {
-                            message.push_str(&format!("\nsuggestion: write {} -> add write permission", target));
-                        }
-                    } else {
-                        if perms.contains(&Permission::Reads) {
-                            message.push_str(&format!("\n\nSuggestion:\nreads write {0}: Int = ...\n      ~~~~~ -> add write permission here", target));
-                        } else if perms.contains(&Permission::Read) {
-                            message.push_str(&format!("\n\nSuggestion:\nread write {0}: Int = ...\n     ~~~~~ -> add write permission here", target));
-                        } else {
-                            message.push_str(&format!("\n\nSuggestion:\nwrite {0}: Int = ...\n~~~~~ -> add write permission here", target));
-                        }
-                    }
-                    
-                    self.errors.push(PermissionError {
-                        message,
-                        location: None,
-                    });
-                }
-                has_write
-            },
-            None => {
-                self.errors.push(PermissionError {
-                    message: format!("Cannot write to '{}' - variable not found", target),
-                    location: None,
-                });
-                false
+    impl HirVisitor for NameCollector {
+        fn visit_statement(&mut self, stmt: &HirStatement) {
+            if let HirStatement::Assignment(assign) = stmt {
+                self.names.insert(Name::new(&assign.target));
             }
+            visitor::walk_statement(self, stmt);
         }
-    }
-
-    /// Check read permissions for variable access
-    fn check_read_permission(&mut self, target: &str) -> bool {
-        match self.permissions.get(target) {
-            Some(perms) => {
-                let has_read = perms.contains(&Permission::Read) || perms.contains(&Permission::Reads);
-                if !has_read {
-                    let mut message = format!("Cannot read from '{}' - no read permission", target);
-                    
-                    if let Some(location) = self.locations.get(target) {
-                        message = format!("{} | x = y\n    ~ -> Cannot read from '{}' - no read permission", location.0, target);
-                        
-                        if perms.contains(&Permission::Writes) {
-                            message.push_str(&format!("\nsuggestion: reads writes {} -> add reads permission", target));
-                        } else if perms.contains(&Permission::Write) {
-                            message.push_str(&format!("\nsuggestion: read write {} -> add read permission", target));
-                        } else {
-                            message.push_str(&format!("\nsuggestion: read {} -> add read permission", target));
-                        }
-                    } else {
-                        if perms.contains(&Permission::Writes) {
-                            message.push_str(&format!("\n\nSuggestion:\nreads writes {0}: Int = ...\n~~~~~ -> add reads permission here", target));
-                        } else if perms.contains(&Permission::Write) {
-                            message.push_str(&format!("\n\nSuggestion:\nread write {0}: Int = ...\n~~~~ -> add read permission here", target));
-                        } else {
-                            message.push_str(&format!("\n\nSuggestion:\nread {0}: Int = ...\n~~~~ -> add read permission here", target));
-                        }
-                    }
-                    
-                    self.errors.push(PermissionError {
-                        message,
-                        location: None,
-                    });
-                }
-                has_read
-            },
-            None => {
-                self.errors.push(PermissionError {
-                    message: format!("Cannot read from '{}' - variable not found", target),
-                    location: None,
-                });
-                false
+        
+        fn visit_expression(&mut self, expr: &HirExpression) {
+            if let HirExpression::Variable(name, _, _, _) = expr {
+                self.names.insert(Name::new(name));
             }
+            visitor::walk_expression(self, expr);
         }
     }
     
-    /// Check permissions for peak operation
-    fn check_peak_permission(&mut self, target: &str) -> bool {
-        match self.permissions.get(target) {
-            Some(perms) => {
-                let has_read = perms.contains(&Permission::Read) || perms.contains(&Permission::Reads);
-                if !has_read {
-                    let mut message = format!("Cannot peak '{}' - peak requires read permission", target);
-                    
-                    if let Some(location) = self.locations.get(target) {
-                        message = format!("{} | x = y\n    ~ -> Cannot peak '{}' - peak requires read permission", location.0, target);
-                        
-                        if perms.contains(&Permission::Write) {
-                            message.push_str(&format!("\nsuggestion: read write {} -> add read permission", target));
-                        } else if perms.contains(&Permission::Writes) {
-                            message.push_str(&format!("\nsuggestion: reads writes {} -> add reads permission", target));
-                        } else {
-                            message.push_str(&format!("\nsuggestion: read {} -> add read permission", target));
-                        }
-                    } else {
-                        if perms.contains(&Permission::Write) {
-                            message.push_str(&format!("\n\nSuggestion:\nread write {0}: Int = ...\n~~~~ -> add read permission here", target));
-                        } else if perms.contains(&Permission::Writes) {
-                            message.push_str(&format!("\n\nSuggestion:\nreads writes {0}: Int = ...\n~~~~~ -> add reads permission here", target));
-                        } else {
-                            message.push_str(&format!("\n\nSuggestion:\nread {0}: Int = ...\n~~~~ -> add read permission here", target));
-                        }
-                    }
-                    
-                    self.errors.push(PermissionError {
-                        message,
-                        location: None,
-                    });
-                }
-                has_read
-            },
-            None => {
-                self.errors.push(PermissionError {
-                    message: format!("Cannot peak '{}' - variable not found", target),
-                    location: None,
-                });
-                false
-            }
+    let mut collector = NameCollector::default();
+    for stmt in statements {
+        collector.visit_statement(stmt);
+    }
+    collector.names
+}
+
+/// Permissions of a field as seen through a reference with the `path`
+/// permissions (viewpoint adaptation).
+///
+/// The path caps what the field allows: a field can only be read through a
+/// readable path and written through a writable one, and a shared path
+/// (`reads`/`writes`) only ever yields shared access. Reading `p.x` through
+/// a `reads` reference therefore gives at most `reads`, whatever the field
+/// was declared with. Nested paths adapt one step at a time, e.g.
+/// `adapt_viewpoint(&adapt_viewpoint(p, q), x)` for `p.q.x`.
+///
+/// The language has no structs yet; this is the rule field access will use.
+pub fn adapt_viewpoint(path: &[Permission], field: &[Permission]) -> Vec<Permission> {
+    let mut adapted = Vec::new();
+    
+    let field_read = field.iter().find(|p| matches!(p, Permission::Read | Permission::Reads));
+    if let Some(read) = field_read {
+        if path.contains(&Permission::Reads) {
+            adapted.push(Permission::Reads);
+        } else if path.contains(&Permission::Read) {
+            adapted.push(read.clone());
         }
     }
     
-    /// Check if aliasing is allowed for a variable
-    fn check_aliasing_permission(&mut self, source: &str) -> (bool, Vec<Permission>) {
-        match self.permissions.get(source) {
-            Some(perms) => {
-                let has_shareable_perm = perms.iter().any(|p| 
-                    matches!(p, Permission::Reads | Permission::Writes)
-                );
-                
-                let has_read = perms.contains(&Permission::Read) || perms.contains(&Permission::Reads);
-                
-                if !has_shareable_perm && has_read {
-                    let mut message = format!("Cannot create alias to '{}' - variable has non-shareable permissions", source);
-                    
-                    if perms.contains(&Permission::Read) && perms.contains(&Permission::Write) {
-                        message.push_str(&format!("\nsuggestion: reads writes {} -> use shareable permissions instead of read write", source));
-                    } else if perms.contains(&Permission::Read) {
-                        message.push_str(&format!("\nsuggestion: reads {} -> use reads instead of read", source));
-                    } else if perms.contains(&Permission::Write) {
-                        message.push_str(&format!("\nsuggestion: writes {} -> use writes instead of write", source));
-                    }
-                    
-                    self.errors.push(PermissionError {
-                        message,
-                        location: None,
-                    });
-                }
-                
-                (has_shareable_perm, perms.clone())
-            },
-            None => {
-                self.errors.push(PermissionError {
-                    message: format!("Cannot alias '{}' - variable not found", source),
-                    location: None,
-                });
-                (false, vec![])
-            }
+    let field_write = field.iter().find(|p| matches!(p, Permission::Write | Permission::Writes));
+    if let Some(write) = field_write {
+        if path.contains(&Permission::Writes) {
+            adapted.push(Permission::Writes);
+        } else if path.contains(&Permission::Write) {
+            adapted.push(write.clone());
         }
     }
+    
+    adapted
+}
+
+/// Whether a value with these permissions may be sent to a behavior, which
+/// runs concurrently with the sender.
+///
+/// Only two kinds of value are safe to share across that boundary: exclusive
+/// ones (`read write`, like Pony's iso), whose sender has the only
+/// reference, and immutable shareable ones (`reads`, like Pony's val), which
+/// nobody can write. Declarations without permissions are still to be
+/// inferred and are not rejected.
+pub fn is_sendable(permissions: &[Permission]) -> bool {
+    unsendable_reason(permissions).is_none()
 }
 
-/// Create a new public function to check permissions with source code
-pub fn check_permissions_with_source(program: &HirProgram, source: &str) -> Vec<PermissionError> {
-    let mut checker = PermissionChecker::new();
-    checker.check_program_with_source(program, source)
-}
\ No newline at end of file
+/// Why a value with these permissions cannot be sent to a behavior, naming
+/// the permission at fault, or `None` if it can
+pub fn unsendable_reason(permissions: &[Permission]) -> Option<String> {
+    let has = |p: Permission| permissions.contains(&p);
+    
+    if permissions.is_empty() {
+        return None;
+    }
+    if has(Permission::Writes) {
+        return Some("'writes' lets other references change the value while the behavior uses it".to_string());
+    }
+    match (has(Permission::Read), has(Permission::Reads), has(Permission::Write)) {
+        // iso and val
+        (true, false, true) | (_, true, false) => None,
+        (_, true, true) => Some("'write' lets the value change while other 'reads' references read it".to_string()),
+        (true, false, false) => Some("'read' alone is neither exclusive ('read write') nor immutable ('reads')".to_string()),
+        (false, false, _) => Some("'write' alone is neither exclusive ('read write') nor immutable ('reads')".to_string()),
+    }
+}
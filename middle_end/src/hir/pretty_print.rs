@@ -1,8 +1,14 @@
 //! Pretty printer for HIR
 //!
-//! This module provides functionality to print HIR in a human-readable format.
+//! This module prints HIR in the language's own concrete syntax, with
+//! permission annotations and explicit types, so the output can be parsed
+//! again by the front end. Binary expressions are parenthesized only where
+//! precedence requires it. Constructs without surface syntax yet (casts and
+//! conditional expressions) are printed in a readable approximation.
 
 use crate::hir::types::*;
+use front_end::token::TokenType;
+use front_end::types::Permission;
 use std::fmt::Write;
 
 /// Pretty-print a HIR program to a string
@@ -27,172 +33,227 @@ impl HirPrinter {
             indent: 0,
         }
     }
-    
+
     /// Print a HIR program
     fn print_program(&mut self, program: &HirProgram) -> String {
-        writeln!(self.output, "HIR Program with {} statements", program.statements.len()).unwrap();
-        
+        // Members of an actor were flattened by the converter; group each
+        // run of them back into an `actor` declaration
+        let mut current: Option<&str> = None;
         for stmt in &program.statements {
+            let actor = actor_of(stmt);
+            if actor != current {
+                if current.is_some() {
+                    self.indent -= 1;
+                    writeln!(self.output, "}}").unwrap();
+                }
+                if let Some(name) = actor {
+                    writeln!(self.output, "actor {} {{", name).unwrap();
+                    self.indent += 1;
+                }
+                current = actor;
+            }
             self.print_statement(stmt);
         }
-        
-        // Print type information
-        writeln!(self.output, "\nType Information:").unwrap();
-        writeln!(self.output, "  Variables: {} entries", program.type_info.variables.len()).unwrap();
-        for (name, typ) in &program.type_info.variables {
-            writeln!(self.output, "    {}: {:?}", name, typ).unwrap();
-        }
-        
-        writeln!(self.output, "  Functions: {} entries", program.type_info.functions.len()).unwrap();
-        for (name, return_type) in &program.type_info.functions {
-            writeln!(self.output, "    {}() -> {:?}", name, return_type).unwrap();
+        if current.is_some() {
+            self.indent -= 1;
+            writeln!(self.output, "}}").unwrap();
         }
-        
+
         self.output.clone()
     }
-    
+
     /// Print a statement with proper indentation
     fn print_statement(&mut self, stmt: &HirStatement) {
         self.print_indent();
-        
+
         match stmt {
             HirStatement::Declaration(var) => {
-                        // Print permissions
-                        let perms: Vec<String> = var.permissions.iter()
-                            .map(|p| format!("{:?}", p).to_lowercase())
-                            .collect();
-                
-                        writeln!(self.output, "var {} : {:?} [{}]", 
-                            var.name, var.typ, perms.join(", ")).unwrap();
-                
-                        if let Some(init) = &var.initializer {
-                            self.indent += 1;
-                            self.print_indent();
-                            write!(self.output, "= ").unwrap();
-                            self.print_expression(init);
-                            writeln!(self.output).unwrap();
-                            self.indent -= 1;
-                        }
-                    },
+                write!(self.output, "{}{}: {:?}", permission_prefix(&var.permissions), var.name, var.typ).unwrap();
+                if let Some(init) = &var.initializer {
+                    write!(self.output, " = ").unwrap();
+                    self.print_expression(init);
+                }
+                writeln!(self.output).unwrap();
+            },
             HirStatement::Assignment(assign) => {
-                        write!(self.output, "{} = ", assign.target).unwrap();
-                        self.print_expression(&assign.value);
-                        writeln!(self.output).unwrap();
-                    },
+                match &assign.operator {
+                    Some(operator) => write!(self.output, "{} {}= ", assign.target, operator.operator_symbol()).unwrap(),
+                    None => write!(self.output, "{} = ", assign.target).unwrap(),
+                }
+                self.print_expression(&assign.value);
+                writeln!(self.output).unwrap();
+            },
             HirStatement::Function(func) => {
-                        // Function header
-                        write!(self.output, "fn {}(", func.name).unwrap();
-                
-                        for (i, param) in func.parameters.iter().enumerate() {
-                            if i > 0 { write!(self.output, ", ").unwrap(); }
-                    
-                            let perms: Vec<String> = param.permissions.iter()
-                                .map(|p| format!("{:?}", p).to_lowercase())
-                                .collect();
-                    
-                            write!(self.output, "{}: {:?} [{}]", 
-                                param.name, param.typ, perms.join(", ")).unwrap();
-                        }
-                
-                        if let Some(ret_type) = &func.return_type {
-                            write!(self.output, ") -> {:?}", ret_type).unwrap();
-                        } else {
-                            write!(self.output, ")").unwrap();
-                        }
-                
-                        writeln!(self.output, " {{").unwrap();
-                
-                        // Function body
-                        self.indent += 1;
-                        for body_stmt in &func.body {
-                            self.print_statement(body_stmt);
-                        }
-                        self.indent -= 1;
-                
-                        self.print_indent();
-                        writeln!(self.output, "}}").unwrap();
-                    },
-            HirStatement::Return(expr_opt) => {
-                        write!(self.output, "return").unwrap();
-                        if let Some(expr) = expr_opt {
-                            write!(self.output, " ").unwrap();
-                            self.print_expression(expr);
-                        }
-                        writeln!(self.output).unwrap();
-                    },
-            HirStatement::Print(expr) => {
-                        write!(self.output, "print ").unwrap();
-                        self.print_expression(expr);
-                        writeln!(self.output).unwrap();
-                    },
+                let keyword = if func.is_behavior { "on" } else { "fn" };
+                write!(self.output, "{} {}(", keyword, func.name).unwrap();
+                for (i, param) in func.parameters.iter().enumerate() {
+                    if i > 0 { write!(self.output, ", ").unwrap(); }
+                    write!(self.output, "{}{}: {:?}", permission_prefix(&param.permissions), param.name, param.typ).unwrap();
+                }
+                write!(self.output, ")").unwrap();
+                if let Some(ret_type) = &func.return_type {
+                    write!(self.output, " -> {:?}", ret_type).unwrap();
+                }
+                writeln!(self.output, " {{").unwrap();
+
+                self.print_body(&func.body);
+
+                self.print_indent();
+                writeln!(self.output, "}}").unwrap();
+            },
+            HirStatement::Return(expr_opt, _) => {
+                write!(self.output, "return").unwrap();
+                if let Some(expr) = expr_opt {
+                    write!(self.output, " ").unwrap();
+                    self.print_expression(expr);
+                }
+                writeln!(self.output).unwrap();
+            },
+            HirStatement::Print(expr, _) => {
+                write!(self.output, "print ").unwrap();
+                self.print_expression(expr);
+                writeln!(self.output).unwrap();
+            },
             HirStatement::Expression(expr) => {
-                        self.print_expression(expr);
-                        writeln!(self.output).unwrap();
-                    },
-            HirStatement::Block(statements) => {
-                        writeln!(self.output, "{{").unwrap();
-                        self.indent += 1;
-                        for stmt in statements {
-                            self.print_statement(stmt);
-                        }
-                        self.indent -= 1;
-                        self.print_indent();
-                        writeln!(self.output, "}}").unwrap();
-                    },
-HirStatement::If { condition, then_branch, else_branch } => todo!(),
-            HirStatement::While { condition, body } => todo!(),
+                self.print_expression(expr);
+                writeln!(self.output).unwrap();
+            },
+            HirStatement::Block(statements, _) => {
+                writeln!(self.output, "{{").unwrap();
+                self.print_body(statements);
+                self.print_indent();
+                writeln!(self.output, "}}").unwrap();
+            },
+            HirStatement::Atomic(statements, _) => {
+                writeln!(self.output, "atomic {{").unwrap();
+                self.print_body(statements);
+                self.print_indent();
+                writeln!(self.output, "}}").unwrap();
+            },
+            HirStatement::If { condition, then_branch, else_branch, .. } => {
+                write!(self.output, "if ").unwrap();
+                self.print_expression(condition);
+                writeln!(self.output, " {{").unwrap();
+                self.print_branch(then_branch);
+                self.print_indent();
+                if let Some(else_branch) = else_branch {
+                    writeln!(self.output, "}} else {{").unwrap();
+                    self.print_branch(else_branch);
+                    self.print_indent();
+                }
+                writeln!(self.output, "}}").unwrap();
+            },
+            HirStatement::While { condition, body, .. } => {
+                write!(self.output, "while ").unwrap();
+                self.print_expression(condition);
+                writeln!(self.output, " {{").unwrap();
+                self.print_branch(body);
+                self.print_indent();
+                writeln!(self.output, "}}").unwrap();
+            },
+            HirStatement::Break(_) => writeln!(self.output, "break").unwrap(),
+            HirStatement::Continue(_) => writeln!(self.output, "continue").unwrap(),
+        }
+    }
+
+    /// Print the statements of a body one level deeper
+    fn print_body(&mut self, statements: &[HirStatement]) {
+        self.indent += 1;
+        for stmt in statements {
+            self.print_statement(stmt);
+        }
+        self.indent -= 1;
+    }
+
+    /// Print the body of a branch or loop, whose braces are already open
+    fn print_branch(&mut self, stmt: &HirStatement) {
+        match stmt {
+            HirStatement::Block(statements, _) => self.print_body(statements),
+            other => self.print_body(std::slice::from_ref(other)),
         }
     }
-    
+
     /// Print an expression
     fn print_expression(&mut self, expr: &HirExpression) {
         match expr {
             HirExpression::Integer(val, _) => {
-                        write!(self.output, "{}", val).unwrap();
-                    },
-            HirExpression::Boolean(val) => {
-                        write!(self.output, "{}", val).unwrap();
-                    },
-            HirExpression::String(val) => {
-                        write!(self.output, "\"{}\"", val).unwrap();
-                    },
-            HirExpression::Variable(name, typ, _) => {
-                        write!(self.output, "{}: {:?}", name, typ).unwrap();
-                    },
-            HirExpression::Binary { left, operator, right, result_type } => {
-                        write!(self.output, "(").unwrap();
-                        self.print_expression(left);
-                        write!(self.output, " {:?} ", operator).unwrap();
-                        self.print_expression(right);
-                        write!(self.output, "): {:?}", result_type).unwrap();
-                    },
-            HirExpression::Call { function, arguments, result_type } => {
-                        write!(self.output, "{}(", function).unwrap();
-                        for (i, arg) in arguments.iter().enumerate() {
-                            if i > 0 { write!(self.output, ", ").unwrap(); }
-                            self.print_expression(arg);
-                        }
-                        write!(self.output, "): {:?}", result_type).unwrap();
-                    },
-            HirExpression::Cast { expr, target_type } => {
-                        write!(self.output, "cast<").unwrap();
-                        write!(self.output, "{:?}>", target_type).unwrap();
-                        write!(self.output, "(").unwrap();
-                        self.print_expression(expr);
-                        write!(self.output, ")").unwrap();
-                    },
-            HirExpression::Peak(expr) => {
-                        write!(self.output, "peak ").unwrap();
-                        self.print_expression(expr);
-                    },
-            HirExpression::Clone(expr) => {
-                        write!(self.output, "clone ").unwrap();
-                        self.print_expression(expr);
-                    },
-HirExpression::Conditional { condition, then_expr, else_expr, result_type } => todo!(),
+                write!(self.output, "{}", val).unwrap();
+            },
+            HirExpression::Boolean(val, _) => {
+                write!(self.output, "{}", val).unwrap();
+            },
+            HirExpression::String(val, _) => {
+                write!(self.output, "{:?}", val).unwrap();
+            },
+            HirExpression::Variable(name, _, _, _) => {
+                write!(self.output, "{}", name).unwrap();
+            },
+            HirExpression::Binary { left, operator, right, .. } => {
+                let level = precedence(operator);
+                // Operators are left-associative, so only the right operand
+                // needs parentheses at equal precedence
+                self.print_operand(left, level, false);
+                write!(self.output, " {} ", operator.operator_symbol()).unwrap();
+                self.print_operand(right, level, true);
+            },
+            HirExpression::Call { function, arguments, .. } => {
+                write!(self.output, "{}(", function).unwrap();
+                for (i, arg) in arguments.iter().enumerate() {
+                    if i > 0 { write!(self.output, ", ").unwrap(); }
+                    self.print_expression(arg);
+                }
+                write!(self.output, ")").unwrap();
+            },
+            HirExpression::Cast { expr, target_type, .. } => {
+                write!(self.output, "{:?}(", target_type).unwrap();
+                self.print_expression(expr);
+                write!(self.output, ")").unwrap();
+            },
+            HirExpression::Peak(expr, _) => {
+                write!(self.output, "peak ").unwrap();
+                self.print_operand(expr, usize::MAX, false);
+            },
+            HirExpression::Clone(expr, _) => {
+                write!(self.output, "clone ").unwrap();
+                self.print_operand(expr, usize::MAX, false);
+            },
+            HirExpression::Consume(expr, _) => {
+                write!(self.output, "consume ").unwrap();
+                self.print_operand(expr, usize::MAX, false);
+            },
+            HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
+                write!(self.output, "if ").unwrap();
+                self.print_expression(condition);
+                write!(self.output, " {{ ").unwrap();
+                self.print_expression(then_expr);
+                write!(self.output, " }} else {{ ").unwrap();
+                self.print_expression(else_expr);
+                write!(self.output, " }}").unwrap();
+            },
         }
     }
-    
+
+    /// Print an operand of an operator with the given precedence,
+    /// parenthesizing binary expressions that bind more loosely
+    fn print_operand(&mut self, expr: &HirExpression, level: usize, is_right: bool) {
+        let needs_parens = match expr {
+            HirExpression::Binary { operator, .. } => {
+                let inner = precedence(operator);
+                inner < level || (is_right && inner == level)
+            },
+            HirExpression::Conditional { .. } => true,
+            _ => false,
+        };
+
+        if needs_parens {
+            write!(self.output, "(").unwrap();
+            self.print_expression(expr);
+            write!(self.output, ")").unwrap();
+        } else {
+            self.print_expression(expr);
+        }
+    }
+
     /// Print the current indentation
     fn print_indent(&mut self) {
         for _ in 0..self.indent {
@@ -200,3 +261,28 @@ HirExpression::Conditional { condition, then_expr, else_expr, result_type } => t
         }
     }
 }
+
+/// Binding strength of a binary operator, higher binds tighter
+/// The actor a top-level statement belongs to, if any
+fn actor_of(stmt: &HirStatement) -> Option<&str> {
+    match stmt {
+        HirStatement::Declaration(var) => var.actor.as_deref(),
+        HirStatement::Function(func) => func.actor.as_deref(),
+        _ => None,
+    }
+}
+
+fn precedence(operator: &TokenType) -> usize {
+    match operator {
+        TokenType::Star | TokenType::Slash => 3,
+        TokenType::Plus | TokenType::Minus => 2,
+        _ => 1,
+    }
+}
+
+/// Permission keywords as written before a name, e.g. `reads write `
+fn permission_prefix(permissions: &[Permission]) -> String {
+    permissions.iter()
+        .map(|p| format!("{:?} ", p).to_lowercase())
+        .collect()
+}
@@ -2,6 +2,8 @@
 //!
 //! This module provides scope tracking and symbol management for HIR.
 
+use front_end::intern::Name;
+use front_end::symbol_table::{closest_name, Span};
 use front_end::types::Type;
 use std::collections::{HashMap, HashSet};
 use crate::hir::types;
@@ -44,6 +46,11 @@ impl SourceLocation {
         }
     }
     
+    /// The front end span of this position, named after its file
+    pub fn to_span(&self) -> Span {
+        Span::point(self.line, self.column).with_file(&self.file)
+    }
+    
     /// Creates a new source location with specific position information
     /// This helps create more accurate error messages
     pub fn with_position(line: usize, column: usize, file: String) -> Self {
@@ -81,8 +88,11 @@ impl SourceLocation {
 /// A symbol in the symbol table
 #[derive(Debug, Clone)]
 pub struct Symbol {
+    /// Unique ID of this declaration
+    pub id: types::SymbolId,
+    
     /// Symbol name
-    pub name: String,
+    pub name: Name,
     
     /// Symbol type
     pub typ: Type,
@@ -97,6 +107,37 @@ pub struct Symbol {
     pub location: Option<SourceLocation>,
 }
 
+/// The namespace a symbol lives in. Functions and variables are looked up
+/// separately, so a variable and a function may share a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    /// Variables and parameters
+    Value,
+    
+    /// Functions
+    Function,
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Namespace::Value => write!(f, "variable"),
+            Namespace::Function => write!(f, "function"),
+        }
+    }
+}
+
+impl Symbol {
+    /// The namespace this symbol is defined in
+    pub fn namespace(&self) -> Namespace {
+        if self.is_function {
+            Namespace::Function
+        } else {
+            Namespace::Value
+        }
+    }
+}
+
 /// Error information for scope and name resolution issues
 #[derive(Debug, Clone)]
 pub enum ScopeError {
@@ -106,6 +147,8 @@ pub enum ScopeError {
         name: String,
         /// Location of the reference (optional)
         location: Option<SourceLocation>,
+        /// Closest visible name, if one looks like a typo of `name`
+        suggestion: Option<String>,
     },
     
     /// Symbol already defined in the current scope
@@ -113,6 +156,12 @@ pub enum ScopeError {
         /// Symbol name
         name: String,
         
+        /// Whether the duplicate is a variable or a function
+        namespace: Namespace,
+        
+        /// Location of the redefinition
+        location: Option<SourceLocation>,
+        
         /// Previous definition location
         previous: Option<SourceLocation>,
     },
@@ -125,15 +174,59 @@ pub enum ScopeError {
         /// Previous definition location
         previous: Option<SourceLocation>,
     },
+    
+    /// Symbol exists, but in the other namespace (e.g. calling a variable)
+    WrongNamespace {
+        /// Symbol name
+        name: String,
+        
+        /// Namespace the use site required
+        expected: Namespace,
+        
+        /// Location of the reference (optional)
+        location: Option<SourceLocation>,
+        
+        /// Location of the definition that was found instead
+        definition: Option<SourceLocation>,
+    },
+}
+
+/// The symbols declared in a single scope, one map per namespace
+#[derive(Default)]
+struct Scope {
+    values: HashMap<Name, Symbol>,
+    functions: HashMap<Name, Symbol>,
+}
+
+impl Scope {
+    fn symbols(&self, namespace: Namespace) -> &HashMap<Name, Symbol> {
+        match namespace {
+            Namespace::Value => &self.values,
+            Namespace::Function => &self.functions,
+        }
+    }
+    
+    fn symbols_mut(&mut self, namespace: Namespace) -> &mut HashMap<Name, Symbol> {
+        match namespace {
+            Namespace::Value => &mut self.values,
+            Namespace::Function => &mut self.functions,
+        }
+    }
 }
 
 /// A symbol table that tracks scopes and symbols
 pub struct SymbolTable {
     /// Stack of scopes, with innermost scope at the end
-    scopes: Vec<HashMap<String, Symbol>>,
+    scopes: Vec<Scope>,
     
-    /// Track name usage to detect shadowing
-    used_names: HashSet<String>,
+    /// Track name usage per namespace to detect shadowing
+    used_names: HashSet<(Namespace, Name)>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SymbolTable {
@@ -152,7 +245,7 @@ impl SymbolTable {
     
     /// Enter a new scope
     pub fn enter_scope(&mut self) {
-        self.scopes.push(HashMap::new());
+        self.scopes.push(Scope::default());
     }
     
     /// Exit the current scope
@@ -162,19 +255,21 @@ impl SymbolTable {
         }
     }
     
-    /// Add a symbol to the current scope
+    /// Add a symbol to the current scope, in the namespace it belongs to
     pub fn add_symbol(&mut self, symbol: Symbol) -> Result<(), ScopeError> {
-        let name = symbol.name.clone();
+        let name = symbol.name;
+        let namespace = symbol.namespace();
+        let key = (namespace, name);
         
         // Check scope depth before mutable borrow
-        let is_shadowing = self.scopes.len() > 1 && self.used_names.contains(&name);
+        let is_shadowing = self.scopes.len() > 1 && self.used_names.contains(&key);
         
         // Pre-collect previous definition details for shadowing without borrowing self.scopes twice
         let mut previous_def = None;
         if is_shadowing {
             // Before mutable borrow, scan for previous definition
             for scope in &self.scopes[0..self.scopes.len()-1] {
-                if let Some(sym) = scope.get(&name) {
+                if let Some(sym) = scope.symbols(namespace).get(&name) {
                     previous_def = sym.location.clone();
                     break;
                 }
@@ -183,19 +278,25 @@ impl SymbolTable {
         
         // Now handle the mutable borrow
         if let Some(current_scope) = self.scopes.last_mut() {
-            if current_scope.contains_key(&name) {
-                let prev_loc = current_scope.get(&name).and_then(|sym| sym.location.clone());
-                return Err(ScopeError::AlreadyDefined { name, previous: prev_loc });
+            let symbols = current_scope.symbols_mut(namespace);
+            if let Some(existing) = symbols.get(&name) {
+                let prev_loc = existing.location.clone();
+                return Err(ScopeError::AlreadyDefined {
+                    name: name.to_string(),
+                    namespace,
+                    location: symbol.location,
+                    previous: prev_loc,
+                });
             }
             
             // Insert the symbol
-            current_scope.insert(name.clone(), symbol);
-            self.used_names.insert(name.clone());
+            symbols.insert(name, symbol);
+            self.used_names.insert(key);
             
             // Report shadowing if needed
             if is_shadowing {
                 return Err(ScopeError::Shadowing { 
-                    name, 
+                    name: name.to_string(), 
                     previous: previous_def
                 });
             }
@@ -204,27 +305,48 @@ impl SymbolTable {
         Ok(())
     }
     
-    /// Look up a symbol in all scopes, starting from innermost
+    /// Look up a symbol in one namespace, starting from the innermost scope
+    pub fn lookup_in(&self, name: &str, namespace: Namespace) -> Option<&Symbol> {
+        let name = Name::existing(name)?;
+        self.scopes.iter().rev().find_map(|scope| scope.symbols(namespace).get(&name))
+    }
+    
+    /// Look up a variable or parameter
+    pub fn lookup_value(&self, name: &str) -> Option<&Symbol> {
+        self.lookup_in(name, Namespace::Value)
+    }
+    
+    /// Look up a function
+    pub fn lookup_function(&self, name: &str) -> Option<&Symbol> {
+        self.lookup_in(name, Namespace::Function)
+    }
+    
+    /// Look up a symbol in all scopes, starting from innermost.
+    /// Variables take precedence over functions of the same name.
     pub fn lookup(&self, name: &str) -> Option<&Symbol> {
-        // Search from innermost scope to outermost
-        for scope in self.scopes.iter().rev() {
-            if let Some(symbol) = scope.get(name) {
-                return Some(symbol);
-            }
-        }
-        
-        None
+        self.lookup_value(name).or_else(|| self.lookup_function(name))
+    }
+    
+    /// Find the visible name in `namespace` closest to `name`, for
+    /// "did you mean" hints
+    pub fn suggest_similar(&self, name: &str, namespace: Namespace) -> Option<String> {
+        let candidates = self.scopes.iter()
+            .flat_map(|scope| scope.symbols(namespace).keys().map(|name| name.as_str()));
+        closest_name(name, candidates)
     }
     
     /// Look up a symbol in the current scope only
     pub fn lookup_in_current_scope(&self, name: &str) -> Option<&Symbol> {
-        self.scopes.last().and_then(|scope| scope.get(name))
+        let name = Name::existing(name)?;
+        self.scopes.last().and_then(|scope| {
+            scope.values.get(&name).or_else(|| scope.functions.get(&name))
+        })
     }
     
     /// Get all symbols defined in the current scope
     pub fn get_current_scope_symbols(&self) -> Vec<&Symbol> {
         if let Some(current_scope) = self.scopes.last() {
-            current_scope.values().collect()
+            current_scope.values.values().chain(current_scope.functions.values()).collect()
         } else {
             Vec::new()
         }
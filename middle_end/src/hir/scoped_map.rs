@@ -0,0 +1,128 @@
+//! Map with nested scopes
+//!
+//! `ScopedMap` behaves like a `HashMap`, but `push_scope`/`pop_scope` undo
+//! every change made since the matching push. Instead of copying the map on
+//! scope entry, each change records the value it replaced, so entering and
+//! leaving a scope costs time proportional to the changes made inside it
+//! rather than to the size of the map.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A hash map whose changes can be rolled back scope by scope
+#[derive(Debug, Clone)]
+pub struct ScopedMap<K, V> {
+    /// Current contents, including changes made in open scopes
+    entries: HashMap<K, V>,
+
+    /// For each open scope, innermost last, the previous value of every
+    /// key changed in it, in the order the changes were made
+    undo: Vec<Vec<(K, Option<V>)>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> ScopedMap<K, V> {
+    /// Create an empty map with no open scopes
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            undo: Vec::new(),
+        }
+    }
+
+    /// Open a scope; changes from here on are undone by `pop_scope`
+    pub fn push_scope(&mut self) {
+        self.undo.push(Vec::new());
+    }
+
+    /// Close the innermost scope, restoring every entry it changed
+    pub fn pop_scope(&mut self) {
+        let Some(changes) = self.undo.pop() else {
+            return;
+        };
+        for (key, previous) in changes.into_iter().rev() {
+            match previous {
+                Some(value) => self.entries.insert(key, value),
+                None => self.entries.remove(&key),
+            };
+        }
+    }
+
+    /// Remember the current value of a key before it changes
+    fn record<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        if let Some(changes) = self.undo.last_mut() {
+            changes.push((key.to_owned(), self.entries.get(key).cloned()));
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.contains_key(key)
+    }
+
+    /// Mutable access to an entry; the old value is kept for `pop_scope`
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        if !self.entries.contains_key(key) {
+            return None;
+        }
+        self.record(key);
+        self.entries.get_mut(key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.record::<K>(&key);
+        self.entries.insert(key, value);
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+    {
+        if !self.entries.contains_key(key) {
+            return None;
+        }
+        self.record(key);
+        self.entries.remove(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter()
+    }
+
+    /// Remove every entry the predicate rejects
+    pub fn retain(&mut self, mut keep: impl FnMut(&K, &V) -> bool) {
+        let removed: Vec<K> = self.entries.iter()
+            .filter(|(key, value)| !keep(key, value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &removed {
+            self.remove::<K>(key);
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for ScopedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
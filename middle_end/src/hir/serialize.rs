@@ -0,0 +1,46 @@
+//! JSON serialization of HIR
+//!
+//! This module writes a `HirProgram`, including permissions and type
+//! information, as JSON and reads it back, so external tools can consume
+//! compiler output. Maps are written in sorted key order, which keeps the
+//! output stable enough to compare against snapshots.
+
+use crate::hir::types::HirProgram;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Serialize a program to pretty-printed JSON
+pub fn to_json(program: &HirProgram) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(program)
+}
+
+/// Deserialize a program from JSON
+pub fn from_json(json: &str) -> Result<HirProgram, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// Write a program as JSON to a file
+pub fn save_program(program: &HirProgram, path: &Path) -> Result<(), String> {
+    let json = to_json(program)
+        .map_err(|e| format!("Failed to serialize HIR: {}", e))?;
+    fs::write(path, json)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Load a program from a JSON file
+pub fn load_program(path: &Path) -> Result<HirProgram, String> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    from_json(&json)
+        .map_err(|e| format!("Invalid HIR in {}: {}", path.display(), e))
+}
+
+/// Serialize a map with its keys in sorted order
+pub(crate) fn sorted_map<K: Ord + Serialize, V: Serialize, S: Serializer>(
+    map: &HashMap<K, V>,
+    serializer: S
+) -> Result<S::Ok, S::Error> {
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+}
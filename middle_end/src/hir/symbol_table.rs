@@ -1,86 +0,0 @@
-//! Symbol table for HIR
-//!
-//! This module defines a comprehensive symbol table for HIR
-
-use crate::hir::types::*;
-use std::collections::{HashMap, HashSet};
-
-/// Symbol kinds 
-#[derive(Debug, Clone, PartialEq)]
-pub enum SymbolKind {
-    Variable,
-    Function,
-    Parameter,
-    Type,
-    Module,
-}
-
-/// Symbol visibility
-#[derive(Debug, Clone, PartialEq)]
-pub enum Visibility {
-    Public,
-    Private,
-    Internal,
-}
-
-/// Complete symbol information
-#[derive(Debug, Clone)]
-pub struct SymbolInfo {
-    /// Symbol name
-    pub name: String,
-    
-    /// Symbol canonical name (after uniquification)
-    pub canonical_name: String,
-    
-    /// Symbol kind
-    pub kind: SymbolKind,
-    
-    /// Symbol type
-    pub typ: Option<Type>,
-    
-    /// Symbol visibility
-    pub visibility: Visibility,
-    
-    /// Symbol permissions (if applicable)
-    pub permissions: Vec<Permission>,
-    
-    /// Symbol location in source code
-    pub location: Option<SourceLocation>,
-    
-    /// Symbol documentation comments
-    pub documentation: Option<String>,
-    
-    /// Definition scope ID
-    pub defining_scope: ScopeId,
-}
-
-/// Scope identifier
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct ScopeId(pub usize);
-
-/// Symbol identifier
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct SymbolId(pub usize);
-
-/// Comprehensive symbol table
-pub struct SymbolTable {
-    /// All symbols by ID
-    symbols: HashMap<SymbolId, SymbolInfo>,
-    
-    /// Symbol IDs by name within each scope
-    scopes: HashMap<ScopeId, HashMap<String, SymbolId>>,
-    
-    /// Parent scope relationship
-    scope_parents: HashMap<ScopeId, ScopeId>,
-    
-    /// Current scope ID counter
-    next_scope_id: usize,
-    
-    /// Current symbol ID counter
-    next_symbol_id: usize,
-}
-
-impl SymbolTable {
-    // Implementation of symbol table methods
-    // ...
-}
@@ -2,12 +2,14 @@
 //!
 //! This module defines the types that make up the HIR structure.
 
+use front_end::symbol_table::Span;
 use front_end::token::TokenType;
 use front_end::types::{Permission, Type};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// A complete HIR program
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HirProgram {
     /// Top-level statements in the program
     pub statements: Vec<HirStatement>,
@@ -16,6 +18,12 @@ pub struct HirProgram {
     pub type_info: TypeInfo,
 }
 
+impl Default for HirProgram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HirProgram {
     /// Create a new empty HIR program
     pub fn new() -> Self {
@@ -32,17 +40,108 @@ impl HirProgram {
 }
 
 /// Type information for the program
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TypeInfo {
     /// Maps variable names to their types
+    #[serde(serialize_with = "crate::hir::serialize::sorted_map")]
     pub variables: HashMap<String, Type>,
     
-    /// Maps function names to their return types
-    pub functions: HashMap<String, Option<Type>>,
+    /// Maps function names to their signatures
+    #[serde(serialize_with = "crate::hir::serialize::sorted_map")]
+    pub functions: HashMap<String, FunctionSignature>,
+    
+    /// Types of resolved variables and parameters by symbol, so shadowed
+    /// names keep their own types; empty until names are resolved
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "crate::hir::serialize::sorted_map")]
+    pub symbols: HashMap<SymbolId, Type>,
+    
+    /// Instances of generic functions by the name monomorphization gave
+    /// them; empty until the program is monomorphized
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "crate::hir::serialize::sorted_map")]
+    pub instances: HashMap<String, Instance>,
+    
+    /// Nested functions lifted to the top level, by their lifted name;
+    /// empty until closures are converted
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", serialize_with = "crate::hir::serialize::sorted_map")]
+    pub closures: HashMap<String, Closure>,
+}
+
+/// Parameter list and return type of a declared function
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FunctionSignature {
+    /// Parameters, including their permissions
+    pub parameters: Vec<HirParameter>,
+    
+    /// Return type (if specified)
+    pub return_type: Option<Type>,
+}
+
+/// A generic function instantiated for one list of type arguments
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Instance {
+    /// The generic function this is an instance of
+    pub generic: String,
+    
+    /// Concrete types of the generic parameters, in declaration order
+    pub type_arguments: Vec<Type>,
+}
+
+impl Instance {
+    /// Name of the instantiated function, e.g. `pair$Int$Bool`. `$` never
+    /// appears in an identifier, so it cannot clash with a declared function.
+    pub fn symbol(&self) -> String {
+        let mut symbol = self.generic.clone();
+        for typ in &self.type_arguments {
+            symbol.push_str(&format!("${:?}", typ));
+        }
+        symbol
+    }
+}
+
+impl std::fmt::Display for Instance {
+    /// The instance as written in diagnostics, e.g. `pair<Int, Bool>`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arguments: Vec<String> = self.type_arguments.iter()
+            .map(|typ| format!("{:?}", typ))
+            .collect();
+        write!(f, "{}<{}>", self.generic, arguments.join(", "))
+    }
+}
+
+/// A nested function lifted to the top level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Closure {
+    /// The name the function was declared with
+    pub name: String,
+    
+    /// The function it was declared in, by its top-level name
+    pub enclosing: String,
+    
+    /// Variables of enclosing functions it uses, passed as its environment
+    /// after the declared parameters
+    pub captures: Vec<Capture>,
 }
 
-/// Source location information
-#[derive(Debug, Clone, Copy)]
+/// A variable a lifted function takes from its enclosing function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Capture {
+    pub name: String,
+    pub typ: Type,
+    
+    /// Permissions the variable was declared with, which the environment
+    /// parameter keeps
+    pub permissions: Vec<Permission>,
+    
+    /// The variable's symbol, which the environment parameter declares in
+    /// the lifted function
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<SymbolId>,
+}
+
+/// Source location information. Every HIR statement and expression has
+/// one; nodes the compiler creates with no source of their own carry the
+/// default, unknown location.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SourceLocation {
     pub file_id: usize,
     pub start: TextPosition,
@@ -50,15 +149,59 @@ pub struct SourceLocation {
 }
 
 /// Position in a source file
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TextPosition {
     pub line: usize,
     pub column: usize,
     pub offset: usize,
 }
 
+impl SourceLocation {
+    /// Whether this points into a source file. Lines start at 1, so the
+    /// default location is unknown.
+    pub fn is_known(&self) -> bool {
+        self.start.line > 0
+    }
+    
+    /// This location if it is known
+    pub fn known(&self) -> Option<&SourceLocation> {
+        Some(self).filter(|loc| loc.is_known())
+    }
+    
+    /// The front end span covering this location, in its file if the
+    /// location is known
+    pub fn to_span(&self) -> Span {
+        let mut span = Span::new(self.start.line, self.start.column, self.end.line, self.end.column);
+        span.file_id = self.known().map(|location| location.file_id);
+        span
+    }
+}
+
+impl From<&Span> for SourceLocation {
+    fn from(span: &Span) -> Self {
+        Self {
+            file_id: span.file_id.unwrap_or(0),
+            start: TextPosition { line: span.start_line, column: span.start_column, offset: 0 },
+            end: TextPosition { line: span.end_line, column: span.end_column, offset: 0 },
+        }
+    }
+}
+
+/// Identifies one declared variable or parameter. Name resolution assigns
+/// a fresh ID to every declaration and stamps it on each use, so two
+/// variables sharing a name never collide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SymbolId(pub usize);
+
+impl std::fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
 /// A statement in the HIR
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HirStatement {
     /// Variable declaration
     Declaration(HirVariable),
@@ -70,33 +213,45 @@ pub enum HirStatement {
     Function(HirFunction),
     
     /// Return statement
-    Return(Option<HirExpression>),
+    Return(Option<HirExpression>, SourceLocation),
     
     /// Print statement
-    Print(HirExpression),
+    Print(HirExpression, SourceLocation),
     
-    /// Expression statement
+    /// Expression statement, located where its expression is
     Expression(HirExpression),
     
     /// Block of statements
-    Block(Vec<HirStatement>),
+    Block(Vec<HirStatement>, SourceLocation),
+    
+    /// `atomic { ... }`: a block whose statements run as one unit, with no
+    /// other behavior observing the state in between
+    Atomic(Vec<HirStatement>, SourceLocation),
     
     /// If statement
     If {
         condition: HirExpression,
         then_branch: Box<HirStatement>,
         else_branch: Option<Box<HirStatement>>,
+        span: SourceLocation,
     },
     
     /// While loop
     While {
         condition: HirExpression,
         body: Box<HirStatement>,
+        span: SourceLocation,
     },
+    
+    /// `break`: leave the innermost loop
+    Break(SourceLocation),
+    
+    /// `continue`: go back to the condition of the innermost loop
+    Continue(SourceLocation),
 }
 
 /// A variable declaration in HIR
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HirVariable {
     /// Variable name
     pub name: String,
@@ -110,26 +265,51 @@ pub struct HirVariable {
     /// Initial value (if any)
     pub initializer: Option<HirExpression>,
     
-    /// Source location
-    pub location: Option<SourceLocation>,
+    /// Source location of the whole declaration
+    pub span: SourceLocation,
+    
+    /// The actor this is a state field of, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    
+    /// The symbol this declares, once names are resolved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<SymbolId>,
 }
 
 /// An assignment in HIR
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HirAssignment {
     /// Target variable name
     pub target: String,
     
     /// Value being assigned
     pub value: HirExpression,
+    
+    /// Operator of a compound assignment such as `+=`, until desugaring
+    /// rewrites it to a plain assignment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<TokenType>,
+    
+    /// The symbol the target resolves to, once names are resolved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<SymbolId>,
+    
+    /// Source location of the whole assignment
+    pub span: SourceLocation,
 }
 
 /// A function declaration in HIR
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HirFunction {
     /// Function name
     pub name: String,
     
+    /// Generic type parameters; empty for ordinary functions and for the
+    /// instances monomorphization creates
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_params: Vec<String>,
+    
     /// Function parameters
     pub parameters: Vec<HirParameter>,
     
@@ -138,10 +318,22 @@ pub struct HirFunction {
     
     /// Return type (if specified)
     pub return_type: Option<Type>,
+    
+    /// Whether this is an `on` behavior, which runs asynchronously and can
+    /// only be sent values that are safe to share
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_behavior: bool,
+    
+    /// The actor this is a method or behavior of, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    
+    /// Source location of the whole declaration, body included
+    pub span: SourceLocation,
 }
 
 /// A function parameter in HIR
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HirParameter {
     /// Parameter name
     pub name: String,
@@ -151,16 +343,21 @@ pub struct HirParameter {
     
     /// Parameter permissions
     pub permissions: Vec<Permission>,
+    
+    /// The symbol this declares, once names are resolved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<SymbolId>,
 }
 
 /// An expression in HIR
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HirExpression {
     /// Literal value
-    Integer(i64, Option<SourceLocation>),
+    Integer(i64, SourceLocation),
     
-    /// Variable reference
-    Variable(String, Type, Option<SourceLocation>),
+    /// Variable reference, with the symbol it resolves to once names are
+    /// resolved
+    Variable(String, Type, SourceLocation, Option<SymbolId>),
     
     /// Binary operation
     Binary {
@@ -168,6 +365,7 @@ pub enum HirExpression {
         operator: TokenType,
         right: Box<HirExpression>,
         result_type: Type,
+        span: SourceLocation,
     },
     
     /// Function call
@@ -175,19 +373,23 @@ pub enum HirExpression {
         function: String,
         arguments: Vec<HirExpression>,
         result_type: Type,
+        span: SourceLocation,
     },
     
     /// Peak operation (safely borrow a value)
-    Peak(Box<HirExpression>),
+    Peak(Box<HirExpression>, SourceLocation),
     
     /// Clone operation (make a copy of a value)
-    Clone(Box<HirExpression>),
+    Clone(Box<HirExpression>, SourceLocation),
+    
+    /// Consume operation (move a value out, invalidating the source)
+    Consume(Box<HirExpression>, SourceLocation),
     
     /// Boolean literal
-    Boolean(bool),
+    Boolean(bool, SourceLocation),
     
     /// String literal
-    String(String),
+    String(String, SourceLocation),
     
     /// Conditional expression (ternary)
     Conditional {
@@ -195,12 +397,14 @@ pub enum HirExpression {
         then_expr: Box<HirExpression>,
         else_expr: Box<HirExpression>,
         result_type: Type,
+        span: SourceLocation,
     },
     
     /// Type cast
     Cast {
         expr: Box<HirExpression>,
         target_type: Type,
+        span: SourceLocation,
     },
 }
 
@@ -223,3 +427,104 @@ pub enum HirExpression {
 
 
 
+
+impl HirExpression {
+    /// The static type of this expression as recorded during conversion
+    pub fn get_type(&self) -> Type {
+        match self {
+            HirExpression::Integer(_, _) => Type::Int,
+            HirExpression::Variable(_, typ, _, _) => typ.clone(),
+            HirExpression::Binary { result_type, .. } => result_type.clone(),
+            HirExpression::Call { result_type, .. } => result_type.clone(),
+            HirExpression::Peak(inner, _) => inner.get_type(),
+            HirExpression::Clone(inner, _) => inner.get_type(),
+            HirExpression::Consume(inner, _) => inner.get_type(),
+            HirExpression::Boolean(_, _) => Type::Bool,
+            HirExpression::String(_, _) => Type::String,
+            HirExpression::Conditional { result_type, .. } => result_type.clone(),
+            HirExpression::Cast { target_type, .. } => target_type.clone(),
+        }
+    }
+    
+    /// Where this expression was written
+    pub fn span(&self) -> &SourceLocation {
+        match self {
+            HirExpression::Integer(_, span) |
+            HirExpression::Variable(_, _, span, _) |
+            HirExpression::Peak(_, span) |
+            HirExpression::Clone(_, span) |
+            HirExpression::Consume(_, span) |
+            HirExpression::Boolean(_, span) |
+            HirExpression::String(_, span) |
+            HirExpression::Binary { span, .. } |
+            HirExpression::Call { span, .. } |
+            HirExpression::Conditional { span, .. } |
+            HirExpression::Cast { span, .. } => span,
+        }
+    }
+    
+    /// The location of this expression, or for one the compiler built
+    /// without a source location, the first known location inside it
+    pub fn location(&self) -> Option<&SourceLocation> {
+        if let Some(span) = self.span().known() {
+            return Some(span);
+        }
+        match self {
+            HirExpression::Binary { left, right, .. } => left.location().or_else(|| right.location()),
+            HirExpression::Call { arguments, .. } => arguments.iter().find_map(|arg| arg.location()),
+            HirExpression::Peak(inner, _) |
+            HirExpression::Clone(inner, _) |
+            HirExpression::Consume(inner, _) |
+            HirExpression::Cast { expr: inner, .. } => inner.location(),
+            HirExpression::Conditional { condition, then_expr, else_expr, .. } => condition.location()
+                .or_else(|| then_expr.location())
+                .or_else(|| else_expr.location()),
+            HirExpression::Integer(..) |
+            HirExpression::Variable(..) |
+            HirExpression::Boolean(..) |
+            HirExpression::String(..) => None,
+        }
+    }
+}
+
+impl HirStatement {
+    /// Where this statement was written
+    pub fn span(&self) -> &SourceLocation {
+        match self {
+            HirStatement::Declaration(var) => &var.span,
+            HirStatement::Assignment(assign) => &assign.span,
+            HirStatement::Function(func) => &func.span,
+            HirStatement::Expression(expr) => expr.span(),
+            HirStatement::Return(_, span) |
+            HirStatement::Print(_, span) |
+            HirStatement::Block(_, span) |
+            HirStatement::Atomic(_, span) |
+            HirStatement::If { span, .. } |
+            HirStatement::While { span, .. } |
+            HirStatement::Break(span) |
+            HirStatement::Continue(span) => span,
+        }
+    }
+    
+    /// The location of this statement, or for one the compiler built
+    /// without a source location, the first known location inside it
+    pub fn location(&self) -> Option<&SourceLocation> {
+        if let Some(span) = self.span().known() {
+            return Some(span);
+        }
+        match self {
+            HirStatement::Declaration(var) => var.initializer.as_ref().and_then(|init| init.location()),
+            HirStatement::Assignment(assign) => assign.value.location(),
+            HirStatement::Function(func) => func.body.iter().find_map(|stmt| stmt.location()),
+            HirStatement::Return(expr, _) => expr.as_ref().and_then(|e| e.location()),
+            HirStatement::Print(expr, _) |
+            HirStatement::Expression(expr) => expr.location(),
+            HirStatement::Block(statements, _) |
+            HirStatement::Atomic(statements, _) => statements.iter().find_map(|stmt| stmt.location()),
+            HirStatement::If { condition, .. } |
+            HirStatement::While { condition, .. } => condition.location(),
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => None,
+        }
+    }
+}
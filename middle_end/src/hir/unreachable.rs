@@ -0,0 +1,125 @@
+//! Unreachable code detection
+//!
+//! Flags statements in a function body that follow a statement control
+//! never continues past: an unconditional `return`, `break` or `continue`,
+//! a block or `if`/`else` whose every path leaves, or a `while true` loop
+//! with no `break` out of it. Only the first unreachable statement of each
+//! sequence is reported; the HIR itself is left untouched.
+
+use crate::hir::diagnostics::Diagnostic;
+use crate::hir::passes::{AnalysisContext, AnalysisPass};
+use crate::hir::types::*;
+use front_end::codes::semantic;
+
+/// Why control never reaches the statements after a given one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Every path returns from the function, or loops forever
+    Return,
+
+    /// The statement loops forever
+    InfiniteLoop,
+
+    /// Every path leaves or restarts the enclosing loop
+    LoopJump,
+}
+
+/// How control leaves a statement, if it never falls through to the next one
+pub fn exit_kind(stmt: &HirStatement) -> Option<Exit> {
+    match stmt {
+        HirStatement::Return(_, _) => Some(Exit::Return),
+        HirStatement::Break(_) |
+        HirStatement::Continue(_) => Some(Exit::LoopJump),
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => statements.iter().find_map(exit_kind),
+        HirStatement::If { then_branch, else_branch: Some(else_branch), .. } => {
+            match (exit_kind(then_branch)?, exit_kind(else_branch)?) {
+                (then_exit, else_exit) if then_exit == else_exit => Some(then_exit),
+                (Exit::Return, _) | (_, Exit::Return) => Some(Exit::Return),
+                _ => Some(Exit::LoopJump),
+            }
+        },
+        HirStatement::While { condition: HirExpression::Boolean(true, _), body, .. } if !breaks_out(body) => {
+            Some(Exit::InfiniteLoop)
+        },
+        _ => None,
+    }
+}
+
+/// Whether a loop body contains a `break` out of that loop, rather than
+/// out of a loop nested in it
+fn breaks_out(stmt: &HirStatement) -> bool {
+    match stmt {
+        HirStatement::Break(_) => true,
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => statements.iter().any(breaks_out),
+        HirStatement::If { then_branch, else_branch, .. } => {
+            breaks_out(then_branch) || else_branch.as_deref().is_some_and(breaks_out)
+        },
+        _ => false,
+    }
+}
+
+/// Reports statements that can never execute
+pub struct UnreachableCodePass;
+
+impl AnalysisPass for UnreachableCodePass {
+    fn name(&self) -> &'static str {
+        "unreachable"
+    }
+
+    fn enter_function(&mut self, func: &HirFunction, ctx: &mut AnalysisContext) {
+        check_sequence(&func.name, &func.body, ctx);
+    }
+}
+
+/// Check a statement sequence and the sequences nested inside it
+fn check_sequence(function: &str, statements: &[HirStatement], ctx: &mut AnalysisContext) {
+    for (index, stmt) in statements.iter().enumerate() {
+        check_nested(function, stmt, ctx);
+
+        let Some(exit) = exit_kind(stmt) else {
+            continue;
+        };
+        if let Some(dead) = statements.get(index + 1) {
+            ctx.report(unreachable_diagnostic(function, exit, stmt, dead));
+        }
+        break;
+    }
+}
+
+/// Descend into the statement lists of blocks and branches.
+/// Nested functions are checked when the pass manager enters them.
+fn check_nested(function: &str, stmt: &HirStatement, ctx: &mut AnalysisContext) {
+    match stmt {
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => check_sequence(function, statements, ctx),
+        HirStatement::If { then_branch, else_branch, .. } => {
+            check_nested(function, then_branch, ctx);
+            if let Some(else_branch) = else_branch {
+                check_nested(function, else_branch, ctx);
+            }
+        },
+        HirStatement::While { body, .. } => check_nested(function, body, ctx),
+        _ => {},
+    }
+}
+
+fn unreachable_diagnostic(function: &str, exit: Exit, exit_stmt: &HirStatement, dead: &HirStatement) -> Diagnostic {
+    let cause = match exit {
+        Exit::Return => "control returns from the function here",
+        Exit::InfiniteLoop => "this loop never exits",
+        Exit::LoopJump => "control moves to the enclosing loop here",
+    };
+
+    let mut diagnostic = Diagnostic::warning(format!("Unreachable code in function '{}'", function))
+        .with_code(semantic::UNREACHABLE_CODE);
+    if let Some(location) = dead.location() {
+        diagnostic = diagnostic.with_primary(location.to_span(), "unreachable statement");
+    }
+    diagnostic = match exit_stmt.location() {
+        Some(location) => diagnostic.with_secondary(location.to_span(), cause),
+        None => diagnostic.with_note(cause),
+    };
+    diagnostic.with_help("remove the unreachable statements")
+}
@@ -2,8 +2,13 @@
 //!
 //! This module provides functions to validate the HIR for correctness.
 
+use crate::hir::diagnostics::{Diagnostic, DiagnosticRenderer, ToDiagnostic};
 use crate::hir::types::*;
-use std::collections::HashSet;
+use crate::hir::visitor::{self, HirVisitor};
+use front_end::codes::{permission, semantic};
+use front_end::token::TokenType;
+use front_end::types::{NumericKind, Permission, Type};
+use std::collections::{HashMap, HashSet};
 
 /// Error type for HIR validation
 #[derive(Debug, Clone)]
@@ -14,6 +19,8 @@ pub enum ValidationError {
         name: String,
         /// Usage context
         context: String,
+        /// Source location of the use
+        location: Option<SourceLocation>,
     },
     
     /// Type mismatch
@@ -24,14 +31,50 @@ pub enum ValidationError {
         actual: front_end::types::Type,
         /// Context for the mismatch
         context: String,
-        /// Source location of the error
-        location: Option<crate::hir::scope::SourceLocation>,
+        /// Source location of the offending expression
+        location: Option<SourceLocation>,
+    },
+    
+    /// Operator applied to operands it is not defined for
+    InvalidOperands {
+        /// The binary operator
+        operator: TokenType,
+        /// Type of the left operand
+        left: Type,
+        /// Type of the right operand
+        right: Type,
+        /// Context for the error
+        context: String,
+        /// Source location of the binary expression
+        location: Option<SourceLocation>,
+    },
+    
+    /// Call with the wrong number of arguments
+    ArityMismatch {
+        /// Called function
+        function: String,
+        /// Number of declared parameters
+        expected: usize,
+        /// Number of arguments passed
+        found: usize,
+        /// Source location of the arguments
+        location: Option<SourceLocation>,
+    },
+    
+    /// `break` or `continue` outside any loop of its function
+    MisplacedLoopControl {
+        /// `break` or `continue`
+        keyword: &'static str,
+        /// Where the statement was written
+        location: SourceLocation,
     },
     
     /// Permission error
     PermissionError {
         /// Error message
         message: String,
+        /// Source location of the offending argument
+        location: Option<SourceLocation>,
     },
     
     /// Other errors
@@ -39,108 +82,97 @@ pub enum ValidationError {
 }
 
 impl ValidationError {
-    /// Format a validation error for display
+    /// Format a validation error for display, quoting the source if given
     pub fn format(&self, source_code: Option<&str>) -> String {
-        match self {
+        let renderer = match source_code {
+            Some(source) => DiagnosticRenderer::with_source(source),
+            None => DiagnosticRenderer::new(),
+        };
+        renderer.render(&self.to_diagnostic())
+    }
+}
+
+impl From<&ValidationError> for Diagnostic {
+    fn from(error: &ValidationError) -> Self {
+        let diagnostic = match error {
+            ValidationError::UndefinedVariable { name, context, .. } => {
+                Diagnostic::error(format!("Undefined variable '{}' in {}", name, context))
+                    .with_code(permission::UNDEFINED_VARIABLE)
+            },
             ValidationError::TypeMismatch { expected, actual, context, location } => {
-                // Use {:?} to format Type values since they don't implement Display
-                let mut result = format!("Type mismatch error: expected {:?}, found {:?}\n", expected, actual);
-                result.push_str(&format!("In {}\n", context));
-                
-                // Add source code context if available
-                if let (Some(loc), Some(source)) = (location, source_code) {
-                    result.push_str(&format!(" --> {}:{}:{}\n", loc.file, loc.line, loc.column));
-                    
-                    // Extract the line with the error
-                    let lines: Vec<&str> = source.lines().collect();
-                    if loc.line > 0 && loc.line <= lines.len() {
-                        let line_content = lines[loc.line - 1].trim_start();
-                        result.push_str(&format!("   |\n{} | {}\n", loc.line, line_content));
-                        
-                        // Find the token length
-                        let token_len = if loc.column <= line_content.len() {
-                            // Try to find a word boundary
-                            let remainder = &line_content[loc.column.saturating_sub(1)..];
-                            let end = remainder.find(|c: char| !c.is_alphanumeric() && c != '_')
-                                .unwrap_or(remainder.len());
-                            end.max(1) // At least one character
-                        } else {
-                            1 // Default if column is out of range
-                        };
-                        
-                        // Add the tilde marks underlining the error
-                        result.push_str(&format!("   | {}{}\n", 
-                            " ".repeat(loc.column.saturating_sub(1)), 
-                            "~".repeat(token_len)
-                        ));
-                    }
-                } else {
-                    // If we don't have location but do have source, try to find the problematic text
-                    if let Some(source) = source_code {
-                        // Simple heuristic: find the line containing the relevant variable
-                        let var_name = if context.contains("assignment to variable") {
-                            // Extract variable name from context message
-                            context.split('\'').nth(1).unwrap_or("")
-                        } else if context.contains("initialization of variable") {
-                            // Extract variable name from context message
-                            context.split('\'').nth(1).unwrap_or("")
-                        } else {
-                            ""
-                        };
-                        
-                        if !var_name.is_empty() {
-                            // Find the line containing the variable
-                            for (i, line) in source.lines().enumerate() {
-                                if line.contains(var_name) {
-                                    let line_content = line.trim_start();
-                                    let line_num = i + 1;
-                                    let col = line.find(var_name).unwrap_or(1) + 1;
-                                    
-                                    // Add formatted line with error marker
-                                    result.push_str(&format!(" --> input:{}:{}\n", line_num, col));
-                                    result.push_str(&format!("   |\n{} | {}\n", line_num, line_content));
-                                    result.push_str(&format!("   | {}{}\n", 
-                                        " ".repeat(col.saturating_sub(1)), 
-                                        "~".repeat(var_name.len())
-                                    ));
-                                    break;
-                                }
-                            }
-                        }
-                    }
+                let mut diagnostic = Diagnostic::error(format!("Type mismatch: expected {:?}, found {:?}", expected, actual))
+                    .with_code(semantic::TYPE_MISMATCH)
+                    .with_note(format!("in {}", context));
+                if let Some(location) = location {
+                    diagnostic = diagnostic.with_primary(location.to_span(), format!("this is {:?}", actual));
                 }
                 
-                // Add a helpful suggestion
-                result.push_str("\nSuggestion: ");
-                match (expected, actual) {
-                    (front_end::types::Type::Int, front_end::types::Type::Bool) => {
-                        result.push_str("Convert the boolean to an integer with a cast, e.g., 'Int(bool_val)' or use a different variable of integer type.");
+                let help = match (expected, actual) {
+                    (Type::Bool, actual) if actual.is_numeric() => {
+                        "Convert the number to a boolean with a comparison, e.g., 'int_val != 0' or use a different variable of boolean type.".to_string()
                     },
-                    (front_end::types::Type::Bool, front_end::types::Type::Int) => {
-                        result.push_str("Convert the integer to a boolean with a comparison, e.g., 'int_val != 0' or use a different variable of boolean type.");
+                    (expected, actual) if expected.is_numeric() && actual.is_numeric() => {
+                        numeric_conversion_help(expected, actual)
                     },
-                    (front_end::types::Type::Float, front_end::types::Type::Int) => {
-                        result.push_str("Convert the integer to a float with a cast, e.g., 'Float(int_val)'.");
-                    },
-                    (front_end::types::Type::Int, front_end::types::Type::Float) => {
-                        result.push_str("Convert the float to an integer with a cast, e.g., 'Int(float_val)'.");
-                    },
-                    _ => {
-                        // Also use {:?} here for Type formatting
-                        result.push_str(&format!("Make sure the types match. You cannot assign a value of type '{:?}' to a variable of type '{:?}'.", actual, expected));
-                    }
+                    _ => format!("Make sure the types match. You cannot assign a value of type '{:?}' to a variable of type '{:?}'.", actual, expected),
+                };
+                diagnostic.with_help(help)
+            },
+            ValidationError::InvalidOperands { operator, left, right, context, .. } => {
+                let diagnostic = Diagnostic::error(format!("Type error: cannot {} {:?} and {:?}", operator.operation_verb(), left, right))
+                    .with_code(semantic::INVALID_OPERANDS)
+                    .with_note(format!("in {}", context));
+                if left != right {
+                    diagnostic.with_help(format!("Both operands of '{}' must have the same type.", operator.operator_symbol()))
+                } else {
+                    diagnostic.with_help(format!("'{}' is only defined for numeric types.", operator.operator_symbol()))
+                }
+            },
+            ValidationError::ArityMismatch { function, expected, found, .. } => {
+                let diagnostic = Diagnostic::error(format!(
+                    "Call error: function '{}' takes {} argument{} but {} {} supplied",
+                    function, expected, if *expected == 1 { "" } else { "s" },
+                    found, if *found == 1 { "was" } else { "were" }
+                )).with_code(permission::ARITY_MISMATCH);
+                if found > expected {
+                    diagnostic.with_help(format!("Remove the extra argument{}.", if found - expected == 1 { "" } else { "s" }))
+                } else {
+                    diagnostic.with_help(format!("Supply the missing argument{}.", if expected - found == 1 { "" } else { "s" }))
                 }
-                
-                result
             },
-            // Handle other validation error types...
-            _ => String::new(),
+            ValidationError::MisplacedLoopControl { keyword, location } => {
+                let mut diagnostic = Diagnostic::error(format!("'{}' outside of a loop", keyword))
+                    .with_code(semantic::MISPLACED_LOOP_CONTROL);
+                if let Some(location) = location.known() {
+                    diagnostic = diagnostic.with_primary(location.to_span(), format!("cannot '{}' here", keyword));
+                }
+                diagnostic.with_help(format!("'{}' can only be used inside a 'while' loop of the same function", keyword))
+            },
+            ValidationError::PermissionError { message, .. } => {
+                Diagnostic::error(format!("Permission error: {}", message))
+                    .with_code(permission::ARGUMENT_PERMISSION)
+            },
+            ValidationError::Other(message) => Diagnostic::error(message.clone()),
+        };
+        
+        // Type mismatches and misplaced loop control label their location
+        // above; the other errors that have one are pointed at here
+        let location = match error {
+            ValidationError::UndefinedVariable { location, .. } |
+            ValidationError::InvalidOperands { location, .. } |
+            ValidationError::ArityMismatch { location, .. } |
+            ValidationError::PermissionError { location, .. } => location.as_ref().and_then(SourceLocation::known),
+            _ => None,
+        };
+        match location {
+            Some(location) => diagnostic.with_primary(location.to_span(), ""),
+            None => diagnostic,
         }
     }
 }
 
 /// Validate an HIR program
-pub fn validate_hir_with_source(program: &HirProgram, source: &str) -> Result<(), Vec<ValidationError>> {
+pub fn validate_hir(program: &HirProgram) -> Result<(), Vec<ValidationError>> {
     let mut errors = Vec::new();
     
     // Run variable declaration check
@@ -149,10 +181,20 @@ pub fn validate_hir_with_source(program: &HirProgram, source: &str) -> Result<()
     }
     
     // Run type compatibility check
-    if let Err(type_errors) = check_type_compatibility_with_source(program, source) {
+    if let Err(type_errors) = check_type_compatibility(program) {
         errors.extend(type_errors);
     }
     
+    // Run call site check
+    if let Err(call_errors) = check_call_signatures(program) {
+        errors.extend(call_errors);
+    }
+    
+    // Run loop control check
+    if let Err(loop_errors) = check_loop_control(program) {
+        errors.extend(loop_errors);
+    }
+    
     // Return all errors or success
     if errors.is_empty() {
         Ok(())
@@ -190,6 +232,7 @@ pub fn check_undeclared_variables(program: &HirProgram) -> Result<(), Vec<Valida
                     errors.push(ValidationError::UndefinedVariable {
                         name: assign.target.clone(),
                         context: "assignment target".to_string(),
+                        location: Some(assign.span),
                     });
                 }
                 
@@ -198,12 +241,10 @@ pub fn check_undeclared_variables(program: &HirProgram) -> Result<(), Vec<Valida
             HirStatement::Expression(expr) => {
                 check_expr_for_undeclared(expr, &declared_vars, &mut errors);
             },
-            HirStatement::Return(expr_opt) => {
-                if let Some(expr) = expr_opt {
-                    check_expr_for_undeclared(expr, &declared_vars, &mut errors);
-                }
+            HirStatement::Return(Some(expr), _) => {
+                check_expr_for_undeclared(expr, &declared_vars, &mut errors);
             },
-            HirStatement::Print(expr) => {
+            HirStatement::Print(expr, _) => {
                 check_expr_for_undeclared(expr, &declared_vars, &mut errors);
             },
             _ => {}
@@ -217,13 +258,214 @@ pub fn check_undeclared_variables(program: &HirProgram) -> Result<(), Vec<Valida
     }
 }
 
+/// Check that every `break` and `continue` is inside a loop. A function
+/// declared in a loop body does not see that loop.
+pub fn check_loop_control(program: &HirProgram) -> Result<(), Vec<ValidationError>> {
+    let mut checker = LoopControlChecker { depth: 0, errors: Vec::new() };
+    checker.visit_program(program);
+    
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+/// Tracks how many loops enclose the current statement
+struct LoopControlChecker {
+    depth: usize,
+    errors: Vec<ValidationError>,
+}
+
+impl HirVisitor for LoopControlChecker {
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        match stmt {
+            HirStatement::While { .. } => {
+                self.depth += 1;
+                visitor::walk_statement(self, stmt);
+                self.depth -= 1;
+            },
+            _ => {
+                self.errors.extend(loop_control_error(stmt, self.depth > 0));
+                visitor::walk_statement(self, stmt);
+            },
+        }
+    }
+    
+    fn visit_function(&mut self, func: &HirFunction) {
+        let depth = std::mem::replace(&mut self.depth, 0);
+        visitor::walk_function(self, func);
+        self.depth = depth;
+    }
+}
+
+/// The error for a `break` or `continue` that is not inside a loop
+pub(crate) fn loop_control_error(stmt: &HirStatement, in_loop: bool) -> Option<ValidationError> {
+    let (keyword, location) = match stmt {
+        HirStatement::Break(location) => ("break", location),
+        HirStatement::Continue(location) => ("continue", location),
+        _ => return None,
+    };
+    (!in_loop).then_some(ValidationError::MisplacedLoopControl { keyword, location: *location })
+}
+
+/// Check every call against the callee's signature: argument count,
+/// argument types and the permissions required by each parameter
+pub fn check_call_signatures(program: &HirProgram) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    let mut permissions = HashMap::new();
+    
+    for stmt in &program.statements {
+        check_statement_calls(stmt, program, &mut permissions, &mut errors);
+    }
+    
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Walk a statement, recording variable permissions as they are declared
+fn check_statement_calls(
+    stmt: &HirStatement,
+    program: &HirProgram,
+    permissions: &mut HashMap<String, Vec<Permission>>,
+    errors: &mut Vec<ValidationError>
+) {
+    match stmt {
+        HirStatement::Declaration(var) => {
+            if let Some(init) = &var.initializer {
+                check_expr_calls(init, program, permissions, errors);
+            }
+            permissions.insert(var.name.clone(), var.permissions.clone());
+        },
+        HirStatement::Assignment(assign) => {
+            check_expr_calls(&assign.value, program, permissions, errors);
+        },
+        HirStatement::Function(func) => {
+            for param in &func.parameters {
+                permissions.insert(param.name.clone(), param.permissions.clone());
+            }
+            for stmt in &func.body {
+                check_statement_calls(stmt, program, permissions, errors);
+            }
+        },
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => {
+            for stmt in statements {
+                check_statement_calls(stmt, program, permissions, errors);
+            }
+        },
+        HirStatement::Return(Some(expr), _) |
+        HirStatement::Expression(expr) |
+        HirStatement::Print(expr, _) => {
+            check_expr_calls(expr, program, permissions, errors);
+        },
+        _ => {},
+    }
+}
+
+/// Check the calls inside an expression
+fn check_expr_calls(
+    expr: &HirExpression,
+    program: &HirProgram,
+    permissions: &HashMap<String, Vec<Permission>>,
+    errors: &mut Vec<ValidationError>
+) {
+    match expr {
+        HirExpression::Call { function, arguments, .. } => {
+            for arg in arguments {
+                check_expr_calls(arg, program, permissions, errors);
+            }
+            
+            let can_write = |name: &str| permissions.get(name).is_some_and(|perms| {
+                perms.iter().any(|p| matches!(p, Permission::Write | Permission::Writes))
+            });
+            errors.extend(call_site_errors(function, arguments, program, &can_write));
+        },
+        HirExpression::Binary { left, right, .. } => {
+            check_expr_calls(left, program, permissions, errors);
+            check_expr_calls(right, program, permissions, errors);
+        },
+        HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
+            check_expr_calls(condition, program, permissions, errors);
+            check_expr_calls(then_expr, program, permissions, errors);
+            check_expr_calls(else_expr, program, permissions, errors);
+        },
+        HirExpression::Cast { expr, .. } |
+        HirExpression::Peak(expr, _) |
+        HirExpression::Clone(expr, _) |
+        HirExpression::Consume(expr, _) => check_expr_calls(expr, program, permissions, errors),
+        HirExpression::Integer(_, _) |
+        HirExpression::Variable(_, _, _, _) |
+        HirExpression::Boolean(_, _) |
+        HirExpression::String(_, _) => {},
+    }
+}
+
+/// Check one call against the callee's signature. `can_write` tells whether
+/// a variable passed as an argument may be written by the caller.
+/// Unknown callees are left to name resolution.
+pub(crate) fn call_site_errors(
+    function: &str,
+    arguments: &[HirExpression],
+    program: &HirProgram,
+    can_write: &dyn Fn(&str) -> bool
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let Some(signature) = program.type_info.functions.get(function) else {
+        return errors;
+    };
+    
+    if arguments.len() != signature.parameters.len() {
+        errors.push(ValidationError::ArityMismatch {
+            function: function.to_string(),
+            expected: signature.parameters.len(),
+            found: arguments.len(),
+            location: arguments.iter().find_map(|arg| arg.location()).copied(),
+        });
+        return errors;
+    }
+    
+    for (index, (arg, param)) in arguments.iter().zip(&signature.parameters).enumerate() {
+        // A generic parameter accepts any type; monomorphization picks the instance
+        let arg_type = infer_expr_type(arg, program);
+        if !param.typ.is_param() && !converts_implicitly(arg, &arg_type, &param.typ) {
+            errors.push(ValidationError::TypeMismatch {
+                expected: param.typ.clone(),
+                actual: arg_type,
+                context: format!("argument {} ('{}') of call to '{}'", index + 1, param.name, function),
+                location: arg.location().copied(),
+            });
+        }
+        
+        // A parameter that writes needs an argument the caller may write
+        let needs_write = param.permissions.iter()
+            .any(|p| matches!(p, Permission::Write | Permission::Writes));
+        if let HirExpression::Variable(name, _, _, _) = arg {
+            if needs_write && !can_write(name) {
+                errors.push(ValidationError::PermissionError {
+                    message: format!(
+                        "argument '{}' passed to parameter '{}' of '{}' requires write permission",
+                        name, param.name, function
+                    ),
+                    location: arg.location().copied(),
+                });
+            }
+        }
+    }
+    
+    errors
+}
+
 /// Check type compatibility in all expressions
-fn check_type_compatibility_with_source(program: &HirProgram, source: &str) -> Result<(), Vec<ValidationError>> {
+fn check_type_compatibility(program: &HirProgram) -> Result<(), Vec<ValidationError>> {
     let mut errors = Vec::new();
     
     // Check each statement for type compatibility
     for stmt in &program.statements {
-        check_statement_types_with_source(stmt, program, source, &mut errors);
+        check_statement_types(stmt, program, &mut errors);
     }
     
     if errors.is_empty() {
@@ -234,99 +476,174 @@ fn check_type_compatibility_with_source(program: &HirProgram, source: &str) -> R
 }
 
 /// Check type compatibility for a statement
-fn check_statement_types_with_source(stmt: &HirStatement, program: &HirProgram, source: &str, errors: &mut Vec<ValidationError>) {
+fn check_statement_types(stmt: &HirStatement, program: &HirProgram, errors: &mut Vec<ValidationError>) {
     match stmt {
         HirStatement::Declaration(var) => {
             // Check initializer type if present
             if let Some(init) = &var.initializer {
-                let init_type = infer_expr_type(init, program);
-                
-                if init_type != var.typ {
-                    // Try to get source location from expression
-                    let location = match init {
-                        HirExpression::Variable(_, _, loc) => {
-                            loc.as_ref().map(|l| crate::hir::scope::SourceLocation {
-                                line: l.start.line,
-                                column: l.start.column,
-                                file: format!("file_{}", l.file_id),
-                            })
-                        },
-                        _ => None,
-                    };
-                    
-                    errors.push(ValidationError::TypeMismatch {
-                        expected: var.typ.clone(),
-                        actual: init_type,
-                        context: format!("initialization of variable '{}'", var.name),
-                        location,
-                    });
-                }
+                check_expr_operands(init, program, errors);
+                errors.extend(conversion_error(init, &var.typ, format!("initialization of variable '{}'", var.name), program));
             }
         },
         HirStatement::Assignment(assign) => {
             // Get target variable type
+            check_expr_operands(&assign.value, program, errors);
             if let Some(target_type) = program.type_info.variables.get(&assign.target) {
-                let value_type = infer_expr_type(&assign.value, program);
-                
-                if value_type != *target_type {
-                    let location = if let HirExpression::Variable(_, _, loc) = &assign.value {
-                        loc.as_ref().map(|l| crate::hir::scope::SourceLocation {
-                            line: l.start.line,
-                            column: l.start.column,
-                            file: format!("file_{}", l.file_id),
-                        })
-                    } else {
-                        None
-                    };
-                    
-                    errors.push(ValidationError::TypeMismatch {
-                        expected: target_type.clone(),
-                        actual: value_type,
-                        context: format!("assignment to variable '{}'", assign.target),
-                        location,
-                    });
-                }
+                errors.extend(conversion_error(&assign.value, target_type, format!("assignment to variable '{}'", assign.target), program));
             }
         },
-        HirStatement::Return(expr_opt) => {
+        HirStatement::Return(Some(expr), _) => {
             // Find the enclosing function (simplified - in a real compiler we'd track scope)
             // For now, just use the first function we find with a matching return type
-            if let Some(expr) = expr_opt {
-                for stmt in &program.statements {
-                    if let HirStatement::Function(func) = stmt {
-                        if let Some(return_type) = &func.return_type {
-                            let expr_type = infer_expr_type(expr, program);
-                            if expr_type != *return_type {
-                                errors.push(ValidationError::TypeMismatch {
-                                    expected: return_type.clone(),
-                                    actual: expr_type,
-                                    context: format!("return value in function '{}'", func.name),
-                                    location: None,
-                                });
-                            }
-                        }
-                        break;
+            check_expr_operands(expr, program, errors);
+            for stmt in &program.statements {
+                if let HirStatement::Function(func) = stmt {
+                    if let Some(return_type) = &func.return_type {
+                        errors.extend(conversion_error(expr, return_type, format!("return value in function '{}'", func.name), program));
                     }
+                    break;
                 }
             }
         },
         HirStatement::Function(func) => {
             // Check function body
             for stmt in &func.body {
-                check_statement_types_with_source(stmt, program, source, errors);
+                check_statement_types(stmt, program, errors);
             }
         },
-        HirStatement::Block(statements) => {
+        HirStatement::Block(statements, _) |
+        HirStatement::Atomic(statements, _) => {
             // Check each statement in the block
             for stmt in statements {
-                check_statement_types_with_source(stmt, program, source, errors);
+                check_statement_types(stmt, program, errors);
             }
         },
+        HirStatement::Expression(expr) | HirStatement::Print(expr, _) => {
+            check_expr_operands(expr, program, errors);
+        },
         // Other statement types could be added here
         _ => {},
     }
 }
 
+/// Check that the operands of every numeric binary expression share a
+/// common promoted type
+fn check_expr_operands(expr: &HirExpression, program: &HirProgram, errors: &mut Vec<ValidationError>) {
+    match expr {
+        HirExpression::Binary { left, operator, right, span, .. } => {
+            check_expr_operands(left, program, errors);
+            check_expr_operands(right, program, errors);
+            errors.extend(binary_operand_error(left, operator, right, span, program));
+        },
+        HirExpression::Call { arguments, .. } => {
+            for arg in arguments {
+                check_expr_operands(arg, program, errors);
+            }
+        },
+        HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
+            check_expr_operands(condition, program, errors);
+            check_expr_operands(then_expr, program, errors);
+            check_expr_operands(else_expr, program, errors);
+        },
+        HirExpression::Cast { expr, .. } |
+        HirExpression::Peak(expr, _) |
+        HirExpression::Clone(expr, _) |
+        HirExpression::Consume(expr, _) => check_expr_operands(expr, program, errors),
+        HirExpression::Integer(_, _) |
+        HirExpression::Variable(_, _, _, _) |
+        HirExpression::Boolean(_, _) |
+        HirExpression::String(_, _) => {},
+    }
+}
+
+/// Check the operand types of a single binary expression
+pub(crate) fn binary_operand_error(
+    left: &HirExpression,
+    operator: &TokenType,
+    right: &HirExpression,
+    location: &SourceLocation,
+    program: &HirProgram
+) -> Option<ValidationError> {
+    let left_type = infer_expr_type(left, program);
+    let right_type = infer_expr_type(right, program);
+    
+    let is_binary_op = operator.is_arithmetic() || operator.is_ordering() || operator.is_equality();
+    let both_numeric = left_type.is_numeric() && right_type.is_numeric();
+    
+    // Integer literals adopt the type of the other operand
+    let has_literal = matches!(left, HirExpression::Integer(_, _))
+        || matches!(right, HirExpression::Integer(_, _));
+    
+    if is_binary_op && both_numeric {
+        if !has_literal && left_type.promote(&right_type).is_none() {
+            return Some(ValidationError::TypeMismatch {
+                context: format!("operands of binary expression '{:?} {:?} {:?}'", left_type, operator, right_type),
+                expected: left_type,
+                actual: right_type,
+                location: right.location().or_else(|| left.location()).copied(),
+            });
+        }
+    } else if is_binary_op {
+        // Equality works on any matching types, everything else needs numbers
+        let valid = operator.is_equality() && left_type == right_type;
+        if !valid {
+            return Some(ValidationError::InvalidOperands {
+                operator: operator.clone(),
+                left: left_type,
+                right: right_type,
+                context: "binary expression".to_string(),
+                location: Some(*location),
+            });
+        }
+    }
+    
+    None
+}
+
+/// Whether a value of type `from` may be used where `to` is expected,
+/// by the front end's rules: integer literals take any numeric type whose
+/// range holds them, other numbers only widen
+pub(crate) fn converts_implicitly(expr: &HirExpression, from: &Type, to: &Type) -> bool {
+    if let HirExpression::Integer(value, _) = expr {
+        return to.literal_fits(*value) || from == to;
+    }
+    from.can_widen_to(to)
+}
+
+/// The error for `expr` used where a value of type `expected` is, unless
+/// it converts implicitly
+pub(crate) fn conversion_error(expr: &HirExpression, expected: &Type, context: String, program: &HirProgram) -> Option<ValidationError> {
+    let actual = infer_expr_type(expr, program);
+    if expected.is_param() || converts_implicitly(expr, &actual, expected) {
+        return None;
+    }
+    Some(ValidationError::TypeMismatch {
+        expected: expected.clone(),
+        actual,
+        context,
+        location: expr.location().copied(),
+    })
+}
+
+/// Explain why two numeric types don't convert implicitly. There is no
+/// cast syntax, so the fix is declaring the value with the expected type.
+fn numeric_conversion_help(expected: &Type, actual: &Type) -> String {
+    let reason = match (expected.numeric_kind(), actual.numeric_kind()) {
+        (Some(NumericKind::Signed), Some(NumericKind::Unsigned)) |
+        (Some(NumericKind::Unsigned), Some(NumericKind::Signed)) => {
+            "Signed and unsigned integers are never mixed implicitly."
+        },
+        (Some(NumericKind::Float), _) => {
+            "This integer is too wide to convert to that float without losing precision."
+        },
+        (_, Some(NumericKind::Float)) => {
+            "Floats never convert implicitly to integers."
+        },
+        _ => "Implicit conversions may only widen a value, never narrow it.",
+    };
+    format!("{} Declare the value as '{:?}' instead.", reason, expected)
+}
+
 /// Check an expression for undeclared variables
 fn check_expr_for_undeclared(
     expr: &HirExpression, 
@@ -334,11 +651,12 @@ fn check_expr_for_undeclared(
     errors: &mut Vec<ValidationError>
 ) {
     match expr {
-        HirExpression::Variable(name, _, _) => {
+        HirExpression::Variable(name, _, location, _) => {
             if !declared.contains(name) {
                 errors.push(ValidationError::UndefinedVariable {
                     name: name.clone(),
                     context: "variable reference".to_string(),
+                    location: Some(*location),
                 });
             }
         },
@@ -359,16 +677,17 @@ fn check_expr_for_undeclared(
         HirExpression::Cast { expr, .. } => {
             check_expr_for_undeclared(expr, declared, errors);
         },
-        HirExpression::Peak(expr) => {
+        HirExpression::Peak(expr, _) => {
             check_expr_for_undeclared(expr, declared, errors);
         },
-        HirExpression::Clone(expr) => {
+        HirExpression::Clone(expr, _) |
+        HirExpression::Consume(expr, _) => {
             check_expr_for_undeclared(expr, declared, errors);
         },
         // Literals don't contain variables to check
         HirExpression::Integer(_, _) => {},
-        HirExpression::Boolean(_) => {},
-        HirExpression::String(_) => {},
+        HirExpression::Boolean(_, _) => {},
+        HirExpression::String(_, _) => {},
     }
 }
 
@@ -377,7 +696,7 @@ fn infer_expr_type(expr: &HirExpression, program: &HirProgram) -> front_end::typ
     match expr {
         HirExpression::Integer(_, _) => front_end::types::Type::Int,
         
-        HirExpression::Variable(name, typ, _) => {
+        HirExpression::Variable(name, typ, _, _) => {
             // Look up in the type info first, fall back to the annotated type
             program.type_info.variables.get(name).cloned().unwrap_or_else(|| typ.clone())
         },
@@ -386,22 +705,24 @@ fn infer_expr_type(expr: &HirExpression, program: &HirProgram) -> front_end::typ
         
         HirExpression::Call { function, result_type, .. } => {
             // First check if we have the function's return type
-            if let Some(func_type) = program.type_info.functions.get(function) {
-                func_type.clone().unwrap_or_else(|| result_type.clone())
+            if let Some(signature) = program.type_info.functions.get(function) {
+                signature.return_type.clone().unwrap_or_else(|| result_type.clone())
             } else {
                 // Fall back to the annotated result type
                 result_type.clone()
             }
         },
         
-        HirExpression::Peak(inner) => infer_expr_type(inner, program),
+        HirExpression::Peak(inner, _) => infer_expr_type(inner, program),
+        
+        HirExpression::Clone(inner, _) => infer_expr_type(inner, program),
         
-        HirExpression::Clone(inner) => infer_expr_type(inner, program),
+        HirExpression::Consume(inner, _) => infer_expr_type(inner, program),
         
         // Add implementations for the new expression types
-        HirExpression::Boolean(_) => front_end::types::Type::Bool,
+        HirExpression::Boolean(_, _) => front_end::types::Type::Bool,
         
-        HirExpression::String(_) => front_end::types::Type::String,
+        HirExpression::String(_, _) => front_end::types::Type::String,
         
         HirExpression::Conditional { result_type, .. } => result_type.clone(),
         
@@ -1,248 +1,126 @@
 //! HIR visitor traits
 //!
-//! This module defines visitor traits for HIR traversal
+//! This module defines visitor traits for HIR traversal. Every method has a
+//! default implementation that walks into the node's children, so a visitor
+//! only overrides the nodes it cares about and calls the matching `walk_*`
+//! function to keep descending.
 
 use crate::hir::types::*;
 
 /// Trait for HIR visitors
 pub trait HirVisitor {
     /// Visit a program
-    fn visit_program(&mut self, program: &HirProgram);
+    fn visit_program(&mut self, program: &HirProgram) {
+        walk_program(self, program);
+    }
     
     /// Visit a statement
-    fn visit_statement(&mut self, stmt: &HirStatement);
+    fn visit_statement(&mut self, stmt: &HirStatement) {
+        walk_statement(self, stmt);
+    }
     
     /// Visit a function
-    fn visit_function(&mut self, func: &HirFunction);
-    
-    /// Visit a variable declaration
-    fn visit_variable(&mut self, var: &HirVariable);
-    
-    /// Visit an expression
-    fn visit_expression(&mut self, expr: &HirExpression);
-    
-    // Default implementations that traverse the HIR structure
-    fn visit_binary_expression(&mut self, expr: &HirBinaryExpression) {
-        self.visit_expression(&expr.left);
-        self.visit_expression(&expr.right);
+    fn visit_function(&mut self, func: &HirFunction) {
+        walk_function(self, func);
     }
     
-    fn visit_if_statement(&mut self, stmt: &HirIfStatement) {
-        self.visit_expression(&stmt.condition);
-        for s in &stmt.then_branch {
-            self.visit_statement(s);
-        }
-        for s in &stmt.else_branch {
-            self.visit_statement(s);
-        }
-    }
+    /// Visit a function parameter
+    fn visit_parameter(&mut self, _param: &HirParameter) {}
     
-    fn visit_loop_statement(&mut self, stmt: &HirLoopStatement) {
-        if let Some(cond) = &stmt.condition {
-            self.visit_expression(cond);
-        }
-        for s in &stmt.body {
-            self.visit_statement(s);
-        }
+    /// Visit a variable declaration
+    fn visit_variable(&mut self, var: &HirVariable) {
+        walk_variable(self, var);
     }
     
-    fn visit_call_expression(&mut self, expr: &HirCallExpression) {
-        self.visit_expression(&expr.callee);
-        for arg in &expr.arguments {
-            self.visit_expression(arg);
-        }
+    /// Visit an expression
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        walk_expression(self, expr);
     }
 }
 
-/// Walk through all nodes in a HIR program using a visitor
-pub fn walk_program<V: HirVisitor>(visitor: &mut V, program: &HirProgram) {
-    visitor.visit_program(program);
-    
+/// Visit every top-level statement of a program
+pub fn walk_program<V: HirVisitor + ?Sized>(visitor: &mut V, program: &HirProgram) {
     for stmt in &program.statements {
         visitor.visit_statement(stmt);
     }
 }
 
-// Additional walker functions for different node types
-pub fn walk_statement<V: HirVisitor>(visitor: &mut V, stmt: &HirStatement) {
-    visitor.visit_statement(stmt);
-    
+/// Visit the children of a statement
+pub fn walk_statement<V: HirVisitor + ?Sized>(visitor: &mut V, stmt: &HirStatement) {
     match stmt {
-        HirStatement::Expression(expr) => visitor.visit_expression(expr),
-        HirStatement::Variable(var) => visitor.visit_variable(var),
+        HirStatement::Declaration(var) => visitor.visit_variable(var),
+        HirStatement::Assignment(assign) => visitor.visit_expression(&assign.value),
         HirStatement::Function(func) => visitor.visit_function(func),
-        HirStatement::If(if_stmt) => visitor.visit_if_statement(if_stmt),
-        HirStatement::Loop(loop_stmt) => visitor.visit_loop_statement(loop_stmt),
-        HirStatement::Return(expr) => {
+        HirStatement::Return(expr, _) => {
             if let Some(e) = expr {
                 visitor.visit_expression(e);
             }
         },
-        HirStatement::Block(stmts) => {
+        HirStatement::Print(expr, _) |
+        HirStatement::Expression(expr) => visitor.visit_expression(expr),
+        HirStatement::Block(stmts, _) |
+        HirStatement::Atomic(stmts, _) => {
             for s in stmts {
                 visitor.visit_statement(s);
             }
         },
-        _ => {} // Handle other statement types
+        HirStatement::If { condition, then_branch, else_branch, .. } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_statement(else_branch);
+            }
+        },
+        HirStatement::While { condition, body, .. } => {
+            visitor.visit_expression(condition);
+            visitor.visit_statement(body);
+        },
+        HirStatement::Break(_) |
+        HirStatement::Continue(_) => {},
     }
 }
 
-pub fn walk_expression<V: HirVisitor>(visitor: &mut V, expr: &HirExpression) {
-    visitor.visit_expression(expr);
-    
-    match expr {
-        HirExpression::Binary(bin_expr) => visitor.visit_binary_expression(bin_expr),
-        HirExpression::Call(call_expr) => visitor.visit_call_expression(call_expr),
-        // Add other expression patterns as needed
-        _ => {}
+/// Visit the parameters and body of a function
+pub fn walk_function<V: HirVisitor + ?Sized>(visitor: &mut V, func: &HirFunction) {
+    for param in &func.parameters {
+        visitor.visit_parameter(param);
+    }
+    for stmt in &func.body {
+        visitor.visit_statement(stmt);
     }
 }
 
-// Implement various analysis passes using the visitor pattern
-pub mod passes {
-    use super::*;
-    
-    /// Constant folding visitor
-    pub struct ConstantFolder {
-        pub changes_made: bool,
+/// Visit the initializer of a variable declaration
+pub fn walk_variable<V: HirVisitor + ?Sized>(visitor: &mut V, var: &HirVariable) {
+    if let Some(init) = &var.initializer {
+        visitor.visit_expression(init);
     }
-    
-    impl ConstantFolder {
-        pub fn new() -> Self {
-            Self { changes_made: false }
-        }
-        
-        pub fn fold_constant(&mut self, expr: &HirExpression) -> Option<HirExpression> {
-            match expr {
-                HirExpression::Binary(bin_expr) => {
-                    // Implement constant folding for binary operations
-                    if let (HirExpression::Literal(left), HirExpression::Literal(right)) = 
-                        (&*bin_expr.left, &*bin_expr.right) {
-                        // Example: Handle integer addition
-                        // This would need to be expanded for all operations and types
-                        match bin_expr.operator {
-                            BinaryOperator::Add => {
-                                // Implementation depends on your literal type structure
-                                self.changes_made = true;
-                                // Return folded constant
-                                // Some(HirExpression::Literal(...))
-                                None // Placeholder
-                            }
-                            // Handle other operators
-                            _ => None,
-                        }
-                    } else {
-                        None
-                    }
-                }
-                // Handle other constant folding opportunities
-                _ => None,
-            }
-        }
-    }
-    
-    impl HirVisitor for ConstantFolder {
-        fn visit_expression(&mut self, expr: &HirExpression) {
-            // Try to fold this expression
-            if let Some(_folded) = self.fold_constant(expr) {
-                // In a real implementation, you'd need a mutable reference
-                // to replace the expression with the folded version
-            }
-            
-            // Continue traversing
-            walk_expression(self, expr);
-        }
-        
-        // Implement other visitor methods, delegating to walk_* helpers
-        fn visit_program(&mut self, program: &HirProgram) {
-            walk_program(self, program);
-        }
-        
-        fn visit_statement(&mut self, stmt: &HirStatement) {
-            walk_statement(self, stmt);
-        }
-        
-        fn visit_function(&mut self, func: &HirFunction) {
-            for param in &func.parameters {
-                self.visit_variable(param);
-            }
-            for stmt in &func.body {
-                self.visit_statement(stmt);
-            }
-        }
-        
-        fn visit_variable(&mut self, var: &HirVariable) {
-            if let Some(init) = &var.initializer {
-                self.visit_expression(init);
-            }
-        }
-    }
-    
-    /// Dead code elimination visitor
-    pub struct DeadCodeEliminator {
-        pub reachable: std::collections::HashSet<usize>,
-        pub current_function: Option<String>,
-    }
-    
-    impl DeadCodeEliminator {
-        pub fn new() -> Self {
-            Self { 
-                reachable: std::collections::HashSet::new(),
-                current_function: None,
-            }
-        }
-    }
-    
-    impl HirVisitor for DeadCodeEliminator {
-        fn visit_program(&mut self, program: &HirProgram) {
-            // First pass: mark all reachable code
-            walk_program(self, program);
-            
-            // Second pass would remove unreachable code
-            // Would require mutable references to the HIR
-        }
-        
-        fn visit_function(&mut self, func: &HirFunction) {
-            let prev_function = self.current_function.clone();
-            self.current_function = Some(func.name.clone());
-            
-            // Mark function entry point as reachable
-            self.reachable.insert(func.id);
-            
-            // Visit the rest of the function
-            for stmt in &func.body {
-                self.visit_statement(stmt);
-            }
-            
-            self.current_function = prev_function;
-        }
-        
-        fn visit_statement(&mut self, stmt: &HirStatement) {
-            // Mark this statement as reachable
-            self.reachable.insert(stmt.id());
-            
-            // Continue traversal
-            walk_statement(self, stmt);
-            
-            // Special handling for returns, breaks, etc. that affect control flow
-            match stmt {
-                HirStatement::Return(_) => {
-                    // Statements after a return are unreachable
-                    // This would require tracking statement order
-                }
-                // Handle other control flow statements
-                _ => {}
-            }
-        }
-        
-        fn visit_expression(&mut self, expr: &HirExpression) {
-            walk_expression(self, expr);
-        }
-        
-        fn visit_variable(&mut self, var: &HirVariable) {
-            if let Some(init) = &var.initializer {
-                self.visit_expression(init);
+}
+
+/// Visit the sub-expressions of an expression
+pub fn walk_expression<V: HirVisitor + ?Sized>(visitor: &mut V, expr: &HirExpression) {
+    match expr {
+        HirExpression::Binary { left, right, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        },
+        HirExpression::Call { arguments, .. } => {
+            for arg in arguments {
+                visitor.visit_expression(arg);
             }
-        }
+        },
+        HirExpression::Conditional { condition, then_expr, else_expr, .. } => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(then_expr);
+            visitor.visit_expression(else_expr);
+        },
+        HirExpression::Cast { expr, .. } |
+        HirExpression::Peak(expr, _) |
+        HirExpression::Clone(expr, _) |
+        HirExpression::Consume(expr, _) => visitor.visit_expression(expr),
+        HirExpression::Integer(_, _) |
+        HirExpression::Variable(_, _, _, _) |
+        HirExpression::Boolean(_, _) |
+        HirExpression::String(_, _) => {},
     }
 }
@@ -2,10 +2,11 @@
 //!
 //! This module contains the middle-end components of the compiler pipeline,
 //! including HIR (High-level Intermediate Representation) and MIR (Mid-level
-//! Intermediate Representation).
+//! Intermediate Representation), and the optimization pipeline over both.
 
 pub mod hir;
 pub mod mir;
+pub mod optimize;
 
 #[cfg(test)]
 mod tests;
\ No newline at end of file
@@ -0,0 +1,202 @@
+//! Analyses over the MIR control flow graph
+//!
+//! `Liveness` finds where each variable's current value may still be read.
+//! It is the usual backward data flow problem: a variable is live into a
+//! block if the block reads it before assigning it, or if it is live out of
+//! the block and not assigned there, and live out of a block if it is live
+//! into a successor. A phi reads its source at the end of the predecessor
+//! the source belongs to, so phi sources are live out of that predecessor
+//! only, and phi targets are assigned on entry to their block.
+//!
+//! `natural_loops` finds the loops of a function from its back edges: edges
+//! to a block that dominates their source. The loop of a header is the
+//! header and every block that reaches one of its back edges without
+//! passing through the header.
+
+use crate::mir::ssa::immediate_dominators;
+use crate::mir::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// A position in a function: just before the instruction at `index` of
+/// `block`, or before its terminator when `index` is the instruction count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProgramPoint {
+    pub block: BlockId,
+    pub index: usize,
+}
+
+/// Variables live at the boundaries of every block of a function
+#[derive(Debug, Clone, Default)]
+pub struct Liveness {
+    live_in: HashMap<BlockId, HashSet<VarId>>,
+    live_out: HashMap<BlockId, HashSet<VarId>>,
+
+    /// Returned for blocks the function does not have
+    empty: HashSet<VarId>,
+}
+
+impl Liveness {
+    /// Solve liveness for a function, revisiting blocks in postorder until
+    /// nothing changes
+    pub fn compute(func: &MirFunction) -> Self {
+        let mut liveness = Self::default();
+        let mut order = func.reverse_postorder();
+        order.reverse();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in &order {
+                let Some(block) = func.block(id) else {
+                    continue;
+                };
+
+                let live_out: HashSet<VarId> = block.terminator.successors().into_iter()
+                    .flat_map(|successor| liveness.live_across(func, id, successor))
+                    .collect();
+
+                let live_in = transfer(block, live_out.clone()).into_iter().next().unwrap_or_default();
+                changed |= liveness.live_out.get(&id) != Some(&live_out) || liveness.live_in.get(&id) != Some(&live_in);
+                liveness.live_out.insert(id, live_out);
+                liveness.live_in.insert(id, live_in);
+            }
+        }
+        liveness
+    }
+
+    /// Variables live on entry to a block, after its phis
+    pub fn live_in(&self, block: BlockId) -> &HashSet<VarId> {
+        self.live_in.get(&block).unwrap_or(&self.empty)
+    }
+
+    /// Variables live at the end of a block, including those the phis of
+    /// its successors read on the edges out of it
+    pub fn live_out(&self, block: BlockId) -> &HashSet<VarId> {
+        self.live_out.get(&block).unwrap_or(&self.empty)
+    }
+
+    /// Variables live on the edge from one block to another: those live
+    /// into `to` and those its phis read on the edge
+    pub fn live_across(&self, func: &MirFunction, from: BlockId, to: BlockId) -> HashSet<VarId> {
+        let mut live = self.live_in(to).clone();
+        if let Some(to) = func.block(to) {
+            live.extend(phi_sources(to, from));
+        }
+        live
+    }
+
+    /// Variables live just before each instruction of a block and, last,
+    /// before its terminator
+    pub fn live_before(&self, block: &BasicBlock) -> Vec<HashSet<VarId>> {
+        transfer(block, self.live_out(block.id).clone())
+    }
+
+    /// The points of a function at which each variable is live
+    pub fn live_ranges(&self, func: &MirFunction) -> HashMap<VarId, Vec<ProgramPoint>> {
+        let mut ranges: HashMap<VarId, Vec<ProgramPoint>> = HashMap::new();
+        for block in &func.blocks {
+            for (index, live) in self.live_before(block).into_iter().enumerate() {
+                for var in live {
+                    ranges.entry(var).or_default().push(ProgramPoint { block: block.id, index });
+                }
+            }
+        }
+        for points in ranges.values_mut() {
+            points.sort();
+        }
+        ranges
+    }
+}
+
+/// Walk a block backwards from what is live at its end, returning what is
+/// live before each instruction and before the terminator
+fn transfer(block: &BasicBlock, mut live: HashSet<VarId>) -> Vec<HashSet<VarId>> {
+    live.extend(block.terminator.operand().and_then(Operand::variable));
+    let mut before = vec![live.clone()];
+    for instruction in block.instructions.iter().rev() {
+        if let Some(target) = instruction.target() {
+            live.remove(&target);
+        }
+        live.extend(instruction.operands().into_iter().filter_map(Operand::variable));
+        before.push(live.clone());
+    }
+    before.reverse();
+    before
+}
+
+/// Variables the phis of `block` read on the edge from `pred`
+fn phi_sources(block: &BasicBlock, pred: BlockId) -> impl Iterator<Item = VarId> + '_ {
+    block.instructions.iter()
+        .filter_map(move |instruction| match instruction {
+            Instruction::Phi { sources, .. } => sources.iter().find(|(source_pred, _)| *source_pred == pred),
+            _ => None,
+        })
+        .filter_map(|(_, source)| source.variable())
+}
+
+/// A natural loop
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    /// The block every iteration starts in, which dominates the whole loop
+    pub header: BlockId,
+
+    /// The blocks of the loop, including the header
+    pub body: HashSet<BlockId>,
+}
+
+impl Loop {
+    /// The edges that leave the loop
+    pub fn exits(&self, func: &MirFunction) -> Vec<(BlockId, BlockId)> {
+        func.blocks.iter()
+            .filter(|block| self.body.contains(&block.id))
+            .flat_map(|block| block.terminator.successors().into_iter().map(move |successor| (block.id, successor)))
+            .filter(|(_, successor)| !self.body.contains(successor))
+            .collect()
+    }
+}
+
+/// The natural loops of a function, one per header, innermost first
+pub fn natural_loops(func: &MirFunction) -> Vec<Loop> {
+    let idom = immediate_dominators(func);
+    let predecessors = func.predecessors();
+
+    let mut loops: Vec<Loop> = Vec::new();
+    for block in &func.blocks {
+        for header in block.terminator.successors() {
+            if !dominates(header, block.id, &idom) {
+                continue;
+            }
+
+            // Walk backwards from the back edge, stopping at the header
+            let mut body = HashSet::from([header]);
+            let mut work = vec![block.id];
+            while let Some(id) = work.pop() {
+                if body.insert(id) {
+                    work.extend(predecessors.get(&id).into_iter().flatten().filter(|pred| idom.contains_key(*pred)));
+                }
+            }
+
+            match loops.iter_mut().find(|found| found.header == header) {
+                Some(found) => found.body.extend(body),
+                None => loops.push(Loop { header, body }),
+            }
+        }
+    }
+    loops.sort_by_key(|found| found.body.len());
+    loops
+}
+
+/// Whether every path from the entry to `block` passes through `dominator`
+pub fn dominates(dominator: BlockId, block: BlockId, idom: &HashMap<BlockId, BlockId>) -> bool {
+    let mut current = block;
+    loop {
+        if current == dominator {
+            return true;
+        }
+        match idom.get(&current) {
+            Some(&parent) if parent != current => current = parent,
+            _ => return false,
+        }
+    }
+}
+
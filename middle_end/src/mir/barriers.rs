@@ -0,0 +1,95 @@
+//! Write barrier placement for MIR
+//!
+//! A `peak` makes two variables share storage, so a store to either is
+//! seen through the other. While the other one is still live after the
+//! store, the store needs a `WriteBarrier` in front of it, so that what was
+//! written before it is visible by the time the new value is seen through
+//! the alias; where no alias is live, a barrier would only cost time.
+//! `place_write_barriers` drops every barrier a function has and puts one
+//! back before each store to a variable with a live alias at that point,
+//! using `Liveness` for the aliases in the same function.
+//!
+//! An alias of a global can be live in any function up the call stack,
+//! where liveness cannot see it, so every store to a global that some
+//! function peaks keeps its barrier.
+
+use crate::mir::analysis::Liveness;
+use crate::mir::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// Put write barriers before the stores that need them, and only those,
+/// in every function
+pub fn place_write_barriers(program: &mut MirProgram) {
+    let globals: HashSet<VarId> = program.globals.values().map(|var| var.id).collect();
+    let peaked_globals: HashSet<VarId> = program.functions.values()
+        .flat_map(|func| func.blocks.iter().flat_map(|block| &block.instructions))
+        .filter_map(|instruction| match instruction {
+            Instruction::Peak { target, source } => Some([*target, *source]),
+            _ => None,
+        })
+        .flatten()
+        .filter(|var| globals.contains(var))
+        .collect();
+
+    for func in program.functions.values_mut() {
+        for block in func.blocks.iter_mut() {
+            block.instructions.retain(|instruction| !matches!(instruction, Instruction::WriteBarrier { .. }));
+        }
+
+        let aliases = aliases(func);
+        if aliases.is_empty() && peaked_globals.is_empty() {
+            continue;
+        }
+        let liveness = Liveness::compute(func);
+        for block in func.blocks.iter_mut() {
+            let live_before = liveness.live_before(block);
+            let instructions = std::mem::take(&mut block.instructions);
+            for (index, instruction) in instructions.into_iter().enumerate() {
+                if let Some(variable) = store_target(&instruction) {
+                    // What is live before the next instruction is live after this one
+                    let alias_live = aliases.get(&variable)
+                        .is_some_and(|others| others.iter().any(|alias| live_before[index + 1].contains(alias)));
+                    if alias_live || peaked_globals.contains(&variable) {
+                        block.instructions.push(Instruction::WriteBarrier { variable });
+                    }
+                }
+                block.instructions.push(instruction);
+            }
+        }
+    }
+}
+
+/// The variable an instruction stores a new value in. A peak only makes
+/// its target an alias, and a phi's target is a fresh SSA version no
+/// alias can refer to.
+fn store_target(instruction: &Instruction) -> Option<VarId> {
+    match instruction {
+        Instruction::Peak { .. } | Instruction::Phi { .. } => None,
+        instruction => instruction.target(),
+    }
+}
+
+/// The other variables sharing storage with each variable a `peak` of the
+/// function involves, following chains of peaks
+fn aliases(func: &MirFunction) -> HashMap<VarId, HashSet<VarId>> {
+    let mut groups: Vec<HashSet<VarId>> = Vec::new();
+    for instruction in func.blocks.iter().flat_map(|block| &block.instructions) {
+        let Instruction::Peak { target, source } = instruction else {
+            continue;
+        };
+        let (joined, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut groups).into_iter()
+            .partition(|group| group.contains(target) || group.contains(source));
+        let mut group: HashSet<VarId> = joined.into_iter().flatten().collect();
+        group.extend([*target, *source]);
+        groups = rest;
+        groups.push(group);
+    }
+
+    let mut aliases = HashMap::new();
+    for group in groups {
+        for &var in &group {
+            aliases.insert(var, group.iter().copied().filter(|other| *other != var).collect());
+        }
+    }
+    aliases
+}
@@ -0,0 +1,260 @@
+//! Constant propagation for MIR
+//!
+//! Finds the variables holding a known constant at each point and puts the
+//! constant in place of their uses. Operations and casts on constants are
+//! evaluated the way the interpreter runs them, wrapping to the width of
+//! their result, and a branch on a known condition becomes a jump. The
+//! analysis runs forward over the CFG and only follows the edges a branch
+//! can take, so an assignment on a path that is never taken does not spoil
+//! the merge after it (Wegman and Zadeck's conditional constant
+//! propagation, over variables rather than SSA values). Blocks that can no
+//! longer be reached are dropped, along with phi sources for their edges.
+//!
+//! Globals and `writes` variables are never propagated, since other
+//! behaviors may change them between two reads, and neither are the two
+//! sides of a `peak`, since a write to one changes both. Assignments
+//! whose uses were all replaced stay behind for dead store elimination.
+
+use crate::mir::interpreter::{apply_intrinsic, binary_op, cast, Value};
+use crate::mir::types::*;
+use front_end::types::Type;
+use std::collections::{HashMap, HashSet};
+
+/// Variables known to hold a constant at one point
+type Constants = HashMap<VarId, Constant>;
+
+/// Propagate constants and fold branches in every function
+pub fn propagate_constants(program: &mut MirProgram) {
+    for func in program.functions.values_mut() {
+        Propagation::new(func, &mut program.strings).run(func);
+    }
+}
+
+struct Propagation<'a> {
+    /// Types of the variables that may be propagated
+    tracked: HashMap<VarId, Type>,
+
+    /// Constants at the end of each block reached so far
+    exits: HashMap<BlockId, Constants>,
+
+    /// Edges a branch can take
+    taken: HashSet<(BlockId, BlockId)>,
+
+    /// The program's string constants, which folded strings are added to
+    strings: &'a mut StringPool,
+}
+
+impl<'a> Propagation<'a> {
+    fn new(func: &MirFunction, strings: &'a mut StringPool) -> Self {
+        let shared = func.shared_variables();
+        let tracked = func.variables.values()
+            .filter(|var| !shared.contains(&var.id))
+            .map(|var| (var.id, var.typ.clone()))
+            .collect();
+        Self {
+            tracked,
+            exits: HashMap::new(),
+            taken: HashSet::new(),
+            strings,
+        }
+    }
+
+    fn run(mut self, func: &mut MirFunction) {
+        self.analyze(func);
+
+        for block in func.blocks.iter_mut() {
+            if self.exits.contains_key(&block.id) {
+                self.rewrite(block, func.entry_block);
+            }
+        }
+        func.remove_unreachable_blocks();
+    }
+
+    /// Find the constants at the end of every block, revisiting blocks in
+    /// reverse postorder until nothing changes
+    fn analyze(&mut self, func: &MirFunction) {
+        let order = func.reverse_postorder();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in &order {
+                let Some(block) = func.block(id) else {
+                    continue;
+                };
+                let Some(mut constants) = self.entry(id, func.entry_block) else {
+                    continue;
+                };
+                for instruction in &block.instructions {
+                    let value = self.evaluate(instruction, id, &constants);
+                    self.assign(instruction, value, &mut constants);
+                }
+
+                for successor in self.successors(&block.terminator, &constants) {
+                    changed |= self.taken.insert((id, successor));
+                }
+                if self.exits.get(&id) != Some(&constants) {
+                    self.exits.insert(id, constants);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    /// The constants on entry to a block: those that agree on every edge
+    /// taken into it. `None` while no taken edge leads there.
+    fn entry(&self, id: BlockId, entry_block: BlockId) -> Option<Constants> {
+        if id == entry_block {
+            return Some(Constants::new());
+        }
+        let mut incoming = self.exits.iter()
+            .filter(|(pred, _)| self.taken.contains(&(**pred, id)))
+            .map(|(_, exit)| exit);
+        let mut constants = incoming.next()?.clone();
+        for exit in incoming {
+            constants.retain(|var, value| exit.get(var) == Some(value));
+        }
+        Some(constants)
+    }
+
+    /// The blocks a terminator can continue in
+    fn successors(&self, terminator: &Terminator, constants: &Constants) -> Vec<BlockId> {
+        match terminator {
+            Terminator::Branch { condition, true_block, false_block } => match value(condition, constants) {
+                Some(Constant::Boolean(true)) => vec![*true_block],
+                Some(Constant::Boolean(false)) => vec![*false_block],
+                _ => terminator.successors(),
+            },
+            _ => terminator.successors(),
+        }
+    }
+
+    /// The constant an instruction in `block` assigns, if it is one
+    fn evaluate(&mut self, instruction: &Instruction, block: BlockId, constants: &Constants) -> Option<Constant> {
+        let typ = self.tracked.get(&instruction.target()?)?;
+        match instruction {
+            Instruction::Assign { source, .. } => value(source, constants),
+            Instruction::Cast { source, .. } => {
+                let result = cast(Value::from_constant(&value(source, constants)?, self.strings), typ).ok()?;
+                to_constant(result, typ, self.strings)
+            },
+            Instruction::BinaryOp { left, op, right, .. } => {
+                let left = value(left, constants)?;
+                let operand_type = match &left {
+                    Constant::Integer(_, operand_type) => operand_type.clone(),
+                    Constant::Boolean(_) => Type::Bool,
+                    Constant::String(_) => Type::String,
+                };
+                let left = Value::from_constant(&left, self.strings);
+                let right = Value::from_constant(&value(right, constants)?, self.strings);
+                let result = match binary_op(*op, &operand_type, left, right).ok()? {
+                    result @ (Value::Integer(_) | Value::Float(_)) => cast(result, typ).ok()?,
+                    result => result,
+                };
+                to_constant(result, typ, self.strings)
+            },
+            // The same constant along every edge taken
+            Instruction::Phi { sources, .. } => {
+                let mut values = sources.iter()
+                    .filter(|(pred, _)| self.taken.contains(&(*pred, block)))
+                    .map(|(pred, source)| self.exits.get(pred).and_then(|exit| value(source, exit)));
+                let first = values.next()??;
+                values.all(|value| value.as_ref() == Some(&first)).then_some(first)
+            },
+            Instruction::Intrinsic { intrinsic, arguments, .. } if intrinsic.is_pure() => {
+                let arguments = arguments.iter()
+                    .map(|argument| value(argument, constants).map(|constant| Value::from_constant(&constant, self.strings)))
+                    .collect::<Option<Vec<_>>>()?;
+                let result = match apply_intrinsic(*intrinsic, arguments).ok()?? {
+                    result @ (Value::Integer(_) | Value::Float(_)) => cast(result, typ).ok()?,
+                    result => result,
+                };
+                to_constant(result, typ, self.strings)
+            },
+            Instruction::Call { .. } |
+            Instruction::Intrinsic { .. } |
+            Instruction::Peak { .. } |
+            Instruction::AtomicBegin |
+            Instruction::AtomicEnd |
+            Instruction::WriteBarrier { .. } |
+            Instruction::Nop => None,
+        }
+    }
+
+    /// Record what an instruction leaves in its target
+    fn assign(&self, instruction: &Instruction, value: Option<Constant>, constants: &mut Constants) {
+        if let Some(target) = instruction.target() {
+            match value {
+                Some(value) => constants.insert(target, value),
+                None => constants.remove(&target),
+            };
+        }
+    }
+
+    /// Replace uses of constants in a reachable block, fold operations on
+    /// them and turn a branch on a known condition into a jump
+    fn rewrite(&mut self, block: &mut BasicBlock, entry_block: BlockId) {
+        let id = block.id;
+        let Some(mut constants) = self.entry(id, entry_block) else {
+            return;
+        };
+
+        for instruction in block.instructions.iter_mut() {
+            let folded = self.evaluate(instruction, id, &constants);
+            let is_operation = matches!(instruction, Instruction::BinaryOp { .. } | Instruction::Cast { .. });
+            if let Instruction::Phi { sources, .. } = instruction {
+                // Phis stay at the start of the block; only the edges that
+                // can still be taken keep a source
+                sources.retain(|(pred, _)| self.taken.contains(&(*pred, id)));
+                for (pred, source) in sources.iter_mut() {
+                    if let Some(value) = value(source, &self.exits[pred]) {
+                        *source = Operand::Constant(value);
+                    }
+                }
+            } else if let (true, Some(target), Some(value)) = (is_operation, instruction.target(), &folded) {
+                *instruction = Instruction::Assign {
+                    target,
+                    source: Operand::Constant(value.clone()),
+                };
+            } else {
+                for operand in instruction.operands_mut() {
+                    if let Some(value) = value(operand, &constants) {
+                        *operand = Operand::Constant(value);
+                    }
+                }
+            }
+            self.assign(instruction, folded, &mut constants);
+        }
+
+        if let Some(operand) = block.terminator.operand_mut() {
+            if let Some(value) = value(operand, &constants) {
+                *operand = Operand::Constant(value);
+            }
+        }
+        if let Terminator::Branch { condition: Operand::Constant(Constant::Boolean(taken)), true_block, false_block } = block.terminator {
+            block.terminator = Terminator::Jump(if taken { true_block } else { false_block });
+        }
+    }
+}
+
+/// The constant an operand holds, if known
+fn value(operand: &Operand, constants: &Constants) -> Option<Constant> {
+    match operand {
+        Operand::Constant(constant) => Some(constant.clone()),
+        Operand::Variable(id) => constants.get(id).cloned(),
+    }
+}
+
+/// A computed value as a constant of type `typ`. Floats are kept only
+/// when they are whole numbers, the only ones a constant can hold. Strings
+/// go in the pool.
+fn to_constant(value: Value, typ: &Type, strings: &mut StringPool) -> Option<Constant> {
+    match value {
+        Value::Integer(value) => Some(Constant::Integer(value, typ.clone())),
+        Value::Float(value) if value.fract() == 0.0 && value.abs() < (1u64 << 53) as f64 => {
+            Some(Constant::Integer(value as i64, typ.clone()))
+        },
+        Value::Float(_) => None,
+        Value::Boolean(value) => Some(Constant::Boolean(value)),
+        Value::String(value) => Some(Constant::String(strings.intern(&value))),
+    }
+}
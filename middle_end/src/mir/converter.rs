@@ -2,10 +2,14 @@
 //!
 //! This module provides the functionality to convert HIR to MIR.
 
-use crate::hir::types::{HirProgram, HirStatement, HirExpression};
+use crate::hir::effects::pure_functions;
+use crate::hir::escape::EscapeAnalysis;
+use crate::hir::types::{FunctionSignature, HirProgram, HirStatement, HirExpression, SourceLocation, SymbolId};
+use front_end::intern::Name;
 use front_end::token::TokenType; // Import TokenType which might be used as the binary operator
+use front_end::types::Type;
 use crate::mir::types::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Convert a HIR program to a MIR program
 pub fn convert_hir_to_mir(hir: &HirProgram) -> MirProgram {
@@ -13,19 +17,75 @@ pub fn convert_hir_to_mir(hir: &HirProgram) -> MirProgram {
     converter.convert_program(hir)
 }
 
+/// A block whose terminator may not be known yet
+struct OpenBlock {
+    id: BlockId,
+    instructions: Vec<Instruction>,
+    terminator: Option<Terminator>,
+}
+
+impl OpenBlock {
+    fn new(id: BlockId) -> Self {
+        Self { id, instructions: Vec::new(), terminator: None }
+    }
+    
+    fn close(self) -> BasicBlock {
+        BasicBlock {
+            id: self.id,
+            instructions: self.instructions,
+            terminator: self.terminator.expect("blocks are terminated before they are closed"),
+        }
+    }
+}
+
+/// Where `continue` and `break` go in an enclosing loop
+struct LoopTargets {
+    /// The block that evaluates the condition
+    header: BlockId,
+    
+    /// The block after the loop
+    exit: BlockId,
+    
+    /// Number of atomic blocks enclosing the loop itself
+    atomic_depth: usize,
+}
+
 /// Converter for transforming HIR to MIR
 struct HirToMirConverter {
     /// The MIR program being built
     mir: MirProgram,
     
     /// Maps HIR variable names to MIR variable IDs
-    var_map: HashMap<String, VarId>,
+    var_map: HashMap<Name, VarId>,
+    
+    /// Maps resolved HIR symbols to MIR variable IDs. Shadowed variables
+    /// share a name but not a symbol, so lookups try this map first.
+    symbol_map: HashMap<SymbolId, VarId>,
     
     /// Current function being converted
     current_function: Option<MirFunction>,
     
     /// Current block being filled
-    current_block: Option<BasicBlock>,
+    current_block: Option<OpenBlock>,
+    
+    /// Number of atomic blocks enclosing the statement being converted
+    atomic_depth: usize,
+    
+    /// Loops enclosing the statement being converted, innermost last
+    loops: Vec<LoopTargets>,
+    
+    /// Names of the pure functions in the HIR program
+    pure_functions: HashSet<String>,
+    
+    /// Which locals and parameters escape their function
+    escapes: EscapeAnalysis,
+    
+    /// Declared parameter and return types, for typing call arguments and results
+    signatures: HashMap<String, FunctionSignature>,
+    
+    /// Names of the functions the program defines, which take precedence
+    /// over intrinsics of the same name
+    defined_functions: HashSet<String>,
 }
 
 impl HirToMirConverter {
@@ -34,13 +94,30 @@ impl HirToMirConverter {
         Self {
             mir: MirProgram::new(),
             var_map: HashMap::new(),
+            symbol_map: HashMap::new(),
             current_function: None,
             current_block: None,
+            atomic_depth: 0,
+            loops: Vec::new(),
+            pure_functions: HashSet::new(),
+            escapes: EscapeAnalysis::default(),
+            signatures: HashMap::new(),
+            defined_functions: HashSet::new(),
         }
     }
     
     /// Convert a HIR program to a MIR program
     pub fn convert_program(&mut self, hir: &HirProgram) -> MirProgram {
+        self.pure_functions = pure_functions(hir);
+        self.escapes = EscapeAnalysis::analyze(hir);
+        self.signatures = hir.type_info.functions.clone();
+        self.defined_functions = hir.statements.iter()
+            .filter_map(|stmt| match stmt {
+                HirStatement::Function(func) => Some(func.name.clone()),
+                _ => None,
+            })
+            .collect();
+        
         // First collect all global variables
         for stmt in &hir.statements {
             if let HirStatement::Declaration(var) = stmt {
@@ -48,18 +125,16 @@ impl HirToMirConverter {
                 let var_id = self.mir.new_var_id();
                 let mir_var = MirVariable {
                     id: var_id,
-                    name: var.name.clone(),
+                    name: Name::new(&var.name),
                     typ: var.typ.clone(),
+                    permissions: var.permissions.clone(),
+                    storage: Storage::Heap,
+                    location: var.span.known().copied(),
                 };
                 
                 // Add to globals and variable mapping
                 self.mir.globals.insert(var.name.clone(), mir_var);
-                self.var_map.insert(var.name.clone(), var_id);
-                
-                // If there's an initializer, we'll handle it in a special init function
-                if var.initializer.is_some() {
-                    // TODO: Handle global initializers
-                }
+                self.bind(&var.name, var.symbol, var_id);
             }
         }
         
@@ -71,38 +146,100 @@ impl HirToMirConverter {
             }
         }
         
+        // Finally the initializers and other top-level statements
+        if let Some(init) = self.convert_top_level(hir) {
+            self.mir.functions.insert(INIT_FUNCTION.to_string(), init);
+        }
+        
         self.mir.clone()
     }
     
+    /// Convert the global initializers and the other statements outside
+    /// functions, in order, into the function `INIT_FUNCTION`. Returns
+    /// `None` when there is nothing to run.
+    fn convert_top_level(&mut self, hir: &HirProgram) -> Option<MirFunction> {
+        let statements: Vec<&HirStatement> = hir.statements.iter()
+            .filter(|stmt| match stmt {
+                HirStatement::Function(_) => false,
+                HirStatement::Declaration(var) => var.initializer.is_some(),
+                _ => true,
+            })
+            .collect();
+        if statements.is_empty() {
+            return None;
+        }
+        
+        // Functions may have declared locals with the names of globals
+        for stmt in &hir.statements {
+            if let HirStatement::Declaration(var) = stmt {
+                if let Some(global) = self.mir.globals.get(&var.name) {
+                    let id = global.id;
+                    self.bind(&var.name, var.symbol, id);
+                }
+            }
+        }
+        
+        self.begin_function(MirFunction {
+            name: INIT_FUNCTION.to_string(),
+            parameters: Vec::new(),
+            return_type: None,
+            blocks: Vec::new(),
+            entry_block: BlockId(0),
+            variables: HashMap::new(),
+            is_pure: false,
+            is_behavior: false,
+            location: None,
+        });
+        for stmt in statements {
+            match stmt {
+                // The global already exists; only its value is set here
+                HirStatement::Declaration(var) => {
+                    if let (Some(global), Some(init)) = (self.lookup(&var.name, var.symbol), &var.initializer) {
+                        self.initialize(global, init, &var.typ);
+                    }
+                },
+                stmt => self.convert_statement(stmt),
+            }
+        }
+        Some(self.finish_function())
+    }
+    
+    /// Make `func` the function being converted, with an empty entry block
+    fn begin_function(&mut self, mut func: MirFunction) {
+        let entry_id = self.mir.new_block_id();
+        func.entry_block = entry_id;
+        self.current_function = Some(func);
+        self.current_block = Some(OpenBlock::new(entry_id));
+    }
+    
+    /// Return from the function being converted if it has not already,
+    /// and take it
+    fn finish_function(&mut self) -> MirFunction {
+        self.terminate(Terminator::Return(None));
+        let mut func = self.current_function.take().unwrap();
+        if let Some(block) = self.current_block.take() {
+            func.blocks.push(block.close());
+        }
+        func
+    }
+    
     /// Convert a HIR function to a MIR function
     fn convert_function(&mut self, func: &crate::hir::types::HirFunction) -> MirFunction {
         // Create a new MIR function
-        let mut mir_func = MirFunction {
+        let mir_func = MirFunction {
             name: func.name.clone(),
             parameters: Vec::new(),
             return_type: func.return_type.clone(),
             blocks: Vec::new(),
             entry_block: BlockId(0), // Will be set correctly below
             variables: HashMap::new(),
+            is_pure: self.pure_functions.contains(&func.name),
+            is_behavior: func.is_behavior,
+            location: func.span.known().copied(),
         };
         
-        // Set as current function
-        self.current_function = Some(mir_func);
-        
-        // Create entry block
-        let entry_id = self.mir.new_block_id();
-        let entry_block = BasicBlock {
-            id: entry_id,
-            instructions: Vec::new(),
-        };
-        
-        // Set as current block
-        self.current_block = Some(entry_block);
-        
-        // Set entry block ID
-        if let Some(ref mut func) = self.current_function {
-            func.entry_block = entry_id;
-        }
+        // Set as current function, with its entry block
+        self.begin_function(mir_func);
         
         // Convert parameters
         for param in &func.parameters {
@@ -111,8 +248,11 @@ impl HirToMirConverter {
             // Create MIR variable
             let mir_var = MirVariable {
                 id: var_id,
-                name: param.name.clone(),
+                name: Name::new(&param.name),
                 typ: param.typ.clone(),
+                permissions: param.permissions.clone(),
+                storage: self.storage_of(&func.name, &param.name),
+                location: None,
             };
             
             // Add to function variables and parameters
@@ -122,7 +262,7 @@ impl HirToMirConverter {
             }
             
             // Update variable mapping
-            self.var_map.insert(param.name.clone(), var_id);
+            self.bind(&param.name, param.symbol, var_id);
         }
         
         // Convert function body
@@ -131,19 +271,51 @@ impl HirToMirConverter {
         }
         
         // Make sure the function returns if it doesn't already
-        if let Some(ref mut block) = self.current_block {
-            if block.instructions.is_empty() || !matches!(block.instructions.last(), Some(Instruction::Return(_))) {
-                block.instructions.push(Instruction::Return(None));
-            }
+        self.finish_function()
+    }
+    
+    /// Map a declared variable to its MIR variable
+    fn bind(&mut self, name: &str, symbol: Option<SymbolId>, var_id: VarId) {
+        self.var_map.insert(Name::new(name), var_id);
+        if let Some(symbol) = symbol {
+            self.symbol_map.insert(symbol, var_id);
         }
-        
-        // Finalize function
-        let mut func = self.current_function.take().unwrap();
-        if let Some(block) = self.current_block.take() {
-            func.blocks.push(block);
+    }
+    
+    /// The MIR variable a use refers to, by symbol when names were resolved
+    fn lookup(&self, name: &str, symbol: Option<SymbolId>) -> Option<VarId> {
+        symbol.and_then(|symbol| self.symbol_map.get(&symbol))
+            .or_else(|| Name::existing(name).and_then(|name| self.var_map.get(&name)))
+            .copied()
+    }
+    
+    /// The variable a declaration's initializer peaks at, if it is a peak
+    /// of a variable
+    fn peaked_variable(&self, init: &HirExpression) -> Option<VarId> {
+        match init {
+            HirExpression::Peak(inner, _) => match inner.as_ref() {
+                HirExpression::Variable(name, _, _, symbol) => self.lookup(name, *symbol),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    
+    /// Give a declared variable its initial value. Peaking at a variable
+    /// makes the two share storage rather than copying the value.
+    fn initialize(&mut self, var_id: VarId, init: &HirExpression, typ: &Type) {
+        if let Some(source) = self.peaked_variable(init) {
+            self.add_instruction(Instruction::Peak {
+                target: var_id,
+                source,
+            });
+        } else {
+            let operand = self.convert_expression_as(init, typ);
+            self.add_instruction(Instruction::Assign {
+                target: var_id,
+                source: operand,
+            });
         }
-        
-        func
     }
     
     /// Convert a HIR statement to MIR instructions
@@ -152,10 +324,14 @@ impl HirToMirConverter {
             HirStatement::Declaration(var) => {
                 // Create a MIR variable
                 let var_id = self.mir.new_var_id();
+                let function = self.current_function.as_ref().map(|func| func.name.clone()).unwrap_or_default();
                 let mir_var = MirVariable {
                     id: var_id,
-                    name: var.name.clone(),
+                    name: Name::new(&var.name),
                     typ: var.typ.clone(),
+                    permissions: var.permissions.clone(),
+                    storage: self.storage_of(&function, &var.name),
+                    location: var.span.known().copied(),
                 };
                 
                 // Add to function variables
@@ -164,23 +340,20 @@ impl HirToMirConverter {
                 }
                 
                 // Update variable mapping
-                self.var_map.insert(var.name.clone(), var_id);
+                self.bind(&var.name, var.symbol, var_id);
                 
                 // If there's an initializer, convert it
                 if let Some(ref init) = var.initializer {
-                    let operand = self.convert_expression(init);
-                    self.add_instruction(Instruction::Assign {
-                        target: var_id,
-                        source: operand,
-                    });
+                    self.initialize(var_id, init, &var.typ);
                 }
             },
             
             HirStatement::Assignment(assign) => {
                 // Get the target variable ID
-                if let Some(&var_id) = self.var_map.get(&assign.target) {
+                if let Some(var_id) = self.lookup(&assign.target, assign.symbol) {
                     // Convert the value expression
-                    let operand = self.convert_expression(&assign.value);
+                    let typ = self.variable_type(var_id).unwrap_or(Type::Int);
+                    let operand = self.convert_expression_as(&assign.value, &typ);
                     
                     // Add assignment instruction
                     self.add_instruction(Instruction::Assign {
@@ -190,12 +363,131 @@ impl HirToMirConverter {
                 }
             },
             
-            HirStatement::Return(expr_opt) => {
+            HirStatement::Return(expr_opt, _) => {
                 // Convert the return expression if any
-                let operand = expr_opt.as_ref().map(|expr| self.convert_expression(expr));
+                let return_type = self.current_function.as_ref().and_then(|func| func.return_type.clone());
+                let operand = expr_opt.as_ref().map(|expr| match &return_type {
+                    Some(typ) => self.convert_expression_as(expr, typ),
+                    None => self.convert_expression(expr),
+                });
                 
-                // Add return instruction
-                self.add_instruction(Instruction::Return(operand));
+                // Leave every enclosing atomic block first
+                for _ in 0..self.atomic_depth {
+                    self.add_instruction(Instruction::AtomicEnd);
+                }
+                
+                self.terminate(Terminator::Return(operand));
+            },
+            
+            HirStatement::Block(statements, _) => {
+                for stmt in statements {
+                    self.convert_statement(stmt);
+                }
+            },
+            
+            HirStatement::Atomic(statements, _) => {
+                self.add_instruction(Instruction::AtomicBegin);
+                self.atomic_depth += 1;
+                for stmt in statements {
+                    self.convert_statement(stmt);
+                }
+                self.atomic_depth -= 1;
+                
+                // A return inside the block has already ended it
+                if !self.is_terminated() {
+                    self.add_instruction(Instruction::AtomicEnd);
+                }
+            },
+            
+            HirStatement::If { condition, then_branch, else_branch, .. } => {
+                let condition = self.convert_expression(condition);
+                let then_block = self.mir.new_block_id();
+                let else_block = else_branch.as_ref().map(|_| self.mir.new_block_id());
+                let merge_block = self.mir.new_block_id();
+                
+                // Without an else branch a false condition goes straight to the merge block
+                self.terminate(Terminator::Branch {
+                    condition,
+                    true_block: then_block,
+                    false_block: else_block.unwrap_or(merge_block),
+                });
+                
+                self.start_block(then_block);
+                self.convert_statement(then_branch);
+                self.terminate(Terminator::Jump(merge_block));
+                
+                if let (Some(else_block), Some(else_branch)) = (else_block, else_branch) {
+                    self.start_block(else_block);
+                    self.convert_statement(else_branch);
+                    self.terminate(Terminator::Jump(merge_block));
+                }
+                
+                self.start_block(merge_block);
+            },
+            
+            HirStatement::While { condition, body, .. } => {
+                let header_block = self.mir.new_block_id();
+                let body_block = self.mir.new_block_id();
+                let exit_block = self.mir.new_block_id();
+                
+                // The condition is re-evaluated in its own block on every iteration
+                self.terminate(Terminator::Jump(header_block));
+                self.start_block(header_block);
+                let condition = self.convert_expression(condition);
+                self.terminate(Terminator::Branch {
+                    condition,
+                    true_block: body_block,
+                    false_block: exit_block,
+                });
+                
+                self.start_block(body_block);
+                self.loops.push(LoopTargets {
+                    header: header_block,
+                    exit: exit_block,
+                    atomic_depth: self.atomic_depth,
+                });
+                self.convert_statement(body);
+                self.loops.pop();
+                self.terminate(Terminator::Jump(header_block));
+                
+                self.start_block(exit_block);
+            },
+            
+            HirStatement::Break(_) |
+            HirStatement::Continue(_) => {
+                // Validation rejects these outside loops
+                let Some(targets) = self.loops.last() else {
+                    return;
+                };
+                let target = match stmt {
+                    HirStatement::Break(_) => targets.exit,
+                    _ => targets.header,
+                };
+                
+                // Leave the atomic blocks entered inside the loop first
+                for _ in targets.atomic_depth..self.atomic_depth {
+                    self.add_instruction(Instruction::AtomicEnd);
+                }
+                self.terminate(Terminator::Jump(target));
+            },
+            
+            HirStatement::Print(expr, _) => {
+                let argument = self.convert_expression(expr);
+                self.add_instruction(Instruction::Intrinsic {
+                    target: None,
+                    intrinsic: Intrinsic::Print,
+                    arguments: vec![argument],
+                });
+            },
+            
+            // A call whose result is discarded
+            HirStatement::Expression(HirExpression::Call { function, arguments, span, .. }) => {
+                match self.intrinsic(function, arguments) {
+                    Some(intrinsic) => {
+                        self.convert_intrinsic(intrinsic, arguments, span, false);
+                    },
+                    None => self.convert_call(function, arguments, None),
+                }
             },
             
             // Handle other statement types as needed
@@ -210,23 +502,23 @@ impl HirToMirConverter {
     fn convert_expression(&mut self, expr: &HirExpression) -> Operand {
         match expr {
             HirExpression::Integer(value, _) => {
-                // Simple integer constant
-                Operand::Constant(Constant::Integer(*value))
+                // Literals default to Int; a typed context re-types them
+                Operand::Constant(Constant::Integer(*value, Type::Int))
             },
             
-            HirExpression::Boolean(value) => {
+            HirExpression::Boolean(value, _) => {
                 // Simple boolean constant
                 Operand::Constant(Constant::Boolean(*value))
             },
             
-            HirExpression::String(value) => {
-                // Simple string constant
-                Operand::Constant(Constant::String(value.clone()))
+            HirExpression::String(value, _) => {
+                // Stored once in the program's pool
+                Operand::Constant(Constant::String(self.mir.strings.intern(value)))
             },
             
-            HirExpression::Variable(name, _, _) => {
+            HirExpression::Variable(name, _, _, symbol) => {
                 // Look up the variable ID
-                if let Some(&var_id) = self.var_map.get(name) {
+                if let Some(var_id) = self.lookup(name, *symbol) {
                     Operand::Variable(var_id)
                 } else {
                     // Unknown variable, this shouldn't happen if HIR is valid
@@ -234,23 +526,14 @@ impl HirToMirConverter {
                 }
             },
             
-            HirExpression::Binary { left, operator, right, result_type, .. } => {
+            HirExpression::Binary { left, operator, right, result_type, span } => {
                 // Convert the operands
                 let left_operand = self.convert_expression(left);
                 let right_operand = self.convert_expression(right);
                 
-                // Create a temporary variable for the result
-                let result_id = self.mir.new_var_id();
-                let result_var = MirVariable {
-                    id: result_id,
-                    name: format!("temp_{}", result_id.0),
-                    typ: result_type.clone(), // Use the result_type directly from the expression
-                };
-                
-                // Add the variable to the function
-                if let Some(ref mut func) = self.current_function {
-                    func.variables.insert(result_id, result_var);
-                }
+                let common = self.common_type(&left_operand, &right_operand);
+                let left_operand = self.coerce(left_operand, &common);
+                let right_operand = self.coerce(right_operand, &common);
                 
                 // Convert the operator using TokenType instead of BinaryOperator
                 let mir_op = match operator {
@@ -266,11 +549,21 @@ impl HirToMirConverter {
                     TokenType::GreaterEqual => BinaryOperation::GreaterThanEqual,
                     // Any remaining operators
                     _ => {
-                        println!("Warning: Unsupported binary operator encountered in MIR conversion");
+                        eprintln!("Warning: Unsupported binary operator encountered in MIR conversion");
                         BinaryOperation::Add // Default fallback
                     }
                 };
                 
+                // Create a temporary variable for the result
+                let result_type = if mir_op.is_comparison() {
+                    Type::Bool
+                } else if common.is_numeric() {
+                    common
+                } else {
+                    result_type.clone()
+                };
+                let result_id = self.new_temp(&result_type, span.known());
+                
                 // Add the binary operation instruction
                 self.add_instruction(Instruction::BinaryOp {
                     target: result_id,
@@ -283,55 +576,296 @@ impl HirToMirConverter {
                 Operand::Variable(result_id)
             },
             
-            HirExpression::Peak(inner) => {
-                // Bug fix: We should properly handle the peak operation here
-                // Currently it's assigning 0 instead of the value of inner
+            HirExpression::Peak(inner, _) => {
+                // Outside a declaration a peak is only read, so it reads the
+                // current value; declarations bind to the storage instead
+                self.convert_expression(inner)
+            },
+            
+            HirExpression::Clone(inner, span) => {
+                // A clone of a string gets text of its own; for the other
+                // primitive types a copy of the value is a plain read
+                let source = self.convert_expression(inner);
+                if self.operand_type(&source) != Some(Type::String) {
+                    return source;
+                }
+                let target = self.new_temp(&Type::String, span.known());
+                self.add_instruction(Instruction::Intrinsic {
+                    target: Some(target),
+                    intrinsic: Intrinsic::Clone,
+                    arguments: vec![source],
+                });
+                Operand::Variable(target)
+            },
+            
+            HirExpression::Consume(inner, _) => {
+                // Moving a primitive value is a plain read; the permission
+                // checker guarantees the source is never used again
+                self.convert_expression(inner)
+            },
+            
+            HirExpression::Conditional { condition, then_expr, else_expr, result_type, span } => {
+                let condition = self.convert_expression(condition);
+                let result_id = self.new_temp(result_type, span.known());
+                let then_block = self.mir.new_block_id();
+                let else_block = self.mir.new_block_id();
+                let merge_block = self.mir.new_block_id();
+                
+                self.terminate(Terminator::Branch {
+                    condition,
+                    true_block: then_block,
+                    false_block: else_block,
+                });
                 
-                // Get the operand for the inner expression
-                let inner_operand = self.convert_expression(inner);
+                // Each arm stores its value in the shared result before rejoining
+                for (block, arm) in [(then_block, then_expr), (else_block, else_expr)] {
+                    self.start_block(block);
+                    let value = self.convert_expression_as(arm, result_type);
+                    self.add_instruction(Instruction::Assign {
+                        target: result_id,
+                        source: value,
+                    });
+                    self.terminate(Terminator::Jump(merge_block));
+                }
                 
-                // Return the inner operand directly - this way we're "peaking" at the value
-                // Note: This creates a reference semantically but in MIR it looks like a read
-                inner_operand
+                self.start_block(merge_block);
+                Operand::Variable(result_id)
             },
             
-            HirExpression::Clone(inner) => {
-                // Similar for Clone, but semantically this is a deep copy
-                // For primitive types like Int, the MIR representation is the same as Peak
-                // But for complex types, we would generate additional copy instructions
-                let inner_operand = self.convert_expression(inner);
-                inner_operand
+            HirExpression::Call { function, arguments, result_type, span } => {
+                // `print` has no result, so only statements lower to it
+                let intrinsic = self.intrinsic(function, arguments).filter(|intrinsic| *intrinsic != Intrinsic::Print);
+                if let Some(result) = intrinsic.and_then(|intrinsic| self.convert_intrinsic(intrinsic, arguments, span, true)) {
+                    return result;
+                }
+                
+                let result_type = self.signatures.get(function)
+                    .and_then(|signature| signature.return_type.clone())
+                    .unwrap_or_else(|| result_type.clone());
+                let result_id = self.new_temp(&result_type, span.known());
+                self.convert_call(function, arguments, Some(result_id));
+                Operand::Variable(result_id)
             },
             
-            // Handle other expression types as needed
-            _ => {
-                // Default to a dummy constant for now
-                Operand::Constant(Constant::Integer(0))
-            }
+            HirExpression::Cast { expr, target_type, span } => {
+                let source = self.convert_expression(expr);
+                let result_id = self.new_temp(target_type, span.known());
+                self.add_instruction(Instruction::Cast {
+                    target: result_id,
+                    source,
+                });
+                Operand::Variable(result_id)
+            },
         }
     }
     
-    /// Add an instruction to the current block
+    /// Convert an expression whose value is stored as `expected`
+    fn convert_expression_as(&mut self, expr: &HirExpression, expected: &Type) -> Operand {
+        let operand = self.convert_expression(expr);
+        self.coerce(operand, expected)
+    }
+    
+    /// An operand of type `expected`: integer literals take the type
+    /// directly and narrower numbers are widened with a cast
+    fn coerce(&mut self, operand: Operand, expected: &Type) -> Operand {
+        match operand {
+            Operand::Constant(Constant::Integer(value, _)) if expected.is_numeric() => {
+                Operand::Constant(Constant::Integer(value, expected.clone()))
+            },
+            operand => match self.operand_type(&operand) {
+                Some(actual) if actual != *expected && actual.can_widen_to(expected) => {
+                    let location = self.location_of(&operand);
+                    let target = self.new_temp(expected, location.as_ref());
+                    self.add_instruction(Instruction::Cast { target, source: operand });
+                    Operand::Variable(target)
+                },
+                _ => operand,
+            },
+        }
+    }
+    
+    /// The type two operands are brought to before an operation on both: a
+    /// literal takes the other side's type, otherwise the narrower side is
+    /// widened
+    fn common_type(&self, left: &Operand, right: &Operand) -> Type {
+        let left_type = self.operand_type(left).unwrap_or(Type::Int);
+        let right_type = self.operand_type(right).unwrap_or(Type::Int);
+        match (left, right) {
+            (Operand::Constant(Constant::Integer(..)), _) => right_type,
+            (_, Operand::Constant(Constant::Integer(..))) => left_type,
+            _ => left_type.promote(&right_type).unwrap_or(left_type),
+        }
+    }
+    
+    /// The type of a constant, local, parameter or global
+    fn operand_type(&self, operand: &Operand) -> Option<Type> {
+        match operand {
+            Operand::Constant(constant) => Some(constant.typ()),
+            Operand::Variable(id) => self.variable_type(*id),
+        }
+    }
+    
+    /// The source location recorded for a variable operand
+    fn location_of(&self, operand: &Operand) -> Option<SourceLocation> {
+        operand.variable()
+            .and_then(|id| self.variable(id))
+            .and_then(|var| var.location)
+    }
+    
+    fn variable_type(&self, id: VarId) -> Option<Type> {
+        self.variable(id).map(|var| var.typ.clone())
+    }
+    
+    /// A local or parameter of the current function, or a global
+    fn variable(&self, id: VarId) -> Option<&MirVariable> {
+        self.current_function.as_ref()
+            .and_then(|func| func.variables.get(&id))
+            .or_else(|| self.mir.globals.values().find(|var| var.id == id))
+    }
+    
+    /// Panic if an instruction mixes types. Lowering makes every
+    /// conversion an explicit cast, so a mismatch is a converter bug.
+    fn check_types(&self, instruction: &Instruction) {
+        let mismatch = |expected: &Type, operand: &Operand| {
+            self.operand_type(operand).is_some_and(|actual| actual != *expected)
+        };
+        let bad = match instruction {
+            Instruction::Assign { target, source } => {
+                self.variable_type(*target).is_some_and(|typ| mismatch(&typ, source))
+            },
+            Instruction::BinaryOp { target, left, op, right } => {
+                let left_type = self.operand_type(left);
+                let result_type = if op.is_comparison() { Some(Type::Bool) } else { left_type.clone() };
+                left_type.is_some_and(|typ| mismatch(&typ, right))
+                    || result_type.zip(self.variable_type(*target)).is_some_and(|(expected, actual)| expected != actual)
+            },
+            Instruction::Call { function, arguments, .. } => {
+                self.signatures.get(function).is_some_and(|signature| {
+                    signature.parameters.iter().zip(arguments)
+                        .any(|(param, argument)| mismatch(&param.typ, argument))
+                })
+            },
+            Instruction::Phi { target, sources } => {
+                self.variable_type(*target).is_some_and(|typ| sources.iter().any(|(_, source)| mismatch(&typ, source)))
+            },
+            _ => false,
+        };
+        assert!(!bad, "MIR type mismatch: {:?}", instruction);
+    }
+    
+    /// Pass the arguments, left to right, and call `function`, storing
+    /// the result in `target` if there is one
+    fn convert_call(&mut self, function: &str, arguments: &[HirExpression], target: Option<VarId>) {
+        let parameters: Vec<Type> = self.signatures.get(function)
+            .map(|signature| signature.parameters.iter().map(|param| param.typ.clone()).collect())
+            .unwrap_or_default();
+        let arguments = arguments.iter().enumerate()
+            .map(|(index, argument)| match parameters.get(index) {
+                Some(typ) => self.convert_expression_as(argument, typ),
+                None => self.convert_expression(argument),
+            })
+            .collect();
+        self.add_instruction(Instruction::Call {
+            target,
+            function: function.to_string(),
+            arguments,
+        });
+    }
+    
+    /// The intrinsic a call stands for: one with the function's name and
+    /// arity, if the program defines no function of that name
+    fn intrinsic(&self, function: &str, arguments: &[HirExpression]) -> Option<Intrinsic> {
+        Intrinsic::from_name(function)
+            .filter(|intrinsic| !self.defined_functions.contains(function) && intrinsic.arity() == arguments.len())
+    }
+    
+    /// Evaluate the arguments and apply an intrinsic, returning its result
+    /// if `keep_result` is set and the intrinsic has one
+    fn convert_intrinsic(&mut self, intrinsic: Intrinsic, arguments: &[HirExpression], span: &SourceLocation, keep_result: bool) -> Option<Operand> {
+        let mut operands: Vec<Operand> = arguments.iter().map(|argument| self.convert_expression(argument)).collect();
+        if let (Intrinsic::Min | Intrinsic::Max, [left, right]) = (intrinsic, operands.as_slice()) {
+            let common = self.common_type(left, right);
+            operands = operands.into_iter().map(|operand| self.coerce(operand, &common)).collect();
+        }
+        
+        let argument_type = operands.first().and_then(|operand| self.operand_type(operand)).unwrap_or(Type::Int);
+        let target = intrinsic.result_type(&argument_type)
+            .filter(|_| keep_result)
+            .map(|typ| self.new_temp(&typ, span.known()));
+        self.add_instruction(Instruction::Intrinsic { target, intrinsic, arguments: operands });
+        target.map(Operand::Variable)
+    }
+    
+    /// Add an instruction to the current block. Code after a return in
+    /// the same block never runs and is dropped.
     fn add_instruction(&mut self, instruction: Instruction) {
+        self.check_types(&instruction);
         if let Some(ref mut block) = self.current_block {
-            block.instructions.push(instruction);
+            if block.terminator.is_none() {
+                block.instructions.push(instruction);
+            }
         }
     }
-}
-
-// Add this helper method to HirExpression
-impl HirExpression {
-    /// Get the type of this expression
-    fn get_type(&self) -> front_end::types::Type {
-        match self {
-            HirExpression::Integer(_, _) => front_end::types::Type::Int,
-            HirExpression::Boolean(_) => front_end::types::Type::Bool,
-            HirExpression::String(_) => front_end::types::Type::String,
-            HirExpression::Variable(_, typ, _) => typ.clone(),
-            HirExpression::Binary { result_type, .. } => result_type.clone(),
-            // Add other expression types as needed
-            _ => front_end::types::Type::Int, // Default for now
+    
+    /// Whether the current block already has its terminator
+    fn is_terminated(&self) -> bool {
+        self.current_block.as_ref().is_some_and(|block| block.terminator.is_some())
+    }
+    
+    /// End the current block with a terminator, unless it already has one
+    fn terminate(&mut self, terminator: Terminator) {
+        let bad = match &terminator {
+            Terminator::Branch { condition, .. } => {
+                self.operand_type(condition).is_some_and(|typ| typ != Type::Bool)
+            },
+            Terminator::Return(Some(operand)) => {
+                let return_type = self.current_function.as_ref().and_then(|func| func.return_type.clone());
+                return_type.zip(self.operand_type(operand)).is_some_and(|(expected, actual)| expected != actual)
+            },
+            _ => false,
+        };
+        assert!(!bad, "MIR type mismatch: {:?}", terminator);
+        if let Some(ref mut block) = self.current_block {
+            block.terminator.get_or_insert(terminator);
+        }
+    }
+    
+    /// Close the current block and continue filling the block with the
+    /// given ID, falling through to it if the current block is unfinished
+    fn start_block(&mut self, id: BlockId) {
+        self.terminate(Terminator::Jump(id));
+        if let Some(block) = self.current_block.take() {
+            if let Some(ref mut func) = self.current_function {
+                func.blocks.push(block.close());
+            }
+        }
+        
+        self.current_block = Some(OpenBlock::new(id));
+    }
+    
+    /// Create a temporary variable in the current function, holding the
+    /// value of the expression at `location`
+    fn new_temp(&mut self, typ: &Type, location: Option<&SourceLocation>) -> VarId {
+        let id = self.mir.new_var_id();
+        if let Some(ref mut func) = self.current_function {
+            func.variables.insert(id, MirVariable {
+                id,
+                name: Name::new(&format!("temp_{}", id.0)),
+                typ: typ.clone(),
+                permissions: Vec::new(),
+                storage: Storage::Stack,
+                location: location.copied(),
+            });
+        }
+        id
+    }
+    
+    /// Where a local or parameter of `function` is allocated
+    fn storage_of(&self, function: &str, name: &str) -> Storage {
+        if self.escapes.escapes(function, name) {
+            Storage::Heap
+        } else {
+            Storage::Stack
         }
     }
 }
-
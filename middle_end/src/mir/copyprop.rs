@@ -0,0 +1,87 @@
+//! Copy propagation for MIR
+//!
+//! After `x = y`, uses of `x` read `y` directly for as long as neither is
+//! reassigned, so the copies lowering leaves between temporaries and
+//! variables drop out of the data flow and dead store elimination can
+//! remove them. Copies of copies resolve to the original. As in CSE, blocks
+//! are visited in reverse postorder and a block with a single predecessor
+//! starts with the copies available at the end of it; blocks where paths
+//! join start empty. Phi sources are read at the end of their predecessor,
+//! so they use the copies available there.
+//!
+//! Globals and `writes` variables are never propagated through, since
+//! other behaviors or callees may change them between two reads; nor are
+//! variables a `peak` aliases, which change through each other.
+
+use crate::mir::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// Copies available at one point: each copy and the variable it copies
+type Copies = HashMap<VarId, VarId>;
+
+/// Replace uses of copies with the variables they copy in every function
+pub fn propagate_copies(program: &mut MirProgram) {
+    let globals: HashSet<VarId> = program.globals.values().map(|var| var.id).collect();
+
+    for func in program.functions.values_mut() {
+        let mut shared = globals.clone();
+        shared.extend(func.shared_variables());
+
+        let predecessors = func.predecessors();
+        let mut exits: HashMap<BlockId, Copies> = HashMap::new();
+        for id in func.reverse_postorder() {
+            let available = match predecessors[&id].as_slice() {
+                [single] => exits.get(single).cloned().unwrap_or_default(),
+                _ => Copies::new(),
+            };
+            if let Some(block) = func.blocks.iter_mut().find(|block| block.id == id) {
+                exits.insert(id, propagate_in_block(block, available, &shared));
+            }
+        }
+
+        for block in func.blocks.iter_mut() {
+            for instruction in block.instructions.iter_mut() {
+                if let Instruction::Phi { sources, .. } = instruction {
+                    for (pred, source) in sources.iter_mut() {
+                        if let Some(copies) = exits.get(pred) {
+                            substitute(source, copies);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Propagate copies through one block, starting from those available on
+/// entry, and return the copies available at its end
+fn propagate_in_block(block: &mut BasicBlock, mut copies: Copies, shared: &HashSet<VarId>) -> Copies {
+    for instruction in block.instructions.iter_mut() {
+        for operand in instruction.operands_mut() {
+            substitute(operand, &copies);
+        }
+
+        if let Some(target) = instruction.target() {
+            copies.retain(|copy, original| *copy != target && *original != target);
+        }
+        if let Instruction::Assign { target, source: Operand::Variable(original) } = instruction {
+            if target != original && !shared.contains(target) && !shared.contains(original) {
+                copies.insert(*target, *original);
+            }
+        }
+    }
+
+    if let Some(operand) = block.terminator.operand_mut() {
+        substitute(operand, &copies);
+    }
+    copies
+}
+
+/// Read the original instead of a copy
+fn substitute(operand: &mut Operand, copies: &Copies) {
+    if let Operand::Variable(id) = operand {
+        if let Some(original) = copies.get(id) {
+            *id = *original;
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! Common subexpression elimination for MIR
+//!
+//! A binary operation that repeats an earlier one with the same operands
+//! becomes a copy of the earlier result. Blocks are visited in reverse
+//! postorder, and a block with a single predecessor starts with what was
+//! available at the end of it, since every path in passes through there.
+//! Blocks where paths join start empty. An entry is
+//! forgotten as soon as one of its operands or its result is reassigned,
+//! and calls to functions that are not pure forget everything since the
+//! callee may write any variable it can reach. Operations reading a `writes` variable are never reused, as
+//! other writers may change the variable between the two reads, and the
+//! same goes for variables a `peak` aliases.
+
+use crate::mir::types::*;
+use front_end::types::Permission;
+use std::collections::{HashMap, HashSet};
+
+/// Reuse the results of repeated binary operations in every function
+pub fn eliminate_common_subexpressions(program: &mut MirProgram) {
+    let shared: HashSet<VarId> = program.globals.values()
+        .filter(|var| var.permissions.contains(&Permission::Writes))
+        .map(|var| var.id)
+        .collect();
+
+    let pure: HashSet<String> = program.functions.values()
+        .filter(|func| func.is_pure)
+        .map(|func| func.name.clone())
+        .collect();
+
+    for func in program.functions.values_mut() {
+        let mut shared = shared.clone();
+        shared.extend(func.shared_variables());
+
+        let predecessors = func.predecessors();
+        let mut exits: HashMap<BlockId, HashMap<Expression, VarId>> = HashMap::new();
+        for id in func.reverse_postorder() {
+            let available = match predecessors[&id].as_slice() {
+                [single] => exits.get(single).cloned().unwrap_or_default(),
+                _ => HashMap::new(),
+            };
+            if let Some(block) = func.blocks.iter_mut().find(|block| block.id == id) {
+                exits.insert(id, eliminate_in_block(block, available, &shared, &pure));
+            }
+        }
+    }
+}
+
+/// The operation a binary instruction computes, independent of its target
+type Expression = (Operand, BinaryOperation, Operand);
+
+/// Reuse results in one block, starting from those available on entry,
+/// and return the results available at its end
+fn eliminate_in_block(
+    block: &mut BasicBlock,
+    mut available: HashMap<Expression, VarId>,
+    shared: &HashSet<VarId>,
+    pure: &HashSet<String>,
+) -> HashMap<Expression, VarId> {
+
+    for instruction in block.instructions.iter_mut() {
+        match instruction {
+            Instruction::BinaryOp { target, left, op, right } => {
+                let target = *target;
+                let key = (left.clone(), *op, right.clone());
+
+                if let Some(&previous) = available.get(&key) {
+                    *instruction = Instruction::Assign {
+                        target,
+                        source: Operand::Variable(previous),
+                    };
+                    invalidate(&mut available, target);
+                } else {
+                    invalidate(&mut available, target);
+                    let reads_shared = [&key.0, &key.2].iter()
+                        .any(|operand| matches!(operand, Operand::Variable(id) if shared.contains(id)));
+                    if !reads_shared && !uses(&key, target) {
+                        available.insert(key, target);
+                    }
+                }
+            },
+            Instruction::Assign { target, .. } |
+            Instruction::Cast { target, .. } |
+            Instruction::Phi { target, .. } => invalidate(&mut available, *target),
+            Instruction::Call { target: Some(target), function, .. } if pure.contains(function) => {
+                invalidate(&mut available, *target);
+            },
+            Instruction::Call { function, .. } if pure.contains(function) => {},
+            Instruction::Call { .. } => available.clear(),
+            // Intrinsics only write their target
+            Instruction::Intrinsic { target: Some(target), .. } => invalidate(&mut available, *target),
+            _ => {},
+        }
+    }
+    available
+}
+
+/// Whether an expression reads the given variable
+fn uses(expression: &Expression, var: VarId) -> bool {
+    matches!(expression.0, Operand::Variable(id) if id == var)
+        || matches!(expression.2, Operand::Variable(id) if id == var)
+}
+
+/// Forget everything that depends on a variable that is being reassigned
+fn invalidate(available: &mut HashMap<Expression, VarId>, var: VarId) {
+    available.retain(|expression, result| *result != var && !uses(expression, var));
+}
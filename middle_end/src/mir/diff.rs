@@ -0,0 +1,123 @@
+//! Line diffs of textual MIR
+//!
+//! `unified_diff` compares two printouts line by line and writes the
+//! changes in the unified format `diff -u` uses, with three lines of
+//! context around each change. The pass manager uses it to show what each
+//! pass did to the program. The textual format lists functions and
+//! variables in a fixed order, so only what a pass changed shows up.
+
+use std::fmt::Write;
+
+/// Unchanged lines shown before and after each change
+const CONTEXT: usize = 3;
+
+/// One line of the diff, by its index in the old or new text
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Same(usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// The changes from `before` to `after` in unified diff format, labelled
+/// with the given names; empty when the texts have the same lines
+pub fn unified_diff(before: &str, after: &str, before_label: &str, after_label: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let edits = line_edits(&old, &new);
+    let changes: Vec<usize> = edits.iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Same(..)))
+        .map(|(index, _)| index)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut output = String::new();
+    writeln!(output, "--- {}", before_label).unwrap();
+    writeln!(output, "+++ {}", after_label).unwrap();
+
+    // Changes close enough that their context would touch share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &change in &changes {
+        match hunks.last_mut() {
+            Some((_, last)) if change - *last <= 2 * CONTEXT => *last = change,
+            _ => hunks.push((change, change)),
+        }
+    }
+
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+
+        // Lines of each text before the hunk
+        let (old_start, new_start) = edits[..start].iter().fold((0, 0), |(old, new), edit| match edit {
+            Edit::Same(..) => (old + 1, new + 1),
+            Edit::Removed(_) => (old + 1, new),
+            Edit::Added(_) => (old, new + 1),
+        });
+        let old_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Added(_))).count();
+        let new_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Removed(_))).count();
+        writeln!(
+            output,
+            "@@ -{} +{} @@",
+            range(old_start, old_count),
+            range(new_start, new_count),
+        ).unwrap();
+
+        for edit in hunk {
+            match *edit {
+                Edit::Same(index) => writeln!(output, " {}", old[index]).unwrap(),
+                Edit::Removed(index) => writeln!(output, "-{}", old[index]).unwrap(),
+                Edit::Added(index) => writeln!(output, "+{}", new[index]).unwrap(),
+            }
+        }
+    }
+    output
+}
+
+/// A hunk's lines in one text, as `diff -u` writes them: the first line
+/// counting from 1 and the count, or the line before when there are none
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// The shortest edit from `old` to `new`, keeping a longest common
+/// subsequence of lines and listing removals before additions
+fn line_edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Same(i));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(Edit::Removed(i));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(j));
+            j += 1;
+        }
+    }
+    edits
+}
@@ -0,0 +1,66 @@
+//! Dead store elimination for MIR
+//!
+//! Removes assignments whose value can never be read: those after which
+//! the target is not live, whether it is overwritten first on every path
+//! or never read again at all. Removing a store can leave the variables it
+//! read dead in turn, so liveness is recomputed until nothing changes.
+//! Calls are kept even when their result is dead, since the callee may
+//! have effects; intrinsics are removed unless they have effects too.
+//!
+//! Only stores to variables no one else can observe are removed:
+//! temporaries and exclusive locals on the stack. A `reads` variable may be
+//! seen through a `peak` view, a `writes` variable by other behaviors, and
+//! a global or a variable on the heap from outside the function.
+
+use crate::mir::analysis::Liveness;
+use crate::mir::types::*;
+use front_end::types::Permission;
+use std::collections::HashSet;
+
+/// Remove dead stores in every function
+pub fn eliminate_dead_stores(program: &mut MirProgram) {
+    for func in program.functions.values_mut() {
+        let shared = func.shared_variables();
+        let private: HashSet<VarId> = func.variables.values()
+            .filter(|var| var.storage == Storage::Stack && !shared.contains(&var.id))
+            .filter(|var| !var.permissions.iter().any(|p| matches!(p, Permission::Reads | Permission::Writes)))
+            .map(|var| var.id)
+            .collect();
+
+        loop {
+            let liveness = Liveness::compute(func);
+            let mut removed = false;
+            for block in func.blocks.iter_mut() {
+                removed |= eliminate_in_block(block, &private, &liveness);
+            }
+            if !removed {
+                break;
+            }
+        }
+    }
+}
+
+/// Whether an instruction only assigns its target, so dropping it when
+/// the target is dead changes nothing else
+fn is_store(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Assign { .. } | Instruction::BinaryOp { .. } | Instruction::Cast { .. } => true,
+        Instruction::Intrinsic { intrinsic, .. } => intrinsic.is_pure(),
+        _ => false,
+    }
+}
+
+/// Remove the dead stores of one block; returns whether any was removed
+fn eliminate_in_block(block: &mut BasicBlock, private: &HashSet<VarId>, liveness: &Liveness) -> bool {
+    let live_before = liveness.live_before(block);
+    let before = block.instructions.len();
+    let mut index = 0;
+    block.instructions.retain(|instruction| {
+        // What is live before the next instruction is live after this one
+        index += 1;
+        let dead = instruction.target()
+            .is_some_and(|target| private.contains(&target) && !live_before[index].contains(&target));
+        !(dead && is_store(instruction))
+    });
+    block.instructions.len() != before
+}
@@ -0,0 +1,185 @@
+//! Function inlining for MIR
+//!
+//! Replaces calls to small functions with a copy of the callee's blocks, so
+//! trivial accessors cost no call in the interpreter and later passes see
+//! the callee's code in the caller. The block holding the call is split:
+//! the part before the call copies the arguments into fresh copies of the
+//! callee's parameters and jumps to the copy of the callee's entry block,
+//! and every return of the copy assigns the call's target and jumps to a
+//! new block holding the instructions after the call.
+//!
+//! Unlike HIR inlining this handles any control flow in the callee, but
+//! only functions that call nothing are inlined, which rules out recursion.
+//! Inlining repeats until nothing changes, so a helper that only calls
+//! small helpers is inlined once they have been inlined into it. Behaviors
+//! run asynchronously and are never inlined. The target of an inlined call
+//! is assigned on every return, so the pass runs before SSA construction.
+
+use crate::mir::types::*;
+use std::collections::HashMap;
+
+/// Largest body, in instructions, that is inlined by default
+pub const DEFAULT_MIR_INLINE_LIMIT: usize = 16;
+
+/// Inline small functions at their call sites
+pub fn inline_mir_functions(program: &mut MirProgram) {
+    inline_mir_functions_with_limit(program, DEFAULT_MIR_INLINE_LIMIT);
+}
+
+/// Inline functions of at most `limit` instructions that make no calls
+pub fn inline_mir_functions_with_limit(program: &mut MirProgram, limit: usize) {
+    let mut names: Vec<String> = program.functions.keys().cloned().collect();
+    names.sort();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let candidates: HashMap<String, MirFunction> = program.functions.values()
+            .filter(|func| is_candidate(func, limit))
+            .map(|func| (func.name.clone(), func.clone()))
+            .collect();
+
+        for name in &names {
+            let Some(mut func) = program.functions.remove(name) else {
+                continue;
+            };
+            let mut inlined = false;
+            while let Some((block, index, callee)) = find_call(&func, &candidates) {
+                inline_call(program, &mut func, block, index, callee);
+                inlined = true;
+            }
+            if inlined {
+                // Copies of blocks the callee never reached
+                func.remove_unreachable_blocks();
+                changed = true;
+            }
+            program.functions.insert(name.clone(), func);
+        }
+    }
+}
+
+/// Whether a function is small enough to inline and calls nothing
+fn is_candidate(func: &MirFunction, limit: usize) -> bool {
+    let instructions = || func.blocks.iter().flat_map(|block| &block.instructions);
+    !func.is_behavior
+        && instructions().count() <= limit
+        && !instructions().any(|instruction| matches!(instruction, Instruction::Call { .. }))
+        // A copy of the entry block is entered from the caller, which its
+        // phis would have no source for
+        && !func.block(func.entry_block)
+            .is_some_and(|block| block.instructions.iter().any(|instruction| matches!(instruction, Instruction::Phi { .. })))
+}
+
+/// The first call in a function to a candidate it can be inlined at: the
+/// block index, the instruction index and the callee
+fn find_call<'a>(func: &MirFunction, candidates: &'a HashMap<String, MirFunction>) -> Option<(usize, usize, &'a MirFunction)> {
+    func.blocks.iter().enumerate().find_map(|(block, basic_block)| {
+        basic_block.instructions.iter().enumerate().find_map(|(index, instruction)| match instruction {
+            Instruction::Call { function, arguments, .. } => candidates.get(function)
+                .filter(|callee| callee.name != func.name && callee.parameters.len() == arguments.len())
+                .map(|callee| (block, index, callee)),
+            _ => None,
+        })
+    })
+}
+
+/// Replace the call at `index` of the block at `block` with a copy of the
+/// callee
+fn inline_call(program: &mut MirProgram, func: &mut MirFunction, block: usize, index: usize, callee: &MirFunction) {
+    let call_block = &mut func.blocks[block];
+    let rest = call_block.instructions.split_off(index + 1);
+    let Some(Instruction::Call { target, arguments, .. }) = call_block.instructions.pop() else {
+        return;
+    };
+
+    // Fresh variables and blocks for everything the callee declares, in
+    // id order so the new ids do not depend on hashing
+    let mut callee_variables: Vec<&MirVariable> = callee.variables.values().collect();
+    callee_variables.sort_by_key(|var| var.id.0);
+    let mut variables: HashMap<VarId, VarId> = HashMap::new();
+    for var in callee_variables {
+        let id = program.new_var_id();
+        variables.insert(var.id, id);
+        func.variables.insert(id, MirVariable { id, ..var.clone() });
+    }
+    let blocks: HashMap<BlockId, BlockId> = callee.blocks.iter()
+        .map(|callee_block| (callee_block.id, program.new_block_id()))
+        .collect();
+    let continuation = program.new_block_id();
+
+    // Pass the arguments and enter the callee
+    for ((param, _), argument) in callee.parameters.iter().zip(arguments) {
+        call_block.instructions.push(Instruction::Assign { target: variables[param], source: argument });
+    }
+    let terminator = std::mem::replace(&mut call_block.terminator, Terminator::Jump(blocks[&callee.entry_block]));
+    let call_block_id = call_block.id;
+
+    // The rest of the block runs after the callee returns; phis that read
+    // the edges out of the call's block now read them out of its end
+    for successor in terminator.successors() {
+        if let Some(successor) = func.blocks.iter_mut().find(|candidate| candidate.id == successor) {
+            for instruction in successor.instructions.iter_mut() {
+                if let Instruction::Phi { sources, .. } = instruction {
+                    for (pred, _) in sources.iter_mut() {
+                        if *pred == call_block_id {
+                            *pred = continuation;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for callee_block in &callee.blocks {
+        let mut copy = callee_block.clone();
+        copy.id = blocks[&callee_block.id];
+        for instruction in copy.instructions.iter_mut() {
+            rename(instruction.target_mut(), &variables);
+            for operand in instruction.operands_mut() {
+                if let Operand::Variable(id) = operand {
+                    rename(Some(id), &variables);
+                }
+            }
+            match instruction {
+                Instruction::Phi { sources, .. } => {
+                    for (pred, _) in sources.iter_mut() {
+                        *pred = blocks[&*pred];
+                    }
+                },
+                Instruction::Peak { source, .. } |
+                Instruction::WriteBarrier { variable: source } => rename(Some(source), &variables),
+                _ => {},
+            }
+        }
+        if let Some(Operand::Variable(id)) = copy.terminator.operand_mut() {
+            rename(Some(id), &variables);
+        }
+
+        copy.terminator = match copy.terminator {
+            Terminator::Return(value) => {
+                if let (Some(target), Some(value)) = (target, value) {
+                    copy.instructions.push(Instruction::Assign { target, source: value });
+                }
+                Terminator::Jump(continuation)
+            },
+            Terminator::Jump(next) => Terminator::Jump(blocks[&next]),
+            Terminator::Branch { condition, true_block, false_block } => Terminator::Branch {
+                condition,
+                true_block: blocks[&true_block],
+                false_block: blocks[&false_block],
+            },
+        };
+        func.blocks.push(copy);
+    }
+
+    func.blocks.push(BasicBlock { id: continuation, instructions: rest, terminator });
+}
+
+/// Refer to the copy of a callee variable; globals keep their id
+fn rename(id: Option<&mut VarId>, variables: &HashMap<VarId, VarId>) {
+    if let Some(id) = id {
+        if let Some(renamed) = variables.get(id) {
+            *id = *renamed;
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Stepping through a MIR program
+//!
+//! `Debugger` wraps an `Interpreter` and runs a call a little at a time:
+//! one instruction with `step`, a whole call with `step_over`, the rest of
+//! the current call with `step_out`, or up to the next breakpoint with
+//! `resume`. In between, the call stack and the variables of every call in
+//! it can be inspected. Breakpoints are on a function, hit whenever a call
+//! to it starts, or on a position in the MIR, hit before the instruction
+//! there runs. It is meant to sit underneath a debug adapter, which maps
+//! these positions back to source lines.
+//!
+//! A step that fails leaves every call where it was, so the stack can be
+//! inspected at the instruction that failed; stepping again repeats the
+//! failure. With no call in progress there is nothing to run, and every
+//! step reports the run finished.
+
+use super::{Interpreter, InterpreterError, Step, Value};
+use crate::mir::types::{BlockId, VarId};
+use std::collections::HashSet;
+
+/// An instruction of a function, or the terminator of its block when
+/// `index` is the number of instructions in the block
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub function: String,
+    pub block: BlockId,
+    pub index: usize,
+}
+
+/// Where a run stops before going on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// The start of every call to the function
+    Function(String),
+
+    /// The instruction or terminator at a position
+    Position(Position),
+}
+
+/// Why the debugger handed control back
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    /// The step asked for is done
+    Paused,
+
+    /// A breakpoint was reached
+    Breakpoint(Breakpoint),
+
+    /// The call being debugged returned, with its result
+    Finished(Option<Value>),
+}
+
+/// A variable of a call in progress and its current value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub id: VarId,
+    pub name: String,
+    pub value: Value,
+}
+
+/// Runs a call of a MIR program under the control of its caller
+pub struct Debugger<'a> {
+    interpreter: Interpreter<'a>,
+    breakpoints: HashSet<Breakpoint>,
+}
+
+impl<'a> Debugger<'a> {
+    /// Debug calls run by `interpreter`, keeping its limits
+    pub fn new(interpreter: Interpreter<'a>) -> Self {
+        Self {
+            interpreter,
+            breakpoints: HashSet::new(),
+        }
+    }
+
+    /// The interpreter, for what the program printed and its globals
+    pub fn interpreter(&self) -> &Interpreter<'a> {
+        &self.interpreter
+    }
+
+    /// Stop whenever the breakpoint is reached
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    /// Remove a breakpoint, returning whether it was set
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        self.breakpoints.remove(breakpoint)
+    }
+
+    /// The breakpoints set
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    /// Start a call to `function`, pausing before its first instruction.
+    /// Any call still in progress is abandoned.
+    pub fn start(&mut self, function: &str, arguments: Vec<Value>) -> Result<Stop, InterpreterError> {
+        self.interpreter.unwind(0);
+        self.interpreter.push_frame(function, arguments, None)?;
+        let entry = Breakpoint::Function(function.to_string());
+        Ok(match self.breakpoints.contains(&entry) {
+            true => Stop::Breakpoint(entry),
+            false => Stop::Paused,
+        })
+    }
+
+    /// Run one instruction or terminator, pausing in the callee if it is
+    /// a call
+    pub fn step(&mut self) -> Result<Stop, InterpreterError> {
+        if self.depth() == 0 {
+            return Ok(Stop::Finished(None));
+        }
+        let step = self.interpreter.step()?;
+        Ok(self.stop_after(&step).unwrap_or(Stop::Paused))
+    }
+
+    /// Run one instruction or terminator, and if it is a call, the whole
+    /// call unless a breakpoint in it is reached first
+    pub fn step_over(&mut self) -> Result<Stop, InterpreterError> {
+        let depth = self.depth();
+        self.run_while(|debugger| debugger.depth() > depth)
+    }
+
+    /// Run until the current call returns, or a breakpoint is reached
+    pub fn step_out(&mut self) -> Result<Stop, InterpreterError> {
+        let depth = self.depth();
+        self.run_while(|debugger| debugger.depth() >= depth)
+    }
+
+    /// Run until a breakpoint is reached or the call being debugged returns
+    pub fn resume(&mut self) -> Result<Stop, InterpreterError> {
+        self.run_while(|_| true)
+    }
+
+    /// Where the innermost call is, if a call is in progress
+    pub fn position(&self) -> Option<Position> {
+        self.call_stack().pop()
+    }
+
+    /// Where every call in progress is, the outermost first
+    pub fn call_stack(&self) -> Vec<Position> {
+        self.interpreter.frames().iter()
+            .map(|frame| Position {
+                function: frame.func.name.clone(),
+                block: frame.block.id,
+                index: frame.index,
+            })
+            .collect()
+    }
+
+    /// The variables that have a value in the call `depth` calls below the
+    /// innermost, in the order they were created
+    pub fn variables(&self, depth: usize) -> Vec<Variable> {
+        let frames = self.interpreter.frames();
+        let Some(frame) = frames.len().checked_sub(depth + 1).map(|index| &frames[index]) else {
+            return Vec::new();
+        };
+        let mut variables: Vec<Variable> = frame.bindings.iter(self.interpreter.heap())
+            .map(|(id, value)| Variable {
+                id,
+                name: frame.func.variables.get(&id)
+                    .map(|var| var.name.to_string())
+                    .unwrap_or_else(|| format!("var{}", id.0)),
+                value: value.clone(),
+            })
+            .collect();
+        variables.sort_by_key(|var| var.id.0);
+        variables
+    }
+
+    /// The value of the variable `name` in the innermost call, or of the
+    /// global of that name. After SSA a name may have several versions;
+    /// the latest with a value is taken.
+    pub fn variable(&self, name: &str) -> Option<Value> {
+        self.variables(0).into_iter()
+            .rev()
+            .find(|var| var.name == name)
+            .map(|var| var.value)
+            .or_else(|| self.interpreter.global(name).cloned())
+    }
+
+    fn depth(&self) -> usize {
+        self.interpreter.frames().len()
+    }
+
+    /// Take steps for as long as `keep_going` holds, stopping early at a
+    /// breakpoint or once the call being debugged returns
+    fn run_while(&mut self, keep_going: impl Fn(&Self) -> bool) -> Result<Stop, InterpreterError> {
+        if self.depth() == 0 {
+            return Ok(Stop::Finished(None));
+        }
+        loop {
+            let step = self.interpreter.step()?;
+            if let Some(stop) = self.stop_after(&step) {
+                return Ok(stop);
+            }
+            if !keep_going(self) {
+                return Ok(Stop::Paused);
+            }
+        }
+    }
+
+    /// Why to stop after a step, if there is a reason regardless of what
+    /// was asked for: a breakpoint, or the end of the run
+    fn stop_after(&self, step: &Step) -> Option<Stop> {
+        let Some(position) = self.position() else {
+            let result = match step {
+                Step::Returned(value) => value.clone(),
+                _ => None,
+            };
+            return Some(Stop::Finished(result));
+        };
+        let entry = Breakpoint::Function(position.function.clone());
+        if *step == Step::Called && self.breakpoints.contains(&entry) {
+            return Some(Stop::Breakpoint(entry));
+        }
+        let at = Breakpoint::Position(position);
+        self.breakpoints.contains(&at).then_some(Stop::Breakpoint(at))
+    }
+}
@@ -0,0 +1,79 @@
+//! Input and output of interpreted programs
+//!
+//! Everything a program prints, and any line it reads, goes through an
+//! `IoHandler`. Without one the interpreter keeps printed lines in its
+//! `output` and a program reads nothing. `StdIo` connects a program to the
+//! terminal, and `ScriptedIo` feeds it input lines given up front and
+//! records what it prints, for tests and for hosts embedding the
+//! interpreter.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+
+/// Where a program's output goes and its input comes from
+pub trait IoHandler {
+    /// Write one line the program printed
+    fn print(&mut self, line: &str);
+
+    /// The next line of input, without its line ending, or `None` at the
+    /// end of input
+    fn read_line(&mut self) -> Option<String> {
+        None
+    }
+}
+
+/// Standard output and standard input
+#[derive(Debug, Default)]
+pub struct StdIo;
+
+impl IoHandler for StdIo {
+    fn print(&mut self, line: &str) {
+        // A closed standard output is not the program's error
+        let _ = writeln!(std::io::stdout(), "{}", line);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+        }
+    }
+}
+
+/// Input lines given in advance, and the lines printed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptedIo {
+    input: VecDeque<String>,
+    output: Vec<String>,
+}
+
+impl ScriptedIo {
+    /// A program that will read `input`, one line at a time
+    pub fn new<S: Into<String>>(input: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            input: input.into_iter().map(Into::into).collect(),
+            output: Vec::new(),
+        }
+    }
+
+    /// The lines printed so far
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    /// The input lines not read yet
+    pub fn remaining_input(&self) -> impl Iterator<Item = &str> {
+        self.input.iter().map(String::as_str)
+    }
+}
+
+impl IoHandler for ScriptedIo {
+    fn print(&mut self, line: &str) {
+        self.output.push(line.to_string());
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+}
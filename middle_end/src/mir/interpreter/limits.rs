@@ -0,0 +1,83 @@
+//! How much of each resource an interpreter run may use
+//!
+//! A run stops with `InterpreterError::LimitExceeded` as soon as it would
+//! go over one of its `Limits`, so a program that loops, recurses or
+//! allocates without end fails instead of hanging or exhausting memory.
+//! The defaults are far above what the tests and examples need; an
+//! embedder running programs it does not trust can set lower ones.
+
+use std::fmt;
+
+/// Blocks a run may enter, across all calls, by default
+pub const DEFAULT_STEP_LIMIT: usize = 1_000_000;
+
+/// Instructions and terminators a run may execute, across all calls, by
+/// default
+pub const DEFAULT_INSTRUCTION_LIMIT: usize = 10_000_000;
+
+/// Heap cells that may be in use at once by default
+pub const DEFAULT_HEAP_CELL_LIMIT: usize = 1_000_000;
+
+/// Calls that may be in progress at once, counting the outermost, by
+/// default
+pub const DEFAULT_CALL_DEPTH_LIMIT: usize = 256;
+
+/// Something a run uses up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// Blocks entered
+    Blocks,
+
+    /// Instructions and terminators executed
+    Instructions,
+
+    /// Heap cells in use at once
+    HeapCells,
+
+    /// Calls in progress at once
+    CallDepth,
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Resource::Blocks => "blocks entered",
+            Resource::Instructions => "instructions executed",
+            Resource::HeapCells => "heap cells in use",
+            Resource::CallDepth => "nested calls",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The most of each resource a run may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub blocks: usize,
+    pub instructions: usize,
+    pub heap_cells: usize,
+    pub call_depth: usize,
+}
+
+impl Limits {
+    /// The limit on a resource
+    pub fn get(&self, resource: Resource) -> usize {
+        match resource {
+            Resource::Blocks => self.blocks,
+            Resource::Instructions => self.instructions,
+            Resource::HeapCells => self.heap_cells,
+            Resource::CallDepth => self.call_depth,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            blocks: DEFAULT_STEP_LIMIT,
+            instructions: DEFAULT_INSTRUCTION_LIMIT,
+            heap_cells: DEFAULT_HEAP_CELL_LIMIT,
+            call_depth: DEFAULT_CALL_DEPTH_LIMIT,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Memory for the MIR interpreter
+//!
+//! Most variables hold their value directly. A `peak` makes two variables
+//! name the same storage, so a write through either is seen by both: the
+//! value moves into a cell on the `Heap` and both variables refer to it.
+//! Reads and writes go through `Bindings`, which look a variable up in
+//! either place. Copies, including `clone`, read the value out of the cell,
+//! so they never alias. A frame frees the cells it allocated when its call
+//! returns; no reference to them can outlive it, since values never hold
+//! references.
+
+use super::Value;
+use crate::mir::types::VarId;
+use std::collections::HashMap;
+
+/// A cell of the heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellRef(usize);
+
+/// Cells holding values more than one variable refers to
+#[derive(Debug, Clone, Default)]
+pub struct Heap {
+    cells: Vec<Option<Value>>,
+
+    /// Freed cells, reused before the heap grows
+    free: Vec<CellRef>,
+}
+
+impl Heap {
+    /// Create an empty heap
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put a value in a new cell
+    pub fn allocate(&mut self, value: Value) -> CellRef {
+        match self.free.pop() {
+            Some(cell) => {
+                self.cells[cell.0] = Some(value);
+                cell
+            },
+            None => {
+                self.cells.push(Some(value));
+                CellRef(self.cells.len() - 1)
+            },
+        }
+    }
+
+    /// The value in a cell, if it has not been freed
+    pub fn load(&self, cell: CellRef) -> Option<&Value> {
+        self.cells.get(cell.0).and_then(Option::as_ref)
+    }
+
+    /// Replace the value in a cell
+    pub fn store(&mut self, cell: CellRef, value: Value) {
+        self.cells[cell.0] = Some(value);
+    }
+
+    /// Release a cell for reuse
+    pub fn free(&mut self, cell: CellRef) {
+        if self.cells[cell.0].take().is_some() {
+            self.free.push(cell);
+        }
+    }
+
+    /// Cells in use
+    pub fn live_cells(&self) -> usize {
+        self.cells.len() - self.free.len()
+    }
+}
+
+/// Where the variables of a call, or the globals, keep their values
+#[derive(Debug, Default)]
+pub(crate) struct Bindings {
+    values: HashMap<VarId, Value>,
+    cells: HashMap<VarId, CellRef>,
+
+    /// Cells allocated for these variables, freed with them
+    owned: Vec<CellRef>,
+}
+
+impl Bindings {
+    /// Variables holding the given values
+    pub(crate) fn new(values: impl IntoIterator<Item = (VarId, Value)>) -> Self {
+        Self {
+            values: values.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// The value of a variable, if it has one
+    pub(crate) fn get<'h>(&'h self, var: VarId, heap: &'h Heap) -> Option<&'h Value> {
+        match self.cells.get(&var) {
+            Some(cell) => heap.load(*cell),
+            None => self.values.get(&var),
+        }
+    }
+
+    /// Assign a variable, through its cell if it has one
+    pub(crate) fn set(&mut self, var: VarId, value: Value, heap: &mut Heap) {
+        match self.cells.get(&var) {
+            Some(cell) => heap.store(*cell, value),
+            None => {
+                self.values.insert(var, value);
+            },
+        }
+    }
+
+    /// Whether a variable's value is in a cell
+    pub(crate) fn has_cell(&self, var: VarId) -> bool {
+        self.cells.contains_key(&var)
+    }
+
+    /// The cell a variable's value is in, moving the value into a new one
+    /// if it is held directly; `None` if the variable has no value
+    pub(crate) fn cell(&mut self, var: VarId, heap: &mut Heap) -> Option<CellRef> {
+        if let Some(cell) = self.cells.get(&var) {
+            return Some(*cell);
+        }
+        let cell = heap.allocate(self.values.remove(&var)?);
+        self.cells.insert(var, cell);
+        self.owned.push(cell);
+        Some(cell)
+    }
+
+    /// Every variable that has a value, with the value
+    pub(crate) fn iter<'h>(&'h self, heap: &'h Heap) -> impl Iterator<Item = (VarId, &'h Value)> {
+        let held = self.values.iter().map(|(var, value)| (*var, value));
+        let shared = self.cells.iter().filter_map(|(var, cell)| Some((*var, heap.load(*cell)?)));
+        held.chain(shared)
+    }
+
+    /// Make a variable refer to a cell, dropping the value it held
+    pub(crate) fn bind(&mut self, var: VarId, cell: CellRef) {
+        self.values.remove(&var);
+        self.cells.insert(var, cell);
+    }
+
+    /// Free the cells these variables allocated
+    pub(crate) fn release(self, heap: &mut Heap) {
+        for cell in self.owned {
+            heap.free(cell);
+        }
+    }
+}
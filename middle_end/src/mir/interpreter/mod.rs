@@ -0,0 +1,954 @@
+//! MIR interpreter
+//!
+//! Runs MIR functions directly, block by block, to check that lowering and
+//! the MIR passes keep what a program computes. It reports malformed MIR
+//! as errors instead of guessing, and stops programs that trap or run past
+//! their `Limits`.
+
+pub mod debugger;
+pub mod io;
+pub mod limits;
+pub mod memory;
+pub mod scheduler;
+pub mod session;
+pub mod trace;
+
+use crate::hir::diagnostics::Diagnostic;
+use crate::hir::types::SourceLocation;
+use crate::mir::types::*;
+use front_end::codes::runtime;
+use front_end::types::{NumericKind, Type};
+use crate::mir::pretty_print::{get_var_name, pretty_print_instruction, pretty_print_terminator};
+use io::IoHandler;
+use limits::{Limits, Resource};
+use memory::{Bindings, Heap};
+use scheduler::Scheduler;
+use trace::TraceEvent;
+use std::fmt;
+
+/// A runtime value. Arithmetic and ordering are defined on two numbers of
+/// the same kind or two strings, `+` also concatenates strings, and
+/// equality on any two values of the same kind; anything else traps.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Value {
+    /// The value of a constant, looking strings up in the program's pool
+    pub fn from_constant(constant: &Constant, strings: &StringPool) -> Self {
+        match constant {
+            Constant::Integer(value, Type::Float32) => Value::Float(*value as f32 as f64),
+            Constant::Integer(value, Type::Float | Type::Float64) => Value::Float(*value as f64),
+            Constant::Integer(value, _) => Value::Integer(*value),
+            Constant::Boolean(value) => Value::Boolean(*value),
+            Constant::String(id) => Value::String(strings.get(*id).to_string()),
+        }
+    }
+}
+
+/// Where an operation is in a program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub function: String,
+    pub block: BlockId,
+
+    /// The instruction or terminator, as the textual format prints it
+    pub instruction: String,
+
+    /// Where in the source the operation's result comes from, if lowering
+    /// recorded it for the variable the operation assigns or branches on
+    pub source: Option<SourceLocation>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(
+                f,
+                "line {}, column {} (block{} of '{}')",
+                source.start.line, source.start.column, self.block.0, self.function,
+            ),
+            None => write!(f, "block{} of '{}'", self.block.0, self.function),
+        }
+    }
+}
+
+/// Why running a program failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterpreterError {
+    /// Call to a function the program does not define
+    UnknownFunction(String),
+
+    /// Call with the wrong number of arguments
+    ArityMismatch {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// Read of a variable before anything was assigned to it, with its
+    /// name and where it is declared
+    UninitializedVariable {
+        name: String,
+        declared: Option<SourceLocation>,
+    },
+
+    /// Jump or branch to a block the function does not have
+    UnknownBlock(BlockId),
+
+    /// Phi with no operand for the block control came from
+    MissingPhiSource {
+        block: BlockId,
+        predecessor: Option<BlockId>,
+    },
+
+    /// Operation applied to values it is not defined for
+    InvalidOperands {
+        op: BinaryOperation,
+        left: Value,
+        right: Value,
+    },
+
+    /// Branch on a value that is not a boolean
+    NonBooleanCondition(Value),
+
+    /// Cast of a value that is not a number, or to a type that is not one
+    InvalidCast {
+        value: Value,
+        target: Type,
+    },
+
+    /// Intrinsic applied to arguments it is not defined for
+    InvalidIntrinsicArguments {
+        intrinsic: Intrinsic,
+        arguments: Vec<Value>,
+    },
+
+    DivisionByZero,
+
+    /// More of a resource used than the run's limits allow, most likely
+    /// by a loop or recursion that never ends
+    LimitExceeded {
+        resource: Resource,
+        limit: usize,
+    },
+
+    /// Instruction or terminator that failed, and where it is, in the MIR
+    /// and, when lowering recorded it, in the source. As a `Diagnostic` it
+    /// is reported like a compile error, quoting the source line and
+    /// showing the MIR instruction that failed. The location is boxed to
+    /// keep errors small, since every call in progress returns them.
+    Trap {
+        error: Box<InterpreterError>,
+        location: Box<Location>,
+    },
+}
+
+impl InterpreterError {
+    /// What went wrong, without where
+    pub fn cause(&self) -> &InterpreterError {
+        match self {
+            InterpreterError::Trap { error, .. } => error.cause(),
+            error => error,
+        }
+    }
+
+    /// Where the failing operation is, for a trap
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            InterpreterError::Trap { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Stable identifier of what went wrong
+    pub fn code(&self) -> &'static str {
+        match self.cause() {
+            InterpreterError::UnknownFunction(_) => runtime::UNKNOWN_FUNCTION,
+            InterpreterError::ArityMismatch { .. } => runtime::ARITY_MISMATCH,
+            InterpreterError::UninitializedVariable { .. } => runtime::UNINITIALIZED_VARIABLE,
+            InterpreterError::UnknownBlock(_) => runtime::UNKNOWN_BLOCK,
+            InterpreterError::MissingPhiSource { .. } => runtime::MISSING_PHI_SOURCE,
+            InterpreterError::InvalidOperands { .. } => runtime::INVALID_OPERANDS,
+            InterpreterError::NonBooleanCondition(_) => runtime::NON_BOOLEAN_CONDITION,
+            InterpreterError::InvalidCast { .. } => runtime::INVALID_CAST,
+            InterpreterError::InvalidIntrinsicArguments { .. } => runtime::INVALID_INTRINSIC_ARGUMENTS,
+            InterpreterError::DivisionByZero => runtime::DIVISION_BY_ZERO,
+            InterpreterError::LimitExceeded { .. } => runtime::LIMIT_EXCEEDED,
+            InterpreterError::Trap { .. } => unreachable!("a cause is never a trap"),
+        }
+    }
+}
+
+impl From<&InterpreterError> for Diagnostic {
+    fn from(error: &InterpreterError) -> Self {
+        let mut diagnostic = Diagnostic::error(error.cause().to_string()).with_code(error.code());
+        if let Some(location) = error.location() {
+            if let Some(source) = location.source {
+                diagnostic = diagnostic.with_primary(source.to_span(), "");
+            }
+            diagnostic = diagnostic.with_note(format!(
+                "while running `{}` in block{} of '{}'",
+                location.instruction, location.block.0, location.function,
+            ));
+        }
+        if let InterpreterError::UninitializedVariable { name, declared: Some(declared) } = error.cause() {
+            diagnostic = diagnostic.with_secondary(declared.to_span(), format!("'{}' is declared here", name));
+        }
+        if let InterpreterError::LimitExceeded { resource, .. } = error.cause() {
+            diagnostic = diagnostic.with_help(format!("check for a loop or recursion that never ends, or raise the limit on {}", resource));
+        }
+        diagnostic
+    }
+}
+
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterpreterError::UnknownFunction(name) => write!(f, "Unknown function '{}'", name),
+            InterpreterError::ArityMismatch { function, expected, found } => {
+                write!(f, "Function '{}' takes {} argument(s) but {} were passed", function, expected, found)
+            },
+            InterpreterError::UninitializedVariable { name, .. } => write!(f, "Variable '{}' read before assignment", name),
+            InterpreterError::UnknownBlock(block) => write!(f, "Unknown block{}", block.0),
+            InterpreterError::MissingPhiSource { block, predecessor } => match predecessor {
+                Some(predecessor) => write!(f, "Phi in block{} has no source for block{}", block.0, predecessor.0),
+                None => write!(f, "Phi in entry block{}", block.0),
+            },
+            InterpreterError::InvalidOperands { op, left, right } => {
+                write!(f, "Cannot apply {:?} to {:?} and {:?}", op, left, right)
+            },
+            InterpreterError::NonBooleanCondition(value) => write!(f, "Branch on non-boolean {:?}", value),
+            InterpreterError::InvalidCast { value, target } => write!(f, "Cannot cast {:?} to {:?}", value, target),
+            InterpreterError::InvalidIntrinsicArguments { intrinsic, arguments } => {
+                write!(f, "Cannot apply {} to {:?}", intrinsic.name(), arguments)
+            },
+            InterpreterError::DivisionByZero => write!(f, "Division by zero"),
+            InterpreterError::LimitExceeded { resource, limit } => {
+                write!(f, "Exceeded the limit of {} {}", limit, resource)
+            },
+            InterpreterError::Trap { error, location } => write!(f, "{} at {}", error, location),
+        }
+    }
+}
+
+impl std::error::Error for InterpreterError {}
+
+/// A call in progress: its function, its locals and parameters, and the
+/// next instruction it will run
+pub(crate) struct Frame<'a> {
+    pub(crate) func: &'a MirFunction,
+    pub(crate) bindings: Bindings,
+    pub(crate) block: &'a BasicBlock,
+
+    /// The block control came from, which the phis of `block` read by
+    previous: Option<BlockId>,
+
+    /// The instruction of `block` to run next, or its terminator once
+    /// every instruction has run
+    pub(crate) index: usize,
+
+    /// Where the caller stores the result
+    result: Option<VarId>,
+}
+
+/// What running one instruction or terminator did
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Step {
+    /// Ran an instruction, or went on to the next block
+    Ran,
+
+    /// Started a call, whose frame is now the innermost
+    Called,
+
+    /// Returned from the innermost call, with its result
+    Returned(Option<Value>),
+}
+
+/// Runs the functions of a MIR program
+///
+/// Each block runs its instructions in order, then its terminator picks the
+/// next block or returns. Phis read the operand for the block control came
+/// from, all at once on entry to their block. Integer results wrap to the
+/// width of their type, as they would in compiled code.
+pub struct Interpreter<'a> {
+    program: &'a MirProgram,
+
+    /// Values of the globals assigned so far
+    globals: Bindings,
+
+    /// Cells for values that `peak` makes several variables share
+    heap: Heap,
+
+    /// Lines written by `print` when there is no I/O handler, so tests can
+    /// check what a program printed
+    output: Vec<String>,
+
+    /// Where `print` writes, if not to `output`
+    io: Option<&'a mut dyn IoHandler>,
+
+    /// Blocks entered and instructions executed so far
+    steps: usize,
+    instructions: usize,
+
+    /// Every call in progress, the innermost last, each with its own
+    /// locals so recursive calls keep theirs apart. Calls do not nest on
+    /// the Rust stack, so a run can stop between any two instructions and
+    /// continue later, which `debugger` builds on.
+    stack: Vec<Frame<'a>>,
+
+    limits: Limits,
+
+    /// Told about every instruction and terminator run, when tracing
+    tracer: Option<&'a mut dyn FnMut(&TraceEvent)>,
+
+    /// Where calls to behaviors wait, when they are scheduled
+    scheduler: Option<Scheduler>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(program: &'a MirProgram) -> Self {
+        Self {
+            program,
+            globals: Bindings::default(),
+            heap: Heap::new(),
+            output: Vec::new(),
+            io: None,
+            steps: 0,
+            instructions: 0,
+            stack: Vec::new(),
+            limits: Limits::default(),
+            tracer: None,
+            scheduler: None,
+        }
+    }
+
+    /// Report every instruction and terminator run to `tracer`, such as
+    /// one made by `trace::write_trace`, for comparing a run against
+    /// compiled code
+    pub fn with_tracer(mut self, tracer: &'a mut dyn FnMut(&TraceEvent)) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Queue calls to behaviors instead of running them, for
+    /// `run_behaviors` to run in an order picked by `seed`
+    pub fn with_scheduler(mut self, seed: u64) -> Self {
+        self.scheduler = Some(Scheduler::new(seed));
+        self
+    }
+
+    /// Send what the program prints to `io` instead of keeping it in
+    /// `output`
+    pub fn with_io(mut self, io: &'a mut dyn IoHandler) -> Self {
+        self.io = Some(io);
+        self
+    }
+
+    /// Stop runs that would use more blocks, instructions, heap cells or
+    /// nested calls than `limits` allow, so a loop that never exits fails a
+    /// test instead of hanging it
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Fail calls made while `limit` calls are already in progress
+    pub fn with_call_depth_limit(mut self, limit: usize) -> Self {
+        self.limits.call_depth = limit;
+        self
+    }
+
+    /// Stop runs after they have entered `limit` blocks in all
+    pub fn with_step_limit(mut self, limit: usize) -> Self {
+        self.limits.blocks = limit;
+        self
+    }
+
+    /// The blocks entered so far, across all calls
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// The instructions and terminators executed so far, across all calls
+    pub fn instructions(&self) -> usize {
+        self.instructions
+    }
+
+    /// The lines printed so far, when there is no I/O handler
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    /// The value of a global, if it has been assigned
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.program.globals.get(name).and_then(|var| self.globals.get(var.id, &self.heap))
+    }
+
+    /// Assign a global before a run, as an earlier run left it. Names the
+    /// program does not declare are ignored.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        if let Some(var) = self.program.globals.get(name) {
+            self.globals.set(var.id, value, &mut self.heap);
+        }
+    }
+
+    /// The heap, whose cells hold the values `peak` shares
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// Call `function` with `arguments`, returning its result
+    pub fn call(&mut self, function: &str, arguments: Vec<Value>) -> Result<Option<Value>, InterpreterError> {
+        let depth = self.stack.len();
+        let result = self.push_frame(function, arguments, None).and_then(|()| self.finish(depth));
+        self.unwind(depth);
+        result
+    }
+
+    /// Run the program: its top-level statements, which set the globals,
+    /// then `main` if it has one, returning what `main` returns. A script
+    /// without `main` only runs its top-level statements.
+    pub fn run(&mut self) -> Result<Option<Value>, InterpreterError> {
+        if self.program.functions.contains_key(INIT_FUNCTION) {
+            self.call(INIT_FUNCTION, Vec::new())?;
+        }
+        match self.program.functions.contains_key("main") {
+            true => self.call("main", Vec::new()),
+            false => Ok(None),
+        }
+    }
+
+    /// Run the queued calls to behaviors, and those they queue in turn,
+    /// until none is left, returning how many ran. Without a scheduler
+    /// nothing is ever queued.
+    pub fn run_behaviors(&mut self) -> Result<usize, InterpreterError> {
+        let mut ran = 0;
+        while let Some(message) = self.scheduler.as_mut().and_then(Scheduler::next_message) {
+            self.call(&message.behavior, message.arguments)?;
+            ran += 1;
+        }
+        Ok(ran)
+    }
+
+    /// The calls to behaviors queued that have not run yet
+    pub fn pending_behaviors(&self) -> usize {
+        self.scheduler.as_ref().map_or(0, Scheduler::pending)
+    }
+
+    /// Every call in progress, the outermost first
+    pub(crate) fn frames(&self) -> &[Frame<'a>] {
+        &self.stack
+    }
+
+    /// Run until the call that has `depth` calls below it returns
+    pub(crate) fn finish(&mut self, depth: usize) -> Result<Option<Value>, InterpreterError> {
+        loop {
+            if let Step::Returned(value) = self.step()? {
+                if self.stack.len() == depth {
+                    return Ok(value);
+                }
+            }
+        }
+    }
+
+    /// Drop the calls above `depth` that an error left in progress
+    pub(crate) fn unwind(&mut self, depth: usize) {
+        while self.stack.len() > depth {
+            if let Some(frame) = self.stack.pop() {
+                frame.bindings.release(&mut self.heap);
+            }
+        }
+    }
+
+    /// Start a call to `function`, whose result the caller stores in
+    /// `result`. Each call gets its own locals, so a recursive call does
+    /// not see or overwrite those of the calls it is nested in.
+    pub(crate) fn push_frame(
+        &mut self,
+        function: &str,
+        arguments: Vec<Value>,
+        result: Option<VarId>,
+    ) -> Result<(), InterpreterError> {
+        let program = self.program;
+        let func = program.functions.get(function)
+            .ok_or_else(|| InterpreterError::UnknownFunction(function.to_string()))?;
+        if func.parameters.len() != arguments.len() {
+            return Err(InterpreterError::ArityMismatch {
+                function: function.to_string(),
+                expected: func.parameters.len(),
+                found: arguments.len(),
+            });
+        }
+        let entry = func.block(func.entry_block).ok_or(InterpreterError::UnknownBlock(func.entry_block))?;
+
+        self.check_limit(Resource::CallDepth, self.stack.len())?;
+
+        self.stack.push(Frame {
+            func,
+            bindings: Bindings::new(func.parameters.iter().map(|(id, _)| *id).zip(arguments)),
+            block: entry,
+            previous: None,
+            index: 0,
+            result,
+        });
+        self.enter(entry.id, None)
+    }
+
+    /// Run the next instruction or terminator of the innermost call. A
+    /// failed step leaves the call where it was.
+    pub(crate) fn step(&mut self) -> Result<Step, InterpreterError> {
+        let depth = self.stack.len();
+        let frame = self.stack.last().expect("step outside a call");
+        let (func, block, index, result) = (frame.func, frame.block, frame.index, frame.result);
+        let instruction = block.instructions.get(index);
+        let strings = &self.program.strings;
+        let text = || match instruction {
+            Some(instruction) => pretty_print_instruction(instruction, func, strings),
+            None => pretty_print_terminator(&block.terminator, func, strings),
+        };
+        self.check_limit(Resource::Instructions, self.instructions)
+            .map_err(|error| trap(error, func, block.id, None, text()))?;
+        self.instructions += 1;
+        let reads = self.tracer.as_ref().map(|_| {
+            let operands = match instruction {
+                Some(instruction) => instruction.operands(),
+                None => block.terminator.operand().into_iter().collect(),
+            };
+            self.traced(func, operands.into_iter().filter_map(Operand::variable))
+        });
+
+        let step = match instruction {
+            Some(instruction) => {
+                let step = self.execute(func, instruction)
+                    .map_err(|error| trap(error, func, block.id, instruction.target(), text()))?;
+                // The caller's frame, even if the instruction started a call
+                self.stack[depth - 1].index += 1;
+                step
+            },
+            None => self.terminate(block)
+                .map_err(|error| trap(error, func, block.id, block.terminator.operand().and_then(Operand::variable), text()))?,
+        };
+
+        if let Some(reads) = reads {
+            let writes = match (instruction, &step) {
+                (Some(_), Step::Called) => Vec::new(),
+                (Some(instruction), _) => self.traced(func, instruction.target()),
+                (None, Step::Returned(_)) => match self.stack.last() {
+                    Some(caller) => self.traced(caller.func, result),
+                    None => Vec::new(),
+                },
+                // The phis of the block entered
+                (None, _) => match self.stack.last() {
+                    Some(frame) => self.traced(frame.func, frame.block.instructions.iter()
+                        .take_while(|instruction| matches!(instruction, Instruction::Phi { .. }))
+                        .filter_map(Instruction::target)),
+                    None => Vec::new(),
+                },
+            };
+            let event = TraceEvent {
+                function: func.name.clone(),
+                block: block.id,
+                index,
+                text: text(),
+                reads,
+                writes,
+            };
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer(&event);
+            }
+        }
+        Ok(step)
+    }
+
+    /// Fail if `used` of a resource is already as much as the limit allows
+    fn check_limit(&self, resource: Resource, used: usize) -> Result<(), InterpreterError> {
+        let limit = self.limits.get(resource);
+        if used >= limit {
+            return Err(InterpreterError::LimitExceeded { resource, limit });
+        }
+        Ok(())
+    }
+
+    /// The variables of `func` among `vars` that have a value in the
+    /// innermost call, each once, by their printed names, for a trace
+    fn traced(&self, func: &MirFunction, vars: impl IntoIterator<Item = VarId>) -> Vec<(String, Value)> {
+        let mut traced: Vec<(String, Value)> = Vec::new();
+        for var in vars {
+            let name = get_var_name(var, func);
+            if let (Some(value), false) = (self.bindings(var).get(var, &self.heap), traced.iter().any(|(seen, _)| *seen == name)) {
+                traced.push((name, value.clone()));
+            }
+        }
+        traced
+    }
+
+    /// Run the terminator of `block`, the current block of the innermost call
+    fn terminate(&mut self, block: &BasicBlock) -> Result<Step, InterpreterError> {
+        let next = match &block.terminator {
+            Terminator::Return(operand) => {
+                let value = operand.as_ref().map(|operand| self.read(operand)).transpose()?;
+                if let Some(frame) = self.stack.pop() {
+                    frame.bindings.release(&mut self.heap);
+                    if let (Some(target), Some(value)) = (frame.result, &value) {
+                        self.write(target, value.clone());
+                    }
+                }
+                return Ok(Step::Returned(value));
+            },
+            Terminator::Jump(target) => *target,
+            Terminator::Branch { condition, true_block, false_block } => {
+                match self.read(condition)? {
+                    Value::Boolean(true) => *true_block,
+                    Value::Boolean(false) => *false_block,
+                    value => return Err(InterpreterError::NonBooleanCondition(value)),
+                }
+            },
+        };
+        self.enter(next, Some(block.id))?;
+        Ok(Step::Ran)
+    }
+
+    /// Move the innermost call into the block `id`, from the block
+    /// `previous` unless it has just started, and assign the phis there
+    fn enter(&mut self, id: BlockId, previous: Option<BlockId>) -> Result<(), InterpreterError> {
+        self.check_limit(Resource::Blocks, self.steps)?;
+        let frame = self.stack.last_mut().expect("enter outside a call");
+        let block = frame.func.block(id).ok_or(InterpreterError::UnknownBlock(id))?;
+        self.steps += 1;
+
+        frame.previous = previous;
+        frame.block = block;
+        frame.index = 0;
+        self.enter_phis(block)?;
+
+        // The phis have been assigned, so they are skipped
+        if let Some(frame) = self.stack.last_mut() {
+            frame.index = block.instructions.iter()
+                .take_while(|instruction| matches!(instruction, Instruction::Phi { .. }))
+                .count();
+        }
+        Ok(())
+    }
+
+    /// Assign the phis at the start of `block` from the edge taken into it.
+    /// Every phi reads its source before any is assigned.
+    fn enter_phis(&mut self, block: &BasicBlock) -> Result<(), InterpreterError> {
+        let previous = self.stack.last().and_then(|frame| frame.previous);
+        let mut values = Vec::new();
+        for instruction in &block.instructions {
+            let Instruction::Phi { target, sources } = instruction else {
+                break;
+            };
+            let source = sources.iter()
+                .find(|(pred, _)| Some(*pred) == previous)
+                .ok_or(InterpreterError::MissingPhiSource { block: block.id, predecessor: previous })?;
+            values.push((*target, self.read(&source.1)?));
+        }
+        for (target, value) in values {
+            self.write(target, value);
+        }
+        Ok(())
+    }
+
+    /// Run an instruction of `func`; a call only starts, with its frame
+    /// pushed on top
+    fn execute(&mut self, func: &MirFunction, instruction: &Instruction) -> Result<Step, InterpreterError> {
+        match instruction {
+            Instruction::Assign { target, source } => {
+                let value = self.read(source)?;
+                self.write(*target, value);
+            },
+            Instruction::Cast { target, source } => {
+                let value = self.read(source)?;
+                let value = cast(value, &self.variable_type(func, *target))?;
+                self.write(*target, value);
+            },
+            Instruction::BinaryOp { target, left, op, right } => {
+                let typ = self.operand_type(func, left);
+                let left = self.read(left)?;
+                let right = self.read(right)?;
+                let value = match binary_op(*op, &typ, left, right)? {
+                    value @ (Value::Integer(_) | Value::Float(_)) => cast(value, &self.variable_type(func, *target))?,
+                    value => value,
+                };
+                self.write(*target, value);
+            },
+            Instruction::Call { target, function, arguments } => {
+                let arguments = arguments.iter()
+                    .map(|argument| self.read(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // With a scheduler, a behavior runs later, from its mailbox
+                let program = self.program;
+                let behavior = program.functions.get(function).filter(|callee| callee.is_behavior);
+                if let (Some(scheduler), Some(behavior)) = (self.scheduler.as_mut(), behavior) {
+                    if behavior.parameters.len() != arguments.len() {
+                        return Err(InterpreterError::ArityMismatch {
+                            function: function.clone(),
+                            expected: behavior.parameters.len(),
+                            found: arguments.len(),
+                        });
+                    }
+                    scheduler.send(function, arguments);
+                    return Ok(Step::Ran);
+                }
+                self.push_frame(function, arguments, *target)?;
+                return Ok(Step::Called);
+            },
+            Instruction::Intrinsic { target, intrinsic, arguments } => {
+                let arguments = arguments.iter()
+                    .map(|argument| self.read(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if *intrinsic == Intrinsic::Print {
+                    if let [value] = arguments.as_slice() {
+                        let line = value.to_string();
+                        match self.io.as_mut() {
+                            Some(io) => io.print(&line),
+                            None => self.output.push(line),
+                        }
+                    }
+                }
+                let result = match apply_intrinsic(*intrinsic, arguments)? {
+                    Some(value @ (Value::Integer(_) | Value::Float(_))) => match target {
+                        Some(target) => Some(cast(value, &self.variable_type(func, *target))?),
+                        None => Some(value),
+                    },
+                    result => result,
+                };
+                if let (Some(target), Some(result)) = (target, result) {
+                    self.write(*target, result);
+                }
+            },
+            Instruction::Peak { target, source } => self.peak(*target, *source)?,
+            // Assigned on entry to the block; there is only one thread, so
+            // atomic blocks need nothing more, and peaked variables share
+            // a cell, so a write is seen through every alias at once
+            Instruction::Phi { .. } |
+            Instruction::AtomicBegin |
+            Instruction::AtomicEnd |
+            Instruction::WriteBarrier { .. } |
+            Instruction::Nop => {},
+        }
+        Ok(Step::Ran)
+    }
+
+    fn read(&self, operand: &Operand) -> Result<Value, InterpreterError> {
+        match operand {
+            Operand::Constant(constant) => Ok(Value::from_constant(constant, &self.program.strings)),
+            Operand::Variable(id) => self.bindings(*id).get(*id, &self.heap)
+                .cloned()
+                .ok_or_else(|| self.uninitialized(*id)),
+        }
+    }
+
+    /// The type of a constant, or of a local of `func` or a global
+    fn operand_type(&self, func: &MirFunction, operand: &Operand) -> Type {
+        match operand {
+            Operand::Constant(Constant::Integer(_, typ)) => typ.clone(),
+            Operand::Constant(Constant::Boolean(_)) => Type::Bool,
+            Operand::Constant(Constant::String(_)) => Type::String,
+            Operand::Variable(id) => self.variable_type(func, *id),
+        }
+    }
+
+    /// The type of a local of `func` or of a global
+    fn variable_type(&self, func: &MirFunction, id: VarId) -> Type {
+        func.variables.get(&id)
+            .or_else(|| self.program.globals.values().find(|var| var.id == id))
+            .map(|var| var.typ.clone())
+            .unwrap_or(Type::Int)
+    }
+
+    /// Where a variable lives: the globals, or the innermost frame
+    fn bindings(&self, var: VarId) -> &Bindings {
+        match self.stack.last() {
+            Some(frame) if !self.is_global(var) => &frame.bindings,
+            _ => &self.globals,
+        }
+    }
+
+    fn is_global(&self, var: VarId) -> bool {
+        self.program.globals.values().any(|global| global.id == var)
+    }
+
+    fn write(&mut self, target: VarId, value: Value) {
+        let is_global = self.is_global(target);
+        let bindings = match self.stack.last_mut() {
+            Some(frame) if !is_global => &mut frame.bindings,
+            _ => &mut self.globals,
+        };
+        bindings.set(target, value, &mut self.heap);
+    }
+
+    /// Make the local `target` refer to the storage of `source`, so each
+    /// sees what is written through the other
+    fn peak(&mut self, target: VarId, source: VarId) -> Result<(), InterpreterError> {
+        let source_is_global = self.is_global(source);
+        let frame = &mut self.stack.last_mut().expect("peak outside a call").bindings;
+        let allocates = match source_is_global {
+            true => !self.globals.has_cell(source),
+            false => !frame.has_cell(source),
+        };
+        if allocates && self.heap.live_cells() >= self.limits.heap_cells {
+            return Err(InterpreterError::LimitExceeded {
+                resource: Resource::HeapCells,
+                limit: self.limits.heap_cells,
+            });
+        }
+        let cell = match source_is_global {
+            true => self.globals.cell(source, &mut self.heap),
+            false => frame.cell(source, &mut self.heap),
+        };
+        match cell {
+            Some(cell) => frame.bind(target, cell),
+            None => return Err(self.uninitialized(source)),
+        }
+        Ok(())
+    }
+
+    /// The error for a read of `var` before anything was assigned to it,
+    /// naming it as the source does
+    fn uninitialized(&self, var: VarId) -> InterpreterError {
+        let variable = self.program.globals.values().find(|global| global.id == var)
+            .or_else(|| self.stack.last().and_then(|frame| frame.func.variables.get(&var)));
+        InterpreterError::UninitializedVariable {
+            name: variable.map_or_else(|| format!("var_{}", var.0), |variable| variable.name.to_string()),
+            declared: variable.and_then(|variable| variable.location),
+        }
+    }
+}
+
+/// An error raised by an operation of `func`, with where the operation is
+/// and its text. Traps raised by a call this one made are returned as they
+/// are.
+fn trap(error: InterpreterError, func: &MirFunction, block: BlockId, var: Option<VarId>, instruction: String) -> InterpreterError {
+    match error {
+        InterpreterError::Trap { .. } => error,
+        error => InterpreterError::Trap {
+            error: Box::new(error),
+            location: Box::new(Location {
+                function: func.name.clone(),
+                block,
+                instruction,
+                source: var.and_then(|var| func.variables.get(&var)).and_then(|var| var.location),
+            }),
+        },
+    }
+}
+
+/// The result of `op` on two operands of type `typ`. Integer results wrap
+/// to the width of `typ`, and unsigned integers divide and compare as
+/// unsigned.
+pub(crate) fn binary_op(op: BinaryOperation, typ: &Type, left: Value, right: Value) -> Result<Value, InterpreterError> {
+    use BinaryOperation::*;
+
+    let unsigned = typ.numeric_kind() == Some(NumericKind::Unsigned);
+    let value = match (op, &left, &right) {
+        (Add, Value::Integer(l), Value::Integer(r)) => Value::Integer(wrap(l.wrapping_add(*r), typ)),
+        (Subtract, Value::Integer(l), Value::Integer(r)) => Value::Integer(wrap(l.wrapping_sub(*r), typ)),
+        (Multiply, Value::Integer(l), Value::Integer(r)) => Value::Integer(wrap(l.wrapping_mul(*r), typ)),
+        (Divide | Remainder, Value::Integer(_), Value::Integer(0)) => return Err(InterpreterError::DivisionByZero),
+        (Divide, Value::Integer(l), Value::Integer(r)) if unsigned => Value::Integer(wrap((*l as u64 / *r as u64) as i64, typ)),
+        (Remainder, Value::Integer(l), Value::Integer(r)) if unsigned => Value::Integer(wrap((*l as u64 % *r as u64) as i64, typ)),
+        (Divide, Value::Integer(l), Value::Integer(r)) => Value::Integer(wrap(l.wrapping_div(*r), typ)),
+        (Remainder, Value::Integer(l), Value::Integer(r)) => Value::Integer(wrap(l.wrapping_rem(*r), typ)),
+        (LessThan | LessThanEqual | GreaterThan | GreaterThanEqual, Value::Integer(l), Value::Integer(r)) => {
+            let ordering = match unsigned {
+                true => (*l as u64).cmp(&(*r as u64)),
+                false => l.cmp(r),
+            };
+            Value::Boolean(match op {
+                LessThan => ordering.is_lt(),
+                LessThanEqual => ordering.is_le(),
+                GreaterThan => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        },
+        (And, Value::Boolean(l), Value::Boolean(r)) => Value::Boolean(*l && *r),
+        (Or, Value::Boolean(l), Value::Boolean(r)) => Value::Boolean(*l || *r),
+        (Add, Value::Float(l), Value::Float(r)) => Value::Float(l + r),
+        (Subtract, Value::Float(l), Value::Float(r)) => Value::Float(l - r),
+        (Multiply, Value::Float(l), Value::Float(r)) => Value::Float(l * r),
+        (Divide, Value::Float(l), Value::Float(r)) => Value::Float(l / r),
+        (Remainder, Value::Float(l), Value::Float(r)) => Value::Float(l % r),
+        (LessThan, Value::Float(l), Value::Float(r)) => Value::Boolean(l < r),
+        (LessThanEqual, Value::Float(l), Value::Float(r)) => Value::Boolean(l <= r),
+        (GreaterThan, Value::Float(l), Value::Float(r)) => Value::Boolean(l > r),
+        (GreaterThanEqual, Value::Float(l), Value::Float(r)) => Value::Boolean(l >= r),
+        (Add, Value::String(l), Value::String(r)) => Value::String(format!("{}{}", l, r)),
+        (LessThan, Value::String(l), Value::String(r)) => Value::Boolean(l < r),
+        (LessThanEqual, Value::String(l), Value::String(r)) => Value::Boolean(l <= r),
+        (GreaterThan, Value::String(l), Value::String(r)) => Value::Boolean(l > r),
+        (GreaterThanEqual, Value::String(l), Value::String(r)) => Value::Boolean(l >= r),
+        (Equal, _, _) if same_kind(&left, &right) => Value::Boolean(left == right),
+        (NotEqual, _, _) if same_kind(&left, &right) => Value::Boolean(left != right),
+        _ => return Err(InterpreterError::InvalidOperands { op, left, right }),
+    };
+    Ok(value)
+}
+
+/// The result of an intrinsic; `print` has none, and writing its argument
+/// out is left to the caller
+pub(crate) fn apply_intrinsic(intrinsic: Intrinsic, arguments: Vec<Value>) -> Result<Option<Value>, InterpreterError> {
+    let value = match (intrinsic, arguments.as_slice()) {
+        (Intrinsic::Print, [_]) => return Ok(None),
+        (Intrinsic::Abs, [Value::Integer(v)]) => Value::Integer(v.wrapping_abs()),
+        (Intrinsic::Abs, [Value::Float(v)]) => Value::Float(v.abs()),
+        (Intrinsic::Min, [Value::Integer(l), Value::Integer(r)]) => Value::Integer(*l.min(r)),
+        (Intrinsic::Max, [Value::Integer(l), Value::Integer(r)]) => Value::Integer(*l.max(r)),
+        (Intrinsic::Min, [Value::Float(l), Value::Float(r)]) => Value::Float(l.min(*r)),
+        (Intrinsic::Max, [Value::Float(l), Value::Float(r)]) => Value::Float(l.max(*r)),
+        (Intrinsic::Length, [Value::String(s)]) => Value::Integer(s.chars().count() as i64),
+        (Intrinsic::Concat, [Value::String(l), Value::String(r)]) => Value::String(format!("{}{}", l, r)),
+        (Intrinsic::Clone, [value]) => value.clone(),
+        _ => return Err(InterpreterError::InvalidIntrinsicArguments { intrinsic, arguments }),
+    };
+    Ok(Some(value))
+}
+
+fn same_kind(left: &Value, right: &Value) -> bool {
+    std::mem::discriminant(left) == std::mem::discriminant(right)
+}
+
+/// A number converted to the numeric type `target`. Integers wrap to the
+/// width of an integer type and floats truncate towards zero.
+pub(crate) fn cast(value: Value, target: &Type) -> Result<Value, InterpreterError> {
+    let converted = match (&value, target) {
+        (Value::Integer(v), Type::Float32) => Value::Float(*v as f32 as f64),
+        (Value::Integer(v), Type::Float | Type::Float64) => Value::Float(*v as f64),
+        (Value::Float(v), Type::Float32) => Value::Float(*v as f32 as f64),
+        (Value::Float(v), Type::Float | Type::Float64) => Value::Float(*v),
+        (Value::Integer(v), _) if target.is_numeric() => Value::Integer(wrap(*v, target)),
+        (Value::Float(v), _) if target.is_numeric() => Value::Integer(wrap(*v as i64, target)),
+        _ => return Err(InterpreterError::InvalidCast { value, target: target.clone() }),
+    };
+    Ok(converted)
+}
+
+/// An integer wrapped to the width of an integer type
+fn wrap(value: i64, typ: &Type) -> i64 {
+    match typ {
+        Type::Int8 => value as i8 as i64,
+        Type::Int16 => value as i16 as i64,
+        Type::Int32 => value as i32 as i64,
+        Type::UInt8 => value as u8 as i64,
+        Type::UInt16 => value as u16 as i64,
+        Type::UInt32 => value as u32 as i64,
+        _ => value,
+    }
+}
@@ -0,0 +1,91 @@
+//! Deterministic scheduling of actor behaviors
+//!
+//! Calling a behavior sends it a message instead of running it: the call
+//! returns at once and the message waits in the behavior's mailbox. With a
+//! `Scheduler` set, the interpreter queues these messages, and
+//! `Interpreter::run_behaviors` runs them one at a time, each to
+//! completion, until every mailbox is empty. Messages to one behavior run
+//! in the order they were sent; which mailbox goes next is picked by a
+//! pseudo-random generator seeded by the caller. The same seed gives the
+//! same interleaving on every run, so a test of a concurrent program is
+//! reproducible, and trying several seeds explores several interleavings.
+//!
+//! MIR does not say which actor a behavior belongs to, so each behavior has
+//! a mailbox of its own.
+
+use super::Value;
+use std::collections::VecDeque;
+
+/// A call to a behavior waiting to run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub behavior: String,
+    pub arguments: Vec<Value>,
+}
+
+/// The messages waiting for one behavior, oldest first
+#[derive(Debug)]
+struct Mailbox {
+    behavior: String,
+    messages: VecDeque<Vec<Value>>,
+}
+
+/// Queues the messages sent to behaviors and picks which runs next
+#[derive(Debug)]
+pub struct Scheduler {
+    /// In the order each behavior was first sent a message, so picking
+    /// among them does not depend on hashing
+    mailboxes: Vec<Mailbox>,
+
+    /// State of the generator that picks the next mailbox
+    state: u64,
+}
+
+impl Scheduler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            mailboxes: Vec::new(),
+            state: seed,
+        }
+    }
+
+    /// Queue a call to `behavior` behind those already sent to it
+    pub fn send(&mut self, behavior: &str, arguments: Vec<Value>) {
+        match self.mailboxes.iter_mut().find(|mailbox| mailbox.behavior == behavior) {
+            Some(mailbox) => mailbox.messages.push_back(arguments),
+            None => self.mailboxes.push(Mailbox {
+                behavior: behavior.to_string(),
+                messages: VecDeque::from([arguments]),
+            }),
+        }
+    }
+
+    /// The oldest message of a mailbox picked from those that are not empty
+    pub fn next_message(&mut self) -> Option<Message> {
+        let waiting = self.mailboxes.iter().filter(|mailbox| !mailbox.messages.is_empty()).count();
+        if waiting == 0 {
+            return None;
+        }
+        let pick = (self.next_random() % waiting as u64) as usize;
+        let mailbox = self.mailboxes.iter_mut()
+            .filter(|mailbox| !mailbox.messages.is_empty())
+            .nth(pick)?;
+        let arguments = mailbox.messages.pop_front()?;
+        Some(Message { behavior: mailbox.behavior.clone(), arguments })
+    }
+
+    /// The messages sent that have not run yet
+    pub fn pending(&self) -> usize {
+        self.mailboxes.iter().map(|mailbox| mailbox.messages.len()).sum()
+    }
+
+    /// The next number of a SplitMix64 sequence, which is small and the
+    /// same on every platform
+    fn next_random(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
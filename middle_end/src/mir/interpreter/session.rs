@@ -0,0 +1,211 @@
+//! Interpreter sessions for a REPL
+//!
+//! A REPL lowers each input on its own, so every chunk of MIR it produces
+//! numbers its variables, blocks and strings from zero. A `Session` keeps
+//! one program that grows as chunks are added: each function is copied in
+//! with fresh ids, a global with the name of one already defined refers to
+//! it, and a function with the name of one already defined replaces it.
+//! The values of the globals and the lines printed are kept from one run
+//! to the next, including what a run assigned or printed before it failed.
+
+use super::limits::Limits;
+use super::{Interpreter, InterpreterError, Value};
+use crate::mir::types::*;
+use std::collections::HashMap;
+
+/// A program built up a chunk at a time, and the state of its globals
+#[derive(Debug)]
+pub struct Session {
+    program: MirProgram,
+
+    /// Values of the globals assigned so far, by name
+    globals: HashMap<String, Value>,
+
+    /// Every line printed, across runs
+    output: Vec<String>,
+
+    limits: Limits,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            program: MirProgram::new(),
+            globals: HashMap::new(),
+            output: Vec::new(),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Stop each run that would use more than `limits` allow
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Add the globals and functions of `chunk` to the session
+    pub fn define(&mut self, chunk: MirProgram) {
+        let strings: HashMap<StringId, StringId> = chunk.strings.iter()
+            .map(|(id, value)| (id, self.program.strings.intern(value)))
+            .collect();
+
+        // Globals in id order, so the new ids do not depend on hashing
+        let mut globals: Vec<(String, MirVariable)> = chunk.globals.into_iter().collect();
+        globals.sort_by_key(|(_, var)| var.id.0);
+        let mut variables: HashMap<VarId, VarId> = HashMap::new();
+        for (name, var) in globals {
+            let chunk_id = var.id;
+            let id = match self.program.globals.get(&name) {
+                Some(existing) => existing.id,
+                None => {
+                    let id = self.program.new_var_id();
+                    self.program.globals.insert(name, MirVariable { id, ..var });
+                    id
+                },
+            };
+            variables.insert(chunk_id, id);
+        }
+
+        let mut functions: Vec<MirFunction> = chunk.functions.into_values().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+        for func in functions {
+            let func = self.renumber(func, &variables, &strings);
+            self.program.functions.insert(func.name.clone(), func);
+        }
+    }
+
+    /// Add `chunk` to the session and call its function `entry`, such as
+    /// the one a REPL wraps an input's statements in
+    pub fn eval(&mut self, chunk: MirProgram, entry: &str) -> Result<Option<Value>, InterpreterError> {
+        self.define(chunk);
+        self.call(entry, Vec::new())
+    }
+
+    /// Call a function defined in the session, starting from the globals
+    /// earlier runs left
+    pub fn call(&mut self, function: &str, arguments: Vec<Value>) -> Result<Option<Value>, InterpreterError> {
+        let mut interpreter = Interpreter::new(&self.program).with_limits(self.limits);
+        for (name, value) in &self.globals {
+            interpreter.set_global(name, value.clone());
+        }
+        let result = interpreter.call(function, arguments);
+
+        let globals: Vec<(String, Value)> = self.program.globals.keys()
+            .filter_map(|name| Some((name.clone(), interpreter.global(name)?.clone())))
+            .collect();
+        let output = interpreter.output().to_vec();
+        self.globals.extend(globals);
+        self.output.extend(output);
+        result
+    }
+
+    /// The program as defined so far
+    pub fn program(&self) -> &MirProgram {
+        &self.program
+    }
+
+    /// The names of the functions defined so far, in name order
+    pub fn functions(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.program.functions.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// The names of the globals defined so far, in name order
+    pub fn globals(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.program.globals.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// The value of a global, if a run has assigned it
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// Every line printed so far
+    pub fn output(&self) -> &[String] {
+        &self.output
+    }
+
+    /// The lines printed since the last time they were taken
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// A function of a chunk with fresh ids for its locals and blocks,
+    /// its globals and strings referring to those of the session
+    fn renumber(
+        &mut self,
+        mut func: MirFunction,
+        globals: &HashMap<VarId, VarId>,
+        strings: &HashMap<StringId, StringId>,
+    ) -> MirFunction {
+        let mut locals: Vec<MirVariable> = func.variables.into_values().collect();
+        locals.sort_by_key(|var| var.id.0);
+        let mut variables = globals.clone();
+        func.variables = HashMap::new();
+        for var in locals {
+            let id = self.program.new_var_id();
+            variables.insert(var.id, id);
+            func.variables.insert(id, MirVariable { id, ..var });
+        }
+        let blocks: HashMap<BlockId, BlockId> = func.blocks.iter()
+            .map(|block| (block.id, self.program.new_block_id()))
+            .collect();
+
+        for (param, _) in func.parameters.iter_mut() {
+            rename(param, &variables);
+        }
+        func.entry_block = blocks.get(&func.entry_block).copied().unwrap_or(func.entry_block);
+        for block in func.blocks.iter_mut() {
+            block.id = blocks[&block.id];
+            for instruction in block.instructions.iter_mut() {
+                if let Some(target) = instruction.target_mut() {
+                    rename(target, &variables);
+                }
+                for operand in instruction.operands_mut() {
+                    rename_operand(operand, &variables, strings);
+                }
+                match instruction {
+                    Instruction::Phi { sources, .. } => {
+                        for (pred, source) in sources.iter_mut() {
+                            *pred = blocks.get(pred).copied().unwrap_or(*pred);
+                            rename_operand(source, &variables, strings);
+                        }
+                    },
+                    Instruction::Peak { source, .. } |
+                    Instruction::WriteBarrier { variable: source } => rename(source, &variables),
+                    _ => {},
+                }
+            }
+            if let Some(operand) = block.terminator.operand_mut() {
+                rename_operand(operand, &variables, strings);
+            }
+            for successor in block.terminator.successors_mut() {
+                *successor = blocks.get(successor).copied().unwrap_or(*successor);
+            }
+        }
+        func
+    }
+}
+
+fn rename(id: &mut VarId, variables: &HashMap<VarId, VarId>) {
+    if let Some(renamed) = variables.get(id) {
+        *id = *renamed;
+    }
+}
+
+fn rename_operand(operand: &mut Operand, variables: &HashMap<VarId, VarId>, strings: &HashMap<StringId, StringId>) {
+    match operand {
+        Operand::Variable(id) => rename(id, variables),
+        Operand::Constant(Constant::String(id)) => *id = strings[id],
+        Operand::Constant(_) => {},
+    }
+}
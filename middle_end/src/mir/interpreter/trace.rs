@@ -0,0 +1,64 @@
+//! Traces of interpreter runs
+//!
+//! With tracing on, the interpreter reports every instruction and
+//! terminator it runs as a `TraceEvent`: where it is, its text in the MIR
+//! format, the values of the variables it read and the values of those it
+//! assigned. For a jump or branch those are the phis of the block entered,
+//! and for a return, the caller's variable that receives the result; a
+//! call assigns nothing until it returns. Comparing a trace against one
+//! from compiled code shows the first instruction where the two disagree.
+
+use super::Value;
+use crate::mir::types::BlockId;
+use std::fmt;
+use std::io::Write;
+
+/// One instruction or terminator that ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    pub function: String,
+    pub block: BlockId,
+
+    /// The instruction's index in the block, or for the terminator, the
+    /// number of instructions
+    pub index: usize,
+
+    /// The instruction as the textual format prints it
+    pub text: String,
+
+    /// Variables read, by their printed names, and their values
+    pub reads: Vec<(String, Value)>,
+
+    /// Variables assigned, by their printed names, and their new values
+    pub writes: Vec<(String, Value)>,
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:block{}:{}  {}", self.function, self.block.0, self.index, self.text)?;
+        for (label, values) in [("read", &self.reads), ("wrote", &self.writes)] {
+            if values.is_empty() {
+                continue;
+            }
+            write!(f, "  ; {} ", label)?;
+            for (index, (name, value)) in values.iter().enumerate() {
+                if index > 0 {
+                    write!(f, ", ")?;
+                }
+                match value {
+                    Value::String(text) => write!(f, "{} = {:?}", name, text)?,
+                    value => write!(f, "{} = {}", name, value)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A tracer writing each event to `output` on a line of its own. Write
+/// errors are ignored, so tracing never changes how a run ends.
+pub fn write_trace(mut output: impl Write) -> impl FnMut(&TraceEvent) {
+    move |event| {
+        let _ = writeln!(output, "{}", event);
+    }
+}
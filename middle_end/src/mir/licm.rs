@@ -0,0 +1,215 @@
+//! Loop-invariant code motion for MIR
+//!
+//! Moves computations whose operands do not change inside a loop to its
+//! preheader, so they run once instead of on every iteration, as long as
+//! that changes nothing else the program can see.
+
+use crate::mir::analysis::{dominates, natural_loops, Liveness, Loop};
+use crate::mir::ssa::immediate_dominators;
+use crate::mir::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// Hoist loop-invariant computations in every function. Loops are visited
+/// innermost first and revisited after every move, so an operation hoisted
+/// out of an inner loop can move out of the outer one as well.
+pub fn hoist_loop_invariants(program: &mut MirProgram) {
+    let globals: HashSet<VarId> = program.globals.values().map(|var| var.id).collect();
+    let mut names: Vec<String> = program.functions.keys().cloned().collect();
+    names.sort();
+
+    let mut next_block_id = program.next_block_id;
+    for name in names {
+        let Some(func) = program.functions.get_mut(&name) else {
+            continue;
+        };
+        let mut shared = globals.clone();
+        shared.extend(func.shared_variables());
+
+        while natural_loops(func).iter().any(|found| hoist_from_loop(func, found, &shared, &mut next_block_id)) {}
+    }
+    program.next_block_id = next_block_id;
+}
+
+/// Move the invariant instructions of one loop to its preheader; returns
+/// whether any moved
+fn hoist_from_loop(func: &mut MirFunction, found: &Loop, shared: &HashSet<VarId>, next_block_id: &mut usize) -> bool {
+    let invariant = invariant_instructions(func, found, shared);
+    if invariant.is_empty() {
+        return false;
+    }
+    let Some(preheader) = preheader(func, found, next_block_id) else {
+        return false;
+    };
+
+    // Take the instructions out back to front, so earlier positions in the
+    // same block stay valid, then insert them in the order they were found
+    let mut positions = invariant.clone();
+    positions.sort_by(|a, b| b.cmp(a));
+    let mut moved: HashMap<(BlockId, usize), Instruction> = HashMap::new();
+    for (block, index) in positions {
+        if let Some(block) = func.blocks.iter_mut().find(|candidate| candidate.id == block) {
+            moved.insert((block.id, index), block.instructions.remove(index));
+        }
+    }
+    if let Some(preheader) = func.blocks.iter_mut().find(|block| block.id == preheader) {
+        for position in &invariant {
+            preheader.instructions.extend(moved.remove(position));
+        }
+    }
+    true
+}
+
+/// Positions of the instructions of a loop that can be hoisted, each after
+/// the invariant instructions it reads
+///
+/// An instruction is invariant when each operand is a constant, a variable
+/// the loop never assigns, or the result of another invariant instruction.
+/// It is only moved when:
+///
+/// - its target is assigned nowhere else in the loop and is not live into
+///   the header, so no read in the loop can see an older value;
+/// - its target is dead on every edge out of the loop, or its block
+///   dominates every block the loop is left from, so a loop that runs no
+///   iterations does not leave a different value behind;
+/// - a division or remainder, which may fail, is only moved from a block
+///   that dominates every exit.
+///
+/// Operations reading a global, a `writes` variable or either side of a
+/// `peak`, the variables in `shared`, are never moved, since they may
+/// change between iterations without an assignment in the loop.
+fn invariant_instructions(func: &MirFunction, found: &Loop, shared: &HashSet<VarId>) -> Vec<(BlockId, usize)> {
+    let idom = immediate_dominators(func);
+    let liveness = Liveness::compute(func);
+    let exits = found.exits(func);
+    let order: Vec<BlockId> = func.reverse_postorder().into_iter()
+        .filter(|id| found.body.contains(id))
+        .collect();
+
+    // How often the loop assigns each variable
+    let mut assignments: HashMap<VarId, usize> = HashMap::new();
+    for id in &order {
+        for instruction in func.block(*id).map(|block| block.instructions.as_slice()).unwrap_or_default() {
+            if let Some(target) = instruction.target() {
+                *assignments.entry(target).or_default() += 1;
+            }
+        }
+    }
+
+    let mut invariant = Vec::new();
+    let mut hoisted: HashSet<VarId> = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &id in &order {
+            let Some(block) = func.block(id) else {
+                continue;
+            };
+            let dominates_exits = !exits.is_empty() && exits.iter().all(|(from, _)| dominates(id, *from, &idom));
+
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                let (Some(target), true) = (instruction.target(), is_movable(instruction)) else {
+                    continue;
+                };
+                if hoisted.contains(&target) || shared.contains(&target) || assignments.get(&target) != Some(&1) {
+                    continue;
+                }
+
+                let operands_invariant = instruction.operands().into_iter().all(|operand| match operand.variable() {
+                    Some(var) => !shared.contains(&var) && (!assignments.contains_key(&var) || hoisted.contains(&var)),
+                    None => true,
+                });
+                let dead_after_loop = exits.iter()
+                    .all(|(from, to)| !liveness.live_across(func, *from, *to).contains(&target));
+                let may_fail = matches!(
+                    instruction,
+                    Instruction::BinaryOp { op: BinaryOperation::Divide | BinaryOperation::Remainder, .. }
+                );
+
+                if operands_invariant
+                    && !liveness.live_in(found.header).contains(&target)
+                    && (dominates_exits || (dead_after_loop && !may_fail))
+                {
+                    hoisted.insert(target);
+                    invariant.push((id, index));
+                    changed = true;
+                }
+            }
+        }
+    }
+    invariant
+}
+
+/// Whether an instruction only computes its target from its operands
+fn is_movable(instruction: &Instruction) -> bool {
+    matches!(instruction, Instruction::Assign { .. } | Instruction::BinaryOp { .. } | Instruction::Cast { .. })
+}
+
+/// The block that runs just before the loop is entered: the block that
+/// enters it when there is just one and it leads nowhere else, or else a
+/// new block placed in front of the header. Without one that can be made, because the header has phis for
+/// several outside predecessors, there is nowhere to hoist to.
+fn preheader(func: &mut MirFunction, found: &Loop, next_block_id: &mut usize) -> Option<BlockId> {
+    let outside: Vec<BlockId> = func.predecessors().remove(&found.header).unwrap_or_default().into_iter()
+        .filter(|pred| !found.body.contains(pred))
+        .collect();
+
+    if let [single] = outside[..] {
+        if func.block(single).is_some_and(|block| block.terminator.successors() == vec![found.header]) {
+            return Some(single);
+        }
+    }
+
+    let header_has_phis = func.block(found.header)
+        .is_some_and(|block| matches!(block.instructions.first(), Some(Instruction::Phi { .. })));
+    let is_entry = func.entry_block == found.header;
+    if (outside.is_empty() && !is_entry) || (header_has_phis && outside.len() > 1) {
+        return None;
+    }
+
+    let preheader = BlockId(*next_block_id);
+    *next_block_id += 1;
+    for block in func.blocks.iter_mut() {
+        if outside.contains(&block.id) {
+            redirect(&mut block.terminator, found.header, preheader);
+        }
+        if block.id == found.header {
+            for instruction in block.instructions.iter_mut() {
+                if let Instruction::Phi { sources, .. } = instruction {
+                    for (pred, _) in sources.iter_mut() {
+                        if outside.contains(pred) {
+                            *pred = preheader;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if is_entry {
+        func.entry_block = preheader;
+    }
+    func.blocks.push(BasicBlock {
+        id: preheader,
+        instructions: Vec::new(),
+        terminator: Terminator::Jump(found.header),
+    });
+    Some(preheader)
+}
+
+/// Send the edges of a terminator that lead to `from` to `to` instead
+fn redirect(terminator: &mut Terminator, from: BlockId, to: BlockId) {
+    match terminator {
+        Terminator::Jump(target) => {
+            if *target == from {
+                *target = to;
+            }
+        },
+        Terminator::Branch { true_block, false_block, .. } => {
+            for target in [true_block, false_block] {
+                if *target == from {
+                    *target = to;
+                }
+            }
+        },
+        Terminator::Return(_) => {},
+    }
+}
@@ -3,10 +3,44 @@
 //! This module provides the MIR implementation for the compiler.
 
 pub mod types;
+pub mod analysis;
 pub mod converter;
 pub mod pretty_print;
+pub mod diff;
+pub mod parser;
+pub mod cse;
+pub mod ssa;
+pub mod constprop;
+pub mod dse;
+pub mod copyprop;
+pub mod licm;
+pub mod peephole;
+pub mod inline;
+pub mod slots;
+pub mod barriers;
+pub mod interpreter;
+pub mod passes;
+pub mod stats;
 
 // Re-export key functions and types
-pub use types::{MirProgram, MirFunction, BasicBlock, Instruction, Operand};
+pub use types::{MirProgram, MirFunction, BasicBlock, Instruction, Intrinsic, Terminator, Operand, Storage, StringId, StringPool};
+pub use analysis::Liveness;
 pub use converter::convert_hir_to_mir;
-pub use pretty_print::pretty_print_program;
+pub use pretty_print::{pretty_print_program, to_dot};
+pub use diff::unified_diff;
+pub use parser::{parse_mir, MirParseError};
+pub use cse::eliminate_common_subexpressions;
+pub use ssa::construct_ssa;
+pub use constprop::propagate_constants;
+pub use dse::eliminate_dead_stores;
+pub use copyprop::propagate_copies;
+pub use licm::hoist_loop_invariants;
+pub use peephole::optimize_peepholes;
+pub use inline::inline_mir_functions;
+pub use slots::{coalesce_temporaries, temporary_lifetimes, Lifetime};
+pub use barriers::place_write_barriers;
+pub use interpreter::{Interpreter, Value};
+pub use interpreter::session::Session;
+pub use interpreter::debugger::{Breakpoint, Debugger, Position, Stop, Variable};
+pub use passes::{MirPass, MirPassManager, PassStats};
+pub use stats::{FunctionStats, MirStats};
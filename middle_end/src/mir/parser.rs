@@ -0,0 +1,579 @@
+//! Parser for the textual MIR format
+//!
+//! Reads the text `pretty_print_program` writes back into a `MirProgram`,
+//! so MIR-level tests and fuzzers can state their input directly instead of
+//! going through the front end.
+
+use crate::mir::pretty_print::integer_suffix;
+use crate::mir::types::*;
+use front_end::intern::Name;
+use front_end::types::{Permission, Type};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why MIR text could not be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirParseError {
+    /// The line the error was found on, counting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MirParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parse a program in the textual MIR format:
+///
+/// ```text
+/// var total: Int [0] heap writes
+///
+/// fn double(x: Int [1] reads) -> Int pure {
+///     var temp_2: Int [2]
+///
+///     block0:
+///         temp_2[2] = x[1] * 2
+///         return temp_2[2]
+/// }
+/// ```
+///
+/// Variables are named `name[id]` and the id is what identifies them; the
+/// name is only kept for printing. `var_N` refers to variable N without a
+/// name, as the printer writes globals used inside functions. Blocks run
+/// from the first one unless an `entry blockN` line says otherwise, and
+/// `//` starts a comment.
+pub fn parse_mir(source: &str) -> Result<MirProgram, MirParseError> {
+    let tokens = tokenize(source)?;
+    let mut parser = MirParser { tokens, position: 0, program: MirProgram::new() };
+    parser.parse_program()?;
+    Ok(parser.program)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Integer(i64, Type),
+    String(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Identifier(name) => write!(f, "'{}'", name),
+            Token::Integer(value, typ) => write!(f, "'{}{}'", value, integer_suffix(typ)),
+            Token::String(value) => write!(f, "{:?}", value),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+/// Symbols of the format, longest first so `->` is not read as `-`
+const SYMBOLS: [&str; 24] = [
+    "->", "==", "!=", "<=", ">=", "&&", "||",
+    "[", "]", "(", ")", "{", "}", ":", ",", "=", "?", "+", "-", "*", "/", "%", "<", ">",
+];
+
+/// Integer types a constant suffix can name
+const INTEGER_TYPES: [Type; 13] = [
+    Type::Int, Type::Int8, Type::Int16, Type::Int32, Type::Int64,
+    Type::UInt, Type::UInt8, Type::UInt16, Type::UInt32, Type::UInt64,
+    Type::Float, Type::Float32, Type::Float64,
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, MirParseError> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| MirParseError { line: number, message };
+        let mut rest = line;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with("//") {
+                break;
+            }
+
+            let negative_number = rest.starts_with('-') && rest[1..].starts_with(|c: char| c.is_ascii_digit());
+            if rest.starts_with(|c: char| c.is_ascii_digit()) || negative_number {
+                // Digits, then the suffix naming the type, as in `-5i8`
+                let digits_end = rest[1..].find(|c: char| !c.is_ascii_digit()).map_or(rest.len(), |end| end + 1);
+                let end = rest[digits_end..].find(|c: char| !c.is_ascii_alphanumeric()).map_or(rest.len(), |end| end + digits_end);
+                let (text, suffix) = (&rest[..digits_end], &rest[digits_end..end]);
+                let value = text.parse().map_err(|_| error(format!("Invalid integer '{}'", text)))?;
+                let typ = INTEGER_TYPES.iter()
+                    .find(|typ| integer_suffix(typ) == suffix)
+                    .ok_or_else(|| error(format!("Unknown integer suffix '{}'", suffix)))?;
+                tokens.push((Token::Integer(value, typ.clone()), number));
+                rest = &rest[end..];
+            } else if rest.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$') {
+                let end = rest.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '$' | '.'))).unwrap_or(rest.len());
+                tokens.push((Token::Identifier(rest[..end].to_string()), number));
+                rest = &rest[end..];
+            } else if let Some(body) = rest.strip_prefix('"') {
+                let (value, length) = unescape(body).ok_or_else(|| error("Unterminated string".to_string()))?;
+                tokens.push((Token::String(value), number));
+                rest = &body[length..];
+            } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                tokens.push((Token::Symbol(symbol), number));
+                rest = &rest[symbol.len()..];
+            } else {
+                return Err(error(format!("Unexpected character '{}'", rest.chars().next().unwrap_or_default())));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Read a string written with Rust escapes, up to its closing quote;
+/// returns the value and the length of the text read, quote included
+fn unescape(body: &str) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = body.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, index + 1)),
+            '\\' => value.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                'u' => {
+                    let start = chars.next().filter(|(_, c)| *c == '{')?.0 + 1;
+                    let end = chars.find(|(_, c)| *c == '}')?.0;
+                    char::from_u32(u32::from_str_radix(&body[start..end], 16).ok()?)?
+                },
+                other => other,
+            }),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+struct MirParser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    program: MirProgram,
+}
+
+impl MirParser {
+    fn parse_program(&mut self) -> Result<(), MirParseError> {
+        while let Some(token) = self.peek() {
+            if self.is_keyword("var") {
+                self.advance();
+                let var = self.parse_variable()?;
+                self.program.globals.insert(var.name.as_str().to_string(), var);
+            } else if self.is_keyword("fn") {
+                let func = self.parse_function()?;
+                if self.program.functions.contains_key(&func.name) {
+                    return Err(self.error(format!("Function '{}' is defined twice", func.name)));
+                }
+                self.program.functions.insert(func.name.clone(), func);
+            } else {
+                return Err(self.error(format!("Expected 'var' or 'fn', found {}", token)));
+            }
+        }
+
+        // New ids must not collide with any the text used
+        let mut max_var = self.program.globals.values().map(|var| var.id.0).max();
+        let mut max_block = None;
+        for func in self.program.functions.values() {
+            max_var = max_var.max(func.variables.keys().map(|id| id.0).max());
+            for block in &func.blocks {
+                max_block = max_block.max(Some(block.id.0));
+                for instruction in &block.instructions {
+                    max_var = max_var.max(instruction.target().map(|id| id.0));
+                    max_var = max_var.max(instruction.operands().into_iter().filter_map(Operand::variable).map(|id| id.0).max());
+                }
+            }
+        }
+        self.program.next_var_id = max_var.map_or(0, |id| id + 1);
+        self.program.next_block_id = max_block.map_or(0, |id| id + 1);
+        Ok(())
+    }
+
+    /// `fn name(params) [-> Type] [pure] [behavior] { vars [entry blockN] blocks }`
+    fn parse_function(&mut self) -> Result<MirFunction, MirParseError> {
+        self.expect_keyword("fn")?;
+        let name = self.parse_identifier()?;
+        let mut variables = HashMap::new();
+        let mut parameters = Vec::new();
+
+        self.expect(Token::Symbol("("))?;
+        while !self.eat(&Token::Symbol(")")) {
+            if !parameters.is_empty() {
+                self.expect(Token::Symbol(","))?;
+            }
+            let param = self.parse_variable()?;
+            parameters.push((param.id, param.typ.clone()));
+            variables.insert(param.id, param);
+        }
+
+        let return_type = if self.eat(&Token::Symbol("->")) { Some(self.parse_type()?) } else { None };
+        let is_pure = self.is_keyword("pure");
+        if is_pure {
+            self.advance();
+        }
+        let is_behavior = self.is_keyword("behavior");
+        if is_behavior {
+            self.advance();
+        }
+
+        self.expect(Token::Symbol("{"))?;
+        let mut entry_block = None;
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        while !self.eat(&Token::Symbol("}")) {
+            if self.is_keyword("var") {
+                self.advance();
+                let var = self.parse_variable()?;
+                variables.insert(var.id, var);
+            } else if self.is_keyword("entry") {
+                self.advance();
+                entry_block = Some(self.parse_block_id()?);
+            } else {
+                let block = self.parse_block()?;
+                if blocks.iter().any(|existing| existing.id == block.id) {
+                    return Err(self.error(format!("block{} is defined twice", block.id.0)));
+                }
+                blocks.push(block);
+            }
+        }
+
+        let Some(entry_block) = entry_block.or_else(|| blocks.first().map(|block| block.id)) else {
+            return Err(self.error(format!("Function '{}' has no blocks", name)));
+        };
+        Ok(MirFunction { name, parameters, return_type, blocks, entry_block, variables, is_pure, is_behavior, location: None })
+    }
+
+    /// `name: Type [id]`, then `heap` and permissions if any
+    fn parse_variable(&mut self) -> Result<MirVariable, MirParseError> {
+        let name = self.parse_identifier()?;
+        self.expect(Token::Symbol(":"))?;
+        let typ = self.parse_type()?;
+        let id = self.parse_var_index()?;
+
+        let mut storage = Storage::Stack;
+        let mut permissions = Vec::new();
+        while let Some(Token::Identifier(word)) = self.peek() {
+            match word.as_str() {
+                "heap" => storage = Storage::Heap,
+                "read" => permissions.push(Permission::Read),
+                "write" => permissions.push(Permission::Write),
+                "reads" => permissions.push(Permission::Reads),
+                "writes" => permissions.push(Permission::Writes),
+                _ => break,
+            }
+            self.advance();
+        }
+        Ok(MirVariable { id, name: Name::new(&name), typ, permissions, storage, location: None })
+    }
+
+    /// Types as the printer writes them, which is their `Debug` form
+    fn parse_type(&mut self) -> Result<Type, MirParseError> {
+        let Some(Token::Identifier(name)) = self.peek() else {
+            return Err(self.unexpected("a type"));
+        };
+        let typ = match name.as_str() {
+            "Int" => Type::Int,
+            "Int8" => Type::Int8,
+            "Int16" => Type::Int16,
+            "Int32" => Type::Int32,
+            "Int64" => Type::Int64,
+            "UInt" => Type::UInt,
+            "UInt8" => Type::UInt8,
+            "UInt16" => Type::UInt16,
+            "UInt32" => Type::UInt32,
+            "UInt64" => Type::UInt64,
+            "Float" => Type::Float,
+            "Float32" => Type::Float32,
+            "Float64" => Type::Float64,
+            "Bool" => Type::Bool,
+            "String" => Type::String,
+            "Unit" => Type::Unit,
+            "Param" => {
+                self.advance();
+                self.expect(Token::Symbol("("))?;
+                let Some(Token::String(param)) = self.peek().cloned() else {
+                    return Err(self.unexpected("the name of a type parameter"));
+                };
+                self.advance();
+                self.expect(Token::Symbol(")"))?;
+                return Ok(Type::Param(param));
+            },
+            _ => return Err(self.unexpected("a type")),
+        };
+        self.advance();
+        Ok(typ)
+    }
+
+    /// `blockN:` followed by instructions and a terminator
+    fn parse_block(&mut self) -> Result<BasicBlock, MirParseError> {
+        let id = self.parse_block_id()?;
+        self.expect(Token::Symbol(":"))?;
+
+        let mut instructions = Vec::new();
+        loop {
+            let terminator = if self.is_keyword("return") {
+                self.advance();
+                let ends_block = matches!(self.peek(), None | Some(Token::Symbol("}"))) || self.at_block_label();
+                Some(Terminator::Return(if ends_block { None } else { Some(self.parse_operand()?) }))
+            } else if self.is_keyword("jump") {
+                self.advance();
+                Some(Terminator::Jump(self.parse_block_id()?))
+            } else if self.is_keyword("branch") {
+                self.advance();
+                let condition = self.parse_operand()?;
+                self.expect(Token::Symbol("?"))?;
+                let true_block = self.parse_block_id()?;
+                self.expect(Token::Symbol(":"))?;
+                let false_block = self.parse_block_id()?;
+                Some(Terminator::Branch { condition, true_block, false_block })
+            } else {
+                None
+            };
+
+            match terminator {
+                Some(terminator) => return Ok(BasicBlock { id, instructions, terminator }),
+                None => instructions.push(self.parse_instruction()?),
+            }
+        }
+    }
+
+    fn parse_instruction(&mut self) -> Result<Instruction, MirParseError> {
+        if self.is_keyword("nop") {
+            self.advance();
+            return Ok(Instruction::Nop);
+        }
+        if self.is_keyword("atomic") {
+            self.advance();
+            let instruction = match self.peek() {
+                Some(Token::Identifier(word)) if word == "begin" => Instruction::AtomicBegin,
+                Some(Token::Identifier(word)) if word == "end" => Instruction::AtomicEnd,
+                _ => return Err(self.unexpected("'begin' or 'end'")),
+            };
+            self.advance();
+            return Ok(instruction);
+        }
+        if self.is_keyword("barrier") {
+            self.advance();
+            let variable = self.parse_variable_reference()?;
+            return Ok(Instruction::WriteBarrier { variable });
+        }
+        if self.is_keyword("call") || self.is_keyword("intrinsic") {
+            return self.parse_call(None);
+        }
+
+        let target = self.parse_variable_reference()?;
+        self.expect(Token::Symbol("="))?;
+        if self.is_keyword("call") || self.is_keyword("intrinsic") {
+            return self.parse_call(Some(target));
+        }
+        if self.is_keyword("phi") {
+            self.advance();
+            self.expect(Token::Symbol("("))?;
+            let mut sources = Vec::new();
+            while !self.eat(&Token::Symbol(")")) {
+                if !sources.is_empty() {
+                    self.expect(Token::Symbol(","))?;
+                }
+                let block = self.parse_block_id()?;
+                self.expect(Token::Symbol(":"))?;
+                sources.push((block, self.parse_operand()?));
+            }
+            return Ok(Instruction::Phi { target, sources });
+        }
+        if self.is_keyword("peak") {
+            self.advance();
+            let source = self.parse_variable_reference()?;
+            return Ok(Instruction::Peak { target, source });
+        }
+
+        let source = self.parse_operand()?;
+        if self.is_keyword("as") {
+            // The target's declaration already says what it is cast to
+            self.advance();
+            self.parse_type()?;
+            return Ok(Instruction::Cast { target, source });
+        }
+        let op = match self.peek() {
+            Some(Token::Symbol("+")) => BinaryOperation::Add,
+            Some(Token::Symbol("-")) => BinaryOperation::Subtract,
+            Some(Token::Symbol("*")) => BinaryOperation::Multiply,
+            Some(Token::Symbol("/")) => BinaryOperation::Divide,
+            Some(Token::Symbol("%")) => BinaryOperation::Remainder,
+            Some(Token::Symbol("==")) => BinaryOperation::Equal,
+            Some(Token::Symbol("!=")) => BinaryOperation::NotEqual,
+            Some(Token::Symbol("<")) => BinaryOperation::LessThan,
+            Some(Token::Symbol("<=")) => BinaryOperation::LessThanEqual,
+            Some(Token::Symbol(">")) => BinaryOperation::GreaterThan,
+            Some(Token::Symbol(">=")) => BinaryOperation::GreaterThanEqual,
+            Some(Token::Symbol("&&")) => BinaryOperation::And,
+            Some(Token::Symbol("||")) => BinaryOperation::Or,
+            _ => return Ok(Instruction::Assign { target, source }),
+        };
+        self.advance();
+        let right = self.parse_operand()?;
+        Ok(Instruction::BinaryOp { target, left: source, op, right })
+    }
+
+    /// `call name(arguments)` or `intrinsic name(arguments)`, after the
+    /// target if there is one
+    fn parse_call(&mut self, target: Option<VarId>) -> Result<Instruction, MirParseError> {
+        let is_intrinsic = self.is_keyword("intrinsic");
+        self.advance();
+        let intrinsic = match self.peek() {
+            Some(Token::Identifier(name)) if is_intrinsic => {
+                Some(Intrinsic::from_name(name).ok_or_else(|| self.unexpected("an intrinsic"))?)
+            },
+            _ => None,
+        };
+        let function = self.parse_identifier()?;
+        self.expect(Token::Symbol("("))?;
+        let mut arguments = Vec::new();
+        while !self.eat(&Token::Symbol(")")) {
+            if !arguments.is_empty() {
+                self.expect(Token::Symbol(","))?;
+            }
+            arguments.push(self.parse_operand()?);
+        }
+        Ok(match intrinsic {
+            Some(intrinsic) => Instruction::Intrinsic { target, intrinsic, arguments },
+            None => Instruction::Call { target, function, arguments },
+        })
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, MirParseError> {
+        let constant = match self.peek() {
+            Some(Token::Integer(value, typ)) => Constant::Integer(*value, typ.clone()),
+            Some(Token::String(value)) => {
+                let value = value.clone();
+                Constant::String(self.program.strings.intern(&value))
+            },
+            Some(Token::Identifier(word)) if (word == "true" || word == "false") && !self.at_index() => {
+                Constant::Boolean(word == "true")
+            },
+            _ => return Ok(Operand::Variable(self.parse_variable_reference()?)),
+        };
+        self.advance();
+        Ok(Operand::Constant(constant))
+    }
+
+    /// `name[id]`, or `var_N` for a variable printed without a name
+    fn parse_variable_reference(&mut self) -> Result<VarId, MirParseError> {
+        let Some(Token::Identifier(name)) = self.peek() else {
+            return Err(self.unexpected("a variable"));
+        };
+        if self.at_index() {
+            self.advance();
+            return self.parse_var_index();
+        }
+        let id = name.strip_prefix("var_").and_then(|id| id.parse().ok()).ok_or_else(|| self.unexpected("a variable"))?;
+        self.advance();
+        Ok(VarId(id))
+    }
+
+    /// `[id]` after a variable's name
+    fn parse_var_index(&mut self) -> Result<VarId, MirParseError> {
+        self.expect(Token::Symbol("["))?;
+        let id = match self.peek() {
+            Some(Token::Integer(id, Type::Int)) => usize::try_from(*id).ok(),
+            _ => None,
+        };
+        let id = id.ok_or_else(|| self.unexpected("a variable id"))?;
+        self.advance();
+        self.expect(Token::Symbol("]"))?;
+        Ok(VarId(id))
+    }
+
+    fn parse_block_id(&mut self) -> Result<BlockId, MirParseError> {
+        let id = match self.peek() {
+            Some(Token::Identifier(name)) => name.strip_prefix("block").and_then(|id| id.parse().ok()),
+            _ => None,
+        };
+        let id = id.ok_or_else(|| self.unexpected("a block"))?;
+        self.advance();
+        Ok(BlockId(id))
+    }
+
+    fn parse_identifier(&mut self) -> Result<String, MirParseError> {
+        match self.peek() {
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            },
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    /// Whether the next tokens are a block label, which ends the block
+    /// before it
+    fn at_block_label(&self) -> bool {
+        matches!(self.peek(), Some(Token::Identifier(name)) if name.starts_with("block"))
+            && matches!(self.tokens.get(self.position + 1), Some((Token::Symbol(":"), _)))
+    }
+
+    /// Whether the token after the next one opens a variable id
+    fn at_index(&self) -> bool {
+        matches!(self.tokens.get(self.position + 1), Some((Token::Symbol("["), _)))
+    }
+
+    /// Whether the next token is the given word used as a keyword: not a
+    /// variable of that name
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Identifier(word)) if word == keyword) && !self.at_index()
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), MirParseError> {
+        if self.is_keyword(keyword) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", keyword)))
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), MirParseError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&token.to_string()))
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek() == Some(token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> MirParseError {
+        match self.peek() {
+            Some(token) => self.error(format!("Expected {}, found {}", expected, token)),
+            None => self.error(format!("Expected {}, found the end of the input", expected)),
+        }
+    }
+
+    /// An error on the line of the next token, or of the last one at the
+    /// end of the input
+    fn error(&self, message: String) -> MirParseError {
+        let line = self.tokens.get(self.position)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line);
+        MirParseError { line, message }
+    }
+}
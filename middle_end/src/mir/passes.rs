@@ -0,0 +1,244 @@
+//! MIR optimization pass manager
+//!
+//! Transformations implement `MirPass` and register with a
+//! `MirPassManager`, which runs them over the whole program in
+//! registration order. Each run is measured, so the manager reports how
+//! many instructions every pass removed and how long it took.
+
+use crate::mir::constprop::propagate_constants;
+use crate::mir::copyprop::propagate_copies;
+use crate::mir::cse::eliminate_common_subexpressions;
+use crate::mir::diff::unified_diff;
+use crate::mir::dse::eliminate_dead_stores;
+use crate::mir::inline::inline_mir_functions;
+use crate::mir::licm::hoist_loop_invariants;
+use crate::mir::peephole::optimize_peepholes;
+use crate::mir::pretty_print::pretty_print_program;
+use crate::mir::slots::coalesce_temporaries;
+use crate::mir::ssa::construct_ssa;
+use crate::mir::types::MirProgram;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// A MIR transformation run by the `MirPassManager`
+pub trait MirPass {
+    /// Short name used when listing passes and on the command line
+    fn name(&self) -> &'static str;
+
+    /// Transform the program in place
+    fn run(&mut self, program: &mut MirProgram);
+}
+
+/// Inlines small functions at their call sites
+pub struct Inlining;
+
+impl MirPass for Inlining {
+    fn name(&self) -> &'static str {
+        "mir-inline"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        inline_mir_functions(program);
+    }
+}
+
+/// Rewrites small patterns lowering leaves behind, such as `x + 0`
+pub struct Peephole;
+
+impl MirPass for Peephole {
+    fn name(&self) -> &'static str {
+        "peephole"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        optimize_peepholes(program);
+    }
+}
+
+/// Puts every function into SSA form
+pub struct SsaConstruction;
+
+impl MirPass for SsaConstruction {
+    fn name(&self) -> &'static str {
+        "ssa"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        construct_ssa(program);
+    }
+}
+
+/// Replaces uses of constants and folds branches on them
+pub struct ConstantPropagation;
+
+impl MirPass for ConstantPropagation {
+    fn name(&self) -> &'static str {
+        "const-prop"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        propagate_constants(program);
+    }
+}
+
+/// Reads the original variable in place of a copy of it
+pub struct CopyPropagation;
+
+impl MirPass for CopyPropagation {
+    fn name(&self) -> &'static str {
+        "copy-prop"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        propagate_copies(program);
+    }
+}
+
+/// Moves computations that do not change inside a loop in front of it
+pub struct LoopInvariantCodeMotion;
+
+impl MirPass for LoopInvariantCodeMotion {
+    fn name(&self) -> &'static str {
+        "licm"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        hoist_loop_invariants(program);
+    }
+}
+
+/// Reuses repeated binary operations and pure calls
+pub struct CommonSubexpressionElimination;
+
+impl MirPass for CommonSubexpressionElimination {
+    fn name(&self) -> &'static str {
+        "cse"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        eliminate_common_subexpressions(program);
+    }
+}
+
+/// Removes assignments whose value is never read
+pub struct DeadStoreElimination;
+
+impl MirPass for DeadStoreElimination {
+    fn name(&self) -> &'static str {
+        "dse"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        eliminate_dead_stores(program);
+    }
+}
+
+/// Lets temporaries that are never in use at once share a variable
+pub struct StackSlots;
+
+impl MirPass for StackSlots {
+    fn name(&self) -> &'static str {
+        "slots"
+    }
+
+    fn run(&mut self, program: &mut MirProgram) {
+        coalesce_temporaries(program);
+    }
+}
+
+/// What one run of a pass did to the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassStats {
+    /// Name of the pass
+    pub name: &'static str,
+
+    /// Instructions in the program before the pass ran
+    pub instructions_before: usize,
+
+    /// Instructions in the program after the pass ran
+    pub instructions_after: usize,
+
+    /// Time the pass took
+    pub elapsed: Duration,
+
+    /// The change to the textual MIR, in unified diff format and empty if
+    /// the pass changed nothing; only recorded when asked for
+    pub diff: Option<String>,
+}
+
+impl PassStats {
+    /// Instructions the pass removed; negative if it added some
+    pub fn instructions_removed(&self) -> isize {
+        self.instructions_before as isize - self.instructions_after as isize
+    }
+}
+
+impl fmt::Display for PassStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {} instructions ({:+}) in {:.3}ms",
+            self.name,
+            self.instructions_before,
+            self.instructions_after,
+            -self.instructions_removed(),
+            self.elapsed.as_secs_f64() * 1000.0,
+        )
+    }
+}
+
+/// Runs registered MIR passes over a program, one after another
+#[derive(Default)]
+pub struct MirPassManager {
+    passes: Vec<Box<dyn MirPass>>,
+
+    /// Whether to print the program around every pass and diff the two
+    record_diffs: bool,
+}
+
+impl MirPassManager {
+    /// Create a pass manager with no passes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pass; passes run in registration order
+    pub fn register(&mut self, pass: Box<dyn MirPass>) {
+        self.passes.push(pass);
+    }
+
+    /// Record what every pass changes in the textual MIR, at the cost of
+    /// printing the whole program twice per pass
+    pub fn record_diffs(&mut self, record: bool) {
+        self.record_diffs = record;
+    }
+
+    /// Names of the registered passes, in order
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Run every pass over the program and return what each one did
+    pub fn run(&mut self, program: &mut MirProgram) -> Vec<PassStats> {
+        let record_diffs = self.record_diffs;
+        self.passes.iter_mut().map(|pass| {
+            let before = record_diffs.then(|| pretty_print_program(program));
+            let instructions_before = program.instruction_count();
+            let start = Instant::now();
+            pass.run(program);
+            let elapsed = start.elapsed();
+            PassStats {
+                name: pass.name(),
+                instructions_before,
+                instructions_after: program.instruction_count(),
+                elapsed,
+                diff: before.map(|before| unified_diff(
+                    &before,
+                    &pretty_print_program(program),
+                    &format!("before {}", pass.name()),
+                    &format!("after {}", pass.name()),
+                )),
+            }
+        }).collect()
+    }
+}
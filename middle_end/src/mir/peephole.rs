@@ -0,0 +1,110 @@
+//! Peephole optimization for MIR
+//!
+//! Rewrites small instruction patterns lowering tends to leave behind, one
+//! block at a time:
+//!
+//! - `x + 0`, `0 + x` and `x - 0` become `x` (not for floats, where
+//!   `-0.0 + 0.0` is `0.0`);
+//! - `x * 1`, `1 * x` and `x / 1` become `x`;
+//! - `0 - (0 - x)`, a double negation, becomes `x`;
+//! - after `x = t`, a load `t = x` back into the stored variable is
+//!   dropped, as is an assignment of a variable to itself.
+//!
+//! The last two only look back within the block, and forget a pattern as
+//! soon as one of its variables is reassigned. Globals, `writes` variables
+//! and variables a `peak` aliases never take part in them, since they may
+//! change between the two instructions without being assigned.
+
+use crate::mir::types::*;
+use front_end::types::Type;
+use std::collections::{HashMap, HashSet};
+
+/// Rewrite peephole patterns in every function
+pub fn optimize_peepholes(program: &mut MirProgram) {
+    let globals: HashSet<VarId> = program.globals.values().map(|var| var.id).collect();
+
+    for func in program.functions.values_mut() {
+        let mut shared = globals.clone();
+        shared.extend(func.shared_variables());
+
+        for block in func.blocks.iter_mut() {
+            optimize_block(block, &shared);
+        }
+    }
+}
+
+fn optimize_block(block: &mut BasicBlock, shared: &HashSet<VarId>) {
+    // Results of `0 - x` in the block, with the operand they negate
+    let mut negations: HashMap<VarId, Operand> = HashMap::new();
+    // Variables stored to in the block, with the variable stored
+    let mut stores: HashMap<VarId, VarId> = HashMap::new();
+
+    let mut kept = Vec::with_capacity(block.instructions.len());
+    for instruction in block.instructions.drain(..) {
+        let instruction = simplify(instruction, &negations);
+        if let Instruction::Assign { target, source: Operand::Variable(slot) } = &instruction {
+            if target == slot || stores.get(slot) == Some(target) {
+                continue;
+            }
+        }
+
+        if let Some(target) = instruction.target() {
+            negations.retain(|negated, original| *negated != target && original.variable() != Some(target));
+            stores.retain(|slot, value| *slot != target && *value != target);
+        }
+        let is_shared = |operand: &Operand| operand.variable().is_some_and(|var| shared.contains(&var));
+        match &instruction {
+            Instruction::BinaryOp { target, left, op: BinaryOperation::Subtract, right }
+                if is_zero(left) && !is_shared(right) && right.variable() != Some(*target) =>
+            {
+                negations.insert(*target, right.clone());
+            },
+            Instruction::Assign { target, source: Operand::Variable(value) }
+                if !shared.contains(target) && !shared.contains(value) =>
+            {
+                stores.insert(*target, *value);
+            },
+            _ => {},
+        }
+        kept.push(instruction);
+    }
+    block.instructions = kept;
+}
+
+/// An arithmetic instruction with an identity operand as a plain copy
+fn simplify(instruction: Instruction, negations: &HashMap<VarId, Operand>) -> Instruction {
+    let Instruction::BinaryOp { target, left, op, right } = &instruction else {
+        return instruction;
+    };
+    let source = match op {
+        BinaryOperation::Add if is_zero(right) && !is_float(right) => left,
+        BinaryOperation::Add if is_zero(left) && !is_float(left) => right,
+        BinaryOperation::Subtract if is_zero(right) && !is_float(right) => left,
+        BinaryOperation::Subtract if is_zero(left) => {
+            match right.variable().and_then(|negated| negations.get(&negated)) {
+                Some(original) => original,
+                None => return instruction,
+            }
+        },
+        BinaryOperation::Multiply if is_one(right) => left,
+        BinaryOperation::Multiply if is_one(left) => right,
+        BinaryOperation::Divide if is_one(right) => left,
+        _ => return instruction,
+    };
+    Instruction::Assign {
+        target: *target,
+        source: source.clone(),
+    }
+}
+
+fn is_zero(operand: &Operand) -> bool {
+    matches!(operand, Operand::Constant(Constant::Integer(0, _)))
+}
+
+fn is_one(operand: &Operand) -> bool {
+    matches!(operand, Operand::Constant(Constant::Integer(1, _)))
+}
+
+fn is_float(operand: &Operand) -> bool {
+    matches!(operand, Operand::Constant(Constant::Integer(_, Type::Float | Type::Float32 | Type::Float64)))
+}
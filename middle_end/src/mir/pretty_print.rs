@@ -1,8 +1,14 @@
 //! MIR pretty printer
 //!
-//! This module provides functionality for pretty-printing MIR for debugging.
+//! This module provides functionality for pretty-printing MIR for debugging,
+//! as text or as a Graphviz graph of each function's basic blocks.
+//!
+//! The text form is stable: globals and functions are printed in name order
+//! and locals in id order, and everything a `MirProgram` holds is spelled
+//! out, so `mir::parser::parse_mir` reads it back into the same program.
 
 use crate::mir::types::*;
+use front_end::types::Permission;
 use std::fmt::Write;
 
 /// Pretty-print a MIR program
@@ -12,15 +18,19 @@ pub fn pretty_print_program(program: &MirProgram) -> String {
     // Print global variables
     if !program.globals.is_empty() {
         writeln!(&mut output, "// Global Variables").unwrap();
-        for (name, var) in &program.globals {
-            writeln!(&mut output, "var {}: {:?} [{}]", name, var.typ, var.id.0).unwrap();
+        let mut globals: Vec<&MirVariable> = program.globals.values().collect();
+        globals.sort_by(|a, b| a.name.as_str().cmp(b.name.as_str()));
+        for var in globals {
+            writeln!(&mut output, "var {}", pretty_print_variable(var)).unwrap();
         }
         writeln!(&mut output).unwrap();
     }
     
     // Print functions
-    for (_, func) in &program.functions {
-        pretty_print_function(func, &mut output);
+    let mut functions: Vec<&MirFunction> = program.functions.values().collect();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    for func in functions {
+        pretty_print_function(func, &program.strings, &mut output);
         writeln!(&mut output).unwrap();
     }
     
@@ -28,7 +38,7 @@ pub fn pretty_print_program(program: &MirProgram) -> String {
 }
 
 /// Pretty-print a MIR function
-pub fn pretty_print_function(func: &MirFunction, output: &mut String) {
+pub fn pretty_print_function(func: &MirFunction, strings: &StringPool, output: &mut String) {
     // Print function signature
     write!(output, "fn {}(", func.name).unwrap();
     
@@ -38,64 +48,157 @@ pub fn pretty_print_function(func: &MirFunction, output: &mut String) {
             write!(output, ", ").unwrap();
         }
         
-        let var_name = func.variables.get(var_id)
-            .map(|v| v.name.as_str())
-            .unwrap_or("unknown");
-            
-        write!(output, "{}: {:?} [{}]", var_name, typ, var_id.0).unwrap();
+        match func.variables.get(var_id) {
+            Some(var) => write!(output, "{}", pretty_print_variable(var)).unwrap(),
+            None => write!(output, "unknown: {:?} [{}]", typ, var_id.0).unwrap(),
+        }
     }
+    write!(output, ")").unwrap();
     
     // Print return type
     if let Some(ret_type) = &func.return_type {
-        writeln!(output, ") -> {:?} {{", ret_type).unwrap();
-    } else {
-        writeln!(output, ") {{").unwrap();
+        write!(output, " -> {:?}", ret_type).unwrap();
     }
+    if func.is_pure {
+        write!(output, " pure").unwrap();
+    }
+    if func.is_behavior {
+        write!(output, " behavior").unwrap();
+    }
+    writeln!(output, " {{").unwrap();
     
     // Print local variables that aren't parameters
     let param_ids: std::collections::HashSet<_> = func.parameters.iter()
         .map(|(id, _)| *id)
         .collect();
         
-    let locals: Vec<_> = func.variables.values()
+    let mut locals: Vec<_> = func.variables.values()
         .filter(|var| !param_ids.contains(&var.id))
         .collect();
+    locals.sort_by_key(|var| var.id.0);
         
     if !locals.is_empty() {
         writeln!(output, "    // Local variables").unwrap();
         for var in locals {
-            writeln!(output, "    var {}: {:?} [{}]", var.name, var.typ, var.id.0).unwrap();
+            writeln!(output, "    var {}", pretty_print_variable(var)).unwrap();
         }
         writeln!(output).unwrap();
     }
     
+    // Blocks are entered at the first one unless told otherwise
+    if func.blocks.first().is_some_and(|block| block.id != func.entry_block) {
+        writeln!(output, "    entry block{}", func.entry_block.0).unwrap();
+        writeln!(output).unwrap();
+    }
+    
     // Print blocks
     for block in &func.blocks {
-        pretty_print_block(block, output, func);
+        pretty_print_block(block, output, func, strings);
     }
     
     writeln!(output, "}}").unwrap();
 }
 
+/// Render the control flow graph of every function in Graphviz DOT
+/// format, one cluster per function, for viewing with `dot -Tsvg`
+pub fn to_dot(program: &MirProgram) -> String {
+    let mut output = String::new();
+    writeln!(output, "digraph mir {{").unwrap();
+    writeln!(output, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+    let strings = &program.strings;
+    
+    let mut functions: Vec<&MirFunction> = program.functions.values().collect();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    for func in functions {
+        writeln!(output, "    subgraph \"cluster_{}\" {{", escape_dot(&func.name)).unwrap();
+        writeln!(output, "        label=\"fn {}\";", escape_dot(&func.name)).unwrap();
+        
+        for block in &func.blocks {
+            // Left-justified lines, the label first and the terminator last
+            let mut label = format!("block{}:\\l", block.id.0);
+            for instr in &block.instructions {
+                label.push_str(&escape_dot(&pretty_print_instruction(instr, func, strings)));
+                label.push_str("\\l");
+            }
+            label.push_str(&escape_dot(&pretty_print_terminator(&block.terminator, func, strings)));
+            label.push_str("\\l");
+            
+            let style = if block.id == func.entry_block { ", penwidth=2" } else { "" };
+            writeln!(output, "        block{} [label=\"{}\"{}];", block.id.0, label, style).unwrap();
+        }
+        
+        for block in &func.blocks {
+            match &block.terminator {
+                Terminator::Jump(target) => {
+                    writeln!(output, "        block{} -> block{};", block.id.0, target.0).unwrap();
+                },
+                Terminator::Branch { true_block, false_block, .. } => {
+                    writeln!(output, "        block{} -> block{} [label=\"true\"];", block.id.0, true_block.0).unwrap();
+                    writeln!(output, "        block{} -> block{} [label=\"false\"];", block.id.0, false_block.0).unwrap();
+                },
+                Terminator::Return(_) => {},
+            }
+        }
+        writeln!(output, "    }}").unwrap();
+    }
+    
+    writeln!(output, "}}").unwrap();
+    output
+}
+
+/// Escape text for a quoted DOT string
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Pretty-print a variable declaration: name, type and id, then where it
+/// lives and its permissions, as in `x: Int [3] heap reads`
+fn pretty_print_variable(var: &MirVariable) -> String {
+    let mut result = format!("{}: {:?} [{}]", var.name, var.typ, var.id.0);
+    if var.storage == Storage::Heap {
+        result.push_str(" heap");
+    }
+    for permission in &var.permissions {
+        let name = match permission {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Reads => "reads",
+            Permission::Writes => "writes",
+        };
+        write!(result, " {}", name).unwrap();
+    }
+    result
+}
+
 /// Pretty-print a basic block
-fn pretty_print_block(block: &BasicBlock, output: &mut String, func: &MirFunction) {
-    // Print block header
-    writeln!(output, "    block {}:", block.id.0).unwrap();
+fn pretty_print_block(block: &BasicBlock, output: &mut String, func: &MirFunction, strings: &StringPool) {
+    // Print block label, as jumps and branches name it
+    writeln!(output, "    block{}:", block.id.0).unwrap();
     
     // Print instructions
     for instr in &block.instructions {
-        writeln!(output, "        {}", pretty_print_instruction(instr, func)).unwrap();
+        writeln!(output, "        {}", pretty_print_instruction(instr, func, strings)).unwrap();
     }
+    writeln!(output, "        {}", pretty_print_terminator(&block.terminator, func, strings)).unwrap();
     
     writeln!(output).unwrap();
 }
 
 /// Pretty-print an instruction
-fn pretty_print_instruction(instr: &Instruction, func: &MirFunction) -> String {
+pub(crate) fn pretty_print_instruction(instr: &Instruction, func: &MirFunction, strings: &StringPool) -> String {
     match instr {
         Instruction::Assign { target, source } => {
             let target_name = get_var_name(*target, func);
-            format!("{} = {}", target_name, pretty_print_operand(source, func))
+            format!("{} = {}", target_name, pretty_print_operand(source, func, strings))
+        },
+        
+        Instruction::Peak { target, source } => {
+            format!("{} = peak {}", get_var_name(*target, func), get_var_name(*source, func))
+        },
+        
+        Instruction::Cast { target, source } => {
+            let target_type = func.variables.get(target).map(|var| format!("{:?}", var.typ)).unwrap_or_default();
+            format!("{} = {} as {}", get_var_name(*target, func), pretty_print_operand(source, func, strings), target_type)
         },
         
         Instruction::BinaryOp { target, left, op, right } => {
@@ -119,9 +222,9 @@ fn pretty_print_instruction(instr: &Instruction, func: &MirFunction) -> String {
             format!(
                 "{} = {} {} {}", 
                 target_name,
-                pretty_print_operand(left, func),
+                pretty_print_operand(left, func, strings),
                 op_str,
-                pretty_print_operand(right, func)
+                pretty_print_operand(right, func, strings)
             )
         },
         
@@ -142,42 +245,75 @@ fn pretty_print_instruction(instr: &Instruction, func: &MirFunction) -> String {
                 if i > 0 {
                     write!(&mut result, ", ").unwrap();
                 }
-                write!(&mut result, "{}", pretty_print_operand(arg, func)).unwrap();
+                write!(&mut result, "{}", pretty_print_operand(arg, func, strings)).unwrap();
             }
             
             write!(&mut result, ")").unwrap();
             result
         },
         
-        Instruction::Return(operand) => {
+        Instruction::Intrinsic { target, intrinsic, arguments } => {
+            let arguments: Vec<String> = arguments.iter().map(|arg| pretty_print_operand(arg, func, strings)).collect();
+            let call = format!("intrinsic {}({})", intrinsic.name(), arguments.join(", "));
+            match target {
+                Some(target) => format!("{} = {}", get_var_name(*target, func), call),
+                None => call,
+            }
+        },
+        
+        Instruction::Phi { target, sources } => {
+            let sources: Vec<String> = sources.iter()
+                .map(|(block, operand)| format!("block{}: {}", block.0, pretty_print_operand(operand, func, strings)))
+                .collect();
+            format!("{} = phi({})", get_var_name(*target, func), sources.join(", "))
+        },
+        
+        Instruction::AtomicBegin => {
+            "atomic begin".to_string()
+        },
+        
+        Instruction::AtomicEnd => {
+            "atomic end".to_string()
+        },
+        
+        Instruction::WriteBarrier { variable } => {
+            format!("barrier {}", get_var_name(*variable, func))
+        },
+        
+        Instruction::Nop => {
+            "nop".to_string()
+        },
+    }
+}
+
+/// Pretty-print the terminator of a block
+pub(crate) fn pretty_print_terminator(terminator: &Terminator, func: &MirFunction, strings: &StringPool) -> String {
+    match terminator {
+        Terminator::Return(operand) => {
             if let Some(op) = operand {
-                format!("return {}", pretty_print_operand(op, func))
+                format!("return {}", pretty_print_operand(op, func, strings))
             } else {
                 "return".to_string()
             }
         },
         
-        Instruction::Jump(block_id) => {
+        Terminator::Jump(block_id) => {
             format!("jump block{}", block_id.0)
         },
         
-        Instruction::Branch { condition, true_block, false_block } => {
+        Terminator::Branch { condition, true_block, false_block } => {
             format!(
                 "branch {} ? block{} : block{}", 
-                pretty_print_operand(condition, func),
+                pretty_print_operand(condition, func, strings),
                 true_block.0,
                 false_block.0
             )
         },
-        
-        Instruction::Nop => {
-            "nop".to_string()
-        },
     }
 }
 
 /// Pretty-print an operand
-fn pretty_print_operand(operand: &Operand, func: &MirFunction) -> String {
+fn pretty_print_operand(operand: &Operand, func: &MirFunction, strings: &StringPool) -> String {
     match operand {
         Operand::Variable(var_id) => {
             get_var_name(*var_id, func)
@@ -185,16 +321,37 @@ fn pretty_print_operand(operand: &Operand, func: &MirFunction) -> String {
         
         Operand::Constant(constant) => {
             match constant {
-                Constant::Integer(value) => value.to_string(),
+                Constant::Integer(value, typ) => format!("{}{}", value, integer_suffix(typ)),
                 Constant::Boolean(value) => value.to_string(),
-                Constant::String(value) => format!("\"{}\"", value),
+                Constant::String(id) => format!("{:?}", strings.get(*id)),
             }
         },
     }
 }
 
+/// The suffix marking the type of an integer constant, as in `5u8`. `Int`
+/// constants have none.
+pub(crate) fn integer_suffix(typ: &front_end::types::Type) -> &'static str {
+    use front_end::types::Type;
+    match typ {
+        Type::Int8 => "i8",
+        Type::Int16 => "i16",
+        Type::Int32 => "i32",
+        Type::Int64 => "i64",
+        Type::UInt => "u",
+        Type::UInt8 => "u8",
+        Type::UInt16 => "u16",
+        Type::UInt32 => "u32",
+        Type::UInt64 => "u64",
+        Type::Float => "f",
+        Type::Float32 => "f32",
+        Type::Float64 => "f64",
+        _ => "",
+    }
+}
+
 /// Get the name of a variable
-fn get_var_name(var_id: VarId, func: &MirFunction) -> String {
+pub(crate) fn get_var_name(var_id: VarId, func: &MirFunction) -> String {
     if let Some(var) = func.variables.get(&var_id) {
         format!("{}[{}]", var.name, var_id.0)
     } else {
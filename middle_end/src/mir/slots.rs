@@ -0,0 +1,166 @@
+//! Stack slot assignment for MIR temporaries
+//!
+//! Lowering gives every intermediate result its own temporary, so a long
+//! function ends up with many variables that are each only in use for a
+//! few instructions. `temporary_lifetimes` lays a function's blocks out in
+//! reverse postorder and finds, for every temporary, the first and last
+//! position at which it is assigned or live. `coalesce_temporaries` then
+//! lets temporaries of the same type whose lifetimes do not overlap share
+//! one variable, a slot, by a linear scan in order of where the lifetimes
+//! start. Fewer variables means a smaller interpreter frame and less for a
+//! register allocator to do later.
+//!
+//! A lifetime covers everything between its ends, including the parts of
+//! loops it is not live in, which keeps the overlap test a comparison of
+//! numbers at the cost of missing some sharing. Within an instruction the
+//! operands are read before the target is written, so `t2 = t1 + 1` can
+//! reuse the slot of `t1` when that is its last use. Variables a phi
+//! assigns or reads are left alone, since the phis of a block assign in
+//! parallel, as are named variables, parameters, heap values and both
+//! sides of a `peak`. Sharing
+//! a slot breaks SSA form, so the pass runs after everything that needs it.
+
+use crate::mir::analysis::Liveness;
+use crate::mir::types::*;
+use front_end::types::Type;
+use std::collections::{HashMap, HashSet};
+
+/// The positions between which a temporary is in use. Each block has two
+/// positions per instruction, the first before it runs and the second
+/// once it has assigned its target, and one before its terminator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lifetime {
+    /// Where the temporary is first assigned or live
+    pub first: usize,
+
+    /// Where the temporary is last live, or assigned if it is never read
+    pub last: usize,
+}
+
+impl Lifetime {
+    /// Whether the temporary may hold a value the other still needs
+    pub fn overlaps(&self, other: &Lifetime) -> bool {
+        self.first <= other.last && other.first <= self.last
+    }
+
+    /// Widen the lifetime to include a position
+    fn include(&mut self, position: usize) {
+        self.first = self.first.min(position);
+        self.last = self.last.max(position);
+    }
+}
+
+/// The lifetime of every temporary of a function that may share a slot
+pub fn temporary_lifetimes(func: &MirFunction) -> HashMap<VarId, Lifetime> {
+    let temporaries = temporaries(func);
+    let liveness = Liveness::compute(func);
+
+    // Reachable blocks in reverse postorder, then any others
+    let mut order = func.reverse_postorder();
+    let reachable: HashSet<BlockId> = order.iter().copied().collect();
+    order.extend(func.blocks.iter().map(|block| block.id).filter(|id| !reachable.contains(id)));
+
+    let mut lifetimes: HashMap<VarId, Lifetime> = HashMap::new();
+    let mut include = |var: VarId, position: usize| {
+        if temporaries.contains(&var) {
+            lifetimes.entry(var)
+                .or_insert(Lifetime { first: position, last: position })
+                .include(position);
+        }
+    };
+
+    let mut start = 0;
+    for id in order {
+        let Some(block) = func.block(id) else {
+            continue;
+        };
+        for (index, live) in liveness.live_before(block).into_iter().enumerate() {
+            for var in live {
+                include(var, start + 2 * index);
+            }
+        }
+        for (index, instruction) in block.instructions.iter().enumerate() {
+            if let Some(target) = instruction.target() {
+                include(target, start + 2 * index + 1);
+            }
+        }
+        start += 2 * block.instructions.len() + 1;
+    }
+    lifetimes
+}
+
+/// Let temporaries whose lifetimes do not overlap share a variable in
+/// every function
+pub fn coalesce_temporaries(program: &mut MirProgram) {
+    for func in program.functions.values_mut() {
+        coalesce_function(func);
+    }
+}
+
+fn coalesce_function(func: &mut MirFunction) {
+    let lifetimes = temporary_lifetimes(func);
+    let mut ordered: Vec<(VarId, Lifetime)> = lifetimes.into_iter().collect();
+    ordered.sort_by_key(|(var, lifetime)| (lifetime.first, var.0));
+
+    // Each slot is named after the first temporary placed in it and is
+    // free again after the last position of the latest one
+    let mut slots: Vec<(VarId, Type, usize)> = Vec::new();
+    let mut renames: HashMap<VarId, VarId> = HashMap::new();
+    for (var, lifetime) in ordered {
+        let typ = &func.variables[&var].typ;
+        match slots.iter_mut().find(|(_, slot_type, end)| slot_type == typ && *end < lifetime.first) {
+            Some((slot, _, end)) => {
+                renames.insert(var, *slot);
+                *end = lifetime.last;
+            },
+            None => slots.push((var, typ.clone(), lifetime.last)),
+        }
+    }
+    if renames.is_empty() {
+        return;
+    }
+
+    let rename = |id: &mut VarId| {
+        if let Some(slot) = renames.get(id) {
+            *id = *slot;
+        }
+    };
+    for block in func.blocks.iter_mut() {
+        for instruction in block.instructions.iter_mut() {
+            if let Some(target) = instruction.target_mut() {
+                rename(target);
+            }
+            for operand in instruction.operands_mut() {
+                if let Operand::Variable(id) = operand {
+                    rename(id);
+                }
+            }
+        }
+        if let Some(Operand::Variable(id)) = block.terminator.operand_mut() {
+            rename(id);
+        }
+        // A copy between two temporaries that now share a slot
+        block.instructions.retain(|instruction| !matches!(
+            instruction,
+            Instruction::Assign { target, source: Operand::Variable(source) } if target == source
+        ));
+    }
+    func.variables.retain(|id, _| !renames.contains_key(id));
+}
+
+/// Variables of a function that may share a slot: stack temporaries that
+/// are not parameters, take no part in a phi and are not shared
+fn temporaries(func: &MirFunction) -> HashSet<VarId> {
+    let mut excluded: HashSet<VarId> = func.parameters.iter().map(|(id, _)| *id).collect();
+    excluded.extend(func.shared_variables());
+    for instruction in func.blocks.iter().flat_map(|block| &block.instructions) {
+        if let Instruction::Phi { target, sources } = instruction {
+            excluded.insert(*target);
+            excluded.extend(sources.iter().filter_map(|(_, source)| source.variable()));
+        }
+    }
+    func.variables.values()
+        .filter(|var| var.permissions.is_empty() && var.storage == Storage::Stack && !excluded.contains(&var.id))
+        .map(|var| var.id)
+        .collect()
+}
@@ -0,0 +1,295 @@
+//! SSA construction
+//!
+//! Rewrites every function so each variable is assigned exactly once. An
+//! assignment to a local creates a new version of it, each use refers to
+//! the version that reaches it, and where versions from different paths
+//! meet, a `Phi` at the start of the block picks the one for the edge that
+//! was taken. Phis are placed at the iterated dominance frontier of each
+//! variable's assignments (Cytron et al.), with dominators computed as in
+//! Cooper, Harvey and Kennedy's "A Simple, Fast Dominance Algorithm". A
+//! phi goes only where its variable is live on entry (pruned SSA), so no
+//! phi merges a version that is never read.
+//!
+//! Versions are new variables with the name, type, permissions and storage
+//! of the original, so they print as `x[7]`. Parameters, and locals read
+//! before any assignment, keep their original ID as the value they have on
+//! entry. Globals and `writes` variables stay as they are: other behaviors
+//! may change them between two reads, so they remain named storage. So do
+//! both sides of a `peak`, which must keep naming the same storage. Blocks
+//! unreachable from the entry are dropped first.
+
+use crate::mir::analysis::Liveness;
+use crate::mir::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// Put every function of the program into SSA form
+pub fn construct_ssa(program: &mut MirProgram) {
+    let mut names: Vec<String> = program.functions.keys().cloned().collect();
+    names.sort();
+
+    let mut next_var_id = program.next_var_id;
+    for name in names {
+        if let Some(func) = program.functions.get_mut(&name) {
+            SsaBuilder::new(func, &mut next_var_id).run();
+        }
+    }
+    program.next_var_id = next_var_id;
+}
+
+/// The immediate dominator of every block reachable from the entry. The
+/// entry block is its own immediate dominator.
+pub fn immediate_dominators(func: &MirFunction) -> HashMap<BlockId, BlockId> {
+    let order = func.reverse_postorder();
+    let index: HashMap<BlockId, usize> = order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+    let predecessors = func.predecessors();
+
+    let mut idom = HashMap::new();
+    idom.insert(func.entry_block, func.entry_block);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in order.iter().skip(1) {
+            // Only predecessors already processed take part
+            let new_idom = predecessors[&block].iter()
+                .filter(|pred| idom.contains_key(*pred))
+                .copied()
+                .reduce(|a, b| intersect(a, b, &idom, &index));
+            if let Some(new_idom) = new_idom {
+                if idom.insert(block, new_idom) != Some(new_idom) {
+                    changed = true;
+                }
+            }
+        }
+    }
+    idom
+}
+
+/// The closest common dominator of two blocks
+fn intersect(mut a: BlockId, mut b: BlockId, idom: &HashMap<BlockId, BlockId>, index: &HashMap<BlockId, usize>) -> BlockId {
+    while a != b {
+        while index[&a] > index[&b] {
+            a = idom[&a];
+        }
+        while index[&b] > index[&a] {
+            b = idom[&b];
+        }
+    }
+    a
+}
+
+/// The blocks where each block's dominance ends
+fn dominance_frontiers(func: &MirFunction, idom: &HashMap<BlockId, BlockId>) -> HashMap<BlockId, HashSet<BlockId>> {
+    let mut frontiers: HashMap<BlockId, HashSet<BlockId>> = HashMap::new();
+    for (block, predecessors) in func.predecessors() {
+        if predecessors.len() < 2 {
+            continue;
+        }
+        for pred in predecessors {
+            let mut runner = pred;
+            while runner != idom[&block] {
+                frontiers.entry(runner).or_default().insert(block);
+                runner = idom[&runner];
+            }
+        }
+    }
+    frontiers
+}
+
+/// The version of `var` reaching the current point
+fn current(versions: &HashMap<VarId, Vec<VarId>>, var: VarId) -> VarId {
+    versions.get(&var).and_then(|stack| stack.last()).copied().unwrap_or(var)
+}
+
+struct SsaBuilder<'a> {
+    func: &'a mut MirFunction,
+    next_var_id: &'a mut usize,
+
+    /// Variables that are renamed
+    renamed: HashSet<VarId>,
+
+    /// The variable each phi was placed for, by block and position
+    phi_vars: HashMap<(BlockId, usize), VarId>,
+
+    /// Version of each variable reaching the current point, innermost
+    /// definition last
+    versions: HashMap<VarId, Vec<VarId>>,
+}
+
+impl<'a> SsaBuilder<'a> {
+    fn new(func: &'a mut MirFunction, next_var_id: &'a mut usize) -> Self {
+        let shared = func.shared_variables();
+        let renamed = func.variables.values()
+            .filter(|var| !shared.contains(&var.id))
+            .map(|var| var.id)
+            .collect();
+        Self {
+            func,
+            next_var_id,
+            renamed,
+            phi_vars: HashMap::new(),
+            versions: HashMap::new(),
+        }
+    }
+
+    fn run(mut self) {
+        self.func.remove_unreachable_blocks();
+
+        let idom = immediate_dominators(self.func);
+        self.insert_phis(&idom);
+
+        let mut children: HashMap<BlockId, Vec<BlockId>> = HashMap::new();
+        for block in &self.func.blocks {
+            if block.id != self.func.entry_block {
+                children.entry(idom[&block.id]).or_default().push(block.id);
+            }
+        }
+        let index: HashMap<BlockId, usize> = self.func.blocks.iter()
+            .enumerate()
+            .map(|(i, block)| (block.id, i))
+            .collect();
+        self.rename(self.func.entry_block, &children, &index);
+        self.remove_unused_variables();
+    }
+
+    /// Place an empty phi for each renamed variable wherever two of its
+    /// definitions may meet and the variable is still to be read
+    fn insert_phis(&mut self, idom: &HashMap<BlockId, BlockId>) {
+        let frontiers = dominance_frontiers(self.func, idom);
+        let liveness = Liveness::compute(self.func);
+
+        let mut definitions: HashMap<VarId, Vec<BlockId>> = HashMap::new();
+        for block in &self.func.blocks {
+            for target in block.instructions.iter().filter_map(Instruction::target) {
+                if self.renamed.contains(&target) {
+                    definitions.entry(target).or_default().push(block.id);
+                }
+            }
+        }
+
+        // Sorted so versions are numbered the same on every run
+        let mut variables: Vec<VarId> = definitions.keys().copied().collect();
+        variables.sort_by_key(|var| var.0);
+        let mut phis: HashMap<BlockId, Vec<VarId>> = HashMap::new();
+        for var in variables {
+            let mut worklist = definitions[&var].clone();
+            let mut placed = HashSet::new();
+            while let Some(block) = worklist.pop() {
+                for &frontier in frontiers.get(&block).into_iter().flatten() {
+                    // Where the variable is dead, every path on assigns it
+                    // again before reading it, so no merge is needed
+                    if liveness.live_in(frontier).contains(&var) && placed.insert(frontier) {
+                        phis.entry(frontier).or_default().push(var);
+                        worklist.push(frontier);
+                    }
+                }
+            }
+        }
+
+        for block in self.func.blocks.iter_mut() {
+            let Some(vars) = phis.remove(&block.id) else {
+                continue;
+            };
+            for (position, var) in vars.iter().enumerate() {
+                self.phi_vars.insert((block.id, position), *var);
+            }
+            let phis = vars.into_iter().map(|var| Instruction::Phi { target: var, sources: Vec::new() });
+            block.instructions.splice(0..0, phis);
+        }
+    }
+
+    fn rename_operand(&self, operand: &mut Operand) {
+        if let Operand::Variable(var) = operand {
+            *var = current(&self.versions, *var);
+        }
+    }
+
+    /// A new version of `var`
+    fn define(&mut self, var: VarId) -> VarId {
+        let version = VarId(*self.next_var_id);
+        *self.next_var_id += 1;
+
+        let mut variable = self.func.variables[&var].clone();
+        variable.id = version;
+        self.func.variables.insert(version, variable);
+        self.versions.entry(var).or_default().push(version);
+        version
+    }
+
+    /// Rename the uses and definitions in a block and the blocks it
+    /// dominates, filling in the phi sources of its successors
+    fn rename(&mut self, id: BlockId, children: &HashMap<BlockId, Vec<BlockId>>, index: &HashMap<BlockId, usize>) {
+        let mut defined = Vec::new();
+        let mut instructions = std::mem::take(&mut self.func.blocks[index[&id]].instructions);
+        for instruction in instructions.iter_mut() {
+            for operand in instruction.operands_mut() {
+                self.rename_operand(operand);
+            }
+            if let Some(target) = instruction.target_mut() {
+                if self.renamed.contains(target) {
+                    defined.push(*target);
+                    *target = self.define(*target);
+                }
+            }
+        }
+        let mut terminator = self.func.blocks[index[&id]].terminator.clone();
+        if let Some(operand) = terminator.operand_mut() {
+            self.rename_operand(operand);
+        }
+        let block = &mut self.func.blocks[index[&id]];
+        block.instructions = instructions;
+        block.terminator = terminator;
+
+        for successor in self.func.blocks[index[&id]].terminator.successors() {
+            let successor = &mut self.func.blocks[index[&successor]];
+            for (position, instruction) in successor.instructions.iter_mut().enumerate() {
+                let Instruction::Phi { sources, .. } = instruction else {
+                    break;
+                };
+                let var = self.phi_vars[&(successor.id, position)];
+                let version = current(&self.versions, var);
+                sources.push((id, Operand::Variable(version)));
+            }
+        }
+
+        for &child in children.get(&id).into_iter().flatten() {
+            self.rename(child, children, index);
+        }
+
+        for var in defined {
+            if let Some(stack) = self.versions.get_mut(&var) {
+                stack.pop();
+            }
+        }
+    }
+
+    /// Drop variables no instruction refers to any more, such as locals
+    /// whose every assignment now defines a version
+    fn remove_unused_variables(&mut self) {
+        let mut used: HashSet<VarId> = self.func.parameters.iter().map(|(id, _)| *id).collect();
+        for block in self.func.blocks.iter_mut() {
+            for instruction in block.instructions.iter_mut() {
+                if let Some(target) = instruction.target_mut() {
+                    used.insert(*target);
+                }
+                if let Instruction::Phi { sources, .. } = instruction {
+                    used.extend(sources.iter().filter_map(|(_, operand)| match operand {
+                        Operand::Variable(id) => Some(*id),
+                        Operand::Constant(_) => None,
+                    }));
+                }
+                for operand in instruction.operands_mut() {
+                    if let Operand::Variable(id) = operand {
+                        used.insert(*id);
+                    }
+                }
+            }
+            if let Some(Operand::Variable(id)) = block.terminator.operand_mut() {
+                used.insert(*id);
+            }
+        }
+
+        // Variables that are never renamed stay, even if unused here
+        let renamed = &self.renamed;
+        self.func.variables.retain(|id, _| used.contains(id) || !renamed.contains(id));
+    }
+}
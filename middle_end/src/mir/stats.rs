@@ -0,0 +1,120 @@
+//! Size statistics for MIR programs
+//!
+//! `MirStats` counts what a program is made of: instructions of each kind,
+//! blocks and temporaries, in total and per function. Comparing the counts
+//! before and after a change to the passes shows where code grew or shrank.
+
+use crate::mir::types::*;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Sizes of one function
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FunctionStats {
+    pub name: String,
+    pub blocks: usize,
+
+    /// Instructions, not counting terminators
+    pub instructions: usize,
+
+    /// Variables lowering introduced, which have no permissions and are
+    /// not parameters
+    pub temporaries: usize,
+}
+
+/// Sizes of a whole program
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MirStats {
+    /// Instructions of each kind across all functions, by kind name
+    pub instructions_by_kind: BTreeMap<&'static str, usize>,
+
+    /// Every function, in name order
+    pub functions: Vec<FunctionStats>,
+}
+
+impl MirStats {
+    /// Count the instructions, blocks and temporaries of a program
+    pub fn compute(program: &MirProgram) -> Self {
+        let mut stats = Self::default();
+        let mut functions: Vec<&MirFunction> = program.functions.values().collect();
+        functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for func in functions {
+            let parameters: HashSet<VarId> = func.parameters.iter().map(|(id, _)| *id).collect();
+            let mut instructions = 0;
+            for instruction in func.blocks.iter().flat_map(|block| &block.instructions) {
+                *stats.instructions_by_kind.entry(kind(instruction)).or_default() += 1;
+                instructions += 1;
+            }
+            stats.functions.push(FunctionStats {
+                name: func.name.clone(),
+                blocks: func.blocks.len(),
+                instructions,
+                temporaries: func.variables.values()
+                    .filter(|var| var.permissions.is_empty() && !parameters.contains(&var.id))
+                    .count(),
+            });
+        }
+        stats
+    }
+
+    /// Blocks in all functions
+    pub fn blocks(&self) -> usize {
+        self.functions.iter().map(|func| func.blocks).sum()
+    }
+
+    /// Instructions in all functions, not counting terminators
+    pub fn instructions(&self) -> usize {
+        self.functions.iter().map(|func| func.instructions).sum()
+    }
+
+    /// Temporaries in all functions
+    pub fn temporaries(&self) -> usize {
+        self.functions.iter().map(|func| func.temporaries).sum()
+    }
+}
+
+impl fmt::Display for MirStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} functions, {} blocks, {} instructions, {} temporaries",
+            self.functions.len(),
+            self.blocks(),
+            self.instructions(),
+            self.temporaries(),
+        )?;
+
+        writeln!(f, "Instructions by kind:")?;
+        for (kind, count) in &self.instructions_by_kind {
+            writeln!(f, "  {}: {}", kind, count)?;
+        }
+
+        writeln!(f, "Functions:")?;
+        for func in &self.functions {
+            writeln!(
+                f,
+                "  {}: {} blocks, {} instructions, {} temporaries",
+                func.name, func.blocks, func.instructions, func.temporaries,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The name an instruction is counted under
+fn kind(instruction: &Instruction) -> &'static str {
+    match instruction {
+        Instruction::Assign { .. } => "assign",
+        Instruction::BinaryOp { .. } => "binary",
+        Instruction::Cast { .. } => "cast",
+        Instruction::Call { .. } => "call",
+        Instruction::Intrinsic { .. } => "intrinsic",
+        Instruction::Peak { .. } => "peak",
+        Instruction::Phi { .. } => "phi",
+        Instruction::AtomicBegin => "atomic begin",
+        Instruction::AtomicEnd => "atomic end",
+        Instruction::WriteBarrier { .. } => "barrier",
+        Instruction::Nop => "nop",
+    }
+}
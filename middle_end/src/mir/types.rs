@@ -2,17 +2,23 @@
 //!
 //! This module defines the core data structures for the MIR (Middle Intermediate Representation).
 
-use std::collections::HashMap;
-use front_end::types::Type as FrontEndType;
+use std::collections::{HashMap, HashSet};
+use front_end::intern::Name;
+use front_end::types::{Permission, Type as FrontEndType};
+use crate::hir::types::SourceLocation;
 
 /// A unique identifier for a basic block
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct BlockId(pub usize);
 
 /// A unique identifier for a variable
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct VarId(pub usize);
 
+/// The index of a string in the program's constant pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StringId(pub usize);
+
 /// A single MIR instruction
 #[derive(Debug, Clone)]
 pub enum Instruction {
@@ -30,6 +36,14 @@ pub enum Instruction {
         right: Operand,
     },
     
+    /// Convert a number to the numeric type of `target`: a widening the
+    /// source language does implicitly, or an explicit cast, which may
+    /// truncate
+    Cast {
+        target: VarId,
+        source: Operand,
+    },
+    
     /// Call a function
     Call {
         target: Option<VarId>,
@@ -37,25 +51,258 @@ pub enum Instruction {
         arguments: Vec<Operand>,
     },
     
-    /// Return from a function
+    /// A built-in operation, such as `print`, that the interpreter and
+    /// backends implement directly instead of calling a function
+    Intrinsic {
+        target: Option<VarId>,
+        intrinsic: Intrinsic,
+        arguments: Vec<Operand>,
+    },
+    
+    /// Make `target` another name for the storage of `source`, as a `peak`
+    /// binding does: later writes to either are seen through both
+    Peak {
+        target: VarId,
+        source: VarId,
+    },
+    
+    /// The value of `target` depends on the block control came from: the
+    /// operand paired with that predecessor. Phis only appear at the start
+    /// of a block, once the function is in SSA form.
+    Phi {
+        target: VarId,
+        sources: Vec<(BlockId, Operand)>,
+    },
+    
+    /// Start of an atomic block: the instructions up to the matching
+    /// `AtomicEnd` run as one unit, with no other behavior observing the
+    /// state in between
+    AtomicBegin,
+    
+    /// End of an atomic block. Every path out of the block passes one,
+    /// including returns from inside it.
+    AtomicEnd,
+    
+    /// Comes just before a store to `variable` while a `peak` alias of it
+    /// is live: every earlier write is made visible before the new value
+    /// can be seen through the alias. `place_write_barriers` adds these
+    /// where they are needed and removes the rest.
+    WriteBarrier {
+        variable: VarId,
+    },
+    
+    /// No operation (placeholder)
+    Nop,
+}
+
+impl Instruction {
+    /// The variable the instruction assigns, if any
+    pub fn target(&self) -> Option<VarId> {
+        match self {
+            Instruction::Assign { target, .. } |
+            Instruction::Cast { target, .. } |
+            Instruction::BinaryOp { target, .. } |
+            Instruction::Peak { target, .. } |
+            Instruction::Phi { target, .. } => Some(*target),
+            Instruction::Call { target, .. } |
+            Instruction::Intrinsic { target, .. } => *target,
+            Instruction::AtomicBegin |
+            Instruction::AtomicEnd |
+            Instruction::WriteBarrier { .. } |
+            Instruction::Nop => None,
+        }
+    }
+    
+    /// The variable the instruction assigns, for rewriting
+    pub fn target_mut(&mut self) -> Option<&mut VarId> {
+        match self {
+            Instruction::Assign { target, .. } |
+            Instruction::Cast { target, .. } |
+            Instruction::BinaryOp { target, .. } |
+            Instruction::Peak { target, .. } |
+            Instruction::Phi { target, .. } => Some(target),
+            Instruction::Call { target, .. } |
+            Instruction::Intrinsic { target, .. } => target.as_mut(),
+            Instruction::AtomicBegin |
+            Instruction::AtomicEnd |
+            Instruction::WriteBarrier { .. } |
+            Instruction::Nop => None,
+        }
+    }
+    
+    /// The operands the instruction reads, other than phi sources. A peak
+    /// reads nothing; it only refers to its source.
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::Assign { source, .. } |
+            Instruction::Cast { source, .. } => vec![source],
+            Instruction::BinaryOp { left, right, .. } => vec![left, right],
+            Instruction::Call { arguments, .. } |
+            Instruction::Intrinsic { arguments, .. } => arguments.iter().collect(),
+            Instruction::Peak { .. } |
+            Instruction::Phi { .. } |
+            Instruction::AtomicBegin |
+            Instruction::AtomicEnd |
+            Instruction::WriteBarrier { .. } |
+            Instruction::Nop => Vec::new(),
+        }
+    }
+    
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Assign { source, .. } |
+            Instruction::Cast { source, .. } => vec![source],
+            Instruction::BinaryOp { left, right, .. } => vec![left, right],
+            Instruction::Call { arguments, .. } |
+            Instruction::Intrinsic { arguments, .. } => arguments.iter_mut().collect(),
+            Instruction::Peak { .. } |
+            Instruction::Phi { .. } |
+            Instruction::AtomicBegin |
+            Instruction::AtomicEnd |
+            Instruction::WriteBarrier { .. } |
+            Instruction::Nop => Vec::new(),
+        }
+    }
+}
+
+/// How control leaves a basic block
+#[derive(Debug, Clone)]
+pub enum Terminator {
+    /// Return from the function
     Return(Option<Operand>),
     
-    /// Jump to another block
+    /// Continue in another block
     Jump(BlockId),
     
-    /// Conditional jump
+    /// Continue in one of two blocks depending on a boolean
     Branch {
         condition: Operand,
         true_block: BlockId,
         false_block: BlockId,
     },
+}
+
+impl Terminator {
+    /// The blocks control may continue in
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Return(_) => Vec::new(),
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch { true_block, false_block, .. } => vec![*true_block, *false_block],
+        }
+    }
     
-    /// No operation (placeholder)
-    Nop,
+    /// The blocks control may continue in, for rewriting
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Return(_) => Vec::new(),
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch { true_block, false_block, .. } => vec![true_block, false_block],
+        }
+    }
+    
+    /// The operand the terminator reads, if any
+    pub fn operand(&self) -> Option<&Operand> {
+        match self {
+            Terminator::Return(operand) => operand.as_ref(),
+            Terminator::Branch { condition, .. } => Some(condition),
+            Terminator::Jump(_) => None,
+        }
+    }
+    
+    pub fn operand_mut(&mut self) -> Option<&mut Operand> {
+        match self {
+            Terminator::Return(operand) => operand.as_mut(),
+            Terminator::Branch { condition, .. } => Some(condition),
+            Terminator::Jump(_) => None,
+        }
+    }
+}
+
+/// Built-in operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Intrinsic {
+    /// Write a value and a newline to standard output
+    Print,
+    
+    /// Absolute value of a number
+    Abs,
+    
+    /// The smaller of two numbers
+    Min,
+    
+    /// The larger of two numbers
+    Max,
+    
+    /// Number of characters in a string
+    Length,
+    
+    /// Two strings joined together
+    Concat,
+    
+    /// A copy of a value with storage of its own, for `clone`. Lowering
+    /// only uses it for strings; a copy of any other value is the value.
+    Clone,
+}
+
+impl Intrinsic {
+    pub const ALL: [Intrinsic; 7] = [
+        Intrinsic::Print,
+        Intrinsic::Abs,
+        Intrinsic::Min,
+        Intrinsic::Max,
+        Intrinsic::Length,
+        Intrinsic::Concat,
+        Intrinsic::Clone,
+    ];
+    
+    /// The intrinsic a call to `name` stands for, when the program does not
+    /// define a function of that name
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|intrinsic| intrinsic.name() == name)
+    }
+    
+    /// The name the intrinsic is called and printed by
+    pub fn name(&self) -> &'static str {
+        match self {
+            Intrinsic::Print => "print",
+            Intrinsic::Abs => "abs",
+            Intrinsic::Min => "min",
+            Intrinsic::Max => "max",
+            Intrinsic::Length => "length",
+            Intrinsic::Concat => "concat",
+            Intrinsic::Clone => "clone",
+        }
+    }
+    
+    /// Number of arguments the intrinsic takes
+    pub fn arity(&self) -> usize {
+        match self {
+            Intrinsic::Print | Intrinsic::Abs | Intrinsic::Length | Intrinsic::Clone => 1,
+            Intrinsic::Min | Intrinsic::Max | Intrinsic::Concat => 2,
+        }
+    }
+    
+    /// Whether the intrinsic only computes its result, so calls with the
+    /// same arguments can be merged or dropped
+    pub fn is_pure(&self) -> bool {
+        !matches!(self, Intrinsic::Print)
+    }
+    
+    /// The type of the result for arguments of type `argument`; `print`
+    /// has none
+    pub fn result_type(&self, argument: &FrontEndType) -> Option<FrontEndType> {
+        match self {
+            Intrinsic::Print => None,
+            Intrinsic::Abs | Intrinsic::Min | Intrinsic::Max | Intrinsic::Clone => Some(argument.clone()),
+            Intrinsic::Length => Some(FrontEndType::Int),
+            Intrinsic::Concat => Some(FrontEndType::String),
+        }
+    }
 }
 
 /// Binary operations
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BinaryOperation {
     Add,
     Subtract,
@@ -72,8 +319,20 @@ pub enum BinaryOperation {
     Or,
 }
 
+impl BinaryOperation {
+    /// Whether the operation compares its operands, producing a Bool
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperation::Equal | BinaryOperation::NotEqual |
+            BinaryOperation::LessThan | BinaryOperation::LessThanEqual |
+            BinaryOperation::GreaterThan | BinaryOperation::GreaterThanEqual
+        )
+    }
+}
+
 /// An operand to an instruction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Operand {
     /// A variable reference
     Variable(VarId),
@@ -83,16 +342,84 @@ pub enum Operand {
 }
 
 /// A constant value
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Constant {
-    /// An integer constant
-    Integer(i64),
+    /// An integer constant of the given numeric type
+    Integer(i64, FrontEndType),
     
     /// A boolean constant
     Boolean(bool),
     
-    /// A string constant
-    String(String),
+    /// A string constant, stored in the program's constant pool
+    String(StringId),
+}
+
+impl Operand {
+    /// The variable the operand reads, if it is one
+    pub fn variable(&self) -> Option<VarId> {
+        match self {
+            Operand::Variable(id) => Some(*id),
+            Operand::Constant(_) => None,
+        }
+    }
+}
+
+impl Constant {
+    /// The type of the constant
+    pub fn typ(&self) -> FrontEndType {
+        match self {
+            Constant::Integer(_, typ) => typ.clone(),
+            Constant::Boolean(_) => FrontEndType::Bool,
+            Constant::String(_) => FrontEndType::String,
+        }
+    }
+}
+
+/// The string constants of a program, each stored once. Instructions
+/// refer to a string by its `StringId`, so equal strings compare equal
+/// without looking at their text. Strings are never removed, so an id
+/// stays valid for the life of the program.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StringPool {
+    strings: Vec<String>,
+}
+
+impl StringPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// The id of a string, adding it to the pool if it is not there yet
+    pub fn intern(&mut self, value: &str) -> StringId {
+        match self.strings.iter().position(|existing| existing == value) {
+            Some(index) => StringId(index),
+            None => {
+                self.strings.push(value.to_string());
+                StringId(self.strings.len() - 1)
+            },
+        }
+    }
+    
+    /// The string with the given id
+    pub fn get(&self, id: StringId) -> &str {
+        &self.strings[id.0]
+    }
+    
+    /// The strings in the pool, in id order
+    pub fn iter(&self) -> impl Iterator<Item = (StringId, &str)> {
+        self.strings.iter().enumerate().map(|(index, value)| (StringId(index), value.as_str()))
+    }
+    
+    /// The number of strings in the pool
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+    
+    /// Whether the pool holds no strings
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
 }
 
 /// A basic block in the MIR
@@ -101,8 +428,11 @@ pub struct BasicBlock {
     /// The block's ID
     pub id: BlockId,
     
-    /// The instructions in this block
+    /// The instructions in this block, none of which transfer control
     pub instructions: Vec<Instruction>,
+    
+    /// Where control goes after the instructions
+    pub terminator: Terminator,
 }
 
 /// A function definition in the MIR
@@ -125,6 +455,88 @@ pub struct MirFunction {
     
     /// Variable information
     pub variables: HashMap<VarId, MirVariable>,
+    
+    /// Whether calls have no effect beyond their result, as inferred by
+    /// `hir::effects`
+    pub is_pure: bool,
+    
+    /// Whether this is an actor behavior, which callers run asynchronously
+    pub is_behavior: bool,
+    
+    /// Where the function is defined; `None` when lowering had no
+    /// location, and for MIR read from text
+    pub location: Option<SourceLocation>,
+}
+
+impl MirFunction {
+    /// Variables something other than this function's own instructions
+    /// may read or change between two of them: `writes` variables, which
+    /// other behaviors share, and both sides of a `peak`, each of which
+    /// changes with the other. Passes leave their reads and writes alone.
+    pub fn shared_variables(&self) -> HashSet<VarId> {
+        let mut shared: HashSet<VarId> = self.variables.values()
+            .filter(|var| var.permissions.contains(&Permission::Writes))
+            .map(|var| var.id)
+            .collect();
+        for instruction in self.blocks.iter().flat_map(|block| &block.instructions) {
+            if let Instruction::Peak { target, source } = instruction {
+                shared.insert(*target);
+                shared.insert(*source);
+            }
+        }
+        shared
+    }
+    
+    /// The block with the given ID
+    pub fn block(&self, id: BlockId) -> Option<&BasicBlock> {
+        self.blocks.iter().find(|block| block.id == id)
+    }
+    
+    /// The blocks each block is entered from, for every block
+    pub fn predecessors(&self) -> HashMap<BlockId, Vec<BlockId>> {
+        let mut predecessors: HashMap<BlockId, Vec<BlockId>> = self.blocks.iter()
+            .map(|block| (block.id, Vec::new()))
+            .collect();
+        for block in &self.blocks {
+            for successor in block.terminator.successors() {
+                predecessors.entry(successor).or_default().push(block.id);
+            }
+        }
+        predecessors
+    }
+    
+    /// Blocks reachable from the entry, each before its successors except
+    /// along loop back edges
+    pub fn reverse_postorder(&self) -> Vec<BlockId> {
+        let mut visited = HashSet::new();
+        let mut postorder = Vec::new();
+        
+        // Depth-first, remembering how many successors of each block on the
+        // stack have been visited
+        let mut stack = vec![(self.entry_block, 0)];
+        visited.insert(self.entry_block);
+        while let Some((id, next)) = stack.pop() {
+            let successors = self.block(id).map(|block| block.terminator.successors()).unwrap_or_default();
+            match successors.get(next) {
+                Some(&successor) => {
+                    stack.push((id, next + 1));
+                    if visited.insert(successor) {
+                        stack.push((successor, 0));
+                    }
+                },
+                None => postorder.push(id),
+            }
+        }
+        
+        postorder.reverse();
+        postorder
+    }
+    
+    /// Drop the blocks that cannot be reached from the entry
+    pub fn remove_unreachable_blocks(&mut self) {
+        let reachable: HashSet<BlockId> = self.reverse_postorder().into_iter().collect();
+        self.blocks.retain(|block| reachable.contains(&block.id));
+    }
 }
 
 /// A variable in the MIR
@@ -134,12 +546,40 @@ pub struct MirVariable {
     pub id: VarId,
     
     /// The variable's name (for debugging)
-    pub name: String,
+    pub name: Name,
     
     /// The variable's type
     pub typ: FrontEndType,
+    
+    /// Permissions from the declaration; empty for temporaries
+    pub permissions: Vec<Permission>,
+    
+    /// Where the value lives
+    pub storage: Storage,
+    
+    /// Where the variable is declared, or for a temporary, the expression
+    /// whose value it holds; `None` when lowering had no location, and for
+    /// MIR read from text
+    pub location: Option<SourceLocation>,
+}
+
+/// Where a variable's value is allocated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Storage {
+    /// In the function's frame. Nothing refers to the value after the call
+    /// returns, so `peak` and `clone` of it need no heap bookkeeping.
+    Stack,
+    
+    /// On the heap, for globals and for values that escape their function
+    Heap,
 }
 
+/// The function lowering puts the global initializers and the other
+/// top-level statements in, in source order. A program runs it before
+/// `main`, or on its own when it is a script without one. The `$` keeps it
+/// from clashing with any function the source can name.
+pub const INIT_FUNCTION: &str = "$init";
+
 /// A complete MIR program
 #[derive(Debug, Clone)]
 pub struct MirProgram {
@@ -154,6 +594,15 @@ pub struct MirProgram {
     
     /// The next available block ID
     pub next_block_id: usize,
+    
+    /// String constants of all functions
+    pub strings: StringPool,
+}
+
+impl Default for MirProgram {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MirProgram {
@@ -164,8 +613,10 @@ impl MirProgram {
             functions: HashMap::new(),
             next_var_id: 0,
             next_block_id: 0,
+            strings: StringPool::new(),
         }
     }
+
     
     /// Generate a new variable ID
     pub fn new_var_id(&mut self) -> VarId {
@@ -180,4 +631,12 @@ impl MirProgram {
         self.next_block_id += 1;
         BlockId(id)
     }
+    
+    /// Instructions in all functions, not counting terminators
+    pub fn instruction_count(&self) -> usize {
+        self.functions.values()
+            .flat_map(|func| &func.blocks)
+            .map(|block| block.instructions.len())
+            .sum()
+    }
 }
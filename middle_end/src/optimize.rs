@@ -0,0 +1,285 @@
+//! Optimization pipeline
+//!
+//! An `OptLevel` selects a default sequence of passes, and a `Pipeline`
+//! runs a sequence of passes in order. HIR passes run before lowering and
+//! MIR passes after it, so callers only choose which passes run and in what
+//! order within each stage. MIR passes run under a `MirPassManager`, which
+//! reports what each of them did.
+
+use crate::hir::{self, HirProgram};
+use crate::mir::{self, MirPass, MirPassManager, MirProgram, PassStats};
+use crate::mir::passes::{
+    CommonSubexpressionElimination, ConstantPropagation, CopyPropagation, DeadStoreElimination, Inlining,
+    LoopInvariantCodeMotion, Peephole, SsaConstruction, StackSlots,
+};
+use std::fmt;
+use std::str::FromStr;
+
+/// How aggressively to optimize
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    /// No optimization (`-O0`)
+    #[default]
+    None,
+
+    /// Cheap cleanups: constant folding and dead code elimination (`-O1`)
+    Basic,
+
+    /// Everything, including inlining, peephole rewrites, SSA
+    /// construction, constant and copy propagation, loop-invariant code
+    /// motion, CSE and dead store elimination (`-O2`)
+    Full,
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    /// Parse `0`, `1` or `2`, with or without a leading `-O`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches("-O") {
+            "0" => Ok(OptLevel::None),
+            "1" => Ok(OptLevel::Basic),
+            "2" => Ok(OptLevel::Full),
+            other => Err(format!("unknown optimization level '{}', expected 0, 1 or 2", other)),
+        }
+    }
+}
+
+/// A single optimization pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Inline small functions (HIR)
+    Inline,
+
+    /// Fold constant expressions and propagate constants (HIR)
+    ConstantFold,
+
+    /// Remove unreachable functions and unused variables (HIR)
+    DeadCode,
+
+    /// Inline small functions with any control flow at their call sites (MIR)
+    InlineMir,
+
+    /// Rewrite small patterns such as `x + 0` and `x * 1` (MIR)
+    Peephole,
+
+    /// Put functions into SSA form (MIR)
+    Ssa,
+
+    /// Replace uses of constants and fold branches on them (MIR)
+    ConstantPropagation,
+
+    /// Read the original variable in place of a copy of it (MIR)
+    CopyPropagation,
+
+    /// Move computations that do not change inside a loop in front of it (MIR)
+    LoopInvariants,
+
+    /// Reuse repeated binary operations (MIR)
+    CommonSubexpressions,
+
+    /// Remove assignments whose value is never read (MIR)
+    DeadStores,
+
+    /// Share one variable between temporaries never in use at once (MIR)
+    StackSlots,
+}
+
+impl Pass {
+    /// Whether the pass runs on the MIR rather than the HIR
+    pub fn is_mir(&self) -> bool {
+        self.mir_pass().is_some()
+    }
+
+    /// The MIR pass that implements this pass, if it runs on the MIR
+    pub fn mir_pass(&self) -> Option<Box<dyn MirPass>> {
+        match self {
+            Pass::InlineMir => Some(Box::new(Inlining)),
+            Pass::Peephole => Some(Box::new(Peephole)),
+            Pass::Ssa => Some(Box::new(SsaConstruction)),
+            Pass::ConstantPropagation => Some(Box::new(ConstantPropagation)),
+            Pass::CopyPropagation => Some(Box::new(CopyPropagation)),
+            Pass::LoopInvariants => Some(Box::new(LoopInvariantCodeMotion)),
+            Pass::CommonSubexpressions => Some(Box::new(CommonSubexpressionElimination)),
+            Pass::DeadStores => Some(Box::new(DeadStoreElimination)),
+            Pass::StackSlots => Some(Box::new(StackSlots)),
+            Pass::Inline | Pass::ConstantFold | Pass::DeadCode => None,
+        }
+    }
+
+    /// Every pass, in the order `-O2` runs them
+    pub const ALL: [Pass; 12] = [
+        Pass::Inline,
+        Pass::ConstantFold,
+        Pass::DeadCode,
+        Pass::InlineMir,
+        Pass::Peephole,
+        Pass::Ssa,
+        Pass::ConstantPropagation,
+        Pass::CopyPropagation,
+        Pass::LoopInvariants,
+        Pass::CommonSubexpressions,
+        Pass::DeadStores,
+        Pass::StackSlots,
+    ];
+}
+
+impl FromStr for Pass {
+    type Err = String;
+
+    /// Parse the name a pass is displayed with
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Pass::ALL.into_iter()
+            .find(|pass| pass.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<String> = Pass::ALL.iter().map(|pass| pass.to_string()).collect();
+                format!("unknown pass '{}', expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Pass::Inline => "inline",
+            Pass::ConstantFold => "const-fold",
+            Pass::DeadCode => "dce",
+            Pass::InlineMir => "mir-inline",
+            Pass::Peephole => "peephole",
+            Pass::Ssa => "ssa",
+            Pass::ConstantPropagation => "const-prop",
+            Pass::CopyPropagation => "copy-prop",
+            Pass::LoopInvariants => "licm",
+            Pass::CommonSubexpressions => "cse",
+            Pass::DeadStores => "dse",
+            Pass::StackSlots => "slots",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// An ordered list of optimization passes
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    passes: Vec<Pass>,
+
+    /// Whether the MIR passes record what they change
+    record_diffs: bool,
+}
+
+impl Pipeline {
+    /// Create a pipeline that runs no passes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The default pipeline for an optimization level
+    pub fn for_level(level: OptLevel) -> Self {
+        match level {
+            OptLevel::None => Self::new(),
+            OptLevel::Basic => Self::new()
+                .with_pass(Pass::ConstantFold)
+                .with_pass(Pass::DeadCode),
+            OptLevel::Full => Self::new()
+                .with_pass(Pass::Inline)
+                .with_pass(Pass::ConstantFold)
+                .with_pass(Pass::DeadCode)
+                .with_pass(Pass::InlineMir)
+                .with_pass(Pass::Peephole)
+                .with_pass(Pass::Ssa)
+                .with_pass(Pass::ConstantPropagation)
+                .with_pass(Pass::CopyPropagation)
+                .with_pass(Pass::LoopInvariants)
+                .with_pass(Pass::CommonSubexpressions)
+                .with_pass(Pass::DeadStores)
+                .with_pass(Pass::StackSlots),
+        }
+    }
+
+    /// Record the change each MIR pass makes to the textual MIR in its
+    /// `PassStats`
+    pub fn with_diffs(mut self) -> Self {
+        self.record_diffs = true;
+        self
+    }
+
+    /// Append a pass to the pipeline
+    pub fn with_pass(mut self, pass: Pass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+
+    /// The passes in the order they run within their stage
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Run the HIR passes, in order
+    pub fn run_hir(&self, program: &mut HirProgram) {
+        for pass in self.passes.iter().filter(|pass| !pass.is_mir()) {
+            match pass {
+                Pass::Inline => hir::inline_functions(program),
+                Pass::ConstantFold => hir::fold_constants(program),
+                Pass::DeadCode => hir::eliminate_dead_code(program),
+                Pass::InlineMir |
+                Pass::Peephole |
+                Pass::Ssa |
+                Pass::ConstantPropagation |
+                Pass::CopyPropagation |
+                Pass::LoopInvariants |
+                Pass::CommonSubexpressions |
+                Pass::DeadStores |
+                Pass::StackSlots => {},
+            }
+        }
+    }
+
+    /// The pass manager that runs the MIR passes, in order
+    pub fn mir_passes(&self) -> MirPassManager {
+        let mut manager = MirPassManager::new();
+        manager.record_diffs(self.record_diffs);
+        for pass in self.passes.iter().filter_map(|pass| pass.mir_pass()) {
+            manager.register(pass);
+        }
+        manager
+    }
+
+    /// Run the MIR passes, in order, and return what each one did
+    pub fn run_mir(&self, program: &mut MirProgram) -> Vec<PassStats> {
+        self.mir_passes().run(program)
+    }
+
+    /// Optimize a HIR program, lower it to MIR and optimize the result.
+    /// Nested functions are lifted and generic functions monomorphized
+    /// first, and write barriers placed last, whatever the passes.
+    pub fn lower(&self, program: HirProgram) -> MirProgram {
+        self.lower_with_stats(program).0
+    }
+
+    /// Like `lower`, also returning what each MIR pass did
+    pub fn lower_with_stats(&self, mut program: HirProgram) -> (MirProgram, Vec<PassStats>) {
+        hir::lift_closures(&mut program);
+        hir::monomorphize(&mut program);
+        // Shadowing declarations lower to distinct MIR variables only once
+        // every use is bound to its declaration
+        hir::bind_symbols(&mut program);
+        self.run_hir(&mut program);
+        let mut mir_program = mir::convert_hir_to_mir(&program);
+        let stats = self.run_mir(&mut mir_program);
+        // The passes move and remove stores, so barriers go in after them
+        mir::place_write_barriers(&mut mir_program);
+        (mir_program, stats)
+    }
+}
+
+impl FromStr for Pipeline {
+    type Err = String;
+
+    /// Parse a comma-separated list of pass names, such as `const-fold,ssa,cse`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .try_fold(Pipeline::new(), |pipeline, name| Ok(pipeline.with_pass(name.parse()?)))
+    }
+}
@@ -2,7 +2,10 @@
 //!
 //! This module contains simple tests for name resolution features.
 
-use crate::hir::{convert_statements_to_hir, resolve_names};
+use crate::hir::{convert_statements_to_hir, resolve_names, bind_symbols};
+use crate::hir::types::{HirExpression, HirStatement};
+use front_end::types::Type;
+use crate::hir::scope::{Namespace, ScopeError};
 use front_end::parser::Parser;
 
 #[test]
@@ -156,3 +159,123 @@ fn get_not_found_name(err: &impl std::fmt::Debug) -> Option<String> {
     }
     None
 }
+
+#[test]
+fn test_calling_a_variable_reports_wrong_namespace() {
+    let source = r#"
+        reads write x: Int = 1
+        
+        fn test() {
+            x(2)
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    let hir_program = convert_statements_to_hir(statements);
+    let resolved = resolve_names(&hir_program);
+    
+    let has_namespace_error = resolved.errors.iter().any(|err| matches!(
+        err,
+        ScopeError::WrongNamespace { name, expected: Namespace::Function, .. } if name == "x"
+    ));
+    assert!(has_namespace_error, "Calling variable 'x' should report a wrong namespace error, got {:?}", resolved.errors);
+    
+    let has_not_found = resolved.errors.iter().any(|err| get_not_found_name(err).as_deref() == Some("x"));
+    assert!(!has_not_found, "'x' exists as a variable and should not be reported as undefined");
+}
+
+#[test]
+fn test_function_used_as_value_reports_wrong_namespace() {
+    let source = r#"
+        fn answer() -> Int {
+            return 42
+        }
+        
+        fn test() -> Int {
+            return answer + 1
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    let hir_program = convert_statements_to_hir(statements);
+    let resolved = resolve_names(&hir_program);
+    
+    let has_namespace_error = resolved.errors.iter().any(|err| matches!(
+        err,
+        ScopeError::WrongNamespace { name, expected: Namespace::Value, .. } if name == "answer"
+    ));
+    assert!(has_namespace_error, "Using function 'answer' as a value should report a wrong namespace error, got {:?}", resolved.errors);
+}
+
+#[test]
+fn test_undefined_variable_suggests_similar_name() {
+    let source = r#"
+        fn test() -> Int {
+            reads write counter: Int = 10
+            return countr + 1
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let statements = parser.parse_statements();
+    let hir_program = convert_statements_to_hir(statements);
+    let resolved = resolve_names(&hir_program);
+    
+    let suggestion = resolved.errors.iter().find_map(|err| match err {
+        ScopeError::NotFound { name, suggestion, .. } if name == "countr" => suggestion.clone(),
+        _ => None,
+    });
+    assert_eq!(suggestion.as_deref(), Some("counter"), "Expected a suggestion for 'countr', got {:?}", resolved.errors);
+}
+
+#[test]
+fn test_shadowed_variables_get_distinct_symbols() {
+    let source = r#"
+        fn test() -> Int {
+            reads x: Int = 1
+            {
+                reads x: Int64 = 2
+                print x
+            }
+            return x
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let mut hir_program = convert_statements_to_hir(parser.parse_statements());
+    let resolved = bind_symbols(&mut hir_program);
+    assert!(resolved.errors.iter().all(|err| matches!(err, ScopeError::Shadowing { .. })),
+        "Unexpected errors: {:?}", resolved.errors);
+    
+    let HirStatement::Function(func) = &hir_program.statements[0] else {
+        panic!("Expected a function, got {:?}", hir_program.statements[0]);
+    };
+    let HirStatement::Declaration(outer) = &func.body[0] else {
+        panic!("Expected the outer declaration, got {:?}", func.body[0]);
+    };
+    let HirStatement::Block(block, _) = &func.body[1] else {
+        panic!("Expected a block, got {:?}", func.body[1]);
+    };
+    let HirStatement::Declaration(inner) = &block[0] else {
+        panic!("Expected the inner declaration, got {:?}", block[0]);
+    };
+    let outer_id = outer.symbol.expect("Outer 'x' should have a symbol");
+    let inner_id = inner.symbol.expect("Inner 'x' should have a symbol");
+    assert_ne!(outer_id, inner_id);
+    
+    // Each use refers to the declaration in scope
+    let HirStatement::Print(HirExpression::Variable(_, _, _, printed), _) = &block[1] else {
+        panic!("Expected a print of 'x', got {:?}", block[1]);
+    };
+    let HirStatement::Return(Some(HirExpression::Variable(_, _, _, returned)), _) = &func.body[2] else {
+        panic!("Expected a return of 'x', got {:?}", func.body[2]);
+    };
+    assert_eq!(*printed, Some(inner_id));
+    assert_eq!(*returned, Some(outer_id));
+    
+    // Both types survive in the type info
+    assert_eq!(hir_program.type_info.symbols.get(&outer_id), Some(&Type::Int));
+    assert_eq!(hir_program.type_info.symbols.get(&inner_id), Some(&Type::Int64));
+}
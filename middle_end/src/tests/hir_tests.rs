@@ -0,0 +1,589 @@
+//! Tests for HIR construction, traversal and optimization
+
+use crate::hir::{convert_statements_to_hir, desugar_program, eliminate_dead_code, fold_constants, inline_functions, lift_closures, monomorphize, pretty_print, pure_functions, suggest_permissions};
+use crate::hir::builder::{binary, call, int, var, FunctionBuilder, HirBuilder, StatementBuilder};
+use crate::hir::inline::inline_functions_with_limit;
+use crate::hir::types::{HirExpression, HirFunction, HirParameter, HirProgram, HirStatement};
+use crate::hir::visitor::{walk_expression, HirVisitor};
+use front_end::parser::Parser;
+use front_end::token::TokenType;
+use front_end::types::{Permission, Type};
+
+/// Collects every variable reference and parameter it walks over
+#[derive(Default)]
+struct NameCollector {
+    references: Vec<String>,
+    parameters: Vec<String>,
+}
+
+impl HirVisitor for NameCollector {
+    fn visit_parameter(&mut self, param: &HirParameter) {
+        self.parameters.push(param.name.clone());
+    }
+    
+    fn visit_expression(&mut self, expr: &HirExpression) {
+        if let HirExpression::Variable(name, _, _, _) = expr {
+            self.references.push(name.clone());
+        }
+        walk_expression(self, expr);
+    }
+}
+
+#[test]
+fn test_visitor_reaches_nested_nodes() {
+    let source = r#"
+        reads write total: Int = 1
+        
+        fn add(reads a: Int, reads b: Int) -> Int {
+            reads sum: Int = a + b
+            return sum + total
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let hir_program = convert_statements_to_hir(parser.parse_statements());
+    
+    let mut collector = NameCollector::default();
+    collector.visit_program(&hir_program);
+    
+    assert_eq!(collector.parameters, vec!["a", "b"]);
+    assert_eq!(collector.references, vec!["a", "b", "sum", "total"]);
+}
+
+/// The folded initializer of the top-level declaration with the given name
+fn initializer_of<'a>(program: &'a HirProgram, name: &str) -> &'a HirExpression {
+    program.statements.iter()
+        .find_map(|stmt| match stmt {
+            HirStatement::Declaration(var) if var.name == name => var.initializer.as_ref(),
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("No declaration of '{}'", name))
+}
+
+#[test]
+fn test_constant_propagation() {
+    let source = r#"
+        reads x: Int = 2
+        reads y: Int = x * 3
+        reads write counter: Int = 4
+        reads z: Int = counter + y
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let mut program = convert_statements_to_hir(parser.parse_statements());
+    fold_constants(&mut program);
+    println!("{}", pretty_print(&program));
+    
+    assert!(matches!(initializer_of(&program, "y"), HirExpression::Integer(6, _)), "x should propagate into y");
+    
+    // `counter` may be written, so only `y` is replaced in z's initializer
+    match initializer_of(&program, "z") {
+        HirExpression::Binary { left, right, .. } => {
+            assert!(matches!(left.as_ref(), HirExpression::Variable(name, _, _, _) if name == "counter"));
+            assert!(matches!(right.as_ref(), HirExpression::Integer(6, _)));
+        },
+        other => panic!("z should keep its addition, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_constant_propagation_respects_shadowing() {
+    let source = r#"
+        reads limit: Int = 10
+        
+        fn clamp(reads limit: Int) -> Int {
+            return limit + 1
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let mut program = convert_statements_to_hir(parser.parse_statements());
+    fold_constants(&mut program);
+    
+    let body = program.statements.iter()
+        .find_map(|stmt| match stmt {
+            HirStatement::Function(func) => Some(&func.body),
+            _ => None,
+        })
+        .expect("clamp should be lowered");
+    
+    // The parameter hides the global constant inside the function
+    assert!(matches!(
+        &body[0],
+        HirStatement::Return(Some(HirExpression::Binary { left, .. }), _)
+            if matches!(left.as_ref(), HirExpression::Variable(name, _, _, _) if name == "limit")
+    ), "Parameter should not be replaced, got {:?}", body[0]);
+}
+
+#[test]
+fn test_fold_arithmetic_wraps_like_the_interpreter() {
+    let mut program = [
+        (i64::MAX, TokenType::Plus, 1),
+        (i64::MIN, TokenType::Minus, 1),
+        (i64::MAX, TokenType::Star, 2),
+        (i64::MIN, TokenType::Slash, -1),
+        (1, TokenType::Slash, 0),
+    ]
+    .into_iter()
+    .fold(HirBuilder::new(), |builder, (left, operator, right)| {
+        builder.statement(HirStatement::Expression(binary(int(left), operator, int(right))))
+    })
+    .build();
+    fold_constants(&mut program);
+    
+    let folded: Vec<_> = program.statements.iter()
+        .map(|stmt| match stmt {
+            HirStatement::Expression(HirExpression::Integer(value, _)) => Some(*value),
+            _ => None,
+        })
+        .collect();
+    // Divisions that trap at run time stay unfolded
+    assert_eq!(folded, vec![Some(i64::MIN), Some(i64::MAX), Some(-2), None, None]);
+}
+
+#[test]
+fn test_fold_comparisons() {
+    let mut program = [
+        (1, TokenType::Less, 2),
+        (3, TokenType::GreaterEqual, 4),
+        (5, TokenType::EqualEqual, 5),
+        (5, TokenType::BangEqual, 5),
+    ]
+    .into_iter()
+    .fold(HirBuilder::new(), |builder, (left, operator, right)| {
+        builder.statement(HirStatement::Expression(binary(int(left), operator, int(right))))
+    })
+    .build();
+    fold_constants(&mut program);
+    
+    let folded: Vec<_> = program.statements.iter()
+        .map(|stmt| match stmt {
+            HirStatement::Expression(HirExpression::Boolean(value, _)) => *value,
+            other => panic!("Comparison should fold to a boolean, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(folded, vec![true, false, true, false]);
+}
+
+#[test]
+fn test_constant_branches_pruned() {
+    // if 1 < 2 { print 1 } else { print 2 }; if 2 < 1 { print 3 }; while 1 > 2 { print 4 }
+    let mut program = HirBuilder::new()
+        .if_else(binary(int(1), TokenType::Less, int(2)), |b| b.print(int(1)), |b| b.print(int(2)))
+        .if_then(binary(int(2), TokenType::Less, int(1)), |b| b.print(int(3)))
+        .while_loop(binary(int(1), TokenType::Greater, int(2)), |b| b.print(int(4)))
+        .build();
+    
+    fold_constants(&mut program);
+    eliminate_dead_code(&mut program);
+    println!("{}", pretty_print(&program));
+    
+    assert!(matches!(
+        &program.statements[0],
+        HirStatement::Block(stmts, _) if matches!(stmts.as_slice(), [HirStatement::Print(HirExpression::Integer(1, _), _)])
+    ), "Only the taken branch should remain, got {:?}", program.statements[0]);
+    assert!(matches!(&program.statements[1], HirStatement::Block(stmts, _) if stmts.is_empty()));
+    assert!(matches!(&program.statements[2], HirStatement::Block(stmts, _) if stmts.is_empty()));
+}
+
+/// Names of the functions defined at the top level of a program
+fn function_names(program: &HirProgram) -> Vec<&str> {
+    program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            HirStatement::Function(func) => Some(func.name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_dead_functions_and_variables_removed() {
+    let source = r#"
+        fn helper(reads a: Int) -> Int {
+            return a
+        }
+        
+        fn orphan(reads b: Int) -> Int {
+            reads only_orphan: Int = 1
+            return helper(b)
+        }
+        
+        fn main() {
+            reads write unread: Int = 1
+            unread = 2
+            reads kept: Int = 3
+            helper(kept)
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let mut program = convert_statements_to_hir(parser.parse_statements());
+    eliminate_dead_code(&mut program);
+    println!("{}", pretty_print(&program));
+    
+    assert_eq!(function_names(&program), vec!["helper", "main"]);
+    assert!(!program.type_info.functions.contains_key("orphan"));
+    
+    let main_body = program.statements.iter()
+        .find_map(|stmt| match stmt {
+            HirStatement::Function(func) if func.name == "main" => Some(&func.body),
+            _ => None,
+        })
+        .unwrap();
+    let declared: Vec<_> = main_body.iter()
+        .filter_map(|stmt| match stmt {
+            HirStatement::Declaration(var) => Some(var.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(declared, vec!["kept"], "Write-only variable and its assignment should be removed");
+    assert!(!main_body.iter().any(|stmt| matches!(stmt, HirStatement::Assignment(_))));
+}
+
+#[test]
+fn test_functions_kept_without_entry_point() {
+    let source = r#"
+        fn first(reads a: Int) -> Int {
+            return a
+        }
+        
+        fn second(reads b: Int) -> Int {
+            return b
+        }
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let mut program = convert_statements_to_hir(parser.parse_statements());
+    eliminate_dead_code(&mut program);
+    
+    assert_eq!(function_names(&program), vec!["first", "second"]);
+}
+
+/// A function whose body is the single `return` of an expression
+fn single_return_function(name: &str, parameter: &str, body: HirExpression) -> FunctionBuilder {
+    FunctionBuilder::new(name)
+        .param(&[Permission::Reads], parameter, Type::Int)
+        .returns(Type::Int)
+        .return_value(body)
+}
+
+fn call_int(function: &str, argument: HirExpression) -> HirExpression {
+    call(function, vec![argument], Type::Int)
+}
+
+fn int_variable(name: &str) -> HirExpression {
+    var(name, Type::Int)
+}
+
+#[test]
+fn test_inline_small_helpers() {
+    // fn double(x) { return x * 2 }  fn quadruple(y) { return double(double(y)) }
+    let mut program = HirBuilder::new()
+        .function(single_return_function("double", "x", binary(int_variable("x"), TokenType::Star, int(2))))
+        .function(single_return_function("quadruple", "y", call_int("double", call_int("double", int_variable("y")))))
+        .declare(&[Permission::Reads], "result", Type::Int, call_int("quadruple", int(5)))
+        .build();
+    
+    inline_functions(&mut program);
+    fold_constants(&mut program);
+    println!("{}", pretty_print(&program));
+    
+    assert!(matches!(initializer_of(&program, "result"), HirExpression::Integer(20, _)),
+        "Both helpers should be inlined and folded, got {:?}", initializer_of(&program, "result"));
+}
+
+#[test]
+fn test_inline_skips_recursion_and_large_bodies() {
+    let sum = |count: usize| (1..count).fold(int_variable("a"), |acc, _| binary(acc, TokenType::Plus, int_variable("a")));
+    
+    let mut program = HirBuilder::new()
+        .function(single_return_function("forever", "n", call_int("forever", int_variable("n"))))
+        .function(single_return_function("bulky", "a", sum(6)))
+        .declare(&[Permission::Reads], "looped", Type::Int, call_int("forever", int(1)))
+        .declare(&[Permission::Reads], "big", Type::Int, call_int("bulky", int(1)))
+        .build();
+    
+    inline_functions_with_limit(&mut program, 4);
+    
+    // The recursive call is expanded once, then stops at itself
+    assert!(matches!(
+        initializer_of(&program, "looped"),
+        HirExpression::Call { function, arguments, .. }
+            if function == "forever" && matches!(arguments[0], HirExpression::Integer(1, _))
+    ));
+    assert!(matches!(initializer_of(&program, "big"), HirExpression::Call { function, .. } if function == "bulky"));
+}
+
+#[test]
+fn test_pure_calls_folded_and_removed() {
+    let mut program = HirBuilder::new()
+        .function(single_return_function("double", "x", binary(int_variable("x"), TokenType::Star, int(2))))
+        .function(single_return_function("forever", "n", call_int("forever", int_variable("n"))))
+        .function(FunctionBuilder::new("noisy")
+            .param(&[Permission::Reads], "a", Type::Int)
+            .returns(Type::Int)
+            .print(int_variable("a"))
+            .return_value(int_variable("a")))
+        .declare(&[Permission::Reads], "doubled", Type::Int, call_int("double", int(21)))
+        .declare(&[Permission::Reads], "looped", Type::Int, call_int("forever", int(1)))
+        .declare(&[Permission::Reads], "logged", Type::Int, call_int("noisy", int(1)))
+        .build();
+    
+    let mut pure: Vec<String> = pure_functions(&program).into_iter().collect();
+    pure.sort();
+    assert_eq!(pure, vec!["double", "forever"]);
+    
+    // Evaluating the recursive call gives up instead of looping
+    fold_constants(&mut program);
+    assert!(matches!(initializer_of(&program, "doubled"), HirExpression::Integer(42, _)));
+    assert!(matches!(initializer_of(&program, "looped"), HirExpression::Call { .. }));
+    
+    // Unused results of pure calls go away, the print in noisy stays
+    eliminate_dead_code(&mut program);
+    let declared: Vec<_> = program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            HirStatement::Declaration(var) => Some(var.name.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(declared, vec!["logged"]);
+}
+
+/// Print a program, parse the output again and print the result
+fn reprint(program: &HirProgram) -> (String, String) {
+    let printed = pretty_print(program);
+    let mut parser = Parser::from_source(&printed);
+    let reparsed = convert_statements_to_hir(parser.parse_statements());
+    assert!(parser.get_errors().is_empty(), "Printed HIR should parse:\n{}\n{:?}", printed, parser.get_errors());
+    (printed, pretty_print(&reparsed))
+}
+
+#[test]
+fn test_pretty_print_round_trip() {
+    let source = r#"
+        fn scale(reads factor: Int, reads write total: Int) -> Int {
+            reads product: Int = (total - 1) * (factor + 2) / 3
+            total = product - (factor - 1)
+            return total
+        }
+        
+        reads write counter: Int = 5
+        reads small: Int8 = 3
+        read snapshot: Int = peak counter
+        reads copy: Int = clone counter
+        counter = counter + 1
+        scale(2, counter)
+        print counter
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let program = convert_statements_to_hir(parser.parse_statements());
+    let (printed, reprinted) = reprint(&program);
+    println!("{}", printed);
+    
+    assert!(printed.contains("fn scale(reads factor: Int, reads write total: Int) -> Int {"));
+    assert!(printed.contains("reads small: Int8 = 3"));
+    assert!(printed.contains("(total - 1) * (factor + 2) / 3"), "Only needed parentheses should be printed");
+    assert!(printed.contains("product - (factor - 1)"));
+    assert_eq!(printed, reprinted, "Printing should be stable across a parse round trip");
+    
+    // Every optimization pass must leave printable, re-parsable HIR behind
+    let mut optimized = program.clone();
+    inline_functions(&mut optimized);
+    fold_constants(&mut optimized);
+    eliminate_dead_code(&mut optimized);
+    let (printed, reprinted) = reprint(&optimized);
+    assert_eq!(printed, reprinted);
+}
+
+#[test]
+fn test_compound_assignment_desugared() {
+    let source = r#"
+        reads write total: Int = 1
+        total += 2
+        write sink: Int = 0
+        sink *= 3
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let mut program = convert_statements_to_hir(parser.parse_statements());
+    assert!(pretty_print(&program).contains("total += 2"));
+    
+    let reporter = desugar_program(&mut program);
+    let HirStatement::Assignment(assign) = &program.statements[1] else {
+        panic!("Expected an assignment, found {:?}", program.statements[1]);
+    };
+    assert!(assign.operator.is_none());
+    assert!(matches!(&assign.value, HirExpression::Binary { left, operator: TokenType::Plus, .. }
+        if matches!(left.as_ref(), HirExpression::Variable(name, _, _, _) if name == "total")));
+    assert!(pretty_print(&program).contains("total = total + 2"));
+    
+    // Only the write-only target is reported
+    assert_eq!(reporter.error_count, 1, "{}", reporter.report());
+    assert!(reporter.diagnostics[0].message.contains("'sink'"));
+    assert!(reporter.diagnostics[0].message.contains("write-only reference cannot read its own value"));
+}
+
+#[test]
+fn test_permissions_inferred_for_unannotated_declarations() {
+    let source = r#"
+        fn bump(reads write value: Int) {
+            value = value + 1
+        }
+        
+        constant: Int = 5
+        counter: Int = 0
+        shared: Int = 1
+        reads write annotated: Int = clone constant
+        read view = peak shared
+        counter = constant + 1
+        scratch: Int = counter
+        bump(shared)
+        print scratch
+    "#;
+    
+    let mut parser = Parser::from_source(source);
+    let program = convert_statements_to_hir(parser.parse_statements());
+    let suggestions = suggest_permissions(&program);
+    let inferred: Vec<(&str, &[Permission])> = suggestions.iter()
+        .map(|s| (s.name.as_str(), s.permissions.as_slice()))
+        .collect();
+    
+    assert_eq!(inferred, vec![
+        ("constant", &[Permission::Read][..]),
+        ("counter", &[Permission::Reads, Permission::Write][..]),
+        ("shared", &[Permission::Reads, Permission::Write][..]),
+        ("scratch", &[Permission::Read][..]),
+    ]);
+    assert_eq!(suggestions[0].to_string(), "read constant (only read after its declaration)");
+}
+
+#[test]
+fn test_statements_and_expressions_carry_spans() {
+    let source = "reads write total: Int = 1\ntotal = total + 2\nprint total";
+    let mut parser = Parser::from_source(source);
+    let program = convert_statements_to_hir(parser.parse_statements());
+    
+    let lines: Vec<usize> = program.statements.iter()
+        .map(|stmt| stmt.span().start.line)
+        .collect();
+    assert_eq!(lines, vec![1, 2, 3]);
+    
+    let HirStatement::Assignment(assign) = &program.statements[1] else {
+        panic!("Expected an assignment, got {:?}", program.statements[1]);
+    };
+    let HirExpression::Binary { left, right, span, .. } = &assign.value else {
+        panic!("Expected a binary value, got {:?}", assign.value);
+    };
+    assert_eq!((span.start, span.end), (left.span().start, right.span().end));
+    assert_eq!(assign.span.start.column, 1);
+    assert!(left.span().start.column > assign.span.start.column);
+}
+
+/// Names of the functions called anywhere in the program
+fn called_functions(program: &HirProgram) -> Vec<String> {
+    #[derive(Default)]
+    struct CallCollector(Vec<String>);
+    
+    impl HirVisitor for CallCollector {
+        fn visit_expression(&mut self, expr: &HirExpression) {
+            if let HirExpression::Call { function, .. } = expr {
+                self.0.push(function.clone());
+            }
+            walk_expression(self, expr);
+        }
+    }
+    
+    let mut collector = CallCollector::default();
+    collector.visit_program(program);
+    collector.0
+}
+
+#[test]
+fn test_generic_functions_instantiated_per_type() {
+    let source = r#"
+        fn id<T>(reads x: T) -> T {
+            return x
+        }
+        
+        fn main() {
+            reads a: Int = 1
+            reads b: Bool = 1 > 0
+            id(a)
+            id(b)
+            id(a)
+        }
+    "#;
+    let mut parser = Parser::from_source(source);
+    let mut program = convert_statements_to_hir(parser.parse_statements());
+    monomorphize(&mut program);
+    
+    let functions: Vec<&HirFunction> = program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            HirStatement::Function(func) => Some(func),
+            _ => None,
+        })
+        .collect();
+    let names: Vec<&str> = functions.iter().map(|func| func.name.as_str()).collect();
+    assert_eq!(names, vec!["main", "id$Int", "id$Bool"]);
+    assert_eq!(called_functions(&program), vec!["id$Int", "id$Bool", "id$Int"]);
+    
+    // Instances have concrete types only
+    let bool_instance = functions[2];
+    assert!(bool_instance.type_params.is_empty());
+    assert_eq!(bool_instance.parameters[0].typ, Type::Bool);
+    assert_eq!(bool_instance.return_type, Some(Type::Bool));
+    
+    let instance = &program.type_info.instances["id$Bool"];
+    assert_eq!(instance.generic, "id");
+    assert_eq!(instance.to_string(), "id<Bool>");
+    assert!(program.type_info.functions.contains_key("id$Int"));
+}
+
+#[test]
+fn test_nested_functions_lifted_with_environment() {
+    let source = r#"
+        fn outer(reads base: Int) -> Int {
+            reads write step: Int = 2
+            fn add_step(reads n: Int) -> Int {
+                return n + step
+            }
+            fn apply(reads n: Int) -> Int {
+                add_step(n)
+                return n + base
+            }
+            apply(1)
+            return step
+        }
+    "#;
+    let mut parser = Parser::from_source(source);
+    let mut program = convert_statements_to_hir(parser.parse_statements());
+    lift_closures(&mut program);
+    println!("{}", pretty_print(&program));
+    
+    let functions: Vec<&HirFunction> = program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            HirStatement::Function(func) => Some(func),
+            _ => None,
+        })
+        .collect();
+    let names: Vec<&str> = functions.iter().map(|func| func.name.as_str()).collect();
+    assert_eq!(names, vec!["outer", "outer$add_step", "outer$apply"]);
+    assert!(functions[0].body.iter().all(|stmt| !matches!(stmt, HirStatement::Function(_))));
+    
+    // `apply` captures `step` through its call to `add_step`, then `base`
+    let parameters: Vec<(&str, &[Permission])> = functions[2].parameters.iter()
+        .map(|param| (param.name.as_str(), &param.permissions[..]))
+        .collect();
+    assert_eq!(parameters, vec![
+        ("n", &[Permission::Reads][..]),
+        ("step", &[Permission::Reads, Permission::Write][..]),
+        ("base", &[Permission::Reads][..]),
+    ]);
+    assert_eq!(called_functions(&program), vec!["outer$apply", "outer$add_step"]);
+    
+    let closure = &program.type_info.closures["outer$apply"];
+    assert_eq!((closure.name.as_str(), closure.enclosing.as_str()), ("apply", "outer"));
+    let captured: Vec<&str> = closure.captures.iter().map(|capture| capture.name.as_str()).collect();
+    assert_eq!(captured, vec!["step", "base"]);
+}
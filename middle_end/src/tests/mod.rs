@@ -6,4 +6,6 @@
 mod hir_tests;
 mod hir_errors_tests;
 mod mir_tests;
-mod hir_resolution_tests;
\ No newline at end of file
+mod hir_resolution_tests;
+mod pass_manager_tests;
+mod serialize_tests;
\ No newline at end of file
@@ -0,0 +1,212 @@
+//! Tests for the semantic analysis pass manager
+//!
+//! These tests check that the built-in passes report their diagnostics
+//! from a single run and that custom passes share the traversal.
+
+use crate::hir::{analyze_program, convert_statements_to_hir, AnalysisContext, AnalysisPass, PassManager};
+use crate::hir::diagnostics::Diagnostic;
+use crate::hir::builder::{boolean, FunctionBuilder, HirBuilder, StatementBuilder};
+use crate::hir::types::{HirExpression, HirFunction, HirProgram, HirStatement, TextPosition};
+use crate::hir::types::SourceLocation as TypesLocation;
+use front_end::types::Type;
+use front_end::parser::Parser;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn lower(source: &str) -> HirProgram {
+    let mut parser = Parser::from_source(source);
+    convert_statements_to_hir(parser.parse_statements())
+}
+
+#[test]
+fn test_loop_control_outside_loops() {
+    // A function declared in a loop body cannot leave that loop
+    let source = r#"
+        fn count() -> Int {
+            reads write i: Int = 0
+            while i < 3 {
+                i += 1
+                fn inner() {
+                    continue
+                }
+                break
+            }
+            break
+            return i
+        }
+    "#;
+    
+    let reporter = analyze_program(&lower(source));
+    let report = reporter.report();
+    println!("Pass Manager Report:\n{}", report);
+    
+    assert_eq!(reporter.error_count, 2, "Only the break inside the loop is allowed");
+    assert!(report.contains("'continue' outside of a loop"));
+    assert!(report.contains("'break' outside of a loop"));
+    assert!(report.contains("Unreachable code in function 'count'"), "The return follows a break");
+}
+
+#[test]
+fn test_default_passes_report_together() {
+    let source = r#"
+        fn add(reads a: Int, reads b: Int) -> Int {
+            return a + b
+        }
+        
+        fn main() {
+            reads x: Int = 5
+            x = 10
+            add(x)
+        }
+    "#;
+    
+    let reporter = analyze_program(&lower(source));
+    let report = reporter.report();
+    println!("Pass Manager Report:\n{}", report);
+    
+    assert_eq!(reporter.error_count, 2, "Should report the write and the arity error");
+    assert!(report.contains("Cannot write to 'x'"), "Permission pass should flag the write");
+    assert!(report.contains("'add' takes 2 arguments but 1 was supplied"), "Call pass should flag the arity");
+}
+
+#[test]
+fn test_permission_errors_share_the_traversal() {
+    // The write is reported at the assignment, and the structured error
+    // comes from the same run as the diagnostic
+    let source = r#"
+        fn main() {
+            reads x: Int = 5
+            x = 10
+        }
+    "#;
+    
+    let analysis = PassManager::with_default_passes().analyze(&lower(source));
+    let codes: Vec<&str> = analysis.permission_errors.iter().map(|error| error.code()).collect();
+    assert_eq!(codes, vec!["P0002"]);
+    
+    let diagnostic = &analysis.diagnostics.diagnostics[0];
+    assert_eq!(diagnostic.code.as_deref(), Some("P0002"));
+    let line = diagnostic.primary.as_ref().map(|label| label.span.start_line);
+    assert_eq!(line, Some(4), "The error should point at 'x = 10'");
+}
+
+#[test]
+fn test_default_passes_accept_valid_program() {
+    let source = r#"
+        fn add(reads a: Int, reads b: Int) -> Int {
+            return a + b
+        }
+        
+        fn main() {
+            reads write total: Int = 1
+            total = add(total, 2)
+        }
+    "#;
+    
+    let reporter = analyze_program(&lower(source));
+    assert!(!reporter.has_errors(), "Valid program should pass, got:\n{}", reporter.report());
+}
+
+/// Records the functions it enters and warns about functions that never return
+struct FunctionTracker {
+    entered: Rc<RefCell<Vec<String>>>,
+    saw_return: bool,
+}
+
+impl AnalysisPass for FunctionTracker {
+    fn name(&self) -> &'static str {
+        "function-tracker"
+    }
+    
+    fn enter_function(&mut self, func: &HirFunction, ctx: &mut AnalysisContext) {
+        self.entered.borrow_mut().push(func.name.clone());
+        self.saw_return = false;
+        for param in &func.parameters {
+            assert!(ctx.lookup(&param.name).is_some(), "Parameters should be in scope");
+        }
+    }
+    
+    fn check_statement(&mut self, stmt: &HirStatement, ctx: &mut AnalysisContext) {
+        if matches!(stmt, HirStatement::Return(_, _)) && ctx.current_function().is_some() {
+            self.saw_return = true;
+        }
+    }
+    
+    fn exit_function(&mut self, func: &HirFunction, ctx: &mut AnalysisContext) {
+        if !self.saw_return {
+            ctx.report(Diagnostic::warning(format!("'{}' never returns a value", func.name)));
+        }
+    }
+}
+
+#[test]
+fn test_custom_pass_shares_traversal() {
+    let source = r#"
+        fn first(reads a: Int) -> Int {
+            return a
+        }
+        
+        fn second() {
+            reads y: Int = 1
+        }
+    "#;
+    
+    let entered = Rc::new(RefCell::new(Vec::new()));
+    let mut manager = PassManager::with_default_passes();
+    manager.register(Box::new(FunctionTracker { entered: entered.clone(), saw_return: false }));
+    assert_eq!(manager.pass_names(), vec!["permissions", "calls", "operands", "conversions", "loops", "unreachable", "concurrency", "function-tracker"]);
+    
+    let reporter = manager.run(&lower(source));
+    
+    assert_eq!(*entered.borrow(), vec!["first".to_string(), "second".to_string()]);
+    assert!(!reporter.has_errors());
+    assert_eq!(reporter.warning_count, 1);
+    assert!(reporter.report().contains("'second' never returns a value"));
+}
+
+#[test]
+fn test_unreachable_after_return() {
+    let source = r#"
+        fn early() -> Int {
+            reads x: Int = 1
+            return x
+            reads y: Int = 2
+            return y
+        }
+    "#;
+    
+    let reporter = analyze_program(&lower(source));
+    let report = reporter.report();
+    println!("Unreachable Code Report:\n{}", report);
+    
+    assert!(!reporter.has_errors(), "Unreachable code is only a warning");
+    assert_eq!(reporter.warning_count, 1, "Only the first dead statement is reported");
+    assert!(report.contains("Unreachable code in function 'early'"));
+    assert!(report.contains("control returns from the function here"));
+}
+
+#[test]
+fn test_unreachable_after_infinite_loop_has_location() {
+    let location = |line| TypesLocation {
+        file_id: 0,
+        start: TextPosition { line, column: 5, offset: 0 },
+        end: TextPosition { line, column: 6, offset: 0 },
+    };
+    
+    // while true { } followed by a return, and an if/else where only one arm returns
+    let program = HirBuilder::new()
+        .function(FunctionBuilder::new("spin")
+            .returns(Type::Int)
+            .if_else(boolean(false), |b| b.return_nothing(), |b| b)
+            .while_loop(boolean(true), |b| b)
+            .return_value(HirExpression::Integer(0, location(7))))
+        .build();
+    
+    let reporter = analyze_program(&program);
+    println!("Infinite Loop Report:\n{}", reporter.report());
+    
+    assert_eq!(reporter.warning_count, 1, "The if/else falls through, only the loop is final");
+    let warning = &reporter.diagnostics[0];
+    assert_eq!(warning.primary.as_ref().map(|label| label.span.start_line), Some(7));
+    assert!(warning.notes[0].contains("this loop never exits"));
+}
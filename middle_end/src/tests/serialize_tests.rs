@@ -0,0 +1,67 @@
+//! Tests for HIR JSON serialization
+//!
+//! Set `UPDATE_GOLDEN=1` to rewrite the snapshots in `tests/golden` after an
+//! intended change to the HIR.
+
+use crate::hir::convert_statements_to_hir;
+use crate::hir::serialize::{from_json, load_program, save_program, to_json};
+use crate::hir::types::HirProgram;
+use front_end::parser::Parser;
+use std::path::PathBuf;
+
+const SOURCE: &str = r#"
+    fn add(reads a: Int, reads b: Int) -> Int {
+        return a + b
+    }
+    
+    reads write total: Int = 1
+    reads limit: Int8 = 10
+"#;
+
+fn lower(source: &str) -> HirProgram {
+    let mut parser = Parser::from_source(source);
+    convert_statements_to_hir(parser.parse_statements())
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/tests/golden").join(name)
+}
+
+#[test]
+fn test_hir_json_round_trip() {
+    let program = lower(SOURCE);
+    let json = to_json(&program).expect("HIR should serialize");
+    let loaded = from_json(&json).expect("Serialized HIR should load");
+    
+    assert_eq!(loaded.statements.len(), program.statements.len());
+    assert_eq!(to_json(&loaded).unwrap(), json, "Serialization should be stable");
+    assert!(json.contains("\"Writes\"") || json.contains("\"Write\""), "Permissions should be included");
+}
+
+#[test]
+fn test_hir_json_golden_snapshot() {
+    let json = to_json(&lower(SOURCE)).unwrap();
+    let path = golden_path("simple_program.json");
+    
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &json).unwrap();
+    }
+    
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Missing snapshot {}: {}", path.display(), e));
+    assert_eq!(json, expected, "HIR JSON differs from the snapshot; rerun with UPDATE_GOLDEN=1 if intended");
+}
+
+#[test]
+fn test_hir_json_file_loader() {
+    let program = lower(SOURCE);
+    let path = std::env::temp_dir().join(format!("custod_hir_{}.json", std::process::id()));
+    
+    save_program(&program, &path).unwrap();
+    let loaded = load_program(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(to_json(&loaded).unwrap(), to_json(&program).unwrap());
+    
+    let missing = load_program(&golden_path("does_not_exist.json"));
+    assert!(missing.unwrap_err().contains("Failed to read"));
+}
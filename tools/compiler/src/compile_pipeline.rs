@@ -1,205 +1,120 @@
-//! Complete compilation pipeline
+//! Complete compilation pipeline, up to MIR
 //!
-//! This module integrates the front-end, middle-end, and (eventually) back-end
-//! stages of compilation with proper error handling at each stage.
+//! The front end reports syntax, resolution and type errors as it parses.
+//! The middle end desugars the HIR, then runs the semantic analyses of the
+//! `PassManager` over it: permissions, call signatures, operand types, loop
+//! control, reachability and concurrency. Each stage's diagnostics go to
+//! the reporter, and a stage with errors ends the compilation before the
+//! next one. What `emit` asks for is written as each stage makes it.
 
+use std::process;
+
+use front_end::diagnostic::{Diagnostic, ToDiagnostic};
 use front_end::lexer::Lexer;
 use front_end::parser::Parser;
 use front_end::source_manager::SourceManager;
-use front_end::diagnostics_reporter::DiagnosticReporter;
-use front_end::error::CompileError;
+use middle_end::hir::{analyze_program, convert_statements_to_hir, desugar_program, pretty_print, suggest_permissions};
+use middle_end::mir::{pretty_print_program, MirProgram};
+use middle_end::optimize::Pipeline;
 
-use middle_end::hir::converters::convert_to_hir;
-use middle_end::type_system::{TypeChecker, TypeError};
-use middle_end::error_handler::{ErrorHandler, MiddleEndError};
-use middle_end::hir::permissions::PermissionChecker;
-use middle_end::hir::HirProgram;
+use crate::{Emit, Options, EXIT_ERRORS};
 
-/// The result of a compilation stage
-pub enum CompilationResult {
-    /// Compilation succeeded
-    Success,
-    /// Compilation failed
-    Failure(Vec<String>),
-}
+/// Check the program in the source manager and lower it to MIR, exiting
+/// if it has errors. `None` when there is nothing more to do: the
+/// permissions were suggested instead, or everything to emit was.
+/// Reports of errors, pass statistics and diffs go to stderr, leaving
+/// stdout to what is emitted and the program's output.
+pub(crate) fn lower_to_mir(options: &Options, source_manager: &SourceManager) -> Option<MirProgram> {
+    if options.emits(Emit::Tokens) {
+        for file_id in source_manager.file_ids() {
+            let source = source_manager.file_source(file_id).unwrap_or_default();
+            let tokens = Lexer::new(source.to_string()).scan_tokens();
+            let listing: String = tokens.iter()
+                .map(|token| format!("{}:{} {:?} {:?}\n", token.line, token.column, token.token_type, token.lexeme))
+                .collect();
+            options.write(Emit::Tokens, source_manager.file_name(file_id).unwrap_or_default(), listing);
+        }
+    }
+    if options.done_after(Emit::Tokens) {
+        return None;
+    }
 
-impl CompilationResult {
-    /// Check if compilation was successful
-    pub fn is_success(&self) -> bool {
-        matches!(self, CompilationResult::Success)
+    let mut parser = Parser::new(Vec::new());
+    let ast = parser.parse_files(source_manager);
+    if options.emits(Emit::Ast) {
+        options.write(Emit::Ast, &options.inputs[0], format!("{:#?}\n", ast));
     }
-    
-    /// Get error messages if compilation failed
-    pub fn error_messages(&self) -> Vec<String> {
-        match self {
-            CompilationResult::Success => vec![],
-            CompilationResult::Failure(msgs) => msgs.clone(),
-        }
+
+    // Report inferred permissions for unannotated declarations. Missing
+    // permissions are what makes the front end reject them, so this runs
+    // before errors are checked.
+    if options.suggest {
+        let suggestions = suggest_permissions(&convert_statements_to_hir(ast));
+        println!("Found {} unannotated declarations", suggestions.len());
+        for suggestion in suggestions {
+            println!("  {}", suggestion);
+        }
+        return None;
     }
-}
 
-/// The complete compilation pipeline
-pub struct CompilationPipeline {
-    source_manager: SourceManager,
-    reporter: DiagnosticReporter,
-    error_handler: ErrorHandler,
-    verbose: bool,
-}
+    // Check for front-end errors
+    let front_end_errors = parser.get_errors();
+    if !front_end_errors.is_empty() {
+        let diagnostics: Vec<Diagnostic> = front_end_errors.iter().map(|error| error.to_diagnostic()).collect();
+        options.report(source_manager, &diagnostics);
+        process::exit(EXIT_ERRORS);
+    }
+    if options.done_after(Emit::Ast) {
+        return None;
+    }
 
-impl CompilationPipeline {
-    /// Create a new compilation pipeline
-    pub fn new(verbose: bool) -> Self {
-        let source_manager = SourceManager::new();
-        let reporter = DiagnosticReporter::new(source_manager.clone());
-        let error_handler = ErrorHandler::new();
-        
-        Self {
-            source_manager,
-            reporter,
-            error_handler,
-            verbose,
-        }
+    // The middle end: desugaring, then the semantic analyses
+    let mut hir_program = convert_statements_to_hir(ast);
+    let desugar_diagnostics = desugar_program(&mut hir_program);
+    if desugar_diagnostics.has_errors() {
+        options.report(source_manager, &desugar_diagnostics.diagnostics);
+        process::exit(EXIT_ERRORS);
     }
-    
-    /// Set source code to compile
-    pub fn with_source(&mut self, filename: &str, source: String) -> &mut Self {
-        self.source_manager.add_file(filename.to_string(), source);
-        self
+    if options.emits(Emit::Hir) {
+        options.write(Emit::Hir, &options.inputs[0], pretty_print(&hir_program));
     }
-    
-    /// Run the entire compilation pipeline
-    pub fn compile(&mut self) -> CompilationResult {
-        // Step 1: Front-end (syntax analysis)
-        if self.verbose {
-            println!("Starting front-end compilation phase...");
-        }
-        
-        let front_end_result = self.run_front_end();
-        if !front_end_result.is_success() {
-            return front_end_result; // Early return on front-end errors
-        }
-        
-        let ast_statements = match front_end_result {
-            CompilationResult::Success => {
-                // This is a bit hacky - we're re-parsing to get the AST
-                // In a real implementation we'd return the AST from run_front_end
-                let source = self.source_manager.get_default_source();
-                let mut parser = Parser::from_source(&source);
-                parser.parse_statements()
-            },
-            _ => unreachable!(), // We already checked for success
-        };
-        
-        // Step 2: Middle-end (semantic analysis)
-        if self.verbose {
-            println!("Starting middle-end compilation phase...");
-        }
-        
-        // 2a: Convert AST to HIR
-        let mut hir_program = HirProgram {
-            statements: Vec::new(),
-            type_info: Default::default(),
-        };
-        
-        for stmt in ast_statements {
-            let hir = convert_to_hir(stmt);
-            hir_program.statements.extend(hir.statements);
-        }
-        
-        if self.verbose {
-            println!("Successfully converted AST to HIR");
-        }
-        
-        // 2b: Check permissions
-        if self.verbose {
-            println!("Checking permissions...");
-        }
-        
-        if let Err(msg) = PermissionChecker::check_program(&hir_program) {
-            self.error_handler.add_permission_error(msg);
-        }
-        
-        // 2c: Type checking
-        if self.verbose {
-            println!("Performing type checking...");
-        }
-        
-        let mut type_checker = TypeChecker::new();
-        let type_errors = type_checker.check_program(&hir_program);
-        
-        for error in type_errors {
-            self.error_handler.add_type_error(error);
-        }
-        
-        // Check for middle-end errors
-        if self.error_handler.has_errors() {
-            if self.verbose {
-                println!("Middle-end analysis found errors:");
-            }
-            return CompilationResult::Failure(
-                self.error_handler.format_errors()
-            );
-        }
-        
-        // All compilation stages passed successfully
-        if self.verbose {
-            println!("Compilation completed successfully!");
-        }
-        
-        CompilationResult::Success
+
+    // Permissions, calls, operands, loop control, reachability and
+    // concurrency, in one walk; warnings are reported but do not stop it
+    let analysis = analyze_program(&hir_program);
+    options.report(source_manager, &analysis.diagnostics);
+    if analysis.has_errors() {
+        process::exit(EXIT_ERRORS);
     }
-    
-    /// Run the front-end phase of compilation
-    fn run_front_end(&self) -> CompilationResult {
-        let source = self.source_manager.get_default_source();
-        
-        // Step 1a: Lexical analysis 
-        if self.verbose {
-            println!("Performing lexical analysis...");
-        }
-        
-        let mut lexer = Lexer::new(source.clone());
-        let tokens = lexer.scan_tokens();
-        
-        if self.verbose {
-            println!("Generated {} tokens", tokens.len());
-        }
-        
-        // Step 1b: Parsing
-        if self.verbose {
-            println!("Performing syntax parsing...");
-        }
-        
-        let mut parser = Parser::from_source(&source);
-        let statements = parser.parse_statements();
-        
-        if self.verbose {
-            println!("Parsed {} statements", statements.len());
+    if options.done_after(Emit::Hir) {
+        return None;
+    }
+
+    // Optimize and lower to MIR; an explicit pass list replaces the level's
+    let mut pipeline = options.custom_pipeline.clone().unwrap_or_else(|| Pipeline::for_level(options.opt_level));
+    if options.mir_diff {
+        pipeline = pipeline.with_diffs();
+    }
+    let (mir_program, stats) = pipeline.lower_with_stats(hir_program);
+    if options.pass_stats {
+        eprintln!("MIR pass statistics:");
+        for stat in &stats {
+            eprintln!("  {}", stat);
         }
-        
-        // Check for front-end errors
-        let front_end_errors = parser.get_errors();
-        if !front_end_errors.is_empty() {
-            if self.verbose {
-                println!("Front-end found {} errors:", front_end_errors.len());
-            }
-            
-            let mut error_messages = Vec::new();
-            for error in front_end_errors {
-                match error {
-                    CompileError::Resolution(res_error) => {
-                        let formatted = self.reporter.report_error(&res_error);
-                        error_messages.push(formatted);
-                    },
-                    _ => {
-                        error_messages.push(format!("Error: {:?}", error));
-                    }
-                }
+    }
+
+    // Show what each MIR pass changed, to find the one that broke a program
+    if options.mir_diff {
+        for stat in &stats {
+            match stat.diff.as_deref() {
+                Some("") | None => eprintln!("{}: no changes", stat.name),
+                Some(diff) => eprint!("{}", diff),
             }
-            
-            return CompilationResult::Failure(error_messages);
         }
-        
-        CompilationResult::Success
     }
+    if options.emits(Emit::Mir) {
+        options.write(Emit::Mir, &options.inputs[0], pretty_print_program(&mir_program));
+    }
+    Some(mir_program)
 }
+
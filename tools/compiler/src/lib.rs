@@ -0,0 +1,465 @@
+//! The compiler's command line
+//!
+//! `main` reads the program, checks it and lowers it to MIR, then runs it
+//! in the interpreter or hands it to a `Backend` for code generation. The
+//! `compiler` binary of this crate has no LLVM back end, so it checks, runs
+//! and emits up to MIR and C source; the one in `back_end` adds LLVM.
+
+mod compile_pipeline;
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use c_back_end::to_c;
+use front_end::source_manager::SourceManager;
+use front_end::diagnostic::{dedup, Diagnostic, DiagnosticRenderer, ToDiagnostic};
+use middle_end::mir::interpreter::io::StdIo;
+use middle_end::mir::{to_dot, Interpreter, MirProgram, MirStats, Value};
+use middle_end::optimize::{OptLevel, Pipeline};
+
+use crate::compile_pipeline::lower_to_mir;
+
+const USAGE: &str = "\
+Usage: compiler <command> [options] <file or directory>...
+
+The program is every file given and every .cd file in the directories
+given, however deep, compiled as one. Later files see the declarations
+of earlier ones; a directory's files come in path order. What is
+written is named after the first file or directory.
+
+Commands:
+  check   Check the program, stopping before code generation
+  run     Run the program's main in the interpreter
+  build   Compile the program to an executable
+  emit    Write what the compiler makes of the program
+
+Options for every command:
+  -O0|-O1|-O2|-O3        Optimization level
+  --passes=<pass>,...    Run these MIR passes instead of the level's
+  --pass-stats           Show what each MIR pass did
+  --mir-diff             Show how each MIR pass changed the program
+  --error-format=human|json   Report errors as text or as a line of JSON each
+  --color=auto|always|never   Color errors; auto does when stderr is a terminal
+
+check:  --suggest-permissions   List the permissions undeclared variables need
+run:    --jit                   Compile main with LLVM's JIT instead
+build:  -o <executable>         Where to write the executable
+        -g                      Describe the program for debuggers
+        --target=native|wasm32  Platform to compile for
+emit:   --emit=<kind>           What to write, repeatable; one of tokens, ast,
+                                hir, mir, mir-dot, mir-stats, c, llvm-ir or llvm-bc
+        --out-dir=<directory>   Write each to a file there instead of stdout
+        -g, --target            As for build";
+
+// Exit statuses; `run` exits with the program's own status instead
+pub const EXIT_SUCCESS: i32 = 0;
+
+/// The program has errors
+pub const EXIT_ERRORS: i32 = 1;
+
+/// The command line is wrong
+pub const EXIT_USAGE: i32 = 2;
+
+/// A file could not be read or written, or code could not be generated
+/// or linked
+pub const EXIT_FAILURE: i32 = 3;
+
+/// The interpreter stopped the program
+pub const EXIT_TRAP: i32 = 101;
+
+/// Code generation from MIR, for `build`, `run --jit` and what `emit`
+/// writes after MIR
+pub trait Backend {
+    /// Check that `--target=<target>` names a platform the back end
+    /// compiles for, or say why not
+    fn check_target(&self, target: &str) -> Result<(), String>;
+
+    /// Write, build or run `mir_program` as `options` ask, exiting on
+    /// failure. The program's sources are in `source_manager`.
+    fn generate(&self, options: &Options, source_manager: &SourceManager, mir_program: &MirProgram);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Check,
+    Run,
+    Build,
+    Emit,
+}
+
+impl Command {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "check" => Some(Command::Check),
+            "run" => Some(Command::Run),
+            "build" => Some(Command::Build),
+            "emit" => Some(Command::Emit),
+            _ => None,
+        }
+    }
+
+    /// The flags only this command takes
+    fn flags(self) -> &'static [&'static str] {
+        match self {
+            Command::Check => &["--suggest-permissions"],
+            Command::Run => &["--jit"],
+            Command::Build => &["-o", "-g", "--target"],
+            Command::Emit => &["--emit", "--out-dir", "-g", "--target"],
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Command::Check => "check",
+            Command::Run => "run",
+            Command::Build => "build",
+            Command::Emit => "emit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What `emit` writes, in the order the compiler makes them
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    Hir,
+    Mir,
+    MirDot,
+    MirStats,
+    C,
+    LlvmIr,
+    LlvmBc,
+}
+
+impl Emit {
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "tokens" => Some(Emit::Tokens),
+            "ast" => Some(Emit::Ast),
+            "hir" => Some(Emit::Hir),
+            "mir" => Some(Emit::Mir),
+            "mir-dot" => Some(Emit::MirDot),
+            "mir-stats" => Some(Emit::MirStats),
+            "c" => Some(Emit::C),
+            "llvm-ir" => Some(Emit::LlvmIr),
+            "llvm-bc" => Some(Emit::LlvmBc),
+            _ => None,
+        }
+    }
+
+    /// The extension of the file it is written to in the output directory
+    fn extension(self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::Hir => "hir",
+            Emit::Mir => "mir",
+            Emit::MirDot => "dot",
+            Emit::MirStats => "stats",
+            Emit::C => "c",
+            Emit::LlvmIr => "ll",
+            Emit::LlvmBc => "bc",
+        }
+    }
+}
+
+/// How errors are reported on stderr
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// Rendered with the source lines they point at
+    Human,
+    /// One JSON object per diagnostic, for editors and CI
+    Json,
+}
+
+/// What the command line asks for
+pub struct Options {
+    pub command: Command,
+    /// The files and directories given
+    pub inputs: Vec<String>,
+    pub opt_level: OptLevel,
+    /// The level of -O as given, 0 to 3, for the back end's optimizations
+    pub backend_opt_level: u8,
+    pub custom_pipeline: Option<Pipeline>,
+    pub pass_stats: bool,
+    pub mir_diff: bool,
+    pub suggest: bool,
+    pub jit: bool,
+    pub output: Option<PathBuf>,
+    pub debug_info: bool,
+    /// The platform --target names, the native one without it
+    pub target: Option<String>,
+    pub emit: Vec<Emit>,
+    pub out_dir: Option<PathBuf>,
+    pub error_format: ErrorFormat,
+    pub color: bool,
+}
+
+impl Options {
+    pub fn emits(&self, kind: Emit) -> bool {
+        self.emit.contains(&kind)
+    }
+
+    /// Whether the command needs code generated from MIR
+    fn generates_code(&self) -> bool {
+        match self.command {
+            Command::Check => false,
+            Command::Run => self.jit,
+            Command::Build => true,
+            Command::Emit => self.emit.iter().any(|&kind| kind > Emit::C),
+        }
+    }
+
+    /// Whether `emit` has written everything asked for once `stage` is,
+    /// so the compiler can stop there
+    pub fn done_after(&self, stage: Emit) -> bool {
+        self.command == Command::Emit && self.emit.iter().all(|&kind| kind <= stage)
+    }
+
+    /// Write `text`, the `kind` made of the source file `source`, to
+    /// stdout, or to its file in the output directory
+    pub fn write(&self, kind: Emit, source: &str, text: impl AsRef<[u8]>) {
+        let Some(path) = self.out_path(kind, source) else {
+            std::io::stdout().write_all(text.as_ref()).unwrap_or_else(|e| self.fail(e));
+            return;
+        };
+        fs::write(&path, text).unwrap_or_else(|e| self.fail(format!("cannot write {}: {}", path.display(), e)));
+    }
+
+    /// The file in the output directory for the `kind` made of `source`,
+    /// creating the directory if need be, or `None` without one
+    pub fn out_path(&self, kind: Emit, source: &str) -> Option<PathBuf> {
+        let out_dir = self.out_dir.as_ref()?;
+        fs::create_dir_all(out_dir).unwrap_or_else(|e| self.fail(format!("cannot create {}: {}", out_dir.display(), e)));
+        let stem = Path::new(source).file_stem().unwrap_or_default();
+        Some(out_dir.join(stem).with_extension(kind.extension()))
+    }
+
+    /// Report `diagnostics` on stderr, quoting `sources` in the human
+    /// format
+    pub fn report(&self, sources: &SourceManager, diagnostics: &[Diagnostic]) {
+        let renderer = DiagnosticRenderer::with_source_manager(sources).with_color(self.color);
+        // A problem more than one check finds is reported once
+        let mut diagnostics = diagnostics.to_vec();
+        dedup(&mut diagnostics);
+        for diagnostic in &diagnostics {
+            match self.error_format {
+                ErrorFormat::Human => eprintln!("{}\n", renderer.render(diagnostic)),
+                ErrorFormat::Json => eprintln!("{}", renderer.render_json(diagnostic)),
+            }
+        }
+    }
+
+    /// Report `error` and exit, for what fails outside the program itself
+    pub fn fail(&self, error: impl fmt::Display) -> ! {
+        self.report(&SourceManager::new(), &[Diagnostic::error(error.to_string())]);
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+/// Do what the command line asks, generating code with `backend`
+pub fn main(backend: Option<&dyn Backend>) {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if matches!(args.first().map(String::as_str), Some("help" | "--help" | "-h")) {
+        println!("{}", USAGE);
+        process::exit(EXIT_SUCCESS);
+    }
+    let options = parse_args(&args, backend).unwrap_or_else(|message| {
+        eprintln!("{}\n\n{}", message, USAGE);
+        process::exit(EXIT_USAGE);
+    });
+
+    // Read every source file into the source manager for error reporting
+    let mut source_manager = SourceManager::new();
+    let filenames = source_files(&options.inputs).unwrap_or_else(|e| options.fail(e));
+    for filename in &filenames {
+        let source = fs::read_to_string(filename)
+            .unwrap_or_else(|e| options.fail(format!("cannot read {}: {}", filename, e)));
+        source_manager.add_file(filename.clone(), source);
+    }
+
+    let Some(mir_program) = lower_to_mir(&options, &source_manager) else {
+        return;
+    };
+    match options.command {
+        Command::Check => {},
+        Command::Run if !options.jit => run(&options, &source_manager, &mir_program),
+        Command::Run | Command::Build | Command::Emit => {
+            let filename = &options.inputs[0];
+            if options.emits(Emit::MirDot) {
+                options.write(Emit::MirDot, filename, to_dot(&mir_program));
+            }
+            if options.emits(Emit::MirStats) {
+                options.write(Emit::MirStats, filename, MirStats::compute(&mir_program).to_string());
+            }
+            // C source for compiling without LLVM
+            if options.emits(Emit::C) {
+                options.write(Emit::C, filename, to_c(&mir_program).unwrap_or_else(|e| options.fail(e)));
+            }
+            if options.done_after(Emit::C) {
+                return;
+            }
+            // Parsing the arguments made sure there is one
+            if let Some(backend) = backend {
+                backend.generate(&options, &source_manager, &mir_program);
+            }
+        },
+    }
+}
+
+/// The options on the command line `args`, or why they are wrong.
+/// What needs code generation is wrong without a `backend`.
+fn parse_args(args: &[String], backend: Option<&dyn Backend>) -> Result<Options, String> {
+    let Some(first) = args.first() else {
+        return Err("No command given".to_string());
+    };
+    let command = Command::parse(first).ok_or_else(|| format!("Unknown command: {}", first))?;
+    let mut options = Options {
+        command,
+        inputs: Vec::new(),
+        opt_level: OptLevel::default(),
+        backend_opt_level: 0,
+        custom_pipeline: None,
+        pass_stats: false,
+        mir_diff: false,
+        suggest: false,
+        jit: false,
+        output: None,
+        debug_info: false,
+        target: None,
+        emit: Vec::new(),
+        out_dir: None,
+        error_format: ErrorFormat::Human,
+        color: std::io::stderr().is_terminal(),
+    };
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let flag = arg.split('=').next().unwrap_or(arg);
+        let common = flag.starts_with("-O") || ["--passes", "--pass-stats", "--mir-diff", "--error-format", "--color"].contains(&flag);
+        if flag.starts_with('-') && !common && !command.flags().contains(&flag) {
+            return Err(format!("{} is not an option of {}", flag, command));
+        }
+        if arg == "-o" {
+            let path = args.next().ok_or("-o needs the path of the executable to write")?;
+            options.output = Some(PathBuf::from(path));
+        } else if arg == "--jit" {
+            options.jit = true;
+        } else if arg == "-g" {
+            options.debug_info = true;
+        } else if let Some(target) = arg.strip_prefix("--target=") {
+            if let Some(backend) = backend {
+                backend.check_target(target)?;
+            }
+            options.target = Some(target.to_string());
+        } else if arg == "--suggest-permissions" {
+            options.suggest = true;
+        } else if arg == "--pass-stats" {
+            options.pass_stats = true;
+        } else if arg == "--mir-diff" {
+            options.mir_diff = true;
+        } else if let Some(kind) = arg.strip_prefix("--emit=") {
+            let kind = Emit::parse(kind).ok_or_else(|| {
+                format!("Unknown emit kind: {} (expected tokens, ast, hir, mir, mir-dot, mir-stats, c, llvm-ir or llvm-bc)", kind)
+            })?;
+            options.emit.push(kind);
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            options.error_format = match format {
+                "human" => ErrorFormat::Human,
+                "json" => ErrorFormat::Json,
+                _ => return Err(format!("Unknown error format: {} (expected human or json)", format)),
+            };
+        } else if let Some(when) = arg.strip_prefix("--color=") {
+            options.color = match when {
+                "auto" => std::io::stderr().is_terminal(),
+                "always" => true,
+                "never" => false,
+                _ => return Err(format!("Unknown color choice: {} (expected auto, always or never)", when)),
+            };
+        } else if let Some(directory) = arg.strip_prefix("--out-dir=") {
+            options.out_dir = Some(PathBuf::from(directory));
+        } else if let Some(passes) = arg.strip_prefix("--passes=") {
+            options.custom_pipeline = Some(passes.parse::<Pipeline>().map_err(|e| e.to_string())?);
+        } else if let Some(level) = arg.strip_prefix("-O") {
+            // LLVM has a level 3 the MIR pipeline does not; it runs the MIR
+            // passes of level 2
+            options.backend_opt_level = level.parse().ok().filter(|&level| level <= 3)
+                .ok_or_else(|| format!("unknown optimization level '{}', expected 0, 1, 2 or 3", level))?;
+            options.opt_level = level.replace('3', "2").parse::<OptLevel>().map_err(|e| e.to_string())?;
+        } else if arg.starts_with('-') {
+            return Err(format!("Unknown option: {}", arg));
+        } else {
+            options.inputs.push(arg.clone());
+        }
+    }
+    if options.inputs.is_empty() {
+        return Err(format!("{} needs the files of the program", command));
+    }
+    if command == Command::Emit && options.emit.is_empty() {
+        return Err("emit needs --emit=<kind> to say what to write".to_string());
+    }
+    if options.emits(Emit::LlvmBc) && options.out_dir.is_none() {
+        return Err("--emit=llvm-bc writes binary bitcode, so it needs --out-dir".to_string());
+    }
+    if backend.is_none() && options.generates_code() {
+        return Err(format!("{} needs a back end, and this compiler was built without one; use the compiler in back_end", command));
+    }
+    Ok(options)
+}
+
+/// The source files `inputs` name: files as they are, and directories as
+/// the .cd files in them and their subdirectories, in path order
+fn source_files(inputs: &[String]) -> Result<Vec<String>, String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if !path.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+        let mut found = Vec::new();
+        let mut directories = vec![path.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            let entries = fs::read_dir(&directory)
+                .map_err(|e| format!("cannot read {}: {}", directory.display(), e))?;
+            for entry in entries {
+                let entry = entry.map_err(|e| format!("cannot read {}: {}", directory.display(), e))?;
+                let path = entry.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else if path.extension().is_some_and(|extension| extension == "cd") {
+                    found.push(path.to_string_lossy().into_owned());
+                }
+            }
+        }
+        if found.is_empty() {
+            return Err(format!("no .cd files in {}", input));
+        }
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
+
+/// Run the program in the interpreter, its top-level statements and then
+/// its main, and exit with main's result
+fn run(options: &Options, source_manager: &SourceManager, mir_program: &MirProgram) -> ! {
+    let mut io = StdIo;
+    let result = Interpreter::new(mir_program).with_io(&mut io).run();
+    match result {
+        Ok(Some(Value::Integer(status))) => process::exit(status as i32),
+        Ok(_) => process::exit(EXIT_SUCCESS),
+        Err(e) => {
+            options.report(source_manager, &[e.to_diagnostic()]);
+            process::exit(EXIT_TRAP);
+        }
+    }
+}
+
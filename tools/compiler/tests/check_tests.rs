@@ -0,0 +1,93 @@
+//! `compiler check` on programs the middle end rejects
+
+mod common;
+
+use std::process::Output;
+
+/// Run `compiler check` with `flags` on `source`
+fn check(name: &str, source: &str, flags: &[&str]) -> Output {
+    common::compile("check", name, source, flags)
+}
+
+#[test]
+fn test_check_rejects_use_after_consume() {
+    let output = check("consume", r#"
+        reads write original: Int = 5
+        reads moved: Int = consume original
+        print original
+    "#, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("error[P0008]: Use of 'original' after consume"), "{}", stderr);
+}
+
+#[test]
+fn test_check_rejects_data_races() {
+    let output = check("race", r#"
+        reads writes total: Int = 0
+        on deposit(reads amount: Int) {
+            total = total + amount
+        }
+        on report() {
+            print total
+        }
+    "#, &["--error-format=json"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.lines().all(|line| line.starts_with('{')), "{}", stderr);
+    assert!(stderr.contains(r#""code":"P0015""#), "{}", stderr);
+}
+
+#[test]
+fn test_check_reports_unreachable_code() {
+    let output = check("unreachable", r#"
+        fn early() -> Int {
+            reads x: Int = 1
+            return x
+            reads y: Int = 2
+            return y
+        }
+    "#, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // A warning, so the program still checks
+    assert_eq!(output.status.code(), Some(0), "{}", stderr);
+    assert!(stderr.contains("warning[S0004]: Unreachable code in function 'early'"), "{}", stderr);
+}
+
+#[test]
+fn test_check_reports_each_problem_once() {
+    let output = check("undefined", "reads a: Int = 1\ny = 3\n", &["--error-format=json"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+    assert!(stderr.contains(r#""code":"E0002","message":"undefined variable `y`""#), "{}", stderr);
+    assert!(stderr.contains(r#""line_start":2,"column_start":1,"#), "{}", stderr);
+}
+
+#[test]
+fn test_check_locates_middle_end_errors_in_their_file() {
+    let output = check("arity", r#"
+        fn add(reads a: Int, reads b: Int) -> Int {
+            return a + b
+        }
+        add(1)
+    "#, &["--error-format=json"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+    assert!(stderr.contains(r#""code":"P0011""#), "{}", stderr);
+    assert!(stderr.contains(r#"arity.cd","line_start":5,"#), "{}", stderr);
+}
+
+#[test]
+fn test_check_rejects_narrowing_without_suggesting_a_cast() {
+    let output = check("narrowing", r#"
+        reads wide: Int32 = 1
+        reads narrow: Int8 = clone wide
+    "#, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("error[S0001]: Type mismatch: expected Int8, found Int32"), "{}", stderr);
+    assert!(stderr.contains("Declare the value as 'Int8' instead."), "{}", stderr);
+    assert!(!stderr.contains("Int8(value)"), "{}", stderr);
+}
@@ -0,0 +1,20 @@
+//! Running the compiler on programs written to files of their own
+
+use std::fs;
+use std::process::{Command, Output};
+
+/// Run `compiler <command>` with `flags` on `source`, written to a file of
+/// its own under `name`
+pub fn compile(command: &str, name: &str, source: &str, flags: &[&str]) -> Output {
+    let directory = std::env::temp_dir().join(format!("custod_{}_tests_{}", command, std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(name).with_extension("cd");
+    fs::write(&path, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg(command)
+        .arg("--color=never")
+        .args(flags)
+        .arg(&path)
+        .output()
+        .unwrap()
+}
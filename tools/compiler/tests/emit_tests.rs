@@ -0,0 +1,83 @@
+//! `compiler emit` of the MIR at different optimization levels, and of C
+
+mod common;
+
+/// The MIR of `function` in the program in `source`, lowered with `flags`
+fn emit_mir(name: &str, source: &str, function: &str, flags: &[&str]) -> String {
+    let output = common::compile("emit", name, source, &[&["--emit=mir"], flags].concat());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    let start = stdout.find(&format!("fn {}(", function)).unwrap_or_else(|| panic!("no {} in\n{}", function, stdout));
+    let end = stdout[start..].find("\n}").map_or(stdout.len(), |end| start + end);
+    stdout[start..end].to_string()
+}
+
+#[test]
+fn test_write_barriers_guard_the_same_stores_at_every_level() {
+    // Only the first store to `a` happens while its alias `v` is live
+    let source = r#"
+        fn share(reads n: Int) -> Int {
+            reads write a: Int = clone n
+            read v: Int = peak a
+            a = n + 1
+            reads w: Int = clone v
+            a = w + n
+            reads write b: Int = clone w
+            b = b + 1
+            return b + a
+        }
+        print share(4)
+    "#;
+    for level in ["-O0", "-O1", "-O2"] {
+        let mir = emit_mir("barriers", source, "share", &[level]);
+        let lines: Vec<&str> = mir.lines().map(str::trim).collect();
+        // For each store to `a`, whether a barrier comes right before it
+        let guarded: Vec<bool> = lines.iter().enumerate()
+            .filter(|(_, line)| line.starts_with("a[") && line.contains("] = "))
+            .map(|(i, _)| i > 0 && lines[i - 1].starts_with("barrier a["))
+            .collect();
+        assert_eq!(guarded, [false, true, false], "at {}:\n{}", level, mir);
+    }
+}
+
+#[test]
+fn test_emitted_c_compiles_to_what_the_interpreter_prints() {
+    let source = r#"
+        fn triangle(reads n: Int) -> Int {
+            reads write total: Int = 0
+            reads write i: Int = 1
+            while i <= n {
+                total = total + i
+                i = i + 1
+            }
+            return total
+        }
+        fn main() -> Int {
+            print triangle(10)
+            print triangle(3) > 5
+            return 0
+        }
+    "#;
+    let emitted = common::compile("emit", "triangle", source, &["--emit=c"]);
+    assert_eq!(emitted.status.code(), Some(0), "{}", String::from_utf8_lossy(&emitted.stderr));
+
+    let directory = std::env::temp_dir().join(format!("custod_emit_c_tests_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let file = directory.join("triangle.c");
+    let executable = directory.join("triangle");
+    std::fs::write(&file, &emitted.stdout).unwrap();
+    let cc = std::env::var("CC").ok().filter(|cc| !cc.is_empty()).unwrap_or_else(|| "cc".to_string());
+    let compiled = std::process::Command::new(cc)
+        .args(["-std=c99", "-fwrapv", "-o"])
+        .arg(&executable)
+        .arg(&file)
+        .arg("-lm")
+        .output()
+        .unwrap();
+    assert!(compiled.status.success(), "{}", String::from_utf8_lossy(&compiled.stderr));
+
+    let native = std::process::Command::new(&executable).output().unwrap();
+    let interpreted = common::compile("run", "triangle", source, &[]);
+    assert_eq!(String::from_utf8_lossy(&native.stdout), String::from_utf8_lossy(&interpreted.stdout));
+    assert_eq!(String::from_utf8_lossy(&native.stdout), "55\ntrue\n");
+}
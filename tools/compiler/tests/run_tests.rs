@@ -0,0 +1,69 @@
+//! `compiler run` on programs the interpreter runs to completion
+
+mod common;
+
+/// Run the program in `source` with `flags` and return what it printed,
+/// failing on any error
+fn run(name: &str, source: &str, flags: &[&str]) -> String {
+    let output = common::compile("run", name, source, flags);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_run_widens_sized_integers() {
+    // Literals take the sized types, and narrower integers widen in
+    // initializers, operands, arguments and returns
+    let source = r#"
+        fn narrow(reads x: Int8) -> Int8 {
+            return x
+        }
+        fn sum() -> Int32 {
+            reads a: Int8 = 1
+            reads b: Int32 = clone a
+            reads c: Int32 = a + b
+            reads d: Int8 = narrow(5) + 1
+            return c + d
+        }
+        reads small: Int16 = 300
+        print sum()
+        print small + sum()
+    "#;
+    assert_eq!(run("widening", source, &[]), "8\n308\n");
+}
+
+#[test]
+fn test_run_wraps_overflow_at_every_level() {
+    // Constant propagation folds `a + 1`, and must wrap as the
+    // interpreter does
+    let source = "read a: Int = 9223372036854775807\nprint a + 1\n";
+    for level in ["-O0", "-O1", "-O2"] {
+        assert_eq!(run("overflow", source, &[level]), "-9223372036854775808\n", "at {}", level);
+    }
+}
+
+#[test]
+fn test_run_function_with_loop_inlined_into_loop() {
+    // Inlining `fact` puts its locals inside the outer loop; SSA must not
+    // merge them at the outer loop's header, where they are not yet set
+    let source = r#"
+        fn fact(reads n: Int) -> Int {
+            reads write result: Int = 1
+            reads write i: Int = 1
+            while i <= n {
+                result = result * i
+                i = i + 1
+            }
+            return result
+        }
+        reads write k: Int = 1
+        while k <= 5 {
+            print fact(k)
+            k = k + 1
+        }
+    "#;
+    for flags in [&["-O0"][..], &["-O1"], &["-O2"], &["--passes=mir-inline,ssa"]] {
+        assert_eq!(run("inline_loop", source, flags), "1\n2\n6\n24\n120\n", "with {:?}", flags);
+    }
+}